@@ -0,0 +1,101 @@
+use atari2600::address_space::Cartridge;
+use atari2600::atari::{Atari, AtariAddressSpace, AUDIO_SAMPLE_RATE};
+use atari2600::colors;
+use atari2600::frame_renderer::FrameRendererBuilder;
+use atari2600::tia::{Tia, VideoOutput, HBLANK_WIDTH, HSYNC_END, HSYNC_START, TOTAL_WIDTH};
+use common::app::{FrameStatus, Machine};
+use common::audio::create_consumer_and_source;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::path::Path;
+use std::time::Duration;
+use ya6502::cpu::PowerOnState;
+use ya6502::memory::RamPattern;
+
+fn read_test_rom(name: &str) -> Vec<u8> {
+    std::fs::read(Path::new(env!("OUT_DIR")).join("test_roms").join(name)).unwrap()
+}
+
+fn tia_tick(c: &mut Criterion) {
+    let mut tia = Tia::new();
+    c.bench_function("tia_tick", |b| b.iter(|| black_box(tia.tick())));
+}
+
+fn tia_scanline(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tia_scanline");
+    let mut tia = Tia::new();
+    group.bench_function("tick", |b| {
+        b.iter(|| {
+            for _ in 0..TOTAL_WIDTH {
+                black_box(tia.tick());
+            }
+        })
+    });
+    let mut outputs = Vec::with_capacity(TOTAL_WIDTH as usize);
+    group.bench_function("tick_until_cpu_cycle", |b| {
+        b.iter(|| {
+            outputs.clear();
+            while outputs.len() < TOTAL_WIDTH as usize {
+                tia.tick_until_cpu_cycle(&mut outputs);
+            }
+            black_box(&outputs);
+        })
+    });
+    group.finish();
+}
+
+fn frame_renderer_frame(c: &mut Criterion) {
+    // An NTSC frame: 3 lines of VSYNC, followed by 259 lines, each of them
+    // filled with a different color gradient.
+    let outputs: Vec<VideoOutput> = (0..262)
+        .flat_map(|line| {
+            (0..TOTAL_WIDTH).map(move |x| VideoOutput {
+                vsync: line < 3,
+                hsync: (HSYNC_START..HSYNC_END).contains(&x),
+                pixel: (line >= 3 && x >= HBLANK_WIDTH).then(|| (line + x) as u8 & 0xFE),
+            })
+        })
+        .collect();
+    let mut frame_renderer = FrameRendererBuilder::new()
+        .with_palette(colors::ntsc_palette())
+        .build();
+    c.bench_function("frame_renderer_frame", |b| {
+        b.iter(|| {
+            for output in &outputs {
+                frame_renderer.consume(*output);
+            }
+            black_box(frame_renderer.frame_image());
+        })
+    });
+}
+
+fn atari_frame(c: &mut Criterion) {
+    let rom = read_test_rom("horizontal_stripes.bin");
+    let address_space = Box::new(AtariAddressSpace::new(
+        Cartridge::with_detected_bank_switching(&rom).unwrap(),
+        RamPattern::default(),
+        PowerOnState::Seeded(0),
+    ));
+    let (consumer, _) = create_consumer_and_source(AUDIO_SAMPLE_RATE, Duration::from_millis(50));
+    let mut atari = Atari::new(
+        address_space,
+        FrameRendererBuilder::new()
+            .with_palette(colors::ntsc_palette())
+            .build(),
+        consumer,
+        PowerOnState::Seeded(0),
+    );
+    atari.reset();
+
+    c.bench_function("atari_frame", |b| {
+        b.iter(|| while let FrameStatus::Pending = atari.tick().unwrap() {})
+    });
+}
+
+criterion_group!(
+    benches,
+    tia_tick,
+    tia_scanline,
+    frame_renderer_frame,
+    atari_frame
+);
+criterion_main!(benches);
@@ -1,12 +1,22 @@
+use serde::Deserialize;
+use std::error;
 use std::fmt;
+use ya6502::mapper::Mapper;
 use ya6502::memory::dump_zero_page;
+use ya6502::memory::memory_map_from;
 use ya6502::memory::Inspect;
+use ya6502::memory::MemoryKind;
+use ya6502::memory::MemoryRegion;
 use ya6502::memory::Read;
 use ya6502::memory::Write;
 use ya6502::memory::{Memory, ReadError, ReadResult, WriteError, WriteResult};
 
 /// Dispatches read/write calls to various devices with memory-mapped interfaces:
-/// TIA, RAM, RIOT (not yet implemented), and ROM.
+/// TIA, RAM, RIOT, and ROM. The ROM device (typically, a [`Cartridge`]) is a
+/// [`Mapper`] that is additionally notified about every bus access. Atari 2600
+/// cartridges are connected to all 13 address lines and the data bus, so they
+/// see every read and write, even those that don't target the ROM area; this
+/// is what bank switching schemes rely on.
 #[derive(Debug)]
 pub struct AddressSpace<T, Ram, Riot, Rom>
 where
@@ -44,6 +54,23 @@ where
             None => Err(ReadError { address }),
         }
     }
+
+    fn memory_map(&self) -> Vec<MemoryRegion> {
+        // The ROM device may have some RAM of its own (e.g. the SuperChip).
+        let rom_map = self.rom.memory_map();
+        let rom_kind = |address| {
+            rom_map
+                .iter()
+                .find(|region| (region.start..=region.end).contains(&address))
+                .map_or(MemoryKind::Rom, |region| region.kind)
+        };
+        memory_map_from(|address| match map_address(address) {
+            Some(MemoryArea::Tia | MemoryArea::Riot) => MemoryKind::Io,
+            Some(MemoryArea::Ram) => MemoryKind::Ram,
+            Some(MemoryArea::Rom) => rom_kind(address),
+            None => MemoryKind::Unmapped,
+        })
+    }
 }
 
 impl<T, Ram, Riot, Rom> Read for AddressSpace<T, Ram, Riot, Rom>
@@ -51,16 +78,18 @@ where
     T: Memory,
     Ram: Memory,
     Riot: Memory,
-    Rom: Read,
+    Rom: Mapper,
 {
     fn read(&mut self, address: u16) -> ReadResult {
-        match map_address(address) {
+        let value = match map_address(address) {
             Some(MemoryArea::Tia) => self.tia.read(address),
             Some(MemoryArea::Ram) => self.ram.read(address),
             Some(MemoryArea::Rom) => self.rom.read(address),
             Some(MemoryArea::Riot) => self.riot.read(address),
             None => Err(ReadError { address }),
-        }
+        }?;
+        self.rom.observe_read(address, value);
+        return Ok(value);
     }
 }
 
@@ -69,7 +98,7 @@ where
     T: Memory,
     Ram: Memory,
     Riot: Memory,
-    Rom: Read,
+    Rom: Mapper,
 {
     fn write(&mut self, address: u16, value: u8) -> WriteResult {
         match map_address(address) {
@@ -78,7 +107,9 @@ where
             Some(MemoryArea::Rom) => Ok(()),
             Some(MemoryArea::Riot) => self.riot.write(address, value),
             None => Err(WriteError { address, value }),
-        }
+        }?;
+        self.rom.observe_write(address, value);
+        return Ok(());
     }
 }
 
@@ -87,7 +118,7 @@ where
     T: Memory,
     Ram: Memory,
     Riot: Memory,
-    Rom: Read,
+    Rom: Mapper,
 {
 }
 
@@ -105,6 +136,311 @@ fn map_address(address: u16) -> Option<MemoryArea> {
     }
 }
 
+/// Returns the canonical address of the memory location visible at a given
+/// address, taking into account the mirrors of the 13-bit address bus and of
+/// the RAM. Used to identify the locations pinned by cheats.
+pub fn mirrored_address(address: u16) -> u16 {
+    return match map_address(address) {
+        Some(MemoryArea::Rom) => 0xF000 | (address & 0x0FFF),
+        Some(MemoryArea::Ram) => 0x0080 | (address & 0x007F),
+        _ => address & 0x1FFF,
+    };
+}
+
+/// Returns the address at which the name of a TIA or RIOT register visible at
+/// a given address is stored: $00-$3F for the TIA, and $280-$29F for the RIOT.
+/// Used to annotate the registers in the disassembly.
+pub fn register_address(address: u16) -> u16 {
+    return match map_address(address) {
+        Some(MemoryArea::Tia) => address & 0x003F,
+        Some(MemoryArea::Riot) => 0x0280 | (address & 0x001F),
+        _ => address,
+    };
+}
+
+/// Extensions of files that contain cartridge ROM images. Used to find the
+/// image in ZIP archives.
+pub const ROM_FILE_EXTENSIONS: &[&str] = &["a26", "bin", "rom"];
+
+/// Bank switching scheme used by a cartridge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ArgEnum)]
+pub enum BankSwitching {
+    /// A flat 2K or 4K ROM image without any bank switching.
+    Flat,
+    /// Atari 8K: two 4K banks, selected by accessing $1FF8-$1FF9.
+    F8,
+    /// Atari 16K: four 4K banks, selected by accessing $1FF6-$1FF9.
+    F6,
+    /// Atari 32K: eight 4K banks, selected by accessing $1FF4-$1FFB.
+    F4,
+    /// Activision 8K: two 4K banks, selected by bit 5 of the byte that follows
+    /// an access to $01FE on the data bus (in practice, by the high byte of
+    /// the address pushed or pulled by JSR and RTS).
+    FE,
+    /// Tigervision: 2K banks; the lower half of the ROM area is switched by
+    /// writing to $0000-$003F, and the upper half is fixed to the last bank.
+    #[clap(name = "3f")]
+    Tigervision,
+}
+
+impl BankSwitching {
+    /// Guesses the bank switching scheme used by a given ROM image, judging by
+    /// its size and some telltale instruction sequences.
+    pub fn detect(bytes: &[u8]) -> Self {
+        let tigervision_size = Self::Tigervision.is_valid_size(bytes.len());
+        if tigervision_size && count_occurrences(bytes, &[0x85, 0x3F]) >= 2 {
+            // STA $3F, the Tigervision bank switching instruction. A 2K image
+            // is too small to switch banks, so it's just mirrored.
+            return Self::Tigervision;
+        }
+        return match bytes.len() {
+            0x2000 if is_probably_fe(bytes) => Self::FE,
+            0x2000 => Self::F8,
+            0x4000 => Self::F6,
+            0x8000 => Self::F4,
+            _ => Self::Flat,
+        };
+    }
+
+    /// Guesses whether a ROM image needs the SuperChip RAM. Since the first
+    /// 256 bytes of each bank are shadowed by the RAM ports, they are usually
+    /// filled with a single value.
+    pub fn detect_super_chip(bytes: &[u8]) -> bool {
+        let bank_switching = Self::detect(bytes);
+        if bank_switching.hotspots().is_none() {
+            return false;
+        }
+        return bytes
+            .chunks(bank_switching.bank_size())
+            .all(|bank| bank[..0x100].iter().all(|byte| *byte == bank[0]));
+    }
+
+    fn bank_size(&self) -> usize {
+        match self {
+            Self::Tigervision => 0x800,
+            _ => 0x1000,
+        }
+    }
+
+    /// Returns the address of the first hotspot and the number of banks for
+    /// the Atari "F" family of bank switching schemes.
+    fn hotspots(&self) -> Option<(u16, usize)> {
+        match self {
+            Self::F8 => Some((0x1FF8, 2)),
+            Self::F6 => Some((0x1FF6, 4)),
+            Self::F4 => Some((0x1FF4, 8)),
+            _ => None,
+        }
+    }
+
+    /// Returns the bank that is selected after powering on. Real hardware
+    /// powers up in a random bank; we start in the last one for the "F"
+    /// family, since that's where games usually expect to be after reset.
+    fn initial_bank(&self) -> usize {
+        match self.hotspots() {
+            Some((_, num_banks)) => num_banks - 1,
+            None => 0,
+        }
+    }
+
+    fn is_valid_size(&self, size: usize) -> bool {
+        match self {
+            Self::Flat => size == 0x800 || size == 0x1000,
+            Self::F8 | Self::FE => size == 0x2000,
+            Self::F6 => size == 0x4000,
+            Self::F4 => size == 0x8000,
+            Self::Tigervision => (0x1000..=0x80000).contains(&size) && size % 0x800 == 0,
+        }
+    }
+}
+
+/// Code fragments that are characteristic for the Activision games that use
+/// the FE bank switching scheme.
+const FE_SIGNATURES: [[u8; 5]; 4] = [
+    [0x20, 0x00, 0xD0, 0xC6, 0xC5], // JSR $D000; DEC $C5
+    [0x20, 0xC3, 0xF8, 0xA5, 0x82], // JSR $F8C3; LDA $82
+    [0xD0, 0xFB, 0x20, 0x73, 0xFE], // BNE -5; JSR $FE73
+    [0x20, 0x00, 0xF0, 0x84, 0xD6], // JSR $F000; STY $D6
+];
+
+fn is_probably_fe(bytes: &[u8]) -> bool {
+    FE_SIGNATURES
+        .iter()
+        .any(|signature| count_occurrences(bytes, signature) > 0)
+}
+
+fn count_occurrences(haystack: &[u8], needle: &[u8]) -> usize {
+    haystack
+        .windows(needle.len())
+        .filter(|w| w == &needle)
+        .count()
+}
+
+/// An Atari 2600 cartridge: a ROM image, optionally split into banks that are
+/// switched using one of the supported [`BankSwitching`] schemes.
+#[derive(Debug)]
+pub struct Cartridge {
+    bytes: Vec<u8>,
+    bank_switching: BankSwitching,
+    /// Index of the currently selected bank. For the Tigervision scheme, it
+    /// only applies to the lower half of the ROM area.
+    bank: usize,
+    /// Set by the FE scheme if the previous bus access was to $01FE.
+    fe_armed: bool,
+    /// The 128 bytes of SuperChip RAM, if the cartridge has one. It's written
+    /// at $1000-$107F and read at $1080-$10FF.
+    super_chip_ram: Option<Vec<u8>>,
+}
+
+impl Cartridge {
+    pub fn new(bytes: &[u8], bank_switching: BankSwitching) -> Result<Self, CartridgeSizeError> {
+        if !bank_switching.is_valid_size(bytes.len()) {
+            return Err(CartridgeSizeError {
+                size: bytes.len(),
+                bank_switching,
+            });
+        }
+        return Ok(Self {
+            bytes: bytes.to_vec(),
+            bank_switching,
+            bank: bank_switching.initial_bank(),
+            fe_armed: false,
+            super_chip_ram: None,
+        });
+    }
+
+    /// Creates a cartridge, detecting its bank switching scheme and the
+    /// presence of SuperChip RAM automatically.
+    pub fn with_detected_bank_switching(bytes: &[u8]) -> Result<Self, CartridgeSizeError> {
+        Self::new(bytes, BankSwitching::detect(bytes))
+            .map(|cartridge| cartridge.with_super_chip(BankSwitching::detect_super_chip(bytes)))
+    }
+
+    /// Adds or removes the 128 bytes of SuperChip (also known as SARA) RAM.
+    /// Only the F8, F6, and F4 schemes support it; for other schemes, this
+    /// setting is ignored.
+    pub fn with_super_chip(mut self, super_chip: bool) -> Self {
+        self.super_chip_ram = if super_chip && self.bank_switching.hotspots().is_some() {
+            Some(vec![0; 0x80])
+        } else {
+            None
+        };
+        return self;
+    }
+
+    fn num_banks(&self) -> usize {
+        self.bytes.len() / self.bank_switching.bank_size()
+    }
+
+    /// Switches banks if a given address is a hotspot of an "F" scheme. For
+    /// these schemes, both reads and writes trigger the switch.
+    fn touch_hotspot(&mut self, address: u16) {
+        if let Some((first_hotspot, num_banks)) = self.bank_switching.hotspots() {
+            let address = address & 0x1FFF;
+            if address >= first_hotspot && ((address - first_hotspot) as usize) < num_banks {
+                self.bank = (address - first_hotspot) as usize;
+            }
+        }
+    }
+
+    /// Implements the FE scheme: the data that appears on the bus right after
+    /// accessing $01FE selects the bank.
+    fn observe_fe(&mut self, address: u16, value: u8) {
+        if self.fe_armed {
+            self.bank = if value & (1 << 5) != 0 { 0 } else { 1 };
+        }
+        self.fe_armed = address & 0x1FFF == 0x01FE;
+    }
+}
+
+impl Inspect for Cartridge {
+    fn inspect(&self, address: u16) -> ReadResult {
+        if let Some(ram) = &self.super_chip_ram {
+            if (0x1080..=0x10FF).contains(&(address & 0x1FFF)) {
+                return Ok(ram[address as usize & 0x7F]);
+            }
+        }
+        let offset = match self.bank_switching {
+            BankSwitching::Flat => address as usize & (self.bytes.len() - 1),
+            BankSwitching::Tigervision => {
+                let bank = if address & 0x0800 == 0 {
+                    self.bank
+                } else {
+                    self.num_banks() - 1
+                };
+                bank * 0x800 + (address as usize & 0x07FF)
+            }
+            _ => self.bank * 0x1000 + (address as usize & 0x0FFF),
+        };
+        Ok(self.bytes[offset])
+    }
+
+    fn memory_map(&self) -> Vec<MemoryRegion> {
+        if self.super_chip_ram.is_none() {
+            return vec![];
+        }
+        memory_map_from(|address| match address & 0x1FFF {
+            0x1000..=0x10FF => MemoryKind::Ram,
+            _ => MemoryKind::Rom,
+        })
+    }
+}
+
+impl Read for Cartridge {
+    fn read(&mut self, address: u16) -> ReadResult {
+        self.inspect(address)
+    }
+}
+
+impl Mapper for Cartridge {
+    fn observe_read(&mut self, address: u16, value: u8) {
+        match self.bank_switching {
+            BankSwitching::FE => self.observe_fe(address, value),
+            _ => self.touch_hotspot(address),
+        }
+    }
+
+    fn observe_write(&mut self, address: u16, value: u8) {
+        match self.bank_switching {
+            BankSwitching::FE => self.observe_fe(address, value),
+            BankSwitching::Tigervision if address & 0x1FFF <= 0x003F => {
+                self.bank = value as usize % self.num_banks();
+            }
+            _ => {
+                if let Some(ram) = &mut self.super_chip_ram {
+                    if (0x1000..=0x107F).contains(&(address & 0x1FFF)) {
+                        ram[address as usize & 0x7F] = value;
+                    }
+                }
+                self.touch_hotspot(address);
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.bank = self.bank_switching.initial_bank();
+        self.fe_armed = false;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CartridgeSizeError {
+    size: usize,
+    bank_switching: BankSwitching,
+}
+
+impl error::Error for CartridgeSizeError {}
+
+impl fmt::Display for CartridgeSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Illegal ROM size for {:?} bank switching: {} bytes",
+            self.bank_switching, self.size
+        )
+    }
+}
+
 impl<T, Ram, Riot, Rom> fmt::Display for AddressSpace<T, Ram, Riot, Rom>
 where
     T: Memory + Inspect,
@@ -123,6 +459,25 @@ mod tests {
     use std::error;
     use ya6502::memory::Ram;
 
+    /// Creates a ROM image with banks of a given size, each filled with its
+    /// own index.
+    fn banked_rom(bank_size: usize, num_banks: usize) -> Vec<u8> {
+        (0..num_banks)
+            .flat_map(|bank| std::iter::repeat(bank as u8).take(bank_size))
+            .collect()
+    }
+
+    fn address_space_with_cartridge(
+        cartridge: Cartridge,
+    ) -> AddressSpace<Ram, Ram, Ram, Cartridge> {
+        AddressSpace {
+            tia: Ram::new(16),
+            ram: Ram::new(16),
+            riot: Ram::new(16),
+            rom: cartridge,
+        }
+    }
+
     #[test]
     fn reads_and_writes() -> Result<(), Box<dyn error::Error>> {
         let mut address_space = AddressSpace {
@@ -165,6 +520,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn memory_map() {
+        let cartridge = Cartridge::new(&[0; 0x1000], BankSwitching::Flat).unwrap();
+        let address_space = address_space_with_cartridge(cartridge);
+        let region = |start, end, kind| MemoryRegion { start, end, kind };
+        let memory_map = address_space.memory_map();
+        assert_eq!(
+            memory_map[..5],
+            [
+                region(0x0000, 0x007F, MemoryKind::Io),
+                region(0x0080, 0x00FF, MemoryKind::Ram),
+                region(0x0100, 0x017F, MemoryKind::Io),
+                region(0x0180, 0x01FF, MemoryKind::Ram),
+                region(0x0200, 0x047F, MemoryKind::Io),
+            ]
+        );
+        assert_eq!(
+            memory_map.last(),
+            Some(&region(0xF000, 0xFFFF, MemoryKind::Rom))
+        );
+    }
+
     #[test]
     fn address_mapping() {
         let mut address_space = AddressSpace {
@@ -187,4 +564,257 @@ mod tests {
         assert_eq!(address_space.ram.bytes[0xC59A], 12);
         assert_eq!(address_space.riot.bytes[0x86AB], 13);
     }
+
+    #[test]
+    fn mirrored_addresses() {
+        assert_eq!(mirrored_address(0x0080), 0x0080);
+        assert_eq!(mirrored_address(0x01FF), 0x00FF);
+        assert_eq!(mirrored_address(0x6CD3), 0x00D3);
+        assert_eq!(mirrored_address(0x1234), 0xF234);
+        assert_eq!(mirrored_address(0xFFFC), 0xFFFC);
+        assert_eq!(mirrored_address(0x8F45), 0x0F45);
+        assert_eq!(mirrored_address(0x2285), 0x0285);
+    }
+
+    #[test]
+    fn register_address_mirroring() {
+        assert_eq!(register_address(0x0009), 0x0009);
+        assert_eq!(register_address(0x0049), 0x0009);
+        assert_eq!(register_address(0x413C), 0x003C);
+        assert_eq!(register_address(0x0380), 0x0280);
+        assert_eq!(register_address(0x02BE), 0x029E);
+        assert_eq!(register_address(0x0080), 0x0080);
+        assert_eq!(register_address(0xF009), 0xF009);
+    }
+
+    #[test]
+    fn flat_cartridge() {
+        let mut rom = vec![0; 0x800];
+        rom[0x000] = 1;
+        rom[0x7FF] = 2;
+        let mut cartridge = Cartridge::new(&rom, BankSwitching::Flat).unwrap();
+        assert_eq!(cartridge.read(0xF000).unwrap(), 1);
+        assert_eq!(cartridge.read(0xF7FF).unwrap(), 2);
+        assert_eq!(cartridge.read(0xF800).unwrap(), 1);
+        assert_eq!(cartridge.read(0xFFFF).unwrap(), 2);
+    }
+
+    #[test]
+    fn f8_bank_switching() {
+        let cartridge = Cartridge::new(&banked_rom(0x1000, 2), BankSwitching::F8).unwrap();
+        let mut address_space = address_space_with_cartridge(cartridge);
+        assert_eq!(address_space.read(0xF000).unwrap(), 1);
+
+        address_space.read(0xFFF8).unwrap();
+        assert_eq!(address_space.read(0xF000).unwrap(), 0);
+        assert_eq!(address_space.read(0xFFFF).unwrap(), 0);
+
+        // Writes also switch banks, and so do accesses to mirrored hotspots.
+        address_space.write(0x3FF9, 0).unwrap();
+        assert_eq!(address_space.read(0xF000).unwrap(), 1);
+        address_space.write(0xDFF8, 0).unwrap();
+        assert_eq!(address_space.read(0xF000).unwrap(), 0);
+
+        // Addresses outside the hotspot range shouldn't do anything.
+        address_space.read(0xFFF7).unwrap();
+        address_space.read(0xFFFA).unwrap();
+        assert_eq!(address_space.read(0xF000).unwrap(), 0);
+    }
+
+    #[test]
+    fn f6_bank_switching() {
+        let cartridge = Cartridge::new(&banked_rom(0x1000, 4), BankSwitching::F6).unwrap();
+        let mut address_space = address_space_with_cartridge(cartridge);
+        assert_eq!(address_space.read(0xF000).unwrap(), 3);
+        for bank in 0..4 {
+            address_space.read(0xFFF6 + bank).unwrap();
+            assert_eq!(address_space.read(0xF123).unwrap(), bank as u8);
+        }
+        address_space.read(0xFFFA).unwrap();
+        assert_eq!(address_space.read(0xF123).unwrap(), 3);
+    }
+
+    #[test]
+    fn f4_bank_switching() {
+        let cartridge = Cartridge::new(&banked_rom(0x1000, 8), BankSwitching::F4).unwrap();
+        let mut address_space = address_space_with_cartridge(cartridge);
+        assert_eq!(address_space.read(0xF000).unwrap(), 7);
+        for bank in 0..8 {
+            address_space.write(0x1FF4 + bank, 0).unwrap();
+            assert_eq!(address_space.read(0xF123).unwrap(), bank as u8);
+        }
+    }
+
+    #[test]
+    fn fe_bank_switching() {
+        let cartridge = Cartridge::new(&banked_rom(0x1000, 2), BankSwitching::FE).unwrap();
+        let mut address_space = address_space_with_cartridge(cartridge);
+        assert_eq!(address_space.read(0xF000).unwrap(), 0);
+
+        // Simulate a JSR $D123: push the return address, then fetch the high
+        // byte of the target address.
+        address_space.write(0x01FF, 0xF0).unwrap();
+        address_space.write(0x01FE, 0x12).unwrap();
+        address_space.rom.observe_read(0xF004, 0xD1);
+        assert_eq!(address_space.read(0xF000).unwrap(), 1);
+
+        // Now an RTS to $F012: pull the return address from the stack.
+        address_space.ram.bytes[0x01FE] = 0x12;
+        address_space.ram.bytes[0x01FF] = 0xF0;
+        address_space.read(0x01FE).unwrap();
+        address_space.read(0x01FF).unwrap();
+        assert_eq!(address_space.read(0xF000).unwrap(), 0);
+    }
+
+    #[test]
+    fn tigervision_bank_switching() {
+        let cartridge = Cartridge::new(&banked_rom(0x800, 4), BankSwitching::Tigervision).unwrap();
+        let mut address_space = address_space_with_cartridge(cartridge);
+        assert_eq!(address_space.read(0xF000).unwrap(), 0);
+        assert_eq!(address_space.read(0xF800).unwrap(), 3);
+
+        address_space.write(0x003F, 2).unwrap();
+        assert_eq!(address_space.read(0xF000).unwrap(), 2);
+        assert_eq!(address_space.read(0xF7FF).unwrap(), 2);
+        assert_eq!(address_space.read(0xF800).unwrap(), 3);
+        assert_eq!(address_space.read(0xFFFF).unwrap(), 3);
+        // The write should also reach the TIA.
+        assert_eq!(address_space.tia.bytes[0x003F], 2);
+
+        // Bank numbers wrap around.
+        address_space.write(0x0000, 5).unwrap();
+        assert_eq!(address_space.read(0xF000).unwrap(), 1);
+
+        // Reads and writes outside $00-$3F don't switch banks.
+        address_space.tia.bytes[0x003F] = 0;
+        address_space.read(0x003F).unwrap();
+        address_space.write(0x0040, 0).unwrap();
+        assert_eq!(address_space.read(0xF000).unwrap(), 1);
+    }
+
+    #[test]
+    fn reset_selects_initial_bank() {
+        let cartridge = Cartridge::new(&banked_rom(0x1000, 4), BankSwitching::F6).unwrap();
+        let mut address_space = address_space_with_cartridge(cartridge);
+        address_space.read(0xFFF7).unwrap();
+        assert_eq!(address_space.read(0xF000).unwrap(), 1);
+        address_space.rom.reset();
+        assert_eq!(address_space.read(0xF000).unwrap(), 3);
+
+        let cartridge = Cartridge::new(&banked_rom(0x1000, 2), BankSwitching::FE).unwrap();
+        let mut address_space = address_space_with_cartridge(cartridge);
+        address_space.write(0x01FE, 0x12).unwrap();
+        address_space.rom.observe_read(0xF004, 0xD1);
+        assert_eq!(address_space.read(0xF000).unwrap(), 1);
+        address_space.write(0x01FE, 0x12).unwrap();
+        address_space.rom.reset();
+        assert_eq!(address_space.read(0xF000).unwrap(), 0);
+        // Reset also forgets that $01FE has just been accessed.
+        address_space.rom.observe_read(0xF004, 0xD1);
+        assert_eq!(address_space.read(0xF000).unwrap(), 0);
+
+        let cartridge = Cartridge::new(&banked_rom(0x800, 4), BankSwitching::Tigervision).unwrap();
+        let mut address_space = address_space_with_cartridge(cartridge);
+        address_space.write(0x003F, 2).unwrap();
+        assert_eq!(address_space.read(0xF000).unwrap(), 2);
+        address_space.rom.reset();
+        assert_eq!(address_space.read(0xF000).unwrap(), 0);
+    }
+
+    #[test]
+    fn super_chip() {
+        let cartridge = Cartridge::new(&banked_rom(0x1000, 2), BankSwitching::F8)
+            .unwrap()
+            .with_super_chip(true);
+        let mut address_space = address_space_with_cartridge(cartridge);
+        address_space.write(0xF000, 12).unwrap();
+        address_space.write(0xF07F, 34).unwrap();
+        assert_eq!(address_space.read(0xF080).unwrap(), 12);
+        assert_eq!(address_space.read(0xF0FF).unwrap(), 34);
+
+        // The RAM stays in place when switching banks, and it's also visible
+        // in mirrors of the ROM area.
+        address_space.read(0xFFF8).unwrap();
+        assert_eq!(address_space.read(0xF080).unwrap(), 12);
+        assert_eq!(address_space.read(0x1080).unwrap(), 12);
+        assert_eq!(address_space.read(0xF100).unwrap(), 0);
+
+        assert_eq!(
+            address_space.memory_map().last(),
+            Some(&MemoryRegion {
+                start: 0xF100,
+                end: 0xFFFF,
+                kind: MemoryKind::Rom
+            })
+        );
+    }
+
+    #[test]
+    fn super_chip_ram_without_super_chip() {
+        let cartridge = Cartridge::new(&banked_rom(0x1000, 2), BankSwitching::F8).unwrap();
+        let mut address_space = address_space_with_cartridge(cartridge);
+        address_space.write(0xF000, 12).unwrap();
+        assert_eq!(address_space.read(0xF080).unwrap(), 1);
+
+        // Only the "F" family of schemes supports the SuperChip.
+        let cartridge = Cartridge::new(&banked_rom(0x1000, 2), BankSwitching::FE)
+            .unwrap()
+            .with_super_chip(true);
+        let mut address_space = address_space_with_cartridge(cartridge);
+        address_space.write(0xF000, 12).unwrap();
+        assert_eq!(address_space.read(0xF080).unwrap(), 0);
+    }
+
+    #[test]
+    fn cartridge_size_validation() {
+        assert!(Cartridge::new(&[0; 0x1000], BankSwitching::Flat).is_ok());
+        assert!(Cartridge::new(&[0; 0x2000], BankSwitching::Flat).is_err());
+        assert!(Cartridge::new(&[0; 0x1000], BankSwitching::F8).is_err());
+        assert!(Cartridge::new(&[0; 0x4000], BankSwitching::F6).is_ok());
+        assert!(Cartridge::new(&[0; 0x4000], BankSwitching::F4).is_err());
+        assert!(Cartridge::new(&[0; 0x2800], BankSwitching::Tigervision).is_ok());
+        assert!(Cartridge::new(&[0; 0x2900], BankSwitching::Tigervision).is_err());
+    }
+
+    #[test]
+    fn bank_switching_detection() {
+        assert_eq!(BankSwitching::detect(&[0; 0x800]), BankSwitching::Flat);
+        assert_eq!(BankSwitching::detect(&[0; 0x1000]), BankSwitching::Flat);
+        assert_eq!(BankSwitching::detect(&[0; 0x2000]), BankSwitching::F8);
+        assert_eq!(BankSwitching::detect(&[0; 0x4000]), BankSwitching::F6);
+        assert_eq!(BankSwitching::detect(&[0; 0x8000]), BankSwitching::F4);
+
+        let mut rom = vec![0; 0x2000];
+        rom[0x100..0x105].copy_from_slice(&[0x20, 0x00, 0xD0, 0xC6, 0xC5]);
+        assert_eq!(BankSwitching::detect(&rom), BankSwitching::FE);
+
+        let mut rom = vec![0; 0x2000];
+        rom[0x100..0x102].copy_from_slice(&[0x85, 0x3F]);
+        rom[0x900..0x902].copy_from_slice(&[0x85, 0x3F]);
+        assert_eq!(BankSwitching::detect(&rom), BankSwitching::Tigervision);
+
+        let mut rom = vec![0; 0x800];
+        rom[0x100..0x102].copy_from_slice(&[0x85, 0x3F]);
+        rom[0x200..0x202].copy_from_slice(&[0x85, 0x3F]);
+        assert_eq!(BankSwitching::detect(&rom), BankSwitching::Flat);
+        assert!(Cartridge::with_detected_bank_switching(&rom).is_ok());
+    }
+
+    #[test]
+    fn super_chip_detection() {
+        let mut rom = banked_rom(0x1000, 4);
+        assert!(BankSwitching::detect_super_chip(&rom));
+        assert!(!BankSwitching::detect_super_chip(&[0; 0x1000]));
+
+        rom[0x20FF] = 0xEA;
+        assert!(!BankSwitching::detect_super_chip(&rom));
+        rom[0x20FF] = 2;
+        rom[0x2100] = 0xEA;
+        assert!(BankSwitching::detect_super_chip(&rom));
+
+        let cartridge = Cartridge::with_detected_bank_switching(&rom).unwrap();
+        assert!(cartridge.super_chip_ram.is_some());
+        let cartridge = Cartridge::with_detected_bank_switching(&[0; 0x1000]).unwrap();
+        assert!(cartridge.super_chip_ram.is_none());
+    }
 }
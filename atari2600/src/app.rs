@@ -1,29 +1,100 @@
 use common::app::AppController;
 use common::app::MachineController;
+use common::cheats::Cheat;
+use common::cheats::CheatError;
 use common::debugger::adapter::DebugAdapter;
+use common::debugger::coverage::CoverageTracker;
+use common::debugger::trace::Tracer;
 use common::debugger::Debugger;
+use common::gamepad::GamepadInput;
+use common::gamepad::GamepadMapping;
 use image::RgbaImage;
-use piston_window::{Button, ButtonState, Event, Input, Key, Loop};
+use piston::{Button, ButtonArgs, ButtonState, Event, Input, Key, Loop, Motion, MouseButton};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
-use crate::atari::{Atari, JoystickInput, JoystickPort, Switch, SwitchPosition};
+use crate::atari::{Atari, ControllerType, JoystickInput, JoystickPort, Paddle};
+use crate::keymap::Keymap;
+
+/// Number of paddle position units per one pixel of mouse movement.
+const PADDLE_MOUSE_SENSITIVITY: f64 = 0.5;
 
 pub struct AtariController<'a, A: DebugAdapter> {
     machine_controller: MachineController<'a, Atari, A>,
+    gamepad_mapping: GamepadMapping,
+    keymap: Keymap,
+    /// Part of a driving controller step left over from previous mouse wheel
+    /// events. Touchpads scroll in small fractions, which would be lost
+    /// otherwise.
+    scroll_remainder: f64,
 }
 
 impl<'a, A: DebugAdapter> AtariController<'a, A> {
-    pub fn new(atari: &'a mut Atari, debugger_adapter: Option<A>) -> Self {
-        let debugger = debugger_adapter.map(Debugger::new);
+    pub fn new(
+        atari: &'a mut Atari,
+        debugger: Option<Debugger<A>>,
+        gamepad_mapping: GamepadMapping,
+    ) -> Self {
         return AtariController {
             machine_controller: MachineController::new(atari, debugger),
+            gamepad_mapping,
+            keymap: Keymap::default(),
+            scroll_remainder: 0.0,
         };
     }
 
     fn mut_atari(&mut self) -> &mut Atari {
         self.machine_controller.mut_machine()
     }
+
+    pub fn set_tracer(&mut self, tracer: Option<Tracer<BufWriter<File>>>) {
+        self.machine_controller.set_tracer(tracer);
+    }
+
+    pub fn set_coverage_tracker(&mut self, tracker: Option<CoverageTracker<BufWriter<File>>>) {
+        self.machine_controller.set_coverage_tracker(tracker);
+    }
+
+    pub fn install_cheats(&mut self, cheats: &[Cheat]) -> Result<(), CheatError> {
+        self.machine_controller.install_cheats(cheats)
+    }
+
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    fn handle_key(&mut self, key: Key, state: ButtonState) {
+        if let Some(action) = self.keymap.action(key) {
+            action.perform(self.mut_atari(), state == ButtonState::Press);
+        }
+    }
+
+    /// Returns the paddle controlled by the mouse: the first one that is
+    /// connected.
+    fn mouse_paddle(&self) -> Option<Paddle> {
+        let atari = self.machine_controller.machine();
+        [
+            Paddle::Paddle0,
+            Paddle::Paddle1,
+            Paddle::Paddle2,
+            Paddle::Paddle3,
+        ]
+        .into_iter()
+        .find(|paddle| atari.paddle_position(*paddle).is_some())
+    }
+
+    /// Returns the port of the driving controller controlled by the mouse
+    /// wheel: the first one that is connected.
+    fn mouse_driving_port(&self) -> Option<JoystickPort> {
+        let atari = self.machine_controller.machine();
+        [JoystickPort::Left, JoystickPort::Right]
+            .into_iter()
+            .find(|port| atari.controller(*port) == ControllerType::Driving)
+    }
 }
 
 impl<'a, A: DebugAdapter> AppController for AtariController<'a, A> {
@@ -39,79 +110,92 @@ impl<'a, A: DebugAdapter> AppController for AtariController<'a, A> {
         self.machine_controller.interrupted()
     }
 
+    fn paused(&self) -> bool {
+        self.machine_controller.paused()
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.machine_controller.set_paused(paused);
+    }
+
+    fn frame_count(&self) -> u64 {
+        self.machine_controller.frame_count()
+    }
+
+    fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.machine_controller.load_program(path)
+    }
+
     fn display_machine_state(&self) -> String {
         self.machine_controller.display_state()
     }
 
+    fn machine_status(&self) -> String {
+        self.machine_controller.machine_status()
+    }
+
     /// Handles Piston events.
     fn event(&mut self, event: &Event) {
+        // The first gamepad controls the left joystick, and the second one
+        // controls the right one.
+        for gamepad_event in self.gamepad_mapping.translate(event) {
+            let port = match gamepad_event.gamepad {
+                0 => JoystickPort::Left,
+                1 => JoystickPort::Right,
+                _ => continue,
+            };
+            let input = match gamepad_event.input {
+                GamepadInput::Up => JoystickInput::Up,
+                GamepadInput::Down => JoystickInput::Down,
+                GamepadInput::Left => JoystickInput::Left,
+                GamepadInput::Right => JoystickInput::Right,
+                GamepadInput::Fire => JoystickInput::Fire,
+            };
+            self.mut_atari()
+                .set_joystick_input_state(port, input, gamepad_event.pressed);
+        }
+
         match event {
             Event::Input(
-                Input::Button(piston_window::ButtonArgs {
-                    state: ButtonState::Press,
-                    button: Button::Keyboard(key @ (Key::D1 | Key::D2 | Key::D3)),
+                Input::Button(ButtonArgs {
+                    state,
+                    button: Button::Keyboard(key),
                     ..
                 }),
                 _timestamp,
-            ) => {
-                if let Some(switch) = match key {
-                    Key::D1 => Some(Switch::TvType),
-                    Key::D2 => Some(Switch::LeftDifficulty),
-                    Key::D3 => Some(Switch::RightDifficulty),
-                    _ => None,
-                } {
+            ) => self.handle_key(*key, *state),
+            Event::Input(Input::Move(Motion::MouseRelative([dx, _])), _timestamp) => {
+                if let Some(paddle) = self.mouse_paddle() {
                     let atari = self.mut_atari();
-                    atari.flip_switch(switch, !atari.switch_position(switch));
+                    let position = atari.paddle_position(paddle).unwrap() as f64;
+                    let position = (position + dx * PADDLE_MOUSE_SENSITIVITY).clamp(0.0, 255.0);
+                    atari.set_paddle_position(paddle, position as u8);
                 }
             }
             Event::Input(
-                Input::Button(piston_window::ButtonArgs {
+                Input::Button(ButtonArgs {
                     state,
-                    button: Button::Keyboard(key @ (Key::D4 | Key::D5)),
+                    button: Button::Mouse(MouseButton::Left),
                     ..
                 }),
                 _timestamp,
             ) => {
-                if let Some(switch) = match key {
-                    Key::D4 => Some(Switch::GameSelect),
-                    Key::D5 => Some(Switch::GameReset),
-                    _ => None,
-                } {
-                    self.machine_controller.mut_machine().flip_switch(
-                        switch,
-                        match state {
-                            ButtonState::Press => SwitchPosition::Down,
-                            ButtonState::Release => SwitchPosition::Up,
-                        },
-                    );
+                let pressed = *state == ButtonState::Press;
+                if let Some(paddle) = self.mouse_paddle() {
+                    self.mut_atari().set_paddle_button_state(paddle, pressed);
+                } else if let Some(port) = self.mouse_driving_port() {
+                    self.mut_atari()
+                        .set_joystick_input_state(port, JoystickInput::Fire, pressed);
                 }
             }
-            Event::Input(
-                Input::Button(piston_window::ButtonArgs {
-                    state,
-                    button: Button::Keyboard(key),
-                    ..
-                }),
-                _timestamp,
-            ) => {
-                if let Some((port, input)) = match key {
-                    Key::W => Some((JoystickPort::Left, JoystickInput::Up)),
-                    Key::A => Some((JoystickPort::Left, JoystickInput::Left)),
-                    Key::S => Some((JoystickPort::Left, JoystickInput::Down)),
-                    Key::D => Some((JoystickPort::Left, JoystickInput::Right)),
-                    Key::LShift | Key::Space => Some((JoystickPort::Left, JoystickInput::Fire)),
-
-                    Key::I | Key::Up => Some((JoystickPort::Right, JoystickInput::Up)),
-                    Key::J | Key::Left => Some((JoystickPort::Right, JoystickInput::Left)),
-                    Key::K | Key::Down => Some((JoystickPort::Right, JoystickInput::Down)),
-                    Key::L | Key::Right => Some((JoystickPort::Right, JoystickInput::Right)),
-                    Key::N | Key::Period => Some((JoystickPort::Right, JoystickInput::Fire)),
-                    _ => None,
-                } {
-                    self.machine_controller
-                        .mut_machine()
-                        .set_joystick_input_state(port, input, *state == ButtonState::Press);
-                };
+            Event::Input(Input::Move(Motion::MouseScroll([_, dy])), _timestamp) => {
+                // Scrolling up turns the wheel clockwise.
+                if let Some(port) = self.mouse_driving_port() {
+                    let scroll = self.scroll_remainder + dy;
+                    self.scroll_remainder = scroll.fract();
+                    self.mut_atari()
+                        .turn_driving_controller(port, scroll.trunc() as i32);
+                }
             }
             Event::Loop(Loop::Update(_)) => self.machine_controller.run_until_end_of_frame(),
             _ => {}
@@ -125,14 +209,22 @@ mod tests {
     use crate::test_utils::assert_current_frame;
     use crate::test_utils::atari_with_rom;
     use common::debugger::adapter::TcpDebugAdapter;
-    use piston_window::ButtonArgs;
-    use piston_window::UpdateArgs;
+    use piston::ControllerAxisArgs;
+    use piston::ControllerButton;
+    use piston::ControllerHat;
+    use piston::HatState;
+    use piston::UpdateArgs;
     use std::sync::atomic::Ordering;
+    use ya6502::cpu::MachineInspector;
 
     #[test]
     fn controller_produces_images_until_interrupted() {
         let mut atari = atari_with_rom("horizontal_stripes_animated.bin");
-        let mut controller = AtariController::new(&mut atari, None::<TcpDebugAdapter>);
+        let mut controller = AtariController::new(
+            &mut atari,
+            None::<Debugger<TcpDebugAdapter>>,
+            GamepadMapping::default(),
+        );
         controller.reset();
 
         controller.event(&Event::from(UpdateArgs { dt: 1.0 / 60.0 }));
@@ -169,10 +261,43 @@ mod tests {
         }));
     }
 
+    fn send_gamepad_button<A>(controller: &mut AtariController<A>, id: u32, state: ButtonState)
+    where
+        A: DebugAdapter,
+    {
+        controller.event(&Event::from(ButtonArgs {
+            button: Button::Controller(ControllerButton::new(id, 0)),
+            state,
+            scancode: None,
+        }));
+    }
+
+    fn send_hat<A>(controller: &mut AtariController<A>, id: u32, state: HatState)
+    where
+        A: DebugAdapter,
+    {
+        controller.event(&Event::from(ButtonArgs {
+            button: Button::Hat(ControllerHat::new(id, 0, state)),
+            state: ButtonState::Press,
+            scancode: None,
+        }));
+    }
+
+    fn send_axis<A>(controller: &mut AtariController<A>, id: u32, axis: u8, position: f64)
+    where
+        A: DebugAdapter,
+    {
+        controller.event(&Event::from(ControllerAxisArgs::new(id, axis, position)));
+    }
+
     #[test]
     fn console_switches() {
         let mut atari = atari_with_rom("io_monitor.bin");
-        let mut controller = AtariController::new(&mut atari, None::<TcpDebugAdapter>);
+        let mut controller = AtariController::new(
+            &mut atari,
+            None::<Debugger<TcpDebugAdapter>>,
+            GamepadMapping::default(),
+        );
         controller.reset();
         controller.event(&Event::from(UpdateArgs { dt: 1.0 / 60.0 }));
         assert_current_frame(
@@ -233,7 +358,11 @@ mod tests {
     #[test]
     fn joysticks() {
         let mut atari = atari_with_rom("io_monitor.bin");
-        let mut controller = AtariController::new(&mut atari, None::<TcpDebugAdapter>);
+        let mut controller = AtariController::new(
+            &mut atari,
+            None::<Debugger<TcpDebugAdapter>>,
+            GamepadMapping::default(),
+        );
         controller.reset();
         controller.event(&Event::from(UpdateArgs { dt: 1.0 / 60.0 }));
 
@@ -255,4 +384,137 @@ mod tests {
         controller.event(&Event::from(UpdateArgs { dt: 1.0 / 60.0 }));
         assert_current_frame(&mut controller, "joysticks_2.png", "joysticks_2");
     }
+
+    #[test]
+    fn gamepads() {
+        let mut atari = atari_with_rom("io_monitor.bin");
+        let mut controller = AtariController::new(
+            &mut atari,
+            None::<Debugger<TcpDebugAdapter>>,
+            GamepadMapping::default(),
+        );
+        controller.reset();
+        controller.event(&Event::from(UpdateArgs { dt: 1.0 / 60.0 }));
+
+        // Same input state as in the `joysticks` test, expressed using
+        // gamepads: the first one uses a hat, and the second one uses analog
+        // axes.
+        send_hat(&mut controller, 0, HatState::RightDown);
+        send_gamepad_button(&mut controller, 0, ButtonState::Press);
+        send_axis(&mut controller, 1, 0, -1.0);
+        send_axis(&mut controller, 1, 1, -1.0);
+        send_gamepad_button(&mut controller, 1, ButtonState::Press);
+        controller.event(&Event::from(UpdateArgs { dt: 1.0 / 60.0 }));
+        assert_current_frame(&mut controller, "joysticks_1.png", "gamepads_1");
+
+        send_hat(&mut controller, 0, HatState::LeftUp);
+        send_gamepad_button(&mut controller, 0, ButtonState::Release);
+        send_axis(&mut controller, 1, 0, 1.0);
+        send_axis(&mut controller, 1, 1, 1.0);
+        send_gamepad_button(&mut controller, 1, ButtonState::Release);
+        controller.event(&Event::from(UpdateArgs { dt: 1.0 / 60.0 }));
+        assert_current_frame(&mut controller, "joysticks_2.png", "gamepads_2");
+    }
+
+    #[test]
+    fn mouse_controls_paddle() {
+        let mut atari = atari_with_rom("io_monitor.bin");
+        let mut controller = AtariController::new(
+            &mut atari,
+            None::<Debugger<TcpDebugAdapter>>,
+            GamepadMapping::default(),
+        );
+        controller.reset();
+
+        // Without paddles, the mouse is ignored.
+        controller.event(&Event::from(Motion::MouseRelative([10.0, 0.0])));
+        assert_eq!(
+            controller.mut_atari().paddle_position(Paddle::Paddle0),
+            None
+        );
+
+        controller.mut_atari().connect_paddles();
+        controller.event(&Event::from(Motion::MouseRelative([10.0, 5.0])));
+        assert_eq!(
+            controller.mut_atari().paddle_position(Paddle::Paddle0),
+            Some(0x85)
+        );
+        controller.event(&Event::from(Motion::MouseRelative([-1000.0, 0.0])));
+        assert_eq!(
+            controller.mut_atari().paddle_position(Paddle::Paddle0),
+            Some(0x00)
+        );
+        controller.event(&Event::from(Motion::MouseRelative([1000.0, 0.0])));
+        assert_eq!(
+            controller.mut_atari().paddle_position(Paddle::Paddle0),
+            Some(0xFF)
+        );
+        assert_eq!(
+            controller.mut_atari().paddle_position(Paddle::Paddle1),
+            Some(0x80)
+        );
+
+        // With paddles connected only to the right port, the mouse controls
+        // paddle 2.
+        controller
+            .mut_atari()
+            .connect_controller(JoystickPort::Left, ControllerType::Joystick);
+        controller.event(&Event::from(Motion::MouseRelative([-10.0, 0.0])));
+        assert_eq!(
+            controller.mut_atari().paddle_position(Paddle::Paddle2),
+            Some(0x7B)
+        );
+    }
+
+    #[test]
+    fn mouse_wheel_controls_driving_controller() {
+        let mut atari = atari_with_rom("io_monitor.bin");
+        let mut controller = AtariController::new(
+            &mut atari,
+            None::<Debugger<TcpDebugAdapter>>,
+            GamepadMapping::default(),
+        );
+        controller.reset();
+        let read_right_port = |controller: &AtariController<_>| {
+            controller
+                .machine_controller
+                .machine()
+                .inspect_memory(0x0280)
+                & 0xF
+        };
+
+        // Without a driving controller, the wheel is ignored.
+        controller.event(&Event::from(Motion::MouseScroll([0.0, 1.0])));
+        assert_eq!(read_right_port(&controller), 0b1111);
+
+        controller
+            .mut_atari()
+            .connect_controller(JoystickPort::Right, ControllerType::Driving);
+        controller.event(&Event::from(Motion::MouseScroll([0.0, 1.0])));
+        assert_eq!(read_right_port(&controller), 0b1101);
+        controller.event(&Event::from(Motion::MouseScroll([0.0, 2.0])));
+        assert_eq!(read_right_port(&controller), 0b1110);
+        controller.event(&Event::from(Motion::MouseScroll([0.0, -3.0])));
+        assert_eq!(read_right_port(&controller), 0b1111);
+        // Fractions of a step add up.
+        controller.event(&Event::from(Motion::MouseScroll([0.0, 0.5])));
+        assert_eq!(read_right_port(&controller), 0b1111);
+        controller.event(&Event::from(Motion::MouseScroll([0.0, 0.5])));
+        assert_eq!(read_right_port(&controller), 0b1101);
+
+        // The left mouse button acts as the fire button.
+        controller.event(&Event::from(ButtonArgs {
+            state: ButtonState::Press,
+            button: Button::Mouse(MouseButton::Left),
+            scancode: None,
+        }));
+        assert_eq!(
+            controller
+                .machine_controller
+                .machine()
+                .inspect_memory(0x000D)
+                & 0x80,
+            0
+        );
+    }
 }
@@ -1,65 +1,123 @@
 use crate::address_space::AddressSpace;
-use crate::audio::AudioConsumer;
+use crate::address_space::Cartridge;
+use crate::address_space::ROM_FILE_EXTENSIONS;
 use crate::frame_renderer::FrameRenderer;
 use crate::riot;
 use crate::riot::Riot;
 use crate::tia;
 use crate::tia::Tia;
+use crate::tv_standard::TvStandard;
 use common::app::FrameStatus;
 use common::app::Machine;
-use delegate::delegate;
+use common::app::RasterPosition;
+use common::archive::read_image_file;
+use common::audio::create_consumer_and_source;
+use common::audio::AudioConsumer;
+use common::audio::Mixer;
+use common::cheats::CheatMemory;
+use common::cheats::CheatTable;
+use common::debugger::symbols::RegisterNames;
 use enum_map::{enum_map, Enum, EnumMap};
 use image;
 use image::RgbaImage;
-use std::error;
+use serde::Deserialize;
+use std::error::Error;
+use std::path::Path;
+use std::time::Duration;
+use ya6502::cpu::ChipState;
+use ya6502::cpu::CompositeMachine;
 use ya6502::cpu::Cpu;
-use ya6502::cpu::MachineInspector;
+use ya6502::cpu::CpuError;
+use ya6502::cpu::CpuVariant;
+use ya6502::cpu::PowerOnState;
+use ya6502::mapper::Mapper;
 use ya6502::memory::Ram;
-use ya6502::memory::Rom;
+use ya6502::memory::RamPattern;
+use ya6502::memory::ReadError;
 
-pub type AtariAddressSpace = AddressSpace<Tia, Ram, Riot, Rom>;
+pub type AtariAddressSpace = AddressSpace<Tia, Ram, Riot, Box<dyn Mapper>>;
 
 impl AtariAddressSpace {
-    pub fn new(rom: Rom) -> Self {
+    /// Creates an address space with a given cartridge plugged in. Typically,
+    /// it's a [`crate::address_space::Cartridge`], but any [`Mapper`] will do.
+    /// The RIOT RAM is initialized with a given pattern, and the RIOT timer
+    /// with a given power-on state.
+    pub fn new(
+        cartridge: impl Mapper + 'static,
+        ram_pattern: RamPattern,
+        power_on_state: PowerOnState,
+    ) -> Self {
         Self {
             tia: Tia::new(),
-            ram: Ram::new(7),
-            riot: Riot::new(),
-            rom,
+            ram: Ram::with_pattern(ram_pattern, 7),
+            riot: Riot::with_power_on_state(power_on_state),
+            rom: Box::new(cartridge),
         }
     }
 }
 
+/// TIA generates two audio samples per scanline.
+pub const AUDIO_SAMPLE_RATE: u32 = 31_440;
+
+/// Number of TIA audio channels, as seen by the [`Mixer`].
+pub const AUDIO_CHANNELS: usize = 2;
+
+/// Latency of the audio buffer used when there's no audio output. It doesn't
+/// really matter, since the samples are never played.
+const DISCARDED_AUDIO_LATENCY: Duration = Duration::from_millis(50);
+
+/// The Atari 2600 console. Use [`AtariBuilder`] to create one, and the
+/// [`Machine`] interface to run it and retrieve the frames.
 pub struct Atari {
-    cpu: Cpu<AtariAddressSpace>,
+    cpu: Cpu<CheatMemory<AtariAddressSpace>>,
     frame_renderer: FrameRenderer,
     audio_consumer: AudioConsumer,
+    mixer: Mixer,
     switch_positions: EnumMap<Switch, SwitchPosition>,
+    controllers: EnumMap<JoystickPort, ControllerType>,
     joysticks: EnumMap<JoystickPort, Joystick>,
+    /// Paddle positions, or `None` if a given paddle is not connected.
+    paddle_positions: EnumMap<Paddle, Option<u8>>,
+    paddle_buttons: EnumMap<Paddle, bool>,
+    keypads: EnumMap<JoystickPort, Keypad>,
+    driving_controllers: EnumMap<JoystickPort, DrivingController>,
 
+    /// TIA outputs rendered ahead, up to the next CPU cycle, and the index of
+    /// the next one to be consumed. The TIA registers can only change at a CPU
+    /// cycle, so it's enough to decode them once per run.
+    tia_outputs: Vec<tia::TiaOutput>,
+    next_tia_output: usize,
     at_cpu_cycle: bool,
 }
 
 impl Machine for Atari {
     /// Performs a single clock tick. If it resulted in an error reported by the
-    /// CPU, dump debug information on standard error stream and return
-    /// `TickResult::Error`.
-    fn tick(&mut self) -> Result<FrameStatus, Box<dyn error::Error>> {
-        let tia_result = self.mut_tia().tick();
+    /// CPU, returns the error.
+    fn tick(&mut self) -> Result<FrameStatus, CpuError> {
+        if self.next_tia_output >= self.tia_outputs.len() {
+            self.tia_outputs.clear();
+            self.next_tia_output = 0;
+            let tia = &mut self.cpu.mut_memory().tia;
+            tia.tick_until_cpu_cycle(&mut self.tia_outputs);
+        }
+        let tia_result = self.tia_outputs[self.next_tia_output];
+        self.next_tia_output += 1;
         self.at_cpu_cycle = tia_result.cpu_tick;
         if self.at_cpu_cycle {
             if let Err(e) = self.cpu.tick() {
                 return Err(e);
             }
+            self.update_keypads();
         }
         if tia_result.riot_tick {
             self.mut_riot().tick();
         }
         if let Some(audio) = tia_result.audio {
-            self.audio_consumer
-                .consume((audio.au0 + audio.au1) as f32 / 30.0 - 0.5);
+            let samples = [audio.au0, audio.au1].map(|sample| sample as f32 / 15.0 - 0.5);
+            self.audio_consumer.consume_stereo(self.mixer.mix(&samples));
         }
         return if self.frame_renderer.consume(tia_result.video) {
+            self.rotate_driving_controllers();
             Ok(FrameStatus::Complete)
         } else {
             Ok(FrameStatus::Pending)
@@ -71,55 +129,100 @@ impl Machine for Atari {
     }
 
     fn reset(&mut self) {
+        // Pending TIA outputs are kept, since the reset doesn't affect the TIA,
+        // and they still describe the next pixels of the current scanline.
+        self.cpu.mut_memory().rom.reset();
         self.cpu.reset()
     }
 
     fn display_state(&self) -> String {
         format!("{}\n{}", self.cpu(), self.cpu().memory())
     }
+
+    fn raster_position(&self) -> RasterPosition {
+        self.frame_renderer.raster_position()
+    }
+
+    fn register_names(&self) -> RegisterNames {
+        tia::register_names()
+            .merge(riot::register_names(0x280))
+            .with_mirroring(crate::address_space::register_address)
+    }
+
+    /// Plugs in a cartridge loaded from a given ROM image. The bank switching
+    /// scheme is detected automatically.
+    fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let rom = read_image_file(path, ROM_FILE_EXTENSIONS)?.bytes;
+        self.cpu.mut_memory().rom = Box::new(Cartridge::with_detected_bank_switching(&rom)?);
+        Ok(())
+    }
+
+    fn mut_cheats(&mut self) -> Option<&mut CheatTable> {
+        Some(self.cpu.mut_memory().mut_cheats())
+    }
 }
 
-impl MachineInspector for Atari {
-    delegate! {
-        to self.cpu {
-            fn reg_pc(&self) -> u16;
-            fn reg_a(&self) -> u8;
-            fn reg_x(&self) -> u8;
-            fn reg_y(&self) -> u8;
-            fn reg_sp(&self) -> u8;
-            fn flags(&self) -> u8;
-            fn inspect_memory(&self, address: u16) -> u8;
-        }
+impl CompositeMachine for Atari {
+    type Memory = CheatMemory<AtariAddressSpace>;
+
+    fn cpu(&self) -> &Cpu<Self::Memory> {
+        &self.cpu
     }
 
-    fn at_instruction_start(&self) -> bool {
-        self.at_cpu_cycle && self.cpu.at_instruction_start()
+    fn mut_cpu(&mut self) -> &mut Cpu<Self::Memory> {
+        &mut self.cpu
+    }
+
+    fn at_cpu_cycle(&self) -> bool {
+        self.at_cpu_cycle
+    }
+
+    fn chip_states(&self) -> Result<Vec<ChipState>, ReadError> {
+        let memory = self.cpu.memory();
+        Ok(vec![memory.tia.chip_state()?, memory.riot.chip_state()?])
     }
 }
 
 impl Atari {
+    /// Creates a console out of the given parts. [`AtariBuilder`] is usually
+    /// more convenient.
     pub fn new(
         address_space: Box<AtariAddressSpace>,
         frame_renderer: FrameRenderer,
         audio_consumer: AudioConsumer,
+        power_on_state: PowerOnState,
     ) -> Self {
+        let memory = CheatMemory::new(
+            *address_space,
+            CheatTable::new().with_mirroring(crate::address_space::mirrored_address),
+        );
         let mut atari = Atari {
-            cpu: Cpu::new(address_space),
+            cpu: Cpu::with_power_on_state(Box::new(memory), CpuVariant::Nmos6502, power_on_state),
             frame_renderer,
             audio_consumer,
+            mixer: Mixer::new(AUDIO_CHANNELS),
             switch_positions: enum_map! { _ => SwitchPosition::Up },
+            controllers: enum_map! { _ => ControllerType::Joystick },
             joysticks: enum_map! { _ => Joystick::new() },
+            paddle_positions: enum_map! { _ => None },
+            paddle_buttons: enum_map! { _ => false },
+            keypads: enum_map! { _ => Keypad::new() },
+            driving_controllers: enum_map! { _ => DrivingController::new() },
 
+            tia_outputs: Vec::with_capacity(3),
+            next_tia_output: 0,
             at_cpu_cycle: false,
         };
 
         atari.update_switches_riot_port();
-        atari.update_joystick_ports();
+        atari.update_controller_ports();
         return atari;
     }
 
-    pub fn cpu(&self) -> &Cpu<AtariAddressSpace> {
-        &self.cpu
+    /// Replaces the mixer of TIA audio channels. The default one plays both
+    /// channels centered.
+    pub fn set_mixer(&mut self, mixer: Mixer) {
+        self.mixer = mixer;
     }
 
     fn mut_tia(&mut self) -> &mut Tia {
@@ -130,10 +233,23 @@ impl Atari {
         return &mut self.cpu.mut_memory().riot;
     }
 
+    /// Returns `true` if frame blending is enabled.
+    pub fn frame_blending(&self) -> bool {
+        self.frame_renderer.frame_blending()
+    }
+
+    /// Enables or disables frame blending. See
+    /// [`FrameRenderer::set_frame_blending`].
+    pub fn set_frame_blending(&mut self, frame_blending: bool) {
+        self.frame_renderer.set_frame_blending(frame_blending);
+    }
+
+    /// Returns the current position of a given console switch.
     pub fn switch_position(&self, switch: Switch) -> SwitchPosition {
         self.switch_positions[switch]
     }
 
+    /// Moves a given console switch to a given position.
     pub fn flip_switch(&mut self, switch: Switch, position: SwitchPosition) {
         self.switch_positions[switch] = position;
         self.update_switches_riot_port();
@@ -144,10 +260,37 @@ impl Atari {
             .switch_positions
             .iter()
             .map(|(switch, pos)| switch.port_value_when(*pos))
-            .fold(0b0011_0100, |acc, item| acc | item);
+            .fold(riot::flags::SWCHB_UNUSED, |acc, item| acc | item);
         self.mut_riot().set_port(riot::Port::PB, port_value);
     }
 
+    /// Returns the type of the controller connected to a given port.
+    pub fn controller(&self, port: JoystickPort) -> ControllerType {
+        self.controllers[port]
+    }
+
+    /// Connects a controller of a given type to a given port, replacing the
+    /// previous one. Just like on a real console, this can be done at any time,
+    /// although some games only check the controllers once they start.
+    pub fn connect_controller(&mut self, port: JoystickPort, controller: ControllerType) {
+        self.controllers[port] = controller;
+        self.joysticks[port] = Joystick::new();
+        self.keypads[port] = Keypad::new();
+        self.driving_controllers[port] = DrivingController::new();
+        for paddle in port.paddles() {
+            // Paddles are centered when connected.
+            self.paddle_positions[paddle] = match controller {
+                ControllerType::Paddles => Some(0x80),
+                _ => None,
+            };
+            self.paddle_buttons[paddle] = false;
+            self.update_paddle_port(paddle);
+        }
+        self.update_controller_ports();
+    }
+
+    /// Presses (`state == true`) or releases a joystick button or direction.
+    /// A driving controller is turned using left and right directions.
     pub fn set_joystick_input_state(
         &mut self,
         port: JoystickPort,
@@ -155,20 +298,299 @@ impl Atari {
         state: bool,
     ) {
         self.joysticks[port].set_state(input, state);
-        self.update_joystick_ports();
+        self.driving_controllers[port].set_state(input, state);
+        self.update_controller_ports();
+    }
+
+    /// Connects paddles to both controller ports and centers them.
+    pub fn connect_paddles(&mut self) {
+        self.connect_controller(JoystickPort::Left, ControllerType::Paddles);
+        self.connect_controller(JoystickPort::Right, ControllerType::Paddles);
+    }
+
+    /// Returns the position of a given paddle, or `None` if it's not
+    /// connected.
+    pub fn paddle_position(&self, paddle: Paddle) -> Option<u8> {
+        self.paddle_positions[paddle]
+    }
+
+    /// Sets the position of a given paddle. 0 means turned all the way
+    /// counterclockwise, 255 means all the way clockwise. Ignored if the paddle
+    /// is not connected.
+    pub fn set_paddle_position(&mut self, paddle: Paddle, position: u8) {
+        if self.paddle_positions[paddle].is_some() {
+            self.paddle_positions[paddle] = Some(position);
+            self.update_paddle_port(paddle);
+        }
+    }
+
+    /// Presses or releases the button of a given paddle. Ignored if the paddle
+    /// is not connected.
+    pub fn set_paddle_button_state(&mut self, paddle: Paddle, pressed: bool) {
+        if self.paddle_positions[paddle].is_some() {
+            self.paddle_buttons[paddle] = pressed;
+            self.update_controller_ports();
+        }
+    }
+
+    fn update_paddle_port(&mut self, paddle: Paddle) {
+        // Turning the paddle clockwise decreases its resistance.
+        let resistance = self.paddle_positions[paddle].map(|position| 0xFF - position);
+        self.mut_tia()
+            .set_paddle_resistance(paddle.tia_port(), resistance);
+    }
+
+    /// Presses or releases a key of a keyboard controller connected to a given
+    /// port. Ignored if there's no keyboard controller there.
+    pub fn set_keypad_key_state(&mut self, port: JoystickPort, key: KeypadKey, pressed: bool) {
+        if self.controllers[port] == ControllerType::Keypad {
+            self.keypads[port].pressed_keys[key] = pressed;
+            self.update_keypads();
+        }
+    }
+
+    /// Returns the port A direction lines and the fire button line of a
+    /// controller connected to a given port. The fire button line is `None` if
+    /// it's driven by a keypad column instead.
+    fn controller_port_values(&self, port: JoystickPort) -> (u8, Option<bool>) {
+        match self.controllers[port] {
+            ControllerType::Joystick => {
+                let (dir_port, fire_port) = self.joysticks[port].port_values();
+                (dir_port, Some(fire_port))
+            }
+            // Paddle buttons are handled separately.
+            ControllerType::Paddles => (0b1111, Some(true)),
+            // Keypads don't pull any port A lines down; they use them as
+            // outputs.
+            ControllerType::Keypad => (0b1111, None),
+            ControllerType::Driving => {
+                let (dir_port, fire_port) = self.driving_controllers[port].port_values();
+                (dir_port, Some(fire_port))
+            }
+        }
+    }
+
+    /// Turns the wheel of a driving controller connected to a given port by a
+    /// given number of Gray code steps. Positive values turn it clockwise,
+    /// negative ones counterclockwise. Ignored if there's no driving
+    /// controller there.
+    pub fn turn_driving_controller(&mut self, port: JoystickPort, steps: i32) {
+        if self.controllers[port] == ControllerType::Driving {
+            self.driving_controllers[port].turn(steps);
+            self.update_controller_ports();
+        }
+    }
+
+    /// Turns the wheels of driving controllers. Called once per frame.
+    fn rotate_driving_controllers(&mut self) {
+        let mut rotated = false;
+        for (port, driving_controller) in &mut self.driving_controllers {
+            if self.controllers[port] == ControllerType::Driving {
+                rotated |= driving_controller.rotate();
+            }
+        }
+        if rotated {
+            self.update_controller_ports();
+        }
+    }
+
+    fn update_controller_ports(&mut self) {
+        let (left_dir_port, left_fire_port) = self.controller_port_values(JoystickPort::Left);
+        let (right_dir_port, right_fire_port) = self.controller_port_values(JoystickPort::Right);
+        // Paddle buttons share the lines with joystick directions.
+        let paddle_buttons_mask = self
+            .paddle_buttons
+            .iter()
+            .filter(|(_, pressed)| **pressed)
+            .fold(0, |acc, (paddle, _)| acc | paddle.button_mask());
+        self.mut_riot().set_port(
+            riot::Port::PA,
+            ((left_dir_port << 4) | right_dir_port) & !paddle_buttons_mask,
+        );
+        if let Some(left_fire_port) = left_fire_port {
+            self.mut_tia().set_port(tia::Port::Input4, left_fire_port);
+        }
+        if let Some(right_fire_port) = right_fire_port {
+            self.mut_tia().set_port(tia::Port::Input5, right_fire_port);
+        }
+        self.update_keypads();
+    }
+
+    /// Updates the keypad column lines, depending on which keys are pressed
+    /// and which rows are currently selected by the program. Since the rows
+    /// are selected using RIOT port A outputs, this needs to happen on every
+    /// CPU cycle.
+    fn update_keypads(&mut self) {
+        let port_a = self.cpu.memory().riot.port_a_output();
+        for (port, keypad) in self.keypads {
+            if self.controllers[port] != ControllerType::Keypad {
+                continue;
+            }
+            let rows = match port {
+                JoystickPort::Left => port_a >> 4,
+                JoystickPort::Right => port_a & 0b1111,
+            };
+            let [column0, column1, column2] = keypad.pressed_columns(rows);
+            let (pot0, pot1, fire) = port.keypad_column_ports();
+            // Columns 0 and 1 are connected to the paddle inputs. When no key
+            // is pressed, they are pulled up and charge instantly; a pressed
+            // key grounds them.
+            let tia = self.mut_tia();
+            tia.set_paddle_resistance(pot0, if column0 { None } else { Some(0) });
+            tia.set_paddle_resistance(pot1, if column1 { None } else { Some(0) });
+            tia.set_port(fire, !column2);
+        }
+    }
+}
+
+/// Assembles an [`Atari`] with a given cartridge plugged in. The rest of the
+/// configuration is optional; by default, the builder creates an NTSC console
+/// with zeroed RAM, random CPU registers, joysticks connected, all switches up,
+/// and audio samples discarded.
+pub struct AtariBuilder {
+    cartridge: Box<dyn Mapper>,
+    tv_standard: TvStandard,
+    ram_pattern: RamPattern,
+    power_on_state: PowerOnState,
+    audio_consumer: Option<AudioConsumer>,
+    mixer: Option<Mixer>,
+    frame_blending: bool,
+    threaded_frame_conversion: bool,
+    controllers: EnumMap<JoystickPort, ControllerType>,
+    permissive: bool,
+    switch_positions: EnumMap<Switch, SwitchPosition>,
+}
+
+impl AtariBuilder {
+    /// Starts building a console with a given cartridge. Typically, it's a
+    /// [`crate::address_space::Cartridge`], but any [`Mapper`] will do.
+    pub fn new(cartridge: impl Mapper + 'static) -> Self {
+        Self {
+            cartridge: Box::new(cartridge),
+            tv_standard: TvStandard::Ntsc,
+            ram_pattern: RamPattern::default(),
+            power_on_state: PowerOnState::Random,
+            audio_consumer: None,
+            mixer: None,
+            frame_blending: false,
+            threaded_frame_conversion: false,
+            controllers: enum_map! { _ => ControllerType::Joystick },
+            permissive: false,
+            switch_positions: enum_map! { _ => SwitchPosition::Up },
+        }
+    }
+
+    pub fn with_tv_standard(mut self, tv_standard: TvStandard) -> Self {
+        self.tv_standard = tv_standard;
+        self
+    }
+
+    pub fn with_ram_pattern(mut self, ram_pattern: RamPattern) -> Self {
+        self.ram_pattern = ram_pattern;
+        self
+    }
+
+    pub fn with_power_on_state(mut self, power_on_state: PowerOnState) -> Self {
+        self.power_on_state = power_on_state;
+        self
+    }
+
+    /// Sends the audio samples to a given consumer. The samples are generated
+    /// at [`AUDIO_SAMPLE_RATE`].
+    pub fn with_audio_consumer(mut self, audio_consumer: AudioConsumer) -> Self {
+        self.audio_consumer = Some(audio_consumer);
+        self
+    }
+
+    /// Mixes the TIA audio channels using a given mixer, which should have
+    /// [`AUDIO_CHANNELS`] channels. See [`Atari::set_mixer`].
+    pub fn with_mixer(mut self, mixer: Mixer) -> Self {
+        self.mixer = Some(mixer);
+        self
+    }
+
+    pub fn with_frame_blending(mut self, frame_blending: bool) -> Self {
+        self.frame_blending = frame_blending;
+        self
+    }
+
+    /// Converts the picture to RGBA on a separate thread, overlapping with the
+    /// emulation. Unlike the default mode, it never shows partially drawn
+    /// frames, e.g. after stopping the console in the middle of a frame. See
+    /// [`crate::frame_renderer::FrameRendererBuilder::with_threaded_conversion`].
+    pub fn with_threaded_frame_conversion(mut self, threaded_frame_conversion: bool) -> Self {
+        self.threaded_frame_conversion = threaded_frame_conversion;
+        self
     }
 
-    fn update_joystick_ports(&mut self) {
-        let (left_dir_port, left_fire_port) = self.joysticks[JoystickPort::Left].port_values();
-        let (right_dir_port, right_fire_port) = self.joysticks[JoystickPort::Right].port_values();
-        self.mut_riot()
-            .set_port(riot::Port::PA, (left_dir_port << 4) | right_dir_port);
-        self.mut_tia().set_port(tia::Port::Input4, left_fire_port);
-        self.mut_tia().set_port(tia::Port::Input5, right_fire_port);
+    /// Connects a controller of a given type to a given port.
+    pub fn with_controller(mut self, port: JoystickPort, controller: ControllerType) -> Self {
+        self.controllers[port] = controller;
+        self
+    }
+
+    /// Connects paddles to both ports instead of joysticks. See
+    /// [`Atari::connect_paddles`].
+    pub fn with_paddles(mut self, paddles: bool) -> Self {
+        let controller = if paddles {
+            ControllerType::Paddles
+        } else {
+            ControllerType::Joystick
+        };
+        self.controllers = enum_map! { _ => controller };
+        self
+    }
+
+    /// Keeps the CPU running after an unknown opcode or an unsupported
+    /// register write. See [`Cpu::set_permissive`].
+    pub fn with_permissive(mut self, permissive: bool) -> Self {
+        self.permissive = permissive;
+        self
+    }
+
+    /// Sets the initial position of a given console switch. For the game
+    /// select and game reset buttons, `SwitchPosition::Down` means that the
+    /// button is held down.
+    pub fn with_switch_position(mut self, switch: Switch, position: SwitchPosition) -> Self {
+        self.switch_positions[switch] = position;
+        self
+    }
+
+    pub fn build(self) -> Atari {
+        let audio_consumer = self.audio_consumer.unwrap_or_else(|| {
+            let (consumer, _) =
+                create_consumer_and_source(AUDIO_SAMPLE_RATE, DISCARDED_AUDIO_LATENCY);
+            consumer
+        });
+        let mut atari = Atari::new(
+            Box::new(AtariAddressSpace::new(
+                self.cartridge,
+                self.ram_pattern,
+                self.power_on_state,
+            )),
+            self.tv_standard
+                .frame_renderer_builder()
+                .with_frame_blending(self.frame_blending)
+                .with_threaded_conversion(self.threaded_frame_conversion)
+                .build(),
+            audio_consumer,
+            self.power_on_state,
+        );
+        if let Some(mixer) = self.mixer {
+            atari.set_mixer(mixer);
+        }
+        for (port, controller) in self.controllers {
+            atari.connect_controller(port, controller);
+        }
+        atari.cpu.set_permissive(self.permissive);
+        for (switch, position) in self.switch_positions {
+            atari.flip_switch(switch, position);
+        }
+        return atari;
     }
 }
 
-#[derive(Debug, Copy, Clone, Enum)]
+#[derive(Debug, Copy, Clone, PartialEq, Enum, Deserialize)]
 pub enum Switch {
     TvType,
     LeftDifficulty,
@@ -182,11 +604,11 @@ impl Switch {
         match position {
             SwitchPosition::Down => 0,
             SwitchPosition::Up => match self {
-                Self::RightDifficulty => 1 << 7,
-                Self::LeftDifficulty => 1 << 6,
-                Self::TvType => 1 << 3,
-                Self::GameSelect => 1 << 1,
-                Self::GameReset => 1,
+                Self::RightDifficulty => riot::flags::SWCHB_P1_DIFFICULTY,
+                Self::LeftDifficulty => riot::flags::SWCHB_P0_DIFFICULTY,
+                Self::TvType => riot::flags::SWCHB_COLOR,
+                Self::GameSelect => riot::flags::SWCHB_SELECT,
+                Self::GameReset => riot::flags::SWCHB_RESET,
             },
         }
     }
@@ -208,7 +630,7 @@ impl std::ops::Not for SwitchPosition {
     }
 }
 
-#[derive(Enum)]
+#[derive(Debug, Copy, Clone, PartialEq, Enum, Deserialize)]
 pub enum JoystickInput {
     Up,
     Down,
@@ -270,42 +692,249 @@ impl Joystick {
     }
 }
 
-#[derive(Enum)]
+#[derive(Debug, Copy, Clone, PartialEq, Enum, Deserialize)]
 pub enum JoystickPort {
     Left,
     Right,
 }
 
+impl JoystickPort {
+    /// Returns the paddles that are connected to this port if it has a pair
+    /// of paddles plugged in.
+    fn paddles(&self) -> [Paddle; 2] {
+        match *self {
+            Self::Left => [Paddle::Paddle0, Paddle::Paddle1],
+            Self::Right => [Paddle::Paddle2, Paddle::Paddle3],
+        }
+    }
+
+    /// Returns the TIA inputs connected to keypad columns 0, 1, and 2.
+    fn keypad_column_ports(&self) -> (tia::PaddlePort, tia::PaddlePort, tia::Port) {
+        match *self {
+            Self::Left => (
+                tia::PaddlePort::Input0,
+                tia::PaddlePort::Input1,
+                tia::Port::Input4,
+            ),
+            Self::Right => (
+                tia::PaddlePort::Input2,
+                tia::PaddlePort::Input3,
+                tia::Port::Input5,
+            ),
+        }
+    }
+}
+
+/// Type of a controller connected to one of the controller ports.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, clap::ArgEnum)]
+pub enum ControllerType {
+    Joystick,
+    /// A pair of paddles.
+    #[clap(name = "paddle")]
+    Paddles,
+    /// A 12-key keyboard controller.
+    Keypad,
+    /// A driving controller, like the one bundled with Indy 500.
+    Driving,
+}
+
+impl ControllerType {
+    /// Returns the next controller type, in the order of declaration. Used to
+    /// cycle through the controller types at runtime.
+    pub fn next(&self) -> Self {
+        match *self {
+            Self::Joystick => Self::Paddles,
+            Self::Paddles => Self::Keypad,
+            Self::Keypad => Self::Driving,
+            Self::Driving => Self::Joystick,
+        }
+    }
+}
+
+/// Gray code reported by a driving controller in consecutive positions while
+/// turning clockwise.
+const DRIVING_CONTROLLER_GRAY_CODE: [u8; 4] = [0b11, 0b01, 0b00, 0b10];
+
+/// Number of wheel position units per one step of the Gray code.
+const DRIVING_CONTROLLER_UNITS_PER_STEP: u8 = 4;
+
+/// A driving controller: a wheel that can be turned indefinitely in both
+/// directions, and a fire button. The wheel position is reported as a 2-bit
+/// Gray code on the joystick up and down lines. Since host keys and gamepads
+/// are digital, holding left or right turns the wheel at a constant speed.
+#[derive(Debug, Copy, Clone)]
+struct DrivingController {
+    /// Wheel position, in quarters of a Gray code step.
+    position: u8,
+    turning_left: bool,
+    turning_right: bool,
+    fire_port: bool,
+}
+
+impl DrivingController {
+    fn new() -> Self {
+        Self {
+            position: 0,
+            turning_left: false,
+            turning_right: false,
+            fire_port: true,
+        }
+    }
+
+    fn set_state(&mut self, input: JoystickInput, state: bool) {
+        match input {
+            JoystickInput::Left => self.turning_left = state,
+            JoystickInput::Right => self.turning_right = state,
+            JoystickInput::Fire => self.fire_port = !state,
+            JoystickInput::Up | JoystickInput::Down => {}
+        }
+    }
+
+    /// Turns the wheel by a single unit if left or right is being held.
+    /// Returns `true` if the wheel has been turned.
+    fn rotate(&mut self) -> bool {
+        match (self.turning_left, self.turning_right) {
+            (true, false) => self.position = self.position.wrapping_sub(1),
+            (false, true) => self.position = self.position.wrapping_add(1),
+            _ => return false,
+        }
+        return true;
+    }
+
+    /// Turns the wheel by a given number of Gray code steps, regardless of the
+    /// held directions.
+    fn turn(&mut self, steps: i32) {
+        let units = steps.wrapping_mul(DRIVING_CONTROLLER_UNITS_PER_STEP as i32);
+        self.position = self.position.wrapping_add(units as u8);
+    }
+
+    fn port_values(&self) -> (u8, bool) {
+        let gray_code = DRIVING_CONTROLLER_GRAY_CODE
+            [(self.position / DRIVING_CONTROLLER_UNITS_PER_STEP) as usize % 4];
+        (0b1100 | gray_code, self.fire_port)
+    }
+}
+
+/// Keys of a keyboard controller (also known as Keyboard Controller, Video
+/// Touch Pad, or Star Raiders keypad), in the row-major order: 1, 2, 3 in the
+/// top row, and *, 0, # in the bottom one.
+#[derive(Debug, Copy, Clone, PartialEq, Enum, Deserialize)]
+pub enum KeypadKey {
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    Star,
+    Key0,
+    Pound,
+}
+
+impl KeypadKey {
+    fn row(&self) -> usize {
+        *self as usize / 3
+    }
+
+    fn column(&self) -> usize {
+        *self as usize % 3
+    }
+}
+
+/// A 12-key keyboard controller. Its four rows are connected to the RIOT port A
+/// lines that are normally used for joystick directions, and the program
+/// selects a row by driving its line low. Pressing a key connects its row to
+/// its column, so the column reads as low if the key's row is selected.
+#[derive(Debug, Copy, Clone)]
+struct Keypad {
+    pressed_keys: EnumMap<KeypadKey, bool>,
+}
+
+impl Keypad {
+    fn new() -> Self {
+        Self {
+            pressed_keys: enum_map! { _ => false },
+        }
+    }
+
+    /// Returns which columns are pulled low, given the levels of the row lines
+    /// (one bit per row, starting from the top row at bit 0).
+    fn pressed_columns(&self, rows: u8) -> [bool; 3] {
+        let mut columns = [false; 3];
+        for (key, pressed) in self.pressed_keys {
+            if pressed && rows & (1 << key.row()) == 0 {
+                columns[key.column()] = true;
+            }
+        }
+        return columns;
+    }
+}
+
+/// Paddles come in pairs; paddles 0 and 1 are connected to the left controller
+/// port, and paddles 2 and 3 to the right one.
+#[derive(Debug, Enum, Clone, Copy)]
+pub enum Paddle {
+    Paddle0,
+    Paddle1,
+    Paddle2,
+    Paddle3,
+}
+
+impl Paddle {
+    fn tia_port(&self) -> tia::PaddlePort {
+        match *self {
+            Self::Paddle0 => tia::PaddlePort::Input0,
+            Self::Paddle1 => tia::PaddlePort::Input1,
+            Self::Paddle2 => tia::PaddlePort::Input2,
+            Self::Paddle3 => tia::PaddlePort::Input3,
+        }
+    }
+
+    /// Returns a mask of the RIOT port A bit that reports the paddle button.
+    fn button_mask(&self) -> u8 {
+        match *self {
+            Self::Paddle0 => 1 << 7,
+            Self::Paddle1 => 1 << 6,
+            Self::Paddle2 => 1 << 3,
+            Self::Paddle3 => 1 << 2,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     extern crate test;
 
     use super::*;
-    use crate::audio::create_consumer_and_source;
+    use crate::address_space::Cartridge;
     use crate::colors;
     use crate::frame_renderer::FrameRendererBuilder;
     use crate::test_utils::assert_images_equal;
     use crate::test_utils::atari_with_rom;
     use crate::test_utils::read_test_rom;
+    use common::audio::create_consumer_and_source;
     use common::test_utils::read_test_image;
     use image::DynamicImage;
+    use std::time::Duration;
     use test::Bencher;
-    use ya6502::cpu::{opcodes, CpuHaltedError};
-
-    fn next_frame(atari: &mut Atari) -> Result<RgbaImage, Box<dyn error::Error>> {
-        loop {
-            match atari.tick() {
-                Ok(FrameStatus::Pending) => {}
-                Ok(FrameStatus::Complete) => break,
-                Err(e) => {
-                    eprintln!("ERROR: {}. Atari halted.", e);
-                    eprintln!("{}", atari.cpu);
-                    eprintln!("{}", atari.cpu.memory());
-                    return Err(e);
-                }
+    use ya6502::cpu::opcodes;
+    use ya6502::cpu::MachineInspector;
+    use ya6502::memory::Inspect;
+    use ya6502::memory::Write;
+
+    fn next_frame(atari: &mut Atari) -> Result<RgbaImage, CpuError> {
+        match atari.run_frame() {
+            Ok(image) => return Ok(image.clone()),
+            Err(e) => {
+                eprintln!("ERROR: {}. Atari halted.", e);
+                eprintln!("{}", atari.cpu);
+                eprintln!("{}", atari.cpu.memory());
+                return Err(e);
             }
         }
-        return Ok(atari.frame_renderer.frame_image().clone());
     }
 
     fn assert_produces_frame(atari: &mut Atari, test_image_name: &str, test_name: &str) {
@@ -355,10 +984,8 @@ mod tests {
 
         let expected_image = read_test_image("reports_halt.png");
         assert_eq!(
-            *(*next_frame(&mut atari).unwrap_err())
-                .downcast_ref::<CpuHaltedError>()
-                .unwrap(),
-            CpuHaltedError {
+            next_frame(&mut atari).unwrap_err(),
+            CpuError::Halted {
                 opcode: opcodes::HLT1,
                 address: 0xF2BA
             }
@@ -439,6 +1066,191 @@ mod tests {
         assert_produces_frame(&mut atari, "input_5.png", "input_5");
     }
 
+    #[test]
+    fn initial_switch_positions() {
+        let atari = AtariBuilder::new(Ram::new(12))
+            .with_switch_position(Switch::LeftDifficulty, SwitchPosition::Down)
+            .with_switch_position(Switch::TvType, SwitchPosition::Down)
+            .build();
+        assert_eq!(
+            atari.switch_position(Switch::LeftDifficulty),
+            SwitchPosition::Down
+        );
+        assert_eq!(
+            atari.switch_position(Switch::RightDifficulty),
+            SwitchPosition::Up
+        );
+        assert_eq!(
+            atari.cpu.memory().inspect(0x0282).unwrap(),
+            riot::flags::SWCHB_UNUSED
+                | riot::flags::SWCHB_P1_DIFFICULTY
+                | riot::flags::SWCHB_SELECT
+                | riot::flags::SWCHB_RESET
+        );
+    }
+
+    #[test]
+    fn keypads() {
+        let mut atari = AtariBuilder::new(Ram::new(12))
+            .with_controller(JoystickPort::Right, ControllerType::Keypad)
+            .build();
+        let read_inputs = |atari: &Atari| {
+            [0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D]
+                .map(|address| atari.cpu.memory().inspect(address).unwrap() >> 7)
+        };
+        // Select the second row of the right keypad.
+        atari.cpu.mut_memory().write(0x0281, 0b0000_1111).unwrap();
+        atari.cpu.mut_memory().write(0x0280, 0b1111_1101).unwrap();
+        atari.update_keypads();
+        assert_eq!(read_inputs(&atari), [0, 0, 1, 1, 1, 1]);
+
+        // Keys in the selected row pull their columns low.
+        atari.set_keypad_key_state(JoystickPort::Right, KeypadKey::Key5, true);
+        assert_eq!(read_inputs(&atari), [0, 0, 1, 0, 1, 1]);
+        atari.set_keypad_key_state(JoystickPort::Right, KeypadKey::Key6, true);
+        assert_eq!(read_inputs(&atari), [0, 0, 1, 0, 1, 0]);
+
+        // Keys in other rows don't.
+        atari.set_keypad_key_state(JoystickPort::Right, KeypadKey::Key5, false);
+        atari.set_keypad_key_state(JoystickPort::Right, KeypadKey::Key6, false);
+        atari.set_keypad_key_state(JoystickPort::Right, KeypadKey::Star, true);
+        assert_eq!(read_inputs(&atari), [0, 0, 1, 1, 1, 1]);
+
+        atari.cpu.mut_memory().write(0x0280, 0b1111_0111).unwrap();
+        atari.update_keypads();
+        assert_eq!(read_inputs(&atari), [0, 0, 0, 1, 1, 1]);
+
+        // Joystick inputs on the keypad's port are ignored.
+        atari.set_joystick_input_state(JoystickPort::Right, JoystickInput::Up, true);
+        atari.set_joystick_input_state(JoystickPort::Right, JoystickInput::Fire, true);
+        assert_eq!(read_inputs(&atari), [0, 0, 0, 1, 1, 1]);
+        assert_eq!(atari.cpu.memory().inspect(0x0280).unwrap(), 0b1111_0111);
+    }
+
+    #[test]
+    fn driving_controllers() {
+        let mut atari = AtariBuilder::new(Ram::new(12))
+            .with_controller(JoystickPort::Left, ControllerType::Driving)
+            .build();
+        let read_left_port = |atari: &Atari| atari.cpu.memory().inspect(0x0280).unwrap() >> 4;
+        assert_eq!(read_left_port(&atari), 0b1111);
+
+        // Each step of the Gray code takes 4 frames.
+        atari.set_joystick_input_state(JoystickPort::Left, JoystickInput::Right, true);
+        let mut codes = vec![];
+        for _ in 0..9 {
+            for _ in 0..4 {
+                atari.rotate_driving_controllers();
+            }
+            codes.push(read_left_port(&atari));
+        }
+        assert_eq!(
+            codes,
+            [0b1101, 0b1100, 0b1110, 0b1111, 0b1101, 0b1100, 0b1110, 0b1111, 0b1101]
+        );
+
+        atari.set_joystick_input_state(JoystickPort::Left, JoystickInput::Right, false);
+        atari.set_joystick_input_state(JoystickPort::Left, JoystickInput::Left, true);
+        for _ in 0..4 {
+            atari.rotate_driving_controllers();
+        }
+        assert_eq!(read_left_port(&atari), 0b1111);
+
+        // Up and down don't do anything, and fire works just like with a
+        // joystick.
+        atari.set_joystick_input_state(JoystickPort::Left, JoystickInput::Up, true);
+        atari.set_joystick_input_state(JoystickPort::Left, JoystickInput::Fire, true);
+        assert_eq!(read_left_port(&atari), 0b1111);
+        assert_eq!(atari.cpu.memory().inspect(0x000C).unwrap(), 0);
+    }
+
+    #[test]
+    fn turning_driving_controllers() {
+        let mut atari = AtariBuilder::new(Ram::new(12))
+            .with_controller(JoystickPort::Right, ControllerType::Driving)
+            .build();
+        let read_right_port = |atari: &Atari| atari.cpu.memory().inspect(0x0280).unwrap() & 0xF;
+
+        atari.turn_driving_controller(JoystickPort::Right, 1);
+        assert_eq!(read_right_port(&atari), 0b1101);
+        atari.turn_driving_controller(JoystickPort::Right, 2);
+        assert_eq!(read_right_port(&atari), 0b1110);
+        atari.turn_driving_controller(JoystickPort::Right, -4);
+        assert_eq!(read_right_port(&atari), 0b1110);
+        atari.turn_driving_controller(JoystickPort::Right, -3);
+        assert_eq!(read_right_port(&atari), 0b1111);
+
+        // Turning is ignored on ports without a driving controller.
+        atari.turn_driving_controller(JoystickPort::Left, 1);
+        assert_eq!(atari.cpu.memory().inspect(0x0280).unwrap(), 0b1111_1111);
+    }
+
+    #[test]
+    fn swapping_controllers() {
+        let mut atari = AtariBuilder::new(Ram::new(12)).build();
+        assert_eq!(
+            atari.controller(JoystickPort::Left),
+            ControllerType::Joystick
+        );
+        atari.set_joystick_input_state(JoystickPort::Left, JoystickInput::Up, true);
+        assert_eq!(atari.cpu.memory().inspect(0x0280).unwrap(), 0b1110_1111);
+
+        // Swapping controllers releases the joystick.
+        atari.connect_controller(JoystickPort::Left, ControllerType::Paddles);
+        assert_eq!(
+            atari.controller(JoystickPort::Left),
+            ControllerType::Paddles
+        );
+        assert_eq!(atari.paddle_position(Paddle::Paddle1), Some(0x80));
+        assert_eq!(atari.paddle_position(Paddle::Paddle2), None);
+        assert_eq!(atari.cpu.memory().inspect(0x0280).unwrap(), 0b1111_1111);
+        atari.set_paddle_button_state(Paddle::Paddle1, true);
+        atari.set_paddle_button_state(Paddle::Paddle2, true);
+        assert_eq!(atari.cpu.memory().inspect(0x0280).unwrap(), 0b1011_1111);
+
+        atari.connect_controller(JoystickPort::Left, ControllerType::Joystick);
+        assert_eq!(atari.paddle_position(Paddle::Paddle1), None);
+        assert_eq!(atari.cpu.memory().inspect(0x0280).unwrap(), 0b1111_1111);
+    }
+
+    #[test]
+    fn paddles() {
+        let mut atari = atari_with_rom("io_monitor.bin");
+        assert_eq!(atari.paddle_position(Paddle::Paddle0), None);
+        atari.set_paddle_position(Paddle::Paddle0, 0x12);
+        assert_eq!(atari.paddle_position(Paddle::Paddle0), None);
+
+        atari.connect_paddles();
+        assert_eq!(atari.paddle_position(Paddle::Paddle0), Some(0x80));
+        atari.set_paddle_position(Paddle::Paddle0, 0x12);
+        atari.set_paddle_position(Paddle::Paddle2, 0xFF);
+        assert_eq!(atari.paddle_position(Paddle::Paddle0), Some(0x12));
+        assert_eq!(atari.paddle_position(Paddle::Paddle2), Some(0xFF));
+
+        atari.set_paddle_button_state(Paddle::Paddle0, true);
+        atari.set_paddle_button_state(Paddle::Paddle3, true);
+        assert_eq!(atari.inspect_memory(0x0280), 0b0111_1011);
+        atari.set_paddle_button_state(Paddle::Paddle0, false);
+        assert_eq!(atari.inspect_memory(0x0280), 0b1111_1011);
+    }
+
+    #[test]
+    fn register_names() {
+        let atari = atari_with_rom("io_monitor.bin");
+        let names = atari.register_names();
+        assert_eq!(names.write_name(0x0009), Some("COLUBK"));
+        assert_eq!(names.read_name(0x0009), Some("INPT1"));
+        assert_eq!(names.read_name(0x0280), Some("SWCHA"));
+        assert_eq!(names.write_name(0x0296), Some("TIM64T"));
+        assert_eq!(names.read_name(0x0080), None);
+
+        // Mirrors
+        assert_eq!(names.write_name(0x0049), Some("COLUBK"));
+        assert_eq!(names.read_name(0x003C), Some("INPT4"));
+        assert_eq!(names.read_name(0x0388), Some("SWCHA"));
+        assert_eq!(names.write_name(0x029E), Some("TIM64T"));
+    }
+
     #[test]
     fn joystick_single_buttons() {
         let mut joystick = Joystick::new();
@@ -508,18 +1320,63 @@ mod tests {
         assert!(!atari.at_instruction_start());
     }
 
+    #[test]
+    fn renders_tia_at_most_one_cpu_cycle_ahead() {
+        // The test program uses WSYNC, which mustn't make the TIA run ahead.
+        let mut atari = atari_with_rom("horizontal_stripes.bin");
+        for _ in 0..2 {
+            while let FrameStatus::Pending = atari.tick().unwrap() {
+                assert!(atari.tia_outputs.len() <= 3);
+            }
+        }
+    }
+
+    #[test]
+    fn reset_keeps_pending_tia_outputs() {
+        let mut atari = atari_with_rom("horizontal_stripes.bin");
+        while !atari.at_cpu_cycle() {
+            atari.tick().unwrap();
+        }
+        atari.tick().unwrap();
+        let pending = atari.tia_outputs[atari.next_tia_output..].to_vec();
+        assert_eq!(pending.len(), 2);
+        atari.reset();
+        assert_eq!(atari.tia_outputs[atari.next_tia_output..], pending);
+    }
+
+    #[test]
+    fn builds_configured_console() {
+        let rom = read_test_rom("horizontal_stripes.bin");
+        let mut atari = AtariBuilder::new(Cartridge::with_detected_bank_switching(&rom).unwrap())
+            .with_power_on_state(PowerOnState::Seeded(0))
+            .with_frame_blending(true)
+            .with_paddles(true)
+            .build();
+        assert!(atari.frame_blending());
+        assert_eq!(atari.paddle_position(Paddle::Paddle3), Some(0x80));
+
+        atari.reset();
+        next_frame(&mut atari).unwrap();
+    }
+
     #[bench]
     fn benchmark(b: &mut Bencher) {
         let rom = read_test_rom("horizontal_stripes.bin");
         b.iter(|| {
-            let address_space = Box::new(AtariAddressSpace::new(Rom::new(&rom).unwrap()));
-            let (consumer, _) = create_consumer_and_source();
+            let address_space = Box::new(AtariAddressSpace::new(
+                Cartridge::with_detected_bank_switching(&rom).unwrap(),
+                RamPattern::default(),
+                PowerOnState::Seeded(0),
+            ));
+            let (consumer, _) =
+                create_consumer_and_source(AUDIO_SAMPLE_RATE, Duration::from_millis(50));
             let mut atari = Atari::new(
                 address_space,
                 FrameRendererBuilder::new()
                     .with_palette(colors::ntsc_palette())
                     .build(),
                 consumer,
+                PowerOnState::Seeded(0),
             );
 
             atari.reset();
@@ -0,0 +1,69 @@
+use atari2600::address_space::BankSwitching;
+use atari2600::address_space::Cartridge;
+use atari2600::atari::{AtariBuilder, AUDIO_SAMPLE_RATE};
+use atari2600::tv_standard::TvStandard;
+use clap::Parser;
+use common::audio;
+use common::player::AudioPlayer;
+use std::time::Duration;
+
+/// Plays the music from an Atari 2600 ROM without opening a window.
+#[derive(Parser)]
+struct Args {
+    /// Bank switching scheme of the cartridge. If not specified, it will be
+    /// detected automatically.
+    #[clap(long, arg_enum)]
+    bank_switching: Option<BankSwitching>,
+
+    /// Enables the 128 bytes of SuperChip RAM in F8, F6, and F4 cartridges.
+    /// Use it if the automatic detection fails.
+    #[clap(long)]
+    super_chip: bool,
+
+    /// TV standard of the console. Music engines usually update the sound
+    /// once per frame, so it affects the tempo.
+    #[clap(long, arg_enum, default_value = "ntsc")]
+    tv_standard: TvStandard,
+
+    /// Audio latency, in milliseconds.
+    #[clap(long, default_value = "50")]
+    audio_latency: u64,
+
+    /// Stops playing after a given number of seconds. If not specified, plays
+    /// until interrupted.
+    #[clap(long)]
+    seconds: Option<f64>,
+
+    cartridge_file: String,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let rom_bytes = std::fs::read(args.cartridge_file).expect("Unable to read the ROM image file");
+    let cartridge = match args.bank_switching {
+        Some(bank_switching) => Cartridge::new(&rom_bytes, bank_switching),
+        None => Cartridge::with_detected_bank_switching(&rom_bytes),
+    }
+    .map(|cartridge| {
+        if args.super_chip {
+            cartridge.with_super_chip(true)
+        } else {
+            cartridge
+        }
+    });
+    let (audio_consumer, _stream, _sink) =
+        audio::initialize(AUDIO_SAMPLE_RATE, Duration::from_millis(args.audio_latency));
+    let monitor = audio_consumer.monitor();
+    let mut atari = AtariBuilder::new(cartridge.expect("Unable to load the ROM into Atari"))
+        .with_tv_standard(args.tv_standard)
+        .with_audio_consumer(audio_consumer)
+        .build();
+
+    let mut player = AudioPlayer::new(&mut atari, monitor);
+    signal_hook::flag::register(signal_hook::consts::SIGINT, player.interrupted())
+        .expect("Unable to set interrupt signal handler");
+    if let Err(e) = player.run(args.seconds.map(Duration::from_secs_f64)) {
+        eprintln!("ERROR: {}. Machine halted.", e);
+    }
+}
@@ -1,20 +1,102 @@
 use crate::colors::Palette;
 use crate::tia;
 use crate::tia::VideoOutput;
+use common::app::RasterPosition;
+use common::colors::put_rgba;
+use common::colors::PaletteLookup;
 use image::{Pixel, Rgba, RgbaImage};
+use std::sync::mpsc;
+use std::thread;
+
+/// Maximum number of scanlines per frame that is taken into account while
+/// detecting the viewport height. It's the PAL frame length; anything longer
+/// than that is most likely garbage produced by a ROM that doesn't emit VSYNC
+/// properly (yet).
+const MAX_SCANLINES: i32 = 312;
+
+/// Number of consecutive frames that need to have the same detected viewport
+/// before the viewport is actually resized. This way, the viewport doesn't
+/// jump around when the program skips a VSYNC once in a while.
+const HEIGHT_LOCK_FRAMES: u32 = 3;
+
+/// Number of scanlines that are sent to the frame conversion thread at once.
+/// Sending every scanline separately would make the emulation spend more time
+/// on synchronization than it saves on conversion.
+const SCANLINES_PER_BATCH: u32 = 16;
+
+/// Limits the length of a frame, much like the vertical hold circuit of a TV
+/// set does. VSYNC signals that come before `min_scanlines` lines since the
+/// previous one are ignored, and if there's no VSYNC for `max_scanlines`
+/// lines, a new frame starts anyway. Without vertical hold, the renderer
+/// follows the VSYNC signal exactly, whatever it looks like.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VerticalHold {
+    pub min_scanlines: u32,
+    pub max_scanlines: u32,
+}
+
+/// Viewport size detected in a frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Viewport {
+    /// Number of scanlines of a single field.
+    height: u32,
+    /// If `true`, two consecutive fields are woven together, so the viewport
+    /// is twice as tall.
+    interlaced: bool,
+}
+
+impl Viewport {
+    fn rows(&self) -> u32 {
+        return if self.interlaced {
+            self.height * 2
+        } else {
+            self.height
+        };
+    }
+}
 
 /// This structure simulates a TV display. It consumes
 /// [`VideoOutput`](../tia/struct.VideoOutput.html) structures and renders them
 /// on an image surface. Use
 /// [`FrameRendererBuilder`](struct.FrameRendererBuilder.html) to create an
 /// instance of this class.
+///
+/// If the program produces an interlaced picture, i.e. every other VSYNC
+/// starts in the middle of a scanline, the renderer weaves the odd and even
+/// fields together into a single, twice as tall, image.
 pub struct FrameRenderer {
     // *** CONFIGURATION ***
-    palette: Palette,
+    palette: PaletteLookup,
     first_visible_scanline_index: i32,
+    /// If `true`, the viewport height is adjusted to the number of scanlines
+    /// that actually contain pixels. The height never gets smaller than
+    /// `min_height`.
+    auto_height: bool,
+    min_height: u32,
+    vertical_hold: Option<VerticalHold>,
+    /// If `true`, each displayed frame is an average of the two last ones.
+    frame_blending: bool,
+    /// Converts the pixels to RGBA on a separate thread, or `None` if they are
+    /// converted right away.
+    converter: Option<FrameConverter>,
 
     // *** INTERNAL STATE ***
+    /// The frame being rendered. With a converter, it's never drawn on, and
+    /// it only keeps the viewport size.
     frame: RgbaImage,
+    /// A copy of the previous complete frame, used for frame blending.
+    previous_frame: Option<RgbaImage>,
+    /// The last complete frame blended with the previous one, or `None` if
+    /// frame blending is disabled.
+    blended_frame: Option<RgbaImage>,
+    /// Palette indices of the pixels rendered with a converter. `None` means
+    /// that a pixel keeps its previous color.
+    indexed_frame: Vec<Option<u8>>,
+    /// Number of viewport rows of the current frame already sent to the
+    /// converter.
+    rows_sent: u32,
+    /// The last complete frame received from the converter.
+    converted_frame: Option<RgbaImage>,
 
     /// The X coordinate (column) of the next pixel to be processed. 0 is the
     /// beginning of the "front porch" signal (before the HSYNC part). Visible
@@ -28,7 +110,28 @@ pub struct FrameRenderer {
 
     in_hsync: bool,
     in_vsync: bool,
+    /// Set if the current VSYNC signal has been ignored by the vertical hold.
+    vsync_ignored: bool,
     had_first_vsync: bool,
+    /// Set if the current frame has been started without a VSYNC signal.
+    free_running: bool,
+    /// Set if the current field has been started by a VSYNC signal in the
+    /// middle of a scanline, which puts its scanlines half a line lower.
+    odd_field: bool,
+    /// Set if the viewport shows woven odd and even fields. Each scanline
+    /// then occupies every other row of the viewport.
+    interlaced: bool,
+    /// Set when a frame has been completed, but not yet taken by the caller.
+    frame_handed_over: bool,
+
+    /// Number of scanlines between the two last VSYNC signals, not counting
+    /// the VSYNC itself.
+    scanlines_between_vsyncs: Option<u32>,
+    /// The last scanline of the current frame that contained any pixels.
+    last_pixel_scanline: Option<i32>,
+    /// Viewport detected in the last complete frames, along with the number
+    /// of consecutive frames it has been detected in.
+    detected_viewport: Option<(Viewport, u32)>,
 }
 
 impl FrameRenderer {
@@ -36,60 +139,277 @@ impl FrameRenderer {
     /// Returns `true` if this particular cycle marks the frame as ready to be
     /// rendered on screen.
     pub fn consume(&mut self, video_output: VideoOutput) -> bool {
+        if self.frame_handed_over {
+            // The previous frame has already been taken by the caller, so it's
+            // now safe to resize the viewport.
+            self.frame_handed_over = false;
+            self.apply_detected_viewport();
+        }
+
         // Handle the VSYNC signal by resetting the CRT beam to point at the top
         // of the screen. If it's not the first time, we return `true` to mark
-        // the completion of a single frame.
+        // the completion of a single frame. With vertical hold enabled, a VSYNC
+        // that comes too early is ignored, and the beam keeps going.
         if video_output.vsync {
             if !self.in_vsync {
-                // This quirk is one reason why `self.y` is a signed number.
-                // Because the "first visible scanline index" is counted
-                // starting from the first line AFTER the VSYNC signal (which is
-                // counted as scan line 0), we set the scanline counter to -1
-                // here.
-                self.y = -1;
                 self.in_vsync = true;
-                if !self.had_first_vsync {
-                    self.had_first_vsync = true;
-                    return false;
+                self.vsync_ignored = !self.locks_to_vsync();
+                if !self.vsync_ignored {
+                    // This quirk is one reason why `self.y` is a signed number.
+                    // Because the "first visible scanline index" is counted
+                    // starting from the first line AFTER the VSYNC signal
+                    // (which is counted as scan line 0), we set the scanline
+                    // counter to -1 here.
+                    self.free_running = false;
+                    // A VSYNC that starts around the middle of a scanline
+                    // begins an odd field of an interlaced picture.
+                    let width = tia::TOTAL_WIDTH as i32;
+                    let column = self.x.rem_euclid(width);
+                    let odd_field = column >= width / 4 && column < width * 3 / 4;
+                    return self.complete_frame(self.y + 1, -1, odd_field);
                 }
-                return true;
             }
-            return false;
+            if !self.vsync_ignored {
+                return false;
+            }
+        } else {
+            self.in_vsync = false;
         }
-        self.in_vsync = false;
 
         // Handle the HSYNC signal. If encountered, move to the next scanline.
         // Because HSYNC lasts for a couple of cycles, we use `self.in_hsync` to
         // make sure that we move vertically only once per given HSYNC signal.
         if video_output.hsync {
             if !self.in_hsync {
+                self.in_hsync = true;
                 self.y += 1;
                 self.x = tia::HSYNC_END as i32;
+                let finished_rows = self.viewport_row(self.y).max(0) as u32;
+                if finished_rows >= self.rows_sent + SCANLINES_PER_BATCH {
+                    self.send_rows(finished_rows);
+                }
+                if let Some(hold) = &self.vertical_hold {
+                    if self.y >= hold.max_scanlines as i32 {
+                        // No VSYNC in sight. Just like a TV set, we start a
+                        // new frame anyway; this is what makes the picture
+                        // roll.
+                        self.free_running = true;
+                        return self.complete_frame(self.y, 0, false);
+                    }
+                }
             }
-            self.in_hsync = true;
             return false;
         }
         self.in_hsync = false;
 
         // Actually handle pixel data.
         if let Some(pixel) = video_output.pixel {
-            let color = self.palette[pixel as usize];
+            self.last_pixel_scanline = self.last_pixel_scanline.max(Some(self.y));
             // Calculate coordinates in the viewport space.
             let x = self.x - tia::HBLANK_WIDTH as i32;
-            let y = self.y - self.first_visible_scanline_index;
+            let y = self.viewport_row(self.y);
             let x_within_viewport = x >= 0 && x < self.frame.width() as i32;
             let y_within_viewport = y >= 0 && y < self.frame.height() as i32;
             if x_within_viewport && y_within_viewport {
-                self.frame.put_pixel(x as u32, y as u32, color);
+                let width = self.frame.width() as usize;
+                if self.converter.is_some() {
+                    self.indexed_frame[y as usize * width + x as usize] = Some(pixel);
+                } else {
+                    put_rgba(
+                        &mut self.frame,
+                        width,
+                        x as usize,
+                        y as usize,
+                        self.palette.rgba(pixel),
+                    );
+                }
             }
         }
         self.x += 1;
         return false;
     }
 
-    /// Returns a reference to the underlying frame image.
+    /// Decides whether a VSYNC signal that has just started should start a new
+    /// frame.
+    fn locks_to_vsync(&self) -> bool {
+        match &self.vertical_hold {
+            None => true,
+            Some(hold) => {
+                // After the frame has been started without a VSYNC, the
+                // picture is out of sync anyway, so we re-lock to whatever
+                // comes first.
+                !self.had_first_vsync
+                    || self.free_running
+                    || self.y + 1 >= hold.min_scanlines as i32
+            }
+        }
+    }
+
+    /// Returns the viewport row that displays a given scanline of the current
+    /// field. The row may lie outside the viewport.
+    fn viewport_row(&self, scanline: i32) -> i32 {
+        let row = scanline - self.first_visible_scanline_index;
+        return if self.interlaced {
+            2 * row + self.odd_field as i32
+        } else {
+            row
+        };
+    }
+
+    /// Moves the beam to a given scanline of the next frame, which is either
+    /// an odd or even field. Returns `true` if the frame that has just ended
+    /// should be displayed, i.e. if it's not the first one.
+    fn complete_frame(&mut self, scanlines: i32, next_y: i32, next_odd_field: bool) -> bool {
+        self.y = next_y;
+        let fields_alternate = next_odd_field != self.odd_field;
+        self.odd_field = next_odd_field;
+        if !self.had_first_vsync {
+            self.had_first_vsync = true;
+            self.last_pixel_scanline = None;
+            self.rows_sent = 0;
+            return false;
+        }
+        self.scanlines_between_vsyncs = Some(scanlines.max(0) as u32);
+        self.detect_viewport(fields_alternate);
+        if self.converter.is_some() {
+            self.receive_converted_frame();
+        } else if self.frame_blending {
+            self.blend_frames();
+        }
+        self.frame_handed_over = true;
+        return true;
+    }
+
+    /// Sends the viewport rows that haven't been sent yet, up to (but not
+    /// including) a given one, to the converter.
+    fn send_rows(&mut self, end_row: u32) {
+        let end_row = end_row.min(self.frame.height());
+        if let Some(converter) = &self.converter {
+            if end_row > self.rows_sent {
+                let width = self.frame.width() as usize;
+                converter.send(ConverterMessage::Rows {
+                    first_row: self.rows_sent,
+                    pixels: self.indexed_frame
+                        [self.rows_sent as usize * width..end_row as usize * width]
+                        .to_vec(),
+                });
+                self.rows_sent = end_row;
+            }
+        }
+    }
+
+    /// Sends the rest of the frame to the converter and waits until it's
+    /// converted. Most of the frame has already been converted while the
+    /// remaining scanlines were being emulated, so it doesn't take long.
+    fn receive_converted_frame(&mut self) {
+        self.send_rows(self.frame.height());
+        self.rows_sent = 0;
+        if let Some(converter) = &self.converter {
+            converter.send(ConverterMessage::CompleteFrame {
+                frame_blending: self.frame_blending,
+                buffer: self.converted_frame.take(),
+            });
+            self.converted_frame = Some(converter.receive());
+        }
+    }
+
+    /// Returns a reference to the underlying frame image. If frame blending is
+    /// enabled, it's the last complete frame averaged with the previous one.
+    /// If the pixels are converted on a separate thread (see
+    /// [`FrameRendererBuilder::with_threaded_conversion`]), it's always the
+    /// last complete frame, even if the next one is being rendered.
     pub fn frame_image(&self) -> &RgbaImage {
-        &self.frame
+        self.converted_frame
+            .as_ref()
+            .or(self.blended_frame.as_ref())
+            .unwrap_or(&self.frame)
+    }
+
+    pub fn frame_blending(&self) -> bool {
+        self.frame_blending
+    }
+
+    /// Enables or disables frame blending. Many games display different
+    /// objects on alternating frames; on a real TV set, this looks like a
+    /// slight flicker, but on a computer screen it's usually much more
+    /// disturbing. Averaging consecutive frames makes such objects look
+    /// semi-transparent instead.
+    pub fn set_frame_blending(&mut self, frame_blending: bool) {
+        self.frame_blending = frame_blending;
+        if !frame_blending {
+            self.previous_frame = None;
+            self.blended_frame = None;
+        }
+    }
+
+    /// Averages the frame that has just been completed with the previous one.
+    fn blend_frames(&mut self) {
+        let mut blended_frame = self.frame.clone();
+        if let Some(previous) = &self.previous_frame {
+            blend(&mut blended_frame, previous);
+        }
+        self.previous_frame = Some(self.frame.clone());
+        self.blended_frame = Some(blended_frame);
+    }
+
+    /// Returns the number of scanlines between the two last VSYNC signals, not
+    /// counting the VSYNC itself, or `None` if there weren't two VSYNC signals
+    /// yet. If a frame has been started without a VSYNC because of the
+    /// [`VerticalHold`], it's the number of scanlines in that frame.
+    pub fn scanlines_between_vsyncs(&self) -> Option<u32> {
+        self.scanlines_between_vsyncs
+    }
+
+    /// Computes the viewport required to display all the pixels of the frame
+    /// that has just been completed, and counts how many frames in a row
+    /// required the same viewport. `fields_alternate` tells whether the frame
+    /// and the next one are fields of different parity.
+    fn detect_viewport(&mut self, fields_alternate: bool) {
+        let last_pixel_scanline = self.last_pixel_scanline.take();
+        let height = if self.auto_height {
+            let max_height = (MAX_SCANLINES - self.first_visible_scanline_index).max(0) as u32;
+            match last_pixel_scanline {
+                Some(scanline) => (scanline - self.first_visible_scanline_index + 1).max(0) as u32,
+                None => 0,
+            }
+            .clamp(self.min_height.min(max_height), max_height)
+        } else {
+            self.min_height
+        };
+        let viewport = Viewport {
+            height,
+            interlaced: fields_alternate,
+        };
+        self.detected_viewport = match self.detected_viewport {
+            Some((detected, frames)) if detected == viewport => Some((viewport, frames + 1)),
+            _ => Some((viewport, 1)),
+        };
+    }
+
+    /// Resizes the viewport if the same viewport has been detected in enough
+    /// consecutive frames.
+    fn apply_detected_viewport(&mut self) {
+        if let Some((viewport, frames)) = self.detected_viewport {
+            let rows = viewport.rows();
+            let changed = rows != self.frame.height() || viewport.interlaced != self.interlaced;
+            if frames >= HEIGHT_LOCK_FRAMES && changed {
+                self.interlaced = viewport.interlaced;
+                self.frame = blank_frame(rows);
+                if let Some(converter) = &self.converter {
+                    self.indexed_frame = vec![None; self.frame.as_raw().len() / 4];
+                    converter.send(ConverterMessage::Resize(rows));
+                }
+            }
+        }
+    }
+
+    /// Returns the current beam position. Scanlines are counted from the end
+    /// of VSYNC, and the horizontal position is expressed in color clocks.
+    pub fn raster_position(&self) -> RasterPosition {
+        RasterPosition {
+            line: self.y.max(0) as u32,
+            x: self.x.max(0) as u32,
+        }
     }
 }
 
@@ -98,13 +418,16 @@ impl FrameRenderer {
 /// # Examples
 /// ## Creating a `FrameRenderer` with default settings
 /// ```
+/// # use atari2600::frame_renderer::FrameRendererBuilder;
 /// let mut frame_renderer = FrameRendererBuilder::new().build();
 /// ```
 ///
 /// ## Creating a more customized version
 /// ```
+/// # use atari2600::colors;
+/// # use atari2600::frame_renderer::FrameRendererBuilder;
 /// let mut frame_renderer = FrameRendererBuilder::new()
-///     .with_palette(SECAM_palette)
+///     .with_palette(colors::secam_palette())
 ///     .with_height(1)
 ///     .with_first_visible_scanline_index(0)
 ///     .build();
@@ -113,6 +436,10 @@ pub struct FrameRendererBuilder {
     height: u32,
     palette: Palette,
     first_visible_scanline_index: i32,
+    auto_height: bool,
+    vertical_hold: Option<VerticalHold>,
+    frame_blending: bool,
+    threaded_conversion: bool,
 }
 
 impl FrameRendererBuilder {
@@ -122,6 +449,10 @@ impl FrameRendererBuilder {
             height: 192,
             palette: Palette::new(),
             first_visible_scanline_index: 37,
+            auto_height: false,
+            vertical_hold: None,
+            frame_blending: false,
+            threaded_conversion: false,
         }
     }
 
@@ -137,9 +468,39 @@ impl FrameRendererBuilder {
         return self;
     }
 
+    /// Enables or disables adjusting the viewport height at runtime, so that
+    /// programs that display more scanlines than usual are shown in full. If
+    /// enabled, the height set with [`Self::with_height`] is the minimum one.
+    pub fn with_auto_height(mut self, auto_height: bool) -> Self {
+        self.auto_height = auto_height;
+        return self;
+    }
+
+    /// Enables or disables the vertical hold. See [`VerticalHold`] for
+    /// details.
+    pub fn with_vertical_hold(mut self, vertical_hold: Option<VerticalHold>) -> Self {
+        self.vertical_hold = vertical_hold;
+        return self;
+    }
+
+    /// Enables or disables frame blending. See
+    /// [`FrameRenderer::set_frame_blending`] for details.
+    pub fn with_frame_blending(mut self, frame_blending: bool) -> Self {
+        self.frame_blending = frame_blending;
+        return self;
+    }
+
+    /// Enables or disables converting the pixels to RGBA and blending the
+    /// frames on a separate thread, while the next scanlines are being
+    /// rendered. In this mode, [`FrameRenderer::frame_image`] only returns
+    /// complete frames.
+    pub fn with_threaded_conversion(mut self, threaded_conversion: bool) -> Self {
+        self.threaded_conversion = threaded_conversion;
+        return self;
+    }
+
     /// Sets which scanline will be the first one visible in the viewport. 0
     /// means the scanline that occurs immediately after VSYNC signal ends.
-    #[cfg(test)]
     pub fn with_first_visible_scanline_index(mut self, index: i32) -> Self {
         self.first_visible_scanline_index = index;
         return self;
@@ -147,20 +508,171 @@ impl FrameRendererBuilder {
 
     /// Creates the `FrameRenderer`. The builder can later be reused.
     pub fn build(&self) -> FrameRenderer {
+        let palette = PaletteLookup::new(&self.palette);
+        let frame = blank_frame(self.height);
         FrameRenderer {
-            palette: self.palette.clone(),
-            frame: RgbaImage::from_pixel(
-                tia::FRAME_WIDTH,
-                self.height,
-                Rgba::from_channels(0x00, 0x00, 0x00, 0xFF),
-            ),
+            converter: self
+                .threaded_conversion
+                .then(|| FrameConverter::new(palette.clone(), self.height)),
+            indexed_frame: if self.threaded_conversion {
+                vec![None; frame.as_raw().len() / 4]
+            } else {
+                vec![]
+            },
+            rows_sent: 0,
+            converted_frame: None,
+            palette,
+            frame,
             first_visible_scanline_index: self.first_visible_scanline_index,
+            auto_height: self.auto_height,
+            min_height: self.height,
+            vertical_hold: self.vertical_hold,
+            frame_blending: self.frame_blending,
 
+            previous_frame: None,
+            blended_frame: None,
             x: 0,
             y: self.first_visible_scanline_index + self.height as i32,
             in_hsync: false,
             in_vsync: false,
+            vsync_ignored: false,
             had_first_vsync: false,
+            free_running: false,
+            odd_field: false,
+            interlaced: false,
+            frame_handed_over: false,
+
+            scanlines_between_vsyncs: None,
+            last_pixel_scanline: None,
+            detected_viewport: None,
+        }
+    }
+}
+
+fn blank_frame(height: u32) -> RgbaImage {
+    RgbaImage::from_pixel(
+        tia::FRAME_WIDTH,
+        height,
+        Rgba::from_channels(0x00, 0x00, 0x00, 0xFF),
+    )
+}
+
+impl Default for FrameRendererBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Averages each pixel of an image with the corresponding one of the previous
+/// image, unless their sizes differ.
+fn blend(image: &mut RgbaImage, previous: &RgbaImage) {
+    if previous.dimensions() != image.dimensions() {
+        return;
+    }
+    for (pixel, previous_pixel) in image.pixels_mut().zip(previous.pixels()) {
+        pixel.apply2(previous_pixel, |a, b| ((a as u16 + b as u16 + 1) / 2) as u8);
+    }
+}
+
+enum ConverterMessage {
+    /// Palette indices of consecutive viewport rows, starting from a given
+    /// one.
+    Rows {
+        first_row: u32,
+        pixels: Vec<Option<u8>>,
+    },
+    /// Changes the viewport height and clears the frame.
+    Resize(u32),
+    /// Marks the frame as complete and asks for its image. The buffer of the
+    /// previous image is returned to be reused.
+    CompleteFrame {
+        frame_blending: bool,
+        buffer: Option<RgbaImage>,
+    },
+}
+
+/// A thread that converts palette indices to RGBA images. Together with the
+/// frame being drawn and the one being displayed, the converter and the
+/// renderer exchange frame buffers instead of allocating new ones.
+struct FrameConverter {
+    sender: Option<mpsc::Sender<ConverterMessage>>,
+    receiver: mpsc::Receiver<RgbaImage>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl FrameConverter {
+    fn new(palette: PaletteLookup, height: u32) -> Self {
+        let (sender, messages) = mpsc::channel();
+        let (image_sender, receiver) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut frame = blank_frame(height);
+            let mut previous_frame: Option<RgbaImage> = None;
+            for message in messages {
+                match message {
+                    ConverterMessage::Rows { first_row, pixels } => {
+                        let width = frame.width() as usize;
+                        for (i, pixel) in pixels.into_iter().enumerate() {
+                            if let Some(color) = pixel {
+                                let (x, y) = (i % width, first_row as usize + i / width);
+                                put_rgba(&mut frame, width, x, y, palette.rgba(color));
+                            }
+                        }
+                    }
+                    ConverterMessage::Resize(height) => frame = blank_frame(height),
+                    ConverterMessage::CompleteFrame {
+                        frame_blending,
+                        buffer,
+                    } => {
+                        let mut image = match buffer {
+                            Some(mut buffer) if buffer.dimensions() == frame.dimensions() => {
+                                buffer.copy_from_slice(&frame);
+                                buffer
+                            }
+                            _ => frame.clone(),
+                        };
+                        if frame_blending {
+                            if let Some(previous) = &previous_frame {
+                                blend(&mut image, previous);
+                            }
+                            previous_frame = Some(frame.clone());
+                        } else {
+                            previous_frame = None;
+                        }
+                        if image_sender.send(image).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+        Self {
+            sender: Some(sender),
+            receiver,
+            thread: Some(thread),
+        }
+    }
+
+    fn send(&self, message: ConverterMessage) {
+        if let Some(sender) = &self.sender {
+            sender
+                .send(message)
+                .expect("The frame converter thread has stopped");
+        }
+    }
+
+    fn receive(&self) -> RgbaImage {
+        self.receiver
+            .recv()
+            .expect("The frame converter thread has stopped")
+    }
+}
+
+impl Drop for FrameConverter {
+    fn drop(&mut self) {
+        // Closing the channel makes the thread finish.
+        drop(self.sender.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
         }
     }
 }
@@ -170,8 +682,13 @@ mod tests {
     use super::*;
     use crate::colors;
     use crate::test_utils;
+    use crate::tia::flags;
+    use crate::tia::registers;
+    use crate::tia::Tia;
+    use crate::tv_standard::TvStandard;
     use image::Pixel;
     use std::iter;
+    use ya6502::memory::Write;
 
     /// Returns a simple, 3-color palette that is nowhere near the actual palette
     /// of Atari, but is very convenient for testing.
@@ -436,6 +953,259 @@ mod tests {
         );
     }
 
+    /// Returns an encoded frame that starts with a VSYNC line, followed by a
+    /// given number of lines filled with pixels.
+    fn encoded_frame(lines: usize) -> String {
+        let vsync = "----------------++++++++++++++++------------------------------------"
+            .to_string()
+            + &"-".repeat(tia::FRAME_WIDTH as usize);
+        let line = "................||||||||||||||||...................................."
+            .to_string()
+            + &"2".repeat(tia::FRAME_WIDTH as usize);
+        return vsync + &line.repeat(lines);
+    }
+
+    #[test]
+    fn detects_frame_height() {
+        let mut fr = FrameRendererBuilder::new()
+            .with_palette(simple_palette())
+            .with_height(2)
+            .with_first_visible_scanline_index(0)
+            .with_auto_height(true)
+            .build();
+        assert_eq!(fr.scanlines_between_vsyncs(), None);
+
+        // The height should only change after it's been stable for a while.
+        for _ in 0..3 {
+            decode_and_consume(&mut fr, &encoded_frame(4));
+        }
+        assert_eq!(fr.scanlines_between_vsyncs(), Some(4));
+        assert_eq!(fr.frame_image().height(), 2);
+        decode_and_consume(&mut fr, &encoded_frame(4));
+        assert_eq!(fr.frame_image().height(), 4);
+        itertools::assert_equal(
+            fr.frame_image().pixels().cloned(),
+            iter::repeat(Rgba::from_channels(0x22, 0xFF, 0x22, 0xFF))
+                .take(4 * tia::FRAME_WIDTH as usize),
+        );
+
+        // A single longer frame shouldn't affect the height.
+        decode_and_consume(&mut fr, &encoded_frame(6));
+        for _ in 0..3 {
+            decode_and_consume(&mut fr, &encoded_frame(4));
+        }
+        assert_eq!(fr.frame_image().height(), 4);
+
+        // The height never drops below the configured one.
+        for _ in 0..4 {
+            decode_and_consume(&mut fr, &encoded_frame(1));
+        }
+        assert_eq!(fr.scanlines_between_vsyncs(), Some(1));
+        assert_eq!(fr.frame_image().height(), 2);
+    }
+
+    #[test]
+    fn keeps_fixed_height_by_default() {
+        let mut fr = FrameRendererBuilder::new()
+            .with_palette(simple_palette())
+            .with_height(2)
+            .with_first_visible_scanline_index(0)
+            .build();
+        for _ in 0..5 {
+            decode_and_consume(&mut fr, &encoded_frame(4));
+        }
+        assert_eq!(fr.scanlines_between_vsyncs(), Some(4));
+        assert_eq!(fr.frame_image().height(), 2);
+    }
+
+    /// Feeds an encoded signal to a given `FrameRenderer` and returns the
+    /// number of frames that it completed.
+    fn count_frames(renderer: &mut FrameRenderer, encoded_signal: &str) -> usize {
+        test_utils::decode_video_outputs(encoded_signal)
+            .filter(|output| renderer.consume(output.clone()))
+            .count()
+    }
+
+    /// Returns an encoded line filled with a given pixel color.
+    fn encoded_line(color: char) -> String {
+        "................||||||||||||||||....................................".to_string()
+            + &color.to_string().repeat(tia::FRAME_WIDTH as usize)
+    }
+
+    #[test]
+    fn blends_frames() {
+        let mut fr = FrameRendererBuilder::new()
+            .with_palette(simple_palette())
+            .with_height(1)
+            .with_first_visible_scanline_index(0)
+            .with_frame_blending(true)
+            .build();
+        let vsync_line = encoded_frame(0);
+        let green = Rgba::from_channels(0x22, 0xFF, 0x22, 0xFF);
+        let red = Rgba::from_channels(0xFF, 0x11, 0x11, 0xFF);
+        let blue = Rgba::from_channels(0x33, 0x33, 0xFF, 0xFF);
+
+        // There's nothing to blend the first frame with.
+        decode_and_consume(&mut fr, &(vsync_line.clone() + &encoded_line('2')));
+        decode_and_consume(&mut fr, &vsync_line);
+        assert_eq!(fr.frame_image().get_pixel(0, 0), &green);
+
+        decode_and_consume(&mut fr, &(encoded_line('0') + &vsync_line));
+        assert_eq!(
+            fr.frame_image().get_pixel(0, 0),
+            &Rgba::from_channels(0x91, 0x88, 0x1A, 0xFF)
+        );
+
+        // The blended frame stays intact while the next one is being drawn.
+        decode_and_consume(&mut fr, &encoded_line('4'));
+        assert_eq!(
+            fr.frame_image().get_pixel(0, 0),
+            &Rgba::from_channels(0x91, 0x88, 0x1A, 0xFF)
+        );
+        decode_and_consume(&mut fr, &vsync_line);
+        assert_eq!(
+            fr.frame_image().get_pixel(0, 0),
+            &Rgba::from_channels(0x99, 0x22, 0x88, 0xFF)
+        );
+
+        fr.set_frame_blending(false);
+        assert_eq!(fr.frame_image().get_pixel(0, 0), &blue);
+        decode_and_consume(&mut fr, &(encoded_line('0') + &vsync_line));
+        assert_eq!(fr.frame_image().get_pixel(0, 0), &red);
+    }
+
+    #[test]
+    fn threaded_conversion_renders_the_same_frames() {
+        let builder = FrameRendererBuilder::new()
+            .with_palette(simple_palette())
+            .with_height(20)
+            .with_first_visible_scanline_index(2)
+            .with_auto_height(true)
+            .with_frame_blending(true);
+        let mut inline_renderer = builder.build();
+        let mut threaded_renderer = builder.with_threaded_conversion(true).build();
+
+        // Frames long enough to be sent in several batches, some of them
+        // resizing the viewport, and some leaving parts of the previous frame
+        // intact.
+        for i in 0..12 {
+            let lines = if i < 6 { 30 } else { 45 };
+            let signal = encoded_frame(0)
+                + &(0..lines)
+                    .map(|line| match (i + line) % 5 {
+                        0 => encoded_line('.'),
+                        n => encoded_line("0246".chars().nth(n - 1).unwrap()),
+                    })
+                    .collect::<String>();
+            let frames = count_frames(&mut inline_renderer, &signal);
+            assert_eq!(count_frames(&mut threaded_renderer, &signal), frames);
+            if frames == 0 {
+                // Only complete frames are guaranteed to be the same.
+                continue;
+            }
+            assert_eq!(
+                inline_renderer.frame_image(),
+                threaded_renderer.frame_image(),
+                "Frame {}",
+                i,
+            );
+        }
+        assert_eq!(threaded_renderer.frame_image().height(), 43);
+    }
+
+    #[test]
+    fn vertical_hold_ignores_early_vsync() {
+        let mut fr = FrameRendererBuilder::new()
+            .with_palette(simple_palette())
+            .with_height(4)
+            .with_first_visible_scanline_index(0)
+            .with_vertical_hold(Some(VerticalHold {
+                min_scanlines: 3,
+                max_scanlines: 10,
+            }))
+            .build();
+        decode_and_consume(&mut fr, &encoded_frame(0));
+
+        // The VSYNC after the first line comes too early, so it only counts as
+        // a blank line.
+        let signal =
+            encoded_line('2') + &encoded_frame(0) + &encoded_line('4') + &encoded_line('4');
+        assert_eq!(count_frames(&mut fr, &signal), 0);
+        assert_eq!(count_frames(&mut fr, &encoded_frame(0)), 1);
+        assert_eq!(fr.scanlines_between_vsyncs(), Some(4));
+        itertools::assert_equal(
+            fr.frame_image().pixels().cloned(),
+            line_of(0x22, 0xFF, 0x22, 0xFF)
+                .chain(line_of(0x00, 0x00, 0x00, 0xFF))
+                .chain(line_of(0x33, 0x33, 0xFF, 0xFF))
+                .chain(line_of(0x33, 0x33, 0xFF, 0xFF)),
+        );
+    }
+
+    #[test]
+    fn vertical_hold_free_runs_and_relocks() {
+        let mut fr = FrameRendererBuilder::new()
+            .with_palette(simple_palette())
+            .with_height(2)
+            .with_first_visible_scanline_index(0)
+            .with_vertical_hold(Some(VerticalHold {
+                min_scanlines: 2,
+                max_scanlines: 3,
+            }))
+            .build();
+        decode_and_consume(&mut fr, &encoded_frame(0));
+
+        // No VSYNC: a new frame starts on the 4th line.
+        let signal = encoded_line('2').repeat(3);
+        assert_eq!(count_frames(&mut fr, &signal), 0);
+        assert_eq!(count_frames(&mut fr, &encoded_line('4')), 1);
+        assert_eq!(fr.scanlines_between_vsyncs(), Some(3));
+        itertools::assert_equal(
+            fr.frame_image().pixels().cloned(),
+            line_of(0x33, 0x33, 0xFF, 0xFF).chain(line_of(0x22, 0xFF, 0x22, 0xFF)),
+        );
+
+        // The next VSYNC is accepted immediately, even though it comes early.
+        assert_eq!(count_frames(&mut fr, &encoded_frame(0)), 1);
+        assert_eq!(fr.scanlines_between_vsyncs(), Some(1));
+        // Now that we're locked again, early VSYNC signals are ignored again.
+        let signal = encoded_line('0') + &encoded_frame(0);
+        assert_eq!(count_frames(&mut fr, &signal), 0);
+        let signal = encoded_line('0') + &encoded_frame(0);
+        assert_eq!(count_frames(&mut fr, &signal), 1);
+        assert_eq!(fr.scanlines_between_vsyncs(), Some(3));
+    }
+
+    #[test]
+    fn tolerates_off_spec_vsync() {
+        let vsync_line = encoded_frame(0);
+        let picture = encoded_line('2') + &encoded_line('4');
+
+        // In odd fields of an interlaced picture, VSYNC starts in the middle
+        // of a scanline.
+        let odd_vsync = ".".repeat(80)
+            + &"-".repeat(80)
+            + "----------------++++++++++++++++------------------------------------"
+            + &"-".repeat(80)
+            + &".".repeat(80);
+        let extended_vsync = vsync_line.repeat(3);
+
+        for vsync in [vsync_line, odd_vsync, extended_vsync] {
+            let mut fr = FrameRendererBuilder::new()
+                .with_palette(simple_palette())
+                .with_height(2)
+                .with_first_visible_scanline_index(0)
+                .build();
+            let signal = vsync.clone() + &picture + &vsync;
+            assert_eq!(count_frames(&mut fr, &signal), 1, "signal: {}", signal);
+            assert_eq!(fr.scanlines_between_vsyncs(), Some(2));
+            itertools::assert_equal(
+                fr.frame_image().pixels().cloned(),
+                line_of(0x22, 0xFF, 0x22, 0xFF).chain(line_of(0x33, 0x33, 0xFF, 0xFF)),
+            );
+        }
+    }
+
     #[test]
     fn supports_hsync_oddities() {
         let mut fr = FrameRendererBuilder::new()
@@ -447,8 +1217,8 @@ mod tests {
         // This case is "weird", but may occur if the program strobes the TIA
         // RSYNC register.
         //
-        // Note that the frame renderer doesn't support the interlaced mode
-        // detection just yet.
+        // Note that an RSYNC strobe doesn't make the frame renderer switch to
+        // the interlaced mode; it's only detected by the position of VSYNC.
         decode_and_consume(
             &mut fr,
             "----------------++++++++++++++++------------------------------------\
@@ -478,4 +1248,166 @@ mod tests {
                 .chain(line_of(0x33, 0x33, 0xFF, 0xFF)),
         );
     }
+
+    /// Drives a real TIA the way a program would, and feeds its video output
+    /// to a frame renderer.
+    struct TiaDriver {
+        tia: Tia,
+        renderer: FrameRenderer,
+        column: u32,
+        frames: usize,
+    }
+
+    impl TiaDriver {
+        fn new(renderer: FrameRenderer) -> Self {
+            let mut driver = Self {
+                tia: Tia::new(),
+                renderer,
+                column: 0,
+                frames: 0,
+            };
+            driver.write(registers::VBLANK, flags::VBLANK_ON);
+            return driver;
+        }
+
+        fn write(&mut self, address: u16, value: u8) {
+            self.tia.write(address, value).unwrap();
+        }
+
+        /// Runs the TIA for a given number of color clocks and counts the
+        /// frames completed by the renderer.
+        fn tick(&mut self, cycles: u32) {
+            for _ in 0..cycles {
+                if self.renderer.consume(self.tia.tick().video) {
+                    self.frames += 1;
+                }
+                self.column = (self.column + 1) % tia::TOTAL_WIDTH;
+            }
+        }
+
+        /// Runs the TIA until the beginning of the next scanline, just like
+        /// `STA WSYNC` does.
+        fn wsync(&mut self) {
+            self.tick(tia::TOTAL_WIDTH - self.column);
+        }
+
+        /// Emits a given number of blank scanlines.
+        fn blank_lines(&mut self, lines: u32) {
+            self.tick(lines * tia::TOTAL_WIDTH);
+        }
+
+        /// Emits a scanline filled with each of the given background colors.
+        fn lines(&mut self, colors: &[u8]) {
+            self.write(registers::VBLANK, 0);
+            for &color in colors {
+                self.write(registers::COLUBK, color);
+                self.wsync();
+            }
+            self.write(registers::VBLANK, flags::VBLANK_ON);
+        }
+
+        /// Emits a single field, starting at the beginning of a scanline. The
+        /// VSYNC signal starts at a given column and lasts for a given number
+        /// of scanlines. It's followed by the picture, one scanline for each
+        /// of the given background colors.
+        fn field(&mut self, vsync_column: u32, vsync_lines: u32, colors: &[u8]) {
+            self.tick(vsync_column);
+            self.write(registers::VSYNC, flags::VSYNC_ON);
+            self.blank_lines(vsync_lines);
+            self.write(registers::VSYNC, 0);
+            if self.column != 0 {
+                self.wsync();
+            }
+            self.lines(colors);
+        }
+    }
+
+    #[test]
+    fn weaves_interlaced_fields() {
+        let mut driver = TiaDriver::new(
+            FrameRendererBuilder::new()
+                .with_palette(simple_palette())
+                .with_height(2)
+                .with_first_visible_scanline_index(0)
+                .build(),
+        );
+        let green = Rgba::from_channels(0x22, 0xFF, 0x22, 0xFF);
+        let blue = Rgba::from_channels(0x33, 0x33, 0xFF, 0xFF);
+
+        // Odd fields start in the middle of a scanline.
+        let half_line = tia::TOTAL_WIDTH / 2;
+        driver.field(0, 3, &[0x02, 0x02]);
+        driver.field(half_line, 3, &[0x04, 0x04]);
+        driver.field(0, 3, &[]);
+        assert_eq!(driver.frames, 2);
+        assert_eq!(driver.renderer.frame_image().height(), 2);
+        itertools::assert_equal(
+            driver.renderer.frame_image().pixels().cloned(),
+            line_of(0x33, 0x33, 0xFF, 0xFF).chain(line_of(0x33, 0x33, 0xFF, 0xFF)),
+        );
+
+        // Once the signal is stable, the fields get woven together.
+        for _ in 0..3 {
+            driver.field(half_line, 3, &[0x04, 0x04]);
+            driver.field(0, 3, &[0x02, 0x02]);
+        }
+        let frame = driver.renderer.frame_image();
+        assert_eq!(frame.height(), 4);
+        for (row, color) in [green, blue, green, blue].iter().enumerate() {
+            assert_eq!(frame.get_pixel(0, row as u32), color, "row {}", row);
+            assert_eq!(frame.get_pixel(159, row as u32), color, "row {}", row);
+        }
+
+        // A progressive signal switches the viewport back.
+        for _ in 0..4 {
+            driver.field(0, 3, &[0x04, 0x02]);
+        }
+        assert_eq!(driver.renderer.frame_image().height(), 2);
+        itertools::assert_equal(
+            driver.renderer.frame_image().pixels().cloned(),
+            line_of(0x33, 0x33, 0xFF, 0xFF).chain(line_of(0x22, 0xFF, 0x22, 0xFF)),
+        );
+    }
+
+    #[test]
+    fn keeps_tia_picture_stable_with_off_spec_vsync() {
+        let mut driver = TiaDriver::new(TvStandard::Ntsc.frame_renderer_builder().build());
+        let picture = [0x0E; 192];
+        let ntsc_frame = |driver: &mut TiaDriver, vsync_column, vsync_lines| {
+            driver.field(vsync_column, vsync_lines, &[]);
+            driver.blank_lines(37);
+            driver.lines(&picture);
+            driver.blank_lines(30);
+        };
+
+        // Short, long, late and early VSYNC signals still keep the picture in
+        // place, as long as the frame length is within the vertical hold
+        // limits.
+        let vsyncs = [(0, 3), (0, 1), (0, 6), (40, 3), (200, 3), (0, 3)];
+        for (column, lines) in vsyncs {
+            ntsc_frame(&mut driver, column, lines);
+        }
+        assert_eq!(driver.frames, vsyncs.len() - 1);
+        let frame = driver.renderer.frame_image();
+        assert_eq!(frame.height(), 210);
+        let white = *frame.get_pixel(0, 0);
+        let black = Rgba::from_channels(0x00, 0x00, 0x00, 0xFF);
+        assert_ne!(white, black);
+        itertools::assert_equal(
+            frame.pixels().cloned(),
+            iter::repeat(white)
+                .take(192 * tia::FRAME_WIDTH as usize)
+                .chain(iter::repeat(black).take(18 * tia::FRAME_WIDTH as usize)),
+        );
+
+        // Without VSYNC, the picture rolls, but a single VSYNC is enough to
+        // lock to it again.
+        driver.blank_lines(700);
+        assert_eq!(driver.frames, vsyncs.len() + 1);
+        ntsc_frame(&mut driver, 0, 3);
+        let frames_before_lock = driver.frames;
+        ntsc_frame(&mut driver, 0, 3);
+        assert_eq!(driver.frames, frames_before_lock + 1);
+        assert_eq!(driver.renderer.scanlines_between_vsyncs(), Some(259));
+    }
 }
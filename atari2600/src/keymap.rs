@@ -0,0 +1,227 @@
+use crate::atari::{Atari, JoystickInput, JoystickPort, KeypadKey, Switch, SwitchPosition};
+use common::config::read_config_file;
+use common::keymap::parse_key_map;
+use common::keymap::KeymapError;
+use piston::Key;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// What happens when a given host key is pressed.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(from = "KeyActionConfig")]
+pub enum KeyAction {
+    /// Flips a toggle switch on each key press, or holds a momentary switch
+    /// (game select or game reset) down while the key is pressed.
+    Switch(Switch),
+    Joystick(JoystickPort, JoystickInput),
+    /// Presses a key of a keyboard controller connected to a given port.
+    Keypad(JoystickPort, KeypadKey),
+    /// Toggles frame blending on each key press.
+    FrameBlending,
+    /// Replaces the controller connected to a given port with the next type
+    /// of controller on each key press.
+    NextController(JoystickPort),
+}
+
+/// The way a [`KeyAction`] is written in a keymap file. Variants with
+/// multiple fields hold a tuple, so that they are written as arrays, e.g.
+/// `{ joystick = ["Left", "Fire"] }`; TOML doesn't allow that for tuple
+/// variants.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum KeyActionConfig {
+    Switch(Switch),
+    Joystick((JoystickPort, JoystickInput)),
+    Keypad((JoystickPort, KeypadKey)),
+    FrameBlending,
+    NextController(JoystickPort),
+}
+
+impl From<KeyActionConfig> for KeyAction {
+    fn from(config: KeyActionConfig) -> Self {
+        match config {
+            KeyActionConfig::Switch(switch) => KeyAction::Switch(switch),
+            KeyActionConfig::Joystick((port, input)) => KeyAction::Joystick(port, input),
+            KeyActionConfig::Keypad((port, key)) => KeyAction::Keypad(port, key),
+            KeyActionConfig::FrameBlending => KeyAction::FrameBlending,
+            KeyActionConfig::NextController(port) => KeyAction::NextController(port),
+        }
+    }
+}
+
+impl KeyAction {
+    /// Performs the action on a given machine after a key has been pressed or
+    /// released.
+    pub fn perform(self, atari: &mut Atari, pressed: bool) {
+        match self {
+            KeyAction::Switch(
+                switch @ (Switch::TvType | Switch::LeftDifficulty | Switch::RightDifficulty),
+            ) => {
+                if pressed {
+                    atari.flip_switch(switch, !atari.switch_position(switch));
+                }
+            }
+            KeyAction::Switch(switch @ (Switch::GameSelect | Switch::GameReset)) => {
+                atari.flip_switch(
+                    switch,
+                    if pressed {
+                        SwitchPosition::Down
+                    } else {
+                        SwitchPosition::Up
+                    },
+                );
+            }
+            KeyAction::Joystick(port, input) => {
+                atari.set_joystick_input_state(port, input, pressed);
+            }
+            KeyAction::Keypad(port, key) => {
+                atari.set_keypad_key_state(port, key, pressed);
+            }
+            KeyAction::FrameBlending => {
+                if pressed {
+                    atari.set_frame_blending(!atari.frame_blending());
+                }
+            }
+            KeyAction::NextController(port) => {
+                if pressed {
+                    atari.connect_controller(port, atari.controller(port).next());
+                }
+            }
+        }
+    }
+}
+
+/// Maps host keys to console switches, joysticks, and keyboard controllers.
+pub struct Keymap {
+    actions: HashMap<Key, KeyAction>,
+}
+
+/// Keymap configuration, as read from a file. Keys that are not mentioned in
+/// the file retain their default mapping.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct KeymapConfig {
+    keys: HashMap<String, KeyAction>,
+}
+
+impl Keymap {
+    pub fn read(path: &Path) -> Result<Self, KeymapError> {
+        return Self::from_config(read_config_file(path)?);
+    }
+
+    fn from_config(config: KeymapConfig) -> Result<Self, KeymapError> {
+        let mut keymap = Self::default();
+        keymap.actions.extend(parse_key_map(config.keys)?);
+        return Ok(keymap);
+    }
+
+    pub fn action(&self, key: Key) -> Option<KeyAction> {
+        self.actions.get(&key).copied()
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use JoystickInput as Input;
+        use JoystickPort as Port;
+        use KeyAction::Joystick;
+        use KeyAction::Keypad;
+        Self {
+            actions: HashMap::from([
+                (Key::D1, KeyAction::Switch(Switch::TvType)),
+                (Key::D2, KeyAction::Switch(Switch::LeftDifficulty)),
+                (Key::D3, KeyAction::Switch(Switch::RightDifficulty)),
+                (Key::D4, KeyAction::Switch(Switch::GameSelect)),
+                (Key::D5, KeyAction::Switch(Switch::GameReset)),
+                (Key::D6, KeyAction::FrameBlending),
+                (Key::D7, KeyAction::NextController(Port::Left)),
+                (Key::D8, KeyAction::NextController(Port::Right)),
+                (Key::W, Joystick(Port::Left, Input::Up)),
+                (Key::A, Joystick(Port::Left, Input::Left)),
+                (Key::S, Joystick(Port::Left, Input::Down)),
+                (Key::D, Joystick(Port::Left, Input::Right)),
+                (Key::LShift, Joystick(Port::Left, Input::Fire)),
+                (Key::Space, Joystick(Port::Left, Input::Fire)),
+                (Key::I, Joystick(Port::Right, Input::Up)),
+                (Key::J, Joystick(Port::Right, Input::Left)),
+                (Key::K, Joystick(Port::Right, Input::Down)),
+                (Key::L, Joystick(Port::Right, Input::Right)),
+                (Key::N, Joystick(Port::Right, Input::Fire)),
+                (Key::Up, Joystick(Port::Right, Input::Up)),
+                (Key::Left, Joystick(Port::Right, Input::Left)),
+                (Key::Down, Joystick(Port::Right, Input::Down)),
+                (Key::Right, Joystick(Port::Right, Input::Right)),
+                (Key::Period, Joystick(Port::Right, Input::Fire)),
+                (Key::NumPad1, Keypad(Port::Right, KeypadKey::Key1)),
+                (Key::NumPad2, Keypad(Port::Right, KeypadKey::Key2)),
+                (Key::NumPad3, Keypad(Port::Right, KeypadKey::Key3)),
+                (Key::NumPad4, Keypad(Port::Right, KeypadKey::Key4)),
+                (Key::NumPad5, Keypad(Port::Right, KeypadKey::Key5)),
+                (Key::NumPad6, Keypad(Port::Right, KeypadKey::Key6)),
+                (Key::NumPad7, Keypad(Port::Right, KeypadKey::Key7)),
+                (Key::NumPad8, Keypad(Port::Right, KeypadKey::Key8)),
+                (Key::NumPad9, Keypad(Port::Right, KeypadKey::Key9)),
+                (Key::NumPad0, Keypad(Port::Right, KeypadKey::Key0)),
+                (Key::NumPadMultiply, Keypad(Port::Right, KeypadKey::Star)),
+                (Key::NumPadEnter, Keypad(Port::Right, KeypadKey::Pound)),
+            ]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::config::parse_config;
+    use common::config::ConfigFormat;
+
+    #[test]
+    fn overrides_defaults() {
+        let config = parse_config(
+            r#"
+                [keys]
+                D1 = { switch = "GameReset" }
+                Z = { joystick = ["Left", "Fire"] }
+                F8 = "frame_blending"
+                F1 = { keypad = ["Left", "Pound"] }
+                F2 = { next_controller = "Right" }
+            "#,
+            ConfigFormat::Toml,
+        )
+        .unwrap();
+        let keymap = Keymap::from_config(config).unwrap();
+        assert_eq!(
+            keymap.action(Key::D1),
+            Some(KeyAction::Switch(Switch::GameReset))
+        );
+        assert_eq!(
+            keymap.action(Key::Z),
+            Some(KeyAction::Joystick(JoystickPort::Left, JoystickInput::Fire))
+        );
+        assert_eq!(
+            keymap.action(Key::W),
+            Some(KeyAction::Joystick(JoystickPort::Left, JoystickInput::Up))
+        );
+        assert_eq!(keymap.action(Key::F8), Some(KeyAction::FrameBlending));
+        assert_eq!(
+            keymap.action(Key::F1),
+            Some(KeyAction::Keypad(JoystickPort::Left, KeypadKey::Pound))
+        );
+        assert_eq!(
+            keymap.action(Key::F2),
+            Some(KeyAction::NextController(JoystickPort::Right))
+        );
+        assert_eq!(keymap.action(Key::Q), None);
+    }
+
+    #[test]
+    fn reports_unknown_keys() {
+        let config = parse_config(
+            r#"{"keys": {"Foo": {"switch": "TvType"}}}"#,
+            ConfigFormat::Json,
+        )
+        .unwrap();
+        assert!(Keymap::from_config(config).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+//! An Atari 2600 emulator. Besides the `atari2600` binary, the crate can be
+//! used as a library to embed the console in other programs:
+//!
+//! ```no_run
+//! use atari2600::address_space::Cartridge;
+//! use atari2600::AtariBuilder;
+//! use atari2600::JoystickInput;
+//! use atari2600::JoystickPort;
+//! use common::app::Machine;
+//!
+//! let rom = std::fs::read("game.bin").unwrap();
+//! let cartridge = Cartridge::with_detected_bank_switching(&rom).unwrap();
+//! let mut atari = AtariBuilder::new(cartridge).build();
+//! atari.reset();
+//! atari.set_joystick_input_state(JoystickPort::Left, JoystickInput::Fire, true);
+//! let frame = atari.run_frame().unwrap().clone();
+//! // Examine the console after each of the next 60 frames.
+//! atari
+//!     .run_frames(60, |atari| println!("{}", atari.display_state()))
+//!     .unwrap();
+//! ```
+//!
+//! [`AtariController`] connects the console to the emulator application
+//! provided by the `common` crate, translating host input events.
+
+#![feature(test)]
+
+pub mod address_space;
+pub mod app;
+pub mod atari;
+pub mod colors;
+pub mod frame_renderer;
+pub mod keymap;
+pub mod riot;
+pub mod rom_database;
+pub mod tia;
+pub mod tv_standard;
+
+mod test_utils;
+
+pub use crate::app::AtariController;
+pub use crate::atari::{
+    Atari, AtariBuilder, JoystickInput, JoystickPort, Paddle, Switch, SwitchPosition,
+};
@@ -1,79 +1,303 @@
-#![feature(test)]
-
-mod address_space;
-mod app;
-mod atari;
-mod audio;
-mod colors;
-mod frame_renderer;
-mod riot;
-mod tia;
-
-mod test_utils;
-
-use crate::app::AtariController;
-use atari::{Atari, AtariAddressSpace};
+use atari2600::address_space::BankSwitching;
+use atari2600::address_space::Cartridge;
+use atari2600::address_space::ROM_FILE_EXTENSIONS;
+use atari2600::app::AtariController;
+use atari2600::atari::{
+    AtariBuilder, ControllerType, JoystickPort, Switch, SwitchPosition, AUDIO_CHANNELS,
+    AUDIO_SAMPLE_RATE,
+};
+use atari2600::keymap::Keymap;
+use atari2600::rom_database::RomDatabase;
+use atari2600::tv_standard::TvStandard;
 use clap::Parser;
+use common::app::AppController;
 use common::app::Application;
 use common::app::CommonCliArguments;
-use common::debugger::adapter::TcpDebugAdapter;
-use frame_renderer::FrameRendererBuilder;
-use std::sync::atomic::Ordering;
-use ya6502::memory::Rom;
+use common::archive::read_image_file;
+use common::audio;
+use common::audio::ChannelPan;
+use common::audio::Mixer;
+use common::gamepad::GamepadMapping;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 struct Args {
     #[clap(flatten)]
     common: CommonCliArguments,
+
+    #[clap(flatten)]
+    gamepad_mapping: GamepadMapping,
+
+    /// Bank switching scheme of the cartridge. If not specified, it will be
+    /// taken from the ROM database or detected automatically.
+    #[clap(long, arg_enum)]
+    bank_switching: Option<BankSwitching>,
+
+    /// Enables the 128 bytes of SuperChip RAM in F8, F6, and F4 cartridges.
+    /// Use it if the automatic detection fails.
+    #[clap(long)]
+    super_chip: bool,
+
+    /// Controller connected to the left port. The first connected paddle is
+    /// controlled with the mouse. If not specified, it will be taken from the
+    /// ROM database, or a joystick will be used.
+    #[clap(long, arg_enum)]
+    p0_controller: Option<ControllerType>,
+
+    /// Controller connected to the right port. If not specified, it will be
+    /// taken from the ROM database, or a joystick will be used.
+    #[clap(long, arg_enum)]
+    p1_controller: Option<ControllerType>,
+
+    /// Deprecated: use `--p0-controller=paddle --p1-controller=paddle`.
+    #[clap(
+        long,
+        hide = true,
+        conflicts_with_all = &["p0-controller", "p1-controller", "left-keypad", "right-keypad"]
+    )]
+    paddles: bool,
+
+    /// Deprecated: use `--p0-controller=keypad`.
+    #[clap(long, hide = true, conflicts_with = "p0-controller")]
+    left_keypad: bool,
+
+    /// Deprecated: use `--p1-controller=keypad`.
+    #[clap(long, hide = true, conflicts_with = "p1-controller")]
+    right_keypad: bool,
+
+    /// Initial position of the left player's difficulty switch: A (up) or B
+    /// (down).
+    #[clap(long, arg_enum, default_value = "a")]
+    left_difficulty: Difficulty,
+
+    /// Initial position of the right player's difficulty switch: A (up) or B
+    /// (down).
+    #[clap(long, arg_enum, default_value = "a")]
+    right_difficulty: Difficulty,
+
+    /// Starts with the TV type switch in the black and white position.
+    #[clap(long)]
+    black_and_white: bool,
+
+    /// TV standard of the console. Determines the color palette, the number of
+    /// visible scanlines, and the frame rate. If not specified, it will be
+    /// taken from the ROM database, or NTSC will be used.
+    #[clap(long, arg_enum)]
+    tv_standard: Option<TvStandard>,
+
+    /// Averages consecutive frames to reduce the flicker of objects that are
+    /// displayed only on every other frame. Can be toggled at runtime.
+    #[clap(long)]
+    frame_blending: bool,
+
+    /// A file with additional entries of the ROM database, in JSON or TOML
+    /// format (depending on the file name extension). Its entries take
+    /// precedence over the built-in ones.
+    #[clap(long)]
+    rom_database: Option<PathBuf>,
+
+    /// Master volume of the audio output, from 0 to 1.
+    #[clap(long, default_value = "1", parse(try_from_str = parse_volume))]
+    volume: f32,
+
+    /// Places a TIA audio channel (0 or 1) in the stereo field, from -1 (left)
+    /// to 1 (right), e.g. `--pan 0=-0.5`. Can be repeated.
+    #[clap(long = "pan")]
+    pans: Vec<ChannelPan>,
+
+    /// Mutes a TIA audio channel (0 or 1). Can be repeated. Channels can also
+    /// be muted at runtime with Ctrl and a digit key.
+    #[clap(long = "mute")]
+    muted_channels: Vec<usize>,
+
+    /// ROM image to run. Can also be compressed in a ZIP or gzip file.
     cartridge_file: String,
 }
 
+impl Args {
+    /// Returns the controller type requested for a given port, taking into
+    /// account the deprecated options.
+    fn controller(&self, port: JoystickPort) -> Option<ControllerType> {
+        let (controller, keypad) = match port {
+            JoystickPort::Left => (self.p0_controller, self.left_keypad),
+            JoystickPort::Right => (self.p1_controller, self.right_keypad),
+        };
+        return if self.paddles {
+            Some(ControllerType::Paddles)
+        } else if keypad {
+            Some(ControllerType::Keypad)
+        } else {
+            controller
+        };
+    }
+
+    /// Creates a mixer of TIA audio channels configured by the command line
+    /// arguments.
+    fn create_mixer(&self) -> Mixer {
+        let mut mixer = Mixer::new(AUDIO_CHANNELS);
+        mixer.set_master_volume(self.volume);
+        for pan in &self.pans {
+            mixer.set_pan(pan.channel, pan.pan);
+        }
+        for &channel in &self.muted_channels {
+            mixer.set_muted(channel, true);
+        }
+        return mixer;
+    }
+}
+
+/// Parses the `--volume` argument, rejecting values outside of the 0 to 1
+/// range instead of silently clamping them.
+fn parse_volume(arg: &str) -> Result<f32, String> {
+    let volume: f32 = arg.parse().map_err(|e| format!("{}", e))?;
+    if !(0.0..=1.0).contains(&volume) {
+        return Err("The volume has to be between 0 and 1".to_string());
+    }
+    return Ok(volume);
+}
+
+#[derive(Clone, Copy, clap::ArgEnum)]
+enum Difficulty {
+    A,
+    B,
+}
+
+impl From<Difficulty> for SwitchPosition {
+    fn from(difficulty: Difficulty) -> Self {
+        match difficulty {
+            Difficulty::A => SwitchPosition::Up,
+            Difficulty::B => SwitchPosition::Down,
+        }
+    }
+}
+
 fn main() {
     let args = Args::parse();
 
-    println!("Ready player ONE!");
-
-    let rom_bytes = std::fs::read(args.cartridge_file).expect("Unable to read the ROM image file");
-    // Create and initialize components of the emulated system.
-    let address_space = Box::new(AtariAddressSpace::new(
-        Rom::new(&rom_bytes[..]).expect("Unable to load the ROM into Atari"),
-    ));
-    let (audio_consumer, stream, _sink) = audio::initialize();
-    let mut atari = Atari::new(
-        address_space,
-        FrameRendererBuilder::new()
-            .with_palette(colors::ntsc_palette())
-            .with_height(210)
-            .build(),
-        audio_consumer,
-    );
+    eprintln!("Ready player ONE!");
+    if args.paddles || args.left_keypad || args.right_keypad {
+        eprintln!(
+            "Warning: --paddles, --left-keypad, and --right-keypad are deprecated; \
+            use --p0-controller and --p1-controller instead"
+        );
+    }
 
-    let debugger_adapter = if args.common.debugger {
-        Some(TcpDebugAdapter::new(args.common.debugger_port))
-    } else {
+    let rom_bytes = read_image_file(Path::new(&args.cartridge_file), ROM_FILE_EXTENSIONS)
+        .expect("Unable to read the ROM image file")
+        .bytes;
+    let mut rom_database = RomDatabase::built_in();
+    if let Some(path) = &args.rom_database {
+        rom_database = rom_database
+            .with_overrides(RomDatabase::read(path).expect("Unable to read the ROM database"));
+    }
+    let rom_info = rom_database.lookup(&rom_bytes).cloned().unwrap_or_default();
+    if let Some(title) = &rom_info.title {
+        eprintln!("Found in the ROM database: {}", title);
+    }
+    let tv_standard = args
+        .tv_standard
+        .or(rom_info.tv_standard)
+        .unwrap_or(TvStandard::Ntsc);
+    let cartridge = match args.bank_switching.or(rom_info.bank_switching) {
+        Some(bank_switching) => Cartridge::new(&rom_bytes, bank_switching),
+        None => Cartridge::with_detected_bank_switching(&rom_bytes),
+    }
+    .map(|cartridge| {
+        if args.super_chip || rom_info.super_chip == Some(true) {
+            cartridge.with_super_chip(true)
+        } else {
+            cartridge
+        }
+    });
+    let mut atari_builder =
+        AtariBuilder::new(cartridge.expect("Unable to load the ROM into Atari"))
+            .with_tv_standard(tv_standard)
+            .with_ram_pattern(args.common.ram_pattern())
+            .with_power_on_state(args.common.power_on_state())
+            .with_permissive(args.common.permissive)
+            .with_frame_blending(args.frame_blending)
+            // While debugging, it's useful to see how far the current frame
+            // has been drawn.
+            .with_threaded_frame_conversion(!args.common.debugger)
+            .with_controller(
+                JoystickPort::Left,
+                args.controller(JoystickPort::Left)
+                    .or(rom_info.p0_controller)
+                    .unwrap_or(ControllerType::Joystick),
+            )
+            .with_controller(
+                JoystickPort::Right,
+                args.controller(JoystickPort::Right)
+                    .or(rom_info.p1_controller)
+                    .unwrap_or(ControllerType::Joystick),
+            )
+            .with_switch_position(Switch::LeftDifficulty, args.left_difficulty.into())
+            .with_switch_position(Switch::RightDifficulty, args.right_difficulty.into())
+            .with_switch_position(
+                Switch::TvType,
+                if args.black_and_white {
+                    SwitchPosition::Down
+                } else {
+                    SwitchPosition::Up
+                },
+            );
+    let mixer = args.create_mixer();
+    atari_builder = atari_builder.with_mixer(mixer.clone());
+    // In the headless mode, the audio samples are simply discarded.
+    let audio_output = if args.common.headless {
         None
+    } else {
+        let audio_latency = Duration::from_millis(args.common.audio_latency);
+        let (consumer, source) =
+            audio::create_consumer_and_source(AUDIO_SAMPLE_RATE, audio_latency);
+        let monitor = consumer.monitor();
+        atari_builder = atari_builder.with_audio_consumer(consumer);
+        Some((source, monitor))
     };
+    let mut atari = atari_builder.build();
 
-    let mut app = Application::new(
-        AtariController::new(&mut atari, debugger_adapter),
-        "Atari 2600",
-        5,
-        3,
+    let mut controller = AtariController::new(
+        &mut atari,
+        args.common.create_debugger(),
+        args.gamepad_mapping,
     );
-    let interrupted = app.interrupted();
-
-    signal_hook::flag::register(signal_hook::consts::SIGINT, interrupted)
+    controller.set_tracer(args.common.create_tracer());
+    controller.set_coverage_tracker(args.common.create_coverage_tracker());
+    controller
+        .install_cheats(&args.common.cheats)
+        .expect("Unable to install cheats");
+    if let Some(path) = &args.common.keymap {
+        let keymap = Keymap::read(Path::new(path)).expect("Unable to read the keymap file");
+        controller.set_keymap(keymap);
+    }
+    signal_hook::flag::register(signal_hook::consts::SIGINT, controller.interrupted())
         .expect("Unable to set interrupt signal handler");
+    let controller = args.common.create_input_log_controller(controller);
 
-    app.run();
+    if args.common.headless {
+        args.common
+            .create_headless_runner(controller)
+            .run(args.common.frames)
+            .expect("Unable to store the frames");
+        return;
+    }
 
-    // Note: The order of dropping is important here, hence we make it explicit.
-    // If we drop Atari before the audio stream, we'll end up with a potential
-    // deadlock: the audio stream may not finish until a blocking read of the
-    // audio sample is performed, and it won't be interrupted unless we "hang
-    // up" on the writing side (the AudioConsumer), which owns an
-    // mspc::SyncSender instance. Since the audio consumer is owned by Atari, we
-    // need to drop it first.
-    drop(atari);
-    drop(stream);
+    let controller = args
+        .common
+        .create_watching_controller(controller, Some(Path::new(&args.cartridge_file)));
+    let mut app = Application::new(controller, args.common.backend, "Atari 2600", 5, 3);
+    app.set_frame_rate(tv_standard.frame_rate());
+    app.set_speed(args.common.speed);
+    app.set_sync_mode(args.common.sync);
+    app.set_max_frame_skip(args.common.max_frame_skip);
+    if let Some((source, monitor)) = audio_output {
+        app.set_audio_monitor(monitor);
+        app.play_audio(source);
+    }
+    app.set_mixer(mixer);
+    app.set_capture_dir(&args.common.capture_dir);
+    app.set_crt_effects(args.common.crt_effects());
+    app.run();
 }
@@ -1,4 +1,8 @@
+use common::debugger::symbols::RegisterNames;
 use rand::Rng;
+use ya6502::cpu::ChipRegister;
+use ya6502::cpu::ChipState;
+use ya6502::cpu::PowerOnState;
 use ya6502::memory::Inspect;
 use ya6502::memory::Read;
 use ya6502::memory::Write;
@@ -7,6 +11,9 @@ use ya6502::memory::{Memory, ReadError, ReadResult, WriteError, WriteResult};
 /// A MOS Technology 6532 RIOT chip. Note that originally, this chip also
 /// included 128 bytes of RAM, but for the sake of single-responsibility
 /// principle, it's been split out to a separate struct: `memory::AtariRam`.
+///
+/// The interrupt output is not emulated, since the 6507 CPU doesn't have an
+/// IRQ line. Programs can still poll the interrupt flags, though.
 #[derive(Debug)]
 pub struct Riot {
     /// A divider that counts from 0 to `interval_length` and then wraps around.
@@ -62,9 +69,21 @@ enum EdgeDetectionMode {
     Negative,
 }
 
+impl Default for Riot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Riot {
     pub fn new() -> Riot {
-        let mut rng = rand::thread_rng();
+        Self::with_power_on_state(PowerOnState::Random)
+    }
+
+    /// Creates a RIOT with the timer state, which is undefined on real
+    /// hardware, determined by a given power-on state.
+    pub fn with_power_on_state(power_on_state: PowerOnState) -> Riot {
+        let mut rng = power_on_state.rng();
         Riot {
             timer_divider: rng.gen(),
             interval_length: [1, 8, 64, 1024][rng.gen_range(0..4)],
@@ -102,33 +121,71 @@ impl Riot {
     pub fn set_port(&mut self, port: Port, value: u8) {
         match port {
             Port::PA => {
-                let pa7_change = (value & (1 << 7)) as i32 - (self.port_a & (1 << 7)) as i32;
-                match self.pa7_edge_detection_mode {
-                    EdgeDetectionMode::Negative => {
-                        if pa7_change < 0 {
-                            self.reg_timint |= flags::TIMINT_PA7;
-                        }
-                    }
-                    EdgeDetectionMode::Positive => {
-                        if pa7_change > 0 {
-                            self.reg_timint |= flags::TIMINT_PA7;
-                        }
-                    }
-                }
+                let pa7 = self.pa7();
                 self.port_a = value;
+                self.detect_pa7_edge(pa7);
             }
             Port::PB => self.port_b = value,
         };
     }
+
+    /// Reports the values of RIOT registers for debugging purposes. Ports are
+    /// reported as seen by the CPU, and the direction registers are reported
+    /// separately.
+    pub fn chip_state(&self) -> Result<ChipState, ReadError> {
+        let readable_registers = [
+            ("SWCHA", registers::SWCHA),
+            ("SWACNT", registers::SWACNT),
+            ("SWCHB", registers::SWCHB),
+            ("SWBCNT", registers::SWBCNT),
+            ("INTIM", registers::INTIM),
+            ("TIMINT", registers::TIMINT),
+        ];
+        let mut registers = readable_registers
+            .iter()
+            .map(|&(name, address)| Ok(ChipRegister::new(name, self.inspect(address)?)))
+            .collect::<Result<Vec<_>, ReadError>>()?;
+        registers.push(ChipRegister::new("INTERVAL", self.interval_length as u16));
+        return Ok(ChipState {
+            name: "RIOT".to_string(),
+            registers,
+        });
+    }
+
+    /// Returns the levels driven by RIOT on port A pins. Output pins reflect
+    /// the output register, and input pins are reported as high.
+    pub fn port_a_output(&self) -> u8 {
+        self.reg_swcha | !self.reg_swacnt
+    }
+
+    /// Returns the value of port A, as seen by the CPU.
+    fn swcha(&self) -> u8 {
+        (self.reg_swacnt & self.reg_swcha & self.port_a) | (!self.reg_swacnt & self.port_a)
+    }
+
+    fn pa7(&self) -> bool {
+        self.swcha() & (1 << 7) != 0
+    }
+
+    /// Sets the PA7 interrupt flag if the PA7 pin state has changed from
+    /// `previous_pa7` in the direction selected by the edge detection mode.
+    /// Note that the pin state depends not only on the port input, but also on
+    /// the output register, so writing to it can also trigger the flag.
+    fn detect_pa7_edge(&mut self, previous_pa7: bool) {
+        let edge_detected = match self.pa7_edge_detection_mode {
+            EdgeDetectionMode::Negative => previous_pa7 && !self.pa7(),
+            EdgeDetectionMode::Positive => !previous_pa7 && self.pa7(),
+        };
+        if edge_detected {
+            self.reg_timint |= flags::TIMINT_PA7;
+        }
+    }
 }
 
 impl Inspect for Riot {
     fn inspect(&self, address: u16) -> ReadResult {
         match canonical_read_address(address) {
-            registers::SWCHA => {
-                Ok((self.reg_swacnt & self.reg_swcha & self.port_a)
-                    | (!self.reg_swacnt & self.port_a))
-            }
+            registers::SWCHA => Ok(self.swcha()),
             registers::SWACNT => Ok(self.reg_swacnt),
             registers::SWCHB => {
                 Ok((self.reg_swbcnt & self.reg_swchb) | (!self.reg_swbcnt & self.port_b))
@@ -160,6 +217,7 @@ impl Read for Riot {
 
 impl Write for Riot {
     fn write(&mut self, address: u16, value: u8) -> WriteResult {
+        let pa7 = self.pa7();
         match canonical_write_address(address) {
             registers::SWCHA => self.reg_swcha = value,
             registers::SWACNT => self.reg_swacnt = value,
@@ -176,12 +234,55 @@ impl Write for Riot {
 
             _ => return Err(WriteError { address, value }),
         };
+        self.detect_pa7_edge(pa7);
         Ok(())
     }
 }
 
 impl Memory for Riot {}
 
+/// Returns names of the RIOT registers, used to annotate the disassembly. The
+/// register addresses are relative to `base`. Since the RIOT doesn't decode all
+/// the address lines, the names are repeated throughout the 32 bytes of its I/O
+/// area.
+pub fn register_names(base: u16) -> RegisterNames {
+    use registers::*;
+    let port_registers = [
+        ("SWCHA", SWCHA),
+        ("SWACNT", SWACNT),
+        ("SWCHB", SWCHB),
+        ("SWBCNT", SWBCNT),
+    ];
+    let read_registers = [("INTIM", INTIM), ("TIMINT", TIMINT)];
+    let write_registers = [
+        ("TIM1T", TIM1T),
+        ("TIM8T", TIM8T),
+        ("TIM64T", TIM64T),
+        ("T1024T", T1024T),
+    ];
+    let reads = [&port_registers[..], &read_registers].concat();
+    let writes = [&port_registers[..], &write_registers].concat();
+    RegisterNames::new()
+        .with_read_registers(mirrored_registers(&reads, canonical_read_address, base))
+        .with_write_registers(mirrored_registers(&writes, canonical_write_address, base))
+}
+
+/// Returns the registers from a given list that are visible at each address of
+/// the RIOT I/O area, relative to `base`.
+fn mirrored_registers<'a>(
+    registers: &'a [(&'a str, u16)],
+    canonical_address: fn(u16) -> u16,
+    base: u16,
+) -> impl Iterator<Item = (&'a str, u16)> + 'a {
+    (0..0x20).filter_map(move |offset| {
+        let address = canonical_address(offset);
+        registers
+            .iter()
+            .find(|(_, register)| *register == address)
+            .map(|&(name, _)| (name, base + offset))
+    })
+}
+
 fn canonical_read_address(address: u16) -> u16 {
     if address & 0b0100 != 0 {
         address & 0b0101
@@ -218,9 +319,20 @@ mod registers {
     pub const PA7_POS: u16 = 0x05; // Use positive edge detection
 }
 
-mod flags {
+pub mod flags {
     pub const TIMINT_TIMER: u8 = 1 << 7;
     pub const TIMINT_PA7: u8 = 1 << 6;
+
+    // Console switches connected to port B. A bit is set if a toggle switch is
+    // up (color TV, difficulty A), or if a button is released.
+    pub const SWCHB_P1_DIFFICULTY: u8 = 1 << 7;
+    pub const SWCHB_P0_DIFFICULTY: u8 = 1 << 6;
+    pub const SWCHB_COLOR: u8 = 1 << 3;
+    pub const SWCHB_SELECT: u8 = 1 << 1;
+    pub const SWCHB_RESET: u8 = 1 << 0;
+    /// Port B pins that aren't connected to anything. They are pulled up, so
+    /// they read as 1 unless configured as outputs.
+    pub const SWCHB_UNUSED: u8 = 0b0011_0100;
 }
 
 #[cfg(test)]
@@ -316,6 +428,45 @@ mod tests {
         assert_eq!(riot.read(registers::INTIM).unwrap(), 0xFA);
     }
 
+    #[test]
+    fn inspecting_timer() {
+        let mut riot = Riot::new();
+        riot.write(registers::TIM1T, 0x00).unwrap();
+        riot.tick();
+        riot.tick();
+
+        // Inspecting the timer registers doesn't reset the interrupt flag.
+        assert_eq!(riot.inspect(registers::INTIM).unwrap(), 0xFE);
+        assert_eq!(
+            riot.inspect(registers::TIMINT).unwrap(),
+            flags::TIMINT_TIMER
+        );
+        assert_eq!(riot.read(registers::TIMINT).unwrap(), flags::TIMINT_TIMER);
+        assert_eq!(riot.read(registers::INTIM).unwrap(), 0xFE);
+        assert_eq!(riot.inspect(registers::TIMINT).unwrap(), 0);
+    }
+
+    #[test]
+    fn reports_chip_state() {
+        let mut riot = Riot::new();
+        riot.write(registers::SWACNT, 0xF0).unwrap();
+        riot.write(registers::TIM64T, 0x12).unwrap();
+        let state = riot.chip_state().unwrap();
+        assert_eq!(state.name, "RIOT");
+        let register = |name| {
+            state
+                .registers
+                .iter()
+                .find(|register| register.name == name)
+                .unwrap()
+                .value
+        };
+        assert_eq!(register("SWACNT"), 0xF0);
+        assert_eq!(register("INTIM"), 0x12);
+        assert_eq!(register("TIMINT"), 0);
+        assert_eq!(register("INTERVAL"), 64);
+    }
+
     #[test]
     fn timer_reset() {
         let mut riot = Riot::new();
@@ -406,6 +557,49 @@ mod tests {
         assert_eq!(riot.read(registers::TIMINT).unwrap(), 0);
     }
 
+    #[test]
+    fn pa7_edge_detection_on_output() {
+        let mut riot = Riot::new();
+        riot.set_port(Port::PA, 0xFF);
+        riot.write(registers::PA7_NEG, 0).unwrap();
+        riot.write(registers::SWACNT, 1 << 7).unwrap();
+        riot.write(registers::SWCHA, 0xFF).unwrap();
+        assert_eq!(riot.read(registers::TIMINT).unwrap(), 0);
+
+        // Driving PA7 low through the output register triggers the flag.
+        riot.write(registers::SWCHA, 0x00).unwrap();
+        assert_eq!(riot.read(registers::TIMINT).unwrap(), flags::TIMINT_PA7);
+
+        // Switching PA7 back to input mode releases it, causing a positive
+        // edge.
+        riot.write(registers::PA7_POS, 0).unwrap();
+        riot.write(registers::SWACNT, 0).unwrap();
+        assert_eq!(riot.read(registers::TIMINT).unwrap(), flags::TIMINT_PA7);
+
+        // In input mode, the output register doesn't matter.
+        riot.write(registers::SWCHA, 0x00).unwrap();
+        riot.write(registers::SWCHA, 0xFF).unwrap();
+        assert_eq!(riot.read(registers::TIMINT).unwrap(), 0);
+    }
+
+    #[test]
+    fn reading_timint_keeps_timer_flag() {
+        let mut riot = Riot::new();
+        riot.set_port(Port::PA, 0xFF);
+        riot.write(registers::PA7_NEG, 0).unwrap();
+        riot.write(registers::TIM1T, 0x00).unwrap();
+        riot.tick();
+        riot.set_port(Port::PA, 0x00);
+        assert_eq!(
+            riot.read(registers::TIMINT).unwrap(),
+            flags::TIMINT_TIMER | flags::TIMINT_PA7
+        );
+        assert_eq!(riot.read(registers::TIMINT).unwrap(), flags::TIMINT_TIMER);
+        riot.tick();
+        assert_eq!(riot.read(registers::INTIM).unwrap(), 0xFE);
+        assert_eq!(riot.read(registers::TIMINT).unwrap(), 0);
+    }
+
     #[test]
     fn address_mirroring() {
         assert_eq!(canonical_read_address(0xEDF8), registers::SWCHA);
@@ -417,4 +611,30 @@ mod tests {
         assert_eq!(canonical_write_address(0xEDFF), registers::T1024T);
         assert_eq!(canonical_write_address(0xEDEF), registers::PA7_POS);
     }
+
+    #[test]
+    fn seeded_power_on_state_is_reproducible() {
+        let timer_values = |riot: &mut Riot| {
+            (0..2048)
+                .map(|_| {
+                    riot.tick();
+                    riot.inspect(registers::INTIM).unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut riot1 = Riot::with_power_on_state(PowerOnState::Seeded(7));
+        let mut riot2 = Riot::with_power_on_state(PowerOnState::Seeded(7));
+        assert_eq!(timer_values(&mut riot1), timer_values(&mut riot2));
+    }
+
+    #[test]
+    fn mirrored_register_names() {
+        let names = register_names(0x280);
+        assert_eq!(names.read_name(0x280), Some("SWCHA"));
+        assert_eq!(names.write_name(0x29A), Some("SWCHB"));
+        assert_eq!(names.read_name(0x28C), Some("INTIM"));
+        assert_eq!(names.write_name(0x28C), None);
+        assert_eq!(names.write_name(0x29C), Some("TIM1T"));
+        assert_eq!(names.read_name(0x2A0), None);
+    }
 }
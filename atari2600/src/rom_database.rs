@@ -0,0 +1,201 @@
+//! A database of known cartridges. Some ROM images can't be run correctly with
+//! the default settings: they need a particular bank switching scheme that
+//! can't be detected, paddles instead of joysticks, or a PAL console. The
+//! database identifies ROM images by MD5 hashes (the same ones that are used
+//! by other emulators, like Stella) and tells how to configure the console.
+
+use crate::address_space::BankSwitching;
+use crate::atari::ControllerType;
+use crate::tv_standard::TvStandard;
+use common::config::parse_config;
+use common::config::read_config_file;
+use common::config::ConfigError;
+use common::config::ConfigFormat;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Database entries that are built into the emulator.
+const BUILT_IN_DATABASE: &str = include_str!("rom_database.toml");
+
+/// Known properties of a ROM image. Properties that are not set should be
+/// either detected automatically or taken from the command line.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RomInfo {
+    pub title: Option<String>,
+    pub bank_switching: Option<BankSwitching>,
+    pub super_chip: Option<bool>,
+    pub p0_controller: Option<ControllerType>,
+    pub p1_controller: Option<ControllerType>,
+    pub tv_standard: Option<TvStandard>,
+}
+
+impl RomInfo {
+    /// Overrides properties of this entry with the ones that are set in
+    /// another entry.
+    fn merge(&mut self, other: RomInfo) {
+        self.title = other.title.or(self.title.take());
+        self.bank_switching = other.bank_switching.or(self.bank_switching);
+        self.super_chip = other.super_chip.or(self.super_chip);
+        self.p0_controller = other.p0_controller.or(self.p0_controller);
+        self.p1_controller = other.p1_controller.or(self.p1_controller);
+        self.tv_standard = other.tv_standard.or(self.tv_standard);
+    }
+}
+
+/// The database, as read from a JSON or TOML file.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RomDatabaseConfig {
+    /// Entries indexed by MD5 hashes, written as hexadecimal numbers.
+    roms: HashMap<String, RomInfo>,
+}
+
+pub struct RomDatabase {
+    entries: HashMap<String, RomInfo>,
+}
+
+impl RomDatabase {
+    /// Returns the database that is built into the emulator.
+    pub fn built_in() -> Self {
+        let config = parse_config(BUILT_IN_DATABASE, ConfigFormat::Toml)
+            .expect("Unable to parse the built-in ROM database");
+        return Self::from_config(config);
+    }
+
+    /// Reads a database from a JSON or TOML file, depending on the file name
+    /// extension.
+    pub fn read(path: &Path) -> Result<Self, ConfigError> {
+        return Ok(Self::from_config(read_config_file(path)?));
+    }
+
+    fn from_config(config: RomDatabaseConfig) -> Self {
+        Self {
+            entries: config
+                .roms
+                .into_iter()
+                .map(|(hash, info)| (hash.to_ascii_lowercase(), info))
+                .collect(),
+        }
+    }
+
+    /// Adds entries from another database. If both databases describe the same
+    /// ROM image, properties set by the other database take precedence.
+    pub fn with_overrides(mut self, overrides: RomDatabase) -> Self {
+        for (hash, info) in overrides.entries {
+            self.entries.entry(hash).or_default().merge(info);
+        }
+        return self;
+    }
+
+    /// Looks up a given ROM image.
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.entries.get(&rom_hash(rom))
+    }
+}
+
+/// Returns the MD5 hash of a given ROM image, formatted as a lowercase
+/// hexadecimal number.
+pub fn rom_hash(rom: &[u8]) -> String {
+    format!("{:x}", md5::compute(rom))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> RomDatabase {
+        RomDatabase::from_config(parse_config(text, ConfigFormat::Toml).unwrap())
+    }
+
+    #[test]
+    fn hashes_roms() {
+        assert_eq!(rom_hash(&[]), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(rom_hash(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+    }
+
+    #[test]
+    fn parses_built_in_database() {
+        let database = RomDatabase::built_in();
+        assert!(!database.entries.is_empty());
+        for (hash, info) in &database.entries {
+            assert_eq!(hash.len(), 32, "Invalid hash: {}", hash);
+            assert!(
+                hash.chars().all(|c| c.is_ascii_hexdigit()),
+                "Invalid hash: {}",
+                hash
+            );
+            assert!(info.title.is_some(), "Missing title: {}", hash);
+        }
+    }
+
+    #[test]
+    fn looks_up_roms() {
+        let database = parse(
+            r#"
+            [roms.900150983CD24FB0D6963F7D28E17F72]
+            title = "ABC"
+            bank_switching = "F8"
+            super_chip = true
+            p0_controller = "Paddles"
+            tv_standard = "Pal"
+            "#,
+        );
+        assert_eq!(
+            database.lookup(b"abc"),
+            Some(&RomInfo {
+                title: Some("ABC".to_string()),
+                bank_switching: Some(BankSwitching::F8),
+                super_chip: Some(true),
+                p0_controller: Some(ControllerType::Paddles),
+                p1_controller: None,
+                tv_standard: Some(TvStandard::Pal),
+            })
+        );
+        assert_eq!(database.lookup(b"abd"), None);
+    }
+
+    #[test]
+    fn merges_overrides() {
+        let database = parse(
+            r#"
+            [roms.900150983cd24fb0d6963f7d28e17f72]
+            title = "ABC"
+            p0_controller = "Paddles"
+
+            [roms.d41d8cd98f00b204e9800998ecf8427e]
+            title = "Empty"
+            "#,
+        )
+        .with_overrides(parse(
+            r#"
+            [roms.900150983cd24fb0d6963f7d28e17f72]
+            p0_controller = "Driving"
+            tv_standard = "Secam"
+
+            [roms.e99a18c428cb38d5f260853678922e03]
+            title = "abc123"
+            "#,
+        ));
+        assert_eq!(
+            database.lookup(b"abc"),
+            Some(&RomInfo {
+                title: Some("ABC".to_string()),
+                p0_controller: Some(ControllerType::Driving),
+                tv_standard: Some(TvStandard::Secam),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            database.lookup(b"").and_then(|info| info.title.as_deref()),
+            Some("Empty")
+        );
+        assert_eq!(
+            database
+                .lookup(b"abc123")
+                .and_then(|info| info.title.as_deref()),
+            Some("abc123")
+        );
+    }
+}
@@ -1,17 +1,21 @@
 #![cfg(test)]
-use crate::audio::create_consumer_and_source;
+use crate::address_space::Cartridge;
+use crate::atari::Atari;
+use crate::atari::AtariAddressSpace;
+use crate::atari::AUDIO_SAMPLE_RATE;
 use crate::colors;
+use crate::frame_renderer::FrameRendererBuilder;
 use crate::tia::VideoOutput;
-use crate::Atari;
-use crate::AtariAddressSpace;
-use crate::FrameRendererBuilder;
 use common::app::AppController;
 use common::app::Machine;
+use common::audio::create_consumer_and_source;
 use common::test_utils::as_single_hex_digit;
 use image::DynamicImage;
 use std::iter;
 use std::path::Path;
-use ya6502::memory::Rom;
+use std::time::Duration;
+use ya6502::cpu::PowerOnState;
+use ya6502::memory::RamPattern;
 
 /// Decodes a convenient, character-based representation of a TIA video output to
 /// an iterator over a `VideoOutput` structure. Useful for representing test
@@ -102,14 +106,19 @@ pub fn encode_audio<I: Iterator<Item = u8>>(outputs: I) -> String {
 
 pub fn atari_with_rom(file_name: &str) -> Atari {
     let rom = read_test_rom(file_name);
-    let address_space = Box::new(AtariAddressSpace::new(Rom::new(&rom).unwrap()));
-    let (consumer, _) = create_consumer_and_source();
+    let address_space = Box::new(AtariAddressSpace::new(
+        Cartridge::with_detected_bank_switching(&rom).unwrap(),
+        RamPattern::default(),
+        PowerOnState::Seeded(0),
+    ));
+    let (consumer, _) = create_consumer_and_source(AUDIO_SAMPLE_RATE, Duration::from_millis(50));
     let mut atari = Atari::new(
         address_space,
         FrameRendererBuilder::new()
             .with_palette(colors::ntsc_palette())
             .build(),
         consumer,
+        PowerOnState::Seeded(0),
     );
     atari.reset();
     return atari;
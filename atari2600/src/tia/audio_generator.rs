@@ -1,31 +1,48 @@
+/// A single-channel TIA audio generator.
+///
+/// The implementation follows the audio circuit from the TIA schematics, which
+/// consists of a frequency divider and two shift registers: a 4-bit pulse
+/// counter and a 5-bit noise counter. The `AUDCx` register selects feedback
+/// logic for both registers, and it may also make the noise counter control
+/// when the pulse counter shifts. Depending on the mode, they become either
+/// simple dividers or polynomial counters (poly4, poly5, and in case of mode 8,
+/// a single 9-bit one). The generated sample is taken from the lowest bit of
+/// the pulse counter. Note that the circuit uses inverting stages, so the pulse
+/// counter shifts through negated values.
+///
+/// Each tick is performed in two phases, just like on the real chip: first,
+/// the feedback is computed and the frequency divider advances; then, if the
+/// divider allows it, the counters shift.
 #[derive(Debug)]
 pub struct AudioGenerator {
     volume: u8,
     pattern: u8,
     frequency_divider: u8,
-    frequency_counter: u8,
-    div2: u32,
-    div3: u32,
-    div31: u32,
-    poly4: u32,
-    poly5: u32,
-    poly9: u32,
+    divider_counter: u8,
+    /// Set when the frequency divider allows the counters to shift.
+    clock_enabled: bool,
+    pulse_counter: u8,
+    noise_counter: u8,
+    /// Holds the pulse counter, so that it's controlled by the noise counter.
+    pulse_counter_hold: bool,
+    noise_feedback: bool,
+    /// The bit that has just been shifted out of the noise counter.
+    noise_counter_bit4: bool,
 }
 
-/// A single-channel TIA audio generator.
 impl AudioGenerator {
     pub fn new() -> Self {
         Self {
             volume: 0,
             pattern: 0,
             frequency_divider: 0,
-            frequency_counter: 0,
-            div2: 1,
-            div3: 0,
-            div31: 0,
-            poly4: 0b1111,
-            poly5: 0b0001_1111,
-            poly9: 0b0001_1111_1111,
+            divider_counter: 0,
+            clock_enabled: false,
+            pulse_counter: 0,
+            noise_counter: 0,
+            pulse_counter_hold: false,
+            noise_feedback: false,
+            noise_counter_bit4: false,
         }
     }
 
@@ -44,63 +61,79 @@ impl AudioGenerator {
     /// Performs a single tick of audio generator. It's supposed to be called
     /// twice per scanline. Returns a sample from a [0,15] range.
     pub fn tick(&mut self) -> u8 {
-        // Note: the implementation was based on
-        // https://problemkaputt.de/2k6specs.htm#audio, but there are a couple
-        // of places where it's contradicting or unclear; some test cases are
-        // corrected based on my attempts to interpret the TIA schematics.
-        // Unfortunately, the audio circuit looks like something a drunk
-        // designer scribbled randomly during one Saturday night, and it's for a
-        // good reason; after all, a lot of it is dedicated to generate noise.
-        // But it also means I have no idea what I'm doing here, and let's
-        // consider this implementation a "best effort".
-        let output = match self.pattern {
-            0x0 | 0xB => 1,
-            0x1 | 0x2 | 0x3 => self.poly4 & 0b1,
-            0x4 | 0x5 => self.div2,
-            0x6 | 0xA | 0xE => {
-                if self.div31 < 18 {
-                    1
-                } else {
-                    0
+        self.phase0();
+        self.phase1();
+        return (self.pulse_counter & 0b1) * self.volume;
+    }
+
+    /// Computes the noise feedback and decides whether the pulse counter will
+    /// shift, then advances the frequency divider.
+    fn phase0(&mut self) {
+        if self.clock_enabled {
+            self.noise_counter_bit4 = self.noise_counter & 0b1 != 0;
+            self.pulse_counter_hold = match self.pattern & 0b11 {
+                // Divide by 31 (the pulse counter only shifts twice per noise
+                // counter period).
+                0b10 => self.noise_counter & 0b1_1110 != 0b0_0010,
+                // Shift along with the poly5 output.
+                0b11 => !self.noise_counter_bit4,
+                _ => false,
+            };
+            self.noise_feedback = match self.pattern & 0b11 {
+                // Together with the pulse counter, the noise counter forms a
+                // single poly9 register in mode 8. In mode 0, the feedback is
+                // simply kept high.
+                0b00 => {
+                    (self.pulse_counter ^ self.noise_counter) & 0b1 != 0
+                        || (self.noise_counter == 0 && self.pulse_counter == 0b1010)
+                        || self.pattern & 0b1100 == 0
                 }
-            }
-            0x7 | 0x9 | 0xF => self.poly5 & 0b1,
-            0x8 => self.poly9 & 0b1,
-            0xC | 0xD => self.div2,
-            _ => 0,
-        } as u8
-            * self.volume;
+                // Poly5, with a protection against getting stuck at zero.
+                _ => {
+                    ((self.noise_counter >> 2) ^ self.noise_counter) & 0b1 != 0
+                        || self.noise_counter == 0
+                }
+            };
+        }
 
-        if self.frequency_counter >= self.frequency_divider {
-            self.frequency_counter = 0;
-            self.div3 = (self.div3 + 1) % 3;
-            if self.pattern != 0xE || self.div3 == 0 {
-                self.div31 = (self.div31 + 1) % 31;
-            }
-            if (self.pattern != 0xC && self.pattern != 0xD) || self.div3 == 0 {
-                self.div2 = (self.div2 + 1) % 2;
-            }
-            let poly5_carry = self.poly5 & 0b1 != 0;
-            if self.pattern != 0xF || self.div3 == 0 {
-                let new_bit = (((self.poly5 & 0b1) << 2) ^ (self.poly5 & 0b100)) << 2;
-                self.poly5 = (self.poly5 >> 1) | new_bit;
-            }
+        self.clock_enabled = self.divider_counter == self.frequency_divider;
+        if self.divider_counter == self.frequency_divider || self.divider_counter == 0b1_1111 {
+            self.divider_counter = 0;
+        } else {
+            self.divider_counter += 1;
+        }
+    }
 
-            if match self.pattern {
-                0x2 => self.div31 == 0 || self.div31 == 18,
-                0x3 => poly5_carry,
-                _ => true,
-            } {
-                let new_bit = (((self.poly4 & 0b1) << 1) ^ (self.poly4 & 0b10)) << 2;
-                self.poly4 = (self.poly4 >> 1) | new_bit;
+    /// Shifts the counters if the frequency divider allows it.
+    fn phase1(&mut self) {
+        if !self.clock_enabled {
+            return;
+        }
+        let pulse_feedback = match self.pattern >> 2 {
+            // Poly4; in mode 0, the output gets stuck at 1.
+            0b00 => {
+                ((self.pulse_counter >> 1) ^ self.pulse_counter) & 0b1 != 0
+                    && self.pulse_counter != 0b1010
+                    && self.pattern & 0b11 != 0
             }
+            // Divide by 2.
+            0b01 => self.pulse_counter & 0b1000 == 0,
+            // Follow the noise counter output.
+            0b10 => !self.noise_counter_bit4,
+            // Divide by 6.
+            _ => !(self.pulse_counter & 0b0010 != 0 || self.pulse_counter & 0b1110 == 0),
+        };
 
-            let new_bit = (((self.poly9 & 0b1) << 4) ^ (self.poly9 & 0b1_0000)) << 4;
-            self.poly9 = (self.poly9 >> 1) | new_bit;
-        } else {
-            self.frequency_counter += 1;
+        self.noise_counter >>= 1;
+        if self.noise_feedback {
+            self.noise_counter |= 0b1_0000;
         }
 
-        return output;
+        if !self.pulse_counter_hold {
+            self.pulse_counter = !(self.pulse_counter >> 1) & 0b0111;
+            if pulse_feedback {
+                self.pulse_counter |= 0b1000;
+            }
+        }
     }
 }
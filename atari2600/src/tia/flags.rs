@@ -7,6 +7,8 @@ pub const VSYNC_ON: u8 = 0b0000_0010;
 pub const VBLANK_ON: u8 = 0b0000_0010;
 /// Bit mask for turning on input latches using `VBLANK` register.
 pub const VBLANK_INPUT_LATCH: u8 = 0b0100_0000;
+/// Bit mask for grounding the paddle input ports using `VBLANK` register.
+pub const VBLANK_DUMP_PADDLES: u8 = 0b1000_0000;
 
 pub const NUSIZX_ONE_COPY: u8 = 0b0000_0000;
 #[allow(dead_code)]
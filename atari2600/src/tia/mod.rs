@@ -1,14 +1,18 @@
 mod audio_generator;
 mod delay_buffer;
-mod flags;
-mod registers;
+pub(crate) mod flags;
+pub(crate) mod registers;
 mod sprite;
 mod tests;
 
 use audio_generator::AudioGenerator;
+use common::debugger::symbols::RegisterNames;
+use common::scheduler::CpuScheduler;
 use delay_buffer::DelayBuffer;
 use enum_map::{enum_map, Enum, EnumMap};
 use sprite::{missile_reset_delay_for_player, set_reg_nusiz, Sprite};
+use ya6502::cpu::ChipRegister;
+use ya6502::cpu::ChipState;
 use ya6502::memory::Inspect;
 use ya6502::memory::Read;
 use ya6502::memory::Write;
@@ -20,6 +24,15 @@ pub enum Port {
     Input5,
 }
 
+/// Input ports 0-3, which are used by paddle controllers.
+#[derive(Debug, Enum, Copy, Clone)]
+pub enum PaddlePort {
+    Input0,
+    Input1,
+    Input2,
+    Input3,
+}
+
 #[derive(Debug, Copy, Clone)]
 enum ScreenHalf {
     Left,
@@ -35,6 +48,7 @@ pub struct Tia {
     reg_vsync: u8,
     /// If bit 1 (`flags::VBLANK_ON`) is set, TIA doesn't emit pixels. Bit 6
     /// (`flags::VBLANK_INPUT_LATCH`) enables latches on input ports 4 and 5.
+    /// Bit 7 (`flags::VBLANK_DUMP_PADDLES`) grounds input ports 0-3.
     reg_vblank: u8,
     /// Color and luminance of player 0. See
     /// [`VideoOutput::pixel`](struct.VideoOutput.html#structfield.pixel) for details.
@@ -82,14 +96,22 @@ pub struct Tia {
     hblank_on: bool,
     /// Indicates whether a horizontal sync signal is being generated.
     hsync_on: bool,
-    /// Holds CPU ticks until we reach the end of a scanline.
-    wait_for_sync: bool,
+    /// Divides the color clock to get the CPU clock, and holds CPU ticks
+    /// until we reach the end of a scanline.
+    cpu_scheduler: CpuScheduler,
     /// Temporarily latches playfield bits for rendering.
     playfield_buffer: DelayBuffer<bool>,
-    /// Latches the HMOVE signal until end of the scanline.
+    /// Latches the HMOVE signal until end of the scanline. It's only set if
+    /// HMOVE gets strobed during the horizontal blank, and it extends the blank
+    /// by 8 pixels, producing the infamous "HMOVE comb".
     hmove_latch: bool,
+    /// Set when HMOVE gets strobed. The motion counter doesn't start until the
+    /// next Hφ1 clock (which occurs every 4 pixels).
+    hmove_pending: bool,
     /// Counts from 7 down to -8 while additional clock ticks are sent to the
-    /// player graphics objects.
+    /// player graphics objects. The value of -8 means that the counter is
+    /// stopped. Note that the counter runs independently of the scanline, so
+    /// a late HMOVE keeps moving the objects on the next scanline.
     hmove_counter: i8,
     /// Indicates which screen half (left or right) we're currently rendering.
     screen_half: ScreenHalf,
@@ -105,6 +127,19 @@ pub struct Tia {
     // "Raw" values on the input port pins. They don't necessarily directly
     // reflect `reg_inpt`, since they are not latched.
     input_ports: EnumMap<Port, bool>,
+    /// Resistances of paddles connected to input ports 0-3, expressed as a
+    /// number of scanlines that it takes to charge the port's capacitor.
+    /// `None` if there's nothing connected.
+    paddle_resistances: EnumMap<PaddlePort, Option<u8>>,
+    /// Number of cycles since paddle capacitors have been released from
+    /// ground using the `VBLANK` register.
+    paddle_charging_cycles: u32,
+}
+
+impl Default for Tia {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Tia {
@@ -137,10 +172,11 @@ impl Tia {
             column_counter: 0,
             hsync_on: false,
             hblank_on: false,
-            wait_for_sync: false,
+            cpu_scheduler: CpuScheduler::new(3),
             playfield_buffer: DelayBuffer::new(2),
             hmove_latch: false,
-            hmove_counter: 0,
+            hmove_pending: false,
+            hmove_counter: -8,
             screen_half: ScreenHalf::Left,
 
             player0: Sprite::new(),
@@ -152,16 +188,59 @@ impl Tia {
             audio1: AudioGenerator::new(),
 
             input_ports: enum_map! { _ => true },
+            paddle_resistances: enum_map! { _ => None },
+            paddle_charging_cycles: 0,
         }
     }
 
     /// Processes a single TIA clock cycle. Returns a TIA output structure. A
     /// single cycle is the time needed to render a single pixel.
     pub fn tick(&mut self) -> TiaOutput {
+        let registers = self.register_snapshot();
+        self.tick_with(&registers)
+    }
+
+    /// Processes TIA clock cycles up to (and including) the next CPU clock
+    /// cycle, appending their outputs to a given vector. Registers can only be
+    /// changed by the CPU, so the result is the same as calling [`Tia::tick`]
+    /// repeatedly, but the registers are only decoded once per run.
+    ///
+    /// A run ends at every CPU clock cycle, even if the CPU waits for the
+    /// horizontal sync, so it's never longer than 3 pixels, and the TIA is
+    /// never more than 2 pixels ahead of the consumed outputs.
+    pub fn tick_until_cpu_cycle(&mut self, outputs: &mut Vec<TiaOutput>) {
+        let registers = self.register_snapshot();
+        loop {
+            let output = self.tick_with(&registers);
+            outputs.push(output);
+            if output.riot_tick {
+                return;
+            }
+        }
+    }
+
+    fn register_snapshot(&self) -> RegisterSnapshot {
+        let playfield_priority = self.reg_ctrlpf & flags::CTRLPF_PRIORITY != 0;
+        RegisterSnapshot {
+            vsync_on: self.reg_vsync & flags::VSYNC_ON != 0,
+            vblank_on: self.reg_vblank & flags::VBLANK_ON != 0,
+            dump_paddles: self.reg_vblank & flags::VBLANK_DUMP_PADDLES != 0,
+            resmp0: self.reg_resmp0 & flags::RESMPX_RESET != 0,
+            resmp1: self.reg_resmp1 & flags::RESMPX_RESET != 0,
+            playfield_priority,
+            // In the score mode, the playfield (but not the ball) takes both
+            // the color and the priority of player 0 on the left half of the
+            // screen, and of player 1 on the right half. The playfield
+            // priority flag overrides the score mode.
+            score_mode: self.reg_ctrlpf & flags::CTRLPF_SCORE != 0 && !playfield_priority,
+        }
+    }
+
+    fn tick_with(&mut self, registers: &RegisterSnapshot) -> TiaOutput {
         match self.column_counter {
             0 => {
                 self.hblank_on = true;
-                self.wait_for_sync = false;
+                self.cpu_scheduler.sync();
                 self.screen_half = ScreenHalf::Left;
             }
             HSYNC_START => self.hsync_on = true,
@@ -183,16 +262,15 @@ impl Tia {
             _ => {}
         }
 
-        let vsync_on = self.reg_vsync & flags::VSYNC_ON != 0;
-        let vblank_on = self.reg_vblank & flags::VBLANK_ON != 0;
+        if registers.dump_paddles {
+            self.paddle_charging_cycles = 0;
+        } else {
+            self.paddle_charging_cycles = self.paddle_charging_cycles.saturating_add(1);
+        }
+
         let playfield_bit = self.playfield_tick();
-        if self.hmove_latch && self.hmove_counter > -8 && self.column_counter % 4 == 0 {
-            self.player0.hmove_tick(self.hmove_counter);
-            self.player1.hmove_tick(self.hmove_counter);
-            self.missile0.hmove_tick(self.hmove_counter);
-            self.missile1.hmove_tick(self.hmove_counter);
-            self.ball.hmove_tick(self.hmove_counter);
-            self.hmove_counter -= 1;
+        if self.column_counter % 4 == 0 {
+            self.hmove_phi1_tick();
         }
 
         let p0_bit = self.player0.tick(!self.hblank_on);
@@ -204,19 +282,17 @@ impl Tia {
         let pixel = if self.hblank_on {
             None
         } else {
-            let resmp0 = self.reg_resmp0 & flags::RESMPX_RESET != 0;
-            let resmp1 = self.reg_resmp1 & flags::RESMPX_RESET != 0;
-            let m0_bit = !resmp0 && m0_bit;
-            let m1_bit = !resmp1 && m1_bit;
-            if resmp0 && self.player0.position_counter() == 1 {
+            let m0_bit = !registers.resmp0 && m0_bit;
+            let m1_bit = !registers.resmp1 && m1_bit;
+            if registers.resmp0 && self.player0.position_counter() == 1 {
                 self.missile0
                     .reset_position(missile_reset_delay_for_player(&self.player0));
             }
-            if resmp1 && self.player1.position_counter() == 1 {
+            if registers.resmp1 && self.player1.position_counter() == 1 {
                 self.missile1
                     .reset_position(missile_reset_delay_for_player(&self.player1));
             }
-            if vblank_on {
+            if registers.vblank_on {
                 None
             } else {
                 if m0_bit && p1_bit {
@@ -264,23 +340,24 @@ impl Tia {
                 if m0_bit && m1_bit {
                     self.reg_cxppmm |= 1 << 6;
                 }
+                let score_mode = registers.score_mode;
+                let (p0_layer, p1_layer) = match self.screen_half {
+                    ScreenHalf::Left if score_mode => {
+                        (p0_bit || m0_bit || playfield_bit, p1_bit || m1_bit)
+                    }
+                    ScreenHalf::Right if score_mode => {
+                        (p0_bit || m0_bit, p1_bit || m1_bit || playfield_bit)
+                    }
+                    _ => (p0_bit || m0_bit, p1_bit || m1_bit),
+                };
                 Some(
-                    // TODO: Need to tweak priorities in the score mode.
-                    if self.reg_ctrlpf & flags::CTRLPF_PRIORITY != 0 && (playfield_bit || ball_bit)
-                    {
+                    if registers.playfield_priority && (playfield_bit || ball_bit) {
                         self.reg_colupf
-                    } else if self.reg_ctrlpf & flags::CTRLPF_SCORE != 0 && playfield_bit {
-                        match self.screen_half {
-                            ScreenHalf::Left => self.reg_colup0,
-                            ScreenHalf::Right => self.reg_colup1,
-                        }
-                    } else if p0_bit || m0_bit {
+                    } else if p0_layer {
                         self.reg_colup0
-                    } else if p1_bit || m1_bit {
+                    } else if p1_layer {
                         self.reg_colup1
-                    } else if self.reg_ctrlpf & flags::CTRLPF_PRIORITY == 0
-                        && (playfield_bit || ball_bit)
-                    {
+                    } else if playfield_bit || ball_bit {
                         self.reg_colupf
                     } else {
                         self.reg_colubk
@@ -289,15 +366,16 @@ impl Tia {
             }
         };
 
+        let scheduled_tick = self.cpu_scheduler.tick();
         let output = TiaOutput {
             video: VideoOutput {
                 hsync: self.hsync_on,
-                vsync: vsync_on,
+                vsync: registers.vsync_on,
                 pixel,
             },
             audio: self.audio_tick(),
-            riot_tick: self.column_counter % 3 == 0,
-            cpu_tick: !self.wait_for_sync && self.column_counter % 3 == 0,
+            riot_tick: scheduled_tick.chip_cycle,
+            cpu_tick: scheduled_tick.cpu_cycle,
         };
 
         self.column_counter = (self.column_counter + 1) % TOTAL_WIDTH;
@@ -368,6 +446,97 @@ impl Tia {
         self.update_port_register(port);
     }
 
+    /// Connects a paddle with a given resistance to one of the input ports 0-3,
+    /// or disconnects it if `resistance` is `None`. See
+    /// [`paddle_resistances`](#structfield.paddle_resistances) for details.
+    pub fn set_paddle_resistance(&mut self, port: PaddlePort, resistance: Option<u8>) {
+        self.paddle_resistances[port] = resistance;
+    }
+
+    /// Computes the value of a paddle input port register. The port reads as
+    /// high once its capacitor is charged.
+    fn paddle_port_register(&self, port: PaddlePort) -> u8 {
+        let dumped = self.reg_vblank & flags::VBLANK_DUMP_PADDLES != 0;
+        let charged = match self.paddle_resistances[port] {
+            Some(resistance) => self.paddle_charging_cycles >= resistance as u32 * TOTAL_WIDTH,
+            None => false,
+        };
+        return if !dumped && charged {
+            flags::INPUT_HIGH
+        } else {
+            0
+        };
+    }
+
+    /// Advances the HMOVE motion counter on an Hφ1 clock and sends the
+    /// additional clock ticks to the graphics objects. During the horizontal
+    /// blank, these are the only ticks that the objects receive. On the visible
+    /// part of the scanline, the additional ticks coincide with the regular
+    /// ones, and so they are lost; this is why an HMOVE strobed in the middle
+    /// of a scanline only partially moves the objects (or doesn't move them at
+    /// all).
+    fn hmove_phi1_tick(&mut self) {
+        if self.hmove_pending {
+            self.hmove_pending = false;
+            self.hmove_counter = 7;
+        }
+        if self.hmove_counter == -8 {
+            return;
+        }
+        if self.hblank_on {
+            self.player0.hmove_tick(self.hmove_counter);
+            self.player1.hmove_tick(self.hmove_counter);
+            self.missile0.hmove_tick(self.hmove_counter);
+            self.missile1.hmove_tick(self.hmove_counter);
+            self.ball.hmove_tick(self.hmove_counter);
+        }
+        self.hmove_counter -= 1;
+    }
+
+    /// Reports the values of TIA registers for debugging purposes. Most of the
+    /// registers are write-only, so they are reported as they were last
+    /// written.
+    pub fn chip_state(&self) -> Result<ChipState, ReadError> {
+        let mut registers = vec![
+            ChipRegister::new("VSYNC", self.reg_vsync),
+            ChipRegister::new("VBLANK", self.reg_vblank),
+            ChipRegister::new("COLUP0", self.reg_colup0),
+            ChipRegister::new("COLUP1", self.reg_colup1),
+            ChipRegister::new("COLUPF", self.reg_colupf),
+            ChipRegister::new("COLUBK", self.reg_colubk),
+            ChipRegister::new("CTRLPF", self.reg_ctrlpf),
+            ChipRegister::new("PF0", self.reg_pf0),
+            ChipRegister::new("PF1", self.reg_pf1),
+            ChipRegister::new("PF2", self.reg_pf2),
+            ChipRegister::new("RESMP0", self.reg_resmp0),
+            ChipRegister::new("RESMP1", self.reg_resmp1),
+        ];
+        let readable_registers = [
+            ("CXM0P", registers::CXM0P),
+            ("CXM1P", registers::CXM1P),
+            ("CXP0FB", registers::CXP0FB),
+            ("CXP1FB", registers::CXP1FB),
+            ("CXM0FB", registers::CXM0FB),
+            ("CXM1FB", registers::CXM1FB),
+            ("CXBLPF", registers::CXBLPF),
+            ("CXPPMM", registers::CXPPMM),
+            ("INPT0", registers::INPT0),
+            ("INPT1", registers::INPT1),
+            ("INPT2", registers::INPT2),
+            ("INPT3", registers::INPT3),
+            ("INPT4", registers::INPT4),
+            ("INPT5", registers::INPT5),
+        ];
+        for (name, address) in readable_registers {
+            registers.push(ChipRegister::new(name, self.inspect(address)?));
+        }
+        registers.push(ChipRegister::new("COLUMN", self.column_counter as u16));
+        return Ok(ChipState {
+            name: "TIA".to_string(),
+            registers,
+        });
+    }
+
     fn update_port_register(&mut self, port: Port) {
         let port_value = self.input_ports[port];
         let reg_previous = self.reg_inpt[port] != 0;
@@ -389,6 +558,10 @@ impl Inspect for Tia {
             registers::CXM1FB => Ok(self.reg_cxm1fb),
             registers::CXBLPF => Ok(self.reg_cxblpf),
             registers::CXPPMM => Ok(self.reg_cxppmm),
+            registers::INPT0 => Ok(self.paddle_port_register(PaddlePort::Input0)),
+            registers::INPT1 => Ok(self.paddle_port_register(PaddlePort::Input1)),
+            registers::INPT2 => Ok(self.paddle_port_register(PaddlePort::Input2)),
+            registers::INPT3 => Ok(self.paddle_port_register(PaddlePort::Input3)),
             registers::INPT4 => Ok(self.reg_inpt[Port::Input4]),
             registers::INPT5 => Ok(self.reg_inpt[Port::Input5]),
             _ => Err(ReadError { address }),
@@ -411,8 +584,11 @@ impl Write for Tia {
                 self.update_port_register(Port::Input4);
                 self.update_port_register(Port::Input5);
             }
-            registers::WSYNC => self.wait_for_sync = true,
-            registers::RSYNC => self.column_counter = TOTAL_WIDTH - 3,
+            registers::WSYNC => self.cpu_scheduler.wait_for_sync(),
+            registers::RSYNC => {
+                self.column_counter = TOTAL_WIDTH - 3;
+                self.cpu_scheduler.restart();
+            }
             registers::NUSIZ0 => {
                 set_reg_nusiz(&mut self.player0, &mut self.missile0, value);
             }
@@ -467,10 +643,14 @@ impl Write for Tia {
             registers::VDELBL => self.ball.set_reg_vdel(value),
             registers::RESMP0 => self.reg_resmp0 = value,
             registers::RESMP1 => self.reg_resmp1 = value,
-            // Note: there is an additional delay here, but it requires emulating the Hφ1 signal.
             registers::HMOVE => {
-                self.hmove_latch = true;
-                self.hmove_counter = 7;
+                // The blank is only extended if HMOVE gets strobed before
+                // the blank would normally end. This is why a late HMOVE (at
+                // the end of the previous scanline) doesn't produce the comb.
+                if self.column_counter < HBLANK_WIDTH {
+                    self.hmove_latch = true;
+                }
+                self.hmove_pending = true;
             }
             registers::HMCLR => {
                 self.player0.set_reg_hm(0);
@@ -498,8 +678,97 @@ impl Write for Tia {
 
 impl Memory for Tia {}
 
+/// Returns names of the TIA registers, used to annotate the disassembly. The
+/// TIA only decodes 6 address lines for writing and 4 for reading, so the read
+/// registers are repeated throughout the $00-$3F block.
+pub fn register_names() -> RegisterNames {
+    use registers::*;
+    let read_registers = [
+        ("CXM0P", CXM0P),
+        ("CXM1P", CXM1P),
+        ("CXP0FB", CXP0FB),
+        ("CXP1FB", CXP1FB),
+        ("CXM0FB", CXM0FB),
+        ("CXM1FB", CXM1FB),
+        ("CXBLPF", CXBLPF),
+        ("CXPPMM", CXPPMM),
+        ("INPT0", INPT0),
+        ("INPT1", INPT1),
+        ("INPT2", INPT2),
+        ("INPT3", INPT3),
+        ("INPT4", INPT4),
+        ("INPT5", INPT5),
+    ];
+    RegisterNames::new()
+        .with_write_registers([
+            ("VSYNC", VSYNC),
+            ("VBLANK", VBLANK),
+            ("WSYNC", WSYNC),
+            ("RSYNC", RSYNC),
+            ("NUSIZ0", NUSIZ0),
+            ("NUSIZ1", NUSIZ1),
+            ("COLUP0", COLUP0),
+            ("COLUP1", COLUP1),
+            ("COLUPF", COLUPF),
+            ("COLUBK", COLUBK),
+            ("CTRLPF", CTRLPF),
+            ("REFP0", REFP0),
+            ("REFP1", REFP1),
+            ("PF0", PF0),
+            ("PF1", PF1),
+            ("PF2", PF2),
+            ("RESP0", RESP0),
+            ("RESP1", RESP1),
+            ("RESM0", RESM0),
+            ("RESM1", RESM1),
+            ("RESBL", RESBL),
+            ("AUDC0", AUDC0),
+            ("AUDC1", AUDC1),
+            ("AUDF0", AUDF0),
+            ("AUDF1", AUDF1),
+            ("AUDV0", AUDV0),
+            ("AUDV1", AUDV1),
+            ("GRP0", GRP0),
+            ("GRP1", GRP1),
+            ("ENAM0", ENAM0),
+            ("ENAM1", ENAM1),
+            ("ENABL", ENABL),
+            ("HMP0", HMP0),
+            ("HMP1", HMP1),
+            ("HMM0", HMM0),
+            ("HMM1", HMM1),
+            ("HMBL", HMBL),
+            ("VDELP0", VDELP0),
+            ("VDELP1", VDELP1),
+            ("VDELBL", VDELBL),
+            ("RESMP0", RESMP0),
+            ("RESMP1", RESMP1),
+            ("HMOVE", HMOVE),
+            ("HMCLR", HMCLR),
+            ("CXCLR", CXCLR),
+        ])
+        .with_read_registers(
+            read_registers
+                .into_iter()
+                .flat_map(|(name, address)| (address..0x40).step_by(0x10).map(move |a| (name, a))),
+        )
+}
+
+/// Register values that affect rendering, decoded once per a run of clock
+/// cycles. See [`Tia::tick_until_cpu_cycle`].
+struct RegisterSnapshot {
+    vsync_on: bool,
+    vblank_on: bool,
+    dump_paddles: bool,
+    resmp0: bool,
+    resmp1: bool,
+    playfield_priority: bool,
+    score_mode: bool,
+}
+
 /// TIA output structure. It indicates how a single TIA clock tick influences
 /// other parts of the system.
+#[derive(PartialEq, Copy, Clone, Debug)]
 pub struct TiaOutput {
     pub video: VideoOutput,
     pub audio: Option<AudioOutput>,
@@ -572,6 +841,7 @@ pub const SCREEN_CENTER: u32 = HBLANK_WIDTH + FRAME_WIDTH / 2;
 pub const LAST_COLUMN: u32 = TOTAL_WIDTH - 1;
 pub const TOTAL_WIDTH: u32 = FRAME_WIDTH + HBLANK_WIDTH;
 
+#[derive(PartialEq, Copy, Clone, Debug)]
 pub struct AudioOutput {
     pub au0: u8,
     pub au1: u8,
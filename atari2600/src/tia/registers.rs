@@ -57,9 +57,9 @@ pub const CXM0FB: u16 = 0x04;
 pub const CXM1FB: u16 = 0x05;
 pub const CXBLPF: u16 = 0x06;
 pub const CXPPMM: u16 = 0x07;
-// pub const INPT0: u16 = 0x08;
-// pub const INPT1: u16 = 0x09;
-// pub const INPT2: u16 = 0x0A;
-// pub const INPT3: u16 = 0x0B;
+pub const INPT0: u16 = 0x08;
+pub const INPT1: u16 = 0x09;
+pub const INPT2: u16 = 0x0A;
+pub const INPT3: u16 = 0x0B;
 pub const INPT4: u16 = 0x0C;
 pub const INPT5: u16 = 0x0D;
@@ -38,8 +38,11 @@ const MISSILE_OFFSETS: [&[i32]; 8] = [
 #[derive(Debug)]
 pub struct Sprite {
     position_counter: i32,
-    /// Position counter value where the current sprite copy was started.
-    current_start: i32,
+    /// Counts pixels drawn using the current bit. Since it's independent from
+    /// the position counter, strobing RESPx doesn't disturb a sprite copy that
+    /// is currently being drawn, and changing NUSIZx in the middle of drawing
+    /// a copy immediately stretches or shrinks its remaining pixels.
+    bit_pixel_counter: i32,
     /// A list of position counter values that trigger a "start drawing" signal.
     offsets: &'static [i32],
     scale: i32,
@@ -65,7 +68,7 @@ impl Sprite {
     pub fn new() -> Self {
         Sprite {
             position_counter: 0,
-            current_start: 0,
+            bit_pixel_counter: 0,
             offsets: PLAYER_OFFSETS[flags::NUSIZX_ONE_COPY as usize],
             scale: 1,
             bitmaps: [0b0000_0000, 0b0000_0000],
@@ -136,15 +139,16 @@ impl Sprite {
                 .shift(self.offsets.contains(&self.position_counter));
             if start {
                 self.current_bit = Some(7);
-                self.current_start = self.position_counter;
+                self.bit_pixel_counter = 0;
             }
             let mask = self.mask_buffer.shift(match self.current_bit {
                 None => 0,
                 Some(bit) => 1 << if self.reflect { 7 - bit } else { bit },
             });
             self.position_counter = (self.position_counter + 1) % 160;
-            let go_to_next_bit = (self.position_counter - self.current_start) % self.scale == 0;
-            if go_to_next_bit {
+            self.bit_pixel_counter += 1;
+            if self.bit_pixel_counter >= self.scale {
+                self.bit_pixel_counter = 0;
                 self.current_bit = match self.current_bit {
                     None | Some(0) => None,
                     Some(bit) => Some(bit - 1),
@@ -154,6 +154,67 @@ fn freezes_cpu_until_wsync() {
     assert_eq!(tia.tick().cpu_tick, true);
 }
 
+#[test]
+fn ticks_until_cpu_cycle() {
+    let mut tia = Tia::new();
+    let mut outputs = vec![];
+    tia.tick_until_cpu_cycle(&mut outputs);
+    assert_eq!(outputs.len(), 1);
+    tia.tick_until_cpu_cycle(&mut outputs);
+    assert_eq!(outputs.len(), 4);
+    itertools::assert_equal(
+        outputs.iter().map(|output| output.cpu_tick),
+        [true, false, false, true],
+    );
+
+    // Even if WSYNC is turned on, the run stops at the next CPU cycle, so that
+    // the TIA doesn't get ahead of the CPU.
+    tia.write(registers::WSYNC, 0x00).unwrap();
+    outputs.clear();
+    tia.tick_until_cpu_cycle(&mut outputs);
+    itertools::assert_equal(
+        outputs
+            .iter()
+            .map(|output| (output.riot_tick, output.cpu_tick)),
+        [(false, false), (false, false), (true, false)],
+    );
+    assert_eq!(tia.column_counter, 7);
+}
+
+#[test]
+fn ticks_until_cpu_cycle_same_as_single_ticks() {
+    let setup = |tia: &mut Tia| {
+        tia.write(registers::COLUBK, 0x02).unwrap();
+        tia.write(registers::COLUPF, 0x04).unwrap();
+        tia.write(registers::COLUP0, 0x06).unwrap();
+        tia.write(registers::PF1, 0b1010_0101).unwrap();
+        tia.write(registers::GRP0, 0b1100_0011).unwrap();
+        tia.write(registers::CTRLPF, flags::CTRLPF_SCORE).unwrap();
+    };
+    let mut single_tia = Tia::new();
+    let mut batch_tia = Tia::new();
+    setup(&mut single_tia);
+    setup(&mut batch_tia);
+
+    let mut single_outputs = vec![];
+    let mut batch_outputs = vec![];
+    for _ in 0..3 {
+        while batch_outputs.len() < 2 * TOTAL_WIDTH as usize {
+            batch_tia.tick_until_cpu_cycle(&mut batch_outputs);
+        }
+        while single_outputs.len() < batch_outputs.len() {
+            single_outputs.push(single_tia.tick());
+        }
+        single_tia.write(registers::WSYNC, 0x00).unwrap();
+        batch_tia.write(registers::WSYNC, 0x00).unwrap();
+        single_tia.write(registers::RESP0, 0x00).unwrap();
+        batch_tia.write(registers::RESP0, 0x00).unwrap();
+        itertools::assert_equal(&single_outputs, &batch_outputs);
+        single_outputs.clear();
+        batch_outputs.clear();
+    }
+}
+
 #[test]
 fn tells_riot_to_tick_every_three_cycles() {
     let mut tia = Tia::new();
@@ -413,6 +474,57 @@ fn moves_sprites() {
     );
 }
 
+#[test]
+fn hmove_timing() {
+    let mut tia = Tia::new();
+    tia.write(registers::COLUBK, 0x02).unwrap();
+    tia.write(registers::COLUP0, 0x04).unwrap();
+    tia.write(registers::GRP0, 0b1111_0000).unwrap();
+    tia.write(registers::HMP0, 3 << 4).unwrap();
+
+    let p0_delay = 30 * 3;
+    wait_ticks(&mut tia, p0_delay);
+    tia.write(registers::RESP0, 0).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH - p0_delay);
+
+    // HMOVE strobed in the middle of a scanline doesn't extend the horizontal
+    // blank, and since all the additional clock ticks fall on the visible part
+    // of the scanline, the player doesn't move at all.
+    let mut scanline = scan_video(&mut tia, 100);
+    tia.write(registers::HMOVE, 0).unwrap();
+    scanline.append(&mut scan_video(&mut tia, TOTAL_WIDTH - 100));
+    assert_eq!(
+        encode_video_outputs(scanline),
+        "................||||||||||||||||....................................\
+         22222222222222222222222222222444422222222222222222222222222222222222222222222222\
+         22222222222222222222222222222222222222222222222222222222222222222222222222222222",
+    );
+
+    // A regular HMOVE at the beginning of a scanline produces the comb and
+    // moves the player by 3 pixels to the left.
+    tia.write(registers::HMOVE, 0).unwrap();
+    assert_eq!(
+        encode_video_outputs(scan_video(&mut tia, TOTAL_WIDTH)),
+        "................||||||||||||||||....................................\
+         ........222222222222222222444422222222222222222222222222222222222222222222222222\
+         22222222222222222222222222222222222222222222222222222222222222222222222222222222",
+    );
+
+    // A late HMOVE, strobed at the end of the previous scanline, avoids the
+    // comb. Since the blank isn't extended, the player doesn't lose 8 regular
+    // clock ticks, so it moves 8 pixels further than usual.
+    let hmove_delay = 74 * 3;
+    wait_ticks(&mut tia, hmove_delay);
+    tia.write(registers::HMOVE, 0).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH - hmove_delay);
+    assert_eq!(
+        encode_video_outputs(scan_video(&mut tia, TOTAL_WIDTH)),
+        "................||||||||||||||||....................................\
+         22222222222222244442222222222222222222222222222222222222222222222222222222222222\
+         22222222222222222222222222222222222222222222222222222222222222222222222222222222",
+    );
+}
+
 #[test]
 fn sprite_delay() {
     let mut tia = Tia::new();
@@ -605,6 +717,77 @@ fn sprite_copies() {
     );
 }
 
+#[test]
+fn resets_player_while_drawing() {
+    use flags::*;
+    let mut tia = Tia::new();
+    tia.write(registers::COLUP0, 0x02).unwrap();
+    tia.write(registers::GRP0, 0b1100_1010).unwrap();
+    tia.write(registers::NUSIZ0, NUSIZX_DOUBLE_SIZED_PLAYER)
+        .unwrap();
+
+    let p0_delay = 30 * 3;
+    wait_ticks(&mut tia, p0_delay);
+    tia.write(registers::RESP0, 0).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH - p0_delay);
+
+    // Strobing RESP0 while the player is being drawn doesn't disturb the
+    // current copy; it only affects where the next one is going to appear.
+    let reset_delay = HBLANK_WIDTH + 35;
+    let mut scanline = scan_video(&mut tia, reset_delay);
+    tia.write(registers::RESP0, 0).unwrap();
+    scanline.append(&mut scan_video(&mut tia, TOTAL_WIDTH - reset_delay));
+    scanline.append(&mut scan_video(&mut tia, TOTAL_WIDTH));
+    assert_eq!(
+        encode_video_outputs(scanline),
+        "................||||||||||||||||....................................\
+         00000000000000000000000000000022220000220022000000000000000000000000000000000000\
+         00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+         ................||||||||||||||||....................................\
+         00000000000000000000000000000000000000000002222000022002200000000000000000000000\
+         00000000000000000000000000000000000000000000000000000000000000000000000000000000",
+    );
+}
+
+#[test]
+fn changes_player_size_while_drawing() {
+    use flags::*;
+    let mut tia = Tia::new();
+    tia.write(registers::COLUP0, 0x02).unwrap();
+    tia.write(registers::GRP0, 0b1010_1010).unwrap();
+
+    let p0_delay = 30 * 3;
+    wait_ticks(&mut tia, p0_delay);
+    tia.write(registers::RESP0, 0).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH - p0_delay);
+
+    // Changing the player size in the middle of drawing stretches the
+    // remaining pixels of the current copy.
+    let nusiz_delay = HBLANK_WIDTH + 31;
+    let mut scanline = scan_video(&mut tia, nusiz_delay);
+    tia.write(registers::NUSIZ0, NUSIZX_QUAD_SIZED_PLAYER)
+        .unwrap();
+    scanline.append(&mut scan_video(&mut tia, TOTAL_WIDTH - nusiz_delay));
+    assert_eq!(
+        encode_video_outputs(scanline),
+        "................||||||||||||||||....................................\
+         00000000000000000000000000000202020000222200000000000000000000000000000000000000\
+         00000000000000000000000000000000000000000000000000000000000000000000000000000000",
+    );
+
+    // The same goes for shrinking it back.
+    let nusiz_delay = HBLANK_WIDTH + 38;
+    let mut scanline = scan_video(&mut tia, nusiz_delay);
+    tia.write(registers::NUSIZ0, NUSIZX_ONE_COPY).unwrap();
+    scanline.append(&mut scan_video(&mut tia, TOTAL_WIDTH - nusiz_delay));
+    assert_eq!(
+        encode_video_outputs(scanline),
+        "................||||||||||||||||....................................\
+         00000000000000000000000000000022220000222202020000000000000000000000000000000000\
+         00000000000000000000000000000000000000000000000000000000000000000000000000000000",
+    );
+}
+
 #[test]
 fn player_scaling() {
     use flags::*;
@@ -870,6 +1053,35 @@ fn score_mode() {
     );
 }
 
+#[test]
+fn score_mode_priorities() {
+    let mut tia = Tia::new();
+    tia.write(registers::COLUBK, 0x00).unwrap();
+    tia.write(registers::COLUPF, 0x02).unwrap();
+    tia.write(registers::COLUP0, 0x04).unwrap();
+    tia.write(registers::COLUP1, 0x06).unwrap();
+    tia.write(registers::PF1, 0b1111_0011).unwrap();
+    tia.write(registers::GRP0, 0b1010_1010).unwrap();
+    tia.write(registers::GRP1, 0b1111_1111).unwrap();
+    tia.write(registers::CTRLPF, flags::CTRLPF_SCORE).unwrap();
+
+    // Put both players on the right half of the screen.
+    let player_delay = 30 * 3 + FRAME_WIDTH / 2;
+    wait_ticks(&mut tia, player_delay);
+    tia.write(registers::RESP0, 0).unwrap();
+    tia.write(registers::RESP1, 0).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH - player_delay);
+
+    // On the right half, the playfield is drawn with player 1 color, but it's
+    // still covered by player 0.
+    assert_eq!(
+        encode_video_outputs(scan_video(&mut tia, TOTAL_WIDTH)),
+        "................||||||||||||||||....................................\
+         00000000000000004444444444444444000000004444444400000000000000000000000000000000\
+         00000000000000006666666666666464646460006666666600000000000000000000000000000000",
+    );
+}
+
 #[test]
 fn sprite_collisions() {
     let mut tia = Tia::new();
@@ -936,6 +1148,83 @@ fn sprite_collisions() {
     assert_collision_latches(&mut tia, [0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b00]);
 }
 
+#[test]
+fn ball_collisions() {
+    let mut tia = Tia::new();
+    tia.write(registers::CTRLPF, flags::CTRLPF_BALL_8).unwrap();
+    tia.write(registers::ENABL, flags::ENAXX_ENABLE).unwrap();
+    tia.write(registers::VBLANK, flags::VBLANK_ON).unwrap();
+
+    // Put all movable objects in the same place, but only show the ball.
+    let sprite_delay = 32 * 3;
+    wait_ticks(&mut tia, sprite_delay);
+    tia.write(registers::RESBL, 0).unwrap();
+    tia.write(registers::RESM0, 0).unwrap();
+    tia.write(registers::RESM1, 0).unwrap();
+    tia.write(registers::RESP0, 0).unwrap();
+    tia.write(registers::RESP1, 0).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH - sprite_delay);
+    tia.write(registers::VBLANK, 0).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH);
+    assert_collision_latches(&mut tia, [0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b00]);
+
+    // P0+BL.
+    tia.write(registers::GRP0, 0xFF).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH);
+    assert_collision_latches(&mut tia, [0b00, 0b00, 0b01, 0b00, 0b00, 0b00, 0b00, 0b00]);
+    // The latch is set again if the objects still overlap after clearing.
+    tia.write(registers::CXCLR, 0).unwrap();
+    assert_collision_latches(&mut tia, [0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b00]);
+    wait_ticks(&mut tia, TOTAL_WIDTH);
+    assert_collision_latches(&mut tia, [0b00, 0b00, 0b01, 0b00, 0b00, 0b00, 0b00, 0b00]);
+    // Otherwise, it stays set until cleared.
+    tia.write(registers::GRP0, 0).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH);
+    assert_collision_latches(&mut tia, [0b00, 0b00, 0b01, 0b00, 0b00, 0b00, 0b00, 0b00]);
+    tia.write(registers::CXCLR, 0).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH);
+    assert_collision_latches(&mut tia, [0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b00]);
+
+    // P1+BL.
+    tia.write(registers::GRP1, 0xFF).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH);
+    tia.write(registers::GRP1, 0).unwrap();
+    assert_collision_latches(&mut tia, [0b00, 0b00, 0b00, 0b01, 0b00, 0b00, 0b00, 0b00]);
+    tia.write(registers::CXCLR, 0).unwrap();
+
+    // M0+BL.
+    tia.write(registers::ENAM0, flags::ENAXX_ENABLE).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH);
+    tia.write(registers::ENAM0, 0).unwrap();
+    assert_collision_latches(&mut tia, [0b00, 0b00, 0b00, 0b00, 0b01, 0b00, 0b00, 0b00]);
+    tia.write(registers::CXCLR, 0).unwrap();
+
+    // M1+BL.
+    tia.write(registers::ENAM1, flags::ENAXX_ENABLE).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH);
+    tia.write(registers::ENAM1, 0).unwrap();
+    assert_collision_latches(&mut tia, [0b00, 0b00, 0b00, 0b00, 0b00, 0b01, 0b00, 0b00]);
+    tia.write(registers::CXCLR, 0).unwrap();
+
+    // BL+PF. The playfield priority doesn't matter.
+    tia.write(
+        registers::CTRLPF,
+        flags::CTRLPF_BALL_8 | flags::CTRLPF_PRIORITY,
+    )
+    .unwrap();
+    tia.write(registers::PF0, 0xF0).unwrap();
+    tia.write(registers::PF1, 0xFF).unwrap();
+    tia.write(registers::PF2, 0xFF).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH);
+    assert_collision_latches(&mut tia, [0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b10, 0b00]);
+
+    // Collisions are not detected without the ball.
+    tia.write(registers::ENABL, 0).unwrap();
+    tia.write(registers::CXCLR, 0).unwrap();
+    wait_ticks(&mut tia, TOTAL_WIDTH);
+    assert_collision_latches(&mut tia, [0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b00, 0b00]);
+}
+
 /// Performs an assertion on the collision registers (0x00-0x07), comparing
 /// them to the expected values. For better call site readability, the
 /// values are shifted 6 bits left, so the collision bit values are given in
@@ -1024,6 +1313,36 @@ fn latched_input_ports() {
     assert_eq!(tia.read(registers::INPT4).unwrap(), 0);
 }
 
+#[test]
+fn paddle_input_ports() {
+    let mut tia = Tia::new();
+    tia.set_paddle_resistance(PaddlePort::Input0, Some(2));
+    tia.set_paddle_resistance(PaddlePort::Input3, Some(0));
+    tia.write(registers::VBLANK, flags::VBLANK_DUMP_PADDLES)
+        .unwrap();
+    wait_ticks(&mut tia, 3 * TOTAL_WIDTH);
+    assert_eq!(tia.read(registers::INPT0).unwrap(), 0);
+    assert_eq!(tia.read(registers::INPT3).unwrap(), 0);
+
+    // Paddle 3 has no resistance, so it's charged immediately. Paddle 0 takes
+    // two scanlines to charge. Nothing is connected to the other ports, so
+    // they are never charged.
+    tia.write(registers::VBLANK, 0).unwrap();
+    assert_eq!(tia.read(registers::INPT3).unwrap(), flags::INPUT_HIGH);
+    wait_ticks(&mut tia, 2 * TOTAL_WIDTH - 1);
+    assert_eq!(tia.read(registers::INPT0).unwrap(), 0);
+    tia.tick();
+    assert_eq!(tia.read(registers::INPT0).unwrap(), flags::INPUT_HIGH);
+    assert_eq!(tia.read(registers::INPT1).unwrap(), 0);
+    assert_eq!(tia.read(registers::INPT2).unwrap(), 0);
+
+    // Dumping the paddles discharges them immediately.
+    tia.write(registers::VBLANK, flags::VBLANK_DUMP_PADDLES)
+        .unwrap();
+    assert_eq!(tia.read(registers::INPT0).unwrap(), 0);
+    assert_eq!(tia.read(registers::INPT3).unwrap(), 0);
+}
+
 #[test]
 fn generates_audio() {
     let mut tia = Tia::new();
@@ -1099,8 +1418,8 @@ fn audio_frequency() {
     tia.write(registers::AUDF0, 2).unwrap();
     tia.write(registers::AUDF1, 4).unwrap();
     let audio: Vec<AudioOutput> = scan_audio(&mut tia, 12).collect();
-    assert_eq!(encode_audio(audio.iter().map(|a| a.au0)), "111000111000");
-    assert_eq!(encode_audio(audio.iter().map(|a| a.au1)), "111110000011");
+    assert_eq!(encode_audio(audio.iter().map(|a| a.au0)), "001110001110");
+    assert_eq!(encode_audio(audio.iter().map(|a| a.au1)), "000011111000");
 }
 
 #[test]
@@ -1111,7 +1430,7 @@ fn audio_frequency_outside_range() {
     tia.write(registers::AUDF0, 0b1110_0001).unwrap();
     assert_eq!(
         encode_audio(scan_audio(&mut tia, 12).map(|a| a.au0)),
-        "110011001100"
+        "011001100110"
     );
 }
 
@@ -1133,29 +1452,27 @@ macro_rules! test_audio_pattern {
     };
 }
 
-// See the implementation note of `AudioGenerator.tick()` for a note about
-// original test cases.
+// These patterns include the initial state of the counters, so they don't
+// necessarily start with a full period.
 test_audio_pattern!(
     audio_pattern_0,
     0x0,
-    "1111111111111111111111111111111111111111111111111111111111111111111111"
+    "1011111111111111111111111111111111111111111111111111111111111111111111"
 );
 test_audio_pattern!(
     audio_pattern_1,
     0x1,
-    "1111000100110101111000100110101111000100110101111000100110101111000100"
+    "1011110001001101011110001001101011110001001101011110001001101011110001"
 );
 test_audio_pattern!(
     audio_pattern_2,
     0x2,
-    "1111111111111111111111111111111111111111111111111111111111111100000000"
+    "1111111111111111110000000000000111111111111111111111111111111111111111"
 );
 test_audio_pattern!(
     audio_pattern_3,
     0x3,
-    // Original test case:
-    // "1111110000001000111001111100011111110001111000110011000001111111110001"
-    "1111000000110011110000011100111111000000100011100111110001111111000111"
+    "1111110001111111000111100011001100000111111111000100000111011001111111"
 );
 test_audio_pattern!(
     audio_pattern_4,
@@ -1175,17 +1492,17 @@ test_audio_pattern!(
 test_audio_pattern!(
     audio_pattern_7,
     0x7,
-    "1111100011011101010000100101100111110001101110101000010010110011111000"
+    "1111110001101110101000010010110011111000110111010100001001011001111100"
 );
 test_audio_pattern!(
     audio_pattern_8,
     0x8,
-    "1111111110000011110111110001011100110010000010010100111011010001111001"
+    "1010000001010010101111001011101110000001110011101001001111010111010100"
 );
 test_audio_pattern!(
     audio_pattern_9,
     0x9,
-    "1111100011011101010000100101100111110001101110101000010010110011111000"
+    "1010000001001011001111100011011101010000100101100111110001101110101000"
 );
 test_audio_pattern!(
     audio_pattern_a,
@@ -1195,31 +1512,27 @@ test_audio_pattern!(
 test_audio_pattern!(
     audio_pattern_b,
     0xB,
-    "1111111111111111111111111111111111111111111111111111111111111111111111"
+    "1111110001101111111111111111111111111111111111111111111111111111111111"
 );
 test_audio_pattern!(
     audio_pattern_c,
     0xC,
-    "1110001110001110001110001110001110001110001110001110001110001110001110"
+    "1011100011100011100011100011100011100011100011100011100011100011100011"
 );
 test_audio_pattern!(
     audio_pattern_d,
     0xD,
-    "1110001110001110001110001110001110001110001110001110001110001110001110"
+    "1011100011100011100011100011100011100011100011100011100011100011100011"
 );
 test_audio_pattern!(
     audio_pattern_e,
     0xE,
-    // Original test case:
-    // "1111111111111111111111111111111111111111111111111000000000000000000000"
-    "1111111111111111111111111111111111111111111111111111110000000000000000"
+    "1111111111111111110000000000000111111111111111111111111111111111111111"
 );
 test_audio_pattern!(
     audio_pattern_f,
     0xF,
-    // Original test case:
-    // "1111111111000001110000000111100000000001111110001111110000111111111000"
-    "1111111111111110000000001111110001111111110001110001110000000000001110"
+    "1111110001111110001111110000111111111000000111110000001111000001111111"
 );
 
 test_audio_pattern!(
@@ -1227,3 +1540,93 @@ test_audio_pattern!(
     0xF4,
     "1010101010101010101010101010101010101010101010101010101010101010101010"
 );
+
+/// Returns two full periods of a steady-state audio pattern, skipping the
+/// initial state of the counters.
+fn steady_audio_pattern(audc: u8, period: usize) -> String {
+    let mut tia = Tia::new();
+    tia.write(registers::AUDF0, 0).unwrap();
+    tia.write(registers::AUDV0, 1).unwrap();
+    tia.write(registers::AUDC0, audc).unwrap();
+    return encode_audio(
+        scan_audio(&mut tia, 1000 + 2 * period)
+            .map(|a| a.au0)
+            .skip(1000),
+    );
+}
+
+#[test]
+fn audio_pattern_periods() {
+    // Periods, numbers of high samples per period, and known-good fragments of
+    // output for each AUDCx mode.
+    let modes: [(u8, usize, usize, &str); 16] = [
+        (0x0, 1, 1, "1"),
+        (0x1, 15, 8, "000100110101111"),
+        (
+            0x2,
+            465,
+            248,
+            "111111111111111111000000000000011111111111111111",
+        ),
+        (
+            0x3,
+            465,
+            248,
+            "1111110000001000111001111100011111110001111000110011000001111111110001",
+        ),
+        (0x4, 2, 1, "10"),
+        (0x5, 2, 1, "10"),
+        (0x6, 31, 18, "1111111111111111110000000000000"),
+        (0x7, 31, 16, "1111100011011101010000100101100"),
+        (
+            0x8,
+            511,
+            256,
+            "1111111110000011110111110001011100110010000010010100111011010001111001",
+        ),
+        (0x9, 31, 16, "1111100011011101010000100101100"),
+        (0xA, 31, 18, "1111111111111111110000000000000"),
+        (0xB, 1, 1, "1"),
+        (0xC, 6, 3, "111000"),
+        (0xD, 6, 3, "111000"),
+        (
+            0xE,
+            93,
+            49,
+            "1111111111111111111111111111111111111111111111111\
+             00000000000000000000000000000000000000000000",
+        ),
+        (
+            0xF,
+            93,
+            47,
+            "1111111111000001110000000111100000000001111110001111110000111111111000",
+        ),
+    ];
+    for (audc, period, n_high, fragment) in modes {
+        let pattern = steady_audio_pattern(audc, period);
+        let (first, second) = pattern.split_at(period);
+        assert_eq!(first, second, "AUDC={:X}", audc);
+        assert_eq!(first.matches('1').count(), n_high, "AUDC={:X}", audc);
+        assert!(pattern.contains(fragment), "AUDC={:X}: {}", audc, first);
+        // Make sure that the period isn't actually shorter.
+        for shorter in (1..period).filter(|p| period % p == 0) {
+            assert_ne!(
+                &pattern[..period],
+                &pattern[shorter..shorter + period],
+                "AUDC={:X}",
+                audc
+            );
+        }
+    }
+}
+
+#[test]
+fn mirrored_register_names() {
+    let names = register_names();
+    assert_eq!(names.write_name(0x09), Some("COLUBK"));
+    assert_eq!(names.read_name(0x09), Some("INPT1"));
+    assert_eq!(names.read_name(0x3C), Some("INPT4"));
+    assert_eq!(names.write_name(0x3C), None);
+    assert_eq!(names.read_name(0x40), None);
+}
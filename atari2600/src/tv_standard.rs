@@ -0,0 +1,95 @@
+use crate::colors;
+use crate::colors::Palette;
+use crate::frame_renderer::FrameRendererBuilder;
+use crate::frame_renderer::VerticalHold;
+use serde::Deserialize;
+
+/// Television standards supported by the emulator. Apart from the color
+/// palette, they differ in the number of scanlines per frame and the frame
+/// rate, so ROMs made for one of them will look wrong (or roll) on another.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, clap::ArgEnum)]
+pub enum TvStandard {
+    Ntsc,
+    Pal,
+    Secam,
+}
+
+impl TvStandard {
+    fn palette(&self) -> Palette {
+        match self {
+            Self::Ntsc => colors::ntsc_palette(),
+            Self::Pal => colors::pal_palette(),
+            Self::Secam => colors::secam_palette(),
+        }
+    }
+
+    /// Number of frames displayed per second.
+    pub fn frame_rate(&self) -> u64 {
+        match self {
+            Self::Ntsc => 60,
+            Self::Pal | Self::Secam => 50,
+        }
+    }
+
+    /// Index of the first scanline (counting from the end of VSYNC) that is
+    /// displayed on the screen. It corresponds to the recommended length of
+    /// the vertical blank period.
+    fn first_visible_scanline_index(&self) -> i32 {
+        match self {
+            Self::Ntsc => 37,
+            Self::Pal | Self::Secam => 45,
+        }
+    }
+
+    /// Minimum number of visible scanlines. It's a bit more than the
+    /// recommended picture height (192 lines for NTSC, 228 for PAL and SECAM),
+    /// since many games don't follow the recommendations exactly. Programs
+    /// that display even more lines make the viewport grow.
+    fn viewport_height(&self) -> u32 {
+        match self {
+            Self::Ntsc => 210,
+            Self::Pal | Self::Secam => 246,
+        }
+    }
+
+    /// Nominal number of scanlines per frame, including VSYNC.
+    fn scanlines(&self) -> u32 {
+        match self {
+            Self::Ntsc => 262,
+            Self::Pal | Self::Secam => 312,
+        }
+    }
+
+    /// Frame length limits of a TV set. They are deliberately loose, since
+    /// plenty of games don't produce exactly the nominal number of scanlines
+    /// per frame.
+    fn vertical_hold(&self) -> VerticalHold {
+        VerticalHold {
+            min_scanlines: self.scanlines() * 3 / 4,
+            max_scanlines: self.scanlines() * 5 / 4,
+        }
+    }
+
+    /// Returns a frame renderer builder configured for this TV standard.
+    pub fn frame_renderer_builder(&self) -> FrameRendererBuilder {
+        FrameRendererBuilder::new()
+            .with_palette(self.palette())
+            .with_height(self.viewport_height())
+            .with_auto_height(true)
+            .with_vertical_hold(Some(self.vertical_hold()))
+            .with_first_visible_scanline_index(self.first_visible_scanline_index())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configures_frame_renderer() {
+        let renderer = TvStandard::Ntsc.frame_renderer_builder().build();
+        assert_eq!(renderer.frame_image().height(), 210);
+        let renderer = TvStandard::Pal.frame_renderer_builder().build();
+        assert_eq!(renderer.frame_image().height(), 246);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::antic::Antic;
+use crate::gtia::Gtia;
+use crate::pia::Pia;
+use crate::pokey::Pokey;
+use std::fmt;
+use ya6502::memory::dump_zero_page;
+use ya6502::memory::memory_map_from;
+use ya6502::memory::Inspect;
+use ya6502::memory::Memory;
+use ya6502::memory::MemoryKind;
+use ya6502::memory::MemoryRegion;
+use ya6502::memory::Ram;
+use ya6502::memory::RamPattern;
+use ya6502::memory::Read;
+use ya6502::memory::ReadResult;
+use ya6502::memory::Rom;
+use ya6502::memory::Write;
+use ya6502::memory::WriteResult;
+
+/// An address space of a 64K Atari XL machine, as seen by the CPU. The OS ROM
+/// can be switched off using bit 0 of PIA port B, revealing the RAM underneath.
+/// The BASIC and self-test ROMs are not supported.
+#[derive(Debug)]
+pub struct AddressSpace {
+    ram: Ram,
+    os_rom: Rom,
+    /// An 8K cartridge, mapped at 0xA000-0xBFFF.
+    pub cartridge: Option<Rom>,
+    pub gtia: Gtia,
+    pub pokey: Pokey,
+    pub pia: Pia,
+    pub antic: Antic,
+}
+
+impl AddressSpace {
+    /// Creates a new address space. The OS ROM image is expected to be 16K
+    /// long; it's mapped at 0xC000-0xCFFF and 0xD800-0xFFFF, while the part
+    /// that would be mapped at 0xD000-0xD7FF is hidden under the I/O area.
+    /// The RAM is initialized with a given pattern.
+    pub fn new(os_rom: Rom, ram_pattern: RamPattern) -> Self {
+        Self {
+            ram: Ram::with_pattern(ram_pattern, 16),
+            os_rom,
+            cartridge: None,
+            gtia: Gtia::new(),
+            pokey: Pokey::new(),
+            pia: Pia::new(),
+            antic: Antic::new(),
+        }
+    }
+
+    fn os_rom_enabled(&self) -> bool {
+        self.pia.port_b() & flags::PORTB_OS_ROM_ENABLED != 0
+    }
+}
+
+/// Address space areas, as seen by the CPU.
+enum Area {
+    Ram,
+    Cartridge,
+    OsRom,
+    Gtia,
+    Pokey,
+    Pia,
+    Antic,
+    /// An I/O area with no devices attached.
+    Unmapped,
+}
+
+impl AddressSpace {
+    /// Decides which memory area is visible at a given address. Shared between
+    /// [`Read`], [`Inspect`], and [`Write`] implementations to make sure that
+    /// all of them see the same memory layout.
+    fn area(&self, address: u16) -> Area {
+        match address {
+            0xA000..=0xBFFF if self.cartridge.is_some() => Area::Cartridge,
+            0xC000..=0xCFFF | 0xD800..=0xFFFF if self.os_rom_enabled() => Area::OsRom,
+            0xD000..=0xD0FF => Area::Gtia,
+            0xD200..=0xD2FF => Area::Pokey,
+            0xD300..=0xD3FF => Area::Pia,
+            0xD400..=0xD4FF => Area::Antic,
+            0xD100..=0xD1FF | 0xD500..=0xD7FF => Area::Unmapped,
+            _ => Area::Ram,
+        }
+    }
+}
+
+impl Inspect for AddressSpace {
+    fn inspect(&self, address: u16) -> ReadResult {
+        match self.area(address) {
+            Area::Ram => self.ram.inspect(address),
+            Area::Cartridge => match &self.cartridge {
+                Some(cartridge) => cartridge.inspect(address),
+                None => self.ram.inspect(address),
+            },
+            Area::OsRom => self.os_rom.inspect(address),
+            Area::Gtia => self.gtia.inspect(address),
+            Area::Pokey => self.pokey.inspect(address),
+            Area::Pia => self.pia.inspect(address),
+            Area::Antic => self.antic.inspect(address),
+            // Nothing drives the data bus, and it floats high.
+            Area::Unmapped => Ok(0xFF),
+        }
+    }
+
+    fn memory_map(&self) -> Vec<MemoryRegion> {
+        memory_map_from(|address| match self.area(address) {
+            Area::Ram => MemoryKind::Ram,
+            Area::Cartridge | Area::OsRom => MemoryKind::Rom,
+            Area::Gtia | Area::Pokey | Area::Pia | Area::Antic => MemoryKind::Io,
+            Area::Unmapped => MemoryKind::Unmapped,
+        })
+    }
+}
+
+impl Read for AddressSpace {
+    fn read(&mut self, address: u16) -> ReadResult {
+        match self.area(address) {
+            Area::Ram => self.ram.read(address),
+            Area::Cartridge => match &mut self.cartridge {
+                Some(cartridge) => cartridge.read(address),
+                None => self.ram.read(address),
+            },
+            Area::OsRom => self.os_rom.read(address),
+            Area::Gtia => self.gtia.read(address),
+            Area::Pokey => self.pokey.read(address),
+            Area::Pia => self.pia.read(address),
+            Area::Antic => self.antic.read(address),
+            Area::Unmapped => Ok(0xFF),
+        }
+    }
+}
+
+impl Write for AddressSpace {
+    fn write(&mut self, address: u16, value: u8) -> WriteResult {
+        match self.area(address) {
+            Area::Ram => self.ram.write(address, value),
+            // Unlike on C64, writing to ROM doesn't affect the RAM underneath.
+            Area::Cartridge | Area::OsRom | Area::Unmapped => Ok(()),
+            Area::Gtia => self.gtia.write(address, value),
+            Area::Pokey => self.pokey.write(address, value),
+            Area::Pia => self.pia.write(address, value),
+            Area::Antic => self.antic.write(address, value),
+        }
+    }
+}
+
+impl Memory for AddressSpace {}
+
+impl fmt::Display for AddressSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        dump_zero_page(self, f)
+    }
+}
+
+mod flags {
+    pub const PORTB_OS_ROM_ENABLED: u8 = 0b0000_0001;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_address_space() -> AddressSpace {
+        AddressSpace::new(Rom::new(&[0xC5; 0x4000]).unwrap(), RamPattern::default())
+    }
+
+    #[test]
+    fn reads_and_writes() {
+        let mut address_space = new_address_space();
+        address_space.write(0x0002, 33).unwrap(); // RAM
+        address_space.write(0x9FFF, 65).unwrap(); // RAM
+        address_space.write(0xA000, 82).unwrap(); // RAM (no cartridge)
+        address_space.write(0xBFFF, 67).unwrap(); // RAM (no cartridge)
+        address_space.write(0xC000, 143).unwrap(); // OS ROM
+        address_space.write(0xD01A, 0x34).unwrap(); // GTIA
+        address_space.write(0xD20E, 0x40).unwrap(); // POKEY
+        address_space.write(0xD40E, 0x40).unwrap(); // ANTIC
+        address_space.write(0xD500, 12).unwrap(); // Nothing
+        address_space.write(0xFFFF, 45).unwrap(); // OS ROM
+
+        assert_eq!(address_space.read(0x0002).unwrap(), 33);
+        assert_eq!(address_space.read(0x9FFF).unwrap(), 65);
+        assert_eq!(address_space.read(0xA000).unwrap(), 82);
+        assert_eq!(address_space.read(0xBFFF).unwrap(), 67);
+        assert_eq!(address_space.read(0xC000).unwrap(), 0xC5);
+        assert_eq!(address_space.read(0xCFFF).unwrap(), 0xC5);
+        assert_eq!(address_space.gtia.background_color(), 0x34);
+        assert_eq!(address_space.read(0xD01F).unwrap(), 0x0F); // CONSOL
+        assert_eq!(address_space.read(0xD20F).unwrap(), 0xFF); // SKSTAT
+        assert_eq!(address_space.read(0xD300).unwrap(), 0x00); // PORTA DDR
+        assert_eq!(address_space.read(0xD40F).unwrap(), 0x1F); // NMIST
+        assert_eq!(address_space.read(0xD500).unwrap(), 0xFF);
+        assert_eq!(address_space.read(0xD7FF).unwrap(), 0xFF);
+        assert_eq!(address_space.read(0xD800).unwrap(), 0xC5);
+        assert_eq!(address_space.read(0xFFFF).unwrap(), 0xC5);
+    }
+
+    #[test]
+    fn switches_os_rom() {
+        let mut address_space = new_address_space();
+        address_space.write(0xD301, 0xFF).unwrap(); // PORTB: all outputs
+        address_space.write(0xD303, 0b0011_0100).unwrap(); // PBCTL: port access
+        address_space.write(0xD301, 0xFE).unwrap(); // PORTB: OS ROM disabled
+        address_space.write(0xC000, 143).unwrap();
+        address_space.write(0xD800, 5).unwrap();
+        address_space.write(0xFFFF, 45).unwrap();
+        assert_eq!(address_space.read(0xC000).unwrap(), 143);
+        assert_eq!(address_space.read(0xD800).unwrap(), 5);
+        assert_eq!(address_space.read(0xFFFF).unwrap(), 45);
+        // The I/O area stays in place.
+        assert_eq!(address_space.read(0xD40F).unwrap(), 0x1F);
+
+        address_space.write(0xD301, 0xFF).unwrap(); // PORTB: OS ROM enabled
+        assert_eq!(address_space.read(0xC000).unwrap(), 0xC5);
+        assert_eq!(address_space.read(0xD800).unwrap(), 0xC5);
+        assert_eq!(address_space.read(0xFFFF).unwrap(), 0xC5);
+    }
+
+    #[test]
+    fn cartridge() {
+        let mut address_space = new_address_space();
+        address_space.cartridge = Some(Rom::new(&[0xCA; 0x2000]).unwrap());
+        address_space.write(0xA000, 82).unwrap();
+        assert_eq!(address_space.read(0x9FFF).unwrap(), 0);
+        assert_eq!(address_space.read(0xA000).unwrap(), 0xCA);
+        assert_eq!(address_space.inspect(0xBFFF).unwrap(), 0xCA);
+        assert_eq!(address_space.read(0xC000).unwrap(), 0xC5);
+    }
+}
@@ -0,0 +1,214 @@
+use common::scheduler::CpuScheduler;
+use ya6502::memory::Inspect;
+use ya6502::memory::Memory;
+use ya6502::memory::Read;
+use ya6502::memory::ReadResult;
+use ya6502::memory::Write;
+use ya6502::memory::WriteResult;
+
+/// Number of CPU cycles per scanline.
+pub const CYCLES_PER_LINE: u32 = 114;
+/// Number of scanlines per frame (NTSC).
+pub const LINES_PER_FRAME: u32 = 262;
+/// The first scanline of the vertical blank.
+pub const VBLANK_START_LINE: u32 = 248;
+
+/// The cycle in which the CPU is released after writing to WSYNC.
+const WSYNC_RELEASE_CYCLE: u32 = 105;
+/// The cycle in which the vertical blank NMI is triggered.
+const VBLANK_NMI_CYCLE: u32 = 7;
+
+/// An ANTIC chip. So far, it only provides the video timing: it counts cycles
+/// and scanlines, halts the CPU on WSYNC, and triggers the vertical blank
+/// interrupt. The display list is not processed yet, and neither is DMA, so
+/// the CPU always runs at full speed.
+#[derive(Debug)]
+pub struct Antic {
+    registers: [u8; 0x10],
+    nmi_enable: u8,
+    nmi_status: u8,
+    /// Current position of the beam: a CPU cycle within the current scanline,
+    /// and the scanline number.
+    cycle: u32,
+    scanline: u32,
+    /// Holds the CPU while it waits for WSYNC. ANTIC is clocked at the CPU
+    /// speed, so there's no clock division.
+    cpu_scheduler: CpuScheduler,
+}
+
+/// Result of a single ANTIC tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnticOutput {
+    /// If `false`, the CPU is halted in this cycle.
+    pub cpu_tick: bool,
+    /// The state of the NMI line.
+    pub nmi: bool,
+    /// Beam position in this cycle.
+    pub cycle: u32,
+    pub scanline: u32,
+    /// Set in the last cycle of a frame.
+    pub frame_complete: bool,
+}
+
+impl Antic {
+    pub fn new() -> Self {
+        Self {
+            registers: [0; 0x10],
+            nmi_enable: 0,
+            nmi_status: 0,
+            cycle: 0,
+            scanline: 0,
+            cpu_scheduler: CpuScheduler::new(1),
+        }
+    }
+
+    /// Returns the current scanline and the CPU cycle within it.
+    pub fn beam_position(&self) -> (u32, u32) {
+        (self.scanline, self.cycle)
+    }
+
+    /// Performs a single CPU cycle tick.
+    pub fn tick(&mut self) -> AnticOutput {
+        if self.cycle == WSYNC_RELEASE_CYCLE {
+            self.cpu_scheduler.sync();
+        }
+        if self.scanline == VBLANK_START_LINE && self.cycle == VBLANK_NMI_CYCLE {
+            self.nmi_status = (self.nmi_status & !flags::NMI_DLI) | flags::NMI_VBI;
+        }
+        // The NMI line is held active until the end of the scanline, so that
+        // the CPU notices it even if it's halted by WSYNC at the moment.
+        let nmi = self.scanline == VBLANK_START_LINE
+            && self.cycle >= VBLANK_NMI_CYCLE
+            && self.nmi_enable & flags::NMI_VBI != 0;
+        let output = AnticOutput {
+            cpu_tick: self.cpu_scheduler.tick().cpu_cycle,
+            nmi,
+            cycle: self.cycle,
+            scanline: self.scanline,
+            frame_complete: self.scanline == LINES_PER_FRAME - 1
+                && self.cycle == CYCLES_PER_LINE - 1,
+        };
+
+        self.cycle += 1;
+        if self.cycle >= CYCLES_PER_LINE {
+            self.cycle = 0;
+            self.scanline = (self.scanline + 1) % LINES_PER_FRAME;
+        }
+        return output;
+    }
+}
+
+impl Default for Antic {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for Antic {
+    fn write(&mut self, address: u16, value: u8) -> WriteResult {
+        let register = address & 0x0F;
+        match register {
+            registers::WSYNC => self.cpu_scheduler.wait_for_sync(),
+            registers::NMIEN => self.nmi_enable = value,
+            registers::NMIRES => self.nmi_status = 0,
+            _ => {}
+        }
+        self.registers[register as usize] = value;
+        Ok(())
+    }
+}
+
+impl Inspect for Antic {
+    fn inspect(&self, address: u16) -> ReadResult {
+        match address & 0x0F {
+            registers::VCOUNT => Ok((self.scanline / 2) as u8),
+            // Light pen isn't supported.
+            registers::PENH | registers::PENV => Ok(0),
+            // The unused bits read as 1.
+            registers::NMIST => Ok(self.nmi_status | 0b0001_1111),
+            _ => Ok(0xFF),
+        }
+    }
+}
+
+impl Read for Antic {
+    fn read(&mut self, address: u16) -> ReadResult {
+        self.inspect(address)
+    }
+}
+
+impl Memory for Antic {}
+
+mod registers {
+    pub const WSYNC: u16 = 0x0A;
+    pub const VCOUNT: u16 = 0x0B;
+    pub const PENH: u16 = 0x0C;
+    pub const PENV: u16 = 0x0D;
+    pub const NMIEN: u16 = 0x0E;
+    pub const NMIRES: u16 = 0x0F;
+    pub const NMIST: u16 = 0x0F;
+}
+
+mod flags {
+    pub const NMI_DLI: u8 = 0b1000_0000;
+    pub const NMI_VBI: u8 = 0b0100_0000;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticks(antic: &mut Antic, n: u32) -> Vec<AnticOutput> {
+        (0..n).map(|_| antic.tick()).collect()
+    }
+
+    #[test]
+    fn counts_scanlines() {
+        let mut antic = Antic::new();
+        assert_eq!(antic.read(0xD40B).unwrap(), 0);
+        ticks(&mut antic, 2 * CYCLES_PER_LINE);
+        assert_eq!(antic.read(0xD40B).unwrap(), 1);
+        ticks(&mut antic, 258 * CYCLES_PER_LINE);
+        assert_eq!(antic.read(0xD40B).unwrap(), 130);
+
+        let outputs = ticks(&mut antic, 2 * CYCLES_PER_LINE);
+        assert!(outputs[..outputs.len() - 1]
+            .iter()
+            .all(|output| !output.frame_complete));
+        assert!(outputs.last().unwrap().frame_complete);
+        assert_eq!(antic.read(0xD40B).unwrap(), 0);
+    }
+
+    #[test]
+    fn wsync() {
+        let mut antic = Antic::new();
+        ticks(&mut antic, 10);
+        antic.write(0xD40A, 0).unwrap();
+        let outputs = ticks(&mut antic, CYCLES_PER_LINE);
+        assert_eq!(
+            outputs.iter().position(|output| output.cpu_tick),
+            Some((WSYNC_RELEASE_CYCLE - 10) as usize)
+        );
+        assert!(outputs[WSYNC_RELEASE_CYCLE as usize - 10..]
+            .iter()
+            .all(|output| output.cpu_tick));
+    }
+
+    #[test]
+    fn vertical_blank_interrupt() {
+        let mut antic = Antic::new();
+        let outputs = ticks(&mut antic, LINES_PER_FRAME * CYCLES_PER_LINE);
+        assert!(outputs.iter().all(|output| !output.nmi));
+        // Even if the interrupt is disabled, the status flag is set.
+        assert_eq!(antic.read(0xD40F).unwrap(), 0b0101_1111);
+        antic.write(0xD40F, 0).unwrap();
+        assert_eq!(antic.read(0xD40F).unwrap(), 0b0001_1111);
+
+        antic.write(0xD40E, 0b0100_0000).unwrap();
+        let outputs = ticks(&mut antic, LINES_PER_FRAME * CYCLES_PER_LINE);
+        let nmi_start = outputs.iter().position(|output| output.nmi).unwrap();
+        assert_eq!(outputs[nmi_start].scanline, VBLANK_START_LINE);
+        assert_eq!(outputs[nmi_start].cycle, VBLANK_NMI_CYCLE);
+        assert_eq!(antic.read(0xD40F).unwrap(), 0b0101_1111);
+    }
+}
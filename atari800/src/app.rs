@@ -0,0 +1,177 @@
+use crate::atari800::Atari800;
+use crate::keyboard::Key as AtariKey;
+use crate::keyboard::KeyState;
+use crate::keymap::Keymap;
+use common::app::AppController;
+use common::app::MachineController;
+use common::cheats::Cheat;
+use common::cheats::CheatError;
+use common::debugger::adapter::DebugAdapter;
+use common::debugger::coverage::CoverageTracker;
+use common::debugger::trace::Tracer;
+use common::debugger::Debugger;
+use image::RgbaImage;
+use piston::Button;
+use piston::ButtonArgs;
+use piston::ButtonState;
+use piston::Event;
+use piston::Input;
+use piston::Key;
+use piston::Loop;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+pub struct Atari800Controller<'a, A: DebugAdapter> {
+    machine_controller: MachineController<'a, Atari800, A>,
+    keymap: Keymap,
+    /// Atari keys pressed by the host keys that are currently pressed.
+    pressed_keys: HashMap<Key, AtariKey>,
+}
+
+impl<'a, A: DebugAdapter> Atari800Controller<'a, A> {
+    pub fn new(atari: &'a mut Atari800, debugger: Option<Debugger<A>>) -> Self {
+        Self {
+            machine_controller: MachineController::new(atari, debugger),
+            keymap: Keymap::default(),
+            pressed_keys: HashMap::new(),
+        }
+    }
+
+    pub fn set_tracer(&mut self, tracer: Option<Tracer<BufWriter<File>>>) {
+        self.machine_controller.set_tracer(tracer);
+    }
+
+    pub fn set_coverage_tracker(&mut self, tracker: Option<CoverageTracker<BufWriter<File>>>) {
+        self.machine_controller.set_coverage_tracker(tracker);
+    }
+
+    pub fn install_cheats(&mut self, cheats: &[Cheat]) -> Result<(), CheatError> {
+        self.machine_controller.install_cheats(cheats)
+    }
+
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    fn handle_key(&mut self, key: Key, state: ButtonState) {
+        match state {
+            ButtonState::Press => {
+                if let Some(atari_key) = self.keymap.atari_key(key) {
+                    self.machine_controller
+                        .mut_machine()
+                        .set_key_state(atari_key, KeyState::Pressed);
+                    self.pressed_keys.insert(key, atari_key);
+                }
+            }
+            ButtonState::Release => {
+                if let Some(atari_key) = self.pressed_keys.remove(&key) {
+                    // Several host keys may be mapped to a single Atari key
+                    // (e.g. both shift keys), so only release it once none of
+                    // them is pressed.
+                    if !self.pressed_keys.values().any(|k| *k == atari_key) {
+                        self.machine_controller
+                            .mut_machine()
+                            .set_key_state(atari_key, KeyState::Released);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a, A: DebugAdapter> AppController for Atari800Controller<'a, A> {
+    fn frame_image(&self) -> &RgbaImage {
+        self.machine_controller.frame_image()
+    }
+
+    fn reset(&mut self) {
+        self.machine_controller.reset();
+    }
+
+    fn interrupted(&self) -> Arc<AtomicBool> {
+        self.machine_controller.interrupted()
+    }
+
+    fn paused(&self) -> bool {
+        self.machine_controller.paused()
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.machine_controller.set_paused(paused);
+    }
+
+    fn frame_count(&self) -> u64 {
+        self.machine_controller.frame_count()
+    }
+
+    fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.machine_controller.load_program(path)
+    }
+
+    fn event(&mut self, event: &Event) {
+        match event {
+            Event::Input(
+                Input::Button(ButtonArgs {
+                    button: Button::Keyboard(key),
+                    state,
+                    ..
+                }),
+                _timestamp,
+            ) => self.handle_key(*key, *state),
+            Event::Loop(Loop::Update(_)) => self.machine_controller.run_until_end_of_frame(),
+            _ => {}
+        }
+    }
+
+    fn display_machine_state(&self) -> String {
+        self.machine_controller.display_state()
+    }
+
+    fn machine_status(&self) -> String {
+        self.machine_controller.machine_status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::atari_with_program;
+    use common::debugger::adapter::TcpDebugAdapter;
+    use ya6502::cpu::MachineInspector;
+
+    fn send_key<A>(controller: &mut Atari800Controller<A>, key: Key, state: ButtonState)
+    where
+        A: DebugAdapter,
+    {
+        controller.event(&Event::from(ButtonArgs {
+            button: Button::Keyboard(key),
+            state,
+            scancode: None,
+        }));
+    }
+
+    #[test]
+    fn shift_keys() {
+        let mut atari = atari_with_program(&[0x4C, 0x00, 0xC0], &[]);
+        let mut controller = Atari800Controller::new(&mut atari, None::<Debugger<TcpDebugAdapter>>);
+        let skstat = |controller: &Atari800Controller<TcpDebugAdapter>| {
+            controller
+                .machine_controller
+                .machine()
+                .inspect_memory(0xD20F)
+        };
+
+        send_key(&mut controller, Key::LShift, ButtonState::Press);
+        send_key(&mut controller, Key::RShift, ButtonState::Press);
+        assert_eq!(skstat(&controller), 0b1111_0111);
+        send_key(&mut controller, Key::LShift, ButtonState::Release);
+        assert_eq!(skstat(&controller), 0b1111_0111);
+        send_key(&mut controller, Key::RShift, ButtonState::Release);
+        assert_eq!(skstat(&controller), 0b1111_1111);
+    }
+}
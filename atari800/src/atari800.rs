@@ -0,0 +1,273 @@
+use crate::address_space::AddressSpace;
+use crate::antic::AnticOutput;
+use crate::colors::ntsc_palette;
+use crate::colors::Palette;
+use crate::keyboard::Key;
+use crate::keyboard::KeyState;
+use crate::keyboard::Keyboard;
+use common::app::FrameStatus;
+use common::app::Machine;
+use common::app::RasterPosition;
+use common::archive::read_image_file;
+use common::cheats::CheatMemory;
+use common::cheats::CheatTable;
+use image::RgbaImage;
+use std::error::Error;
+use std::path::Path;
+use thiserror::Error;
+use ya6502::cpu::CompositeMachine;
+use ya6502::cpu::Cpu;
+use ya6502::cpu::CpuError;
+use ya6502::cpu::CpuVariant;
+use ya6502::cpu::PowerOnState;
+use ya6502::memory::RamPattern;
+use ya6502::memory::Rom;
+
+/// Extensions of files that contain cartridge ROM images. Used to find the
+/// image in ZIP archives.
+pub const CARTRIDGE_FILE_EXTENSIONS: &[&str] = &["rom", "bin"];
+
+/// Width of the frame image, in color clocks. The image covers the overscan
+/// area that is visible on a typical TV set.
+pub const FRAME_WIDTH: u32 = 192;
+/// Height of the frame image, in scanlines.
+pub const FRAME_HEIGHT: u32 = 240;
+
+/// The first CPU cycle of a scanline that is visible in the frame image. Each
+/// CPU cycle corresponds to 2 color clocks.
+const FIRST_VISIBLE_CYCLE: u32 = 16;
+/// The first scanline that is visible in the frame image.
+const FIRST_VISIBLE_LINE: u32 = 8;
+
+#[derive(Error, Debug)]
+#[error("Illegal OS ROM size: {size} bytes. Expected 16384 bytes (an XL/XE OS image)")]
+pub struct OsRomSizeError {
+    size: usize,
+}
+
+/// An Atari XL machine with 64K of RAM. So far, the emulation is limited to the
+/// memory layout, video timing, and keyboard; ANTIC doesn't process display
+/// lists, so the whole screen shows the background color.
+pub struct Atari800 {
+    cpu: Cpu<CheatMemory<AddressSpace>>,
+    palette: Palette,
+    frame_image: RgbaImage,
+    keyboard: Keyboard,
+    at_cpu_cycle: bool,
+}
+
+impl Machine for Atari800 {
+    fn reset(&mut self) {
+        self.cpu.reset();
+    }
+
+    fn tick(&mut self) -> Result<FrameStatus, CpuError> {
+        let antic_output = self.cpu.mut_memory().antic.tick();
+        self.at_cpu_cycle = antic_output.cpu_tick;
+        self.cpu.set_nmi_pin(antic_output.nmi);
+        if self.at_cpu_cycle {
+            self.cpu.tick()?;
+        }
+        let pokey_irq = self.cpu.mut_memory().pokey.tick();
+        self.cpu.set_irq_pin(pokey_irq);
+        self.render(&antic_output);
+        return if antic_output.frame_complete {
+            Ok(FrameStatus::Complete)
+        } else {
+            Ok(FrameStatus::Pending)
+        };
+    }
+
+    fn frame_image(&self) -> &RgbaImage {
+        &self.frame_image
+    }
+
+    fn display_state(&self) -> String {
+        format!("{}\n{}", self.cpu(), self.cpu().memory())
+    }
+
+    fn raster_position(&self) -> RasterPosition {
+        let (line, x) = self.cpu().memory().antic.beam_position();
+        RasterPosition { line, x }
+    }
+
+    /// Plugs in an 8K cartridge loaded from a given ROM image.
+    fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let image = read_image_file(path, CARTRIDGE_FILE_EXTENSIONS)?;
+        self.set_cartridge(Some(Rom::new(&image.bytes)?));
+        Ok(())
+    }
+    fn mut_cheats(&mut self) -> Option<&mut CheatTable> {
+        Some(self.cpu.mut_memory().mut_cheats())
+    }
+}
+
+impl CompositeMachine for Atari800 {
+    type Memory = CheatMemory<AddressSpace>;
+
+    fn cpu(&self) -> &Cpu<Self::Memory> {
+        &self.cpu
+    }
+
+    fn mut_cpu(&mut self) -> &mut Cpu<Self::Memory> {
+        &mut self.cpu
+    }
+
+    fn at_cpu_cycle(&self) -> bool {
+        self.at_cpu_cycle
+    }
+}
+
+impl Atari800 {
+    /// Creates a new machine with a given OS ROM image. The image is not
+    /// distributed with the emulator, so it needs to be supplied by the user.
+    pub fn new(
+        os_rom: &[u8],
+        power_on_state: PowerOnState,
+        ram_pattern: RamPattern,
+    ) -> Result<Self, Box<dyn Error>> {
+        if os_rom.len() != 0x4000 {
+            return Err(Box::new(OsRomSizeError { size: os_rom.len() }));
+        }
+        Ok(Self {
+            cpu: Cpu::with_power_on_state(
+                Box::new(CheatMemory::new(
+                    AddressSpace::new(Rom::new(os_rom)?, ram_pattern),
+                    CheatTable::new(),
+                )),
+                CpuVariant::Nmos6502,
+                power_on_state,
+            ),
+            palette: ntsc_palette(),
+            frame_image: RgbaImage::new(FRAME_WIDTH, FRAME_HEIGHT),
+            keyboard: Keyboard::new(),
+            at_cpu_cycle: false,
+        })
+    }
+
+    pub fn set_cartridge(&mut self, cartridge: Option<Rom>) {
+        self.cpu.mut_memory().cartridge = cartridge;
+    }
+
+    pub fn set_key_state(&mut self, key: Key, state: KeyState) {
+        self.keyboard.set_key_state(key, state);
+        let memory = self.cpu.mut_memory();
+        memory.pokey.set_keyboard_state(
+            self.keyboard.key_code(),
+            self.keyboard.shift_pressed(),
+            self.keyboard.break_pressed(),
+        );
+        memory.gtia.set_console_keys(self.keyboard.console_keys());
+    }
+
+    /// Draws the 2 color clocks that correspond to the current CPU cycle, if
+    /// they fall into the visible area.
+    fn render(&mut self, antic_output: &AnticOutput) {
+        if antic_output.cycle < FIRST_VISIBLE_CYCLE {
+            return;
+        }
+        let x = (antic_output.cycle - FIRST_VISIBLE_CYCLE) * 2;
+        let y = antic_output.scanline.wrapping_sub(FIRST_VISIBLE_LINE);
+        if x >= FRAME_WIDTH || y >= FRAME_HEIGHT {
+            return;
+        }
+        let color = self.palette[self.cpu.memory().gtia.background_color() as usize];
+        self.frame_image.put_pixel(x, y, color);
+        self.frame_image.put_pixel(x + 1, y, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::atari_with_program;
+    use crate::test_utils::next_frame;
+    use ya6502::cpu::MachineInspector;
+
+    #[test]
+    fn rejects_invalid_os_rom() {
+        let ram_pattern = RamPattern::default();
+        assert!(Atari800::new(&[0; 0x2800], PowerOnState::Random, ram_pattern).is_err());
+        assert!(Atari800::new(&[0; 0x4000], PowerOnState::Random, ram_pattern).is_ok());
+    }
+
+    #[test]
+    fn shows_background_color() {
+        let mut atari = atari_with_program(
+            &[
+                0xA9, 0x86, // LDA #$86
+                0x8D, 0x1A, 0xD0, // STA COLBK
+                0x4C, 0x05, 0xC0, // JMP *
+            ],
+            &[],
+        );
+        let frame = next_frame(&mut atari).unwrap();
+        let color = ntsc_palette()[0x86];
+        assert_eq!(*frame.get_pixel(0, 0), color);
+        assert_eq!(*frame.get_pixel(FRAME_WIDTH / 2, FRAME_HEIGHT / 2), color);
+        assert_eq!(*frame.get_pixel(FRAME_WIDTH - 1, FRAME_HEIGHT - 1), color);
+    }
+
+    #[test]
+    fn vertical_blank_interrupt() {
+        let mut atari = atari_with_program(
+            &[
+                0xA9, 0x40, // LDA #$40
+                0x8D, 0x0E, 0xD4, // STA NMIEN
+                0x4C, 0x05, 0xC0, // JMP *
+            ],
+            &[
+                0xE6, 0x80, // INC $80
+                0x8D, 0x0F, 0xD4, // STA NMIRES
+                0x40, // RTI
+            ],
+        );
+        next_frame(&mut atari).unwrap();
+        assert_eq!(atari.inspect_memory(0x80), 1);
+        next_frame(&mut atari).unwrap();
+        next_frame(&mut atari).unwrap();
+        assert_eq!(atari.inspect_memory(0x80), 3);
+    }
+
+    #[test]
+    fn keyboard_interrupt() {
+        let mut atari = atari_with_program(
+            &[
+                0xA9, 0x40, // LDA #$40
+                0x8D, 0x0E, 0xD2, // STA IRQEN
+                0x58, // CLI
+                0x4C, 0x06, 0xC0, // JMP *
+            ],
+            &[
+                0xAD, 0x09, 0xD2, // LDA KBCODE
+                0x85, 0x80, // STA $80
+                0xA9, 0x00, // LDA #$00
+                0x8D, 0x0E, 0xD2, // STA IRQEN
+                0xA9, 0x40, // LDA #$40
+                0x8D, 0x0E, 0xD2, // STA IRQEN
+                0x40, // RTI
+            ],
+        );
+        next_frame(&mut atari).unwrap();
+        assert_eq!(atari.inspect_memory(0x80), 0);
+
+        atari.set_key_state(Key::A, KeyState::Pressed);
+        next_frame(&mut atari).unwrap();
+        assert_eq!(atari.inspect_memory(0x80), 0x3F);
+        atari.set_key_state(Key::A, KeyState::Released);
+
+        atari.set_key_state(Key::Shift, KeyState::Pressed);
+        atari.set_key_state(Key::S, KeyState::Pressed);
+        next_frame(&mut atari).unwrap();
+        assert_eq!(atari.inspect_memory(0x80), 0x7E);
+        assert_eq!(atari.inspect_memory(0xD20F), 0b1111_0011);
+    }
+
+    #[test]
+    fn console_keys() {
+        let mut atari = atari_with_program(&[0x4C, 0x00, 0xC0], &[]);
+        assert_eq!(atari.inspect_memory(0xD01F), 0b0000_1111);
+        atari.set_key_state(Key::Start, KeyState::Pressed);
+        assert_eq!(atari.inspect_memory(0xD01F), 0b0000_1110);
+    }
+}
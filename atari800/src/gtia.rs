@@ -0,0 +1,116 @@
+use ya6502::memory::Inspect;
+use ya6502::memory::Memory;
+use ya6502::memory::Read;
+use ya6502::memory::ReadResult;
+use ya6502::memory::Write;
+use ya6502::memory::WriteResult;
+
+/// A GTIA chip. So far, it only keeps track of its color registers and reports
+/// the background color; playfield graphics, players, missiles, and collisions
+/// are not emulated yet. It also reads the console keys.
+#[derive(Debug)]
+pub struct Gtia {
+    registers: [u8; 0x20],
+    /// Bit mask of pressed console keys (START, SELECT, OPTION).
+    console_keys: u8,
+}
+
+impl Gtia {
+    pub fn new() -> Self {
+        Self {
+            registers: [0; 0x20],
+            console_keys: 0,
+        }
+    }
+
+    /// Returns the current background color. Bit 0 of the luminance is only
+    /// used by the special GTIA modes, so it's ignored.
+    pub fn background_color(&self) -> u8 {
+        self.registers[registers::COLBK as usize] & 0xFE
+    }
+
+    /// Sets the state of console keys. Bit 0 corresponds to START, bit 1 to
+    /// SELECT, and bit 2 to OPTION; a bit set to 1 means that the key is
+    /// pressed.
+    pub fn set_console_keys(&mut self, keys: u8) {
+        self.console_keys = keys;
+    }
+}
+
+impl Default for Gtia {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for Gtia {
+    fn write(&mut self, address: u16, value: u8) -> WriteResult {
+        self.registers[(address & 0x1F) as usize] = value;
+        Ok(())
+    }
+}
+
+impl Inspect for Gtia {
+    fn inspect(&self, address: u16) -> ReadResult {
+        match address & 0x1F {
+            // Collisions aren't emulated yet.
+            registers::M0PF..=registers::P3PL => Ok(0),
+            // No joysticks are connected, so the triggers aren't pressed.
+            registers::TRIG0..=registers::TRIG3 => Ok(1),
+            registers::PAL => Ok(flags::PAL_NTSC),
+            // The console keys are active low. Bit 3 controls the speaker and
+            // always reads 1.
+            registers::CONSOL => Ok(0b0000_1000 | (!self.console_keys & 0b0000_0111)),
+            _ => Ok(0x0F),
+        }
+    }
+}
+
+impl Read for Gtia {
+    fn read(&mut self, address: u16) -> ReadResult {
+        self.inspect(address)
+    }
+}
+
+impl Memory for Gtia {}
+
+mod registers {
+    pub const M0PF: u16 = 0x00;
+    pub const P3PL: u16 = 0x0F;
+    pub const TRIG0: u16 = 0x10;
+    pub const TRIG3: u16 = 0x13;
+    pub const PAL: u16 = 0x14;
+    pub const COLBK: u16 = 0x1A;
+    pub const CONSOL: u16 = 0x1F;
+}
+
+mod flags {
+    pub const PAL_NTSC: u8 = 0x0F;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registers() {
+        let mut gtia = Gtia::new();
+        gtia.write(0xD01A, 0x35).unwrap();
+        assert_eq!(gtia.background_color(), 0x34);
+        assert_eq!(gtia.read(0xD004).unwrap(), 0);
+        assert_eq!(gtia.read(0xD010).unwrap(), 1);
+        assert_eq!(gtia.read(0xD014).unwrap(), 0x0F);
+
+        // Registers are mirrored every 32 bytes.
+        gtia.write(0xD0FA, 0x86).unwrap();
+        assert_eq!(gtia.background_color(), 0x86);
+    }
+
+    #[test]
+    fn console_keys() {
+        let mut gtia = Gtia::new();
+        assert_eq!(gtia.read(0xD01F).unwrap(), 0b0000_1111);
+        gtia.set_console_keys(0b0000_0101);
+        assert_eq!(gtia.read(0xD01F).unwrap(), 0b0000_1010);
+    }
+}
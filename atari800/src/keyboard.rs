@@ -0,0 +1,264 @@
+use enum_map::{enum_map, Enum, EnumMap};
+use serde::Deserialize;
+
+/// Keeps track of the Atari keyboard state. Most of the keys are scanned by
+/// POKEY, which reports a 6-bit code of the pressed key along with the state of
+/// SHIFT and CONTROL keys. BREAK is connected directly to POKEY and triggers an
+/// interrupt, and the console keys (START, SELECT, and OPTION) are read through
+/// GTIA.
+pub struct Keyboard {
+    key_states: EnumMap<Key, KeyState>,
+    /// The most recently pressed key that has a keyboard code. POKEY only
+    /// reports a single key at a time.
+    last_pressed: Option<Key>,
+}
+
+impl Keyboard {
+    pub fn new() -> Self {
+        Self {
+            key_states: enum_map!(_ => KeyState::Released),
+            last_pressed: None,
+        }
+    }
+
+    pub fn set_key_state(&mut self, key: Key, state: KeyState) {
+        self.key_states[key] = state;
+        if key_code(key).is_none() {
+            return;
+        }
+        match state {
+            KeyState::Pressed => self.last_pressed = Some(key),
+            KeyState::Released if self.last_pressed == Some(key) => {
+                // Fall back to any other key that is still held.
+                self.last_pressed = self
+                    .key_states
+                    .iter()
+                    .find(|(key, state)| **state == KeyState::Pressed && key_code(*key).is_some())
+                    .map(|(key, _)| key);
+            }
+            KeyState::Released => {}
+        }
+    }
+
+    fn is_pressed(&self, key: Key) -> bool {
+        self.key_states[key] == KeyState::Pressed
+    }
+
+    /// Returns the keyboard code, as reported by POKEY's KBCODE register, or
+    /// `None` if no key is pressed. Bit 6 is set if SHIFT is pressed, and bit
+    /// 7 if CONTROL is pressed.
+    pub fn key_code(&self) -> Option<u8> {
+        let mut code = key_code(self.last_pressed?)?;
+        if self.shift_pressed() {
+            code |= 0b0100_0000;
+        }
+        if self.is_pressed(Key::Control) {
+            code |= 0b1000_0000;
+        }
+        return Some(code);
+    }
+
+    pub fn shift_pressed(&self) -> bool {
+        self.is_pressed(Key::Shift)
+    }
+
+    pub fn break_pressed(&self) -> bool {
+        self.is_pressed(Key::Break)
+    }
+
+    /// Returns a bit mask of pressed console keys: bit 0 for START, bit 1 for
+    /// SELECT, and bit 2 for OPTION.
+    pub fn console_keys(&self) -> u8 {
+        [Key::Start, Key::Select, Key::Option]
+            .iter()
+            .enumerate()
+            .filter(|(_, key)| self.is_pressed(**key))
+            .fold(0, |keys, (bit, _)| keys | 1 << bit)
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Key {
+    Escape,
+    D1,
+    D2,
+    D3,
+    D4,
+    D5,
+    D6,
+    D7,
+    D8,
+    D9,
+    D0,
+    Less,
+    Greater,
+    Backspace,
+    Break,
+
+    Tab,
+    Q,
+    W,
+    E,
+    R,
+    T,
+    Y,
+    U,
+    I,
+    O,
+    P,
+    Minus,
+    Equals,
+    Return,
+
+    Control,
+    A,
+    S,
+    D,
+    F,
+    G,
+    H,
+    J,
+    K,
+    L,
+    Semicolon,
+    Plus,
+    Asterisk,
+    Caps,
+
+    Shift,
+    Z,
+    X,
+    C,
+    V,
+    B,
+    N,
+    M,
+    Comma,
+    Period,
+    Slash,
+    Inverse,
+
+    Space,
+    Help,
+
+    Start,
+    Select,
+    Option,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyState {
+    Pressed,
+    Released,
+}
+
+/// Returns a 6-bit keyboard code of a given key, or `None` if the key isn't
+/// scanned by POKEY (modifiers, BREAK, and console keys).
+pub fn key_code(key: Key) -> Option<u8> {
+    match key {
+        Key::L => Some(0x00),
+        Key::J => Some(0x01),
+        Key::Semicolon => Some(0x02),
+        Key::K => Some(0x05),
+        Key::Plus => Some(0x06),
+        Key::Asterisk => Some(0x07),
+        Key::O => Some(0x08),
+        Key::P => Some(0x0A),
+        Key::U => Some(0x0B),
+        Key::Return => Some(0x0C),
+        Key::I => Some(0x0D),
+        Key::Minus => Some(0x0E),
+        Key::Equals => Some(0x0F),
+        Key::V => Some(0x10),
+        Key::Help => Some(0x11),
+        Key::C => Some(0x12),
+        Key::B => Some(0x15),
+        Key::X => Some(0x16),
+        Key::Z => Some(0x17),
+        Key::D4 => Some(0x18),
+        Key::D3 => Some(0x1A),
+        Key::D6 => Some(0x1B),
+        Key::Escape => Some(0x1C),
+        Key::D5 => Some(0x1D),
+        Key::D2 => Some(0x1E),
+        Key::D1 => Some(0x1F),
+        Key::Comma => Some(0x20),
+        Key::Space => Some(0x21),
+        Key::Period => Some(0x22),
+        Key::N => Some(0x23),
+        Key::M => Some(0x25),
+        Key::Slash => Some(0x26),
+        Key::Inverse => Some(0x27),
+        Key::R => Some(0x28),
+        Key::E => Some(0x2A),
+        Key::Y => Some(0x2B),
+        Key::Tab => Some(0x2C),
+        Key::T => Some(0x2D),
+        Key::W => Some(0x2E),
+        Key::Q => Some(0x2F),
+        Key::D9 => Some(0x30),
+        Key::D0 => Some(0x32),
+        Key::D7 => Some(0x33),
+        Key::Backspace => Some(0x34),
+        Key::D8 => Some(0x35),
+        Key::Less => Some(0x36),
+        Key::Greater => Some(0x37),
+        Key::F => Some(0x38),
+        Key::H => Some(0x39),
+        Key::D => Some(0x3A),
+        Key::Caps => Some(0x3C),
+        Key::G => Some(0x3D),
+        Key::S => Some(0x3E),
+        Key::A => Some(0x3F),
+        Key::Control | Key::Shift | Key::Break | Key::Start | Key::Select | Key::Option => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_codes() {
+        let mut keyboard = Keyboard::new();
+        assert_eq!(keyboard.key_code(), None);
+
+        keyboard.set_key_state(Key::A, KeyState::Pressed);
+        assert_eq!(keyboard.key_code(), Some(0x3F));
+
+        keyboard.set_key_state(Key::Shift, KeyState::Pressed);
+        assert_eq!(keyboard.key_code(), Some(0x7F));
+        keyboard.set_key_state(Key::Control, KeyState::Pressed);
+        assert_eq!(keyboard.key_code(), Some(0xFF));
+        keyboard.set_key_state(Key::Shift, KeyState::Released);
+        keyboard.set_key_state(Key::Control, KeyState::Released);
+
+        keyboard.set_key_state(Key::Return, KeyState::Pressed);
+        assert_eq!(keyboard.key_code(), Some(0x0C));
+
+        // Releasing the most recent key brings back the one that's still held.
+        keyboard.set_key_state(Key::Return, KeyState::Released);
+        assert_eq!(keyboard.key_code(), Some(0x3F));
+        keyboard.set_key_state(Key::A, KeyState::Released);
+        assert_eq!(keyboard.key_code(), None);
+    }
+
+    #[test]
+    fn special_keys() {
+        let mut keyboard = Keyboard::new();
+        keyboard.set_key_state(Key::Shift, KeyState::Pressed);
+        keyboard.set_key_state(Key::Break, KeyState::Pressed);
+        keyboard.set_key_state(Key::Start, KeyState::Pressed);
+        keyboard.set_key_state(Key::Option, KeyState::Pressed);
+        assert_eq!(keyboard.key_code(), None);
+        assert!(keyboard.shift_pressed());
+        assert!(keyboard.break_pressed());
+        assert_eq!(keyboard.console_keys(), 0b101);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::keyboard::Key as AtariKey;
+use common::config::read_config_file;
+use common::keymap::parse_key_map;
+use common::keymap::KeymapError;
+use piston::Key;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Maps host keys to the Atari keyboard.
+pub struct Keymap {
+    keys: HashMap<Key, AtariKey>,
+}
+
+/// Keymap configuration, as read from a file. Keys that are not mentioned in
+/// the file retain their default mapping.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct KeymapConfig {
+    keys: HashMap<String, AtariKey>,
+}
+
+impl Keymap {
+    pub fn read(path: &Path) -> Result<Self, KeymapError> {
+        return Self::from_config(read_config_file(path)?);
+    }
+
+    fn from_config(config: KeymapConfig) -> Result<Self, KeymapError> {
+        let mut keymap = Self::default();
+        keymap.keys.extend(parse_key_map(config.keys)?);
+        return Ok(keymap);
+    }
+
+    pub fn atari_key(&self, key: Key) -> Option<AtariKey> {
+        self.keys.get(&key).copied()
+    }
+}
+
+impl Default for Keymap {
+    /// Maps host keys to the Atari keys located in the same place on the
+    /// keyboard. Function keys act as the console keys and HELP.
+    fn default() -> Self {
+        Self {
+            keys: HashMap::from([
+                (Key::Escape, AtariKey::Escape),
+                (Key::D1, AtariKey::D1),
+                (Key::D2, AtariKey::D2),
+                (Key::D3, AtariKey::D3),
+                (Key::D4, AtariKey::D4),
+                (Key::D5, AtariKey::D5),
+                (Key::D6, AtariKey::D6),
+                (Key::D7, AtariKey::D7),
+                (Key::D8, AtariKey::D8),
+                (Key::D9, AtariKey::D9),
+                (Key::D0, AtariKey::D0),
+                (Key::Minus, AtariKey::Less),
+                (Key::Equals, AtariKey::Greater),
+                (Key::Backspace, AtariKey::Backspace),
+                (Key::Pause, AtariKey::Break),
+                (Key::F7, AtariKey::Break),
+                (Key::Tab, AtariKey::Tab),
+                (Key::Q, AtariKey::Q),
+                (Key::W, AtariKey::W),
+                (Key::E, AtariKey::E),
+                (Key::R, AtariKey::R),
+                (Key::T, AtariKey::T),
+                (Key::Y, AtariKey::Y),
+                (Key::U, AtariKey::U),
+                (Key::I, AtariKey::I),
+                (Key::O, AtariKey::O),
+                (Key::P, AtariKey::P),
+                (Key::LeftBracket, AtariKey::Minus),
+                (Key::RightBracket, AtariKey::Equals),
+                (Key::Return, AtariKey::Return),
+                (Key::LCtrl, AtariKey::Control),
+                (Key::RCtrl, AtariKey::Control),
+                (Key::A, AtariKey::A),
+                (Key::S, AtariKey::S),
+                (Key::D, AtariKey::D),
+                (Key::F, AtariKey::F),
+                (Key::G, AtariKey::G),
+                (Key::H, AtariKey::H),
+                (Key::J, AtariKey::J),
+                (Key::K, AtariKey::K),
+                (Key::L, AtariKey::L),
+                (Key::Semicolon, AtariKey::Semicolon),
+                (Key::Quote, AtariKey::Plus),
+                (Key::Backslash, AtariKey::Asterisk),
+                (Key::CapsLock, AtariKey::Caps),
+                (Key::LShift, AtariKey::Shift),
+                (Key::RShift, AtariKey::Shift),
+                (Key::Z, AtariKey::Z),
+                (Key::X, AtariKey::X),
+                (Key::C, AtariKey::C),
+                (Key::V, AtariKey::V),
+                (Key::B, AtariKey::B),
+                (Key::N, AtariKey::N),
+                (Key::M, AtariKey::M),
+                (Key::Comma, AtariKey::Comma),
+                (Key::Period, AtariKey::Period),
+                (Key::Slash, AtariKey::Slash),
+                (Key::Backquote, AtariKey::Inverse),
+                (Key::Space, AtariKey::Space),
+                (Key::F1, AtariKey::Help),
+                (Key::F2, AtariKey::Option),
+                (Key::F3, AtariKey::Select),
+                (Key::F4, AtariKey::Start),
+            ]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::config::parse_config;
+    use common::config::ConfigFormat;
+
+    #[test]
+    fn default_mapping() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.atari_key(Key::A), Some(AtariKey::A));
+        assert_eq!(keymap.atari_key(Key::Minus), Some(AtariKey::Less));
+        assert_eq!(keymap.atari_key(Key::F4), Some(AtariKey::Start));
+        assert_eq!(keymap.atari_key(Key::F12), None);
+    }
+
+    #[test]
+    fn overrides_defaults() {
+        let config = parse_config(
+            r#"{"keys": {"F12": "Break", "Backquote": "Escape"}}"#,
+            ConfigFormat::Json,
+        )
+        .unwrap();
+        let keymap = Keymap::from_config(config).unwrap();
+        assert_eq!(keymap.atari_key(Key::F12), Some(AtariKey::Break));
+        assert_eq!(keymap.atari_key(Key::Backquote), Some(AtariKey::Escape));
+        assert_eq!(keymap.atari_key(Key::A), Some(AtariKey::A));
+    }
+}
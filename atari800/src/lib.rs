@@ -0,0 +1,12 @@
+pub mod address_space;
+pub mod antic;
+pub mod app;
+pub mod atari800;
+pub mod colors;
+pub mod gtia;
+pub mod keyboard;
+pub mod keymap;
+pub mod pia;
+pub mod pokey;
+
+mod test_utils;
@@ -0,0 +1,80 @@
+use atari800::app::Atari800Controller;
+use atari800::atari800::Atari800;
+use atari800::atari800::CARTRIDGE_FILE_EXTENSIONS;
+use atari800::keymap::Keymap;
+use clap::Parser;
+use common::app::AppController;
+use common::app::Application;
+use common::app::CommonCliArguments;
+use common::archive::read_image_file;
+use std::path::Path;
+use ya6502::cpu::CompositeMachine;
+use ya6502::memory::Rom;
+
+#[derive(Parser)]
+struct Args {
+    #[clap(flatten)]
+    common: CommonCliArguments,
+
+    /// A 16K XL/XE OS ROM image. For licensing reasons, it's not included in
+    /// the emulator.
+    #[clap(long)]
+    os_rom: String,
+
+    /// An 8K cartridge ROM image, mapped at $A000-$BFFF. Can also be
+    /// compressed in a ZIP or gzip file.
+    #[clap(long)]
+    cartridge: Option<String>,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let os_rom = std::fs::read(&args.os_rom).expect("Unable to read the OS ROM file");
+    let mut atari = Atari800::new(
+        &os_rom,
+        args.common.power_on_state(),
+        args.common.ram_pattern(),
+    )
+    .expect("Unable to initialize Atari");
+    atari.mut_cpu().set_permissive(args.common.permissive);
+
+    if let Some(file) = &args.cartridge {
+        let image = read_image_file(Path::new(file), CARTRIDGE_FILE_EXTENSIONS)
+            .expect("Unable to read the cartridge file");
+        atari.set_cartridge(Some(
+            Rom::new(&image.bytes).expect("Unable to load the cartridge"),
+        ));
+    }
+
+    let mut controller = Atari800Controller::new(&mut atari, args.common.create_debugger());
+    controller.set_tracer(args.common.create_tracer());
+    controller.set_coverage_tracker(args.common.create_coverage_tracker());
+    controller
+        .install_cheats(&args.common.cheats)
+        .expect("Unable to install cheats");
+    if let Some(path) = &args.common.keymap {
+        let keymap = Keymap::read(Path::new(path)).expect("Unable to read the keymap file");
+        controller.set_keymap(keymap);
+    }
+    signal_hook::flag::register(signal_hook::consts::SIGINT, controller.interrupted())
+        .expect("Unable to set interrupt signal handler");
+    let controller = args.common.create_input_log_controller(controller);
+
+    if args.common.headless {
+        args.common
+            .create_headless_runner(controller)
+            .run(args.common.frames)
+            .expect("Unable to store the frames");
+    } else {
+        let program = args.cartridge.as_deref().map(Path::new);
+        let controller = args.common.create_watching_controller(controller, program);
+        let mut app = Application::new(controller, args.common.backend, "Atari 800XL", 4, 2);
+        app.set_speed(args.common.speed);
+        app.set_sync_mode(args.common.sync);
+        app.set_max_frame_skip(args.common.max_frame_skip);
+        app.set_capture_dir(&args.common.capture_dir);
+        app.set_crt_effects(args.common.crt_effects());
+        app.run();
+    }
+}
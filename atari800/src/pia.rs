@@ -0,0 +1,127 @@
+use ya6502::memory::Inspect;
+use ya6502::memory::Memory;
+use ya6502::memory::Read;
+use ya6502::memory::ReadResult;
+use ya6502::memory::Write;
+use ya6502::memory::WriteResult;
+
+/// A 6520 PIA chip. Port A is connected to joystick directions, and port B
+/// controls the memory layout on XL/XE machines. Interrupt lines are not
+/// emulated.
+#[derive(Debug)]
+pub struct Pia {
+    ports: [PiaPort; 2],
+}
+
+#[derive(Debug, Default)]
+struct PiaPort {
+    output: u8,
+    direction: u8,
+    control: u8,
+}
+
+impl PiaPort {
+    /// Input lines are pulled up, so they read as 1 unless something pulls
+    /// them low.
+    fn pins(&self) -> u8 {
+        self.output | !self.direction
+    }
+
+    fn read(&self, register: u16) -> u8 {
+        if register == 0 {
+            return if self.control & flags::CONTROL_PORT_ACCESS != 0 {
+                self.pins()
+            } else {
+                self.direction
+            };
+        }
+        return self.control;
+    }
+
+    fn write(&mut self, register: u16, value: u8) {
+        if register == 0 {
+            if self.control & flags::CONTROL_PORT_ACCESS != 0 {
+                self.output = value;
+            } else {
+                self.direction = value;
+            }
+        } else {
+            self.control = value;
+        }
+    }
+}
+
+impl Pia {
+    pub fn new() -> Self {
+        Self {
+            ports: Default::default(),
+        }
+    }
+
+    /// Returns the state of port B pins.
+    pub fn port_b(&self) -> u8 {
+        self.ports[1].pins()
+    }
+}
+
+impl Default for Pia {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for Pia {
+    fn write(&mut self, address: u16, value: u8) -> WriteResult {
+        // Registers are ordered as: PORTA, PORTB, PACTL, PBCTL.
+        let register = address & 0b11;
+        self.ports[(register & 1) as usize].write(register >> 1, value);
+        Ok(())
+    }
+}
+
+impl Inspect for Pia {
+    fn inspect(&self, address: u16) -> ReadResult {
+        let register = address & 0b11;
+        Ok(self.ports[(register & 1) as usize].read(register >> 1))
+    }
+}
+
+impl Read for Pia {
+    fn read(&mut self, address: u16) -> ReadResult {
+        self.inspect(address)
+    }
+}
+
+impl Memory for Pia {}
+
+mod flags {
+    /// If set in a control register, the port register is accessed instead
+    /// of the data direction register.
+    pub const CONTROL_PORT_ACCESS: u8 = 0b0000_0100;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ports() {
+        let mut pia = Pia::new();
+        assert_eq!(pia.port_b(), 0xFF);
+
+        // Set up port B: the lower 4 bits are outputs.
+        pia.write(0xD301, 0x0F).unwrap();
+        pia.write(0xD303, 0b0011_0100).unwrap();
+        pia.write(0xD301, 0b1010_1010).unwrap();
+        assert_eq!(pia.port_b(), 0b1111_1010);
+        assert_eq!(pia.read(0xD301).unwrap(), 0b1111_1010);
+        assert_eq!(pia.read(0xD303).unwrap(), 0b0011_0100);
+
+        // Port A is independent and reads as all 1s.
+        pia.write(0xD302, 0b0011_0100).unwrap();
+        assert_eq!(pia.read(0xD300).unwrap(), 0xFF);
+
+        // Registers are mirrored every 4 bytes.
+        assert_eq!(pia.read(0xD3FD).unwrap(), 0b1111_1010);
+    }
+}
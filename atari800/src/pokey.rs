@@ -0,0 +1,206 @@
+use ya6502::memory::Inspect;
+use ya6502::memory::Memory;
+use ya6502::memory::Read;
+use ya6502::memory::ReadResult;
+use ya6502::memory::Write;
+use ya6502::memory::WriteResult;
+
+/// A POKEY chip. So far, it only handles the keyboard, the random number
+/// generator, and interrupts. Audio, timers, and serial I/O are not emulated;
+/// their registers only keep the last written values, so that they can be
+/// inspected.
+#[derive(Debug)]
+pub struct Pokey {
+    registers: [u8; 0x10],
+    kbcode: u8,
+    /// Interrupt status. Note that the bits are active low: an interrupt is
+    /// pending if its bit is 0.
+    irq_status: u8,
+    irq_enable: u8,
+    key_pressed: bool,
+    shift_pressed: bool,
+    break_pressed: bool,
+    /// A 17-bit polynomial counter that feeds the RANDOM register.
+    polynomial_counter: u32,
+}
+
+impl Pokey {
+    pub fn new() -> Self {
+        Self {
+            registers: [0; 0x10],
+            kbcode: 0xFF,
+            irq_status: 0xFF,
+            irq_enable: 0,
+            key_pressed: false,
+            shift_pressed: false,
+            break_pressed: false,
+            polynomial_counter: 0x1FFFF,
+        }
+    }
+
+    /// Performs a single CPU cycle tick. Returns `true` if POKEY requests an
+    /// interrupt.
+    pub fn tick(&mut self) -> bool {
+        let feedback = (self.polynomial_counter ^ (self.polynomial_counter >> 5)) & 1;
+        self.polynomial_counter = (self.polynomial_counter >> 1) | (feedback << 16);
+        return self.irq_status != 0xFF;
+    }
+
+    /// Updates the state of keys scanned by POKEY. `key_code` is a code of the
+    /// currently pressed key, or `None` if no key is pressed. Pressing a new
+    /// key or BREAK triggers an interrupt, if enabled.
+    pub fn set_keyboard_state(
+        &mut self,
+        key_code: Option<u8>,
+        shift_pressed: bool,
+        break_pressed: bool,
+    ) {
+        if let Some(key_code) = key_code {
+            if !self.key_pressed || key_code != self.kbcode {
+                self.kbcode = key_code;
+                self.request_interrupt(flags::IRQ_KEYBOARD);
+            }
+        }
+        if break_pressed && !self.break_pressed {
+            self.request_interrupt(flags::IRQ_BREAK);
+        }
+        self.key_pressed = key_code.is_some();
+        self.shift_pressed = shift_pressed;
+        self.break_pressed = break_pressed;
+    }
+
+    fn request_interrupt(&mut self, mask: u8) {
+        self.irq_status &= !(mask & self.irq_enable);
+    }
+
+    fn skstat(&self) -> u8 {
+        let mut value = 0xFF;
+        if self.key_pressed {
+            value &= !flags::SKSTAT_KEY_PRESSED;
+        }
+        if self.shift_pressed {
+            value &= !flags::SKSTAT_SHIFT_PRESSED;
+        }
+        return value;
+    }
+}
+
+impl Default for Pokey {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for Pokey {
+    fn write(&mut self, address: u16, value: u8) -> WriteResult {
+        let register = address & 0x0F;
+        if register == registers::IRQEN {
+            // Disabling an interrupt also clears its status.
+            self.irq_enable = value;
+            self.irq_status |= !value;
+        }
+        self.registers[register as usize] = value;
+        Ok(())
+    }
+}
+
+impl Inspect for Pokey {
+    fn inspect(&self, address: u16) -> ReadResult {
+        match address & 0x0F {
+            // No paddles are connected, so the pot counters reach their
+            // maximum value.
+            registers::POT0..=registers::POT7 => Ok(228),
+            registers::ALLPOT => Ok(0),
+            registers::KBCODE => Ok(self.kbcode),
+            registers::RANDOM => Ok((self.polynomial_counter >> 9) as u8),
+            registers::IRQST => Ok(self.irq_status),
+            registers::SKSTAT => Ok(self.skstat()),
+            _ => Ok(0xFF),
+        }
+    }
+}
+
+impl Read for Pokey {
+    fn read(&mut self, address: u16) -> ReadResult {
+        self.inspect(address)
+    }
+}
+
+impl Memory for Pokey {}
+
+mod registers {
+    pub const POT0: u16 = 0x00;
+    pub const POT7: u16 = 0x07;
+    pub const ALLPOT: u16 = 0x08;
+    pub const KBCODE: u16 = 0x09;
+    pub const RANDOM: u16 = 0x0A;
+    pub const IRQEN: u16 = 0x0E;
+    pub const IRQST: u16 = 0x0E;
+    pub const SKSTAT: u16 = 0x0F;
+}
+
+mod flags {
+    pub const IRQ_BREAK: u8 = 0b1000_0000;
+    pub const IRQ_KEYBOARD: u8 = 0b0100_0000;
+    pub const SKSTAT_SHIFT_PRESSED: u8 = 0b0000_1000;
+    pub const SKSTAT_KEY_PRESSED: u8 = 0b0000_0100;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyboard() {
+        let mut pokey = Pokey::new();
+        pokey.write(0xD20E, 0b1100_0000).unwrap();
+        assert_eq!(pokey.read(0xD20F).unwrap(), 0xFF);
+        assert!(!pokey.tick());
+
+        pokey.set_keyboard_state(Some(0x7F), true, false);
+        assert_eq!(pokey.read(0xD209).unwrap(), 0x7F);
+        assert_eq!(pokey.read(0xD20E).unwrap(), 0b1011_1111);
+        assert_eq!(pokey.read(0xD20F).unwrap(), 0b1111_0011);
+        assert!(pokey.tick());
+
+        // Acknowledge the interrupt.
+        pokey.write(0xD20E, 0b1000_0000).unwrap();
+        pokey.write(0xD20E, 0b1100_0000).unwrap();
+        assert_eq!(pokey.read(0xD20E).unwrap(), 0xFF);
+        assert!(!pokey.tick());
+
+        // Holding the same key doesn't trigger another interrupt.
+        pokey.set_keyboard_state(Some(0x7F), true, false);
+        assert!(!pokey.tick());
+
+        pokey.set_keyboard_state(None, false, false);
+        assert_eq!(pokey.read(0xD209).unwrap(), 0x7F);
+        assert_eq!(pokey.read(0xD20F).unwrap(), 0xFF);
+        assert!(!pokey.tick());
+    }
+
+    #[test]
+    fn break_key() {
+        let mut pokey = Pokey::new();
+        pokey.set_keyboard_state(None, false, true);
+        assert!(!pokey.tick(), "BREAK interrupt should be disabled");
+        pokey.set_keyboard_state(None, false, false);
+
+        pokey.write(0xD20E, 0b1000_0000).unwrap();
+        pokey.set_keyboard_state(None, false, true);
+        assert_eq!(pokey.read(0xD20E).unwrap(), 0b0111_1111);
+        assert!(pokey.tick());
+    }
+
+    #[test]
+    fn random() {
+        let mut pokey = Pokey::new();
+        let values: Vec<u8> = (0..8)
+            .map(|_| {
+                pokey.tick();
+                pokey.read(0xD20A).unwrap()
+            })
+            .collect();
+        assert!(values.iter().any(|v| *v != values[0]));
+    }
+}
@@ -0,0 +1,42 @@
+#![cfg(test)]
+
+use crate::atari800::Atari800;
+use common::app::Machine;
+use image::RgbaImage;
+use ya6502::cpu::CompositeMachine;
+use ya6502::cpu::CpuError;
+use ya6502::cpu::PowerOnState;
+use ya6502::memory::RamPattern;
+
+pub fn next_frame(atari: &mut Atari800) -> Result<RgbaImage, CpuError> {
+    match atari.run_frame() {
+        Ok(image) => return Ok(image.clone()),
+        Err(e) => {
+            eprintln!("ERROR: {}. Machine halted.", e);
+            eprintln!("{}", atari.cpu());
+            eprintln!("{}", atari.cpu().memory());
+            return Err(e);
+        }
+    }
+}
+
+/// Creates a 16K OS ROM image that starts executing a given program at 0xC000.
+/// The interrupt handler is placed at 0xC100 and handles both NMI and IRQ.
+pub fn os_rom_with_program(program: &[u8], interrupt_handler: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0; 0x4000];
+    rom[..program.len()].copy_from_slice(program);
+    rom[0x100..0x100 + interrupt_handler.len()].copy_from_slice(interrupt_handler);
+    rom[0x3FFA..].copy_from_slice(&[0x00, 0xC1, 0x00, 0xC0, 0x00, 0xC1]);
+    return rom;
+}
+
+pub fn atari_with_program(program: &[u8], interrupt_handler: &[u8]) -> Atari800 {
+    let mut atari = Atari800::new(
+        &os_rom_with_program(program, interrupt_handler),
+        PowerOnState::Seeded(0),
+        RamPattern::default(),
+    )
+    .unwrap();
+    atari.reset();
+    return atari;
+}
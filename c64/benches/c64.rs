@@ -0,0 +1,58 @@
+use c64::c64::C64;
+use c64::frame_renderer::FrameRenderer;
+use c64::vic::Vic;
+use c64::vic::VideoOutput;
+use c64::vic::RASTER_LENGTH;
+use c64::vic::TOTAL_HEIGHT;
+use common::app::{FrameStatus, Machine};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::cell::RefCell;
+use std::rc::Rc;
+use ya6502::cpu::PowerOnState;
+use ya6502::memory::{Ram, RamPattern, Write};
+
+fn vic_tick(c: &mut Criterion) {
+    let mut vic = Vic::new(Box::new(Ram::new(16)), Rc::new(RefCell::new(Ram::new(16))));
+    // Turn the screen on and enable all sprites, so that the benchmark covers
+    // the graphics and sprite pipelines.
+    vic.write(0xD011, 0x1B).unwrap();
+    vic.write(0xD015, 0xFF).unwrap();
+    c.bench_function("vic_tick", |b| b.iter(|| black_box(vic.tick().unwrap())));
+}
+
+fn frame_renderer_frame(c: &mut Criterion) {
+    // A full frame, including the blanking areas, with pixels cycling through
+    // all 16 colors.
+    let outputs: Vec<VideoOutput> = (0..TOTAL_HEIGHT)
+        .flat_map(|raster_line| {
+            (0..RASTER_LENGTH).map(move |x| VideoOutput {
+                color: ((x + raster_line) % 16) as u8,
+                x,
+                raster_line,
+            })
+        })
+        .collect();
+    let mut frame_renderer = FrameRenderer::default();
+    c.bench_function("frame_renderer_frame", |b| {
+        b.iter(|| {
+            for output in &outputs {
+                frame_renderer.consume(*output);
+            }
+            black_box(frame_renderer.frame_image());
+        })
+    });
+}
+
+fn c64_frame(c: &mut Criterion) {
+    let mut c64 = C64::new(PowerOnState::Seeded(0), RamPattern::default()).unwrap();
+    c64.reset();
+
+    // Each iteration runs the next frame, so most of the measurements are
+    // taken while the KERNAL idles in the BASIC prompt loop.
+    c.bench_function("c64_frame", |b| {
+        b.iter(|| while let FrameStatus::Pending = c64.tick().unwrap() {})
+    });
+}
+
+criterion_group!(benches, vic_tick, frame_renderer_frame, c64_frame);
+criterion_main!(benches);
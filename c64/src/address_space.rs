@@ -1,10 +1,15 @@
+use crate::cartridge::CartridgeMode;
+use crate::cartridge::ExpansionPortMapper;
 use crate::port::Port;
 use std::cell::RefCell;
 use std::fmt;
 use std::rc::Rc;
 use ya6502::memory::dump_zero_page;
+use ya6502::memory::memory_map_from;
 use ya6502::memory::Inspect;
 use ya6502::memory::Memory;
+use ya6502::memory::MemoryKind;
+use ya6502::memory::MemoryRegion;
 use ya6502::memory::Ram;
 use ya6502::memory::Read;
 use ya6502::memory::ReadError;
@@ -29,13 +34,15 @@ where
     cpu_port: Port,
     ram: Rc<RefCell<Ram>>,
     basic_rom: Rom,
+    /// Character ROM, shared with the VIC address space.
+    char_rom: Rc<RefCell<Rom>>,
     vic: Vic,
     sid: Sid,
     color_ram: Rc<RefCell<Ram>>, // TODO: replace with an actual single-nibble RAM
     cia1: Cia,
     cia2: Cia,
     kernal_rom: Rom,
-    pub cartridge: Option<Cartridge>,
+    pub cartridge: Option<Box<dyn ExpansionPortMapper>>,
 }
 
 impl<Vic, Sid, Cia> AddressSpace<Vic, Sid, Cia>
@@ -44,9 +51,24 @@ where
     Sid: Memory,
     Cia: Memory,
 {
+    pub fn vic(&self) -> &Vic {
+        &self.vic
+    }
     pub fn mut_vic(&mut self) -> &mut Vic {
         &mut self.vic
     }
+    pub fn sid(&self) -> &Sid {
+        &self.sid
+    }
+    pub fn mut_sid(&mut self) -> &mut Sid {
+        &mut self.sid
+    }
+    pub fn cia1(&self) -> &Cia {
+        &self.cia1
+    }
+    pub fn cia2(&self) -> &Cia {
+        &self.cia2
+    }
     pub fn mut_cia1(&mut self) -> &mut Cia {
         &mut self.cia1
     }
@@ -67,6 +89,7 @@ where
     pub fn new(
         ram: Rc<RefCell<Ram>>,
         basic_rom: Rom,
+        char_rom: Rc<RefCell<Rom>>,
         vic: Vic,
         sid: Sid,
         color_ram: Rc<RefCell<Ram>>,
@@ -84,6 +107,7 @@ where
             cpu_port,
             ram,
             basic_rom,
+            char_rom,
             vic,
             sid,
             color_ram,
@@ -95,44 +119,150 @@ where
     }
 }
 
+/// Address space areas, as seen by the CPU when reading memory.
+enum ReadArea {
+    CpuPortDirection,
+    CpuPort,
+    Ram,
+    BasicRom,
+    CharRom,
+    Vic,
+    Sid,
+    ColorRam,
+    Cia1,
+    Cia2,
+    Io,
+    KernalRom,
+    Cartridge,
+}
+
+impl<Vic, Sid, Cia> AddressSpace<Vic, Sid, Cia>
+where
+    Vic: Memory,
+    Sid: Memory,
+    Cia: Memory,
+{
+    /// Decides which memory area is read at a given address. Shared between
+    /// [`Read`] and [`Inspect`] implementations to make sure that both of them
+    /// see the same memory layout.
+    ///
+    /// The layout is controlled by the LORAM, HIRAM, and CHAREN lines of the
+    /// CPU port, and by the cartridge mode. BASIC ROM requires both LORAM and
+    /// HIRAM, and KERNAL ROM requires HIRAM. The $D000-$DFFF area contains
+    /// I/O if CHAREN is set, or character ROM otherwise, unless both LORAM and
+    /// HIRAM are cleared, which makes the entire address space RAM. Ultimax
+    /// cartridges ignore the CPU port when it comes to I/O and KERNAL area.
+    fn read_area(&self, address: u16) -> ReadArea {
+        let cartridge_mode = self.cartridge_mode();
+        let port = self.cpu_port.read();
+        let loram = port & flags::CPU_PORT_LORAM != 0;
+        let hiram = port & flags::CPU_PORT_HIRAM != 0;
+        let charen = port & flags::CPU_PORT_CHAREN != 0;
+        match address {
+            0x0000 => ReadArea::CpuPortDirection,
+            0x0001 => ReadArea::CpuPort,
+            0x8000..=0x9FFF => match cartridge_mode {
+                Some(CartridgeMode::Ultimax) => ReadArea::Cartridge,
+                Some(CartridgeMode::Standard8k | CartridgeMode::Standard16k) if loram && hiram => {
+                    ReadArea::Cartridge
+                }
+                _ => ReadArea::Ram,
+            },
+            0xA000..=0xBFFF => match cartridge_mode {
+                Some(CartridgeMode::Standard16k) if hiram => ReadArea::Cartridge,
+                _ if loram && hiram => ReadArea::BasicRom,
+                _ => ReadArea::Ram,
+            },
+            0xD000..=0xDFFF => {
+                if self.io_visible() {
+                    io_area(address)
+                } else if !charen && (loram || hiram) {
+                    ReadArea::CharRom
+                } else {
+                    ReadArea::Ram
+                }
+            }
+            0xE000..=0xFFFF => match cartridge_mode {
+                Some(CartridgeMode::Ultimax) => ReadArea::Cartridge,
+                _ if hiram => ReadArea::KernalRom,
+                _ => ReadArea::Ram,
+            },
+            _ => ReadArea::Ram,
+        }
+    }
+
+    fn cartridge_mode(&self) -> Option<CartridgeMode> {
+        self.cartridge.as_ref().map(|cartridge| cartridge.mode())
+    }
+
+    /// Returns `true` if the I/O chips are visible at $D000-$DFFF. Note that
+    /// this affects writes as well as reads.
+    fn io_visible(&self) -> bool {
+        if let Some(CartridgeMode::Ultimax) = self.cartridge_mode() {
+            return true;
+        }
+        let port = self.cpu_port.read();
+        return port & flags::CPU_PORT_CHAREN != 0
+            && port & (flags::CPU_PORT_LORAM | flags::CPU_PORT_HIRAM) != 0;
+    }
+}
+
+/// Decides which I/O area is read at a given address in the $D000-$DFFF range.
+fn io_area(address: u16) -> ReadArea {
+    match address {
+        0xD000..=0xD3FF => ReadArea::Vic,
+        0xD400..=0xD7FF => ReadArea::Sid,
+        0xD800..=0xDBFF => ReadArea::ColorRam,
+        0xDC00..=0xDCFF => ReadArea::Cia1,
+        0xDD00..=0xDDFF => ReadArea::Cia2,
+        _ => ReadArea::Io,
+    }
+}
+
 impl<Vic, Sid, Cia> Inspect for AddressSpace<Vic, Sid, Cia>
 where
     Vic: Memory + Inspect,
     Sid: Memory + Inspect,
     Cia: Memory + Inspect,
 {
-    // TODO: Reuse the address matching code between inspect() and read()!
     fn inspect(&self, address: u16) -> ReadResult {
-        match address {
-            0x0000 => Ok(self.cpu_port.direction),
-            0x0001 => Ok(self.cpu_port.read()),
-            0x8000..=0x9FFF => match &self.cartridge {
-                Some(Cartridge { mode: _, rom }) => rom.inspect(address),
-                _ => self.ram.borrow().inspect(address),
+        match self.read_area(address) {
+            ReadArea::CpuPortDirection => Ok(self.cpu_port.direction),
+            ReadArea::CpuPort => Ok(self.cpu_port.read()),
+            ReadArea::Ram => self.ram.borrow().inspect(address),
+            ReadArea::BasicRom => self.basic_rom.inspect(address),
+            ReadArea::CharRom => self.char_rom.borrow().inspect(address),
+            ReadArea::Vic => self.vic.inspect(address),
+            ReadArea::Sid => self.sid.inspect(address),
+            ReadArea::ColorRam => self.color_ram.borrow().inspect(address),
+            ReadArea::Cia1 => self.cia1.inspect(address),
+            ReadArea::Cia2 => self.cia2.inspect(address),
+            ReadArea::Io => Err(ReadError { address }),
+            ReadArea::KernalRom => self.kernal_rom.inspect(address),
+            ReadArea::Cartridge => match &self.cartridge {
+                Some(cartridge) => cartridge.inspect(address),
+                None => Err(ReadError { address }),
             },
-            0xA000..=0xBFFF => match &self.cartridge {
-                Some(Cartridge {
-                    mode: CartridgeMode::Standard16k,
-                    rom,
-                }) => rom.inspect(address),
-                _ => self.basic_rom.inspect(address),
-            },
-            0xD000..=0xD3FF => self.vic.inspect(address),
-            0xD400..=0xD7FF => self.sid.inspect(address),
-            0xD800..=0xDBFF => self.color_ram.borrow().inspect(address),
-            0xDC00..=0xDCFF => self.cia1.inspect(address),
-            0xDD00..=0xDDFF => self.cia2.inspect(address),
-            0xDE00..=0xDFFF => Err(ReadError { address }),
-            0xE000..=0xFFFF => match &self.cartridge {
-                Some(Cartridge {
-                    mode: CartridgeMode::Ultimax,
-                    rom,
-                }) => rom.inspect(address),
-                _ => self.kernal_rom.inspect(address),
-            },
-            _ => self.ram.borrow().inspect(address),
         }
     }
+
+    fn memory_map(&self) -> Vec<MemoryRegion> {
+        memory_map_from(|address| match self.read_area(address) {
+            ReadArea::Ram | ReadArea::ColorRam => MemoryKind::Ram,
+            ReadArea::BasicRom | ReadArea::CharRom | ReadArea::KernalRom => MemoryKind::Rom,
+            ReadArea::CpuPortDirection
+            | ReadArea::CpuPort
+            | ReadArea::Vic
+            | ReadArea::Sid
+            | ReadArea::Cia1
+            | ReadArea::Cia2 => MemoryKind::Io,
+            ReadArea::Io => MemoryKind::Unmapped,
+            ReadArea::Cartridge => match &self.cartridge {
+                Some(_) => MemoryKind::Rom,
+                None => MemoryKind::Unmapped,
+            },
+        })
+    }
 }
 
 impl<Vic, Sid, Cia> Read for AddressSpace<Vic, Sid, Cia>
@@ -141,37 +271,31 @@ where
     Sid: Memory,
     Cia: Memory,
 {
-    // TODO: Reuse the address matching code between inspect() and read()!
+    /// The expansion port carries the entire address and data bus, so the
+    /// cartridge gets notified about every read, not only from its own ROM.
     fn read(&mut self, address: u16) -> ReadResult {
-        match address {
-            0x0000 => Ok(self.cpu_port.direction),
-            0x0001 => Ok(self.cpu_port.read()),
-            0x8000..=0x9FFF => match &mut self.cartridge {
-                Some(Cartridge { mode: _, rom }) => rom.read(address),
-                _ => self.ram.borrow_mut().read(address),
+        let value = match self.read_area(address) {
+            ReadArea::CpuPortDirection => Ok(self.cpu_port.direction),
+            ReadArea::CpuPort => Ok(self.cpu_port.read()),
+            ReadArea::Ram => self.ram.borrow_mut().read(address),
+            ReadArea::BasicRom => self.basic_rom.read(address),
+            ReadArea::CharRom => self.char_rom.borrow_mut().read(address),
+            ReadArea::Vic => self.vic.read(address),
+            ReadArea::Sid => self.sid.read(address),
+            ReadArea::ColorRam => self.color_ram.borrow_mut().read(address),
+            ReadArea::Cia1 => self.cia1.read(address),
+            ReadArea::Cia2 => self.cia2.read(address),
+            ReadArea::Io => Err(ReadError { address }),
+            ReadArea::KernalRom => self.kernal_rom.read(address),
+            ReadArea::Cartridge => match &mut self.cartridge {
+                Some(cartridge) => cartridge.read(address),
+                None => Err(ReadError { address }),
             },
-            0xA000..=0xBFFF => match &mut self.cartridge {
-                Some(Cartridge {
-                    mode: CartridgeMode::Standard16k,
-                    rom,
-                }) => rom.read(address),
-                _ => self.basic_rom.read(address),
-            },
-            0xD000..=0xD3FF => self.vic.read(address),
-            0xD400..=0xD7FF => self.sid.read(address),
-            0xD800..=0xDBFF => self.color_ram.borrow_mut().read(address),
-            0xDC00..=0xDCFF => self.cia1.read(address),
-            0xDD00..=0xDDFF => self.cia2.read(address),
-            0xDE00..=0xDFFF => Err(ReadError { address }),
-            0xE000..=0xFFFF => match &mut self.cartridge {
-                Some(Cartridge {
-                    mode: CartridgeMode::Ultimax,
-                    rom,
-                }) => rom.read(address),
-                _ => self.kernal_rom.read(address),
-            },
-            _ => self.ram.borrow_mut().read(address),
+        }?;
+        if let Some(cartridge) = &mut self.cartridge {
+            cartridge.observe_read(address, value);
         }
+        return Ok(value);
     }
 }
 
@@ -181,25 +305,29 @@ where
     Sid: Memory,
     Cia: Memory,
 {
+    /// Writes to ROM areas always end up in the RAM underneath. Writes to the
+    /// $D000-$DFFF area go to RAM unless I/O is visible. Just like reads, all
+    /// writes are observed by the cartridge.
     fn write(&mut self, address: u16, value: u8) -> WriteResult {
         match address {
             0x0000 => Ok(self.cpu_port.direction = value),
-            0x0001 => {
-                // For now, only allow one memory layout.
-                if value & 0b0000_0111 == 0b0000_0111 {
-                    Ok(self.cpu_port.register = value)
-                } else {
-                    Err(WriteError { address, value })
-                }
-            }
+            0x0001 => Ok(self.cpu_port.register = value),
+            0xD000..=0xDFFF if !self.io_visible() => self.ram.borrow_mut().write(address, value),
             0xD000..=0xD3FF => self.vic.write(address, value),
             0xD400..=0xD7FF => self.sid.write(address, value),
             0xD800..=0xDBFF => self.color_ram.borrow_mut().write(address, value),
             0xDC00..=0xDCFF => self.cia1.write(address, value),
             0xDD00..=0xDDFF => self.cia2.write(address, value),
-            0xDE00..=0xDFFF => Err(WriteError { address, value }),
+            0xDE00..=0xDFFF => match &mut self.cartridge {
+                Some(cartridge) => cartridge.write(address, value),
+                None => Err(WriteError { address, value }),
+            },
             _ => self.ram.borrow_mut().write(address, value),
+        }?;
+        if let Some(cartridge) = &mut self.cartridge {
+            cartridge.observe_write(address, value);
         }
+        return Ok(());
     }
 }
 
@@ -222,27 +350,10 @@ where
     }
 }
 
-#[derive(Debug)]
-pub struct Cartridge {
-    pub mode: CartridgeMode,
-    pub rom: Rom,
-}
-
-/// Types of cartridge ROM available in the C64 architecture.
-#[derive(Debug)]
-pub enum CartridgeMode {
-    /// Standard 8KiB cartridge ($8000-$9FFF)
-    #[allow(dead_code)]
-    Standard8k,
-    /// Standard 16KiB cartridge ($8000-$BFFF)
-    #[allow(dead_code)]
-    Standard16k,
-    /// Ultimax 16KiB cartridge ($8000-$9FFF, $E000-$FFFF).
-    Ultimax,
-}
-
 /// An address space, as visible by the VIC-II chip. Note that it doesn't
 /// include the Color RAM, since it's addressed using a separate address line.
+/// VIC only has 14 address lines, so it sees a single 16K bank of memory at a
+/// time. The two remaining address bits come from the CIA 2 port A.
 #[derive(Debug)]
 pub struct VicAddressSpace<Ram, ChrRam>
 where
@@ -251,6 +362,8 @@ where
 {
     ram: Rc<RefCell<Ram>>,
     char_rom: Rc<RefCell<ChrRam>>,
+    /// Base address of the current 16K bank.
+    bank_base: u16,
 }
 
 impl<Ram, ChrRam> VicAddressSpace<Ram, ChrRam>
@@ -259,7 +372,24 @@ where
     ChrRam: Read,
 {
     pub fn new(ram: Rc<RefCell<Ram>>, char_rom: Rc<RefCell<ChrRam>>) -> Self {
-        Self { ram, char_rom }
+        Self {
+            ram,
+            char_rom,
+            bank_base: 0x0000,
+        }
+    }
+
+    /// Selects a VIC bank using the value of CIA 2 port A. The bank number is
+    /// determined by bits 0 and 1, which are inverted: %11 selects bank 0
+    /// ($0000-$3FFF), and %00 selects bank 3 ($C000-$FFFF).
+    pub fn set_bank_from_cia_port(&mut self, port_value: u8) {
+        let bank = !port_value & 0b11;
+        self.bank_base = bank as u16 * 0x4000;
+    }
+
+    /// Translates a 14-bit VIC address to an address in the current bank.
+    fn bank_address(&self, address: u16) -> u16 {
+        self.bank_base | address & 0x3FFF
     }
 }
 
@@ -268,35 +398,53 @@ where
     Ram: Read + Inspect,
     ChrRam: Read + Inspect,
 {
-    // TODO: Reuse the address matching code between inspect() and read()!
     fn inspect(&self, address: u16) -> ReadResult {
-        let address = address & 0x3FFF;
-        match address {
-            0x1000..=0x1FFF => self.char_rom.borrow().inspect(address),
-            _ => self.ram.borrow().inspect(address),
+        let address = self.bank_address(address);
+        if maps_char_rom(address) {
+            self.char_rom.borrow().inspect(address)
+        } else {
+            self.ram.borrow().inspect(address)
         }
     }
 }
 
 impl<Ram: Read, ChrRam: Read> Read for VicAddressSpace<Ram, ChrRam> {
-    // TODO: Reuse the address matching code between inspect() and read()!
     fn read(&mut self, address: u16) -> ReadResult {
-        let address = address & 0x3FFF;
-        match address {
-            0x1000..=0x1FFF => self.char_rom.borrow_mut().read(address),
-            _ => self.ram.borrow_mut().read(address),
+        let address = self.bank_address(address);
+        if maps_char_rom(address) {
+            self.char_rom.borrow_mut().read(address)
+        } else {
+            self.ram.borrow_mut().read(address)
         }
     }
 }
 
+/// Checks whether the VIC sees the character ROM at a given address (already
+/// translated to the current VIC bank). The character ROM shadows RAM at
+/// $1000-$1FFF in banks 0 and 2; in banks 1 and 3, VIC sees RAM at this
+/// location.
+fn maps_char_rom(address: u16) -> bool {
+    let bank = address >> 14;
+    (bank == 0 || bank == 2) && (0x1000..=0x1FFF).contains(&(address & 0x3FFF))
+}
+
+mod flags {
+    pub const CPU_PORT_LORAM: u8 = 0b0000_0001;
+    pub const CPU_PORT_HIRAM: u8 = 0b0000_0010;
+    pub const CPU_PORT_CHAREN: u8 = 0b0000_0100;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cartridge::Cartridge;
+    use ya6502::test_utils::LatchMapper;
 
     fn new_address_space() -> AddressSpace<Ram, Ram, Ram> {
         AddressSpace::new(
             Rc::new(RefCell::new(Ram::new(16))),
             Rom::new(&[0xBA; 0x2000]).unwrap(),
+            Rc::new(RefCell::new(Rom::new(&[0xC4; 0x1000]).unwrap())),
             Ram::new(10),
             Ram::new(10),
             Rc::new(RefCell::new(Ram::new(10))),
@@ -403,10 +551,9 @@ mod tests {
     #[test]
     fn cartridge_8k() {
         let mut address_space = new_address_space();
-        address_space.cartridge = Some(Cartridge {
-            mode: CartridgeMode::Standard8k,
-            rom: Rom::new(&[1; 0x10000]).unwrap(),
-        });
+        address_space.cartridge = Some(Box::new(
+            Cartridge::from_raw_image(CartridgeMode::Standard8k, &[1; 0x2000]).unwrap(),
+        ));
 
         assert_eq!(address_space.read(0x7FFF).unwrap(), 0);
         assert_eq!(address_space.read(0x8000).unwrap(), 1);
@@ -417,10 +564,9 @@ mod tests {
     #[test]
     fn cartridge_16k() {
         let mut address_space = new_address_space();
-        address_space.cartridge = Some(Cartridge {
-            mode: CartridgeMode::Standard16k,
-            rom: Rom::new(&[2; 0x10000]).unwrap(),
-        });
+        address_space.cartridge = Some(Box::new(
+            Cartridge::from_raw_image(CartridgeMode::Standard16k, &[2; 0x4000]).unwrap(),
+        ));
 
         assert_eq!(address_space.read(0x7FFF).unwrap(), 0);
         assert_eq!(address_space.read(0x8000).unwrap(), 2);
@@ -432,10 +578,9 @@ mod tests {
     #[test]
     fn cartridge_ultimax() {
         let mut address_space = new_address_space();
-        address_space.cartridge = Some(Cartridge {
-            mode: CartridgeMode::Ultimax,
-            rom: Rom::new(&[3; 0x10000]).unwrap(),
-        });
+        address_space.cartridge = Some(Box::new(
+            Cartridge::from_raw_image(CartridgeMode::Ultimax, &[3; 0x4000]).unwrap(),
+        ));
 
         assert_eq!(address_space.read(0x7FFF).unwrap(), 0);
         assert_eq!(address_space.read(0x8000).unwrap(), 3);
@@ -447,6 +592,55 @@ mod tests {
         assert_eq!(address_space.read(0x0000).unwrap(), 0);
     }
 
+    impl ExpansionPortMapper for LatchMapper {
+        fn mode(&self) -> CartridgeMode {
+            CartridgeMode::Standard8k
+        }
+    }
+
+    #[test]
+    fn custom_mapper() {
+        let mut address_space = new_address_space();
+        address_space.cartridge = Some(Box::new(LatchMapper::default()));
+        assert_eq!(address_space.read(0x8000).unwrap(), 0);
+        address_space.write(0x1234, 42).unwrap();
+        assert_eq!(address_space.read(0x8000).unwrap(), 42);
+        assert_eq!(address_space.read(0x1234).unwrap(), 42);
+        // I/O 2 writes go to the cartridge, too.
+        address_space.write(0xDF00, 43).unwrap();
+        assert_eq!(address_space.read(0x9FFF).unwrap(), 43);
+    }
+
+    #[test]
+    fn inspects_the_same_memory_as_reads() {
+        let mut address_space = new_address_space();
+        address_space.write(0xD000, 73).unwrap(); // VIC
+        address_space.write(0xDD00, 88).unwrap(); // CIA2
+        address_space.write(0xE000, 87).unwrap(); // RAM under KERNEL ROM
+        let addresses = [0x0001, 0x8000, 0xA000, 0xD000, 0xDD00, 0xE000];
+        for address in addresses {
+            assert_eq!(
+                address_space.inspect(address).unwrap(),
+                address_space.read(address).unwrap(),
+                "Address ${:04X}",
+                address
+            );
+        }
+
+        address_space.cartridge = Some(Box::new(
+            Cartridge::from_raw_image(CartridgeMode::Ultimax, &[3; 0x4000]).unwrap(),
+        ));
+        for address in addresses {
+            assert_eq!(
+                address_space.inspect(address).unwrap(),
+                address_space.read(address).unwrap(),
+                "Address ${:04X} with an Ultimax cartridge",
+                address
+            );
+        }
+        assert_eq!(address_space.inspect(0xE000).unwrap(), 3);
+    }
+
     #[test]
     fn cpu_port_direction() {
         let mut address_space = new_address_space();
@@ -461,6 +655,80 @@ mod tests {
         assert_eq!(address_space.read(0x0001).unwrap(), 0b0010_0111);
     }
 
+    #[test]
+    fn cpu_port_banking() {
+        let mut address_space = new_address_space();
+        address_space.write(0xA000, 0x0A).unwrap(); // RAM under BASIC ROM
+        address_space.write(0xE000, 0x0E).unwrap(); // RAM under KERNAL ROM
+        address_space.ram.borrow_mut().write(0xD000, 0x0D).unwrap();
+        address_space.vic.write(0xD000, 0x71).unwrap();
+
+        // Expected values at $A000, $D000, and $E000.
+        let configurations = [
+            (0b000, [0x0A, 0x0D, 0x0E]),
+            (0b001, [0x0A, 0xC4, 0x0E]),
+            (0b010, [0x0A, 0xC4, 0xA1]),
+            (0b011, [0xBA, 0xC4, 0xA1]),
+            (0b100, [0x0A, 0x0D, 0x0E]),
+            (0b101, [0x0A, 0x71, 0x0E]),
+            (0b110, [0x0A, 0x71, 0xA1]),
+            (0b111, [0xBA, 0x71, 0xA1]),
+        ];
+        address_space.write(0x0000, 0b0000_0111).unwrap();
+        for (port, expected) in configurations {
+            address_space.write(0x0001, port).unwrap();
+            let actual = [0xA000, 0xD000, 0xE000].map(|a| address_space.read(a).unwrap());
+            assert_eq!(actual, expected, "CPU port %{:03b}", port);
+        }
+    }
+
+    #[test]
+    fn memory_map() {
+        let mut address_space = new_address_space();
+        address_space.write(0x0000, 0b0000_0111).unwrap();
+        address_space.write(0x0001, 0b0000_0111).unwrap();
+        let region = |start, end, kind| MemoryRegion { start, end, kind };
+        assert_eq!(
+            address_space.memory_map(),
+            [
+                region(0x0000, 0x0001, MemoryKind::Io),
+                region(0x0002, 0x9FFF, MemoryKind::Ram),
+                region(0xA000, 0xBFFF, MemoryKind::Rom),
+                region(0xC000, 0xCFFF, MemoryKind::Ram),
+                region(0xD000, 0xD7FF, MemoryKind::Io),
+                region(0xD800, 0xDBFF, MemoryKind::Ram),
+                region(0xDC00, 0xDDFF, MemoryKind::Io),
+                region(0xDE00, 0xDFFF, MemoryKind::Unmapped),
+                region(0xE000, 0xFFFF, MemoryKind::Rom),
+            ]
+        );
+
+        address_space.write(0x0001, 0b0000_0000).unwrap();
+        assert_eq!(
+            address_space.memory_map(),
+            [
+                region(0x0000, 0x0001, MemoryKind::Io),
+                region(0x0002, 0xFFFF, MemoryKind::Ram),
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_under_character_rom() {
+        let mut address_space = new_address_space();
+        address_space.write(0x0000, 0b0000_0111).unwrap();
+        address_space.write(0x0001, 0b0000_0011).unwrap();
+        address_space.write(0xD020, 0x12).unwrap();
+        assert_eq!(address_space.read(0xD020).unwrap(), 0xC4);
+        assert_eq!(address_space.ram.borrow_mut().read(0xD020).unwrap(), 0x12);
+        assert_eq!(address_space.vic.read(0xD020).unwrap(), 0x00);
+
+        address_space.write(0x0001, 0b0000_0111).unwrap();
+        address_space.write(0xD020, 0x34).unwrap();
+        assert_eq!(address_space.read(0xD020).unwrap(), 0x34);
+        assert_eq!(address_space.ram.borrow_mut().read(0xD020).unwrap(), 0x12);
+    }
+
     #[test]
     fn vic_reads() {
         let mut address_space = new_vic_address_space();
@@ -482,6 +750,29 @@ mod tests {
         assert_eq!(address_space.read(0x3FFF).unwrap(), 68);
     }
 
+    #[test]
+    fn vic_banks() {
+        let mut address_space = new_vic_address_space();
+        for bank in 0..4 {
+            let mut ram = address_space.ram.borrow_mut();
+            ram.write(bank * 0x4000 + 0x0123, bank as u8 + 1).unwrap();
+            ram.write(bank * 0x4000 + 0x1123, bank as u8 + 11).unwrap();
+        }
+
+        address_space.set_bank_from_cia_port(0b1111_1111);
+        assert_eq!(address_space.read(0x0123).unwrap(), 1);
+        assert_eq!(address_space.read(0x1123).unwrap(), 0xCC);
+        address_space.set_bank_from_cia_port(0b1111_1110);
+        assert_eq!(address_space.read(0x0123).unwrap(), 2);
+        assert_eq!(address_space.read(0x1123).unwrap(), 12);
+        address_space.set_bank_from_cia_port(0b1111_1101);
+        assert_eq!(address_space.read(0x0123).unwrap(), 3);
+        assert_eq!(address_space.read(0x1123).unwrap(), 0xCC);
+        address_space.set_bank_from_cia_port(0b1111_1100);
+        assert_eq!(address_space.read(0x0123).unwrap(), 4);
+        assert_eq!(address_space.read(0x1123).unwrap(), 14);
+    }
+
     #[test]
     fn vic_mirroring() {
         let mut address_space = new_vic_address_space();
@@ -1,10 +1,24 @@
 use crate::c64::C64;
+use crate::controllers::ControllerType;
+use crate::controllers::MouseButton as C64MouseButton;
+use crate::controllers::Pot;
+use crate::joystick::JoystickInput;
+use crate::joystick::JoystickPort;
 use crate::keyboard::Key as C64Key;
 use crate::keyboard::KeyState;
+use crate::keymap::C64KeyPress;
+use crate::keymap::KeyboardMode;
+use crate::keymap::Keymap;
 use common::app::AppController;
 use common::app::MachineController;
+use common::cheats::Cheat;
+use common::cheats::CheatError;
 use common::debugger::adapter::DebugAdapter;
+use common::debugger::coverage::CoverageTracker;
+use common::debugger::trace::Tracer;
 use common::debugger::Debugger;
+use common::gamepad::GamepadInput;
+use common::gamepad::GamepadMapping;
 use image::RgbaImage;
 use piston::Button;
 use piston::ButtonArgs;
@@ -13,24 +27,202 @@ use piston::Event;
 use piston::Input;
 use piston::Key;
 use piston::Loop;
+use piston::Motion;
+use piston::MouseButton;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
+/// Number of paddle position units per one pixel of mouse movement.
+const PADDLE_MOUSE_SENSITIVITY: f64 = 0.5;
+
 pub struct C64Controller<'a, A: DebugAdapter> {
     machine_controller: MachineController<'a, C64, A>,
+    /// Control port of the keyboard joystick and the first gamepad. The second
+    /// gamepad is connected to the other port.
+    joystick_port: JoystickPort,
+    gamepad_mapping: GamepadMapping,
     l_gui_key_pressed: bool,
     r_gui_key_pressed: bool,
+    keymap: Keymap,
+    /// In the symbolic keyboard mode, the host shift keys aren't mapped
+    /// directly to C64 shift keys, so we need to track their state.
+    l_shift_key_pressed: bool,
+    r_shift_key_pressed: bool,
+    /// C64 key presses caused by the host keys that are currently pressed.
+    pressed_keys: HashMap<Key, C64KeyPress>,
+    /// Mouse cursor position in frame image coordinates, or `None` if the
+    /// cursor is outside the window.
+    mouse_position: Option<[f64; 2]>,
+    /// The light pen only sees the screen while the left mouse button is held.
+    mouse_button_pressed: bool,
 }
 
 impl<'a, A: DebugAdapter> C64Controller<'a, A> {
-    pub fn new(c64: &'a mut C64, debugger_adapter: Option<A>) -> Self {
-        let debugger = debugger_adapter.map(Debugger::new);
+    pub fn new(
+        c64: &'a mut C64,
+        debugger: Option<Debugger<A>>,
+        joystick_port: JoystickPort,
+    ) -> Self {
         Self {
             machine_controller: MachineController::new(c64, debugger),
+            joystick_port,
+            gamepad_mapping: GamepadMapping::default(),
             l_gui_key_pressed: false,
             r_gui_key_pressed: false,
+            keymap: Keymap::default(),
+            l_shift_key_pressed: false,
+            r_shift_key_pressed: false,
+            pressed_keys: HashMap::new(),
+            mouse_position: None,
+            mouse_button_pressed: false,
         }
     }
+
+    pub fn set_tracer(&mut self, tracer: Option<Tracer<BufWriter<File>>>) {
+        self.machine_controller.set_tracer(tracer);
+    }
+
+    pub fn set_coverage_tracker(&mut self, tracker: Option<CoverageTracker<BufWriter<File>>>) {
+        self.machine_controller.set_coverage_tracker(tracker);
+    }
+
+    pub fn install_cheats(&mut self, cheats: &[Cheat]) -> Result<(), CheatError> {
+        self.machine_controller.install_cheats(cheats)
+    }
+
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    pub fn set_gamepad_mapping(&mut self, gamepad_mapping: GamepadMapping) {
+        self.gamepad_mapping = gamepad_mapping;
+    }
+
+    fn handle_key(&mut self, key: Key, state: ButtonState) {
+        let symbolic = self.keymap.mode() == KeyboardMode::Symbolic;
+        match (key, state) {
+            (Key::LShift, _) if symbolic => {
+                self.l_shift_key_pressed = state == ButtonState::Press;
+            }
+            (Key::RShift, _) if symbolic => {
+                self.r_shift_key_pressed = state == ButtonState::Press;
+            }
+            (_, ButtonState::Press) => {
+                let shifted = self.l_shift_key_pressed || self.r_shift_key_pressed;
+                if let Some(key_press) = self.keymap.key_press(key, shifted) {
+                    self.machine_controller
+                        .mut_machine()
+                        .set_key_state(key_press.key, KeyState::Pressed);
+                    self.pressed_keys.insert(key, key_press);
+                }
+            }
+            (_, ButtonState::Release) => {
+                if let Some(key_press) = self.pressed_keys.remove(&key) {
+                    self.machine_controller
+                        .mut_machine()
+                        .set_key_state(key_press.key, KeyState::Released);
+                }
+            }
+        }
+        self.update_shift_key();
+    }
+
+    /// Presses or releases the C64 shift key, depending on the host shift keys
+    /// and the keys that need to be shifted on C64 (like cursor up in the
+    /// positional mode, or most of the symbols in the symbolic mode).
+    fn update_shift_key(&mut self) {
+        let host_shift = match self.keymap.mode() {
+            KeyboardMode::Positional => self
+                .pressed_keys
+                .values()
+                .any(|key_press| key_press.key == C64Key::LShift),
+            KeyboardMode::Symbolic => self.l_shift_key_pressed || self.r_shift_key_pressed,
+        };
+        let shifted = self
+            .pressed_keys
+            .values()
+            .find_map(|key_press| key_press.shift)
+            .unwrap_or(host_shift);
+        self.machine_controller.mut_machine().set_key_state(
+            C64Key::LShift,
+            if shifted {
+                KeyState::Pressed
+            } else {
+                KeyState::Released
+            },
+        );
+    }
+
+    /// Returns the first control port with a given controller type connected.
+    fn port_with_controller(&self, controller: ControllerType) -> Option<JoystickPort> {
+        let c64 = self.machine_controller.machine();
+        [JoystickPort::Port1, JoystickPort::Port2]
+            .into_iter()
+            .find(|port| c64.controller(*port) == controller)
+    }
+
+    /// Translates relative mouse movement to the movement of a 1351 mouse or,
+    /// if there's none, of the first paddle.
+    fn handle_mouse_movement(&mut self, dx: f64, dy: f64) {
+        if let Some(port) = self.port_with_controller(ControllerType::Mouse) {
+            // The host vertical axis points down, and the 1351 one points up.
+            self.machine_controller.mut_machine().move_mouse(
+                port,
+                dx.round() as i32,
+                -dy.round() as i32,
+            );
+        } else if let Some(port) = self.port_with_controller(ControllerType::Paddles) {
+            // Moving the mouse to the right turns the paddle clockwise, which
+            // decreases the reported value.
+            let c64 = self.machine_controller.mut_machine();
+            let position = c64.paddle_position(port, Pot::X).unwrap() as f64;
+            let position = (position - dx * PADDLE_MOUSE_SENSITIVITY).clamp(0.0, 255.0);
+            c64.set_paddle_position(port, Pot::X, position as u8);
+        }
+    }
+
+    /// Handles a host mouse button. If there's a 1351 mouse connected, its
+    /// buttons are controlled directly. Otherwise, the left button controls
+    /// the first paddle or, if there are no paddles, the light pen.
+    fn handle_mouse_button(&mut self, button: MouseButton, pressed: bool) {
+        let c64_button = match button {
+            MouseButton::Left => C64MouseButton::Left,
+            MouseButton::Right => C64MouseButton::Right,
+            _ => return,
+        };
+        if let Some(port) = self.port_with_controller(ControllerType::Mouse) {
+            self.machine_controller
+                .mut_machine()
+                .set_mouse_button_state(port, c64_button, pressed);
+        } else if c64_button != C64MouseButton::Left {
+            return;
+        } else if let Some(port) = self.port_with_controller(ControllerType::Paddles) {
+            self.machine_controller
+                .mut_machine()
+                .set_paddle_button_state(port, Pot::X, pressed);
+        } else {
+            self.mouse_button_pressed = pressed;
+            self.update_light_pen();
+        }
+    }
+
+    /// Points the light pen at the mouse cursor position.
+    fn update_light_pen(&mut self) {
+        let position = match self.mouse_position {
+            Some([x, y]) if self.mouse_button_pressed && x >= 0.0 && y >= 0.0 => {
+                Some((x as usize, y as usize))
+            }
+            _ => None,
+        };
+        self.machine_controller
+            .mut_machine()
+            .set_light_pen_position(position);
+    }
 }
 
 impl<'a, A: DebugAdapter> AppController for C64Controller<'a, A> {
@@ -46,7 +238,41 @@ impl<'a, A: DebugAdapter> AppController for C64Controller<'a, A> {
         self.machine_controller.interrupted()
     }
 
+    fn paused(&self) -> bool {
+        self.machine_controller.paused()
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.machine_controller.set_paused(paused);
+    }
+
+    fn frame_count(&self) -> u64 {
+        self.machine_controller.frame_count()
+    }
+
+    fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.machine_controller.load_program(path)
+    }
+
     fn event(&mut self, event: &Event) {
+        for gamepad_event in self.gamepad_mapping.translate(event) {
+            let port = match gamepad_event.gamepad {
+                0 => self.joystick_port,
+                1 => self.joystick_port.other(),
+                _ => continue,
+            };
+            let input = match gamepad_event.input {
+                GamepadInput::Up => JoystickInput::Up,
+                GamepadInput::Down => JoystickInput::Down,
+                GamepadInput::Left => JoystickInput::Left,
+                GamepadInput::Right => JoystickInput::Right,
+                GamepadInput::Fire => JoystickInput::Fire,
+            };
+            self.machine_controller
+                .mut_machine()
+                .set_joystick_input_state(port, input, gamepad_event.pressed);
+        }
+
         match event {
             Event::Input(
                 Input::Button(ButtonArgs {
@@ -64,20 +290,38 @@ impl<'a, A: DebugAdapter> AppController for C64Controller<'a, A> {
                     self.machine_controller.mut_machine().datasette().map(|d| {
                         d.set_play_pressed(true);
                     });
-                } else if let Some(c64_key) = map_key(*key) {
-                    let c64_key_state = match state {
-                        ButtonState::Press => KeyState::Pressed,
-                        ButtonState::Release => KeyState::Released,
-                    };
+                } else if let Some(input) = self.keymap.joystick_input(*key) {
+                    let port = self.joystick_port;
                     self.machine_controller
                         .mut_machine()
-                        .set_key_state(c64_key, c64_key_state);
+                        .set_joystick_input_state(port, input, state == &ButtonState::Press);
                 } else if key == &Key::LGui {
                     self.l_gui_key_pressed = state == &ButtonState::Press;
                 } else if key == &Key::RGui {
                     self.r_gui_key_pressed = state == &ButtonState::Press;
+                } else {
+                    self.handle_key(*key, *state);
                 }
             }
+            Event::Input(
+                Input::Button(ButtonArgs {
+                    button: Button::Mouse(button),
+                    state,
+                    ..
+                }),
+                _timestamp,
+            ) => self.handle_mouse_button(*button, state == &ButtonState::Press),
+            Event::Input(Input::Move(Motion::MouseRelative([dx, dy])), _timestamp) => {
+                self.handle_mouse_movement(*dx, *dy);
+            }
+            Event::Input(Input::Move(Motion::MouseCursor(position)), _timestamp) => {
+                self.mouse_position = Some(*position);
+                self.update_light_pen();
+            }
+            Event::Input(Input::Cursor(false), _timestamp) => {
+                self.mouse_position = None;
+                self.update_light_pen();
+            }
             Event::Loop(Loop::Update(_)) => self.machine_controller.run_until_end_of_frame(),
             _ => {}
         }
@@ -86,83 +330,9 @@ impl<'a, A: DebugAdapter> AppController for C64Controller<'a, A> {
     fn display_machine_state(&self) -> String {
         self.machine_controller.display_state()
     }
-}
 
-fn map_key(key: Key) -> Option<C64Key> {
-    match key {
-        Key::Backquote => Some(C64Key::LeftArrow),
-        Key::D1 => Some(C64Key::D1),
-        Key::D2 => Some(C64Key::D2),
-        Key::D3 => Some(C64Key::D3),
-        Key::D4 => Some(C64Key::D4),
-        Key::D5 => Some(C64Key::D5),
-        Key::D6 => Some(C64Key::D6),
-        Key::D7 => Some(C64Key::D7),
-        Key::D8 => Some(C64Key::D8),
-        Key::D9 => Some(C64Key::D9),
-        Key::D0 => Some(C64Key::D0),
-        Key::Minus => Some(C64Key::Plus),
-        Key::Equals => Some(C64Key::Minus),
-        // Key::Pound => Some(C64Key::Pound),
-        Key::Home => Some(C64Key::ClrHome),
-        Key::Backspace => Some(C64Key::InstDel),
-
-        Key::Tab => Some(C64Key::Ctrl),
-        Key::Q => Some(C64Key::Q),
-        Key::W => Some(C64Key::W),
-        Key::E => Some(C64Key::E),
-        Key::R => Some(C64Key::R),
-        Key::T => Some(C64Key::T),
-        Key::Y => Some(C64Key::Y),
-        Key::U => Some(C64Key::U),
-        Key::I => Some(C64Key::I),
-        Key::O => Some(C64Key::O),
-        Key::P => Some(C64Key::P),
-        Key::LeftBracket => Some(C64Key::At),
-        Key::RightBracket => Some(C64Key::Asterisk),
-        // Key::UpArrow => Some(C64Key::UpArrow),
-        Key::F12 => Some(C64Key::Restore),
-
-        Key::Escape => Some(C64Key::RunStop),
-        // Key::ShiftLock => Some(C64Key::ShiftLock),
-        Key::A => Some(C64Key::A),
-        Key::S => Some(C64Key::S),
-        Key::D => Some(C64Key::D),
-        Key::F => Some(C64Key::F),
-        Key::G => Some(C64Key::G),
-        Key::H => Some(C64Key::H),
-        Key::J => Some(C64Key::J),
-        Key::K => Some(C64Key::K),
-        Key::L => Some(C64Key::L),
-        Key::Semicolon => Some(C64Key::Colon),
-        Key::Quote => Some(C64Key::Semicolon),
-        Key::Backslash => Some(C64Key::Equals),
-        Key::Return => Some(C64Key::Return),
-
-        Key::LCtrl => Some(C64Key::Commodore),
-        Key::LShift => Some(C64Key::LShift),
-        Key::Z => Some(C64Key::Z),
-        Key::X => Some(C64Key::X),
-        Key::C => Some(C64Key::C),
-        Key::V => Some(C64Key::V),
-        Key::B => Some(C64Key::B),
-        Key::N => Some(C64Key::N),
-        Key::M => Some(C64Key::M),
-        Key::Comma => Some(C64Key::Comma),
-        Key::Period => Some(C64Key::Period),
-        Key::Slash => Some(C64Key::Slash),
-        Key::RShift => Some(C64Key::RShift),
-        Key::Down => Some(C64Key::CrsrUpDown),
-        Key::Right => Some(C64Key::CrsrLeftRight),
-
-        Key::Space => Some(C64Key::Space),
-
-        Key::F1 => Some(C64Key::F1),
-        Key::F3 => Some(C64Key::F3),
-        Key::F5 => Some(C64Key::F5),
-        Key::F7 => Some(C64Key::F7),
-
-        _ => None,
+    fn machine_status(&self) -> String {
+        self.machine_controller.machine_status()
     }
 }
 
@@ -170,12 +340,19 @@ fn map_key(key: Key) -> Option<C64Key> {
 mod tests {
     use super::*;
     use crate::test_utils::assert_current_frame;
+    use common::app::Machine;
     use common::debugger::adapter::TcpDebugAdapter;
     use piston::UpdateArgs;
+    use ya6502::cpu::CompositeMachine;
+    use ya6502::cpu::MachineInspector;
+    use ya6502::memory::Write;
 
     use crate::test_utils::c64_with_cartridge;
     use piston::Button;
     use piston::ButtonState;
+    use piston::ControllerButton;
+    use piston::ControllerHat;
+    use piston::HatState;
 
     fn send_key<A>(controller: &mut C64Controller<A>, key: Key, state: ButtonState)
     where
@@ -191,7 +368,11 @@ mod tests {
     #[test]
     fn keyboard() {
         let mut c64 = c64_with_cartridge("keyboard.bin");
-        let mut controller = C64Controller::new(&mut c64, None::<TcpDebugAdapter>);
+        let mut controller = C64Controller::new(
+            &mut c64,
+            None::<Debugger<TcpDebugAdapter>>,
+            JoystickPort::Port2,
+        );
         controller.reset();
         controller.event(&Event::from(UpdateArgs { dt: 1.0 / 60.0 }));
         controller.event(&Event::from(UpdateArgs { dt: 1.0 / 60.0 }));
@@ -202,4 +383,57 @@ mod tests {
         controller.event(&Event::from(UpdateArgs { dt: 1.0 / 60.0 }));
         assert_current_frame(&mut controller, "app_keyboard_1.png", "app_keyboard_2");
     }
+
+    #[test]
+    fn gamepads() {
+        let mut c64 = c64_with_cartridge("hello_world.bin");
+        let mut controller = C64Controller::new(
+            &mut c64,
+            None::<Debugger<TcpDebugAdapter>>,
+            JoystickPort::Port1,
+        );
+        controller.event(&Event::from(ButtonArgs {
+            button: Button::Hat(ControllerHat::new(0, 0, HatState::Right)),
+            state: ButtonState::Press,
+            scancode: None,
+        }));
+        controller.event(&Event::from(ButtonArgs {
+            button: Button::Controller(ControllerButton::new(1, 0)),
+            state: ButtonState::Press,
+            scancode: None,
+        }));
+        let c64 = controller.machine_controller.mut_machine();
+        c64.tick().unwrap();
+        // The first gamepad controls port 1 (CIA1 port B), and the second one
+        // controls port 2 (CIA1 port A).
+        assert_eq!(c64.inspect_memory(0xDC00), 0b1110_1111);
+        assert_eq!(c64.inspect_memory(0xDC01), 0b1111_0111);
+    }
+
+    #[test]
+    fn host_mouse_controls_1351_mouse() {
+        let mut c64 = c64_with_cartridge("hello_world.bin");
+        c64.connect_controller(JoystickPort::Port1, ControllerType::Mouse);
+        let cia1 = c64.mut_cpu().mut_memory().mut_cia1();
+        cia1.write(0xDC02, 0b1100_0000).unwrap();
+        cia1.write(0xDC00, 0b0100_0000).unwrap();
+        let mut controller = C64Controller::new(
+            &mut c64,
+            None::<Debugger<TcpDebugAdapter>>,
+            JoystickPort::Port2,
+        );
+        controller.event(&Event::from(Motion::MouseRelative([5.0, 2.0])));
+        controller.event(&Event::from(ButtonArgs {
+            button: Button::Mouse(MouseButton::Right),
+            state: ButtonState::Press,
+            scancode: None,
+        }));
+        let c64 = controller.machine_controller.mut_machine();
+        for _ in 0..8 {
+            c64.tick().unwrap();
+        }
+        assert_eq!(c64.inspect_memory(0xD419), 0x0A);
+        assert_eq!(c64.inspect_memory(0xD41A), 0x7C);
+        assert_eq!(c64.inspect_memory(0xDC01), 0b1111_1110);
+    }
 }
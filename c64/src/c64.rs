@@ -1,41 +1,111 @@
 use crate::address_space::AddressSpace;
-use crate::address_space::Cartridge;
 use crate::address_space::VicAddressSpace;
+use crate::cartridge::cartridge_from_image;
+use crate::cartridge::ExpansionPortMapper;
+use crate::cartridge::CARTRIDGE_FILE_EXTENSIONS;
+use crate::cia;
 use crate::cia::Cia;
 use crate::cia::PortName;
+use crate::controllers::ControllerType;
+use crate::controllers::Mouse;
+use crate::controllers::MouseButton;
+use crate::controllers::Paddles;
+use crate::controllers::Pot;
+use crate::drive::Drive;
 use crate::frame_renderer::FrameRenderer;
+use crate::iec::IecInterface;
+use crate::iec::IecLines;
+use crate::joystick::Joystick;
+use crate::joystick::JoystickInput;
+use crate::joystick::JoystickPort;
 use crate::keyboard::Key;
 use crate::keyboard::KeyState;
 use crate::keyboard::Keyboard;
+use crate::roms::RomKind;
+use crate::roms::RomSet;
+use crate::sid;
 use crate::sid::Sid;
+use crate::tape::read_tap_file;
 use crate::tape::Datasette;
-use crate::Vic;
+use crate::vic;
+use crate::vic::Vic;
 use common::app::FrameStatus;
 use common::app::Machine;
-use delegate::delegate;
+use common::app::RasterPosition;
+use common::archive::read_image_file;
+use common::cheats::CheatMemory;
+use common::cheats::CheatTable;
+use common::debugger::symbols::RegisterNames;
+use common::scheduler::CpuScheduler;
+use enum_map::{enum_map, Enum, EnumMap};
 use image::RgbaImage;
 use std::cell::RefCell;
 use std::error::Error;
-use std::fs;
 use std::path::Path;
 use std::rc::Rc;
+use ya6502::cpu::ChipState;
+use ya6502::cpu::CompositeMachine;
 use ya6502::cpu::Cpu;
-use ya6502::cpu::MachineInspector;
+use ya6502::cpu::CpuError;
+use ya6502::cpu::CpuVariant;
+use ya6502::cpu::PowerOnState;
 use ya6502::memory::Ram;
+use ya6502::memory::RamPattern;
+use ya6502::memory::ReadError;
 use ya6502::memory::Rom;
 
+/// Number of CPU cycles per a single period of the 50 Hz power line signal that
+/// drives the CIA time-of-day clocks (PAL).
+const TOD_CLOCK_DIVIDER: u32 = 985_248 / 50;
+
+/// Returns the address at which the name of an I/O register visible at a given
+/// address is stored. The VIC, SID and CIA chips only decode a few address
+/// lines, so their registers are repeated throughout their I/O areas.
+fn register_address(address: u16) -> u16 {
+    return match address {
+        0xD000..=0xD3FF => 0xD000 | (address & 0x3F),
+        0xD400..=0xD7FF => 0xD400 | (address & 0x1F),
+        0xDC00..=0xDCFF => 0xDC00 | (address & 0x0F),
+        0xDD00..=0xDDFF => 0xDD00 | (address & 0x0F),
+        _ => address,
+    };
+}
+
+/// Chips and peripherals that can request interrupts. Each of them pulls one of
+/// the CPU interrupt lines low through an open-collector output, so a line
+/// stays asserted as long as at least one of its sources keeps it asserted.
+/// VIC and CIA1 share the IRQ line, while CIA2 and the RESTORE key share the
+/// NMI line.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+enum InterruptSource {
+    Vic,
+    Cia1,
+    Cia2,
+    Restore,
+}
+
 pub type C64AddressSpace = AddressSpace<Vic<VicAddressSpace<Ram, Rom>, Ram>, Sid, Cia>;
 
+/// The Commodore 64 computer. Use [`C64Builder`] to create one, and the
+/// [`Machine`] interface to run it and retrieve the frames.
 pub struct C64 {
-    cpu: Cpu<C64AddressSpace>,
+    cpu: Cpu<CheatMemory<C64AddressSpace>>,
     frame_renderer: FrameRenderer,
 
-    cpu_clock_divider: u32,
-    cia1_irq: bool,
-    cia2_irq: bool,
+    /// Divides the VIC dot clock to get the CPU clock. Its RDY line follows
+    /// the VIC's BA line: if it's `false`, VIC is about to occupy the bus, and
+    /// the CPU is stopped at its next read cycle.
+    cpu_scheduler: CpuScheduler,
+    tod_clock_divider: u32,
+    interrupt_sources: EnumMap<InterruptSource, bool>,
 
     keyboard: Keyboard,
+    controllers: EnumMap<JoystickPort, ControllerType>,
+    joysticks: EnumMap<JoystickPort, Joystick>,
+    paddles: EnumMap<JoystickPort, Paddles>,
+    mice: EnumMap<JoystickPort, Mouse>,
     datasette: Option<Datasette>,
+    disk_drive: Option<IecInterface<Drive>>,
 }
 
 impl Machine for C64 {
@@ -45,22 +115,38 @@ impl Machine for C64 {
         mem.mut_cia1().write_port(PortName::B, 0b1111_1111);
         mem.mut_cia2().write_port(PortName::A, 0b1111_1111);
         mem.mut_cia2().write_port(PortName::B, 0b1111_1111);
+        if let Some(cartridge) = &mut mem.cartridge {
+            cartridge.reset();
+        }
         self.cpu.reset();
     }
 
-    fn tick(&mut self) -> Result<FrameStatus, Box<dyn Error>> {
+    fn tick(&mut self) -> Result<FrameStatus, CpuError> {
         let vic_result = self.cpu.mut_memory().mut_vic().tick()?;
-        let cia1 = self.cpu.mut_memory().mut_cia1();
-        let keyboard_scan_result = self.keyboard.scan(cia1.read_port(PortName::A));
-        cia1.write_port(PortName::B, keyboard_scan_result);
-        if self.at_cpu_cycle() {
-            self.cpu.tick()?;
-            self.cia1_irq = self.cpu.mut_memory().mut_cia1().tick();
-            self.cia2_irq = self.cpu.mut_memory().mut_cia2().tick();
+        self.update_keyboard();
+        // The CPU only stops on a read cycle, so it can still finish up to 3
+        // write cycles after BA goes low, until VIC takes over the bus.
+        self.cpu_scheduler
+            .set_ready(vic_result.ba || (vic_result.aec && self.cpu.next_cycle_writes()));
+        let scheduled_tick = self.cpu_scheduler.tick();
+        if scheduled_tick.chip_cycle {
+            // Note that the other chips keep running while VIC stops the CPU
+            // on bad lines.
+            if scheduled_tick.cpu_cycle {
+                self.cpu.tick()?;
+            }
+            self.interrupt_sources[InterruptSource::Cia1] = self.cpu.mut_memory().mut_cia1().tick();
+            self.interrupt_sources[InterruptSource::Cia2] = self.cpu.mut_memory().mut_cia2().tick();
+            self.tod_clock_divider = (self.tod_clock_divider + 1) % TOD_CLOCK_DIVIDER;
+            if self.tod_clock_divider == 0 {
+                self.cpu.mut_memory().mut_cia1().tick_tod();
+                self.cpu.mut_memory().mut_cia2().tick_tod();
+            }
             if let Some(datasette) = self.datasette.as_mut() {
                 let port_value = self.cpu.mut_memory().mut_cpu_port().read();
                 let motor_on = port_value & flags::CPU_PORT_CASS_MOTOR == 0;
-                let ds_tick_result = datasette.tick(motor_on);
+                let write_line = port_value & flags::CPU_PORT_CASS_WRITE != 0;
+                let ds_tick_result = datasette.tick(motor_on, write_line);
                 if ds_tick_result.pulse {
                     use std::io::Write;
                     print!(".");
@@ -73,10 +159,17 @@ impl Machine for C64 {
                     self.cpu.mut_memory().mut_cpu_port().pins |= flags::CPU_PORT_CASS_SENSE
                 };
             }
+            self.update_serial_bus();
+            self.update_vic_bank();
+            self.update_pots();
         }
-        self.cpu
-            .set_irq_pin(vic_result.irq | self.cia1_irq | self.cia2_irq);
-        self.cpu_clock_divider = (self.cpu_clock_divider + 1) % 8;
+        self.interrupt_sources[InterruptSource::Vic] = vic_result.irq;
+        // IRQ is level-sensitive, so the CPU keeps getting interrupted until
+        // all of the sources get acknowledged. NMI is edge-sensitive: the CPU
+        // only reacts when the line becomes asserted, so if one source keeps
+        // it asserted, the other one can't trigger another NMI.
+        self.cpu.set_irq_pin(self.irq_line());
+        self.cpu.set_nmi_pin(self.nmi_line());
         return if self.frame_renderer.consume(vic_result.video_output) {
             Ok(FrameStatus::Complete)
         } else {
@@ -91,99 +184,474 @@ impl Machine for C64 {
     fn display_state(&self) -> String {
         format!("{}\n{}", self.cpu(), self.cpu().memory())
     }
-}
 
-impl MachineInspector for C64 {
-    delegate! {
-        to self.cpu {
-            fn reg_pc(&self) -> u16;
-            fn reg_a(&self) -> u8;
-            fn reg_x(&self) -> u8;
-            fn reg_y(&self) -> u8;
-            fn reg_sp(&self) -> u8;
-            fn flags(&self) -> u8;
-            fn inspect_memory(&self, address: u16) -> u8;
+    fn raster_position(&self) -> RasterPosition {
+        let (line, x) = self.cpu().memory().vic().raster_position();
+        RasterPosition {
+            line: line as u32,
+            x: x as u32,
         }
     }
 
-    fn at_instruction_start(&self) -> bool {
-        self.at_cpu_cycle() && self.cpu.at_instruction_start()
+    fn register_names(&self) -> RegisterNames {
+        vic::register_names()
+            .merge(sid::register_names(0xD400))
+            .merge(cia::register_names(0xDC00))
+            .merge(cia::register_names(0xDD00))
+            .with_mirroring(register_address)
+    }
+
+    /// Plugs in a cartridge loaded from a given file, replacing the current
+    /// one, or inserts a tape if it's a TAP file.
+    fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let extensions = [CARTRIDGE_FILE_EXTENSIONS, &["tap"]].concat();
+        let image = read_image_file(path, &extensions)?;
+        if image.has_extension("tap") {
+            let tape = read_tap_file(&image.bytes[..])?;
+            self.set_datasette(Some(Datasette::new(tape)));
+            // Programs are loaded from tapes using BASIC, which wouldn't start
+            // with a cartridge plugged in.
+            self.set_cartridge(None);
+        } else {
+            self.set_cartridge(Some(Box::new(cartridge_from_image(&image)?)));
+        }
+        Ok(())
+    }
+    fn mut_cheats(&mut self) -> Option<&mut CheatTable> {
+        Some(self.cpu.mut_memory().mut_cheats())
+    }
+}
+
+impl CompositeMachine for C64 {
+    type Memory = CheatMemory<C64AddressSpace>;
+
+    fn cpu(&self) -> &Cpu<Self::Memory> {
+        &self.cpu
+    }
+
+    fn mut_cpu(&mut self) -> &mut Cpu<Self::Memory> {
+        &mut self.cpu
+    }
+
+    fn at_cpu_cycle(&self) -> bool {
+        self.cpu_scheduler.phase() == 0 && !self.cpu_scheduler.stalled()
+    }
+
+    fn chip_states(&self) -> Result<Vec<ChipState>, ReadError> {
+        let memory = self.cpu.memory();
+        Ok(vec![
+            memory.vic().chip_state()?,
+            memory.cia1().chip_state("CIA1")?,
+            memory.cia2().chip_state("CIA2")?,
+            memory.sid().chip_state()?,
+        ])
     }
 }
 
 impl C64 {
-    pub fn new() -> Result<Self, Box<dyn Error>> {
-        let basic_rom = fs::read(Path::new(env!("OUT_DIR")).join("roms").join("basic.bin"))?;
-        let char_rom = fs::read(Path::new(env!("OUT_DIR")).join("roms").join("char.bin"))?;
-        let kernal_rom = fs::read(Path::new(env!("OUT_DIR")).join("roms").join("kernal.bin"))?;
-        let ram = Rc::new(RefCell::new(Ram::new(16)));
+    /// Creates a bare computer with the built-in system ROMs. Fails if they
+    /// can't be loaded.
+    pub fn new(
+        power_on_state: PowerOnState,
+        ram_pattern: RamPattern,
+    ) -> Result<Self, Box<dyn Error>> {
+        return Self::with_roms(power_on_state, ram_pattern, &RomSet::builtin()?);
+    }
+
+    /// Creates a bare computer with a given set of system ROMs.
+    pub fn with_roms(
+        power_on_state: PowerOnState,
+        ram_pattern: RamPattern,
+        roms: &RomSet,
+    ) -> Result<Self, Box<dyn Error>> {
+        let basic_rom = roms.image(RomKind::Basic);
+        let char_rom = roms.image(RomKind::Chargen);
+        let kernal_rom = roms.image(RomKind::Kernal);
+        let ram = Rc::new(RefCell::new(Ram::with_pattern(ram_pattern, 16)));
         let color_ram = Rc::new(RefCell::new(Ram::new(10)));
+        let char_rom = Rc::new(RefCell::new(Rom::new(char_rom)?));
         Ok(C64 {
-            cpu: Cpu::new(Box::new(C64AddressSpace::new(
-                ram.clone(),
-                Rom::new(&basic_rom)?,
-                Vic::new(
-                    Box::new(VicAddressSpace::new(
-                        ram,
-                        Rc::new(RefCell::new(Rom::new(&char_rom)?)),
-                    )),
-                    color_ram.clone(),
-                ),
-                Sid::new(),
-                color_ram,
-                Cia::new(),
-                Cia::new(),
-                Rom::new(&kernal_rom)?,
-            ))),
+            cpu: Cpu::with_power_on_state(
+                Box::new(CheatMemory::new(
+                    C64AddressSpace::new(
+                        ram.clone(),
+                        Rom::new(basic_rom)?,
+                        char_rom.clone(),
+                        Vic::new(
+                            Box::new(VicAddressSpace::new(ram, char_rom)),
+                            color_ram.clone(),
+                        ),
+                        Sid::new(),
+                        color_ram,
+                        Cia::new(),
+                        Cia::new(),
+                        Rom::new(kernal_rom)?,
+                    ),
+                    CheatTable::new(),
+                )),
+                CpuVariant::Nmos6502,
+                power_on_state,
+            ),
             frame_renderer: FrameRenderer::default(),
 
-            cpu_clock_divider: 0,
-            cia1_irq: false,
-            cia2_irq: false,
+            cpu_scheduler: CpuScheduler::new(8),
+            tod_clock_divider: 0,
+            interrupt_sources: enum_map! { _ => false },
 
             keyboard: Keyboard::new(),
+            controllers: enum_map! { _ => ControllerType::Joystick },
+            joysticks: enum_map! { _ => Joystick::new() },
+            paddles: enum_map! { _ => Paddles::new() },
+            mice: enum_map! { _ => Mouse::new() },
             datasette: None,
+            disk_drive: None,
         })
     }
 
-    fn at_cpu_cycle(&self) -> bool {
-        self.cpu_clock_divider == 0
-    }
-
-    pub fn set_cartridge(&mut self, cartridge: Option<Cartridge>) {
+    /// Plugs a cartridge into the expansion port, or removes it if `None`.
+    pub fn set_cartridge(&mut self, cartridge: Option<Box<dyn ExpansionPortMapper>>) {
         self.cpu.mut_memory().cartridge = cartridge;
     }
 
+    /// Presses or releases a key on the C64 keyboard.
     pub fn set_key_state(&mut self, key: Key, state: KeyState) {
         self.keyboard.set_key_state(key, state);
     }
 
-    pub fn cpu(&self) -> &Cpu<C64AddressSpace> {
-        &self.cpu
+    /// Returns the type of the controller connected to a given port.
+    pub fn controller(&self, port: JoystickPort) -> ControllerType {
+        self.controllers[port]
+    }
+
+    /// Connects a controller of a given type to a given port, replacing the
+    /// previous one. Paddles are centered when connected.
+    pub fn connect_controller(&mut self, port: JoystickPort, controller: ControllerType) {
+        self.controllers[port] = controller;
+        self.joysticks[port] = Joystick::new();
+        self.paddles[port] = Paddles::new();
+        self.mice[port] = Mouse::new();
+        self.update_control_ports();
+    }
+
+    /// Presses (`pressed == true`) or releases a joystick button or direction.
+    /// Ignored if there's no joystick connected to a given port.
+    pub fn set_joystick_input_state(
+        &mut self,
+        port: JoystickPort,
+        input: JoystickInput,
+        pressed: bool,
+    ) {
+        if self.controllers[port] == ControllerType::Joystick {
+            self.joysticks[port].set_state(input, pressed);
+            self.update_control_ports();
+        }
+    }
+
+    /// Returns the position of a given paddle, or `None` if there are no
+    /// paddles connected to a given port.
+    pub fn paddle_position(&self, port: JoystickPort, pot: Pot) -> Option<u8> {
+        (self.controllers[port] == ControllerType::Paddles)
+            .then(|| self.paddles[port].position(pot))
     }
 
+    /// Sets the position of a given paddle. 0 means turned all the way
+    /// clockwise, 255 means all the way counterclockwise. Ignored if there are
+    /// no paddles connected to a given port.
+    pub fn set_paddle_position(&mut self, port: JoystickPort, pot: Pot, position: u8) {
+        if self.controllers[port] == ControllerType::Paddles {
+            self.paddles[port].set_position(pot, position);
+        }
+    }
+
+    /// Presses or releases the button of a given paddle. Ignored if there are
+    /// no paddles connected to a given port.
+    pub fn set_paddle_button_state(&mut self, port: JoystickPort, pot: Pot, pressed: bool) {
+        if self.controllers[port] == ControllerType::Paddles {
+            self.paddles[port].set_button_state(pot, pressed);
+            self.update_control_ports();
+        }
+    }
+
+    /// Moves a 1351 mouse connected to a given port. Positive `dx` moves it to
+    /// the right, and positive `dy` moves it up. Ignored if there's no mouse
+    /// connected to a given port.
+    pub fn move_mouse(&mut self, port: JoystickPort, dx: i32, dy: i32) {
+        if self.controllers[port] == ControllerType::Mouse {
+            self.mice[port].move_by(dx, dy);
+        }
+    }
+
+    /// Presses or releases a mouse button. Ignored if there's no mouse
+    /// connected to a given port.
+    pub fn set_mouse_button_state(
+        &mut self,
+        port: JoystickPort,
+        button: MouseButton,
+        pressed: bool,
+    ) {
+        if self.controllers[port] == ControllerType::Mouse {
+            self.mice[port].set_button_state(button, pressed);
+            self.update_control_ports();
+        }
+    }
+
+    /// Returns a mask of CIA port lines pulled down by the controller connected
+    /// to a given port.
+    fn controller_pressed_lines(&self, port: JoystickPort) -> u8 {
+        match self.controllers[port] {
+            ControllerType::Joystick => self.joysticks[port].pressed_lines(),
+            ControllerType::Paddles => self.paddles[port].pressed_lines(),
+            ControllerType::Mouse => self.mice[port].pressed_lines(),
+        }
+    }
+
+    fn update_control_ports(&mut self) {
+        let port_a_lines = self.controller_pressed_lines(JoystickPort::Port2);
+        let port_b_lines = self.controller_pressed_lines(JoystickPort::Port1);
+        let cia1 = self.cpu.mut_memory().mut_cia1();
+        cia1.pull_down_lines(PortName::A, port_a_lines);
+        cia1.pull_down_lines(PortName::B, port_b_lines);
+    }
+
+    /// Connects the analog inputs of one of the control ports to the SID. The
+    /// port is selected by CIA1 port A: setting bit 6 selects port 1, and
+    /// setting bit 7 selects port 2.
+    fn update_pots(&mut self) {
+        let selection = self.cpu.memory().cia1().port_output(PortName::A);
+        let port = if selection & flags::CIA1_PORT_A_SELECT_PORT_1 != 0 {
+            Some(JoystickPort::Port1)
+        } else if selection & flags::CIA1_PORT_A_SELECT_PORT_2 != 0 {
+            Some(JoystickPort::Port2)
+        } else {
+            None
+        };
+        let pot_values = match port.map(|port| (port, self.controllers[port])) {
+            Some((port, ControllerType::Paddles)) => self.paddles[port].pot_values(),
+            Some((port, ControllerType::Mouse)) => self.mice[port].pot_values(),
+            // Nothing is connected to the analog inputs.
+            _ => enum_map! { _ => 0xFF },
+        };
+        let sid = self.cpu.mut_memory().mut_sid();
+        for (pot, value) in pot_values {
+            sid.set_pot_value(pot, value);
+        }
+    }
+
+    /// Points the light pen at a given pixel of the frame image, or takes it
+    /// away from the screen if `position` is `None`.
+    pub fn set_light_pen_position(&mut self, position: Option<(usize, usize)>) {
+        let raw_position = position.and_then(|(x, y)| self.frame_renderer.raw_position(x, y));
+        self.cpu
+            .mut_memory()
+            .mut_vic()
+            .set_light_pen_position(raw_position);
+    }
+
+    /// Connects a datasette to the cassette port, or disconnects it if `None`.
     pub fn set_datasette(&mut self, datasette: Option<Datasette>) {
         self.datasette = datasette;
     }
 
+    /// Returns the connected datasette, if any.
     pub fn datasette(&mut self) -> Option<&mut Datasette> {
         self.datasette.as_mut()
     }
+
+    /// Connects a disk drive to the serial port, or disconnects it if `None`.
+    pub fn set_disk_drive(&mut self, disk_drive: Option<IecInterface<Drive>>) {
+        self.disk_drive = disk_drive;
+    }
+
+    /// Connects the keyboard matrix to CIA1 ports. Since the joysticks share
+    /// the port lines with the keyboard, pressing a joystick button has the
+    /// same effect as pressing some of the keys, and vice versa. The RESTORE
+    /// key is connected to the NMI line.
+    fn update_keyboard(&mut self) {
+        let cia1 = self.cpu.mut_memory().mut_cia1();
+        let columns = cia1.port_output(PortName::A);
+        let rows = cia1.port_output(PortName::B);
+        cia1.write_port(PortName::A, self.keyboard.scan_rows(rows));
+        cia1.write_port(PortName::B, self.keyboard.scan(columns));
+        self.interrupt_sources[InterruptSource::Restore] = self.keyboard.restore_pressed();
+    }
+
+    /// Returns `true` if the IRQ line is asserted by VIC or CIA1.
+    fn irq_line(&self) -> bool {
+        self.interrupt_sources[InterruptSource::Vic]
+            || self.interrupt_sources[InterruptSource::Cia1]
+    }
+
+    /// Returns `true` if the NMI line is asserted by CIA2 or the RESTORE key.
+    fn nmi_line(&self) -> bool {
+        self.interrupt_sources[InterruptSource::Cia2]
+            || self.interrupt_sources[InterruptSource::Restore]
+    }
+
+    /// Selects the VIC memory bank, which is controlled by CIA 2 port A.
+    fn update_vic_bank(&mut self) {
+        let mem = self.cpu.mut_memory();
+        let port_value = mem.mut_cia2().port_output(PortName::A);
+        mem.mut_vic()
+            .mut_graphics_memory()
+            .set_bank_from_cia_port(port_value);
+    }
+
+    /// Exchanges the IEC serial bus signals between CIA 2 port A and the disk
+    /// drive, if one is attached. The CIA outputs drive the lines through
+    /// inverters, so setting a bit pulls the corresponding line low; the
+    /// inputs read 0 when a line is low.
+    fn update_serial_bus(&mut self) {
+        let cia2 = self.cpu.mut_memory().mut_cia2();
+        let port_value = cia2.read_port(PortName::A);
+        let computer_lines = IecLines {
+            atn: port_value & flags::CIA2_PORT_A_ATN_OUT != 0,
+            clock: port_value & flags::CIA2_PORT_A_CLOCK_OUT != 0,
+            data: port_value & flags::CIA2_PORT_A_DATA_OUT != 0,
+        };
+        let drive_lines = match self.disk_drive.as_mut() {
+            Some(drive) => drive.tick(computer_lines.combine(drive.output())),
+            None => IecLines::default(),
+        };
+        let lines = computer_lines.combine(drive_lines);
+        let mut pulled_down_lines = 0;
+        if lines.clock {
+            pulled_down_lines |= flags::CIA2_PORT_A_CLOCK_IN;
+        }
+        if lines.data {
+            pulled_down_lines |= flags::CIA2_PORT_A_DATA_IN;
+        }
+        self.cpu
+            .mut_memory()
+            .mut_cia2()
+            .pull_down_lines(PortName::A, pulled_down_lines);
+    }
+}
+
+/// Assembles a [`C64`] with optional peripherals. By default, the builder
+/// creates a bare computer with zeroed RAM and random CPU registers.
+pub struct C64Builder {
+    power_on_state: PowerOnState,
+    ram_pattern: RamPattern,
+    cartridge: Option<Box<dyn ExpansionPortMapper>>,
+    datasette: Option<Datasette>,
+    disk_drive: Option<IecInterface<Drive>>,
+    controllers: EnumMap<JoystickPort, ControllerType>,
+    roms: Option<RomSet>,
+    permissive: bool,
+}
+
+impl C64Builder {
+    pub fn new() -> Self {
+        Self {
+            power_on_state: PowerOnState::Random,
+            ram_pattern: RamPattern::default(),
+            cartridge: None,
+            datasette: None,
+            disk_drive: None,
+            controllers: enum_map! { _ => ControllerType::Joystick },
+            roms: None,
+            permissive: false,
+        }
+    }
+
+    pub fn with_power_on_state(mut self, power_on_state: PowerOnState) -> Self {
+        self.power_on_state = power_on_state;
+        self
+    }
+
+    pub fn with_ram_pattern(mut self, ram_pattern: RamPattern) -> Self {
+        self.ram_pattern = ram_pattern;
+        self
+    }
+
+    /// Plugs a cartridge into the expansion port.
+    pub fn with_cartridge(mut self, cartridge: impl ExpansionPortMapper + 'static) -> Self {
+        self.cartridge = Some(Box::new(cartridge));
+        self
+    }
+
+    pub fn with_datasette(mut self, datasette: Datasette) -> Self {
+        self.datasette = Some(datasette);
+        self
+    }
+
+    /// Connects a disk drive to the serial port.
+    pub fn with_disk_drive(mut self, disk_drive: IecInterface<Drive>) -> Self {
+        self.disk_drive = Some(disk_drive);
+        self
+    }
+
+    /// Connects a controller of a given type to a given control port. By
+    /// default, joysticks are connected to both ports.
+    pub fn with_controller(mut self, port: JoystickPort, controller: ControllerType) -> Self {
+        self.controllers[port] = controller;
+        self
+    }
+
+    /// Uses a given set of system ROMs instead of the built-in one.
+    pub fn with_roms(mut self, roms: RomSet) -> Self {
+        self.roms = Some(roms);
+        self
+    }
+
+    /// Keeps the CPU running after an unknown opcode or an unsupported
+    /// register write. See [`Cpu::set_permissive`].
+    pub fn with_permissive(mut self, permissive: bool) -> Self {
+        self.permissive = permissive;
+        self
+    }
+
+    /// Creates the computer. Fails if the system ROMs can't be loaded.
+    pub fn build(self) -> Result<C64, Box<dyn Error>> {
+        let mut c64 = match &self.roms {
+            Some(roms) => C64::with_roms(self.power_on_state, self.ram_pattern, roms)?,
+            None => C64::new(self.power_on_state, self.ram_pattern)?,
+        };
+        c64.set_cartridge(self.cartridge);
+        c64.set_datasette(self.datasette);
+        c64.set_disk_drive(self.disk_drive);
+        for (port, controller) in self.controllers {
+            c64.connect_controller(port, controller);
+        }
+        c64.cpu.set_permissive(self.permissive);
+        return Ok(c64);
+    }
+}
+
+impl Default for C64Builder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 mod flags {
     pub const CPU_PORT_CASS_MOTOR: u8 = 0b0010_0000;
     pub const CPU_PORT_CASS_SENSE: u8 = 0b0001_0000;
+    pub const CPU_PORT_CASS_WRITE: u8 = 0b0000_1000;
+    pub const CIA1_PORT_A_SELECT_PORT_1: u8 = 0b0100_0000;
+    pub const CIA1_PORT_A_SELECT_PORT_2: u8 = 0b1000_0000;
+    pub const CIA2_PORT_A_ATN_OUT: u8 = 0b0000_1000;
+    pub const CIA2_PORT_A_CLOCK_OUT: u8 = 0b0001_0000;
+    pub const CIA2_PORT_A_DATA_OUT: u8 = 0b0010_0000;
+    pub const CIA2_PORT_A_CLOCK_IN: u8 = 0b0100_0000;
+    pub const CIA2_PORT_A_DATA_IN: u8 = 0b1000_0000;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::cartridge::CartridgeMode;
     use crate::test_utils::c64_with_cartridge;
     use crate::test_utils::c64_with_cartridge_uninitialized;
     use crate::test_utils::next_frame;
+    use crate::test_utils::read_test_rom;
     use common::test_utils::read_test_image;
     use image::DynamicImage;
+    use std::path::Path;
+    use ya6502::cpu::MachineInspector;
+    use ya6502::cpu::MemoryAccess;
+    use ya6502::memory::Write;
 
     pub fn assert_images_equal(actual: DynamicImage, expected: DynamicImage, test_name: &str) {
         common::test_utils::assert_images_equal(
@@ -208,6 +676,32 @@ mod tests {
         assert_produces_frame(&mut c64, "hello_world.png", "shows_hello_world");
     }
 
+    #[test]
+    fn builds_computer_with_peripherals() {
+        let mut c64 = C64Builder::new()
+            .with_power_on_state(PowerOnState::Seeded(0))
+            .with_cartridge(
+                Cartridge::from_raw_image(
+                    CartridgeMode::Ultimax,
+                    &read_test_rom("hello_world.bin"),
+                )
+                .unwrap(),
+            )
+            .with_datasette(Datasette::for_recording())
+            .build()
+            .unwrap();
+        assert!(c64.datasette().is_some());
+
+        c64.reset();
+        next_frame(&mut c64).unwrap(); // Skip the first partial frame.
+        next_frame(&mut c64).unwrap(); // Allow 1 frame for initialization.
+        assert_produces_frame(
+            &mut c64,
+            "hello_world.png",
+            "builds_computer_with_peripherals",
+        );
+    }
+
     #[test]
     fn interrupts() {
         let mut c64 = c64_with_cartridge("interrupts.bin");
@@ -217,6 +711,43 @@ mod tests {
         assert_produces_frame(&mut c64, "interrupts_3.png", "interrupts_3");
     }
 
+    #[test]
+    fn irq_and_nmi() {
+        const IRQ_COUNT: u16 = 0xF0;
+        const NMI_COUNT: u16 = 0xF1;
+        let mut c64 = c64_with_cartridge("irq_and_nmi.bin");
+        // Allow 2 frames for initialization.
+        next_frame(&mut c64).unwrap();
+        next_frame(&mut c64).unwrap();
+        let irqs = c64.inspect_memory(IRQ_COUNT);
+        let nmis = c64.inspect_memory(NMI_COUNT);
+        assert_ne!(irqs, 0);
+        assert_ne!(nmis, 0);
+
+        // Both interrupts fire once per frame. CIA2 doesn't cause an IRQ.
+        for _ in 0..3 {
+            next_frame(&mut c64).unwrap();
+        }
+        assert_eq!(c64.inspect_memory(IRQ_COUNT), irqs + 3);
+        assert_eq!(c64.inspect_memory(NMI_COUNT), nmis + 3);
+
+        // Pressing RESTORE triggers an NMI, but while it holds the NMI line,
+        // CIA2 can't trigger another one.
+        c64.set_key_state(Key::Restore, KeyState::Pressed);
+        next_frame(&mut c64).unwrap();
+        assert_eq!(c64.inspect_memory(IRQ_COUNT), irqs + 4);
+        assert_eq!(c64.inspect_memory(NMI_COUNT), nmis + 4);
+
+        // Since the missed CIA2 interrupt never gets acknowledged, CIA2 keeps
+        // holding the NMI line after RESTORE gets released, and no more NMIs
+        // are triggered. IRQs are not affected.
+        c64.set_key_state(Key::Restore, KeyState::Released);
+        next_frame(&mut c64).unwrap();
+        next_frame(&mut c64).unwrap();
+        assert_eq!(c64.inspect_memory(IRQ_COUNT), irqs + 6);
+        assert_eq!(c64.inspect_memory(NMI_COUNT), nmis + 4);
+    }
+
     #[test]
     fn chip_timing() {
         let mut c64 = c64_with_cartridge("chip_timing.bin");
@@ -227,6 +758,34 @@ mod tests {
         assert_produces_frame(&mut c64, "chip_timing.png", "chip_timing");
     }
 
+    #[test]
+    fn register_names() {
+        let c64 = c64_with_cartridge("hello_world.bin");
+        let names = c64.register_names();
+        assert_eq!(names.write_name(0xD020), Some("EXTCOL"));
+        assert_eq!(names.read_name(0xD012), Some("RASTER"));
+        assert_eq!(names.write_name(0xD418), Some("SIGVOL"));
+        assert_eq!(names.read_name(0xDC00), Some("PRA"));
+        assert_eq!(names.write_name(0xDD0F), Some("CRB"));
+        assert_eq!(names.read_name(0xD02F), None);
+
+        // Mirrors
+        assert_eq!(names.write_name(0xD060), Some("EXTCOL"));
+        assert_eq!(names.write_name(0xD7F8), Some("SIGVOL"));
+        assert_eq!(names.read_name(0xDC10), Some("PRA"));
+        assert_eq!(names.read_name(0xDE00), None);
+    }
+
+    #[test]
+    fn register_address_mirroring() {
+        assert_eq!(register_address(0xD020), 0xD020);
+        assert_eq!(register_address(0xD3E0), 0xD020);
+        assert_eq!(register_address(0xD438), 0xD418);
+        assert_eq!(register_address(0xDCF1), 0xDC01);
+        assert_eq!(register_address(0xDD1F), 0xDD0F);
+        assert_eq!(register_address(0xDE00), 0xDE00);
+    }
+
     #[test]
     fn next_instruction_detection() {
         // Make sure that we only report it once per machine cycle.
@@ -259,4 +818,136 @@ mod tests {
         next_frame(&mut c64).unwrap();
         assert_produces_frame(&mut c64, "c64_keyboard_4.png", "c64_keyboard_4");
     }
+
+    #[test]
+    fn joysticks() {
+        let mut c64 = c64_with_cartridge("hello_world.bin");
+        assert_eq!(c64.inspect_memory(0xDC00), 0b1111_1111);
+        assert_eq!(c64.inspect_memory(0xDC01), 0b1111_1111);
+
+        c64.set_joystick_input_state(JoystickPort::Port2, JoystickInput::Up, true);
+        c64.set_joystick_input_state(JoystickPort::Port2, JoystickInput::Fire, true);
+        c64.set_joystick_input_state(JoystickPort::Port1, JoystickInput::Right, true);
+        c64.tick().unwrap();
+        assert_eq!(c64.inspect_memory(0xDC00), 0b1110_1110);
+        assert_eq!(c64.inspect_memory(0xDC01), 0b1111_0111);
+
+        c64.set_joystick_input_state(JoystickPort::Port2, JoystickInput::Up, false);
+        c64.set_joystick_input_state(JoystickPort::Port2, JoystickInput::Fire, false);
+        c64.set_joystick_input_state(JoystickPort::Port1, JoystickInput::Right, false);
+        c64.tick().unwrap();
+        assert_eq!(c64.inspect_memory(0xDC00), 0b1111_1111);
+        assert_eq!(c64.inspect_memory(0xDC01), 0b1111_1111);
+    }
+
+    #[test]
+    fn paddles_and_mouse() {
+        let mut c64 = C64Builder::new()
+            .with_cartridge(
+                Cartridge::from_raw_image(
+                    CartridgeMode::Ultimax,
+                    &read_test_rom("hello_world.bin"),
+                )
+                .unwrap(),
+            )
+            .with_controller(JoystickPort::Port1, ControllerType::Paddles)
+            .with_controller(JoystickPort::Port2, ControllerType::Mouse)
+            .build()
+            .unwrap();
+        c64.reset();
+        let select_port = |c64: &mut C64, selection: u8| {
+            let cia1 = c64.cpu.mut_memory().mut_cia1();
+            cia1.write(0xDC02, 0b1100_0000).unwrap();
+            cia1.write(0xDC00, selection).unwrap();
+            for _ in 0..8 {
+                c64.tick().unwrap();
+            }
+        };
+
+        c64.set_paddle_position(JoystickPort::Port1, Pot::X, 0x12);
+        c64.set_paddle_button_state(JoystickPort::Port1, Pot::Y, true);
+        c64.move_mouse(JoystickPort::Port2, 3, -1);
+        c64.set_mouse_button_state(JoystickPort::Port2, MouseButton::Left, true);
+        // Moving the mouse with a wrong port does nothing.
+        c64.move_mouse(JoystickPort::Port1, 10, 10);
+
+        select_port(&mut c64, 0b0100_0000);
+        assert_eq!(c64.inspect_memory(0xD419), 0x12);
+        assert_eq!(c64.inspect_memory(0xD41A), 0x80);
+        assert_eq!(c64.inspect_memory(0xDC01), 0b1111_0111);
+
+        select_port(&mut c64, 0b1000_0000);
+        assert_eq!(c64.inspect_memory(0xD419), 0x06);
+        assert_eq!(c64.inspect_memory(0xD41A), 0x7E);
+        assert_eq!(c64.inspect_memory(0xDC00) & 0b0001_1111, 0b0000_1111);
+
+        // With no port selected, the analog inputs are disconnected.
+        select_port(&mut c64, 0b0000_0000);
+        assert_eq!(c64.inspect_memory(0xD419), 0xFF);
+        assert_eq!(c64.inspect_memory(0xD41A), 0xFF);
+
+        // Swapping controllers releases the buttons.
+        c64.connect_controller(JoystickPort::Port2, ControllerType::Joystick);
+        assert_eq!(c64.paddle_position(JoystickPort::Port1, Pot::X), Some(0x12));
+        assert_eq!(c64.paddle_position(JoystickPort::Port2, Pot::X), None);
+        assert_eq!(c64.inspect_memory(0xDC00) & 0b0001_1111, 0b0001_1111);
+    }
+
+    #[test]
+    fn finishes_writes_when_bus_is_requested() {
+        #[rustfmt::skip]
+        let program = [
+            0x78,                   // $E000: SEI
+            0xA9, 0x1B,             // $E001: LDA #$1B
+            0x8D, 0x11, 0xD0,       // $E003: STA $D011
+            0xA2, 0xFF,             // $E006: LDX #$FF
+            0x9A,                   // $E008: TXS
+            0x20, 0x0F, 0xE0,       // $E009: JSR $E00F
+            0x4C, 0x09, 0xE0,       // $E00C: JMP $E009
+            0x60,                   // $E00F: RTS
+        ];
+        let mut image = vec![0; 0x2000];
+        image[..program.len()].copy_from_slice(&program);
+        image[0x1FFC..].copy_from_slice(&[0x00, 0xE0, 0x00, 0x00]);
+        let mut c64 = C64::new(PowerOnState::Seeded(0), RamPattern::default()).unwrap();
+        c64.set_cartridge(Some(Box::new(
+            Cartridge::from_raw_image(CartridgeMode::Ultimax, &image).unwrap(),
+        )));
+        c64.reset();
+
+        // Each bad line stops the CPU for 43 cycles, minus the write cycles
+        // that it managed to perform after BA went low.
+        let mut stalled_cycles = 0;
+        let mut writes_in_a_row = 0;
+        let mut shortened_stalls = 0;
+        for _ in 0..2 * 263 * 65 {
+            let cycles = c64.cpu().cycles();
+            for _ in 0..8 {
+                c64.tick().unwrap();
+            }
+            if c64.cpu().cycles() == cycles {
+                stalled_cycles += 1;
+                continue;
+            }
+            if stalled_cycles > 0 {
+                assert!(
+                    (40..=43).contains(&stalled_cycles),
+                    "Stalled for {} cycles",
+                    stalled_cycles
+                );
+                assert!(43 - stalled_cycles <= writes_in_a_row);
+                if stalled_cycles < 43 {
+                    shortened_stalls += 1;
+                }
+                stalled_cycles = 0;
+                writes_in_a_row = 0;
+            }
+            if matches!(c64.cpu().last_memory_access(), Some(MemoryAccess::Write(_))) {
+                writes_in_a_row += 1;
+            } else {
+                writes_in_a_row = 0;
+            }
+        }
+        assert_ne!(shortened_stalls, 0);
+    }
 }
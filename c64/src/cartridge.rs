@@ -0,0 +1,553 @@
+use common::archive::read_image_file;
+use common::archive::ArchiveError;
+use common::archive::ImageFile;
+use std::io;
+use std::path::Path;
+use ya6502::mapper::Mapper;
+use ya6502::memory::Inspect;
+use ya6502::memory::MemorySizeError;
+use ya6502::memory::Read;
+use ya6502::memory::ReadError;
+use ya6502::memory::ReadResult;
+use ya6502::memory::Rom;
+use ya6502::memory::Write;
+use ya6502::memory::WriteError;
+use ya6502::memory::WriteResult;
+
+/// A [`Mapper`] plugged into the C64 expansion port. Apart from the ROM banks,
+/// it controls the GAME and EXROM lines, which decide where its ROM is visible,
+/// and it can expose registers in the I/O 1 and I/O 2 areas ($DE00-$DFFF),
+/// which are written through the [`Write`] interface.
+pub trait ExpansionPortMapper: Mapper + Write {
+    fn mode(&self) -> CartridgeMode;
+}
+
+/// A C64 expansion port cartridge. It consists of a number of ROM banks, each
+/// of which may contain a ROML chip (mapped at $8000-$9FFF) and a ROMH chip
+/// (mapped at $A000-$BFFF or $E000-$FFFF, depending on the mode). Banks are
+/// switched using registers in the I/O 1 area, as dictated by the cartridge
+/// hardware type.
+#[derive(Debug)]
+pub struct Cartridge {
+    mode: CartridgeMode,
+    initial_mode: CartridgeMode,
+    hardware: CartridgeHardware,
+    roml_banks: Vec<Option<Rom>>,
+    romh_banks: Vec<Option<Rom>>,
+    bank: usize,
+}
+
+/// Types of cartridge ROM available in the C64 architecture. The mode is
+/// determined by the state of GAME and EXROM lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CartridgeMode {
+    /// Both GAME and EXROM inactive: the cartridge ROM is not visible.
+    Off,
+    /// Standard 8KiB cartridge ($8000-$9FFF)
+    Standard8k,
+    /// Standard 16KiB cartridge ($8000-$BFFF)
+    Standard16k,
+    /// Ultimax 16KiB cartridge ($8000-$9FFF, $E000-$FFFF).
+    Ultimax,
+}
+
+impl CartridgeMode {
+    /// Determines the cartridge mode from the GAME and EXROM line states, as
+    /// stored in a CRT file (0 = active).
+    fn from_lines(exrom: u8, game: u8) -> Self {
+        match (exrom != 0, game != 0) {
+            (true, true) => Self::Off,
+            (false, true) => Self::Standard8k,
+            (false, false) => Self::Standard16k,
+            (true, false) => Self::Ultimax,
+        }
+    }
+}
+
+/// Cartridge hardware types, as identified in CRT files. Determines the way
+/// banks are switched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CartridgeHardware {
+    /// A plain cartridge with no bank switching.
+    Normal,
+    /// Ocean type 1: bank selected by writing to $DE00 (bits 0-5).
+    Ocean,
+    /// Magic Desk, Domark, HES Australia: bank selected by writing to $DE00
+    /// (bits 0-6); setting bit 7 disables the cartridge.
+    MagicDesk,
+}
+
+impl CartridgeHardware {
+    fn from_crt_id(id: u16) -> Result<Self, CartridgeError> {
+        match id {
+            0 => Ok(Self::Normal),
+            5 => Ok(Self::Ocean),
+            19 => Ok(Self::MagicDesk),
+            other => Err(CartridgeError::UnsupportedHardwareType(other)),
+        }
+    }
+}
+
+impl Cartridge {
+    /// Creates a cartridge with no bank switching from a raw ROM image. 8KiB
+    /// images are visible both as ROML and ROMH; 16KiB images are split in half
+    /// between ROML and ROMH.
+    pub fn from_raw_image(mode: CartridgeMode, bytes: &[u8]) -> Result<Self, CartridgeError> {
+        let (roml, romh) = if bytes.len() > 0x2000 {
+            bytes.split_at(0x2000)
+        } else {
+            (bytes, bytes)
+        };
+        let mut cartridge = Self::empty(mode, CartridgeHardware::Normal);
+        cartridge.roml_banks.push(Some(Rom::new(roml)?));
+        cartridge.romh_banks.push(Some(Rom::new(romh)?));
+        return Ok(cartridge);
+    }
+
+    fn empty(mode: CartridgeMode, hardware: CartridgeHardware) -> Self {
+        Self {
+            mode,
+            initial_mode: mode,
+            hardware,
+            roml_banks: vec![],
+            romh_banks: vec![],
+            bank: 0,
+        }
+    }
+
+    /// Handles a write to the I/O 1 area ($DE00-$DEFF), where bank switching
+    /// registers live.
+    fn write_io1(&mut self, address: u16, value: u8) -> WriteResult {
+        match self.hardware {
+            CartridgeHardware::Normal => return Err(WriteError { address, value }),
+            CartridgeHardware::Ocean => self.select_bank(value & 0b0011_1111),
+            CartridgeHardware::MagicDesk => {
+                self.select_bank(value & 0b0111_1111);
+                self.mode = if value & 0b1000_0000 != 0 {
+                    CartridgeMode::Off
+                } else {
+                    CartridgeMode::Standard8k
+                };
+            }
+        }
+        return Ok(());
+    }
+
+    /// Switches to a given bank. A cartridge only decodes as many bank number
+    /// bits as it needs for its ROM size, so higher bank numbers wrap around.
+    fn select_bank(&mut self, bank: u8) {
+        let bank_mask = self.roml_banks.len().next_power_of_two() - 1;
+        self.bank = bank as usize & bank_mask;
+    }
+
+    fn chip(&self, address: u16) -> Option<&Rom> {
+        let banks = if (0x8000..=0x9FFF).contains(&address) {
+            &self.roml_banks
+        } else {
+            &self.romh_banks
+        };
+        return banks.get(self.bank).and_then(Option::as_ref);
+    }
+
+    fn add_chip(
+        &mut self,
+        bank: usize,
+        load_address: u16,
+        data: &[u8],
+    ) -> Result<(), CartridgeError> {
+        let (roml, romh) = match (load_address, data.len()) {
+            (0x8000, 0x2000) => (Some(data), None),
+            (0x8000, 0x4000) => {
+                let (roml, romh) = data.split_at(0x2000);
+                (Some(roml), Some(romh))
+            }
+            (0xA000 | 0xE000, 0x2000) | (0xF000, 0x1000) => (None, Some(data)),
+            (load_address, size) => {
+                return Err(CartridgeError::UnsupportedChip { load_address, size })
+            }
+        };
+        if self.roml_banks.len() <= bank {
+            self.roml_banks.resize_with(bank + 1, || None);
+            self.romh_banks.resize_with(bank + 1, || None);
+        }
+        if let Some(roml) = roml {
+            self.roml_banks[bank] = Some(Rom::new(roml)?);
+        }
+        if let Some(romh) = romh {
+            self.romh_banks[bank] = Some(Rom::new(romh)?);
+        }
+        return Ok(());
+    }
+}
+
+/// Note that the cartridge doesn't decide by itself whether a given address
+/// is mapped to it or not; this is the responsibility of the address space,
+/// which takes the cartridge mode into account.
+impl Inspect for Cartridge {
+    fn inspect(&self, address: u16) -> ReadResult {
+        match self.chip(address) {
+            Some(rom) => rom.inspect(address),
+            None => Err(ReadError { address }),
+        }
+    }
+}
+
+impl Read for Cartridge {
+    fn read(&mut self, address: u16) -> ReadResult {
+        self.inspect(address)
+    }
+}
+
+impl Write for Cartridge {
+    fn write(&mut self, address: u16, value: u8) -> WriteResult {
+        match address {
+            0xDE00..=0xDEFF => self.write_io1(address, value),
+            _ => Err(WriteError { address, value }),
+        }
+    }
+}
+
+impl Mapper for Cartridge {
+    /// Brings the cartridge to its power-on state.
+    fn reset(&mut self) {
+        self.mode = self.initial_mode;
+        self.bank = 0;
+    }
+}
+
+impl ExpansionPortMapper for Cartridge {
+    fn mode(&self) -> CartridgeMode {
+        self.mode
+    }
+}
+
+/// Signature that starts every CRT file.
+pub const CRT_SIGNATURE: &[u8] = b"C64 CARTRIDGE   ";
+
+/// Reads a cartridge from a CRT file. Only ROM chips and some of the cartridge
+/// hardware types are supported.
+pub fn read_crt_file(mut reader: impl io::Read) -> Result<Cartridge, CartridgeError> {
+    const MIN_HEADER_SIZE: usize = 0x40;
+    const HEADER_SIZE_OFFSET: usize = 0x10;
+    const HARDWARE_TYPE_OFFSET: usize = 0x16;
+    const EXROM_OFFSET: usize = 0x18;
+    const GAME_OFFSET: usize = 0x19;
+    const CHIP_HEADER_SIZE: usize = 0x10;
+
+    let mut header = [0u8; MIN_HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+    if !header.starts_with(CRT_SIGNATURE) {
+        return Err(CartridgeError::InvalidSignature);
+    }
+    let header_size = u32::from_be_bytes(
+        header[HEADER_SIZE_OFFSET..HEADER_SIZE_OFFSET + 4]
+            .try_into()
+            .unwrap(),
+    ) as usize;
+    let hardware_type = u16::from_be_bytes(
+        header[HARDWARE_TYPE_OFFSET..HARDWARE_TYPE_OFFSET + 2]
+            .try_into()
+            .unwrap(),
+    );
+    let mut cartridge = Cartridge::empty(
+        CartridgeMode::from_lines(header[EXROM_OFFSET], header[GAME_OFFSET]),
+        CartridgeHardware::from_crt_id(hardware_type)?,
+    );
+    // Some files declare a longer header; skip whatever we don't understand.
+    skip(&mut reader, header_size.saturating_sub(MIN_HEADER_SIZE))?;
+
+    loop {
+        let mut chip_header = [0u8; CHIP_HEADER_SIZE];
+        let result = reader.read_exact(&mut chip_header);
+        if let Err(e) = result {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                break;
+            } else {
+                return Err(e.into());
+            }
+        }
+        if !chip_header.starts_with("CHIP".as_bytes()) {
+            return Err(CartridgeError::InvalidChipSignature);
+        }
+        let packet_size = u32::from_be_bytes(chip_header[4..8].try_into().unwrap()) as usize;
+        let chip_type = u16::from_be_bytes(chip_header[8..10].try_into().unwrap());
+        let bank = u16::from_be_bytes(chip_header[10..12].try_into().unwrap());
+        let load_address = u16::from_be_bytes(chip_header[12..14].try_into().unwrap());
+        let size = u16::from_be_bytes(chip_header[14..16].try_into().unwrap()) as usize;
+        // Chip type 1 is RAM; 0 and 2 are ROM and Flash ROM, respectively.
+        if chip_type == 1 {
+            return Err(CartridgeError::UnsupportedChipType(chip_type));
+        }
+
+        let mut data = vec![0u8; size];
+        reader.read_exact(&mut data)?;
+        cartridge.add_chip(bank as usize, load_address, &data)?;
+        skip(
+            &mut reader,
+            packet_size.saturating_sub(CHIP_HEADER_SIZE + size),
+        )?;
+    }
+
+    return Ok(cartridge);
+}
+
+/// Extensions of files that contain cartridge images.
+pub const CARTRIDGE_FILE_EXTENSIONS: &[&str] = &["crt", "bin"];
+
+/// Reads a cartridge from a file, which may also be compressed (see
+/// [`read_image_file`]).
+pub fn read_cartridge_file(path: &Path) -> Result<Cartridge, CartridgeError> {
+    return cartridge_from_image(&read_image_file(path, CARTRIDGE_FILE_EXTENSIONS)?);
+}
+
+/// Creates a cartridge from an image file. CRT files carry all the information
+/// about the cartridge type; raw ROM images are assumed to be Ultimax ones.
+pub fn cartridge_from_image(image: &ImageFile) -> Result<Cartridge, CartridgeError> {
+    return if image.has_extension("crt") {
+        read_crt_file(&image.bytes[..])
+    } else {
+        Cartridge::from_raw_image(CartridgeMode::Ultimax, &image.bytes)
+    };
+}
+
+fn skip(reader: &mut impl io::Read, num_bytes: usize) -> io::Result<()> {
+    io::copy(
+        &mut io::Read::take(reader, num_bytes as u64),
+        &mut io::sink(),
+    )?;
+    return Ok(());
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CartridgeError {
+    #[error("I/O error: {0}")]
+    IoError(#[from] io::Error),
+
+    #[error("{0}")]
+    ArchiveError(#[from] ArchiveError),
+
+    #[error("Invalid CRT file signature")]
+    InvalidSignature,
+
+    #[error("Invalid CHIP packet signature")]
+    InvalidChipSignature,
+
+    #[error("Unsupported cartridge hardware type: {0}")]
+    UnsupportedHardwareType(u16),
+
+    #[error("Unsupported chip type: {0}")]
+    UnsupportedChipType(u16),
+
+    #[error("Unsupported ROM chip at ${load_address:04X}, size: {size} bytes")]
+    UnsupportedChip { load_address: u16, size: usize },
+
+    #[error("{0}")]
+    InvalidRomSize(#[from] MemorySizeError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::assert_matches::assert_matches;
+
+    fn crt_header(hardware_type: u16, exrom: u8, game: u8) -> Vec<u8> {
+        let mut header = [
+            "C64 CARTRIDGE   ".as_bytes(),
+            &[0, 0, 0, 0x40, 1, 0],
+            &hardware_type.to_be_bytes(),
+            &[exrom, game],
+        ]
+        .concat();
+        header.resize(0x40, 0);
+        return header;
+    }
+
+    fn chip_packet(bank: u16, load_address: u16, data: &[u8]) -> Vec<u8> {
+        [
+            "CHIP".as_bytes(),
+            &(data.len() as u32 + 0x10).to_be_bytes(),
+            &[0, 0],
+            &bank.to_be_bytes(),
+            &load_address.to_be_bytes(),
+            &(data.len() as u16).to_be_bytes(),
+            data,
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn raw_image_8k() {
+        let mut cartridge =
+            Cartridge::from_raw_image(CartridgeMode::Standard8k, &[1; 0x2000]).unwrap();
+        assert_eq!(cartridge.mode(), CartridgeMode::Standard8k);
+        assert_eq!(cartridge.read(0x8000).unwrap(), 1);
+        assert_eq!(cartridge.read(0x9FFF).unwrap(), 1);
+    }
+
+    #[test]
+    fn raw_image_16k() {
+        let image = [[1; 0x2000], [2; 0x2000]].concat();
+        let mut cartridge = Cartridge::from_raw_image(CartridgeMode::Ultimax, &image).unwrap();
+        assert_eq!(cartridge.read(0x8000).unwrap(), 1);
+        assert_eq!(cartridge.read(0x9FFF).unwrap(), 1);
+        assert_eq!(cartridge.read(0xE000).unwrap(), 2);
+        assert_eq!(cartridge.read(0xFFFF).unwrap(), 2);
+    }
+
+    #[test]
+    fn crt_file_8k() {
+        let crt = [crt_header(0, 0, 1), chip_packet(0, 0x8000, &[5; 0x2000])].concat();
+        let mut cartridge = read_crt_file(crt.as_slice()).unwrap();
+        assert_eq!(cartridge.mode(), CartridgeMode::Standard8k);
+        assert_eq!(cartridge.read(0x8000).unwrap(), 5);
+        assert_eq!(cartridge.read(0x9FFF).unwrap(), 5);
+    }
+
+    #[test]
+    fn crt_file_16k() {
+        let crt = [
+            crt_header(0, 0, 0),
+            chip_packet(0, 0x8000, &[[6; 0x2000], [7; 0x2000]].concat()),
+        ]
+        .concat();
+        let mut cartridge = read_crt_file(crt.as_slice()).unwrap();
+        assert_eq!(cartridge.mode(), CartridgeMode::Standard16k);
+        assert_eq!(cartridge.read(0x8000).unwrap(), 6);
+        assert_eq!(cartridge.read(0xA000).unwrap(), 7);
+        assert_eq!(cartridge.read(0xBFFF).unwrap(), 7);
+    }
+
+    #[test]
+    fn crt_file_ultimax() {
+        let crt = [crt_header(0, 1, 0), chip_packet(0, 0xE000, &[8; 0x2000])].concat();
+        let mut cartridge = read_crt_file(crt.as_slice()).unwrap();
+        assert_eq!(cartridge.mode(), CartridgeMode::Ultimax);
+        assert_matches!(cartridge.read(0x8000), Err(ReadError { address: 0x8000 }));
+        assert_eq!(cartridge.read(0xE000).unwrap(), 8);
+    }
+
+    #[test]
+    fn crt_file_long_header() {
+        let mut header = crt_header(0, 0, 1);
+        header[0x13] = 0x42;
+        header.extend_from_slice(&[0xFF, 0xFF]);
+        let crt = [header, chip_packet(0, 0x8000, &[5; 0x2000])].concat();
+        let mut cartridge = read_crt_file(crt.as_slice()).unwrap();
+        assert_eq!(cartridge.read(0x8000).unwrap(), 5);
+    }
+
+    #[test]
+    fn crt_file_invalid_signature() {
+        let mut crt = crt_header(0, 0, 1);
+        crt[0..3].copy_from_slice("C65".as_bytes());
+        assert_matches!(
+            read_crt_file(crt.as_slice()),
+            Err(CartridgeError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn crt_file_invalid_chip_signature() {
+        let mut crt = [crt_header(0, 0, 1), chip_packet(0, 0x8000, &[5; 0x2000])].concat();
+        crt[0x40..0x44].copy_from_slice("CHOP".as_bytes());
+        assert_matches!(
+            read_crt_file(crt.as_slice()),
+            Err(CartridgeError::InvalidChipSignature)
+        );
+    }
+
+    #[test]
+    fn crt_file_unsupported_hardware_type() {
+        let crt = crt_header(32, 0, 1);
+        assert_matches!(
+            read_crt_file(crt.as_slice()),
+            Err(CartridgeError::UnsupportedHardwareType(32))
+        );
+    }
+
+    #[test]
+    fn crt_file_unsupported_chip() {
+        let crt = [crt_header(0, 0, 1), chip_packet(0, 0x9000, &[5; 0x1000])].concat();
+        assert_matches!(
+            read_crt_file(crt.as_slice()),
+            Err(CartridgeError::UnsupportedChip {
+                load_address: 0x9000,
+                size: 0x1000
+            })
+        );
+    }
+
+    #[test]
+    fn crt_file_truncated_chip() {
+        let mut crt = [crt_header(0, 0, 1), chip_packet(0, 0x8000, &[5; 0x2000])].concat();
+        crt.truncate(0x1000);
+        assert_matches!(
+            read_crt_file(crt.as_slice()),
+            Err(CartridgeError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn ocean_bank_switching() {
+        let crt = [
+            crt_header(5, 0, 1),
+            chip_packet(0, 0x8000, &[10; 0x2000]),
+            chip_packet(1, 0x8000, &[11; 0x2000]),
+            chip_packet(2, 0x8000, &[12; 0x2000]),
+        ]
+        .concat();
+        let mut cartridge = read_crt_file(crt.as_slice()).unwrap();
+        assert_eq!(cartridge.read(0x8000).unwrap(), 10);
+        cartridge.write_io1(0xDE00, 2).unwrap();
+        assert_eq!(cartridge.read(0x8000).unwrap(), 12);
+        cartridge.write_io1(0xDE00, 0b1100_0001).unwrap();
+        assert_eq!(cartridge.read(0x8000).unwrap(), 11);
+        assert_eq!(cartridge.mode(), CartridgeMode::Standard8k);
+        // Bank numbers wrap around the 4 banks that 3 chips need.
+        cartridge.write_io1(0xDE00, 0b0000_0110).unwrap();
+        assert_eq!(cartridge.read(0x8000).unwrap(), 12);
+
+        cartridge.reset();
+        assert_eq!(cartridge.read(0x8000).unwrap(), 10);
+    }
+
+    #[test]
+    fn magic_desk_bank_switching() {
+        let crt = [
+            crt_header(19, 0, 1),
+            chip_packet(0, 0x8000, &[20; 0x2000]),
+            chip_packet(1, 0x8000, &[21; 0x2000]),
+        ]
+        .concat();
+        let mut cartridge = read_crt_file(crt.as_slice()).unwrap();
+        cartridge.write_io1(0xDE00, 1).unwrap();
+        assert_eq!(cartridge.read(0x8000).unwrap(), 21);
+        assert_eq!(cartridge.mode(), CartridgeMode::Standard8k);
+
+        cartridge.write_io1(0xDE00, 0b1000_0000).unwrap();
+        assert_eq!(cartridge.mode(), CartridgeMode::Off);
+        cartridge.write_io1(0xDE00, 0).unwrap();
+        assert_eq!(cartridge.mode(), CartridgeMode::Standard8k);
+        assert_eq!(cartridge.read(0x8000).unwrap(), 20);
+
+        cartridge.write_io1(0xDE00, 0b0100_0001).unwrap();
+        assert_eq!(cartridge.read(0x8000).unwrap(), 21);
+
+        cartridge.write_io1(0xDE00, 0b1000_0001).unwrap();
+        cartridge.reset();
+        assert_eq!(cartridge.mode(), CartridgeMode::Standard8k);
+    }
+
+    #[test]
+    fn normal_cartridge_has_no_registers() {
+        let mut cartridge =
+            Cartridge::from_raw_image(CartridgeMode::Standard8k, &[1; 0x2000]).unwrap();
+        assert_matches!(
+            cartridge.write_io1(0xDE00, 1),
+            Err(WriteError {
+                address: 0xDE00,
+                value: 1
+            })
+        );
+    }
+}
@@ -1,6 +1,11 @@
 use crate::port::Port;
+use crate::timer::Input;
 use crate::timer::Timer;
+use crate::tod::TimeOfDay;
+use common::debugger::symbols::RegisterNames;
 use enum_map::{Enum, EnumMap};
+use ya6502::cpu::ChipRegister;
+use ya6502::cpu::ChipState;
 use ya6502::memory::Inspect;
 use ya6502::memory::Memory;
 use ya6502::memory::Read;
@@ -15,8 +20,27 @@ pub struct Cia {
     reg_interrupt_status: u8,
 
     ports: EnumMap<PortName, Port>,
+    /// Lines pulled low by external devices, such as joysticks. Unlike the
+    /// regular input, these override the output pins as well.
+    pulled_down_lines: EnumMap<PortName, u8>,
     timer_a: Timer,
     timer_b: Timer,
+    /// Level of the CNT pin. Timers can count its rising edges.
+    cnt: bool,
+    tod: TimeOfDay,
+    /// If set, writing to the TOD registers sets the alarm instead of the
+    /// current time.
+    write_tod_alarm: bool,
+
+    reg_serial_data: u8,
+    /// Whether the serial port works as an output.
+    serial_output: bool,
+    /// Number of timer A underflows left until the current byte is shifted
+    /// out, or 0 if the serial port is idle.
+    serial_shift_counter: u8,
+    /// Set if another byte has been written to the serial data register while
+    /// shifting out the current one.
+    serial_data_pending: bool,
 }
 
 #[derive(Enum, Debug, Clone, Copy)]
@@ -27,18 +51,95 @@ pub enum PortName {
 
 impl Cia {
     pub fn new() -> Self {
-        Self::default()
+        Self {
+            // The CNT line is pulled up on the C64.
+            cnt: true,
+            ..Default::default()
+        }
     }
 
     /// Performs a tick and returns `true` if an interrupt was triggered.
     pub fn tick(&mut self) -> bool {
-        if self.timer_a.tick() {
+        self.timer_a.clock();
+        self.timer_b.clock();
+        self.count_timers(true, false);
+        return self.reg_interrupt_status & flags::ICR_TRIGGERED != 0;
+    }
+
+    /// Sets the level of the CNT pin. Timers configured to count CNT pulses
+    /// count its rising edges.
+    pub fn set_cnt(&mut self, level: bool) {
+        let rising_edge = level && !self.cnt;
+        self.cnt = level;
+        if rising_edge {
+            self.count_timers(false, true);
+        }
+    }
+
+    /// Lets each timer count, if its input signal is active. Timer B may
+    /// count underflows of timer A, so it goes second.
+    fn count_timers(&mut self, system_clock: bool, cnt_edge: bool) {
+        let cnt = self.cnt;
+        let counts = |timer: &Timer, timer_a_underflow: bool| match timer.input() {
+            Input::SystemClock => system_clock,
+            Input::Cnt => cnt_edge,
+            Input::TimerA => timer_a_underflow,
+            Input::TimerAWithCnt => timer_a_underflow && cnt,
+        };
+        let timer_a_underflow = counts(&self.timer_a, false) && self.timer_a.tick();
+        if timer_a_underflow {
             self.set_interrupt_flag(flags::ICR_TIMER_A);
+            self.shift_serial_data();
         }
-        if self.timer_b.tick() {
+        if counts(&self.timer_b, timer_a_underflow) && self.timer_b.tick() {
             self.set_interrupt_flag(flags::ICR_TIMER_B);
         }
-        return self.reg_interrupt_status & flags::ICR_TRIGGERED != 0;
+    }
+
+    /// Indicates a pulse on the TOD pin, which drives the time-of-day clock.
+    pub fn tick_tod(&mut self) {
+        if self.tod.tick() {
+            self.set_interrupt_flag(flags::ICR_ALARM);
+        }
+    }
+
+    /// Shifts out serial data. The CNT line toggles on each timer A underflow,
+    /// so it takes 16 underflows to send a byte. Since nothing is connected to
+    /// the serial port, the data itself is discarded; only the timing matters.
+    fn shift_serial_data(&mut self) {
+        if !self.serial_output || self.serial_shift_counter == 0 {
+            return;
+        }
+        self.serial_shift_counter -= 1;
+        if self.serial_shift_counter == 0 {
+            self.set_interrupt_flag(flags::ICR_SERIAL);
+            if std::mem::take(&mut self.serial_data_pending) {
+                self.serial_shift_counter = SERIAL_SHIFT_LENGTH;
+            }
+        }
+    }
+
+    /// Reads control register A. Apart from timer A settings, it also holds
+    /// the TOD clock frequency and the serial port direction.
+    fn control_a(&self) -> u8 {
+        let mut value = self.timer_a.control();
+        if self.tod.fifty_hz() {
+            value |= flags::CRA_TODIN;
+        }
+        if self.serial_output {
+            value |= flags::CRA_SPMODE;
+        }
+        return value;
+    }
+
+    /// Reads control register B. Apart from timer B settings, it also decides
+    /// whether writing to TOD registers sets the alarm.
+    fn control_b(&self) -> u8 {
+        let mut value = self.timer_b.control();
+        if self.write_tod_alarm {
+            value |= flags::CRB_ALARM;
+        }
+        return value;
     }
 
     /// Writes a given value to the pins of a given port.
@@ -46,10 +147,70 @@ impl Cia {
         self.ports[port_name].pins = value;
     }
 
+    /// Pulls given lines of a given port low. Pulled down lines read as 0,
+    /// regardless of the port direction configuration. This is how joysticks
+    /// interact with the CIA ports.
+    pub fn pull_down_lines(&mut self, port_name: PortName, mask: u8) {
+        self.pulled_down_lines[port_name] = mask;
+    }
+
     /// Reads a value from the pins of a given port. The value takes into
     /// consideration the direction configuration for each particular bit.
     pub fn read_port(&self, port_name: PortName) -> u8 {
-        self.ports[port_name].read()
+        let (mask, value) = self.timer_outputs(port_name);
+        (self.ports[port_name].read() & !mask | value) & !self.pulled_down_lines[port_name]
+    }
+
+    /// Returns a mask of port lines driven by the timers and their levels.
+    /// Timer A can drive PB6, and timer B can drive PB7, regardless of the
+    /// port direction.
+    fn timer_outputs(&self, port_name: PortName) -> (u8, u8) {
+        let mut mask = 0;
+        let mut value = 0;
+        if let PortName::B = port_name {
+            for (timer, line) in [(&self.timer_a, 1 << 6), (&self.timer_b, 1 << 7)] {
+                if timer.output_enabled() {
+                    mask |= line;
+                    if timer.output() {
+                        value |= line;
+                    }
+                }
+            }
+        }
+        return (mask, value);
+    }
+
+    /// Reports the values of CIA registers for debugging purposes. Since the
+    /// C64 has two CIA chips, the caller decides which name to report. Timers
+    /// are reported as 16-bit counters, and the interrupt mask, which can't be
+    /// read by the CPU, is reported separately from the interrupt status.
+    pub fn chip_state(&self, name: &str) -> Result<ChipState, ReadError> {
+        let timer_registers = registers::TA_LO..=registers::TB_HI;
+        let mut registers = REGISTER_NAMES
+            .into_iter()
+            .zip(0..)
+            .filter(|(_, address)| !timer_registers.contains(address))
+            .map(|(name, address)| Ok(ChipRegister::new(name, self.inspect(address)?)))
+            .collect::<Result<Vec<_>, ReadError>>()?;
+        registers.push(ChipRegister::new(
+            "IMR",
+            self.reg_interrupt_control & !flags::ICR_SOURCE_BIT,
+        ));
+        registers.push(ChipRegister::new("TA", self.timer_a.counter()));
+        registers.push(ChipRegister::new("TB", self.timer_b.counter()));
+        return Ok(ChipState {
+            name: name.to_string(),
+            registers,
+        });
+    }
+
+    /// Returns levels driven on the lines of a given port by the chip itself
+    /// and by the devices that pull them down. Output bits come from the data
+    /// register, and input bits are pulled up.
+    pub fn port_output(&self, port_name: PortName) -> u8 {
+        let port = &self.ports[port_name];
+        let (mask, value) = self.timer_outputs(port_name);
+        ((port.register | !port.direction) & !mask | value) & !self.pulled_down_lines[port_name]
     }
 
     /// Indicates a falling edge happening on the /FLAG pin.
@@ -71,20 +232,25 @@ impl Cia {
     }
 }
 
+/// Number of timer A underflows needed to shift out a single byte.
+const SERIAL_SHIFT_LENGTH: u8 = 16;
+
 impl Inspect for Cia {
     fn inspect(&self, address: u16) -> Result<u8, ReadError> {
         match address & 0b1111 {
-            registers::PRA => Ok(self.ports[PortName::A].read()),
-            registers::PRB => Ok(self.ports[PortName::B].read()),
+            registers::PRA => Ok(self.read_port(PortName::A)),
+            registers::PRB => Ok(self.read_port(PortName::B)),
             registers::DDRA => Ok(self.ports[PortName::A].direction),
             registers::DDRB => Ok(self.ports[PortName::B].direction),
             registers::TA_LO => Ok((self.timer_a.counter() & 0xFF) as u8),
             registers::TA_HI => Ok(((self.timer_a.counter() & 0xFF00) >> 8) as u8),
             registers::TB_LO => Ok((self.timer_b.counter() & 0xFF) as u8),
             registers::TB_HI => Ok(((self.timer_b.counter() & 0xFF00) >> 8) as u8),
+            registers::TOD_10THS..=registers::TOD_HR => Ok(self.tod.inspect(tod_register(address))),
+            registers::SDR => Ok(self.reg_serial_data),
             registers::ICR => Ok(self.reg_interrupt_status),
-            registers::CRA => Ok(self.timer_a.control()),
-            registers::CRB => Ok(self.timer_b.control()),
+            registers::CRA => Ok(self.control_a()),
+            registers::CRB => Ok(self.control_b()),
             _ => Err(ReadError { address }),
         }
     }
@@ -94,6 +260,7 @@ impl Read for Cia {
     fn read(&mut self, address: u16) -> Result<u8, ReadError> {
         match address & 0b1111 {
             registers::ICR => Ok(std::mem::take(&mut self.reg_interrupt_status)),
+            registers::TOD_10THS..=registers::TOD_HR => Ok(self.tod.read(tod_register(address))),
             _ => self.inspect(address),
         }
     }
@@ -113,22 +280,35 @@ impl Write for Cia {
             registers::TA_LO => self
                 .timer_a
                 .set_latch(self.timer_a.latch() & 0xFF00 | value as u16),
-            registers::TA_HI => self
-                .timer_a
-                .set_latch(self.timer_a.latch() & 0xFF | (value as u16) << 8),
+            registers::TA_HI => self.timer_a.set_latch_hi(value),
             registers::TB_LO => self
                 .timer_b
                 .set_latch(self.timer_b.latch() & 0xFF00 | value as u16),
-            registers::TB_HI => self
-                .timer_b
-                .set_latch(self.timer_b.latch() & 0xFF | (value as u16) << 8),
+            registers::TB_HI => self.timer_b.set_latch_hi(value),
+            registers::TOD_10THS..=registers::TOD_HR => {
+                self.tod
+                    .write(tod_register(address), value, self.write_tod_alarm)
+            }
+            registers::SDR => {
+                self.reg_serial_data = value;
+                if self.serial_output {
+                    if self.serial_shift_counter == 0 {
+                        self.serial_shift_counter = SERIAL_SHIFT_LENGTH;
+                    } else {
+                        self.serial_data_pending = true;
+                    }
+                }
+            }
             registers::ICR => {
                 if value & flags::ICR_SOURCE_BIT != 0 {
                     // Set mask bits.
-                    // For now, only allow turning on timer and FLAG IRQs.
+                    // For now, only allow turning on timer, TOD alarm, serial
+                    // port, and FLAG IRQs.
                     if value
                         & !(flags::ICR_TIMER_A
                             | flags::ICR_TIMER_B
+                            | flags::ICR_ALARM
+                            | flags::ICR_SERIAL
                             | flags::ICR_FLAG_SIGNAL
                             | flags::ICR_SOURCE_BIT)
                         != 0
@@ -141,14 +321,20 @@ impl Write for Cia {
                 }
             }
             registers::CRA => {
-                if self.timer_a.set_control(value).is_err() {
-                    return Err(WriteError { address, value });
+                // Timer A doesn't count timer A underflows, so its input mode
+                // only takes one bit; the next one controls the serial port.
+                self.timer_a
+                    .set_control(value & !(flags::CRA_TODIN | flags::CRA_SPMODE));
+                self.tod.set_fifty_hz(value & flags::CRA_TODIN != 0);
+                self.serial_output = value & flags::CRA_SPMODE != 0;
+                if !self.serial_output {
+                    self.serial_shift_counter = 0;
+                    self.serial_data_pending = false;
                 }
             }
             registers::CRB => {
-                if self.timer_b.set_control(value).is_err() {
-                    return Err(WriteError { address, value });
-                }
+                self.timer_b.set_control(value & !flags::CRB_ALARM);
+                self.write_tod_alarm = value & flags::CRB_ALARM != 0;
             }
             _ => return Err(WriteError { address, value }),
         };
@@ -158,7 +344,41 @@ impl Write for Cia {
 
 impl Memory for Cia {}
 
-#[allow(dead_code)]
+/// Maps a TOD register address to its index in the time-of-day clock.
+fn tod_register(address: u16) -> usize {
+    ((address & 0b1111) - registers::TOD_10THS) as usize
+}
+
+/// Returns names of the CIA registers, used to annotate the disassembly. The
+/// register addresses are relative to `base`.
+pub fn register_names(base: u16) -> RegisterNames {
+    RegisterNames::new().with_registers(
+        REGISTER_NAMES
+            .into_iter()
+            .map(|(name, offset)| (name, base + offset)),
+    )
+}
+
+/// Names of the CIA registers, along with their offsets.
+const REGISTER_NAMES: [(&str, u16); 0x10] = [
+    ("PRA", registers::PRA),
+    ("PRB", registers::PRB),
+    ("DDRA", registers::DDRA),
+    ("DDRB", registers::DDRB),
+    ("TALO", registers::TA_LO),
+    ("TAHI", registers::TA_HI),
+    ("TBLO", registers::TB_LO),
+    ("TBHI", registers::TB_HI),
+    ("TOD10THS", registers::TOD_10THS),
+    ("TODSEC", registers::TOD_SEC),
+    ("TODMIN", registers::TOD_MIN),
+    ("TODHR", registers::TOD_HR),
+    ("SDR", registers::SDR),
+    ("ICR", registers::ICR),
+    ("CRA", registers::CRA),
+    ("CRB", registers::CRB),
+];
+
 mod registers {
     pub const PRA: u16 = 0x0;
     pub const PRB: u16 = 0x1;
@@ -168,6 +388,11 @@ mod registers {
     pub const TA_HI: u16 = 0x5;
     pub const TB_LO: u16 = 0x6;
     pub const TB_HI: u16 = 0x7;
+    pub const TOD_10THS: u16 = 0x8;
+    pub const TOD_SEC: u16 = 0x9;
+    pub const TOD_MIN: u16 = 0xA;
+    pub const TOD_HR: u16 = 0xB;
+    pub const SDR: u16 = 0xC;
     pub const ICR: u16 = 0xD;
     pub const CRA: u16 = 0xE;
     pub const CRB: u16 = 0xF;
@@ -176,17 +401,53 @@ mod registers {
 mod flags {
     pub const ICR_TIMER_A: u8 = 1 << 0;
     pub const ICR_TIMER_B: u8 = 1 << 1;
+    pub const ICR_ALARM: u8 = 1 << 2;
+    pub const ICR_SERIAL: u8 = 1 << 3;
     pub const ICR_FLAG_SIGNAL: u8 = 1 << 4;
     pub const ICR_TRIGGERED: u8 = 1 << 7;
     pub const ICR_SOURCE_BIT: u8 = 1 << 7;
+
+    pub const CRA_SPMODE: u8 = 1 << 6;
+    pub const CRA_TODIN: u8 = 1 << 7;
+    pub const CRB_ALARM: u8 = 1 << 7;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    // #[test]
-    // fn disabling_interrupts() {}
+    /// Waits until timers that have just been started and loaded begin
+    /// counting.
+    fn wait_for_timers(cia: &mut Cia) {
+        cia.tick();
+        cia.tick();
+    }
+
+    #[test]
+    fn reports_chip_state() {
+        let mut cia = Cia::new();
+        cia.write(registers::DDRA, 0b1111_0000).unwrap();
+        cia.write(registers::TA_LO, 0x34).unwrap();
+        cia.write(registers::TA_HI, 0x12).unwrap();
+        // Load the timer A latch to the counter.
+        cia.write(registers::CRA, 0b0001_0000).unwrap();
+        cia.write(registers::ICR, flags::ICR_SOURCE_BIT | flags::ICR_TIMER_A)
+            .unwrap();
+        let state = cia.chip_state("CIA1").unwrap();
+        assert_eq!(state.name, "CIA1");
+        let register = |name| {
+            state
+                .registers
+                .iter()
+                .find(|register| register.name == name)
+                .unwrap()
+                .value
+        };
+        assert_eq!(register("DDRA"), 0b1111_0000);
+        assert_eq!(register("TA"), 0x1234);
+        assert_eq!(register("IMR"), flags::ICR_TIMER_A as u16);
+        assert_eq!(register("ICR"), 0);
+    }
 
     #[test]
     fn ports_output() {
@@ -237,6 +498,23 @@ mod tests {
         assert_eq!(cia.read_port(PortName::B), 0b0010_1000);
     }
 
+    #[test]
+    fn pulled_down_lines() {
+        let mut cia = Cia::new();
+        cia.write(registers::DDRA, 0b1111_0000).unwrap();
+        cia.write(registers::PRA, 0b1111_1111).unwrap();
+        cia.write_port(PortName::A, 0b1111_1111);
+        cia.pull_down_lines(PortName::A, 0b0001_0001);
+        assert_eq!(cia.read_port(PortName::A), 0b1110_1110);
+        assert_eq!(cia.read(registers::PRA).unwrap(), 0b1110_1110);
+        cia.pull_down_lines(PortName::A, 0);
+        assert_eq!(cia.read(registers::PRA).unwrap(), 0b1111_1111);
+
+        cia.write_port(PortName::B, 0b1111_0000);
+        cia.pull_down_lines(PortName::B, 0b0100_0100);
+        assert_eq!(cia.read(registers::PRB).unwrap(), 0b1011_0000);
+    }
+
     #[test]
     fn address_mirroring() {
         let mut cia = Cia::new();
@@ -251,6 +529,26 @@ mod tests {
         assert_eq!(cia.read(registers::DDRA).unwrap(), 0x14);
     }
 
+    #[test]
+    fn inspecting_interrupts() {
+        use crate::timer::flags::*;
+
+        let mut cia = Cia::new();
+        cia.write(registers::TA_HI, 0x00).unwrap();
+        cia.write(registers::TA_LO, 0x01).unwrap();
+        cia.write(registers::CRA, LOAD | START | RUNMODE_ONE_SHOT)
+            .unwrap();
+        wait_for_timers(&mut cia);
+        cia.tick();
+        cia.tick();
+
+        // Inspecting the interrupt register doesn't reset it.
+        assert_eq!(cia.inspect(registers::ICR).unwrap(), flags::ICR_TIMER_A);
+        assert_eq!(cia.inspect(registers::ICR).unwrap(), flags::ICR_TIMER_A);
+        assert_eq!(cia.read(registers::ICR).unwrap(), flags::ICR_TIMER_A);
+        assert_eq!(cia.inspect(registers::ICR).unwrap(), 0);
+    }
+
     macro_rules! test_timer {
         (
             $fn_name_basics:ident,
@@ -269,6 +567,7 @@ mod tests {
                 cia.write($reg_hi, 0x23).unwrap();
                 cia.write($reg_lo, 0x01).unwrap(); // Load 0x2301
                 cia.write($reg_cr, LOAD | START).unwrap();
+                wait_for_timers(&mut cia);
 
                 cia.tick();
                 cia.tick();
@@ -286,6 +585,7 @@ mod tests {
                 cia.write($reg_hi, 0x00).unwrap();
                 cia.write($reg_lo, 0x01).unwrap(); // Load 0x0001
                 cia.write($reg_cr, LOAD | START).unwrap();
+                wait_for_timers(&mut cia);
                 assert_eq!(cia.read(registers::ICR).unwrap(), 0);
 
                 cia.tick();
@@ -310,6 +610,7 @@ mod tests {
                 // No interrupts.
                 cia.write($reg_cr, LOAD | START | RUNMODE_ONE_SHOT).unwrap();
                 cia.write(registers::ICR, $icr_flag).unwrap();
+                wait_for_timers(&mut cia);
                 assert_eq!(cia.read(registers::ICR).unwrap(), 0);
                 assert_eq!(cia.tick(), false);
                 assert_eq!(cia.tick(), false);
@@ -320,6 +621,7 @@ mod tests {
                     .unwrap();
                 assert_eq!(cia.read(registers::ICR).unwrap(), 0);
                 cia.write($reg_cr, LOAD | START | RUNMODE_ONE_SHOT).unwrap();
+                wait_for_timers(&mut cia);
                 assert_eq!(cia.tick(), false);
                 assert_eq!(cia.tick(), true);
                 assert_eq!(cia.tick(), true); // Report IRQ until acknowledged.
@@ -333,6 +635,7 @@ mod tests {
                 // Disable interrupts again.
                 cia.write(registers::ICR, $icr_flag).unwrap();
                 cia.write($reg_cr, LOAD | START | RUNMODE_ONE_SHOT).unwrap();
+                wait_for_timers(&mut cia);
                 assert_eq!(cia.read(registers::ICR).unwrap(), 0);
                 assert_eq!(cia.tick(), false);
                 assert_eq!(cia.tick(), false);
@@ -390,4 +693,252 @@ mod tests {
         assert_eq!(cia.tick(), false);
         assert_eq!(cia.read(registers::ICR).unwrap(), 0);
     }
+
+    #[test]
+    fn tod_clock() {
+        let mut cia = Cia::new();
+        cia.write(registers::CRA, flags::CRA_TODIN).unwrap();
+        cia.write(registers::TOD_HR, 0x01).unwrap();
+        cia.write(registers::TOD_MIN, 0x02).unwrap();
+        cia.write(registers::TOD_SEC, 0x03).unwrap();
+        cia.write(registers::TOD_10THS, 0x04).unwrap();
+        for _ in 0..50 {
+            cia.tick_tod();
+        }
+        assert_eq!(cia.read(registers::TOD_HR).unwrap(), 0x01);
+        assert_eq!(cia.read(registers::TOD_MIN).unwrap(), 0x02);
+        assert_eq!(cia.read(registers::TOD_SEC).unwrap(), 0x04);
+        assert_eq!(cia.read(registers::TOD_10THS).unwrap(), 0x04);
+        assert_eq!(cia.read(registers::CRA).unwrap(), flags::CRA_TODIN);
+    }
+
+    #[test]
+    fn tod_alarm_interrupt() {
+        let mut cia = Cia::new();
+        cia.write(registers::ICR, flags::ICR_SOURCE_BIT | flags::ICR_ALARM)
+            .unwrap();
+        cia.write(registers::CRB, flags::CRB_ALARM).unwrap();
+        cia.write(registers::TOD_HR, 0x01).unwrap();
+        cia.write(registers::TOD_MIN, 0x00).unwrap();
+        cia.write(registers::TOD_SEC, 0x00).unwrap();
+        cia.write(registers::TOD_10THS, 0x01).unwrap();
+        cia.write(registers::CRB, 0).unwrap();
+        cia.write(registers::TOD_HR, 0x01).unwrap();
+        cia.write(registers::TOD_MIN, 0x00).unwrap();
+        cia.write(registers::TOD_SEC, 0x00).unwrap();
+        cia.write(registers::TOD_10THS, 0x00).unwrap();
+
+        for _ in 0..5 {
+            cia.tick_tod();
+        }
+        assert_eq!(cia.tick(), false);
+        cia.tick_tod();
+        assert_eq!(cia.tick(), true);
+        assert_eq!(
+            cia.read(registers::ICR).unwrap(),
+            flags::ICR_TRIGGERED | flags::ICR_ALARM
+        );
+        assert_eq!(cia.read(registers::TOD_10THS).unwrap(), 0x01);
+    }
+
+    #[test]
+    fn serial_output() {
+        use crate::timer::flags::*;
+
+        let mut cia = Cia::new();
+        cia.write(registers::ICR, flags::ICR_SOURCE_BIT | flags::ICR_SERIAL)
+            .unwrap();
+        cia.write(registers::TA_HI, 0x00).unwrap();
+        cia.write(registers::TA_LO, 0x00).unwrap();
+        cia.write(registers::CRA, LOAD | START | flags::CRA_SPMODE)
+            .unwrap();
+        wait_for_timers(&mut cia);
+        cia.write(registers::SDR, 0x12).unwrap();
+        cia.write(registers::SDR, 0x34).unwrap();
+        assert_eq!(cia.read(registers::SDR).unwrap(), 0x34);
+
+        // Timer A underflows on every tick, so each byte takes 16 ticks.
+        for _ in 0..15 {
+            assert_eq!(cia.tick(), false);
+        }
+        assert_eq!(cia.tick(), true);
+        assert_eq!(
+            cia.read(registers::ICR).unwrap(),
+            flags::ICR_TRIGGERED | flags::ICR_SERIAL | flags::ICR_TIMER_A
+        );
+        for _ in 0..15 {
+            cia.tick();
+        }
+        assert_eq!(cia.read(registers::ICR).unwrap() & flags::ICR_SERIAL, 0);
+        cia.tick();
+        assert_ne!(cia.read(registers::ICR).unwrap() & flags::ICR_SERIAL, 0);
+        for _ in 0..32 {
+            cia.tick();
+        }
+        assert_eq!(cia.read(registers::ICR).unwrap() & flags::ICR_SERIAL, 0);
+    }
+
+    #[test]
+    fn forced_load() {
+        use crate::timer::flags::*;
+
+        let mut cia = Cia::new();
+        cia.write(registers::TA_LO, 0x10).unwrap();
+        cia.write(registers::TA_HI, 0x00).unwrap();
+        cia.write(registers::CRA, LOAD | START).unwrap();
+        // The timer starts counting two cycles after being started.
+        for _ in 0..5 {
+            cia.tick();
+        }
+        assert_eq!(cia.read(registers::TA_LO).unwrap(), 0x0D);
+
+        // Changing the latch of a running timer doesn't affect the counter
+        // until it's loaded explicitly.
+        cia.write(registers::TA_LO, 0x20).unwrap();
+        cia.write(registers::TA_HI, 0x00).unwrap();
+        cia.tick();
+        assert_eq!(cia.read(registers::TA_LO).unwrap(), 0x0C);
+
+        // The latch is transferred to the counter in the cycle after the
+        // write, and the counter doesn't count in that cycle.
+        cia.write(registers::CRA, LOAD | START).unwrap();
+        assert_eq!(cia.read(registers::TA_LO).unwrap(), 0x0C);
+        cia.tick();
+        assert_eq!(cia.read(registers::TA_LO).unwrap(), 0x0B);
+        cia.tick();
+        assert_eq!(cia.read(registers::TA_LO).unwrap(), 0x20);
+        cia.tick();
+        assert_eq!(cia.read(registers::TA_LO).unwrap(), 0x1F);
+        assert_eq!(cia.read(registers::CRA).unwrap(), START);
+    }
+
+    #[test]
+    fn timer_b_counts_timer_a_underflows() {
+        use crate::timer::flags::*;
+
+        let mut cia = Cia::new();
+        cia.write(registers::ICR, flags::ICR_SOURCE_BIT | flags::ICR_TIMER_B)
+            .unwrap();
+        // Timer A underflows every 3 cycles.
+        cia.write(registers::TA_LO, 0x02).unwrap();
+        cia.write(registers::TA_HI, 0x00).unwrap();
+        // Timer B underflows every 2 underflows of timer A.
+        cia.write(registers::TB_LO, 0x01).unwrap();
+        cia.write(registers::TB_HI, 0x00).unwrap();
+        cia.write(registers::CRB, LOAD | START | INMODE_TIMER_A)
+            .unwrap();
+        cia.write(registers::CRA, LOAD | START).unwrap();
+        wait_for_timers(&mut cia);
+
+        for _ in 0..5 {
+            assert_eq!(cia.tick(), false);
+        }
+        assert_eq!(cia.read(registers::TB_LO).unwrap(), 0);
+        assert_eq!(cia.read(registers::ICR).unwrap(), flags::ICR_TIMER_A);
+        assert_eq!(cia.tick(), true);
+        assert_eq!(
+            cia.read(registers::ICR).unwrap(),
+            flags::ICR_TRIGGERED | flags::ICR_TIMER_A | flags::ICR_TIMER_B
+        );
+        assert_eq!(cia.read(registers::TB_LO).unwrap(), 1);
+        assert_eq!(cia.read(registers::CRB).unwrap(), START | INMODE_TIMER_A);
+    }
+
+    #[test]
+    fn timer_b_counts_timer_a_underflows_while_cnt_is_high() {
+        use crate::timer::flags::*;
+
+        let mut cia = Cia::new();
+        cia.write(registers::TA_LO, 0x00).unwrap();
+        cia.write(registers::TA_HI, 0x00).unwrap();
+        cia.write(registers::TB_LO, 0x10).unwrap();
+        cia.write(registers::TB_HI, 0x00).unwrap();
+        cia.write(registers::CRB, LOAD | START | INMODE_TIMER_A_WITH_CNT)
+            .unwrap();
+        cia.write(registers::CRA, LOAD | START).unwrap();
+        wait_for_timers(&mut cia);
+
+        // Timer A underflows on every tick.
+        cia.tick();
+        cia.tick();
+        assert_eq!(cia.read(registers::TB_LO).unwrap(), 0x0E);
+        cia.set_cnt(false);
+        cia.tick();
+        cia.tick();
+        assert_eq!(cia.read(registers::TB_LO).unwrap(), 0x0E);
+        cia.set_cnt(true);
+        cia.tick();
+        assert_eq!(cia.read(registers::TB_LO).unwrap(), 0x0D);
+    }
+
+    #[test]
+    fn timers_count_cnt_pulses() {
+        use crate::timer::flags::*;
+
+        let mut cia = Cia::new();
+        cia.write(registers::TA_LO, 0x01).unwrap();
+        cia.write(registers::TA_HI, 0x00).unwrap();
+        cia.write(registers::TB_LO, 0x10).unwrap();
+        cia.write(registers::TB_HI, 0x00).unwrap();
+        cia.write(registers::CRA, LOAD | START | INMODE_CNT)
+            .unwrap();
+        cia.write(registers::CRB, LOAD | START | INMODE_TIMER_A)
+            .unwrap();
+        wait_for_timers(&mut cia);
+
+        // The system clock doesn't matter.
+        cia.tick();
+        assert_eq!(cia.read(registers::TA_LO).unwrap(), 0x01);
+
+        // Only rising edges are counted.
+        cia.set_cnt(false);
+        cia.set_cnt(true);
+        cia.set_cnt(true);
+        assert_eq!(cia.read(registers::TA_LO).unwrap(), 0x00);
+        cia.set_cnt(false);
+        cia.set_cnt(true);
+        assert_eq!(cia.read(registers::TA_LO).unwrap(), 0x01);
+        assert_eq!(cia.read(registers::TB_LO).unwrap(), 0x0F);
+        assert_eq!(cia.read(registers::ICR).unwrap(), flags::ICR_TIMER_A);
+
+        cia.write(registers::CRB, LOAD | START | INMODE_CNT)
+            .unwrap();
+        wait_for_timers(&mut cia);
+        cia.tick();
+        assert_eq!(cia.read(registers::TB_LO).unwrap(), 0x10);
+        cia.set_cnt(false);
+        cia.set_cnt(true);
+        assert_eq!(cia.read(registers::TB_LO).unwrap(), 0x0F);
+    }
+
+    #[test]
+    fn timer_outputs_on_port_b() {
+        use crate::timer::flags::*;
+
+        let mut cia = Cia::new();
+        cia.write(registers::DDRB, 0b1111_1111).unwrap();
+        cia.write(registers::PRB, 0b0000_0000).unwrap();
+        cia.write(registers::TA_LO, 0x01).unwrap();
+        cia.write(registers::TA_HI, 0x00).unwrap();
+        cia.write(registers::TB_LO, 0x01).unwrap();
+        cia.write(registers::TB_HI, 0x00).unwrap();
+        cia.write(registers::CRA, LOAD | START | PBON | OUTMODE_PULSE)
+            .unwrap();
+        cia.write(registers::CRB, LOAD | START | PBON | OUTMODE_TOGGLE)
+            .unwrap();
+        wait_for_timers(&mut cia);
+
+        let mut port_b = vec![];
+        for _ in 0..4 {
+            cia.tick();
+            port_b.push(cia.read(registers::PRB).unwrap());
+        }
+        assert_eq!(port_b, [0b1000_0000, 0b0100_0000, 0b0000_0000, 0b1100_0000]);
+        assert_eq!(cia.port_output(PortName::B), 0b1100_0000);
+
+        // Without PBON, the port works as usual.
+        cia.write(registers::CRA, START).unwrap();
+        cia.write(registers::CRB, START).unwrap();
+        assert_eq!(cia.read(registers::PRB).unwrap(), 0b0000_0000);
+    }
 }
@@ -0,0 +1,184 @@
+use enum_map::Enum;
+use enum_map::EnumMap;
+use serde::Deserialize;
+
+/// Type of a device connected to one of the C64 control ports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ArgEnum)]
+pub enum ControllerType {
+    Joystick,
+    /// A pair of paddles.
+    #[clap(name = "paddle")]
+    Paddles,
+    /// A Commodore 1351 mouse in its proportional mode.
+    Mouse,
+}
+
+/// Analog inputs of a control port, read through the SID POTX and POTY
+/// registers.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pot {
+    X,
+    Y,
+}
+
+impl Pot {
+    /// Returns a mask of CIA port lines used by a paddle button. The paddle
+    /// connected to POTX uses the joystick left line, and the one connected to
+    /// POTY uses the right line.
+    fn paddle_button_line_mask(&self) -> u8 {
+        match *self {
+            Self::X => 1 << 2,
+            Self::Y => 1 << 3,
+        }
+    }
+}
+
+/// A pair of paddles connected to a single control port. Each paddle is a
+/// potentiometer connected to one of the analog inputs, and a button that pulls
+/// one of the joystick lines low.
+#[derive(Debug, Clone)]
+pub struct Paddles {
+    positions: EnumMap<Pot, u8>,
+    buttons: EnumMap<Pot, bool>,
+}
+
+impl Paddles {
+    /// Creates a pair of centered paddles.
+    pub fn new() -> Self {
+        Self {
+            positions: EnumMap::from_array([0x80; 2]),
+            buttons: EnumMap::default(),
+        }
+    }
+
+    pub fn position(&self, pot: Pot) -> u8 {
+        self.positions[pot]
+    }
+
+    /// Sets the position of a paddle. 0 means turned all the way clockwise,
+    /// 255 means all the way counterclockwise, which corresponds to the
+    /// values reported by the SID.
+    pub fn set_position(&mut self, pot: Pot, position: u8) {
+        self.positions[pot] = position;
+    }
+
+    pub fn set_button_state(&mut self, pot: Pot, pressed: bool) {
+        self.buttons[pot] = pressed;
+    }
+
+    /// Returns the values reported by the SID potentiometer registers.
+    pub fn pot_values(&self) -> EnumMap<Pot, u8> {
+        self.positions
+    }
+
+    /// Returns a mask of CIA port lines pulled down by the paddle buttons.
+    pub fn pressed_lines(&self) -> u8 {
+        self.buttons
+            .iter()
+            .filter(|(_, pressed)| **pressed)
+            .fold(0, |lines, (pot, _)| lines | pot.paddle_button_line_mask())
+    }
+}
+
+impl Default for Paddles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Buttons of a 1351 mouse.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+}
+
+/// A Commodore 1351 mouse in its proportional mode. The mouse keeps track of
+/// its position modulo 64 and reports it through the analog inputs: bits 1-6
+/// of POTX and POTY contain the horizontal and vertical position, respectively.
+/// Programs compute the movement by comparing consecutive readings. The left
+/// button pulls down the joystick fire line, and the right one pulls down the
+/// joystick up line.
+#[derive(Debug, Clone, Default)]
+pub struct Mouse {
+    x: u8,
+    y: u8,
+    buttons: EnumMap<MouseButton, bool>,
+}
+
+impl Mouse {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the mouse by a given number of units. Positive `dx` moves it to
+    /// the right, and positive `dy` moves it up.
+    pub fn move_by(&mut self, dx: i32, dy: i32) {
+        self.x = self.x.wrapping_add(dx as u8) & 0x3F;
+        self.y = self.y.wrapping_add(dy as u8) & 0x3F;
+    }
+
+    pub fn set_button_state(&mut self, button: MouseButton, pressed: bool) {
+        self.buttons[button] = pressed;
+    }
+
+    /// Returns the values reported by the SID potentiometer registers.
+    pub fn pot_values(&self) -> EnumMap<Pot, u8> {
+        EnumMap::from_array([self.x << 1, self.y << 1])
+    }
+
+    /// Returns a mask of CIA port lines pulled down by the mouse buttons.
+    pub fn pressed_lines(&self) -> u8 {
+        let mut lines = 0;
+        if self.buttons[MouseButton::Left] {
+            lines |= 1 << 4;
+        }
+        if self.buttons[MouseButton::Right] {
+            lines |= 1;
+        }
+        return lines;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paddles() {
+        let mut paddles = Paddles::new();
+        assert_eq!(paddles.pot_values().as_slice(), &[0x80, 0x80]);
+        assert_eq!(paddles.pressed_lines(), 0);
+
+        paddles.set_position(Pot::X, 0x12);
+        paddles.set_position(Pot::Y, 0xEF);
+        paddles.set_button_state(Pot::Y, true);
+        assert_eq!(paddles.pot_values().as_slice(), &[0x12, 0xEF]);
+        assert_eq!(paddles.pressed_lines(), 0b0000_1000);
+
+        paddles.set_button_state(Pot::X, true);
+        assert_eq!(paddles.pressed_lines(), 0b0000_1100);
+    }
+
+    #[test]
+    fn mouse_position() {
+        let mut mouse = Mouse::new();
+        assert_eq!(mouse.pot_values().as_slice(), &[0, 0]);
+
+        mouse.move_by(5, 10);
+        assert_eq!(mouse.pot_values().as_slice(), &[10, 20]);
+        mouse.move_by(-6, 60);
+        assert_eq!(mouse.pot_values().as_slice(), &[0x7E, 0x0C]);
+    }
+
+    #[test]
+    fn mouse_buttons() {
+        let mut mouse = Mouse::new();
+        mouse.set_button_state(MouseButton::Left, true);
+        assert_eq!(mouse.pressed_lines(), 0b0001_0000);
+        mouse.set_button_state(MouseButton::Right, true);
+        assert_eq!(mouse.pressed_lines(), 0b0001_0001);
+        mouse.set_button_state(MouseButton::Left, false);
+        assert_eq!(mouse.pressed_lines(), 0b0000_0001);
+    }
+}
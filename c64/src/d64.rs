@@ -0,0 +1,551 @@
+use std::io;
+
+/// A 1541 floppy disk image in the D64 format. The image is a sequence of
+/// 256-byte sectors, ordered by track and sector number. Files are stored as
+/// chains of sectors; the directory and the block availability map (BAM) live
+/// on track 18.
+pub struct DiskImage {
+    bytes: Vec<u8>,
+}
+
+/// Indicates that there is no space left for a new file or its directory
+/// entry.
+#[derive(Debug, PartialEq)]
+pub struct DiskFull;
+
+const SECTOR_SIZE: usize = 256;
+const DIRECTORY_TRACK: u8 = 18;
+const TRACKS: u8 = 35;
+const DIRECTORY_ENTRY_SIZE: usize = 32;
+const FILE_NAME_LENGTH: usize = 16;
+/// Used to pad file and disk names.
+const SHIFTED_SPACE: u8 = 0xA0;
+
+mod bam {
+    pub const DISK_NAME: usize = 0x90;
+    /// Disk ID, followed by a shifted space and the DOS type.
+    pub const DISK_ID: usize = 0xA2;
+    pub const DISK_ID_LENGTH: usize = 5;
+}
+
+mod entry {
+    pub const FILE_TYPE: usize = 0x02;
+    pub const FIRST_SECTOR: usize = 0x03;
+    pub const FILE_NAME: usize = 0x05;
+    pub const SIZE: usize = 0x1E;
+}
+
+mod file_type {
+    pub const PRG: u8 = 0x02;
+    pub const MASK: u8 = 0x07;
+    pub const CLOSED: u8 = 1 << 7;
+    pub const LOCKED: u8 = 1 << 6;
+}
+
+const FILE_TYPE_NAMES: [&str; 5] = ["DEL", "SEQ", "PRG", "USR", "REL"];
+
+/// A track and sector number pair.
+type Location = (u8, u8);
+
+impl DiskImage {
+    /// Returns the image contents, ready to be stored in a D64 file.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the contents of the first file whose name matches a given
+    /// pattern, or `None` if there is no such file. See [`matches_pattern`] for
+    /// the pattern syntax.
+    pub fn read_file(&self, pattern: &[u8]) -> Option<Vec<u8>> {
+        let (_, dir_entry) = self.find_file(pattern)?;
+        let mut data = vec![];
+        for (track, sector) in self.sector_chain(self.first_sector(dir_entry)) {
+            let sector = self.sector(track, sector);
+            let end = if sector[0] == 0 {
+                usize::from(sector[1]) + 1
+            } else {
+                SECTOR_SIZE
+            };
+            data.extend_from_slice(&sector[2..end.max(2)]);
+        }
+        return Some(data);
+    }
+
+    /// Returns `true` if there is a file with a given name.
+    pub fn file_exists(&self, name: &[u8]) -> bool {
+        self.find_file(name).is_some()
+    }
+
+    /// Stores a program file with a given name, replacing a file that already
+    /// has this name.
+    pub fn write_file(&mut self, name: &[u8], data: &[u8]) -> Result<(), DiskFull> {
+        let chunks: Vec<&[u8]> = if data.is_empty() {
+            vec![data]
+        } else {
+            data.chunks(SECTOR_SIZE - 2).collect()
+        };
+        // Nothing may be changed before we know that the file fits, so the
+        // file being replaced is only deleted once the space is reserved. Its
+        // sectors and directory entry are reused.
+        let old_file = self
+            .find_file(name)
+            .map(|(location, dir_entry)| (location, self.first_sector(dir_entry)));
+        let old_sectors: Vec<Location> = match old_file {
+            Some((_, first_sector)) => self.sector_chain(first_sector).collect(),
+            None => vec![],
+        };
+        let free_sectors: Vec<Location> = self
+            .all_sectors()
+            .filter(|(track, _)| *track != DIRECTORY_TRACK)
+            .filter(|location| self.is_free(*location) || old_sectors.contains(location))
+            .take(chunks.len())
+            .collect();
+        if free_sectors.len() < chunks.len() {
+            return Err(DiskFull);
+        }
+        let entry_location = match old_file {
+            Some((location, _)) => location,
+            None => self.free_directory_entry().ok_or(DiskFull)?,
+        };
+        for location in old_sectors {
+            self.free(location);
+        }
+
+        for (i, (chunk, &location)) in chunks.iter().zip(&free_sectors).enumerate() {
+            self.allocate(location);
+            let sector = self.sector_mut(location.0, location.1);
+            sector.fill(0);
+            sector[..2].copy_from_slice(&match free_sectors.get(i + 1) {
+                Some(&(next_track, next_sector)) => [next_track, next_sector],
+                // The last sector holds the index of its last used byte.
+                None => [0, (chunk.len() + 1) as u8],
+            });
+            sector[2..2 + chunk.len()].copy_from_slice(chunk);
+        }
+
+        let dir_entry = self.directory_entry_mut(entry_location);
+        dir_entry[entry::FILE_TYPE] = file_type::PRG | file_type::CLOSED;
+        dir_entry[entry::FIRST_SECTOR..entry::FIRST_SECTOR + 2]
+            .copy_from_slice(&[free_sectors[0].0, free_sectors[0].1]);
+        dir_entry[entry::FILE_NAME..entry::FILE_NAME + FILE_NAME_LENGTH]
+            .copy_from_slice(&padded_name(name));
+        dir_entry[entry::FILE_NAME + FILE_NAME_LENGTH..entry::SIZE].fill(0);
+        dir_entry[entry::SIZE..entry::SIZE + 2]
+            .copy_from_slice(&(chunks.len() as u16).to_le_bytes());
+        return Ok(());
+    }
+
+    /// Generates a directory listing in the form of a BASIC program, just like
+    /// the one that the 1541 drive returns when loading `$`.
+    pub fn directory_listing(&self) -> Vec<u8> {
+        const LOAD_ADDRESS: u16 = 0x0401;
+        const DUMMY_LINK: u16 = 0x0101;
+        const REVERSE_ON: u8 = 0x12;
+
+        let mut lines: Vec<(u16, Vec<u8>)> = vec![];
+        let bam_sector = self.sector(DIRECTORY_TRACK, 0);
+        let mut header = vec![REVERSE_ON, b'"'];
+        header.extend_from_slice(&bam_sector[bam::DISK_NAME..bam::DISK_NAME + FILE_NAME_LENGTH]);
+        header.extend_from_slice(b"\" ");
+        header.extend_from_slice(&bam_sector[bam::DISK_ID..bam::DISK_ID + bam::DISK_ID_LENGTH]);
+        lines.push((0, header));
+
+        for location in self.directory_entries() {
+            let dir_entry = self.directory_entry(location);
+            let type_byte = dir_entry[entry::FILE_TYPE];
+            if type_byte == 0 {
+                continue;
+            }
+            let blocks = u16::from_le_bytes([dir_entry[entry::SIZE], dir_entry[entry::SIZE + 1]]);
+            let name = file_name(dir_entry);
+            // Align file names, assuming that the block count has at most 3
+            // digits.
+            let digits = blocks.to_string().len();
+            let mut line = vec![b' '; digits.max(4) - digits];
+            line.push(b'"');
+            line.extend_from_slice(name);
+            line.push(b'"');
+            line.resize(line.len() + FILE_NAME_LENGTH - name.len(), b' ');
+            line.push(if type_byte & file_type::CLOSED != 0 {
+                b' '
+            } else {
+                b'*'
+            });
+            line.extend_from_slice(
+                FILE_TYPE_NAMES
+                    .get(usize::from(type_byte & file_type::MASK))
+                    .unwrap_or(&"???")
+                    .as_bytes(),
+            );
+            if type_byte & file_type::LOCKED != 0 {
+                line.push(b'<');
+            }
+            lines.push((blocks, line));
+        }
+        lines.push((self.free_blocks(), b"BLOCKS FREE.".to_vec()));
+
+        let mut program = LOAD_ADDRESS.to_le_bytes().to_vec();
+        for (line_number, line) in lines {
+            program.extend_from_slice(&DUMMY_LINK.to_le_bytes());
+            program.extend_from_slice(&line_number.to_le_bytes());
+            program.extend_from_slice(&line);
+            program.push(0);
+        }
+        program.extend_from_slice(&[0, 0]);
+        return program;
+    }
+
+    /// Returns the number of free blocks outside the directory track.
+    pub fn free_blocks(&self) -> u16 {
+        let bam_sector = self.sector(DIRECTORY_TRACK, 0);
+        return (1..=TRACKS)
+            .filter(|track| *track != DIRECTORY_TRACK)
+            .map(|track| u16::from(bam_sector[4 * usize::from(track)]))
+            .sum();
+    }
+
+    fn find_file(&self, pattern: &[u8]) -> Option<(Location, &[u8])> {
+        return self
+            .directory_entries()
+            .map(|location| (location, self.directory_entry(location)))
+            .find(|(_, dir_entry)| {
+                dir_entry[entry::FILE_TYPE] & file_type::MASK != 0
+                    && matches_pattern(file_name(dir_entry), pattern)
+            });
+    }
+
+    /// Returns a location of an unused directory entry. If all directory
+    /// sectors are full, allocates a new one on the directory track.
+    fn free_directory_entry(&mut self) -> Option<Location> {
+        if let Some(location) = self
+            .directory_entries()
+            .find(|&location| self.directory_entry(location)[entry::FILE_TYPE] == 0)
+        {
+            return Some(location);
+        }
+        let last_sector = self.directory_sectors().last().unwrap();
+        let new_sector = (0..sectors_per_track(DIRECTORY_TRACK))
+            .map(|sector| (DIRECTORY_TRACK, sector))
+            .find(|&location| self.is_free(location))?;
+        self.allocate(new_sector);
+        self.sector_mut(last_sector.0, last_sector.1)[..2]
+            .copy_from_slice(&[new_sector.0, new_sector.1]);
+        let sector = self.sector_mut(new_sector.0, new_sector.1);
+        sector.fill(0);
+        sector[1] = 0xFF;
+        return Some((new_sector.0, new_sector.1 * 8));
+    }
+
+    fn first_sector(&self, dir_entry: &[u8]) -> Location {
+        (
+            dir_entry[entry::FIRST_SECTOR],
+            dir_entry[entry::FIRST_SECTOR + 1],
+        )
+    }
+
+    /// Iterates over sectors that form a chain starting at a given location.
+    /// Stops at an invalid link, so that a corrupted image can't cause an
+    /// infinite loop.
+    fn sector_chain(&self, start: Location) -> impl Iterator<Item = Location> + '_ {
+        let mut next = Some(start).filter(|&location| self.is_valid(location));
+        let mut remaining = self.all_sectors().count();
+        return std::iter::from_fn(move || {
+            let current = next?;
+            remaining = remaining.checked_sub(1)?;
+            let sector = self.sector(current.0, current.1);
+            next = Some((sector[0], sector[1])).filter(|&location| self.is_valid(location));
+            return Some(current);
+        });
+    }
+
+    fn directory_sectors(&self) -> impl Iterator<Item = Location> + '_ {
+        let bam_sector = self.sector(DIRECTORY_TRACK, 0);
+        return self.sector_chain((bam_sector[0], bam_sector[1]));
+    }
+
+    /// Iterates over directory entries. Each entry is identified by its
+    /// directory track sector and the entry index multiplied by 8 (so that
+    /// the location fits in a [`Location`]).
+    fn directory_entries(&self) -> impl Iterator<Item = Location> + '_ {
+        self.directory_sectors()
+            .flat_map(|(track, sector)| (0..8).map(move |i| (track, sector * 8 + i)))
+    }
+
+    fn directory_entry(&self, (track, index): Location) -> &[u8] {
+        let offset = usize::from(index % 8) * DIRECTORY_ENTRY_SIZE;
+        &self.sector(track, index / 8)[offset..offset + DIRECTORY_ENTRY_SIZE]
+    }
+
+    fn directory_entry_mut(&mut self, (track, index): Location) -> &mut [u8] {
+        let offset = usize::from(index % 8) * DIRECTORY_ENTRY_SIZE;
+        &mut self.sector_mut(track, index / 8)[offset..offset + DIRECTORY_ENTRY_SIZE]
+    }
+
+    fn all_sectors(&self) -> impl Iterator<Item = Location> {
+        (1..=TRACKS)
+            .flat_map(|track| (0..sectors_per_track(track)).map(move |sector| (track, sector)))
+    }
+
+    fn is_valid(&self, (track, sector): Location) -> bool {
+        (1..=TRACKS).contains(&track) && sector < sectors_per_track(track)
+    }
+
+    fn is_free(&self, (track, sector): Location) -> bool {
+        let bam_sector = self.sector(DIRECTORY_TRACK, 0);
+        let bitmap = 4 * usize::from(track) + 1 + usize::from(sector / 8);
+        return bam_sector[bitmap] & (1 << (sector % 8)) != 0;
+    }
+
+    fn allocate(&mut self, location: Location) {
+        self.set_free(location, false);
+    }
+
+    fn free(&mut self, location: Location) {
+        self.set_free(location, true);
+    }
+
+    fn set_free(&mut self, (track, sector): Location, free: bool) {
+        if self.is_free((track, sector)) == free {
+            return;
+        }
+        let bam_sector = self.sector_mut(DIRECTORY_TRACK, 0);
+        let free_count = 4 * usize::from(track);
+        let bitmap = free_count + 1 + usize::from(sector / 8);
+        bam_sector[bitmap] ^= 1 << (sector % 8);
+        if free {
+            bam_sector[free_count] += 1;
+        } else {
+            bam_sector[free_count] -= 1;
+        }
+    }
+
+    fn sector(&self, track: u8, sector: u8) -> &[u8] {
+        let offset = sector_offset(track, sector);
+        &self.bytes[offset..offset + SECTOR_SIZE]
+    }
+
+    fn sector_mut(&mut self, track: u8, sector: u8) -> &mut [u8] {
+        let offset = sector_offset(track, sector);
+        &mut self.bytes[offset..offset + SECTOR_SIZE]
+    }
+
+    /// Creates an empty, formatted disk image.
+    #[cfg(test)]
+    pub fn formatted(name: &[u8], id: &[u8; 2]) -> Self {
+        let mut image = DiskImage {
+            bytes: vec![0; D64_SIZE],
+        };
+        let all_sectors: Vec<Location> = image.all_sectors().collect();
+        let bam_sector = image.sector_mut(DIRECTORY_TRACK, 0);
+        bam_sector[..4].copy_from_slice(&[DIRECTORY_TRACK, 1, b'A', 0]);
+        bam_sector[bam::DISK_NAME..bam::DISK_NAME + FILE_NAME_LENGTH]
+            .copy_from_slice(&padded_name(name));
+        bam_sector[bam::DISK_ID - 2..bam::DISK_ID].fill(SHIFTED_SPACE);
+        bam_sector[bam::DISK_ID..bam::DISK_ID + bam::DISK_ID_LENGTH].copy_from_slice(&[
+            id[0],
+            id[1],
+            SHIFTED_SPACE,
+            b'2',
+            b'A',
+        ]);
+        for location in all_sectors {
+            image.set_free(location, true);
+        }
+        image.allocate((DIRECTORY_TRACK, 0));
+        image.allocate((DIRECTORY_TRACK, 1));
+        image.sector_mut(DIRECTORY_TRACK, 1)[1] = 0xFF;
+        return image;
+    }
+}
+
+/// Size of a standard 35-track image without error information.
+const D64_SIZE: usize = 174_848;
+/// Size of a 35-track image with a trailing error byte for each sector.
+const D64_WITH_ERRORS_SIZE: usize = 175_531;
+
+fn sectors_per_track(track: u8) -> u8 {
+    match track {
+        1..=17 => 21,
+        18..=24 => 19,
+        25..=30 => 18,
+        _ => 17,
+    }
+}
+
+fn sector_offset(track: u8, sector: u8) -> usize {
+    let preceding_sectors: usize = (1..track)
+        .map(|track| usize::from(sectors_per_track(track)))
+        .sum();
+    return (preceding_sectors + usize::from(sector)) * SECTOR_SIZE;
+}
+
+fn padded_name(name: &[u8]) -> [u8; FILE_NAME_LENGTH] {
+    let mut padded = [SHIFTED_SPACE; FILE_NAME_LENGTH];
+    let length = name.len().min(FILE_NAME_LENGTH);
+    padded[..length].copy_from_slice(&name[..length]);
+    return padded;
+}
+
+/// Extracts a file name from a directory entry and strips the padding.
+fn file_name(dir_entry: &[u8]) -> &[u8] {
+    let name = &dir_entry[entry::FILE_NAME..entry::FILE_NAME + FILE_NAME_LENGTH];
+    let length = name
+        .iter()
+        .rposition(|c| *c != SHIFTED_SPACE)
+        .map_or(0, |i| i + 1);
+    return &name[..length];
+}
+
+/// Checks if a file name matches a given pattern. A `?` character in the
+/// pattern matches any character, and a `*` matches the rest of the name.
+pub fn matches_pattern(name: &[u8], pattern: &[u8]) -> bool {
+    let mut name = name.iter();
+    for pattern_char in pattern {
+        match (pattern_char, name.next()) {
+            (b'*', _) => return true,
+            (b'?', Some(_)) => {}
+            (p, Some(n)) if p == n => {}
+            _ => return false,
+        }
+    }
+    return name.next().is_none();
+}
+
+/// Reads a D64 disk image. Only 35-track images are supported; the error
+/// information, if present, is ignored.
+pub fn read_d64_file(mut reader: impl io::Read) -> Result<DiskImage, D64FileError> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    match bytes.len() {
+        D64_SIZE => {}
+        D64_WITH_ERRORS_SIZE => bytes.truncate(D64_SIZE),
+        other => return Err(D64FileError::UnsupportedSize(other)),
+    }
+    return Ok(DiskImage { bytes });
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum D64FileError {
+    #[error("I/O error: {0}")]
+    IoError(#[from] io::Error),
+
+    #[error("Unsupported D64 file size: {0}")]
+    UnsupportedSize(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::assert_matches::assert_matches;
+
+    #[test]
+    fn reads_d64_files() {
+        let image = DiskImage::formatted(b"TEST", b"01");
+        let image = read_d64_file(image.bytes()).unwrap();
+        assert_eq!(image.free_blocks(), 664);
+
+        let mut bytes = image.bytes().to_vec();
+        bytes.resize(D64_WITH_ERRORS_SIZE, 0);
+        let image = read_d64_file(&bytes[..]).unwrap();
+        assert_eq!(image.bytes().len(), D64_SIZE);
+
+        assert_matches!(
+            read_d64_file(&bytes[..1000]).err(),
+            Some(D64FileError::UnsupportedSize(1000))
+        );
+    }
+
+    #[test]
+    fn writes_and_reads_files() {
+        let mut image = DiskImage::formatted(b"TEST", b"01");
+        let long_file: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        image.write_file(b"EMPTY", &[]).unwrap();
+        image.write_file(b"SHORT", &[1, 2, 3]).unwrap();
+        image.write_file(b"LONG", &long_file).unwrap();
+        image.write_file(b"EXACT", &[7; 254]).unwrap();
+        assert_eq!(image.free_blocks(), 664 - 1 - 1 - 4 - 1);
+
+        assert_eq!(image.read_file(b"EMPTY"), Some(vec![]));
+        assert_eq!(image.read_file(b"SHORT"), Some(vec![1, 2, 3]));
+        assert_eq!(image.read_file(b"LONG"), Some(long_file.clone()));
+        assert_eq!(image.read_file(b"EXACT"), Some(vec![7; 254]));
+        assert_eq!(image.read_file(b"SHOR"), None);
+        assert_eq!(image.read_file(b"SHOR?"), Some(vec![1, 2, 3]));
+        assert_eq!(image.read_file(b"L*"), Some(long_file));
+        assert_eq!(image.read_file(b"*"), Some(vec![]));
+
+        image.write_file(b"LONG", &[4, 5]).unwrap();
+        assert_eq!(image.read_file(b"LONG"), Some(vec![4, 5]));
+        assert_eq!(image.free_blocks(), 664 - 1 - 1 - 1 - 1);
+    }
+
+    #[test]
+    fn extends_directory() {
+        let mut image = DiskImage::formatted(b"TEST", b"01");
+        for i in 0..20 {
+            image
+                .write_file(format!("FILE{}", i).as_bytes(), &[i])
+                .unwrap();
+        }
+        for i in 0..20 {
+            assert_eq!(
+                image.read_file(format!("FILE{}", i).as_bytes()),
+                Some(vec![i])
+            );
+        }
+        assert_eq!(image.free_blocks(), 664 - 20);
+    }
+
+    #[test]
+    fn disk_full() {
+        let mut image = DiskImage::formatted(b"TEST", b"01");
+        assert_eq!(image.write_file(b"HUGE", &[0; 665 * 254]), Err(DiskFull));
+        assert_eq!(image.free_blocks(), 664);
+        image.write_file(b"HUGE", &[0; 664 * 254]).unwrap();
+        assert_eq!(image.free_blocks(), 0);
+        assert_eq!(image.write_file(b"TINY", &[]), Err(DiskFull));
+
+        // Replacing a file reuses its sectors.
+        image.write_file(b"HUGE", &[1, 2, 3]).unwrap();
+        assert_eq!(image.read_file(b"HUGE"), Some(vec![1, 2, 3]));
+        assert_eq!(image.free_blocks(), 663);
+    }
+
+    #[test]
+    fn directory_full() {
+        let mut image = DiskImage::formatted(b"TEST", b"01");
+        for i in 0..144 {
+            image
+                .write_file(format!("FILE{}", i).as_bytes(), &[i])
+                .unwrap();
+        }
+        assert_eq!(image.write_file(b"ONE MORE", &[1]), Err(DiskFull));
+        assert_eq!(image.free_blocks(), 664 - 144);
+
+        // Replacing a file doesn't need a new directory entry.
+        image.write_file(b"FILE0", &[1, 2]).unwrap();
+        assert_eq!(image.read_file(b"FILE0"), Some(vec![1, 2]));
+        assert_eq!(image.read_file(b"FILE143"), Some(vec![143]));
+        assert_eq!(image.free_blocks(), 664 - 144);
+    }
+
+    #[test]
+    fn lists_directory() {
+        let mut image = DiskImage::formatted(b"MY DISK", b"42");
+        image.write_file(b"GAME", &[0; 2000]).unwrap();
+        let listing = image.directory_listing();
+
+        let mut expected = vec![0x01, 0x04];
+        expected.extend_from_slice(&[0x01, 0x01, 0, 0, 0x12]);
+        expected.extend_from_slice(b"\"MY DISK");
+        expected.extend_from_slice(&[SHIFTED_SPACE; 9]);
+        expected.extend_from_slice(b"\" 42\xA02A\0");
+        expected.extend_from_slice(&[0x01, 0x01, 8, 0]);
+        expected.extend_from_slice(b"   \"GAME\"");
+        expected.extend_from_slice(&[b' '; 12 + 1]);
+        expected.extend_from_slice(b"PRG\0");
+        expected.extend_from_slice(&[0x01, 0x01, 0x90, 0x02]);
+        expected.extend_from_slice(b"BLOCKS FREE.\0\0\0");
+        assert_eq!(listing, expected);
+    }
+}
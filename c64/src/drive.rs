@@ -0,0 +1,327 @@
+use crate::d64::DiskFull;
+use crate::d64::DiskImage;
+use crate::iec::IecDevice;
+use std::fs;
+use std::path::PathBuf;
+use std::vec;
+
+/// Device number that a 1541 drive uses by default.
+pub const DEFAULT_DEVICE_NUMBER: u8 = 8;
+
+/// A high-level emulation of a Commodore 1541 disk drive. Instead of running
+/// the drive firmware, it directly implements a subset of the disk operating
+/// system: loading and saving files, listing the directory, and reading the
+/// status from the command channel. Disk commands sent through the command
+/// channel are ignored.
+pub struct Drive {
+    image: DiskImage,
+    /// If set, the image is written back to this file after each change.
+    image_path: Option<PathBuf>,
+    channels: [Option<Channel>; 16],
+    /// Channel selected by the last secondary address.
+    current_channel: Option<u8>,
+    /// A channel that is being opened, along with the file name that is being
+    /// received.
+    opening: Option<(u8, Vec<u8>)>,
+    status: Status,
+}
+
+enum Channel {
+    Read(vec::IntoIter<u8>),
+    Write { name: Vec<u8>, data: Vec<u8> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Ok,
+    WriteError,
+    SyntaxError,
+    FileNotFound,
+    FileExists,
+    DiskFull,
+    DosVersion,
+}
+
+impl Status {
+    fn message(&self) -> &'static str {
+        match self {
+            Status::Ok => "00, OK,00,00\r",
+            Status::WriteError => "25,WRITE ERROR,00,00\r",
+            Status::SyntaxError => "33,SYNTAX ERROR,00,00\r",
+            Status::FileNotFound => "62,FILE NOT FOUND,00,00\r",
+            Status::FileExists => "63,FILE EXISTS,00,00\r",
+            Status::DiskFull => "72,DISK FULL,00,00\r",
+            Status::DosVersion => "73,CBM DOS V2.6 1541,00,00\r",
+        }
+    }
+}
+
+const COMMAND_CHANNEL: u8 = 15;
+/// The KERNAL uses this channel for saving programs.
+const SAVE_CHANNEL: u8 = 1;
+
+mod secondary {
+    pub const OPEN: u8 = 0xF0;
+    pub const CLOSE: u8 = 0xE0;
+    pub const COMMAND_MASK: u8 = 0xF0;
+    pub const CHANNEL_MASK: u8 = 0x0F;
+}
+
+impl Drive {
+    pub fn new(image: DiskImage) -> Self {
+        Self {
+            image,
+            image_path: None,
+            channels: Default::default(),
+            current_channel: None,
+            opening: None,
+            status: Status::DosVersion,
+        }
+    }
+
+    /// Makes the drive store the disk image in a given file whenever a file is
+    /// saved.
+    pub fn with_image_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.image_path = Some(path.into());
+        return self;
+    }
+
+    fn open(&mut self, channel: u8, name: &[u8]) {
+        if channel == COMMAND_CHANNEL {
+            self.status = Status::Ok;
+            return;
+        }
+        let file_name = FileName::parse(name);
+        self.status = if file_name.write || channel == SAVE_CHANNEL {
+            self.open_for_writing(channel, file_name)
+        } else {
+            self.open_for_reading(channel, file_name.pattern)
+        };
+    }
+
+    fn open_for_reading(&mut self, channel: u8, pattern: &[u8]) -> Status {
+        let data = if pattern == b"$" {
+            Some(self.image.directory_listing())
+        } else {
+            self.image.read_file(pattern)
+        };
+        return match data {
+            Some(data) => {
+                self.channels[channel as usize] = Some(Channel::Read(data.into_iter()));
+                Status::Ok
+            }
+            None => Status::FileNotFound,
+        };
+    }
+
+    fn open_for_writing(&mut self, channel: u8, file_name: FileName) -> Status {
+        if file_name.pattern.is_empty()
+            || file_name.pattern.contains(&b'*')
+            || file_name.pattern.contains(&b'?')
+        {
+            return Status::SyntaxError;
+        }
+        if !file_name.replace && self.image.file_exists(file_name.pattern) {
+            return Status::FileExists;
+        }
+        self.channels[channel as usize] = Some(Channel::Write {
+            name: file_name.pattern.to_vec(),
+            data: vec![],
+        });
+        return Status::Ok;
+    }
+
+    fn close(&mut self, channel: u8) {
+        if let Some(Channel::Write { name, data }) = self.channels[channel as usize].take() {
+            self.status = match self.image.write_file(&name, &data) {
+                Ok(()) => self.store_image(),
+                Err(DiskFull) => Status::DiskFull,
+            };
+        }
+    }
+
+    fn store_image(&self) -> Status {
+        match &self.image_path {
+            Some(path) => match fs::write(path, self.image.bytes()) {
+                Ok(()) => Status::Ok,
+                Err(_) => Status::WriteError,
+            },
+            None => Status::Ok,
+        }
+    }
+}
+
+impl IecDevice for Drive {
+    fn secondary_address(&mut self, command: u8) {
+        let channel = command & secondary::CHANNEL_MASK;
+        match command & secondary::COMMAND_MASK {
+            secondary::OPEN => self.opening = Some((channel, vec![])),
+            secondary::CLOSE => self.close(channel),
+            _ => self.current_channel = Some(channel),
+        }
+    }
+
+    fn receive(&mut self, byte: u8) {
+        if let Some((_, name)) = &mut self.opening {
+            name.push(byte);
+        } else if let Some(channel) = self.current_channel {
+            if let Some(Channel::Write { data, .. }) = &mut self.channels[channel as usize] {
+                data.push(byte);
+            }
+        }
+    }
+
+    fn unlisten(&mut self) {
+        if let Some((channel, name)) = self.opening.take() {
+            self.open(channel, &name);
+        }
+    }
+
+    fn send(&mut self) -> Option<(u8, bool)> {
+        let channel = self.current_channel?;
+        if channel == COMMAND_CHANNEL && self.channels[channel as usize].is_none() {
+            let message = self.status.message().as_bytes().to_vec();
+            self.channels[channel as usize] = Some(Channel::Read(message.into_iter()));
+            self.status = Status::Ok;
+        }
+        let (byte, last) = match &mut self.channels[channel as usize] {
+            Some(Channel::Read(data)) => (data.next()?, data.as_slice().is_empty()),
+            _ => return None,
+        };
+        if last && channel == COMMAND_CHANNEL {
+            // Let the next read fetch the current status.
+            self.channels[channel as usize] = None;
+        }
+        return Some((byte, last));
+    }
+}
+
+/// A file name, as given when opening a file, e.g. `@0:NAME,P,W`.
+struct FileName<'a> {
+    pattern: &'a [u8],
+    /// Whether to replace an existing file.
+    replace: bool,
+    write: bool,
+}
+
+impl<'a> FileName<'a> {
+    fn parse(name: &'a [u8]) -> Self {
+        let replace = name.first() == Some(&b'@');
+        let name = if replace { &name[1..] } else { name };
+        // Skip the drive number.
+        let name = match name.iter().position(|c| *c == b':') {
+            Some(colon) => &name[colon + 1..],
+            None => name,
+        };
+        let mut parts = name.split(|c| *c == b',');
+        let pattern = parts.next().unwrap();
+        let write = parts.any(|part| part == b"W");
+        return Self {
+            pattern,
+            replace,
+            write,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(drive: &mut Drive, channel: u8, name: &[u8]) {
+        drive.secondary_address(secondary::OPEN | channel);
+        for byte in name {
+            drive.receive(*byte);
+        }
+        drive.unlisten();
+    }
+
+    fn close(drive: &mut Drive, channel: u8) {
+        drive.secondary_address(secondary::CLOSE | channel);
+        drive.unlisten();
+    }
+
+    fn write(drive: &mut Drive, channel: u8, data: &[u8]) {
+        drive.secondary_address(0x60 | channel);
+        for byte in data {
+            drive.receive(*byte);
+        }
+        drive.unlisten();
+    }
+
+    fn read(drive: &mut Drive, channel: u8) -> Vec<u8> {
+        drive.secondary_address(0x60 | channel);
+        let mut data = vec![];
+        while let Some((byte, last)) = drive.send() {
+            data.push(byte);
+            if last {
+                break;
+            }
+        }
+        return data;
+    }
+
+    fn status(drive: &mut Drive) -> String {
+        String::from_utf8(read(drive, COMMAND_CHANNEL)).unwrap()
+    }
+
+    fn drive_with_files() -> Drive {
+        let mut image = DiskImage::formatted(b"TEST", b"01");
+        image.write_file(b"GAME", &[0x01, 0x08, 1, 2, 3]).unwrap();
+        image.write_file(b"DEMO", &[0x00, 0xC0, 4]).unwrap();
+        return Drive::new(image);
+    }
+
+    #[test]
+    fn loads_files() {
+        let mut drive = drive_with_files();
+        assert_eq!(status(&mut drive), "73,CBM DOS V2.6 1541,00,00\r");
+
+        open(&mut drive, 0, b"DEMO");
+        assert_eq!(read(&mut drive, 0), [0x00, 0xC0, 4]);
+        close(&mut drive, 0);
+        open(&mut drive, 0, b"0:G*");
+        assert_eq!(read(&mut drive, 0), [0x01, 0x08, 1, 2, 3]);
+        close(&mut drive, 0);
+        assert_eq!(status(&mut drive), "00, OK,00,00\r");
+
+        open(&mut drive, 0, b"$");
+        assert_eq!(read(&mut drive, 0), drive.image.directory_listing());
+        close(&mut drive, 0);
+    }
+
+    #[test]
+    fn file_not_found() {
+        let mut drive = drive_with_files();
+        open(&mut drive, 0, b"NOPE");
+        assert!(read(&mut drive, 0).is_empty());
+        close(&mut drive, 0);
+        assert_eq!(status(&mut drive), "62,FILE NOT FOUND,00,00\r");
+        assert_eq!(status(&mut drive), "00, OK,00,00\r");
+    }
+
+    #[test]
+    fn saves_files() {
+        let mut drive = drive_with_files();
+        open(&mut drive, 1, b"NEW");
+        write(&mut drive, 1, &[0x01, 0x08, 5, 6]);
+        close(&mut drive, 1);
+        assert_eq!(status(&mut drive), "00, OK,00,00\r");
+        assert_eq!(drive.image.read_file(b"NEW"), Some(vec![0x01, 0x08, 5, 6]));
+
+        open(&mut drive, 1, b"GAME");
+        write(&mut drive, 1, &[0x01, 0x08, 7]);
+        close(&mut drive, 1);
+        assert_eq!(status(&mut drive), "63,FILE EXISTS,00,00\r");
+        assert_eq!(
+            drive.image.read_file(b"GAME"),
+            Some(vec![0x01, 0x08, 1, 2, 3])
+        );
+
+        open(&mut drive, 2, b"@0:GAME,P,W");
+        write(&mut drive, 2, &[0x01, 0x08, 7]);
+        close(&mut drive, 2);
+        assert_eq!(status(&mut drive), "00, OK,00,00\r");
+        assert_eq!(drive.image.read_file(b"GAME"), Some(vec![0x01, 0x08, 7]));
+    }
+}
@@ -1,8 +1,11 @@
 use crate::vic::raster_line_to_screen_y;
+use crate::vic::screen_y_to_raster_line;
 use crate::vic::VideoOutput;
 use crate::vic::{LEFT_BORDER_START, TOP_BORDER_FIRST_LINE, VISIBLE_LINES, VISIBLE_PIXELS};
 use common::colors::create_palette;
+use common::colors::put_rgba;
 use common::colors::Palette;
+use common::colors::PaletteLookup;
 use graphics::types::Rectangle;
 use image::{Pixel, Rgba, RgbaImage};
 
@@ -10,7 +13,7 @@ use image::{Pixel, Rgba, RgbaImage};
 /// [`VicOutput`](../vic/struct.VicOutput.html) structures and renders them
 /// on an image surface.
 pub struct FrameRenderer {
-    palette: Palette,
+    palette: PaletteLookup,
     viewport: Rectangle<usize>,
     frame: RgbaImage,
     vblank: bool,
@@ -19,7 +22,7 @@ pub struct FrameRenderer {
 impl FrameRenderer {
     pub fn new(palette: Palette, viewport: Rectangle<usize>) -> Self {
         Self {
-            palette,
+            palette: PaletteLookup::new(&palette),
             viewport,
             frame: RgbaImage::from_pixel(
                 viewport[2] as u32,
@@ -41,10 +44,12 @@ impl FrameRenderer {
         );
         let in_y_range = y_range.contains(&y);
         if x_range.contains(&x) && in_y_range {
-            self.frame.put_pixel(
-                (x - x_range.start) as u32,
-                (y - y_range.start) as u32,
-                self.palette[vic_output.color as usize],
+            put_rgba(
+                &mut self.frame,
+                self.viewport[2],
+                x - x_range.start,
+                y - y_range.start,
+                self.palette.rgba(vic_output.color),
             );
         }
         let frame_complete = !self.vblank && !in_y_range;
@@ -55,6 +60,19 @@ impl FrameRenderer {
     pub fn frame_image(&self) -> &RgbaImage {
         &self.frame
     }
+
+    /// Converts a position of a frame image pixel to raw coordinates (X and
+    /// raster line), as reported by [`VideoOutput`]. Returns `None` if the
+    /// position lies outside the frame.
+    pub fn raw_position(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        if x >= self.viewport[2] || y >= self.viewport[3] {
+            return None;
+        }
+        return Some((
+            self.viewport[0] + x,
+            screen_y_to_raster_line(self.viewport[1] + y),
+        ));
+    }
 }
 
 impl Default for FrameRenderer {
@@ -78,7 +96,6 @@ impl Default for FrameRenderer {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::vic::screen_y_to_raster_line;
     use crate::vic::Color;
     use common::colors::create_palette;
 
@@ -174,4 +191,13 @@ mod tests {
         // Moving to top should also switch to true.
         assert_eq!(fr.consume(video_output(4, 2, 0)), true);
     }
+
+    #[test]
+    fn converts_positions() {
+        let fr = FrameRenderer::new(simple_palette(), [2, 3, 4, 5]);
+        assert_eq!(fr.raw_position(0, 0), Some((2, screen_y_to_raster_line(3))));
+        assert_eq!(fr.raw_position(3, 4), Some((5, screen_y_to_raster_line(7))));
+        assert_eq!(fr.raw_position(4, 0), None);
+        assert_eq!(fr.raw_position(0, 5), None);
+    }
 }
@@ -0,0 +1,499 @@
+/// State of the IEC serial bus lines. Each field is `true` if the line is
+/// pulled low (asserted). The lines are open-collector: a line stays asserted
+/// as long as any device pulls it low.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IecLines {
+    pub atn: bool,
+    pub clock: bool,
+    pub data: bool,
+}
+
+impl IecLines {
+    /// Returns the state of the bus when two devices drive it at the same time.
+    pub fn combine(self, other: IecLines) -> IecLines {
+        IecLines {
+            atn: self.atn || other.atn,
+            clock: self.clock || other.clock,
+            data: self.data || other.data,
+        }
+    }
+}
+
+/// A peripheral that exchanges bytes with the computer through an
+/// [`IecInterface`]. The interface takes care of the bus protocol and
+/// addressing; the device only deals with channels and data.
+pub trait IecDevice {
+    /// Handles a secondary address (channel selection, open, or close command)
+    /// sent by the computer to this device.
+    fn secondary_address(&mut self, command: u8);
+
+    /// Handles a data byte sent to this device while it's listening.
+    fn receive(&mut self, byte: u8);
+
+    /// Called when the device is told to stop listening.
+    fn unlisten(&mut self);
+
+    /// Returns the next byte to send while the device is talking, along with
+    /// a flag that tells whether it's the last one. Returns `None` if there is
+    /// nothing to send.
+    fn send(&mut self) -> Option<(u8, bool)>;
+}
+
+/// The device side of the IEC serial bus protocol. The interface receives
+/// commands from the computer while the ATN line is asserted, and then either
+/// receives (as a listener) or sends (as a talker) data bytes, one bit at a
+/// time, using the CLOCK and DATA lines for handshaking.
+pub struct IecInterface<D: IecDevice> {
+    device: D,
+    device_number: u8,
+    role: Role,
+    state: State,
+    /// Number of cycles spent in the current state.
+    timer: u32,
+    output: IecLines,
+    previous_lines: IecLines,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Idle,
+    Listener,
+    Talker,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Not taking part in any transfer.
+    Idle,
+    /// Holding DATA low until the talker releases CLOCK to signal that it's
+    /// ready to send a byte.
+    WaitForTalker,
+    /// DATA released; waiting for the talker to pull CLOCK low and start
+    /// sending bits. If it takes too long, the talker signals EOI.
+    ReadyForData { eoi: bool },
+    /// Holding DATA low to acknowledge EOI.
+    AcknowledgeEoi,
+    /// Receiving bits, each of them valid when the talker releases CLOCK.
+    Receiving { byte: u8, bits: u8 },
+    /// Waiting for the computer to release CLOCK after it became a listener.
+    Turnaround,
+    /// Holding CLOCK low before signaling that the next byte is ready.
+    PrepareByte,
+    /// CLOCK released; waiting for the listener to release DATA.
+    WaitForListener { byte: u8, eoi: bool },
+    /// Signaling EOI by waiting for the listener to pull DATA low.
+    WaitForEoiAcknowledge { byte: u8 },
+    /// Sending a bit on the DATA line.
+    Sending { byte: u8, bit: u8 },
+    /// Waiting for the listener to pull DATA low after receiving a byte.
+    WaitForFrameAcknowledge,
+}
+
+/// Time after which a listener assumes that the talker signals EOI.
+const EOI_TIMEOUT: u32 = 200;
+/// Time for which a listener holds DATA low to acknowledge EOI.
+const EOI_ACKNOWLEDGE_TIME: u32 = 60;
+/// Time for which a talker holds CLOCK low between bytes.
+const BYTE_DELAY: u32 = 80;
+/// Time between setting DATA and releasing CLOCK when sending a bit.
+const BIT_SETUP_TIME: u32 = 60;
+/// Time for which a bit stays valid on the DATA line.
+const BIT_VALID_TIME: u32 = 60;
+/// Time after which a talker gives up waiting for the listener to acknowledge
+/// a byte.
+const FRAME_ACKNOWLEDGE_TIMEOUT: u32 = 1000;
+
+mod commands {
+    pub const LISTEN: u8 = 0x20;
+    pub const UNLISTEN: u8 = 0x3F;
+    pub const TALK: u8 = 0x40;
+    pub const UNTALK: u8 = 0x5F;
+    pub const COMMAND_MASK: u8 = 0xE0;
+    pub const DEVICE_NUMBER_MASK: u8 = 0x1F;
+}
+
+impl<D: IecDevice> IecInterface<D> {
+    pub fn new(device_number: u8, device: D) -> Self {
+        Self {
+            device,
+            device_number,
+            role: Role::Idle,
+            state: State::Idle,
+            timer: 0,
+            output: IecLines::default(),
+            previous_lines: IecLines::default(),
+        }
+    }
+
+    /// Returns the lines currently driven by this interface.
+    pub fn output(&self) -> IecLines {
+        self.output
+    }
+
+    /// Reacts to the current state of the bus. Expected to be called once per
+    /// CPU cycle. Returns the lines driven by this interface.
+    pub fn tick(&mut self, lines: IecLines) -> IecLines {
+        if lines.atn && !self.previous_lines.atn {
+            // All devices have to listen to commands, regardless of what
+            // they've been doing so far.
+            self.output = IecLines {
+                data: true,
+                ..IecLines::default()
+            };
+            self.enter(State::WaitForTalker);
+        } else if !lines.atn && self.previous_lines.atn {
+            match self.role {
+                Role::Listener => {}
+                Role::Talker => {
+                    self.output.data = false;
+                    self.enter(State::Turnaround);
+                }
+                Role::Idle => {
+                    self.output = IecLines::default();
+                    self.enter(State::Idle);
+                }
+            }
+        } else {
+            self.timer += 1;
+            self.update(lines);
+        }
+        self.previous_lines = lines;
+        return self.output;
+    }
+
+    fn update(&mut self, lines: IecLines) {
+        match self.state {
+            State::Idle => {}
+
+            State::WaitForTalker => {
+                if !lines.clock {
+                    self.output.data = false;
+                    self.enter(State::ReadyForData { eoi: false });
+                }
+            }
+            State::ReadyForData { eoi } => {
+                if lines.clock {
+                    self.enter(State::Receiving { byte: 0, bits: 0 });
+                } else if !eoi && self.timer >= EOI_TIMEOUT {
+                    self.output.data = true;
+                    self.enter(State::AcknowledgeEoi);
+                }
+            }
+            State::AcknowledgeEoi => {
+                if self.timer >= EOI_ACKNOWLEDGE_TIME {
+                    self.output.data = false;
+                    self.enter(State::ReadyForData { eoi: true });
+                }
+            }
+            State::Receiving { byte, bits: 8 } => {
+                if lines.clock {
+                    self.output.data = true;
+                    self.enter(State::WaitForTalker);
+                    self.handle_byte(byte, lines.atn);
+                }
+            }
+            State::Receiving { byte, bits } => {
+                if self.previous_lines.clock && !lines.clock {
+                    // Bits are sent starting from the least significant one;
+                    // a released DATA line means 1.
+                    let bit = if lines.data { 0 } else { 0x80 };
+                    self.state = State::Receiving {
+                        byte: (byte >> 1) | bit,
+                        bits: bits + 1,
+                    };
+                }
+            }
+
+            State::Turnaround => {
+                if !lines.clock {
+                    self.output.clock = true;
+                    self.enter(State::PrepareByte);
+                }
+            }
+            State::PrepareByte => {
+                if self.timer >= BYTE_DELAY {
+                    // If there's nothing to send, the listener will eventually
+                    // give up waiting.
+                    self.output.clock = false;
+                    match self.device.send() {
+                        Some((byte, eoi)) => self.enter(State::WaitForListener { byte, eoi }),
+                        None => self.enter(State::Idle),
+                    }
+                }
+            }
+            State::WaitForListener { byte, eoi } => {
+                if !lines.data {
+                    if eoi {
+                        self.enter(State::WaitForEoiAcknowledge { byte });
+                    } else {
+                        self.output.clock = true;
+                        self.send_bit(byte, 0);
+                    }
+                }
+            }
+            State::WaitForEoiAcknowledge { byte } => {
+                if lines.data {
+                    self.enter(State::WaitForListener { byte, eoi: false });
+                }
+            }
+            State::Sending { byte, bit } => {
+                if self.timer == BIT_SETUP_TIME {
+                    self.output.clock = false;
+                } else if self.timer == BIT_SETUP_TIME + BIT_VALID_TIME {
+                    self.output.clock = true;
+                    if bit < 7 {
+                        self.send_bit(byte, bit + 1);
+                    } else {
+                        self.output.data = false;
+                        self.enter(State::WaitForFrameAcknowledge);
+                    }
+                }
+            }
+            State::WaitForFrameAcknowledge => {
+                if lines.data {
+                    self.enter(State::PrepareByte);
+                } else if self.timer >= FRAME_ACKNOWLEDGE_TIMEOUT {
+                    self.output = IecLines::default();
+                    self.enter(State::Idle);
+                }
+            }
+        }
+    }
+
+    fn enter(&mut self, state: State) {
+        self.state = state;
+        self.timer = 0;
+    }
+
+    fn send_bit(&mut self, byte: u8, bit: u8) {
+        self.output.data = byte & (1 << bit) == 0;
+        self.enter(State::Sending { byte, bit });
+    }
+
+    fn handle_byte(&mut self, byte: u8, atn: bool) {
+        if atn {
+            self.handle_command(byte);
+        } else if self.role == Role::Listener {
+            self.device.receive(byte);
+        }
+    }
+
+    fn handle_command(&mut self, command: u8) {
+        let addressed = command & commands::DEVICE_NUMBER_MASK == self.device_number;
+        match command & commands::COMMAND_MASK {
+            commands::LISTEN => {
+                if command == commands::UNLISTEN && self.role == Role::Listener {
+                    self.role = Role::Idle;
+                    self.device.unlisten();
+                } else if addressed {
+                    self.role = Role::Listener;
+                }
+            }
+            commands::TALK => {
+                if addressed && command != commands::UNTALK {
+                    self.role = Role::Talker;
+                } else if self.role == Role::Talker {
+                    // Either UNTALK or some other device has been told to
+                    // talk.
+                    self.role = Role::Idle;
+                }
+            }
+            _ => {
+                if self.role != Role::Idle {
+                    self.device.secondary_address(command);
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records what the computer sends and plays back a given sequence of
+    /// bytes.
+    #[derive(Default)]
+    struct TestDevice {
+        secondary_addresses: Vec<u8>,
+        received: Vec<u8>,
+        unlistened: bool,
+        to_send: Vec<u8>,
+    }
+
+    impl IecDevice for TestDevice {
+        fn secondary_address(&mut self, command: u8) {
+            self.secondary_addresses.push(command);
+        }
+        fn receive(&mut self, byte: u8) {
+            self.received.push(byte);
+        }
+        fn unlisten(&mut self) {
+            self.unlistened = true;
+        }
+        fn send(&mut self) -> Option<(u8, bool)> {
+            if self.to_send.is_empty() {
+                return None;
+            }
+            let byte = self.to_send.remove(0);
+            return Some((byte, self.to_send.is_empty()));
+        }
+    }
+
+    /// Plays the role of the computer on the bus, roughly following what the
+    /// C64 KERNAL does.
+    struct Controller {
+        interface: IecInterface<TestDevice>,
+        output: IecLines,
+    }
+
+    impl Controller {
+        fn new(device: TestDevice) -> Self {
+            Self {
+                interface: IecInterface::new(8, device),
+                output: IecLines::default(),
+            }
+        }
+
+        fn lines(&self) -> IecLines {
+            self.output.combine(self.interface.output())
+        }
+
+        fn tick(&mut self) {
+            self.interface.tick(self.lines());
+        }
+
+        /// Ticks until the bus lines satisfy a given condition. Panics on
+        /// timeout.
+        fn wait(&mut self, condition: impl Fn(IecLines) -> bool) {
+            for _ in 0..10_000 {
+                if condition(self.lines()) {
+                    return;
+                }
+                self.tick();
+            }
+            panic!("Timeout; lines: {:?}", self.lines());
+        }
+
+        fn wait_cycles(&mut self, cycles: u32) {
+            for _ in 0..cycles {
+                self.tick();
+            }
+        }
+
+        fn send_byte(&mut self, byte: u8, eoi: bool) {
+            self.wait(|lines| lines.data);
+            self.output.clock = false;
+            self.wait(|lines| !lines.data);
+            if eoi {
+                self.wait(|lines| lines.data);
+                self.wait(|lines| !lines.data);
+            }
+            self.output.clock = true;
+            for bit in 0..8 {
+                self.output.data = byte & (1 << bit) == 0;
+                self.wait_cycles(10);
+                self.output.clock = false;
+                self.wait_cycles(20);
+                self.output.clock = true;
+                self.output.data = false;
+            }
+            self.wait(|lines| lines.data);
+        }
+
+        fn send_commands(&mut self, commands: &[u8]) {
+            self.output.atn = true;
+            self.output.clock = true;
+            // Stop holding DATA low if the controller has been listening.
+            self.output.data = false;
+            self.wait_cycles(1000);
+            for command in commands {
+                self.send_byte(*command, false);
+            }
+            self.output.atn = false;
+            self.wait_cycles(20);
+        }
+
+        fn send_data(&mut self, data: &[u8]) {
+            for (i, byte) in data.iter().enumerate() {
+                self.send_byte(*byte, i == data.len() - 1);
+            }
+        }
+
+        /// Makes the device a talker and receives bytes until EOI.
+        fn receive_data(&mut self) -> Vec<u8> {
+            self.output.data = true;
+            self.output.clock = false;
+            self.wait(|lines| lines.clock);
+            let mut data = vec![];
+            loop {
+                self.wait(|lines| !lines.clock);
+                self.output.data = false;
+                let mut eoi = false;
+                for _ in 0..EOI_TIMEOUT {
+                    self.tick();
+                    if self.lines().clock {
+                        break;
+                    }
+                }
+                if !self.lines().clock {
+                    eoi = true;
+                    self.output.data = true;
+                    self.wait_cycles(EOI_ACKNOWLEDGE_TIME);
+                    self.output.data = false;
+                    self.wait(|lines| lines.clock);
+                }
+                let mut byte = 0;
+                for _ in 0..8 {
+                    self.wait(|lines| !lines.clock);
+                    byte = (byte >> 1) | if self.lines().data { 0 } else { 0x80 };
+                    self.wait(|lines| lines.clock);
+                }
+                self.output.data = true;
+                data.push(byte);
+                if eoi {
+                    return data;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn listening() {
+        let mut controller = Controller::new(TestDevice::default());
+        controller.send_commands(&[0x28, 0xF1]);
+        controller.send_data(b"HELLO");
+        controller.send_commands(&[0x3F]);
+        // Commands addressed to another device are ignored.
+        controller.send_commands(&[0x29, 0x61, 0x3F]);
+
+        let device = controller.interface.device();
+        assert_eq!(device.secondary_addresses, [0xF1]);
+        assert_eq!(device.received, b"HELLO");
+        assert!(device.unlistened);
+        controller.output = IecLines::default();
+        controller.wait_cycles(10);
+        assert_eq!(controller.lines(), IecLines::default());
+    }
+
+    #[test]
+    fn talking() {
+        let mut controller = Controller::new(TestDevice {
+            to_send: vec![0x01, 0x80, 0xA5],
+            ..TestDevice::default()
+        });
+        controller.send_commands(&[0x48, 0x60]);
+        assert_eq!(controller.receive_data(), [0x01, 0x80, 0xA5]);
+        controller.send_commands(&[0x5F]);
+
+        assert_eq!(controller.interface.device().secondary_addresses, [0x60]);
+        controller.output = IecLines::default();
+        controller.wait_cycles(10);
+        assert_eq!(controller.lines(), IecLines::default());
+    }
+}
@@ -0,0 +1,115 @@
+use enum_map::Enum;
+use serde::Deserialize;
+
+/// A digital joystick connected to one of the C64 control ports. Joystick
+/// switches pull the CIA port lines low, so the state is represented as a mask
+/// of lines that are currently being pulled down.
+#[derive(Debug, Default)]
+pub struct Joystick {
+    pressed_lines: u8,
+}
+
+impl Joystick {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the state of a given input. Pressing a direction releases the
+    /// opposite one, since a physical joystick can't point both ways at once.
+    pub fn set_state(&mut self, input: JoystickInput, pressed: bool) {
+        if pressed {
+            self.pressed_lines &= !input.opposite().line_mask();
+            self.pressed_lines |= input.line_mask();
+        } else {
+            self.pressed_lines &= !input.line_mask();
+        }
+    }
+
+    /// Returns a mask of CIA port lines pulled down by this joystick.
+    pub fn pressed_lines(&self) -> u8 {
+        self.pressed_lines
+    }
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum JoystickInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Fire,
+}
+
+impl JoystickInput {
+    fn line_mask(&self) -> u8 {
+        match *self {
+            Self::Up => 1,
+            Self::Down => 1 << 1,
+            Self::Left => 1 << 2,
+            Self::Right => 1 << 3,
+            Self::Fire => 1 << 4,
+        }
+    }
+
+    fn opposite(&self) -> Self {
+        match *self {
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+            Self::Fire => Self::Fire,
+        }
+    }
+}
+
+/// C64 control ports. Port 1 is connected to CIA1 port B, and port 2 to CIA1
+/// port A; both share the lines with the keyboard matrix.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, clap::ArgEnum)]
+pub enum JoystickPort {
+    #[clap(name = "1")]
+    Port1,
+    #[clap(name = "2")]
+    Port2,
+}
+
+impl JoystickPort {
+    /// Returns the other control port.
+    pub fn other(&self) -> Self {
+        match *self {
+            Self::Port1 => Self::Port2,
+            Self::Port2 => Self::Port1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directions_and_fire() {
+        let mut joystick = Joystick::new();
+        assert_eq!(joystick.pressed_lines(), 0);
+
+        joystick.set_state(JoystickInput::Up, true);
+        joystick.set_state(JoystickInput::Left, true);
+        assert_eq!(joystick.pressed_lines(), 0b0000_0101);
+        joystick.set_state(JoystickInput::Fire, true);
+        assert_eq!(joystick.pressed_lines(), 0b0001_0101);
+
+        joystick.set_state(JoystickInput::Up, false);
+        joystick.set_state(JoystickInput::Fire, false);
+        assert_eq!(joystick.pressed_lines(), 0b0000_0100);
+    }
+
+    #[test]
+    fn opposite_directions() {
+        let mut joystick = Joystick::new();
+        joystick.set_state(JoystickInput::Up, true);
+        joystick.set_state(JoystickInput::Down, true);
+        assert_eq!(joystick.pressed_lines(), 0b0000_0010);
+        joystick.set_state(JoystickInput::Right, true);
+        joystick.set_state(JoystickInput::Left, true);
+        assert_eq!(joystick.pressed_lines(), 0b0000_0110);
+    }
+}
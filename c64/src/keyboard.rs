@@ -1,44 +1,81 @@
 use enum_map::{enum_map, Enum, EnumMap};
+use serde::Deserialize;
 
 pub struct Keyboard {
     key_states: EnumMap<Key, KeyState>,
+    /// For each column, a mask of rows connected to it by pressed keys.
+    pressed_rows: [u8; 8],
 }
 
-/// Emulates the C64 keyboard scanning matrix.
+/// Emulates the C64 keyboard scanning matrix. Columns are connected to CIA1
+/// port A, and rows to port B. The matrix works both ways: a line pulled low
+/// on either side pulls low all lines connected to it by pressed keys. The
+/// RESTORE key is not a part of the matrix; it triggers NMI instead.
 ///
-/// TODO: Support multiple key presses.
-/// TODO: Support the RESTORE key.
 /// TODO: Emulate ghosting.
 impl Keyboard {
     pub fn new() -> Self {
         Self {
             key_states: enum_map!(_ => KeyState::Released),
+            pressed_rows: [0; 8],
         }
     }
 
     pub fn set_key_state(&mut self, key: Key, state: KeyState) {
         self.key_states[key] = state;
+        for (column, keys) in KEY_MATRIX.iter().enumerate() {
+            self.pressed_rows[column] = keys
+                .iter()
+                .enumerate()
+                .filter(|(_, key)| self.is_pressed(**key))
+                .fold(0, |rows, (row, _)| rows | 1 << row);
+        }
+    }
+
+    fn is_pressed(&self, key: Key) -> bool {
+        // SHIFT LOCK is a latching key connected in parallel with the left
+        // SHIFT key.
+        self.key_states[key] == KeyState::Pressed
+            || (key == Key::LShift && self.key_states[Key::ShiftLock] == KeyState::Pressed)
+    }
+
+    pub fn restore_pressed(&self) -> bool {
+        self.key_states[Key::Restore] == KeyState::Pressed
     }
 
     /// Simulates probing the keyboard state with given column bit mask. Returns
     /// row states as bits. The bit layout corresponds to appropriate CIA's port
     /// registers.
-    pub fn scan(&self, mask: u8) -> u8 {
-        for i in 0..=7 {
-            let column_bit = 1 << i;
-            if mask & column_bit == 0 {
-                for j in 0..=7 {
-                    if self.key_states[KEY_MATRIX[i][j]] == KeyState::Pressed {
-                        return !(1 << j);
-                    }
-                }
+    pub fn scan(&self, columns: u8) -> u8 {
+        let mut rows = 0xFF;
+        for (column, pressed_rows) in self.pressed_rows.iter().enumerate() {
+            if columns & (1 << column) == 0 {
+                rows &= !pressed_rows;
             }
         }
-        return 0xff;
+        return rows;
+    }
+
+    /// Simulates probing the keyboard state the other way around: with a given
+    /// row bit mask. Returns column states as bits.
+    pub fn scan_rows(&self, rows: u8) -> u8 {
+        let mut columns = 0xFF;
+        for (column, pressed_rows) in self.pressed_rows.iter().enumerate() {
+            if !rows & pressed_rows != 0 {
+                columns &= !(1 << column);
+            }
+        }
+        return columns;
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-#[derive(Enum, Clone, Copy)]
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Deserialize)]
 pub enum Key {
     LeftArrow,
     D1,
@@ -247,4 +284,43 @@ mod tests {
             [!0, !0, !0, 0b0111_1111, !0, !0, !0, !0]
         );
     }
+
+    #[test]
+    fn multiple_key_presses() {
+        let mut k = Keyboard::new();
+        k.set_key_state(Key::RunStop, KeyState::Pressed);
+        k.set_key_state(Key::Q, KeyState::Pressed);
+        k.set_key_state(Key::LShift, KeyState::Pressed);
+        assert_eq!(
+            scan_all_columns(&k),
+            [0b0011_1111, !0, !0, !0, !0, !0, 0b0111_1111, !0]
+        );
+        // Scanning multiple columns at once.
+        assert_eq!(k.scan(0b0111_1101), 0b0011_1111);
+
+        k.set_key_state(Key::LShift, KeyState::Released);
+        k.set_key_state(Key::ShiftLock, KeyState::Pressed);
+        assert_eq!(k.scan(0b1111_1101), 0b0111_1111);
+    }
+
+    #[test]
+    fn scanning_rows() {
+        let mut k = Keyboard::new();
+        k.set_key_state(Key::R, KeyState::Pressed);
+        k.set_key_state(Key::Space, KeyState::Pressed);
+        assert_eq!(k.scan_rows(0b1111_1101), 0b1111_1011);
+        assert_eq!(k.scan_rows(0b1110_1111), 0b0111_1111);
+        assert_eq!(k.scan_rows(0b1110_1101), 0b0111_1011);
+        assert_eq!(k.scan_rows(0b1111_1110), 0b1111_1111);
+    }
+
+    #[test]
+    fn restore() {
+        let mut k = Keyboard::new();
+        k.set_key_state(Key::Restore, KeyState::Pressed);
+        assert!(k.restore_pressed());
+        assert_eq!(scan_all_columns(&k), [!0; 8]);
+        k.set_key_state(Key::Restore, KeyState::Released);
+        assert!(!k.restore_pressed());
+    }
 }
@@ -0,0 +1,323 @@
+use crate::joystick::JoystickInput;
+use crate::keyboard::Key as C64Key;
+use common::config::read_config_file;
+use common::keymap::parse_key_map;
+use common::keymap::KeymapError;
+use piston::Key;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Determines how host keys are translated to C64 keys.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyboardMode {
+    /// Host keys are mapped to C64 keys located in the same place on the
+    /// keyboard, regardless of symbols printed on them. For example, pressing
+    /// Shift+2 on the host keyboard types a quotation mark, like on C64.
+    Positional,
+    /// Host keys are mapped to C64 keys that produce the same symbols (as far
+    /// as possible, assuming the US host keyboard layout). The C64 shift key
+    /// is pressed or released as needed; for example, pressing Shift+2 on the
+    /// host keyboard presses an unshifted C64 `@` key.
+    Symbolic,
+}
+
+impl Default for KeyboardMode {
+    fn default() -> Self {
+        Self::Positional
+    }
+}
+
+/// A C64 key press that corresponds to a host key press.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct C64KeyPress {
+    pub key: C64Key,
+    /// If set, the C64 shift key must be held (or released) while the key is
+    /// pressed, regardless of the state of host shift keys.
+    pub shift: Option<bool>,
+}
+
+impl From<C64Key> for C64KeyPress {
+    fn from(key: C64Key) -> Self {
+        Self { key, shift: None }
+    }
+}
+
+/// Maps host keys to the C64 keyboard and the joystick.
+pub struct Keymap {
+    mode: KeyboardMode,
+    /// Keyboard mappings that take precedence over the ones implied by the
+    /// keyboard mode.
+    keys: HashMap<Key, C64Key>,
+    joystick: HashMap<Key, JoystickInput>,
+}
+
+/// Keymap configuration, as read from a file. Keys that are not mentioned in
+/// the file retain their default mapping.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct KeymapConfig {
+    mode: KeyboardMode,
+    keys: HashMap<String, C64Key>,
+    joystick: HashMap<String, JoystickInput>,
+}
+
+impl Keymap {
+    pub fn read(path: &Path) -> Result<Self, KeymapError> {
+        return Self::from_config(read_config_file(path)?);
+    }
+
+    fn from_config(config: KeymapConfig) -> Result<Self, KeymapError> {
+        let mut joystick = Self::default().joystick;
+        joystick.extend(parse_key_map(config.joystick)?);
+        return Ok(Self {
+            mode: config.mode,
+            keys: parse_key_map(config.keys)?,
+            joystick,
+        });
+    }
+
+    pub fn mode(&self) -> KeyboardMode {
+        self.mode
+    }
+
+    pub fn joystick_input(&self, key: Key) -> Option<JoystickInput> {
+        self.joystick.get(&key).copied()
+    }
+
+    /// Translates a host key press to a C64 key press, taking into account
+    /// whether the host shift key is pressed.
+    pub fn key_press(&self, key: Key, shifted: bool) -> Option<C64KeyPress> {
+        if let Some(c64_key) = self.keys.get(&key) {
+            return Some((*c64_key).into());
+        }
+        return match self.mode {
+            KeyboardMode::Positional => positional_key_press(key),
+            KeyboardMode::Symbolic => symbolic_key(key, shifted),
+        };
+    }
+}
+
+impl Default for Keymap {
+    /// Maps the numeric keypad to joystick directions and fire button, so that
+    /// it doesn't collide with the C64 keyboard.
+    fn default() -> Self {
+        Self {
+            mode: KeyboardMode::Positional,
+            keys: HashMap::new(),
+            joystick: HashMap::from([
+                (Key::NumPad8, JoystickInput::Up),
+                (Key::NumPad2, JoystickInput::Down),
+                (Key::NumPad4, JoystickInput::Left),
+                (Key::NumPad6, JoystickInput::Right),
+                (Key::NumPad0, JoystickInput::Fire),
+                (Key::NumPad5, JoystickInput::Fire),
+            ]),
+        }
+    }
+}
+
+fn positional_key(key: Key) -> Option<C64Key> {
+    match key {
+        Key::Backquote => Some(C64Key::LeftArrow),
+        Key::D1 => Some(C64Key::D1),
+        Key::D2 => Some(C64Key::D2),
+        Key::D3 => Some(C64Key::D3),
+        Key::D4 => Some(C64Key::D4),
+        Key::D5 => Some(C64Key::D5),
+        Key::D6 => Some(C64Key::D6),
+        Key::D7 => Some(C64Key::D7),
+        Key::D8 => Some(C64Key::D8),
+        Key::D9 => Some(C64Key::D9),
+        Key::D0 => Some(C64Key::D0),
+        Key::Minus => Some(C64Key::Plus),
+        Key::Equals => Some(C64Key::Minus),
+        Key::Insert => Some(C64Key::Pound),
+        Key::Home => Some(C64Key::ClrHome),
+        Key::Backspace => Some(C64Key::InstDel),
+
+        Key::Tab => Some(C64Key::Ctrl),
+        Key::Q => Some(C64Key::Q),
+        Key::W => Some(C64Key::W),
+        Key::E => Some(C64Key::E),
+        Key::R => Some(C64Key::R),
+        Key::T => Some(C64Key::T),
+        Key::Y => Some(C64Key::Y),
+        Key::U => Some(C64Key::U),
+        Key::I => Some(C64Key::I),
+        Key::O => Some(C64Key::O),
+        Key::P => Some(C64Key::P),
+        Key::LeftBracket => Some(C64Key::At),
+        Key::RightBracket => Some(C64Key::Asterisk),
+        Key::Delete => Some(C64Key::UpArrow),
+        Key::F12 => Some(C64Key::Restore),
+
+        Key::Escape => Some(C64Key::RunStop),
+        Key::CapsLock => Some(C64Key::ShiftLock),
+        Key::A => Some(C64Key::A),
+        Key::S => Some(C64Key::S),
+        Key::D => Some(C64Key::D),
+        Key::F => Some(C64Key::F),
+        Key::G => Some(C64Key::G),
+        Key::H => Some(C64Key::H),
+        Key::J => Some(C64Key::J),
+        Key::K => Some(C64Key::K),
+        Key::L => Some(C64Key::L),
+        Key::Semicolon => Some(C64Key::Colon),
+        Key::Quote => Some(C64Key::Semicolon),
+        Key::Backslash => Some(C64Key::Equals),
+        Key::Return => Some(C64Key::Return),
+
+        Key::LCtrl => Some(C64Key::Commodore),
+        Key::LShift => Some(C64Key::LShift),
+        Key::Z => Some(C64Key::Z),
+        Key::X => Some(C64Key::X),
+        Key::C => Some(C64Key::C),
+        Key::V => Some(C64Key::V),
+        Key::B => Some(C64Key::B),
+        Key::N => Some(C64Key::N),
+        Key::M => Some(C64Key::M),
+        Key::Comma => Some(C64Key::Comma),
+        Key::Period => Some(C64Key::Period),
+        Key::Slash => Some(C64Key::Slash),
+        Key::RShift => Some(C64Key::RShift),
+        Key::Down => Some(C64Key::CrsrUpDown),
+        Key::Right => Some(C64Key::CrsrLeftRight),
+
+        Key::Space => Some(C64Key::Space),
+
+        Key::F1 => Some(C64Key::F1),
+        Key::F3 => Some(C64Key::F3),
+        Key::F5 => Some(C64Key::F5),
+        Key::F7 => Some(C64Key::F7),
+
+        _ => None,
+    }
+}
+
+/// Maps keys that don't have their own counterparts on C64, but are available
+/// there using the shift key: cursor up and left, as well as even-numbered
+/// function keys. All other keys are mapped using [`positional_key`].
+fn positional_key_press(key: Key) -> Option<C64KeyPress> {
+    let shifted = |key| {
+        Some(C64KeyPress {
+            key,
+            shift: Some(true),
+        })
+    };
+    match key {
+        Key::Up => shifted(C64Key::CrsrUpDown),
+        Key::Left => shifted(C64Key::CrsrLeftRight),
+        Key::F2 => shifted(C64Key::F1),
+        Key::F4 => shifted(C64Key::F3),
+        Key::F6 => shifted(C64Key::F5),
+        Key::F8 => shifted(C64Key::F7),
+        _ => positional_key(key).map(C64KeyPress::from),
+    }
+}
+
+/// Maps keys whose symbols are placed differently on the US keyboard than on
+/// C64. All other keys are mapped positionally, with the shift state passed
+/// through, except for the keys handled by [`positional_key_press`]. Returns
+/// `None` for symbols that don't exist on C64.
+fn symbolic_key(key: Key, shifted: bool) -> Option<C64KeyPress> {
+    let press = |key, shift| {
+        Some(C64KeyPress {
+            key,
+            shift: Some(shift),
+        })
+    };
+    match (key, shifted) {
+        (Key::D2, true) => press(C64Key::At, false),
+        (Key::D6, true) => press(C64Key::UpArrow, false),
+        (Key::D7, true) => press(C64Key::D6, true),
+        (Key::D8, true) => press(C64Key::Asterisk, false),
+        (Key::D9, true) => press(C64Key::D8, true),
+        (Key::D0, true) => press(C64Key::D9, true),
+        (Key::Minus, false) => press(C64Key::Minus, false),
+        (Key::Minus, true) => press(C64Key::LeftArrow, false),
+        (Key::Equals, false) => press(C64Key::Equals, false),
+        (Key::Equals, true) => press(C64Key::Plus, false),
+        (Key::LeftBracket, false) => press(C64Key::Colon, true),
+        (Key::RightBracket, false) => press(C64Key::Semicolon, true),
+        (Key::Semicolon, false) => press(C64Key::Semicolon, false),
+        (Key::Semicolon, true) => press(C64Key::Colon, false),
+        (Key::Quote, false) => press(C64Key::D7, true),
+        (Key::Quote, true) => press(C64Key::D2, true),
+        (Key::Backslash, false) => press(C64Key::Pound, false),
+        (Key::Backquote, false) => press(C64Key::LeftArrow, false),
+        (Key::LeftBracket | Key::RightBracket | Key::Backslash | Key::Backquote, true) => None,
+        _ => positional_key_press(key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::config::parse_config;
+    use common::config::ConfigFormat;
+
+    fn keymap(config: &str) -> Keymap {
+        Keymap::from_config(parse_config(config, ConfigFormat::Json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn positional_mode() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.key_press(Key::D2, true), Some(C64Key::D2.into()));
+        assert_eq!(
+            keymap.key_press(Key::Minus, false),
+            Some(C64Key::Plus.into())
+        );
+        assert_eq!(
+            keymap.key_press(Key::Left, false),
+            Some(C64KeyPress {
+                key: C64Key::CrsrLeftRight,
+                shift: Some(true)
+            })
+        );
+        assert_eq!(keymap.joystick_input(Key::NumPad8), Some(JoystickInput::Up));
+    }
+
+    #[test]
+    fn symbolic_mode() {
+        let keymap = keymap(r#"{"mode": "symbolic"}"#);
+        let press = |key, shift| Some(C64KeyPress { key, shift });
+        assert_eq!(keymap.key_press(Key::D2, false), press(C64Key::D2, None));
+        assert_eq!(
+            keymap.key_press(Key::D2, true),
+            press(C64Key::At, Some(false))
+        );
+        assert_eq!(
+            keymap.key_press(Key::Quote, true),
+            press(C64Key::D2, Some(true))
+        );
+        assert_eq!(keymap.key_press(Key::A, true), press(C64Key::A, None));
+        assert_eq!(keymap.key_press(Key::Backslash, true), None);
+    }
+
+    #[test]
+    fn overrides_defaults() {
+        let keymap = keymap(
+            r#"{
+                "mode": "symbolic",
+                "keys": {"Backslash": "Pound", "F2": "Restore"},
+                "joystick": {"RCtrl": "Fire"}
+            }"#,
+        );
+        assert_eq!(
+            keymap.key_press(Key::Backslash, true),
+            Some(C64Key::Pound.into())
+        );
+        assert_eq!(
+            keymap.key_press(Key::F2, false),
+            Some(C64Key::Restore.into())
+        );
+        assert_eq!(keymap.joystick_input(Key::RCtrl), Some(JoystickInput::Fire));
+        assert_eq!(
+            keymap.joystick_input(Key::NumPad5),
+            Some(JoystickInput::Fire)
+        );
+    }
+}
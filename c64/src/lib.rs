@@ -0,0 +1,48 @@
+//! A Commodore 64 emulator. Besides the `c64` binary, the crate can be used as
+//! a library to embed the computer in other programs:
+//!
+//! ```no_run
+//! use c64::keyboard::{Key, KeyState};
+//! use c64::C64Builder;
+//! use common::app::Machine;
+//!
+//! let mut c64 = C64Builder::new().build().unwrap();
+//! c64.reset();
+//! c64.set_key_state(Key::Space, KeyState::Pressed);
+//! let frame = c64.run_frame().unwrap().clone();
+//! // Examine the computer after each of the next 60 frames.
+//! c64.run_frames(60, |c64| println!("{}", c64.display_state()))
+//!     .unwrap();
+//! ```
+//!
+//! [`C64Controller`] connects the computer to the emulator application
+//! provided by the `common` crate, translating host input events.
+
+#![feature(test)]
+#![feature(assert_matches)]
+
+pub mod address_space;
+pub mod app;
+pub mod c64;
+pub mod cartridge;
+pub mod cia;
+pub mod controllers;
+pub mod d64;
+pub mod drive;
+pub mod frame_renderer;
+pub mod iec;
+pub mod joystick;
+pub mod keyboard;
+pub mod keymap;
+pub mod port;
+pub mod roms;
+pub mod sid;
+pub mod tape;
+pub mod timer;
+pub mod tod;
+pub mod vic;
+
+mod test_utils;
+
+pub use crate::app::C64Controller;
+pub use crate::c64::{C64Builder, C64};
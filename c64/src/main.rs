@@ -1,86 +1,210 @@
-#![feature(test)]
-#![feature(assert_matches)]
-
-mod address_space;
-mod app;
-mod c64;
-mod cia;
-mod frame_renderer;
-mod keyboard;
-mod port;
-mod sid;
-mod tape;
-mod timer;
-mod vic;
-
-mod test_utils;
-
-use crate::address_space::Cartridge;
-use crate::address_space::CartridgeMode;
-use crate::app::C64Controller;
-use crate::c64::C64;
+use c64::app::C64Controller;
+use c64::c64::C64Builder;
+use c64::cartridge::read_cartridge_file;
+use c64::controllers::ControllerType;
+use c64::d64::read_d64_file;
+use c64::drive;
+use c64::drive::Drive;
+use c64::iec::IecInterface;
+use c64::joystick::JoystickPort;
+use c64::keymap::Keymap;
+use c64::roms::RomConfig;
+use c64::roms::RomKind;
+use c64::roms::RomSet;
+use c64::tape::read_tap_file;
+use c64::tape::write_tap_file;
+use c64::tape::Datasette;
 use clap::Parser;
+use common::app::AppController;
 use common::app::Application;
 use common::app::CommonCliArguments;
-use common::debugger::adapter::TcpDebugAdapter;
+use common::archive::read_image_file;
+use common::gamepad::GamepadMapping;
 use std::fs::File;
 use std::io;
-use tape::read_tap_file;
-use tape::Datasette;
-use vic::Vic;
-use ya6502::memory::Rom;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
 
 #[derive(Parser)]
 struct Args {
     #[clap(flatten)]
     common: CommonCliArguments,
 
+    #[clap(flatten)]
+    gamepad_mapping: GamepadMapping,
+
+    /// Cartridge image: a CRT file or a raw Ultimax ROM image. Can also be
+    /// compressed in a ZIP or gzip file.
     #[clap(long)]
     cartridge: Option<String>,
 
+    /// TAP file to play. Can also be compressed in a ZIP or gzip file.
     #[clap(long)]
     tape: Option<String>,
+
+    /// TAP file to record to. Everything saved to the tape is written to this
+    /// file once the emulator quits.
+    #[clap(long, conflicts_with = "tape")]
+    tape_out: Option<String>,
+
+    /// D64 disk image to insert into a 1541 drive connected as device 8.
+    /// Saved files are written back to the image file.
+    #[clap(long)]
+    disk: Option<String>,
+
+    /// Control port to which the host keyboard joystick and the first gamepad
+    /// are connected. The second gamepad is connected to the other port.
+    #[clap(long, arg_enum, default_value = "2")]
+    joystick_port: JoystickPort,
+
+    /// TOML file that specifies custom system ROM images.
+    #[clap(long)]
+    rom_config: Option<PathBuf>,
+
+    /// Custom BASIC ROM image. Overrides the one in the ROM configuration.
+    #[clap(long)]
+    basic_rom: Option<PathBuf>,
+
+    /// Custom KERNAL ROM image (e.g. JiffyDOS). Overrides the one in the ROM
+    /// configuration.
+    #[clap(long)]
+    kernal_rom: Option<PathBuf>,
+
+    /// Custom character ROM image. Overrides the one in the ROM configuration.
+    #[clap(long)]
+    char_rom: Option<PathBuf>,
+
+    /// Directory to search for ROM images given as relative paths. Can be
+    /// repeated; these directories are searched before the ones from the ROM
+    /// configuration.
+    #[clap(long)]
+    rom_path: Vec<PathBuf>,
+
+    /// Type of the controller connected to control port 1.
+    #[clap(long, arg_enum, default_value = "joystick")]
+    port1_controller: ControllerType,
+
+    /// Type of the controller connected to control port 2.
+    #[clap(long, arg_enum, default_value = "joystick")]
+    port2_controller: ControllerType,
+}
+
+/// Loads the system ROMs specified on the command line, reporting errors in a
+/// human-readable way, since they are most likely caused by a typo.
+fn load_roms(args: &Args) -> RomSet {
+    let result = args
+        .rom_config
+        .as_deref()
+        .map_or_else(|| Ok(RomConfig::default()), RomConfig::read)
+        .and_then(|mut config| {
+            config
+                .search_path
+                .splice(0..0, args.rom_path.iter().cloned());
+            for (kind, path) in [
+                (RomKind::Basic, &args.basic_rom),
+                (RomKind::Kernal, &args.kernal_rom),
+                (RomKind::Chargen, &args.char_rom),
+            ] {
+                if let Some(path) = path {
+                    config.set_path(kind, path.clone());
+                }
+            }
+            RomSet::load(&config.with_default_search_path())
+        });
+    return match result {
+        Ok(roms) => roms,
+        Err(e) => {
+            eprintln!("Unable to load system ROMs: {}", e);
+            process::exit(1);
+        }
+    };
 }
 
 fn main() {
     let args = Args::parse();
 
-    let mut c64 = C64::new().expect("Unable to initialize C64");
+    let mut c64_builder = C64Builder::new()
+        .with_roms(load_roms(&args))
+        .with_power_on_state(args.common.power_on_state())
+        .with_ram_pattern(args.common.ram_pattern())
+        .with_controller(JoystickPort::Port1, args.port1_controller)
+        .with_controller(JoystickPort::Port2, args.port2_controller)
+        .with_permissive(args.common.permissive);
 
-    // Load the cartridge ROM image, if specified. So far, only Ultimax mode is
-    // supported.
-    if let Some(file) = args.cartridge {
-        let cartridge_bytes = std::fs::read(file).expect("Unable to read the cartridge file");
-        c64.set_cartridge(Some(Cartridge {
-            mode: CartridgeMode::Ultimax,
-            rom: Rom::new(&cartridge_bytes).expect("Unable to create ROM cartridge"),
-        }));
+    if let Some(file) = &args.cartridge {
+        let cartridge = read_cartridge_file(Path::new(file)).expect("Unable to load the cartridge");
+        c64_builder = c64_builder.with_cartridge(cartridge);
     }
 
-    if let Some(file) = args.tape {
-        let tape_data = read_tap_file(io::BufReader::new(
-            File::open(file).expect("Unable to open the tape file"),
-        ))
-        .expect("Unable to read the tape file");
-        c64.set_datasette(Some(Datasette::new(tape_data)));
+    if let Some(file) = &args.tape {
+        let image =
+            read_image_file(Path::new(file), &["tap"]).expect("Unable to open the tape file");
+        let tape_data = read_tap_file(&image.bytes[..]).expect("Unable to read the tape file");
+        c64_builder = c64_builder.with_datasette(Datasette::new(tape_data));
     }
 
-    let debugger_adapter = if args.common.debugger {
-        Some(TcpDebugAdapter::new(args.common.debugger_port))
-    } else {
-        None
-    };
+    if args.tape_out.is_some() {
+        c64_builder = c64_builder.with_datasette(Datasette::for_recording());
+    }
 
-    let mut app = Application::new(
-        C64Controller::new(&mut c64, debugger_adapter),
-        "Commodore 64",
-        2,
-        2,
-    );
+    if let Some(file) = args.disk {
+        let image = read_d64_file(io::BufReader::new(
+            File::open(&file).expect("Unable to open the disk image file"),
+        ))
+        .expect("Unable to read the disk image file");
+        c64_builder = c64_builder.with_disk_drive(IecInterface::new(
+            drive::DEFAULT_DEVICE_NUMBER,
+            Drive::new(image).with_image_path(file),
+        ));
+    }
 
-    let interrupted = app.interrupted();
-    signal_hook::flag::register(signal_hook::consts::SIGINT, interrupted)
+    let mut c64 = c64_builder.build().expect("Unable to initialize C64");
+
+    let mut controller =
+        C64Controller::new(&mut c64, args.common.create_debugger(), args.joystick_port);
+    controller.set_tracer(args.common.create_tracer());
+    controller.set_coverage_tracker(args.common.create_coverage_tracker());
+    controller
+        .install_cheats(&args.common.cheats)
+        .expect("Unable to install cheats");
+    controller.set_gamepad_mapping(args.gamepad_mapping);
+    if let Some(path) = &args.common.keymap {
+        let keymap = Keymap::read(Path::new(path)).expect("Unable to read the keymap file");
+        controller.set_keymap(keymap);
+    }
+    signal_hook::flag::register(signal_hook::consts::SIGINT, controller.interrupted())
         .expect("Unable to set interrupt signal handler");
+    let controller = args.common.create_input_log_controller(controller);
 
-    app.run();
+    if args.common.headless {
+        args.common
+            .create_headless_runner(controller)
+            .run(args.common.frames)
+            .expect("Unable to store the frames");
+    } else {
+        let program = args
+            .cartridge
+            .as_ref()
+            .or(args.tape.as_ref())
+            .map(Path::new);
+        let controller = args.common.create_watching_controller(controller, program);
+        let mut app = Application::new(controller, args.common.backend, "Commodore 64", 2, 2);
+        app.set_speed(args.common.speed);
+        app.set_sync_mode(args.common.sync);
+        app.set_max_frame_skip(args.common.max_frame_skip);
+        app.set_capture_dir(&args.common.capture_dir);
+        app.set_crt_effects(args.common.crt_effects());
+        app.run();
+    }
+
+    if let Some(file) = args.tape_out {
+        if let Some(tape_data) = c64.datasette().and_then(|d| d.recorded_tape()) {
+            write_tap_file(
+                io::BufWriter::new(File::create(file).expect("Unable to create the tape file")),
+                tape_data,
+            )
+            .expect("Unable to write the tape file");
+        }
+    }
 }
@@ -0,0 +1,410 @@
+use enum_map::Enum;
+use enum_map::EnumMap;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// System ROM chips of the C64.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomKind {
+    Basic,
+    Kernal,
+    Chargen,
+}
+
+impl RomKind {
+    /// Returns the required size of a ROM image, in bytes.
+    pub fn size(&self) -> usize {
+        match *self {
+            Self::Basic => 0x2000,
+            Self::Kernal => 0x2000,
+            Self::Chargen => 0x1000,
+        }
+    }
+
+    /// Returns the name of a file that holds the built-in ROM image.
+    fn builtin_file_name(&self) -> &'static str {
+        match *self {
+            Self::Basic => "basic.bin",
+            Self::Kernal => "kernal.bin",
+            Self::Chargen => "char.bin",
+        }
+    }
+}
+
+impl fmt::Display for RomKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Self::Basic => "BASIC",
+            Self::Kernal => "KERNAL",
+            Self::Chargen => "character",
+        })
+    }
+}
+
+/// Directories where ROM images are usually installed by other emulators,
+/// searched after the ones given in the configuration.
+const DEFAULT_SEARCH_PATH: [&str; 3] = [
+    "/usr/share/vice/C64",
+    "/usr/lib/vice/C64",
+    "/usr/local/share/vice/C64",
+];
+
+/// Locations of custom ROM images, as read from a configuration file or given
+/// on the command line. ROMs that are not specified use the built-in images.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RomConfig {
+    pub basic: Option<PathBuf>,
+    pub kernal: Option<PathBuf>,
+    pub chargen: Option<PathBuf>,
+    /// Directories searched for ROM images given as relative paths that don't
+    /// exist relative to the current directory.
+    pub search_path: Vec<PathBuf>,
+    /// Expected CRC32 checksums of the ROM images.
+    pub checksums: RomChecksums,
+}
+
+/// Expected CRC32 checksums of custom ROM images. Images without a checksum
+/// are not verified.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RomChecksums {
+    pub basic: Option<u32>,
+    pub kernal: Option<u32>,
+    pub chargen: Option<u32>,
+}
+
+impl RomChecksums {
+    pub fn checksum(&self, kind: RomKind) -> Option<u32> {
+        match kind {
+            RomKind::Basic => self.basic,
+            RomKind::Kernal => self.kernal,
+            RomKind::Chargen => self.chargen,
+        }
+    }
+}
+
+impl RomConfig {
+    /// Reads the configuration from a TOML file. Relative search path entries
+    /// are resolved relative to the directory that contains the file. So are
+    /// relative ROM paths, unless there's no such file in that directory, in
+    /// which case they are looked up in the search path.
+    pub fn read(path: &Path) -> Result<Self, RomError> {
+        let text = fs::read_to_string(path).map_err(|source| RomError::ConfigIoError {
+            path: path.to_owned(),
+            source,
+        })?;
+        let mut config: Self = toml::from_str(&text)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for dir in &mut config.search_path {
+            *dir = base_dir.join(&dir);
+        }
+        for rom_path in [&mut config.basic, &mut config.kernal, &mut config.chargen]
+            .into_iter()
+            .flatten()
+        {
+            let candidate = base_dir.join(&rom_path);
+            if candidate.exists() {
+                *rom_path = candidate;
+            }
+        }
+        return Ok(config);
+    }
+
+    pub fn path(&self, kind: RomKind) -> Option<&Path> {
+        match kind {
+            RomKind::Basic => self.basic.as_deref(),
+            RomKind::Kernal => self.kernal.as_deref(),
+            RomKind::Chargen => self.chargen.as_deref(),
+        }
+    }
+
+    /// Appends the default directories to the search path.
+    pub fn with_default_search_path(mut self) -> Self {
+        self.search_path
+            .extend(DEFAULT_SEARCH_PATH.into_iter().map(PathBuf::from));
+        self
+    }
+
+    pub fn set_path(&mut self, kind: RomKind, path: PathBuf) {
+        match kind {
+            RomKind::Basic => self.basic = Some(path),
+            RomKind::Kernal => self.kernal = Some(path),
+            RomKind::Chargen => self.chargen = Some(path),
+        }
+    }
+
+    /// Finds a ROM image file. Absolute paths and paths that exist relative to
+    /// the current directory are used as they are; otherwise, the search path
+    /// is consulted.
+    fn resolve(&self, kind: RomKind, path: &Path) -> Result<PathBuf, RomError> {
+        if path.is_absolute() || path.exists() {
+            return Ok(path.to_owned());
+        }
+        return self
+            .search_path
+            .iter()
+            .map(|dir| dir.join(path))
+            .find(|candidate| candidate.exists())
+            .ok_or_else(|| RomError::NotFound {
+                kind,
+                path: path.to_owned(),
+                search_path: self.search_path.clone(),
+            });
+    }
+}
+
+/// A complete set of system ROM images.
+#[derive(Debug, Clone)]
+pub struct RomSet {
+    images: EnumMap<RomKind, Vec<u8>>,
+}
+
+impl RomSet {
+    /// Loads the ROM images that are shipped with the emulator.
+    pub fn builtin() -> Result<Self, RomError> {
+        let rom_dir = Path::new(env!("OUT_DIR")).join("roms");
+        let mut images = EnumMap::<RomKind, Vec<u8>>::default();
+        for (kind, image) in &mut images {
+            *image = read_rom_file(kind, &rom_dir.join(kind.builtin_file_name()))?;
+        }
+        return Self::new(images);
+    }
+
+    /// Loads the ROM images specified by a given configuration, falling back
+    /// to the built-in ones. Fails if any of the images can't be found, has a
+    /// wrong size, or doesn't match its expected checksum.
+    pub fn load(config: &RomConfig) -> Result<Self, RomError> {
+        let mut images = Self::builtin()?.images;
+        for (kind, image) in &mut images {
+            let path = match config.path(kind) {
+                Some(path) => config.resolve(kind, path)?,
+                None => continue,
+            };
+            *image = read_rom_file(kind, &path)?;
+            if image.len() != kind.size() {
+                return Err(RomError::WrongSize {
+                    kind,
+                    path,
+                    size: image.len(),
+                });
+            }
+            if let Some(expected) = config.checksums.checksum(kind) {
+                let actual = crc32fast::hash(image);
+                if actual != expected {
+                    return Err(RomError::WrongChecksum {
+                        kind,
+                        path,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+        return Self::new(images);
+    }
+
+    /// Creates a ROM set from raw images. Fails if any of them has a wrong
+    /// size.
+    pub fn new(images: EnumMap<RomKind, Vec<u8>>) -> Result<Self, RomError> {
+        for (kind, image) in &images {
+            if image.len() != kind.size() {
+                return Err(RomError::WrongSize {
+                    kind,
+                    path: PathBuf::new(),
+                    size: image.len(),
+                });
+            }
+        }
+        return Ok(Self { images });
+    }
+
+    pub fn image(&self, kind: RomKind) -> &[u8] {
+        &self.images[kind]
+    }
+}
+
+fn read_rom_file(kind: RomKind, path: &Path) -> Result<Vec<u8>, RomError> {
+    return fs::read(path).map_err(|source| RomError::IoError {
+        kind,
+        path: path.to_owned(),
+        source,
+    });
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum RomError {
+    #[error("Unable to read {kind} ROM image {}: {source}", path.display())]
+    IoError {
+        kind: RomKind,
+        path: PathBuf,
+        source: io::Error,
+    },
+
+    #[error("Unable to read ROM configuration {}: {source}", path.display())]
+    ConfigIoError { path: PathBuf, source: io::Error },
+
+    #[error("Invalid ROM configuration: {0}")]
+    ConfigError(#[from] toml::de::Error),
+
+    #[error(
+        "{kind} ROM image {} not found; searched in: {}",
+        path.display(),
+        search_path.iter().map(|dir| dir.display().to_string()).collect::<Vec<_>>().join(", ")
+    )]
+    NotFound {
+        kind: RomKind,
+        path: PathBuf,
+        search_path: Vec<PathBuf>,
+    },
+
+    #[error(
+        "{kind} ROM image {} has {size} bytes, expected {}",
+        path.display(),
+        kind.size()
+    )]
+    WrongSize {
+        kind: RomKind,
+        path: PathBuf,
+        size: usize,
+    },
+
+    #[error(
+        "{kind} ROM image {} has CRC32 {actual:08X}, expected {expected:08X}",
+        path.display()
+    )]
+    WrongChecksum {
+        kind: RomKind,
+        path: PathBuf,
+        expected: u32,
+        actual: u32,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::assert_matches::assert_matches;
+    use tempfile::TempDir;
+
+    /// Creates a temporary directory with ROM image files of given sizes.
+    fn temp_rom_dir(files: &[(&str, usize)]) -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for (file_name, size) in files {
+            fs::write(dir.path().join(file_name), vec![0xEA; *size]).unwrap();
+        }
+        return dir;
+    }
+
+    #[test]
+    fn loads_builtin_roms() {
+        let roms = RomSet::builtin().unwrap();
+        assert_eq!(roms.image(RomKind::Basic).len(), 0x2000);
+        assert_eq!(roms.image(RomKind::Kernal).len(), 0x2000);
+        assert_eq!(roms.image(RomKind::Chargen).len(), 0x1000);
+    }
+
+    #[test]
+    fn loads_custom_roms() {
+        let dir = temp_rom_dir(&[("jiffydos.bin", 0x2000)]);
+        let config = RomConfig {
+            kernal: Some("jiffydos.bin".into()),
+            search_path: vec!["/nonexistent".into(), dir.path().to_owned()],
+            checksums: RomChecksums {
+                kernal: Some(crc32fast::hash(&[0xEA; 0x2000])),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let roms = RomSet::load(&config).unwrap();
+        assert_eq!(roms.image(RomKind::Kernal), &[0xEA; 0x2000]);
+        assert_eq!(
+            roms.image(RomKind::Basic),
+            RomSet::builtin().unwrap().image(RomKind::Basic)
+        );
+
+        let config = RomConfig {
+            checksums: RomChecksums {
+                kernal: Some(0x12345678),
+                ..Default::default()
+            },
+            ..config
+        };
+        assert_matches!(
+            RomSet::load(&config),
+            Err(RomError::WrongChecksum {
+                kind: RomKind::Kernal,
+                expected: 0x12345678,
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn reports_invalid_roms() {
+        let dir = temp_rom_dir(&[("basic.bin", 0x1000)]);
+        let mut config = RomConfig::default();
+        config.set_path(RomKind::Basic, dir.path().join("basic.bin"));
+        assert_matches!(
+            RomSet::load(&config),
+            Err(RomError::WrongSize {
+                kind: RomKind::Basic,
+                size: 0x1000,
+                ..
+            })
+        );
+
+        config.set_path(RomKind::Basic, "missing.bin".into());
+        config.search_path = vec![dir.path().to_owned()];
+        let error = RomSet::load(&config).unwrap_err();
+        assert_matches!(
+            error,
+            RomError::NotFound {
+                kind: RomKind::Basic,
+                ..
+            }
+        );
+        assert!(error
+            .to_string()
+            .starts_with("BASIC ROM image missing.bin not found"));
+    }
+
+    #[test]
+    fn reads_config_files() {
+        let dir = temp_rom_dir(&[("basic.bin", 0x2000)]);
+        let config_path = dir.path().join("roms.toml");
+        fs::write(
+            &config_path,
+            r#"
+                basic = "basic.bin"
+                kernal = "jiffydos.bin"
+                search_path = ["roms"]
+
+                [checksums]
+                kernal = 0xDBE3E7C7
+            "#,
+        )
+        .unwrap();
+        let config = RomConfig::read(&config_path).unwrap();
+        // Files that exist next to the configuration are taken from there,
+        // others are looked up in the search path.
+        assert_eq!(
+            config.path(RomKind::Basic),
+            Some(dir.path().join("basic.bin").as_path())
+        );
+        assert_eq!(
+            config.path(RomKind::Kernal),
+            Some(Path::new("jiffydos.bin"))
+        );
+        assert_eq!(config.path(RomKind::Chargen), None);
+        assert_eq!(config.search_path, vec![dir.path().join("roms")]);
+        assert_eq!(config.checksums.checksum(RomKind::Kernal), Some(0xDBE3E7C7));
+
+        fs::write(&config_path, "kernel = \"typo.bin\"").unwrap();
+        assert_matches!(RomConfig::read(&config_path), Err(RomError::ConfigError(_)));
+    }
+}
@@ -1,3 +1,8 @@
+use crate::controllers::Pot;
+use common::debugger::symbols::RegisterNames;
+use enum_map::EnumMap;
+use ya6502::cpu::ChipRegister;
+use ya6502::cpu::ChipState;
 use ya6502::memory::Inspect;
 use ya6502::memory::Memory;
 use ya6502::memory::Read;
@@ -6,26 +11,69 @@ use ya6502::memory::ReadResult;
 use ya6502::memory::Write;
 use ya6502::memory::WriteResult;
 
-/// A 6581 SID chip. So far, it's just a dumb address space that doesn't do
-/// anything.
+/// A 6581 SID chip. So far, it doesn't produce any sound; it only keeps track
+/// of its register values, so that they can be inspected, and reports the
+/// values of its analog inputs.
 #[derive(Debug)]
-pub struct Sid {}
+pub struct Sid {
+    registers: [u8; 0x20],
+    pot_values: EnumMap<Pot, u8>,
+}
 
 impl Sid {
     pub fn new() -> Self {
-        Sid {}
+        Sid {
+            registers: [0; 0x20],
+            // No paddles are connected, so the potentiometer inputs stay high.
+            pot_values: EnumMap::from_array([0xFF; 2]),
+        }
+    }
+
+    /// Sets the value that will be reported by the POTX or POTY register. The
+    /// value depends on the resistance between the analog input and +5V.
+    pub fn set_pot_value(&mut self, pot: Pot, value: u8) {
+        self.pot_values[pot] = value;
+    }
+
+    /// Reports the values of SID registers for debugging purposes. Write-only
+    /// registers are reported as they were last written.
+    pub fn chip_state(&self) -> Result<ChipState, ReadError> {
+        Ok(ChipState {
+            name: "SID".to_string(),
+            registers: REGISTER_NAMES
+                .iter()
+                .zip(0..)
+                .map(|(&name, address)| Ok(ChipRegister::new(name, self.inspect(address)?)))
+                .collect::<Result<_, ReadError>>()?,
+        })
+    }
+}
+
+impl Default for Sid {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Write for Sid {
-    fn write(&mut self, _address: u16, _value: u8) -> WriteResult {
+    fn write(&mut self, address: u16, value: u8) -> WriteResult {
+        self.registers[(address & 0x1F) as usize] = value;
         Ok(())
     }
 }
 
 impl Inspect for Sid {
     fn inspect(&self, address: u16) -> ReadResult {
-        Err(ReadError { address })
+        match address & 0x1F {
+            registers::POTX => Ok(self.pot_values[Pot::X]),
+            registers::POTY => Ok(self.pot_values[Pot::Y]),
+            // Voice 3 oscillator and envelope generator aren't emulated yet.
+            registers::OSC3 | registers::ENV3 => Ok(0),
+            // The remaining registers are write-only. A real chip would return
+            // whatever is left on the data bus, but it's much more useful to
+            // see the last value written to a given register.
+            register => Ok(self.registers[register as usize]),
+        }
     }
 }
 
@@ -36,3 +84,71 @@ impl Read for Sid {
 }
 
 impl Memory for Sid {}
+
+/// Returns names of the SID registers, used to annotate the disassembly. The
+/// register addresses are relative to `base`.
+pub fn register_names(base: u16) -> RegisterNames {
+    RegisterNames::new().with_registers(REGISTER_NAMES.into_iter().zip(base..))
+}
+
+/// Names of the SID registers, as used in the Commodore 64 Programmer's
+/// Reference Guide.
+const REGISTER_NAMES: [&str; 0x1D] = [
+    "FRELO1", "FREHI1", "PWLO1", "PWHI1", "VCREG1", "ATDCY1", "SUREL1", "FRELO2", "FREHI2",
+    "PWLO2", "PWHI2", "VCREG2", "ATDCY2", "SUREL2", "FRELO3", "FREHI3", "PWLO3", "PWHI3", "VCREG3",
+    "ATDCY3", "SUREL3", "CUTLO", "CUTHI", "RESON", "SIGVOL", "POTX", "POTY", "RANDOM", "ENV3",
+];
+
+mod registers {
+    pub const POTX: u16 = 0x19;
+    pub const POTY: u16 = 0x1A;
+    pub const OSC3: u16 = 0x1B;
+    pub const ENV3: u16 = 0x1C;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registers() {
+        let mut sid = Sid::new();
+        sid.write(0xD400, 0x12).unwrap();
+        sid.write(0xD418, 0x0F).unwrap();
+        sid.write(0xD41B, 0x34).unwrap();
+        assert_eq!(sid.inspect(0xD400).unwrap(), 0x12);
+        assert_eq!(sid.inspect(0xD418).unwrap(), 0x0F);
+        assert_eq!(sid.inspect(0xD419).unwrap(), 0xFF);
+        assert_eq!(sid.read(0xD41B).unwrap(), 0);
+
+        // Registers are mirrored every 32 bytes.
+        assert_eq!(sid.inspect(0xD420).unwrap(), 0x12);
+        assert_eq!(sid.inspect(0xD7F8).unwrap(), 0x0F);
+    }
+
+    #[test]
+    fn pot_registers() {
+        let mut sid = Sid::new();
+        sid.set_pot_value(Pot::X, 0x12);
+        sid.set_pot_value(Pot::Y, 0x34);
+        assert_eq!(sid.read(0xD419).unwrap(), 0x12);
+        assert_eq!(sid.read(0xD41A).unwrap(), 0x34);
+
+        // Writes don't affect the analog inputs.
+        sid.write(0xD419, 0xAB).unwrap();
+        assert_eq!(sid.read(0xD419).unwrap(), 0x12);
+    }
+
+    #[test]
+    fn reports_chip_state() {
+        let mut sid = Sid::new();
+        sid.write(0xD404, 0x21).unwrap();
+        sid.write(0xD418, 0x0F).unwrap();
+        let state = sid.chip_state().unwrap();
+        assert_eq!(state.name, "SID");
+        assert_eq!(state.registers.len(), 0x1D);
+        assert_eq!(state.registers[0x04], ChipRegister::new("VCREG1", 0x21u8));
+        assert_eq!(state.registers[0x18], ChipRegister::new("SIGVOL", 0x0Fu8));
+        assert_eq!(state.registers[0x19], ChipRegister::new("POTX", 0xFFu8));
+    }
+}
@@ -1,11 +1,23 @@
 use std::{io, vec};
 
 /// A Commodore 1530 Datasette device emulator. It is capable of playing a
-/// series of pulses that represent tape data.
+/// series of pulses that represent tape data, or recording them from the
+/// cassette write line.
 pub struct Datasette {
     tape: vec::IntoIter<u32>,
     tick_countdown: Option<u32>,
     play_pressed: bool,
+    recording: Option<Recording>,
+}
+
+/// Pulses recorded from the cassette write line. Each pulse is a full period
+/// of the signal, measured between its rising edges.
+struct Recording {
+    tape: Tape,
+    write_line: bool,
+    /// Number of cycles since the last rising edge, or `None` if no edge has
+    /// been recorded yet.
+    cycles_since_edge: Option<u32>,
 }
 
 #[derive(PartialEq, Debug)]
@@ -29,16 +41,38 @@ impl Datasette {
             tape: tape.into_iter(),
             tick_countdown: None,
             play_pressed: false,
+            recording: None,
+        }
+    }
+
+    /// Creates a new `Datasette` with a blank tape inserted. Pressing play
+    /// makes it record the signal from the write line; the recorded tape can
+    /// be saved using the [`write_tap_file`] function.
+    pub fn for_recording() -> Self {
+        Datasette {
+            recording: Some(Recording {
+                tape: vec![],
+                write_line: false,
+                cycles_since_edge: None,
+            }),
+            ..Self::new(vec![])
         }
     }
 
-    pub fn tick(&mut self, motor_on: bool) -> TickResult {
+    pub fn tick(&mut self, motor_on: bool, write_line: bool) -> TickResult {
         if !(self.play_pressed && motor_on) {
             return TickResult {
                 button_pressed: self.play_pressed,
                 pulse: false,
             };
         }
+        if let Some(recording) = &mut self.recording {
+            recording.record(write_line);
+            return TickResult {
+                button_pressed: true,
+                pulse: false,
+            };
+        }
         self.tick_countdown = self
             .tick_countdown
             .or_else(|| self.tape.next())
@@ -57,6 +91,29 @@ impl Datasette {
     pub fn set_play_pressed(&mut self, pressed: bool) {
         self.play_pressed = pressed;
     }
+
+    /// Returns pulses recorded so far, or `None` if the datasette hasn't been
+    /// created for recording.
+    pub fn recorded_tape(&self) -> Option<&[u32]> {
+        self.recording
+            .as_ref()
+            .map(|recording| recording.tape.as_slice())
+    }
+}
+
+impl Recording {
+    fn record(&mut self, write_line: bool) {
+        if let Some(cycles) = &mut self.cycles_since_edge {
+            *cycles += 1;
+        }
+        if write_line && !self.write_line {
+            if let Some(cycles) = self.cycles_since_edge {
+                self.tape.push(cycles);
+            }
+            self.cycles_since_edge = Some(0);
+        }
+        self.write_line = write_line;
+    }
 }
 
 /// Reads a TAP file from the given reader and returns a vector of pulses. TAP
@@ -113,6 +170,27 @@ pub fn read_tap_file(mut reader: impl io::Read) -> Result<Vec<u32>, TapFileError
     return Ok(pulses);
 }
 
+/// Writes a TAP file in format version 1. Pulses that don't fit in a single
+/// byte are stored as exact cycle counts.
+pub fn write_tap_file(mut writer: impl io::Write, pulses: &[u32]) -> io::Result<()> {
+    const MAX_PULSE: u32 = 0xFF_FFFF;
+    let mut data = Vec::with_capacity(pulses.len());
+    for &pulse in pulses {
+        match pulse / 8 {
+            1..=0xFF => data.push((pulse / 8) as u8),
+            _ => {
+                data.push(0);
+                data.extend_from_slice(&pulse.min(MAX_PULSE).to_le_bytes()[..3]);
+            }
+        }
+    }
+    writer.write_all("C64-TAPE-RAW".as_bytes())?;
+    writer.write_all(&[1, 0, 0, 0])?;
+    writer.write_all(&(data.len() as u32).to_le_bytes())?;
+    writer.write_all(&data)?;
+    return writer.flush();
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum TapFileError {
     #[error("I/O error: {0}")]
@@ -138,7 +216,7 @@ mod tests {
         let mut ds = Datasette::new(vec![]);
         ds.set_play_pressed(true);
         assert_eq!(
-            ds.tick(true),
+            ds.tick(true, false),
             TickResult {
                 button_pressed: true,
                 pulse: false
@@ -150,7 +228,7 @@ mod tests {
     fn playing_non_empty_tape() {
         let mut ds = Datasette::new(vec![3, 2]);
         ds.set_play_pressed(true);
-        let results: Vec<_> = std::iter::repeat_with(move || ds.tick(true))
+        let results: Vec<_> = std::iter::repeat_with(move || ds.tick(true, false))
             .take(6)
             .collect();
         assert_eq!(
@@ -168,14 +246,14 @@ mod tests {
         let mut ds = Datasette::new(vec![1]);
         ds.set_play_pressed(true);
         assert_eq!(
-            ds.tick(false),
+            ds.tick(false, false),
             TickResult {
                 button_pressed: true,
                 pulse: false,
             }
         );
         assert_eq!(
-            ds.tick(true),
+            ds.tick(true, false),
             TickResult {
                 button_pressed: true,
                 pulse: true,
@@ -188,7 +266,7 @@ mod tests {
         let mut ds = Datasette::new(vec![1]);
         ds.set_play_pressed(false);
         assert_eq!(
-            ds.tick(true),
+            ds.tick(true, false),
             TickResult {
                 button_pressed: false,
                 pulse: false,
@@ -196,7 +274,7 @@ mod tests {
         );
         ds.set_play_pressed(true);
         assert_eq!(
-            ds.tick(true),
+            ds.tick(true, false),
             TickResult {
                 button_pressed: true,
                 pulse: true,
@@ -204,6 +282,48 @@ mod tests {
         );
     }
 
+    #[test]
+    fn recording() {
+        let mut ds = Datasette::for_recording();
+        let write_line = [false, true, true, false, true, false, false, true, false];
+        for line in write_line.iter().take(5) {
+            ds.tick(true, *line);
+        }
+        ds.set_play_pressed(true);
+        for line in write_line {
+            assert_eq!(
+                ds.tick(true, line),
+                TickResult {
+                    button_pressed: true,
+                    pulse: false,
+                }
+            );
+            // Stopping the motor pauses the tape.
+            ds.tick(false, line);
+        }
+        assert_eq!(ds.recorded_tape(), Some([3, 3].as_slice()));
+        assert_eq!(Datasette::new(vec![]).recorded_tape(), None);
+    }
+
+    #[test]
+    fn tap_file_writing() {
+        let mut tape = vec![];
+        write_tap_file(&mut tape, &[80, 2047, 2048, 5, 0x1234567]).unwrap();
+        assert_eq!(
+            tape,
+            [
+                "C64-TAPE-RAW".as_bytes(),
+                &[1, 0, 0, 0, 14, 0, 0, 0],
+                &[10, 255, 0, 0, 8, 0, 0, 5, 0, 0, 0, 0xFF, 0xFF, 0xFF],
+            ]
+            .concat()
+        );
+        itertools::assert_equal(
+            read_tap_file(tape.as_slice()).unwrap(),
+            [80, 2040, 2048, 5, 0xFFFFFF],
+        );
+    }
+
     #[test]
     fn tap_file_reading_success() {
         let tape = [
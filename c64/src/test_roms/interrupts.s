@@ -1,8 +1,9 @@
 ; ==============================================================================
 ;
-; This program tests the IRQ wiring by triggering a sequence of interrupts: CIA1
-; timer -> CIA2 timer -> VIC raster. Each CIA timer triggers setting up the next
-; interrupt in sequence, while the VIC interrupt timer triggers border flashing.
+; This program tests the interrupt wiring by triggering a sequence of
+; interrupts: CIA1 timer (IRQ) -> CIA2 timer (NMI) -> VIC raster (IRQ). Each CIA
+; timer triggers setting up the next interrupt in sequence, while the VIC
+; interrupt timer triggers border flashing.
 ;
 ; ==============================================================================
 
@@ -65,10 +66,6 @@ End:        jmp End
             and #%00000001
             bne Cia1Irq                 ; CIA1 IRQ triggered
 
-            lda CIA2_ICR                ; Poll and acknowledge CIA2 IRQ
-            and #%00000001
-            bne Cia2Irq                 ; CIA2 IRQ triggered
-
             lda VIC_IRR                 ; Poll VIC IRQ
             and #%00000001
             bne VicIrq                  ; VIC IRQ triggered
@@ -80,13 +77,23 @@ Cia1Irq:    lda #%00000001              ; Turn off CIA1 IRQ
             sta CIA2_TA
             lda #0
             sta CIA2_TA + 1
-            lda #%10000001              ; CIA2 timer A triggers IRQ
+            lda #%10000001              ; CIA2 timer A triggers NMI
             sta CIA2_ICR
             lda #%00011001              ; Load and start a one-shot trigger
             sta CIA2_CRA
             rti
 
-Cia2Irq:    lda #%00000001              ; Turn off CIA2 IRQ
+VicIrq:     lda #%00000001              ; Acknowledge VIC IRQ
+            sta VIC_IRR
+            inc VIC_BORDERCOLOR         ; Change border color
+            rti
+.endproc
+
+; ------------------------------------------------------------------------------
+
+.proc Nmi
+            lda CIA2_ICR                ; Acknowledge CIA2 NMI
+            lda #%00000001              ; Turn off CIA2 NMI
             sta CIA2_ICR
             ; Set up raster IRQ interrupt for line 13, which should be the 1st
             ; VBLANK line.
@@ -97,11 +104,6 @@ Cia2Irq:    lda #%00000001              ; Turn off CIA2 IRQ
             lda #%00000001
             sta VIC_IMR
             rti
-
-VicIrq:     lda #%00000001              ; Acknowledge VIC IRQ
-            sta VIC_IRR
-            inc VIC_BORDERCOLOR         ; Change border color
-            rti
 .endproc
 
 ; ==============================================================================
@@ -115,6 +117,6 @@ HELLO_LEN = * - Hello;
 
 .segment "VECTORS"
 
-            .word Reset          ; NMI
+            .word Nmi            ; NMI
             .word Reset          ; RESET
             .word Irq            ; IRQ
\ No newline at end of file
@@ -0,0 +1,85 @@
+; ==============================================================================
+;
+; This program tests the IRQ and NMI line arbitration. A VIC raster interrupt
+; (IRQ) and a CIA2 timer (NMI) both fire once per frame, and the CIA2 timer is
+; started from the IRQ handler, so that the NMI keeps arriving at about the same
+; time as the IRQ. Both handlers count the interrupts in the zero page.
+;
+; ==============================================================================
+
+.include "c64.inc"
+.include "common.inc"
+
+; Number of CPU cycles in a single frame, as emulated.
+FRAME_CYCLES = 65 * 262
+
+; Interrupt counters, inspected by the test.
+IRQ_COUNT = $F0
+NMI_COUNT = $F1
+
+; ==============================================================================
+
+.zeropage
+
+.import Init
+
+; ==============================================================================
+
+.code
+
+Reset:      sei
+            jsr Init
+            lda #0
+            sta IRQ_COUNT
+            sta NMI_COUNT
+
+            lda #100                    ; Set up raster IRQ for line 100
+            sta VIC_HLINE
+            lda #%00011011
+            sta VIC_CTRL1
+            lda #%00000001
+            sta VIC_IMR
+
+            lda #<(FRAME_CYCLES - 1)    ; Set up CIA2 timer A to underflow
+            sta CIA2_TA                 ; once per frame
+            lda #>(FRAME_CYCLES - 1)
+            sta CIA2_TA + 1
+            lda #%10000001              ; CIA2 timer A triggers NMI
+            sta CIA2_ICR
+
+            cli
+
+End:        jmp End
+
+; ------------------------------------------------------------------------------
+
+.proc Irq
+            pha
+            lda #%00000001              ; Acknowledge VIC IRQ
+            sta VIC_IRR
+            lda IRQ_COUNT
+            bne Count
+            lda #%00010001              ; On the first IRQ, load and start
+            sta CIA2_CRA                ; a continuous CIA2 timer
+Count:      inc IRQ_COUNT
+            pla
+            rti
+.endproc
+
+; ------------------------------------------------------------------------------
+
+.proc Nmi
+            pha
+            lda CIA2_ICR                ; Acknowledge CIA2 NMI
+            inc NMI_COUNT
+            pla
+            rti
+.endproc
+
+; ==============================================================================
+
+.segment "VECTORS"
+
+            .word Nmi            ; NMI
+            .word Reset          ; RESET
+            .word Irq            ; IRQ
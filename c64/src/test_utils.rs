@@ -1,30 +1,27 @@
 #![cfg(test)]
 
-use crate::Cartridge;
-use crate::CartridgeMode;
-use crate::C64;
+use crate::c64::C64;
+use crate::cartridge::Cartridge;
+use crate::cartridge::CartridgeMode;
 use common::app::AppController;
-use common::app::FrameStatus;
 use common::app::Machine;
 use image::RgbaImage;
-use std::error::Error;
 use std::path::Path;
-use ya6502::memory::Rom;
+use ya6502::cpu::CompositeMachine;
+use ya6502::cpu::CpuError;
+use ya6502::cpu::PowerOnState;
+use ya6502::memory::RamPattern;
 
-pub fn next_frame(c64: &mut C64) -> Result<RgbaImage, Box<dyn Error>> {
-    loop {
-        match c64.tick() {
-            Ok(FrameStatus::Pending) => {}
-            Ok(FrameStatus::Complete) => break,
-            Err(e) => {
-                eprintln!("ERROR: {}. Machine halted.", e);
-                eprintln!("{}", c64.cpu());
-                eprintln!("{}", c64.cpu().memory());
-                return Err(e);
-            }
+pub fn next_frame(c64: &mut C64) -> Result<RgbaImage, CpuError> {
+    match c64.run_frame() {
+        Ok(image) => return Ok(image.clone()),
+        Err(e) => {
+            eprintln!("ERROR: {}. Machine halted.", e);
+            eprintln!("{}", c64.cpu());
+            eprintln!("{}", c64.cpu().memory());
+            return Err(e);
         }
     }
-    return Ok(c64.frame_image().clone());
 }
 
 pub fn assert_current_frame(
@@ -45,11 +42,10 @@ pub fn read_test_rom(name: &str) -> Vec<u8> {
 }
 
 pub fn c64_with_cartridge_uninitialized(file_name: &str) -> C64 {
-    let mut c64 = C64::new().unwrap();
-    c64.set_cartridge(Some(Cartridge {
-        mode: CartridgeMode::Ultimax,
-        rom: Rom::new(&read_test_rom(file_name)).unwrap(),
-    }));
+    let mut c64 = C64::new(PowerOnState::Seeded(0), RamPattern::default()).unwrap();
+    c64.set_cartridge(Some(Box::new(
+        Cartridge::from_raw_image(CartridgeMode::Ultimax, &read_test_rom(file_name)).unwrap(),
+    )));
     c64.reset();
     return c64;
 }
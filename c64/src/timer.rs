@@ -1,9 +1,48 @@
-/// A CIA timer
+/// A CIA timer. It only knows how to count; the CIA chip decides which signal
+/// the timer counts, as selected by [`Timer::input`].
 #[derive(Default, Debug)]
 pub struct Timer {
     control: u8,
     latch: u16,
     counter: u16,
+    /// Output level in the toggle mode. It's inverted on each underflow and
+    /// set high when the timer starts.
+    toggle_output: bool,
+    /// Output level in the pulse mode, high for a single cycle after an
+    /// underflow.
+    pulse_output: bool,
+    /// Number of cycles left until a timer that has just been started begins
+    /// counting.
+    start_delay: u8,
+    /// Number of cycles left until a forced load transfers the latch to the
+    /// counter.
+    load_delay: u8,
+    /// Set by [`Timer::clock`] for cycles in which the counter doesn't count,
+    /// either because the timer is starting or because it's being loaded.
+    counting_inhibited: bool,
+}
+
+/// Number of cycles between starting the timer and its first count. The CIA
+/// passes the START bit through a pipeline, so the counter holds its value for
+/// the cycle of the write and the next one.
+const START_DELAY: u8 = 2;
+
+/// Number of cycles between writing the LOAD bit and the transfer of the latch
+/// to the counter. The counter doesn't count in the cycle in which it's loaded.
+const LOAD_DELAY: u8 = 2;
+
+/// A signal counted by a timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    /// The system clock (Φ2).
+    SystemClock,
+    /// Rising edges of the CNT pin.
+    Cnt,
+    /// Underflows of timer A. Only available for timer B.
+    TimerA,
+    /// Underflows of timer A that occur while the CNT pin is high. Only
+    /// available for timer B.
+    TimerAWithCnt,
 }
 
 impl Timer {
@@ -13,23 +52,57 @@ impl Timer {
         self.control & !flags::LOAD
     }
 
-    /// Writes to the control register.
-    pub fn set_control(&mut self, value: u8) -> Result<(), ()> {
-        // Not all modes are available just yet.
-        if value & !(flags::START | flags::LOAD | flags::RUNMODE) != 0 {
-            return Err(());
-        }
+    /// Writes to the control register. Bits that belong to other CIA
+    /// functions (TOD frequency, serial port mode, TOD alarm) should be
+    /// cleared, and bit 6 may only be used by timer B. Just like on the real
+    /// chip, starting and loading the timer take effect after a delay; see
+    /// [`Timer::clock`].
+    pub fn set_control(&mut self, value: u8) {
+        let starting = value & flags::START != 0 && self.control & flags::START == 0;
         self.control = value;
+        if starting {
+            self.start();
+        }
         if self.control & flags::LOAD != 0 {
-            self.counter = self.latch;
+            self.load_delay = LOAD_DELAY;
+        }
+    }
+
+    /// Returns the signal that the timer counts.
+    pub fn input(&self) -> Input {
+        match self.control & flags::INMODE {
+            flags::INMODE_SYSTEM_CLOCK => Input::SystemClock,
+            flags::INMODE_CNT => Input::Cnt,
+            flags::INMODE_TIMER_A => Input::TimerA,
+            _ => Input::TimerAWithCnt,
         }
-        Ok(())
     }
 
     pub fn set_latch(&mut self, value: u16) {
         self.latch = value;
     }
 
+    /// Writes the high byte of the latch. If the timer is stopped, the latch
+    /// is also transferred to the counter, and in the one-shot mode, the timer
+    /// starts.
+    pub fn set_latch_hi(&mut self, value: u8) {
+        self.latch = self.latch & 0xFF | (value as u16) << 8;
+        if self.control & flags::START == 0 {
+            self.counter = self.latch;
+            if self.control & flags::RUNMODE == flags::RUNMODE_ONE_SHOT {
+                self.control |= flags::START;
+                self.start();
+            }
+        }
+    }
+
+    /// Sets the toggle output high and lets the timer begin counting after
+    /// [`START_DELAY`].
+    fn start(&mut self) {
+        self.toggle_output = true;
+        self.start_delay = START_DELAY;
+    }
+
     pub fn latch(&self) -> u16 {
         self.latch
     }
@@ -38,9 +111,44 @@ impl Timer {
         self.counter
     }
 
-    /// Performs a tick, returns `true` on underflow
+    /// Returns `true` if the timer output should drive a port B line.
+    pub fn output_enabled(&self) -> bool {
+        self.control & flags::PBON != 0
+    }
+
+    /// Returns the level of the timer output, as seen on the port B line.
+    pub fn output(&self) -> bool {
+        if self.control & flags::OUTMODE == flags::OUTMODE_TOGGLE {
+            self.toggle_output
+        } else {
+            self.pulse_output
+        }
+    }
+
+    /// Advances the timer to the next system clock cycle: ends the output
+    /// pulse and moves pending starts and forced loads through the pipeline.
+    /// Should be called on each cycle before counting.
+    pub fn clock(&mut self) {
+        self.pulse_output = false;
+        self.counting_inhibited = false;
+        if self.start_delay > 0 {
+            self.start_delay -= 1;
+            self.counting_inhibited = true;
+        }
+        if self.load_delay > 0 {
+            self.load_delay -= 1;
+            if self.load_delay == 0 {
+                self.counter = self.latch;
+                self.counting_inhibited = true;
+            }
+        }
+    }
+
+    /// Counts a single pulse of the input signal, returns `true` on
+    /// underflow. Does nothing if the timer is stopped, or if it's still
+    /// starting or being loaded.
     pub fn tick(&mut self) -> bool {
-        if self.control & flags::START != 0 {
+        if self.control & flags::START != 0 && !self.counting_inhibited {
             if self.counter > 0 {
                 self.counter -= 1;
             } else {
@@ -48,6 +156,8 @@ impl Timer {
                 if self.control & flags::RUNMODE == flags::RUNMODE_ONE_SHOT {
                     self.control &= !flags::START;
                 }
+                self.toggle_output = !self.toggle_output;
+                self.pulse_output = true;
                 return true;
             }
         }
@@ -57,46 +167,67 @@ impl Timer {
 
 pub mod flags {
     pub const START: u8 = 1 << 0;
+    pub const PBON: u8 = 1 << 1;
+    pub const OUTMODE: u8 = 1 << 2;
     pub const RUNMODE: u8 = 1 << 3;
     pub const LOAD: u8 = 1 << 4;
+    pub const INMODE: u8 = 0b0110_0000;
 
+    pub const OUTMODE_PULSE: u8 = 0;
+    pub const OUTMODE_TOGGLE: u8 = OUTMODE;
     pub const RUNMODE_ONE_SHOT: u8 = RUNMODE;
     pub const RUNMODE_CONTINUOUS: u8 = 0;
+    pub const INMODE_SYSTEM_CLOCK: u8 = 0b0000_0000;
+    pub const INMODE_CNT: u8 = 0b0010_0000;
+    pub const INMODE_TIMER_A: u8 = 0b0100_0000;
+    pub const INMODE_TIMER_A_WITH_CNT: u8 = 0b0110_0000;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Runs the timer for a single system clock cycle.
+    fn cycle(timer: &mut Timer) -> bool {
+        timer.clock();
+        return timer.tick();
+    }
+
     #[test]
     fn loading_and_starting() {
         use super::flags::*;
 
         let mut timer = Timer::default();
         timer.set_latch(1234);
-        timer.set_control(0).unwrap(); // Don't load or start yet
+        timer.set_control(0); // Don't load or start yet
 
-        timer.tick();
+        cycle(&mut timer);
         assert_eq!(timer.control(), 0);
         assert_eq!(timer.counter(), 0);
 
         // Load, but don't start yet.
-        timer.set_control(LOAD).unwrap();
+        timer.set_control(LOAD);
         // The LOAD flag of the control register should be ignored while reading.
         assert_eq!(timer.control(), 0);
+        // The latch is transferred to the counter in the cycle after the write.
+        cycle(&mut timer);
+        assert_eq!(timer.counter(), 0);
+        cycle(&mut timer);
         assert_eq!(timer.counter(), 1234);
 
-        timer.tick();
+        cycle(&mut timer);
         assert_eq!(timer.counter(), 1234);
 
-        // OK, now start it.
-        timer.set_control(START).unwrap();
+        // OK, now start it. It takes two cycles before it starts counting.
+        timer.set_control(START);
         assert_eq!(timer.control(), START);
+        cycle(&mut timer);
+        cycle(&mut timer);
         assert_eq!(timer.counter(), 1234);
 
-        timer.tick();
+        cycle(&mut timer);
         assert_eq!(timer.counter(), 1233);
-        timer.tick();
+        cycle(&mut timer);
         assert_eq!(timer.counter(), 1232);
     }
 
@@ -106,33 +237,148 @@ mod tests {
 
         let mut timer = Timer::default();
         timer.set_latch(4);
-        timer
-            .set_control(LOAD | START | RUNMODE_CONTINUOUS)
-            .unwrap();
+        timer.set_control(LOAD | START | RUNMODE_CONTINUOUS);
 
+        assert_eq!(cycle(&mut timer), false);
+        assert_eq!(cycle(&mut timer), false);
         assert_eq!(timer.counter(), 4);
-        assert_eq!(timer.tick(), false);
-        assert_eq!(timer.tick(), false);
-        assert_eq!(timer.tick(), false);
-        assert_eq!(timer.tick(), false);
+        assert_eq!(cycle(&mut timer), false);
+        assert_eq!(cycle(&mut timer), false);
+        assert_eq!(cycle(&mut timer), false);
+        assert_eq!(cycle(&mut timer), false);
         assert_eq!(timer.counter(), 0);
 
-        assert_eq!(timer.tick(), true);
+        assert_eq!(cycle(&mut timer), true);
         assert_eq!(timer.counter(), 4);
-        assert_eq!(timer.tick(), false);
+        assert_eq!(cycle(&mut timer), false);
         assert_eq!(timer.counter(), 3);
 
-        timer.set_control(LOAD | START | RUNMODE_ONE_SHOT).unwrap();
-        assert_eq!(timer.tick(), false);
-        assert_eq!(timer.tick(), false);
-        assert_eq!(timer.tick(), false);
+        // A running timer keeps counting until the latch gets loaded.
+        timer.set_control(LOAD | START | RUNMODE_ONE_SHOT);
+        assert_eq!(cycle(&mut timer), false);
+        assert_eq!(timer.counter(), 2);
+        assert_eq!(cycle(&mut timer), false);
+        assert_eq!(timer.counter(), 4);
+        assert_eq!(cycle(&mut timer), false);
+        assert_eq!(cycle(&mut timer), false);
+        assert_eq!(cycle(&mut timer), false);
         assert_eq!(timer.counter(), 1);
-        assert_eq!(timer.tick(), false);
+        assert_eq!(cycle(&mut timer), false);
         assert_eq!(timer.counter(), 0);
 
-        assert_eq!(timer.tick(), true);
+        assert_eq!(cycle(&mut timer), true);
         assert_eq!(timer.counter(), 4);
-        assert_eq!(timer.tick(), false);
+        assert_eq!(cycle(&mut timer), false);
         assert_eq!(timer.counter(), 4);
     }
+
+    #[test]
+    fn restarting_one_shot_timer() {
+        use super::flags::*;
+
+        let mut timer = Timer::default();
+        timer.set_latch_hi(0);
+        timer.set_latch(1);
+        timer.set_control(LOAD | START | RUNMODE_ONE_SHOT);
+        let underflows: Vec<bool> = (0..6).map(|_| cycle(&mut timer)).collect();
+        assert_eq!(underflows, [false, false, false, true, false, false]);
+        assert_eq!(timer.control(), RUNMODE_ONE_SHOT);
+
+        // Starting the timer again also takes two cycles.
+        timer.set_control(START | RUNMODE_ONE_SHOT);
+        let underflows: Vec<bool> = (0..5).map(|_| cycle(&mut timer)).collect();
+        assert_eq!(underflows, [false, false, false, true, false]);
+    }
+
+    #[test]
+    fn loading_latch_while_stopped() {
+        use super::flags::*;
+
+        let mut timer = Timer::default();
+        timer.set_latch(0x0034);
+        timer.set_latch_hi(0x12);
+        assert_eq!(timer.latch(), 0x1234);
+        assert_eq!(timer.counter(), 0x1234);
+
+        // A running timer keeps counting from the current value.
+        timer.set_control(START);
+        for _ in 0..3 {
+            cycle(&mut timer);
+        }
+        timer.set_latch_hi(0x56);
+        assert_eq!(timer.latch(), 0x5634);
+        assert_eq!(timer.counter(), 0x1233);
+    }
+
+    #[test]
+    fn starting_one_shot_timer_with_latch_hi() {
+        use super::flags::*;
+
+        let mut timer = Timer::default();
+        timer.set_control(RUNMODE_ONE_SHOT);
+        timer.set_latch(0x0001);
+        assert_eq!(timer.control(), RUNMODE_ONE_SHOT);
+        timer.set_latch_hi(0x00);
+        assert_eq!(timer.counter(), 1);
+        assert_eq!(timer.control(), START | RUNMODE_ONE_SHOT);
+        let underflows: Vec<bool> = (0..5).map(|_| cycle(&mut timer)).collect();
+        assert_eq!(underflows, [false, false, false, true, false]);
+        assert_eq!(timer.control(), RUNMODE_ONE_SHOT);
+    }
+
+    #[test]
+    fn input_modes() {
+        use super::flags::*;
+
+        let mut timer = Timer::default();
+        assert_eq!(timer.input(), Input::SystemClock);
+        timer.set_control(INMODE_CNT);
+        assert_eq!(timer.input(), Input::Cnt);
+        timer.set_control(INMODE_TIMER_A);
+        assert_eq!(timer.input(), Input::TimerA);
+        timer.set_control(INMODE_TIMER_A_WITH_CNT);
+        assert_eq!(timer.input(), Input::TimerAWithCnt);
+        assert_eq!(timer.control(), INMODE_TIMER_A_WITH_CNT);
+    }
+
+    #[test]
+    fn pulse_output() {
+        use super::flags::*;
+
+        let mut timer = Timer::default();
+        timer.set_latch(1);
+        timer.set_control(LOAD | START | PBON | OUTMODE_PULSE);
+        assert!(timer.output_enabled());
+        assert!(!timer.output());
+        // Loading and starting.
+        cycle(&mut timer);
+        cycle(&mut timer);
+        assert!(!timer.output());
+        cycle(&mut timer);
+        assert!(!timer.output());
+        cycle(&mut timer);
+        assert!(timer.output());
+        cycle(&mut timer);
+        assert!(!timer.output());
+    }
+
+    #[test]
+    fn toggle_output() {
+        use super::flags::*;
+
+        let mut timer = Timer::default();
+        timer.set_latch(1);
+        timer.set_control(LOAD | OUTMODE_TOGGLE);
+        assert!(!timer.output_enabled());
+        // Starting the timer sets the output high.
+        timer.set_control(START | OUTMODE_TOGGLE);
+        assert!(timer.output());
+        let outputs: Vec<bool> = (0..8)
+            .map(|_| {
+                cycle(&mut timer);
+                timer.output()
+            })
+            .collect();
+        assert_eq!(outputs, [true, true, true, false, false, true, true, false]);
+    }
 }
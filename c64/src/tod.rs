@@ -0,0 +1,210 @@
+/// A CIA time-of-day clock. It counts tenths of seconds, seconds, minutes, and
+/// hours (in 12-hour format with an AM/PM flag), all of them in BCD, and can
+/// trigger an alarm at a given time. The clock is driven by an external signal
+/// at the power line frequency.
+#[derive(Debug, Default)]
+pub struct TimeOfDay {
+    time: Time,
+    alarm: Time,
+    /// Reading the hours register freezes the time seen by the CPU until the
+    /// tenths register is read, so that the reading is consistent.
+    latched_time: Option<Time>,
+    /// Writing the hours register stops the clock until the tenths register is
+    /// written.
+    stopped: bool,
+    /// Whether the input signal frequency is 50 Hz (as opposed to 60 Hz).
+    fifty_hz: bool,
+    /// Counts input pulses, since a tenth of second is 5 or 6 pulses long.
+    divider: u8,
+}
+
+/// Clock registers: tenths of seconds, seconds, minutes, and hours.
+type Time = [u8; 4];
+
+pub const TENTHS: usize = 0;
+pub const HOURS: usize = 3;
+
+const REGISTER_MASKS: Time = [0x0F, 0x7F, 0x7F, 0x9F];
+const HOURS_PM: u8 = 1 << 7;
+
+impl TimeOfDay {
+    pub fn fifty_hz(&self) -> bool {
+        self.fifty_hz
+    }
+
+    pub fn set_fifty_hz(&mut self, fifty_hz: bool) {
+        self.fifty_hz = fifty_hz;
+    }
+
+    /// Handles a single pulse of the input signal. Returns `true` if the alarm
+    /// went off.
+    pub fn tick(&mut self) -> bool {
+        if self.stopped {
+            return false;
+        }
+        self.divider += 1;
+        if self.divider < self.pulses_per_tenth() {
+            return false;
+        }
+        self.divider = 0;
+        self.advance();
+        return self.time == self.alarm;
+    }
+
+    fn pulses_per_tenth(&self) -> u8 {
+        if self.fifty_hz {
+            5
+        } else {
+            6
+        }
+    }
+
+    fn advance(&mut self) {
+        let [tenths, seconds, minutes, hours] = &mut self.time;
+        if *tenths < 9 {
+            *tenths += 1;
+            return;
+        }
+        *tenths = 0;
+        if *seconds < 0x59 {
+            *seconds = bcd_increment(*seconds);
+            return;
+        }
+        *seconds = 0;
+        if *minutes < 0x59 {
+            *minutes = bcd_increment(*minutes);
+            return;
+        }
+        *minutes = 0;
+        *hours = match *hours & !HOURS_PM {
+            0x11 => (*hours ^ HOURS_PM) + 1,
+            0x12 => (*hours & HOURS_PM) | 0x01,
+            _ => bcd_increment(*hours),
+        };
+    }
+
+    pub fn inspect(&self, register: usize) -> u8 {
+        self.latched_time.unwrap_or(self.time)[register]
+    }
+
+    pub fn read(&mut self, register: usize) -> u8 {
+        if register == HOURS && self.latched_time.is_none() {
+            self.latched_time = Some(self.time);
+        }
+        let value = self.inspect(register);
+        if register == TENTHS {
+            self.latched_time = None;
+        }
+        return value;
+    }
+
+    /// Sets either the current time or the alarm time.
+    pub fn write(&mut self, register: usize, value: u8, alarm: bool) {
+        let value = value & REGISTER_MASKS[register];
+        if alarm {
+            self.alarm[register] = value;
+            return;
+        }
+        self.time[register] = value;
+        match register {
+            HOURS => self.stopped = true,
+            TENTHS => {
+                self.stopped = false;
+                self.divider = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn bcd_increment(value: u8) -> u8 {
+    if value & 0x0F == 9 {
+        (value & 0xF0) + 0x10
+    } else {
+        value + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_time(tod: &mut TimeOfDay, time: Time) {
+        for register in (TENTHS..=HOURS).rev() {
+            tod.write(register, time[register], false);
+        }
+    }
+
+    fn read_time(tod: &mut TimeOfDay) -> Time {
+        let mut time = Time::default();
+        for register in (TENTHS..=HOURS).rev() {
+            time[register] = tod.read(register);
+        }
+        return time;
+    }
+
+    fn tick_tenths(tod: &mut TimeOfDay, n: u32) {
+        for _ in 0..n * tod.pulses_per_tenth() as u32 {
+            tod.tick();
+        }
+    }
+
+    #[test]
+    fn counts_time() {
+        let mut tod = TimeOfDay::default();
+        set_time(&mut tod, [0x8, 0x59, 0x59, 0x11]);
+        tod.set_fifty_hz(true);
+        for _ in 0..4 {
+            tod.tick();
+        }
+        assert_eq!(read_time(&mut tod), [0x8, 0x59, 0x59, 0x11]);
+        tod.tick();
+        assert_eq!(read_time(&mut tod), [0x9, 0x59, 0x59, 0x11]);
+
+        tod.set_fifty_hz(false);
+        tick_tenths(&mut tod, 1);
+        assert_eq!(read_time(&mut tod), [0x0, 0x00, 0x00, 0x12 | HOURS_PM]);
+        tick_tenths(&mut tod, 10 * 60 * 60);
+        assert_eq!(read_time(&mut tod), [0x0, 0x00, 0x00, 0x01 | HOURS_PM]);
+        tick_tenths(&mut tod, 10 * 60 * 60 * 9 + 10 * 61 + 3);
+        assert_eq!(read_time(&mut tod), [0x3, 0x01, 0x01, 0x10 | HOURS_PM]);
+    }
+
+    #[test]
+    fn stops_while_setting_time() {
+        let mut tod = TimeOfDay::default();
+        tod.write(HOURS, 0x05, false);
+        tick_tenths(&mut tod, 5);
+        assert_eq!(read_time(&mut tod), [0x0, 0x00, 0x00, 0x05]);
+        tod.write(TENTHS, 0x02, false);
+        tick_tenths(&mut tod, 5);
+        assert_eq!(read_time(&mut tod), [0x7, 0x00, 0x00, 0x05]);
+    }
+
+    #[test]
+    fn latches_time_while_reading() {
+        let mut tod = TimeOfDay::default();
+        set_time(&mut tod, [0x9, 0x59, 0x59, 0x03]);
+        assert_eq!(tod.read(HOURS), 0x03);
+        tick_tenths(&mut tod, 1);
+        assert_eq!(tod.read(2), 0x59);
+        assert_eq!(tod.read(1), 0x59);
+        assert_eq!(tod.read(TENTHS), 0x9);
+        assert_eq!(read_time(&mut tod), [0x0, 0x00, 0x00, 0x04]);
+    }
+
+    #[test]
+    fn alarm() {
+        let mut tod = TimeOfDay::default();
+        set_time(&mut tod, [0x0, 0x30, 0x00, 0x02]);
+        tod.write(HOURS, 0x02, true);
+        tod.write(2, 0x00, true);
+        tod.write(1, 0x30, true);
+        tod.write(TENTHS, 0x2, true);
+        assert_eq!(read_time(&mut tod), [0x0, 0x30, 0x00, 0x02]);
+
+        let alarms: Vec<bool> = (0..18).map(|_| tod.tick()).collect();
+        assert_eq!(alarms.iter().filter(|alarm| **alarm).count(), 1);
+        assert!(alarms[11]);
+    }
+}
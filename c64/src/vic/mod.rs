@@ -1,7 +1,12 @@
+mod sprite;
 mod tests;
 
+use common::debugger::symbols::RegisterNames;
+use sprite::Sprite;
 use std::cell::RefCell;
 use std::rc::Rc;
+use ya6502::cpu::ChipRegister;
+use ya6502::cpu::ChipState;
 use ya6502::memory::Inspect;
 use ya6502::memory::Memory;
 use ya6502::memory::Read;
@@ -27,10 +32,29 @@ where
     // Registers
     reg_control_1: u8,
     reg_control_2: u8,
+    reg_memory_pointers: u8,
     reg_interrupt: u8,
     reg_interrupt_mask: u8,
     reg_border_color: Color,
     reg_background_color: Color,
+    /// Background colors 1-3. They are not used for rendering yet, since we
+    /// don't support the ECM and multicolor modes.
+    reg_extra_background_colors: [Color; 3],
+    reg_sprite_x: [u8; 8],
+    reg_sprite_y: [u8; 8],
+    reg_sprite_x_msb: u8,
+    reg_sprite_enable: u8,
+    reg_sprite_y_expansion: u8,
+    reg_sprite_priority: u8,
+    reg_sprite_multicolor: u8,
+    reg_sprite_x_expansion: u8,
+    reg_sprite_sprite_collision: u8,
+    reg_sprite_background_collision: u8,
+    reg_sprite_multicolor_0: Color,
+    reg_sprite_multicolor_1: Color,
+    reg_sprite_colors: [Color; 8],
+    reg_light_pen_x: u8,
+    reg_light_pen_y: u8,
 
     // Internal state
     //
@@ -43,6 +67,10 @@ where
     x_counter: usize,
     screen_on: bool,
 
+    /// Screen codes fetched from the screen memory during the last bad line.
+    video_matrix_line: [u8; 40],
+    /// Colors fetched from the color memory during the last bad line.
+    color_line: [Color; 40],
     /// A buffer for graphics byte to be displayed next.
     graphics_buffer: u8,
     /// A buffer for graphics foreground color to be displayed.
@@ -51,6 +79,14 @@ where
     /// graphics pixel by pixel.
     graphics_shifter: u8,
 
+    sprites: [Sprite; 8],
+
+    /// Raw coordinates (X and raster line) of a screen point that the light pen
+    /// is pointed at, or `None` if the light pen doesn't see the screen.
+    light_pen_position: Option<(usize, usize)>,
+    /// Light pen position is latched only once per frame.
+    light_pen_latched: bool,
+
     /// For now, allow one-time initialization of certain registers to 0.
     reg_initialized: [bool; 0x2F],
 }
@@ -67,20 +103,46 @@ where
 
             reg_control_1: 0,
             reg_control_2: 0,
+            // Start with the memory layout set up by KERNAL: screen at $0400,
+            // characters at $1000.
+            reg_memory_pointers: 0x14 | flags::MEMORY_POINTERS_UNUSED,
             reg_interrupt: flags::INTERRUPT_UNUSED,
             reg_interrupt_mask: flags::INTERRUPT_MASK_UNUSED,
             reg_border_color: 0,
             reg_background_color: 0,
+            reg_extra_background_colors: [0; 3],
+            reg_sprite_x: [0; 8],
+            reg_sprite_y: [0; 8],
+            reg_sprite_x_msb: 0,
+            reg_sprite_enable: 0,
+            reg_sprite_y_expansion: 0,
+            reg_sprite_priority: 0,
+            reg_sprite_multicolor: 0,
+            reg_sprite_x_expansion: 0,
+            reg_sprite_sprite_collision: 0,
+            reg_sprite_background_collision: 0,
+            reg_sprite_multicolor_0: 0,
+            reg_sprite_multicolor_1: 0,
+            reg_sprite_colors: [0; 8],
+            reg_light_pen_x: 0,
+            reg_light_pen_y: 0,
 
             raster_counter: 0,
             irq_raster_line: 0,
             x_counter: 0,
             screen_on: true,
 
+            video_matrix_line: [0; 40],
+            color_line: [0; 40],
             graphics_buffer: 0,
             color_buffer: 0,
             graphics_shifter: 0,
 
+            sprites: Default::default(),
+
+            light_pen_position: None,
+            light_pen_latched: false,
+
             reg_initialized: [false; 0x2F],
         }
     }
@@ -103,7 +165,32 @@ where
             self.screen_on |= self.reg_control_1 & flags::CONTROL_1_SCREEN_ON != 0;
         }
 
+        if self.x_counter == 0 {
+            self.start_sprite_lines()?;
+        }
+
+        let bad_line = self.is_bad_line();
+        if bad_line
+            && (C_ACCESS_START..C_ACCESS_END).contains(&self.x_counter)
+            && (self.x_counter - C_ACCESS_START) % 8 == 0
+        {
+            self.c_access()?;
+        }
+
         let graphics_color = self.graphics_tick()?;
+        let sprite_pixel = self.sprites_tick(graphics_color.is_some());
+        let display_color = match (graphics_color, sprite_pixel) {
+            (
+                _,
+                Some(SpritePixel {
+                    color,
+                    behind_background: false,
+                }),
+            ) => color,
+            (Some(color), _) => color,
+            (None, Some(SpritePixel { color, .. })) => color,
+            (None, None) => self.reg_background_color,
+        };
 
         let color = match self.raster_counter {
             DISPLAY_WINDOW_FIRST_LINE..=DISPLAY_WINDOW_LAST_LINE => {
@@ -113,10 +200,10 @@ where
                     self.x_counter,
                 ) {
                     (true, flags::CONTROL_2_CSEL, DISPLAY_WINDOW_START..=DISPLAY_WINDOW_END) => {
-                        graphics_color
+                        display_color
                     }
                     (true, 0, NARROW_DISPLAY_WINDOW_START..=NARROW_DISPLAY_WINDOW_END) => {
-                        graphics_color
+                        display_color
                     }
                     _ => self.reg_border_color,
                 }
@@ -124,12 +211,10 @@ where
             _ => self.reg_border_color,
         };
 
-        if self.raster_counter == self.irq_raster_line
-            && self.x_counter == 0
-            && self.reg_interrupt_mask & flags::INTERRUPT_RASTER != 0
-        {
-            self.reg_interrupt |= flags::INTERRUPT_PENDING | flags::INTERRUPT_RASTER;
+        if self.raster_counter == self.irq_raster_line && self.x_counter == 0 {
+            self.request_interrupt(flags::INTERRUPT_RASTER);
         }
+        self.light_pen_tick();
 
         let output = VicOutput {
             video_output: VideoOutput {
@@ -138,6 +223,8 @@ where
                 color: color & !flags::COLOR_UNUSED,
             },
             irq: self.reg_interrupt & flags::INTERRUPT_PENDING != 0,
+            ba: !(bad_line && (BA_LOW_START..C_ACCESS_END).contains(&self.x_counter)),
+            aec: !(bad_line && (C_ACCESS_START..C_ACCESS_END).contains(&self.x_counter)),
         };
 
         self.x_counter += 1;
@@ -152,13 +239,181 @@ where
         return Ok(output);
     }
 
+    /// Returns `true` if the current raster line is a bad line, i.e. one where
+    /// VIC fetches a new row of screen codes and colors, stealing 40 cycles
+    /// from the CPU. Bad lines only occur if the screen has been turned on
+    /// during raster line 48. Since vertical scrolling isn't supported yet,
+    /// YSCROLL is always 3, and so the first bad line is the first line of the
+    /// display window.
+    fn is_bad_line(&self) -> bool {
+        const DISPLAY_WINDOW_LAST_LINE: usize = BOTTOM_BORDER_FIRST_LINE - 1;
+        self.screen_on
+            && (DISPLAY_WINDOW_FIRST_LINE..=DISPLAY_WINDOW_LAST_LINE).contains(&self.raster_counter)
+            && (self.raster_counter - DISPLAY_WINDOW_FIRST_LINE) % 8 == 0
+    }
+
+    /// Performs a c-access: fetches a screen code and color of a single
+    /// character cell and stores them for the subsequent 8 raster lines.
+    fn c_access(&mut self) -> Result<(), ReadError> {
+        let char_column = (self.x_counter - C_ACCESS_START) / 8;
+        let char_row = (self.raster_counter - DISPLAY_WINDOW_FIRST_LINE) / 8;
+        let offset = (char_row * 40 + char_column) as u16;
+        self.video_matrix_line[char_column] = self
+            .graphics_memory
+            .read(self.screen_memory_base() + offset)?;
+        self.color_line[char_column] = self.color_memory.borrow_mut().read(0xD800 + offset)?;
+        Ok(())
+    }
+
+    /// Returns the current raster line and the horizontal position within
+    /// it, in pixels (8 per CPU cycle).
+    pub fn raster_position(&self) -> (usize, usize) {
+        (self.raster_counter, self.x_counter)
+    }
+
+    /// Reports the values of VIC registers for debugging purposes, as seen by
+    /// the CPU, along with the full raster position.
+    pub fn chip_state(&self) -> Result<ChipState, ReadError> {
+        let mut registers = REGISTER_NAMES
+            .iter()
+            .zip(registers::BASE..)
+            .map(|(&name, address)| Ok(ChipRegister::new(name, self.inspect(address)?)))
+            .collect::<Result<Vec<_>, ReadError>>()?;
+        registers.push(ChipRegister::new("LINE", self.raster_counter as u16));
+        registers.push(ChipRegister::new("X", self.x_counter as u16));
+        return Ok(ChipState {
+            name: "VIC".to_string(),
+            registers,
+        });
+    }
+
+    pub fn mut_graphics_memory(&mut self) -> &mut GrMem {
+        &mut self.graphics_memory
+    }
+
+    /// Points the light pen at a given raw screen position (see
+    /// [`VideoOutput`]), or takes it away from the screen if `position` is
+    /// `None`.
+    pub fn set_light_pen_position(&mut self, position: Option<(usize, usize)>) {
+        self.light_pen_position = position;
+    }
+
+    /// Latches the light pen position and triggers the light pen interrupt once
+    /// the electron beam reaches the point that the light pen is pointed at.
+    /// This happens at most once per frame.
+    fn light_pen_tick(&mut self) {
+        if self.raster_counter == 0 && self.x_counter == 0 {
+            self.light_pen_latched = false;
+        }
+        if self.light_pen_latched
+            || self.light_pen_position != Some((self.x_counter, self.raster_counter))
+        {
+            return;
+        }
+        // The X position is latched with a resolution of 2 pixels, in the same
+        // coordinate space as sprite X positions.
+        self.reg_light_pen_x =
+            ((self.x_counter + RASTER_LENGTH - SPRITE_X_OFFSET) % RASTER_LENGTH / 2) as u8;
+        self.reg_light_pen_y = self.raster_counter as u8;
+        self.light_pen_latched = true;
+        self.request_interrupt(flags::INTERRUPT_LIGHT_PEN);
+    }
+
+    /// Sets the interrupt flag, provided that a given interrupt source is
+    /// enabled.
+    fn request_interrupt(&mut self, source: u8) {
+        if self.reg_interrupt_mask & source != 0 {
+            self.reg_interrupt |= flags::INTERRUPT_PENDING | source;
+        }
+    }
+
+    /// Decides which sprites are displayed in the current raster line and
+    /// fetches their data. Sprites start being displayed one line after their
+    /// Y coordinate is matched by the raster counter.
+    fn start_sprite_lines(&mut self) -> Result<(), ReadError> {
+        for i in 0..self.sprites.len() {
+            let mask = 1 << i;
+            let data_offset = self.sprites[i].start_line(
+                self.reg_sprite_enable & mask != 0,
+                self.raster_counter == self.reg_sprite_y[i] as usize + 1,
+                self.reg_sprite_y_expansion & mask != 0,
+            );
+            if let Some(data_offset) = data_offset {
+                let pointer = self
+                    .graphics_memory
+                    .read(self.screen_memory_base() + SPRITE_POINTERS_OFFSET + i as u16)?;
+                let address = pointer as u16 * 64 + data_offset;
+                let mut data = [0; 3];
+                for (j, byte) in data.iter_mut().enumerate() {
+                    *byte = self.graphics_memory.read(address + j as u16)?;
+                }
+                self.sprites[i].set_data(data);
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes the topmost sprite pixel at the current position (if any) and
+    /// detects collisions.
+    fn sprites_tick(&mut self, graphics_foreground: bool) -> Option<SpritePixel> {
+        let mut topmost = None;
+        let mut sprites_drawn: u8 = 0;
+        for (i, sprite) in self.sprites.iter().enumerate() {
+            let mask = 1 << i;
+            let x = self.reg_sprite_x[i] as usize
+                | if self.reg_sprite_x_msb & mask != 0 {
+                    0x100
+                } else {
+                    0
+                };
+            let offset = match self.x_counter.checked_sub(x + SPRITE_X_OFFSET) {
+                Some(offset) => offset,
+                None => continue,
+            };
+            let color = match sprite.pixel(
+                offset,
+                self.reg_sprite_x_expansion & mask != 0,
+                self.reg_sprite_multicolor & mask != 0,
+            ) {
+                0 => continue,
+                1 => self.reg_sprite_multicolor_0,
+                2 => self.reg_sprite_colors[i],
+                _ => self.reg_sprite_multicolor_1,
+            };
+            sprites_drawn |= mask;
+            if topmost.is_none() {
+                topmost = Some(SpritePixel {
+                    color,
+                    behind_background: self.reg_sprite_priority & mask != 0,
+                });
+            }
+        }
+
+        // Note that only the first collision after clearing the collision
+        // register triggers an interrupt.
+        if sprites_drawn.count_ones() >= 2 {
+            if self.reg_sprite_sprite_collision == 0 {
+                self.request_interrupt(flags::INTERRUPT_SPRITE_SPRITE);
+            }
+            self.reg_sprite_sprite_collision |= sprites_drawn;
+        }
+        if graphics_foreground && sprites_drawn != 0 {
+            if self.reg_sprite_background_collision == 0 {
+                self.request_interrupt(flags::INTERRUPT_SPRITE_BACKGROUND);
+            }
+            self.reg_sprite_background_collision |= sprites_drawn;
+        }
+        return topmost;
+    }
+
     /// Computes the color currently produced by the character graphics layer.
-    fn graphics_tick(&mut self) -> Result<Color, ReadError> {
+    /// Returns `None` if the background color should be displayed.
+    fn graphics_tick(&mut self) -> Result<Option<Color>, ReadError> {
         const DISPLAY_WINDOW_LAST_LINE: usize = BOTTOM_BORDER_FIRST_LINE - 1;
         const DISPLAY_WINDOW_END: usize = RIGHT_BORDER_START - 1;
 
         if !(DISPLAY_WINDOW_FIRST_LINE..=DISPLAY_WINDOW_LAST_LINE).contains(&self.raster_counter) {
-            return Ok(self.reg_background_color);
+            return Ok(None);
         }
 
         let x_inside_display_window =
@@ -170,9 +425,8 @@ where
             // scrolling by up to 7 pixels.
             if subcolumn == (self.reg_control_2 & flags::CONTROL_2_XSCROLL) as usize {
                 self.graphics_shifter = self.graphics_buffer;
-                // TODO: Move the screen and color memory access to a separate
-                // procedure, to be executed during bad lines.
-                self.color_buffer = self.read_color_memory()?;
+                let char_column = (self.x_counter - DISPLAY_WINDOW_START) / 8;
+                self.color_buffer = self.color_line[char_column];
             }
         }
 
@@ -184,53 +438,61 @@ where
         let draws_graphics_pixel = self.graphics_shifter & (1 << 7) != 0;
         self.graphics_shifter <<= 1;
 
-        if !x_inside_display_window {
-            return Ok(self.reg_background_color);
+        if !x_inside_display_window || !draws_graphics_pixel {
+            return Ok(None);
         }
 
-        let color = if draws_graphics_pixel {
-            self.color_buffer
-        } else {
-            self.reg_background_color
-        };
-
-        Ok(color)
+        Ok(Some(self.color_buffer))
     }
 
     /// Reads from bitmap memory a byte that corrensponds to the _next_
-    /// character cell.
+    /// character cell. The screen code comes from the last bad line.
     fn read_bitmap_memory(&mut self) -> Result<u8, ReadError> {
         let char_column = (self.x_counter + 1 - DISPLAY_WINDOW_START) / 8;
-        let char_row = (self.raster_counter - DISPLAY_WINDOW_FIRST_LINE) / 8;
         let char_offset = (self.raster_counter - DISPLAY_WINDOW_FIRST_LINE) % 8;
-        let character_index = self
-            .graphics_memory
-            .read(0x0400 + (char_row * 40 + char_column) as u16)?;
+        let character_index = self.video_matrix_line[char_column];
         return self
             .graphics_memory
-            .read(0x1000 + character_index as u16 * 8 + char_offset as u16);
+            .read(self.character_memory_base() + character_index as u16 * 8 + char_offset as u16);
     }
 
-    /// Reads from color memory a color that corrensponds to the _current_
-    /// character cell.
-    fn read_color_memory(&mut self) -> Result<Color, ReadError> {
-        let char_column = (self.x_counter - DISPLAY_WINDOW_START) / 8;
-        let char_row = (self.raster_counter - DISPLAY_WINDOW_FIRST_LINE) / 8;
-        self.color_memory
-            .borrow_mut()
-            .read(0xD800 + (char_row * 40 + char_column) as u16)
+    /// Returns the address of screen memory within the VIC bank.
+    fn screen_memory_base(&self) -> u16 {
+        (self.reg_memory_pointers & flags::MEMORY_POINTERS_SCREEN) as u16 >> 4 << 10
+    }
+
+    /// Returns the address of character memory within the VIC bank.
+    fn character_memory_base(&self) -> u16 {
+        (self.reg_memory_pointers & flags::MEMORY_POINTERS_CHARACTERS) as u16 >> 1 << 11
     }
 }
 
+/// A pixel produced by the sprite layer.
+struct SpritePixel {
+    color: Color,
+    /// Whether the pixel should be displayed behind the foreground graphics.
+    behind_background: bool,
+}
+
 pub struct VicOutput {
     /// Whether VIC reports an IRQ interrupt.
     pub irq: bool,
+    /// State of the BA (bus available) line. It goes low 3 cycles before VIC
+    /// takes over the bus on a bad line, and stays low until the last
+    /// c-access. While it's low, the CPU needs to be stopped before its next
+    /// read cycle.
+    pub ba: bool,
+    /// State of the AEC (address enable control) line, restricted to the
+    /// c-accesses. While it's low, VIC occupies the bus, and the CPU can't
+    /// perform any cycle, including a write.
+    pub aec: bool,
     pub video_output: VideoOutput,
 }
 
 /// The video output of [`Vic::tick`]. Note that the coordinates are raw and
 /// include horizontal and vertical blanking areas; it's u to the consumer to
 /// crop pixels to the viewport.
+#[derive(Clone, Copy)]
 pub struct VideoOutput {
     pub color: Color,
     /// Raw X coordinate (including horizontal blanking area).
@@ -247,15 +509,48 @@ where
     ChrMem: Read,
 {
     fn inspect(&self, address: u16) -> ReadResult {
+        let address = canonical_address(address);
         match address {
             registers::CONTROL_1 => Ok(self.reg_control_1 & !flags::CONTROL_1_RASTER_8
                 | (self.raster_counter >> 1) as u8 & flags::CONTROL_1_RASTER_8),
             registers::RASTER => Ok(self.raster_counter as u8),
+            registers::LIGHT_PEN_X => Ok(self.reg_light_pen_x),
+            registers::LIGHT_PEN_Y => Ok(self.reg_light_pen_y),
             registers::CONTROL_2 => Ok(self.reg_control_2 | flags::CONTROL_2_UNUSED),
+            registers::MEMORY_POINTERS => Ok(self.reg_memory_pointers),
             registers::INTERRUPT => Ok(self.reg_interrupt),
             registers::INTERRUPT_MASK => Ok(self.reg_interrupt_mask),
             registers::BORDER_COLOR => Ok(self.reg_border_color | flags::COLOR_UNUSED),
             registers::BACKGROUND_COLOR_0 => Ok(self.reg_background_color | flags::COLOR_UNUSED),
+            registers::BACKGROUND_COLOR_1..=registers::BACKGROUND_COLOR_3 => Ok(self
+                .reg_extra_background_colors[(address - registers::BACKGROUND_COLOR_1) as usize]
+                | flags::COLOR_UNUSED),
+            registers::SPRITE_0_X..=registers::SPRITE_7_Y => {
+                let index = (address - registers::SPRITE_0_X) as usize / 2;
+                Ok(if address % 2 == 0 {
+                    self.reg_sprite_x[index]
+                } else {
+                    self.reg_sprite_y[index]
+                })
+            }
+            registers::SPRITE_X_MSB => Ok(self.reg_sprite_x_msb),
+            registers::SPRITE_ENABLE => Ok(self.reg_sprite_enable),
+            registers::SPRITE_Y_EXPANSION => Ok(self.reg_sprite_y_expansion),
+            registers::SPRITE_PRIORITY => Ok(self.reg_sprite_priority),
+            registers::SPRITE_MULTICOLOR => Ok(self.reg_sprite_multicolor),
+            registers::SPRITE_X_EXPANSION => Ok(self.reg_sprite_x_expansion),
+            registers::SPRITE_SPRITE_COLLISION => Ok(self.reg_sprite_sprite_collision),
+            registers::SPRITE_BACKGROUND_COLLISION => Ok(self.reg_sprite_background_collision),
+            registers::SPRITE_MULTICOLOR_0 => {
+                Ok(self.reg_sprite_multicolor_0 | flags::COLOR_UNUSED)
+            }
+            registers::SPRITE_MULTICOLOR_1 => {
+                Ok(self.reg_sprite_multicolor_1 | flags::COLOR_UNUSED)
+            }
+            registers::SPRITE_0_COLOR..=registers::SPRITE_7_COLOR => Ok(self.reg_sprite_colors
+                [(address - registers::SPRITE_0_COLOR) as usize]
+                | flags::COLOR_UNUSED),
+            registers::UNUSED_FIRST..=registers::UNUSED_LAST => Ok(0xFF),
             _ => Err(ReadError { address }),
         }
     }
@@ -267,12 +562,22 @@ where
     ChrMem: Read,
 {
     fn read(&mut self, address: u16) -> ReadResult {
-        self.inspect(address)
+        // Reading collision registers clears them.
+        match canonical_address(address) {
+            registers::SPRITE_SPRITE_COLLISION => {
+                Ok(std::mem::take(&mut self.reg_sprite_sprite_collision))
+            }
+            registers::SPRITE_BACKGROUND_COLLISION => {
+                Ok(std::mem::take(&mut self.reg_sprite_background_collision))
+            }
+            _ => self.inspect(address),
+        }
     }
 }
 
 impl<GrMem: Read, ChrMem: Read> Write for Vic<GrMem, ChrMem> {
     fn write(&mut self, address: u16, value: u8) -> WriteResult {
+        let address = canonical_address(address);
         match address {
             registers::CONTROL_1 => {
                 if value & !(flags::CONTROL_1_RASTER_8 | flags::CONTROL_1_SCREEN_ON)
@@ -287,24 +592,25 @@ impl<GrMem: Read, ChrMem: Read> Write for Vic<GrMem, ChrMem> {
             registers::RASTER => {
                 self.irq_raster_line = self.irq_raster_line & 0b1_0000_0000 | value as usize;
             }
+            // Light pen registers are read-only.
+            registers::LIGHT_PEN_X | registers::LIGHT_PEN_Y => {}
             registers::CONTROL_2 => {
                 if value & flags::CONTROL_2_MCM != 0 {
                     return Err(WriteError { address, value });
                 }
                 self.reg_control_2 = value | flags::CONTROL_2_UNUSED;
             }
+            registers::MEMORY_POINTERS => {
+                self.reg_memory_pointers = value | flags::MEMORY_POINTERS_UNUSED
+            }
             registers::INTERRUPT => {
-                // TODO: For now, we just ignore acknowledging interrupts that
-                // we don't yet support in the first place.
-                if value & flags::INTERRUPT_RASTER != 0 {
-                    self.reg_interrupt = flags::INTERRUPT_UNUSED;
+                // Writing 1 to an interrupt source bit acknowledges it.
+                self.reg_interrupt &= !(value & flags::INTERRUPT_SOURCES);
+                if self.reg_interrupt & flags::INTERRUPT_SOURCES == 0 {
+                    self.reg_interrupt &= !flags::INTERRUPT_PENDING;
                 }
             }
             registers::INTERRUPT_MASK => {
-                // Only raster interrupts are currently supported.
-                if value & !flags::INTERRUPT_RASTER != 0 {
-                    return Err(WriteError { address, value });
-                }
                 self.reg_interrupt_mask = value | flags::INTERRUPT_MASK_UNUSED;
             }
             registers::BORDER_COLOR => self.reg_border_color = value | flags::COLOR_UNUSED,
@@ -312,9 +618,41 @@ impl<GrMem: Read, ChrMem: Read> Write for Vic<GrMem, ChrMem> {
                 self.reg_background_color = value | flags::COLOR_UNUSED
             }
 
-            // We don't support ECM text mode or sprites just yet; for now,
-            // ignore all writes.
-            registers::BACKGROUND_COLOR_1..=registers::SPRITE_7_COLOR => {}
+            registers::SPRITE_0_X..=registers::SPRITE_7_Y => {
+                let index = (address - registers::SPRITE_0_X) as usize / 2;
+                if address % 2 == 0 {
+                    self.reg_sprite_x[index] = value;
+                } else {
+                    self.reg_sprite_y[index] = value;
+                }
+            }
+            registers::SPRITE_X_MSB => self.reg_sprite_x_msb = value,
+            registers::SPRITE_ENABLE => self.reg_sprite_enable = value,
+            registers::SPRITE_Y_EXPANSION => self.reg_sprite_y_expansion = value,
+            registers::SPRITE_PRIORITY => self.reg_sprite_priority = value,
+            registers::SPRITE_MULTICOLOR => self.reg_sprite_multicolor = value,
+            registers::SPRITE_X_EXPANSION => self.reg_sprite_x_expansion = value,
+            // Collision registers are read-only.
+            registers::SPRITE_SPRITE_COLLISION | registers::SPRITE_BACKGROUND_COLLISION => {}
+            registers::SPRITE_MULTICOLOR_0 => {
+                self.reg_sprite_multicolor_0 = value | flags::COLOR_UNUSED
+            }
+            registers::SPRITE_MULTICOLOR_1 => {
+                self.reg_sprite_multicolor_1 = value | flags::COLOR_UNUSED
+            }
+            registers::SPRITE_0_COLOR..=registers::SPRITE_7_COLOR => {
+                self.reg_sprite_colors[(address - registers::SPRITE_0_COLOR) as usize] =
+                    value | flags::COLOR_UNUSED
+            }
+
+            // We don't support ECM text mode just yet; for now, just store
+            // the values.
+            registers::BACKGROUND_COLOR_1..=registers::BACKGROUND_COLOR_3 => {
+                self.reg_extra_background_colors
+                    [(address - registers::BACKGROUND_COLOR_1) as usize] =
+                    value | flags::COLOR_UNUSED
+            }
+            registers::UNUSED_FIRST..=registers::UNUSED_LAST => {}
 
             _ => {
                 if self.reg_initialized[(address - registers::BASE) as usize] {
@@ -329,13 +667,23 @@ impl<GrMem: Read, ChrMem: Read> Write for Vic<GrMem, ChrMem> {
 
 impl<GrMem: Read, ChrMem: Read> Memory for Vic<GrMem, ChrMem> {}
 
+/// Returns names of the VIC registers, used to annotate the disassembly.
+pub fn register_names() -> RegisterNames {
+    RegisterNames::new().with_registers(REGISTER_NAMES.into_iter().zip(registers::BASE..))
+}
+
+/// VIC registers are mirrored every 64 bytes. Converts a mirrored register
+/// address to its canonical form in the $D000-$D03F range.
+fn canonical_address(address: u16) -> u16 {
+    registers::BASE | address & 0x3F
+}
+
 /// Converts raster line number to Y position on the rendered screen.
 pub fn raster_line_to_screen_y(index: usize) -> usize {
     (index + TOTAL_HEIGHT - TOP_BORDER_FIRST_LINE) % TOTAL_HEIGHT
 }
 
 /// Converts Y position on the rendered screen to raster line number.
-#[cfg(test)]
 pub fn screen_y_to_raster_line(screen_y: usize) -> usize {
     (screen_y + TOP_BORDER_FIRST_LINE) % TOTAL_HEIGHT
 }
@@ -368,19 +716,61 @@ pub const BOTTOM_BORDER_HEIGHT: usize =
 pub const VISIBLE_LINES: usize = TOP_BORDER_HEIGHT + DISPLAY_WINDOW_HEIGHT + BOTTOM_BORDER_HEIGHT;
 pub const TOTAL_HEIGHT: usize = 262; // Including vertical blank
 
+/// Sprite X coordinate that corresponds to the left edge of the display window.
+const SPRITE_X_OFFSET: usize = DISPLAY_WINDOW_START - 24;
+/// Position of the first c-access on a bad line, 1.5 character cells before the
+/// display window starts (cycle 15).
+const C_ACCESS_START: usize = DISPLAY_WINDOW_START - 12;
+/// Position right after the last c-access on a bad line.
+const C_ACCESS_END: usize = C_ACCESS_START + 40 * 8;
+/// Position where the BA line goes low on a bad line, 3 cycles before the first
+/// c-access.
+const BA_LOW_START: usize = C_ACCESS_START - 3 * 8;
+/// Offset of sprite data pointers, relative to the screen memory.
+const SPRITE_POINTERS_OFFSET: u16 = 0x03F8;
+
 mod registers {
     pub const BASE: u16 = 0xD000;
+    pub const SPRITE_0_X: u16 = 0xD000;
+    pub const SPRITE_7_Y: u16 = 0xD00F;
+    pub const SPRITE_X_MSB: u16 = 0xD010;
     pub const CONTROL_1: u16 = 0xD011;
     pub const RASTER: u16 = 0xD012;
+    pub const LIGHT_PEN_X: u16 = 0xD013;
+    pub const LIGHT_PEN_Y: u16 = 0xD014;
+    pub const SPRITE_ENABLE: u16 = 0xD015;
     pub const CONTROL_2: u16 = 0xD016;
+    pub const SPRITE_Y_EXPANSION: u16 = 0xD017;
+    pub const MEMORY_POINTERS: u16 = 0xD018;
     pub const INTERRUPT: u16 = 0xD019;
     pub const INTERRUPT_MASK: u16 = 0xD01A;
+    pub const SPRITE_PRIORITY: u16 = 0xD01B;
+    pub const SPRITE_MULTICOLOR: u16 = 0xD01C;
+    pub const SPRITE_X_EXPANSION: u16 = 0xD01D;
+    pub const SPRITE_SPRITE_COLLISION: u16 = 0xD01E;
+    pub const SPRITE_BACKGROUND_COLLISION: u16 = 0xD01F;
     pub const BORDER_COLOR: u16 = 0xD020;
     pub const BACKGROUND_COLOR_0: u16 = 0xD021;
     pub const BACKGROUND_COLOR_1: u16 = 0xD022;
+    pub const BACKGROUND_COLOR_3: u16 = 0xD024;
+    pub const SPRITE_MULTICOLOR_0: u16 = 0xD025;
+    pub const SPRITE_MULTICOLOR_1: u16 = 0xD026;
+    pub const SPRITE_0_COLOR: u16 = 0xD027;
     pub const SPRITE_7_COLOR: u16 = 0xD02E;
+    pub const UNUSED_FIRST: u16 = 0xD02F;
+    pub const UNUSED_LAST: u16 = 0xD03F;
 }
 
+/// Names of the VIC registers, as used in the Commodore 64 Programmer's
+/// Reference Guide, starting from [`registers::BASE`].
+const REGISTER_NAMES: [&str; 0x2F] = [
+    "SP0X", "SP0Y", "SP1X", "SP1Y", "SP2X", "SP2Y", "SP3X", "SP3Y", "SP4X", "SP4Y", "SP5X", "SP5Y",
+    "SP6X", "SP6Y", "SP7X", "SP7Y", "MSIGX", "SCROLY", "RASTER", "LPENX", "LPENY", "SPENA",
+    "SCROLX", "YXPAND", "VMCSB", "VICIRQ", "IRQMASK", "SPBGPR", "SPMC", "XXPAND", "SPSPCL",
+    "SPBGCL", "EXTCOL", "BGCOL0", "BGCOL1", "BGCOL2", "BGCOL3", "SPMC0", "SPMC1", "SP0COL",
+    "SP1COL", "SP2COL", "SP3COL", "SP4COL", "SP5COL", "SP6COL", "SP7COL",
+];
+
 #[allow(dead_code)]
 mod flags {
     pub const CONTROL_1_YSCROLL: u8 = 0b0000_0111;
@@ -397,6 +787,10 @@ mod flags {
     pub const CONTROL_2_MCM: u8 = 0b0001_0000;
     pub const CONTROL_2_UNUSED: u8 = 0b1100_0000;
 
+    pub const MEMORY_POINTERS_CHARACTERS: u8 = 0b0000_1110;
+    pub const MEMORY_POINTERS_SCREEN: u8 = 0b1111_0000;
+    pub const MEMORY_POINTERS_UNUSED: u8 = 0b0000_0001;
+
     /// Raster interrupt. Valid for [`INTERRUPT`][super::registers::INTERRUPT]
     /// and [`INTERRUPT_MASK`][super::registers::INTERRUPT_MASK]
     /// registers.
@@ -417,6 +811,9 @@ mod flags {
     /// [`INTERRUPT`][super::registers::INTERRUPT] register.
     pub const INTERRUPT_PENDING: u8 = 0b1000_0000;
 
+    /// All interrupt source bits of [`INTERRUPT`][super::registers::INTERRUPT]
+    /// register.
+    pub const INTERRUPT_SOURCES: u8 = 0b0000_1111;
     /// Unused bits of [`INTERRUPT`][super::registers::INTERRUPT] register.
     pub const INTERRUPT_UNUSED: u8 = 0b0111_0000;
 
@@ -0,0 +1,125 @@
+/// Number of raster lines occupied by a sprite (not counting Y expansion).
+const SPRITE_HEIGHT: u8 = 21;
+/// Number of pixels in a sprite line (not counting X expansion).
+const SPRITE_WIDTH: usize = 24;
+
+/// Represents the graphics state of a single VIC-II sprite: which line of
+/// sprite data is being displayed, and the data itself. Sprite registers
+/// (position, colors, etc.) are kept by the VIC itself, since most of them are
+/// shared between all sprites.
+#[derive(Debug, Default)]
+pub struct Sprite {
+    /// Index of the sprite data line being displayed on the current raster
+    /// line, or `None` if the sprite is not displayed.
+    line: Option<u8>,
+    /// Used to display each line twice if the sprite is expanded vertically.
+    /// `true` if the current line is a repetition.
+    repeated_line: bool,
+    /// 24 bits of graphics data for the current raster line, MSB first.
+    data: u32,
+}
+
+impl Sprite {
+    /// Advances the sprite to the next raster line. `y_match` indicates
+    /// whether the sprite should start being displayed in this line. Returns
+    /// an offset of the sprite data that needs to be fetched and passed to
+    /// [`Sprite::set_data`], or `None` if the sprite is not displayed.
+    pub fn start_line(&mut self, enabled: bool, y_match: bool, y_expanded: bool) -> Option<u16> {
+        if !enabled {
+            self.line = None;
+            return None;
+        }
+        if let Some(line) = self.line {
+            if y_expanded && !self.repeated_line {
+                self.repeated_line = true;
+            } else {
+                self.repeated_line = false;
+                self.line = Some(line + 1).filter(|&l| l < SPRITE_HEIGHT);
+            }
+        }
+        if self.line.is_none() && y_match {
+            self.line = Some(0);
+            self.repeated_line = false;
+        }
+        return self.line.map(|line| line as u16 * 3);
+    }
+
+    /// Sets the graphics data for the current raster line.
+    pub fn set_data(&mut self, bytes: [u8; 3]) {
+        self.data = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+    }
+
+    /// Returns a 2-bit color code of a pixel located `offset` pixels from the
+    /// sprite's left edge. The codes follow the multicolor convention: 0 means
+    /// transparent, 1 and 3 are multicolor registers 0 and 1, and 2 is the
+    /// sprite's own color. In high-resolution mode, only codes 0 and 2 are
+    /// returned.
+    pub fn pixel(&self, offset: usize, x_expanded: bool, multicolor: bool) -> u8 {
+        if self.line.is_none() {
+            return 0;
+        }
+        let offset = if x_expanded { offset / 2 } else { offset };
+        if offset >= SPRITE_WIDTH {
+            return 0;
+        }
+        return if multicolor {
+            ((self.data >> (SPRITE_WIDTH - 2 - (offset & !1))) & 0b11) as u8
+        } else {
+            ((self.data >> (SPRITE_WIDTH - 1 - offset)) & 1) as u8 * 2
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn displays_21_lines() {
+        let mut sprite = Sprite::default();
+        assert_eq!(sprite.start_line(true, false, false), None);
+        assert_eq!(sprite.start_line(true, true, false), Some(0));
+        assert_eq!(sprite.start_line(true, false, false), Some(3));
+        for _ in 2..20 {
+            sprite.start_line(true, false, false);
+        }
+        assert_eq!(sprite.start_line(true, false, false), Some(60));
+        assert_eq!(sprite.start_line(true, false, false), None);
+    }
+
+    #[test]
+    fn y_expansion() {
+        let mut sprite = Sprite::default();
+        assert_eq!(sprite.start_line(true, true, true), Some(0));
+        assert_eq!(sprite.start_line(true, false, true), Some(0));
+        assert_eq!(sprite.start_line(true, false, true), Some(3));
+        assert_eq!(sprite.start_line(true, false, true), Some(3));
+        assert_eq!(sprite.start_line(true, false, true), Some(6));
+    }
+
+    #[test]
+    fn disabling() {
+        let mut sprite = Sprite::default();
+        sprite.start_line(true, true, false);
+        assert_eq!(sprite.start_line(false, false, false), None);
+        assert_eq!(sprite.start_line(true, false, false), None);
+    }
+
+    #[test]
+    fn pixels() {
+        let mut sprite = Sprite::default();
+        sprite.start_line(true, true, false);
+        sprite.set_data([0b1000_0000, 0b0000_0000, 0b0001_1011]);
+        let hires: Vec<u8> = (0..25).map(|x| sprite.pixel(x, false, false)).collect();
+        assert_eq!(
+            hires,
+            [2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 0, 2, 2, 0]
+        );
+        let multicolor: Vec<u8> = (16..25).map(|x| sprite.pixel(x, false, true)).collect();
+        assert_eq!(multicolor, [0, 0, 1, 1, 2, 2, 3, 3, 0]);
+        let expanded: Vec<u8> = (0..4).map(|x| sprite.pixel(x, true, false)).collect();
+        assert_eq!(expanded, [2, 2, 0, 0]);
+        assert_eq!(sprite.pixel(47, true, false), 2);
+        assert_eq!(sprite.pixel(48, true, false), 0);
+    }
+}
@@ -174,6 +174,46 @@ fn tick_until_irq<GM: Read, FM: Read>(vic: &mut Vic<GM, FM>) -> VicOutput {
     panic!("IRQ not detected");
 }
 
+/// Runs VIC until any IRQ is reported in [`VicOutput`][super::VicOutput] and
+/// returns the output. Times out after two screenfuls.
+fn tick_until_any_irq<GM: Read, FM: Read>(vic: &mut Vic<GM, FM>) -> VicOutput {
+    for _ in 0..2 * TOTAL_HEIGHT * RASTER_LENGTH {
+        let tick_result = vic.tick().unwrap();
+        if tick_result.irq {
+            return tick_result;
+        }
+    }
+    panic!("IRQ not detected");
+}
+
+/// Sets up a sprite with given graphics data. Sprite data pointers are set up
+/// so that each sprite's data occupies a separate block starting at $2000.
+fn set_up_sprite(vic: &mut Vic<Ram, Ram>, index: usize, x: u8, y: u8, data: &[[u8; 3]]) {
+    let pointer = 0x80 + index as u8;
+    vic.graphics_memory.bytes[0x07F8 + index] = pointer;
+    for (i, line) in data.iter().enumerate() {
+        let address = pointer as usize * 64 + i * 3;
+        vic.graphics_memory.bytes[address..address + 3].copy_from_slice(line);
+    }
+    let index = index as u16;
+    vic.write(registers::SPRITE_0_X + 2 * index, x).unwrap();
+    vic.write(registers::SPRITE_0_X + 2 * index + 1, y).unwrap();
+    let enable = vic.read(registers::SPRITE_ENABLE).unwrap();
+    vic.write(registers::SPRITE_ENABLE, enable | 1 << index)
+        .unwrap();
+}
+
+/// Creates a VIC with black background, 40-column display window and white
+/// border.
+fn vic_for_sprite_testing() -> Vic<Ram, Ram> {
+    let mut vic = initialized_vic_for_testing();
+    vic.write(registers::BORDER_COLOR, 0x01).unwrap();
+    vic.write(registers::BACKGROUND_COLOR_0, 0x00).unwrap();
+    vic.write(registers::CONTROL_2, flags::CONTROL_2_CSEL)
+        .unwrap();
+    return vic;
+}
+
 macro_rules! test_reg {
     ($fn_name: ident, $register:ident, $write:expr, $read:expr) => {
         #[test]
@@ -190,6 +230,18 @@ test_reg!(rw_control_1_1, CONTROL_1, 0b1001_1011, 0b0001_1011);
 test_reg!(rw_raster, RASTER, 0b1111_1111, 0b0000_0000);
 test_reg!(rw_control_2_0, CONTROL_2, 0b1100_1011, 0b1100_1011);
 test_reg!(rw_control_2_1, CONTROL_2, 0b0010_0100, 0b1110_0100);
+test_reg!(
+    rw_memory_pointers_0,
+    MEMORY_POINTERS,
+    0b1111_1111,
+    0b1111_1111
+);
+test_reg!(
+    rw_memory_pointers_1,
+    MEMORY_POINTERS,
+    0b0010_0100,
+    0b0010_0101
+);
 test_reg!(rw_interrupt_0, INTERRUPT, 0b0000_0000, 0b0111_0000);
 test_reg!(rw_interrupt_1, INTERRUPT, 0b0000_0001, 0b0111_0000);
 test_reg!(
@@ -208,6 +260,60 @@ test_reg!(rw_border_color_0, BORDER_COLOR, 0xF9, 0xF9);
 test_reg!(rw_border_color_1, BORDER_COLOR, 0x06, 0xF6);
 test_reg!(rw_background_color_0_0, BACKGROUND_COLOR_0, 0xF7, 0xF7);
 test_reg!(rw_background_color_0_1, BACKGROUND_COLOR_0, 0x08, 0xF8);
+test_reg!(rw_background_color_1, BACKGROUND_COLOR_1, 0x0C, 0xFC);
+test_reg!(rw_background_color_3, BACKGROUND_COLOR_3, 0xF2, 0xF2);
+
+test_reg!(rw_sprite_0_x, SPRITE_0_X, 0xAB, 0xAB);
+test_reg!(rw_sprite_7_y, SPRITE_7_Y, 0xCD, 0xCD);
+test_reg!(rw_sprite_x_msb, SPRITE_X_MSB, 0b1000_0001, 0b1000_0001);
+test_reg!(rw_sprite_enable, SPRITE_ENABLE, 0b0101_0101, 0b0101_0101);
+test_reg!(rw_sprite_y_expansion, SPRITE_Y_EXPANSION, 0x12, 0x12);
+test_reg!(rw_sprite_priority, SPRITE_PRIORITY, 0x34, 0x34);
+test_reg!(rw_sprite_multicolor, SPRITE_MULTICOLOR, 0x56, 0x56);
+test_reg!(rw_sprite_x_expansion, SPRITE_X_EXPANSION, 0x78, 0x78);
+test_reg!(rw_sprite_multicolor_0, SPRITE_MULTICOLOR_0, 0x05, 0xF5);
+test_reg!(rw_sprite_multicolor_1, SPRITE_MULTICOLOR_1, 0xF9, 0xF9);
+test_reg!(rw_sprite_0_color, SPRITE_0_COLOR, 0x0A, 0xFA);
+test_reg!(rw_sprite_7_color, SPRITE_7_COLOR, 0x03, 0xF3);
+test_reg!(
+    rw_sprite_sprite_collision,
+    SPRITE_SPRITE_COLLISION,
+    0xFF,
+    0x00
+);
+test_reg!(
+    rw_sprite_background_collision,
+    SPRITE_BACKGROUND_COLLISION,
+    0xFF,
+    0x00
+);
+test_reg!(
+    rw_interrupt_mask_2,
+    INTERRUPT_MASK,
+    0b0000_0110,
+    0b1111_0110
+);
+test_reg!(
+    rw_interrupt_mask_3,
+    INTERRUPT_MASK,
+    0b0000_1000,
+    0b1111_1000
+);
+test_reg!(rw_light_pen_x, LIGHT_PEN_X, 0xFF, 0x00);
+test_reg!(rw_light_pen_y, LIGHT_PEN_Y, 0xFF, 0x00);
+
+#[test]
+fn register_mirroring() {
+    let mut vic = vic_for_testing();
+    vic.write(registers::BORDER_COLOR + 0x40, 0x05).unwrap();
+    assert_eq!(vic.read(registers::BORDER_COLOR).unwrap(), 0xF5);
+    assert_eq!(vic.inspect(registers::BORDER_COLOR + 0x3C0).unwrap(), 0xF5);
+
+    // Unused registers can be written, but always read as $FF.
+    vic.write(registers::UNUSED_FIRST, 0x00).unwrap();
+    assert_eq!(vic.read(registers::UNUSED_FIRST).unwrap(), 0xFF);
+    assert_eq!(vic.inspect(registers::UNUSED_LAST + 0x40).unwrap(), 0xFF);
+}
 
 #[test]
 fn draws_border() {
@@ -367,6 +473,23 @@ fn draws_characters() {
     );
 }
 
+#[test]
+fn memory_pointers() {
+    let mut vic = initialized_vic_for_testing();
+    vic.write(registers::BORDER_COLOR, 0x01).unwrap();
+    vic.write(registers::BACKGROUND_COLOR_0, 0x00).unwrap();
+    vic.write(registers::CONTROL_2, flags::CONTROL_2_CSEL)
+        .unwrap();
+    // Screen at $0C00, characters at $2800.
+    vic.write(registers::MEMORY_POINTERS, 0b0011_1010).unwrap();
+    vic.graphics_memory.bytes[0x2808..0x2810].copy_from_slice(&[0b1100_0011; 8]);
+    vic.graphics_memory.bytes[0x0C00] = 0x01;
+    vic.color_memory.borrow_mut().bytes[0xD800] = 0x0A;
+
+    skip_to_raster_line(&mut vic, DISPLAY_WINDOW_FIRST_LINE);
+    assert_eq!(encode_video(grab_raster_line(&mut vic, 0, 8)), "AA....AA");
+}
+
 #[test]
 fn horizontal_scrolling() {
     let mut vic = initialized_vic_for_testing();
@@ -544,3 +667,332 @@ fn screen_on_off_decides_on_raster_line_48() {
         "Displays border color after seeing the screen switched off on line 48",
     );
 }
+
+/// Runs VIC for a single raster line and returns the range of X positions
+/// where the BA line is low, or `None` if it stays high.
+fn ba_low_range<GM: Read, FM: Read>(vic: &mut Vic<GM, FM>) -> Option<(usize, usize)> {
+    let mut result: Option<(usize, usize)> = None;
+    for _ in 0..RASTER_LENGTH {
+        let vic_output = vic.tick().unwrap();
+        if !vic_output.ba {
+            let x = vic_output.video_output.x;
+            result = Some(result.map_or((x, x), |(first, _)| (first, x)));
+        }
+    }
+    return result;
+}
+
+#[test]
+fn bad_lines() {
+    let mut vic = initialized_vic_for_testing();
+    skip_to_raster_line(&mut vic, DISPLAY_WINDOW_FIRST_LINE - 1);
+    assert_eq!(ba_low_range(&mut vic), None);
+    // BA goes low 3 cycles before the first c-access and stays low for the
+    // next 40 cycles.
+    let ba_low = Some((DISPLAY_WINDOW_START - 36, DISPLAY_WINDOW_START + 307));
+    assert_eq!(ba_low_range(&mut vic), ba_low);
+    assert_eq!(ba_low_range(&mut vic), None);
+
+    skip_to_raster_line(&mut vic, DISPLAY_WINDOW_FIRST_LINE + 8);
+    assert_eq!(ba_low_range(&mut vic), ba_low);
+    skip_to_raster_line(&mut vic, BOTTOM_BORDER_FIRST_LINE - 8);
+    assert_eq!(ba_low_range(&mut vic), ba_low);
+    skip_to_raster_line(&mut vic, BOTTOM_BORDER_FIRST_LINE);
+    assert_eq!(ba_low_range(&mut vic), None);
+
+    // No bad lines if the screen is turned off.
+    vic.write(
+        registers::CONTROL_1,
+        CONTROL_1_DEFAULT & !flags::CONTROL_1_SCREEN_ON,
+    )
+    .unwrap();
+    skip_to_raster_line(&mut vic, DISPLAY_WINDOW_FIRST_LINE);
+    assert_eq!(ba_low_range(&mut vic), None);
+}
+
+#[test]
+fn fetches_screen_memory_on_bad_lines() {
+    let mut vic = initialized_vic_for_testing();
+    vic.write(registers::BORDER_COLOR, 0x01).unwrap();
+    vic.write(registers::BACKGROUND_COLOR_0, 0x00).unwrap();
+    vic.write(registers::CONTROL_2, flags::CONTROL_2_CSEL)
+        .unwrap();
+    vic.graphics_memory.bytes[0x1008..0x1010].copy_from_slice(&[0b1111_1111; 8]);
+    vic.graphics_memory.bytes[0x1010..0x1018].copy_from_slice(&[0b0000_1111; 8]);
+    vic.graphics_memory.bytes[0x0400] = 0x01;
+    vic.color_memory.borrow_mut().bytes[0xD800] = 0x0A;
+
+    // Changing the screen memory after a bad line doesn't affect the current
+    // character row.
+    skip_to_raster_line(&mut vic, DISPLAY_WINDOW_FIRST_LINE + 1);
+    vic.graphics_memory.bytes[0x0400] = 0x02;
+    vic.color_memory.borrow_mut().bytes[0xD800] = 0x0B;
+    assert_eq!(encode_video(grab_raster_line(&mut vic, 0, 8)), "AAAAAAAA");
+
+    // The new data is fetched on the next bad line.
+    skip_to_raster_line(&mut vic, DISPLAY_WINDOW_FIRST_LINE);
+    assert_eq!(encode_video(grab_raster_line(&mut vic, 0, 8)), "....BBBB");
+}
+
+#[test]
+fn draws_sprites() {
+    let mut vic = vic_for_sprite_testing();
+    set_up_sprite(
+        &mut vic,
+        0,
+        24,
+        50,
+        &[
+            [0b1000_0000, 0b0000_0000, 0b0000_0001],
+            [0b0111_1111, 0b1111_1111, 0b1111_1110],
+        ],
+    );
+    vic.write(registers::SPRITE_0_COLOR, 0x07).unwrap();
+
+    itertools::assert_equal(
+        encode_video_lines(grab_frame(&mut vic, -1, -1, 26, 4)).iter(),
+        &[
+            "11111111111111111111111111",
+            "17......................7.",
+            "1.7777777777777777777777..",
+            "1.........................",
+        ],
+    );
+
+    // Disable the sprite.
+    vic.write(registers::SPRITE_ENABLE, 0).unwrap();
+    itertools::assert_equal(
+        encode_video_lines(grab_frame(&mut vic, 0, 0, 3, 1)).iter(),
+        &["..."],
+    );
+}
+
+#[test]
+fn sprite_expansion() {
+    let mut vic = vic_for_sprite_testing();
+    set_up_sprite(
+        &mut vic,
+        1,
+        32,
+        52,
+        &[[0b1010_0000, 0, 0], [0b0101_0000, 0, 0]],
+    );
+    vic.write(registers::SPRITE_0_COLOR + 1, 0x07).unwrap();
+    vic.write(registers::SPRITE_X_EXPANSION, 0b0000_0010)
+        .unwrap();
+    vic.write(registers::SPRITE_Y_EXPANSION, 0b0000_0010)
+        .unwrap();
+
+    itertools::assert_equal(
+        encode_video_lines(grab_frame(&mut vic, 7, 1, 10, 6)).iter(),
+        &[
+            "..........",
+            ".77..77...",
+            ".77..77...",
+            "...77..77.",
+            "...77..77.",
+            "..........",
+        ],
+    );
+}
+
+#[test]
+fn sprite_x_msb() {
+    let mut vic = vic_for_sprite_testing();
+    set_up_sprite(&mut vic, 4, 20, 50, &[[0b1100_0000, 0, 0]]);
+    vic.write(registers::SPRITE_0_COLOR + 4, 0x07).unwrap();
+    vic.write(registers::SPRITE_X_MSB, 0b0001_0000).unwrap();
+
+    itertools::assert_equal(
+        encode_video_lines(grab_frame(&mut vic, 251, 0, 4, 1)).iter(),
+        &[".77."],
+    );
+}
+
+#[test]
+fn sprite_multicolor() {
+    let mut vic = vic_for_sprite_testing();
+    set_up_sprite(&mut vic, 0, 24, 50, &[[0b0001_1011, 0, 0]]);
+    vic.write(registers::SPRITE_0_COLOR, 0x07).unwrap();
+    vic.write(registers::SPRITE_MULTICOLOR_0, 0x0A).unwrap();
+    vic.write(registers::SPRITE_MULTICOLOR_1, 0x0B).unwrap();
+    vic.write(registers::SPRITE_MULTICOLOR, 0b0000_0001)
+        .unwrap();
+
+    itertools::assert_equal(
+        encode_video_lines(grab_frame(&mut vic, 0, 0, 9, 1)).iter(),
+        &["..AA77BB."],
+    );
+}
+
+#[test]
+fn sprite_sprite_priority() {
+    let mut vic = vic_for_sprite_testing();
+    set_up_sprite(&mut vic, 0, 24, 50, &[[0xFF, 0xFF, 0xFF]]);
+    set_up_sprite(&mut vic, 1, 28, 50, &[[0xFF, 0xFF, 0xFF]]);
+    vic.write(registers::SPRITE_0_COLOR, 0x05).unwrap();
+    vic.write(registers::SPRITE_0_COLOR + 1, 0x06).unwrap();
+
+    itertools::assert_equal(
+        encode_video_lines(grab_frame(&mut vic, 0, 0, 30, 1)).iter(),
+        &["5555555555555555555555556666.."],
+    );
+}
+
+#[test]
+fn sprite_background_priority() {
+    let mut vic = vic_for_sprite_testing();
+    vic.graphics_memory.bytes[0x1008] = 0b1111_0000;
+    vic.graphics_memory.bytes[0x0400] = 0x01;
+    vic.color_memory.borrow_mut().bytes[0xD800] = 0x0C;
+    set_up_sprite(&mut vic, 0, 24, 50, &[[0xFF, 0, 0]]);
+    vic.write(registers::SPRITE_0_COLOR, 0x07).unwrap();
+
+    itertools::assert_equal(
+        encode_video_lines(grab_frame(&mut vic, 0, 0, 9, 1)).iter(),
+        &["77777777."],
+    );
+
+    vic.write(registers::SPRITE_PRIORITY, 0b0000_0001).unwrap();
+    itertools::assert_equal(
+        encode_video_lines(grab_frame(&mut vic, 0, 0, 9, 1)).iter(),
+        &["CCCC7777."],
+    );
+}
+
+#[test]
+fn sprite_sprite_collision() {
+    let mut vic = vic_for_sprite_testing();
+    set_up_sprite(&mut vic, 0, 24, 50, &[[0xFF, 0xFF, 0xFF]]);
+    set_up_sprite(&mut vic, 1, 100, 50, &[[0xFF, 0xFF, 0xFF]]);
+    set_up_sprite(&mut vic, 2, 28, 50, &[[0xFF, 0xFF, 0xFF]]);
+    vic.write(registers::INTERRUPT_MASK, flags::INTERRUPT_SPRITE_SPRITE)
+        .unwrap();
+
+    let vic_output = tick_until_any_irq(&mut vic);
+    assert_eq!(
+        vic_output.video_output.raster_line,
+        DISPLAY_WINDOW_FIRST_LINE
+    );
+    assert_eq!(vic_output.video_output.x, DISPLAY_WINDOW_START + 4);
+    assert_eq!(
+        vic.read(registers::INTERRUPT).unwrap(),
+        flags::INTERRUPT_UNUSED | flags::INTERRUPT_PENDING | flags::INTERRUPT_SPRITE_SPRITE,
+    );
+
+    // Inspecting doesn't clear the collision register, but reading does.
+    assert_eq!(
+        vic.inspect(registers::SPRITE_SPRITE_COLLISION).unwrap(),
+        0b0000_0101
+    );
+    assert_eq!(
+        vic.read(registers::SPRITE_SPRITE_COLLISION).unwrap(),
+        0b0000_0101
+    );
+    assert_eq!(vic.read(registers::SPRITE_SPRITE_COLLISION).unwrap(), 0);
+    assert_eq!(vic.read(registers::SPRITE_BACKGROUND_COLLISION).unwrap(), 0);
+
+    vic.write(registers::INTERRUPT, flags::INTERRUPT_SPRITE_SPRITE)
+        .unwrap();
+    assert_eq!(
+        vic.read(registers::INTERRUPT).unwrap(),
+        flags::INTERRUPT_UNUSED
+    );
+}
+
+#[test]
+fn sprite_background_collision() {
+    let mut vic = vic_for_sprite_testing();
+    vic.graphics_memory.bytes[0x1008] = 0b0000_1111;
+    vic.graphics_memory.bytes[0x0400] = 0x01;
+    set_up_sprite(&mut vic, 3, 24, 50, &[[0xFF, 0, 0]]);
+    vic.write(
+        registers::INTERRUPT_MASK,
+        flags::INTERRUPT_SPRITE_BACKGROUND,
+    )
+    .unwrap();
+
+    let vic_output = tick_until_any_irq(&mut vic);
+    assert_eq!(
+        vic_output.video_output.raster_line,
+        DISPLAY_WINDOW_FIRST_LINE
+    );
+    assert_eq!(vic_output.video_output.x, DISPLAY_WINDOW_START + 4);
+    assert_eq!(
+        vic.read(registers::INTERRUPT).unwrap(),
+        flags::INTERRUPT_UNUSED | flags::INTERRUPT_PENDING | flags::INTERRUPT_SPRITE_BACKGROUND,
+    );
+    assert_eq!(
+        vic.read(registers::SPRITE_BACKGROUND_COLLISION).unwrap(),
+        0b0000_1000
+    );
+    assert_eq!(vic.read(registers::SPRITE_SPRITE_COLLISION).unwrap(), 0);
+}
+
+#[test]
+fn sprite_collisions_without_interrupts() {
+    let mut vic = vic_for_sprite_testing();
+    set_up_sprite(&mut vic, 6, 24, 50, &[[0xFF, 0xFF, 0xFF]]);
+    set_up_sprite(&mut vic, 7, 28, 50, &[[0xFF, 0xFF, 0xFF]]);
+
+    expect_no_interrupts_for(TOTAL_HEIGHT * RASTER_LENGTH, &mut vic);
+    assert_eq!(
+        vic.read(registers::SPRITE_SPRITE_COLLISION).unwrap(),
+        0b1100_0000
+    );
+}
+
+#[test]
+fn light_pen() {
+    let mut vic = initialized_vic_for_testing();
+    vic.write(registers::INTERRUPT_MASK, flags::INTERRUPT_LIGHT_PEN)
+        .unwrap();
+    // Point the light pen at the upper left corner of the display window.
+    vic.set_light_pen_position(Some((DISPLAY_WINDOW_START, DISPLAY_WINDOW_FIRST_LINE)));
+
+    let vic_output = tick_until_any_irq(&mut vic);
+    assert_eq!(
+        vic_output.video_output.raster_line,
+        DISPLAY_WINDOW_FIRST_LINE
+    );
+    assert_eq!(vic_output.video_output.x, DISPLAY_WINDOW_START);
+    assert_eq!(
+        vic.read(registers::INTERRUPT).unwrap(),
+        flags::INTERRUPT_UNUSED | flags::INTERRUPT_PENDING | flags::INTERRUPT_LIGHT_PEN,
+    );
+    // Light pen coordinates are compatible with sprite coordinates.
+    assert_eq!(vic.read(registers::LIGHT_PEN_X).unwrap(), 24 / 2);
+    assert_eq!(vic.read(registers::LIGHT_PEN_Y).unwrap(), 51);
+    vic.write(registers::INTERRUPT, flags::INTERRUPT_LIGHT_PEN)
+        .unwrap();
+
+    // Moving the light pen doesn't cause another interrupt until the next
+    // frame.
+    vic.set_light_pen_position(Some((DISPLAY_WINDOW_START + 100, 100)));
+    expect_no_interrupts_for(
+        (TOTAL_HEIGHT - DISPLAY_WINDOW_FIRST_LINE) * RASTER_LENGTH - 1,
+        &mut vic,
+    );
+    let vic_output = tick_until_any_irq(&mut vic);
+    assert_eq!(vic_output.video_output.raster_line, 100);
+    assert_eq!(vic.read(registers::LIGHT_PEN_X).unwrap(), 124 / 2);
+    assert_eq!(vic.read(registers::LIGHT_PEN_Y).unwrap(), 100);
+    vic.write(registers::INTERRUPT, flags::INTERRUPT_LIGHT_PEN)
+        .unwrap();
+
+    // Taking the light pen away from the screen keeps the latched position.
+    vic.set_light_pen_position(None);
+    expect_no_interrupts_for(TOTAL_HEIGHT * RASTER_LENGTH, &mut vic);
+    assert_eq!(vic.read(registers::LIGHT_PEN_X).unwrap(), 124 / 2);
+}
+
+#[test]
+fn chip_state() {
+    let mut vic = vic_for_testing();
+    vic.write(registers::BACKGROUND_COLOR_3, 0x05).unwrap();
+    let state = vic.chip_state().unwrap();
+    assert_eq!(state.name, "VIC");
+    assert_eq!(state.registers.len(), REGISTER_NAMES.len() + 2);
+    assert_eq!(state.registers[0x24], ChipRegister::new("BGCOL3", 0xF5u8));
+    assert_eq!(state.registers.last(), Some(&ChipRegister::new("X", 0u16)));
+}
@@ -1,35 +1,419 @@
+use crate::audio::AudioBufferMonitor;
+use crate::audio::AudioSource;
+use crate::audio::Mixer;
+use crate::backend::Backend;
+use crate::backend::BackendKind;
+use crate::backend::Osd;
+use crate::capture::capture_path;
+use crate::capture::save_screenshot;
+use crate::capture::GifRecorder;
+use crate::cheats::Cheat;
+use crate::cheats::CheatCommand;
+use crate::cheats::CheatError;
+use crate::cheats::CheatTable;
+use crate::crt::CrtEffects;
+use crate::crt::ThreadedCrtFilter;
 use crate::debugger::adapter::DebugAdapter;
+use crate::debugger::adapter::StdioDebugAdapter;
+use crate::debugger::adapter::TcpDebugAdapter;
+use crate::debugger::coverage::CoverageTracker;
+use crate::debugger::gdb::GdbAdapter;
+use crate::debugger::monitor::MonitorAdapter;
+use crate::debugger::symbols::read_dasm_symbols;
+use crate::debugger::symbols::RegisterNames;
+use crate::debugger::trace::Tracer;
 use crate::debugger::Debugger;
+use crate::debugger::LaunchRequest;
+use crate::headless::HeadlessRunner;
+use crate::input_log::read_input_log;
+use crate::input_log::InputLogController;
+use crate::pacing::FrameGovernor;
+use crate::pacing::SyncMode;
+use crate::text::draw_text;
+use crate::text::text_size;
+#[cfg(feature = "desktop")]
+use crate::watch::FileWatcher;
+#[cfg(feature = "desktop")]
+use crate::watch::WatchingController;
 use clap::Parser;
+use image::Rgba;
 use image::RgbaImage;
-use piston::{Event, EventLoop, WindowSettings};
-use piston_window::{
-    Filter, G2d, G2dTexture, G2dTextureContext, GfxDevice, PistonWindow, Texture, TextureSettings,
+use piston::{
+    Button, ButtonArgs, ButtonEvent, ButtonState, Event, FileDrag, Input, Key, Loop, Motion,
+    MouseCursorEvent, PressEvent,
 };
-use sdl2_window::Sdl2Window;
 use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use ya6502::cpu::CpuError;
 use ya6502::cpu::MachineInspector;
+use ya6502::cpu::PowerOnState;
+use ya6502::memory::RamPattern;
+
+/// Power-on RAM patterns that can be selected from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum RamPatternArg {
+    /// All bytes set to $00.
+    Zeros,
+    /// Alternating blocks of $00 and $FF, typical for a C64. See
+    /// `--ram-stripe-size`.
+    Stripes,
+    /// Random bytes.
+    Random,
+}
+
+/// Protocols that debugger clients can use to connect to the emulator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum DebuggerProtocol {
+    /// Debug Adapter Protocol, used by VS Code.
+    Dap,
+    /// GDB Remote Serial Protocol.
+    Gdb,
+}
 
 #[derive(Parser)]
 pub struct CommonCliArguments {
     #[clap(long)]
     pub debugger: bool,
+    /// Starts a text-based machine monitor in the terminal instead of
+    /// listening for a debugger connection.
+    #[clap(long, conflicts_with = "debugger")]
+    pub monitor: bool,
     #[clap(long, default_value = "1234")]
     pub debugger_port: u16,
+    /// Talks to the debugger client over the standard input and output
+    /// instead of listening on a TCP port. Only supported by the DAP protocol.
+    #[clap(long, requires = "debugger", conflicts_with = "debugger-protocol")]
+    pub debugger_stdio: bool,
+    /// Protocol used by the debugger client. Defaults to DAP.
+    #[clap(long, arg_enum)]
+    pub debugger_protocol: Option<DebuggerProtocol>,
+    /// Symbol file produced by DASM (using the `-s` option). The symbols can
+    /// be used in debugger expressions.
+    #[clap(long)]
+    pub debugger_symbols: Option<String>,
+    /// Writes a log of executed instructions to a given file.
+    #[clap(long)]
+    pub trace: Option<String>,
+    /// Only keeps a given number of most recent instructions in memory and
+    /// writes them to the trace file once the machine halts due to an error.
+    #[clap(long, requires = "trace")]
+    pub trace_buffer: Option<usize>,
+    /// Also logs every memory read and write performed by the CPU, along with
+    /// the transferred value and the cycle number.
+    #[clap(long, requires = "trace")]
+    pub trace_bus: bool,
+    /// Records which instructions have been executed and writes the coverage
+    /// map to a given file when the emulator exits.
+    #[clap(long)]
+    pub coverage: Option<String>,
+    /// Also records which memory locations have been read and written.
+    #[clap(long, requires = "coverage")]
+    pub coverage_data: bool,
+    /// Starts the CPU with the same register values every time instead of
+    /// random ones, which makes runs (and traces) reproducible.
+    #[clap(long)]
+    pub deterministic: bool,
+    /// Keeps running after an unknown opcode or a write to an unsupported
+    /// register, instead of halting the machine. Each unknown opcode and
+    /// unsupported register is only reported once.
+    #[clap(long)]
+    pub permissive: bool,
+    /// Contents of RAM after powering on. Random contents are also affected
+    /// by `--deterministic`.
+    #[clap(long, arg_enum, default_value = "zeros")]
+    pub ram_pattern: RamPatternArg,
+    /// Size of each block of the `stripes` RAM pattern, in bytes. Has to be
+    /// positive.
+    #[clap(long, default_value = "64")]
+    pub ram_stripe_size: NonZeroUsize,
+    /// Audio latency, in milliseconds. Lower values make the sound more
+    /// responsive, but increase the risk of crackling.
+    #[clap(long, default_value = "50")]
+    pub audio_latency: u64,
+    /// Emulation speed, relative to the real machine. Has to be positive.
+    #[clap(long, default_value = "1", parse(try_from_str = parse_speed))]
+    pub speed: f64,
+    /// Source of timing that paces the emulation.
+    #[clap(long, arg_enum, default_value = "timer")]
+    pub sync: SyncMode,
+    /// Maximum number of frames in a row that may be emulated without being
+    /// displayed if the computer can't keep up. 0 disables frame skipping.
+    #[clap(long, default_value = "4")]
+    pub max_frame_skip: u32,
+    /// Runs the emulator without opening a window (and without sound) for a
+    /// number of frames given by `--frames`.
+    #[clap(long)]
+    pub headless: bool,
+    /// Number of frames to emulate in the headless mode.
+    #[clap(long, default_value = "60")]
+    pub frames: u32,
+    /// Directory where frames rendered in the headless mode are stored as PNG
+    /// files.
+    #[clap(long, requires = "headless")]
+    pub frame_dir: Option<String>,
+    /// File where hashes of frames rendered in the headless mode are written,
+    /// one per line.
+    #[clap(long, requires = "headless")]
+    pub frame_hashes: Option<String>,
+    /// Key mapping configuration file, in JSON or TOML format (depending on
+    /// the file name extension).
+    #[clap(long)]
+    pub keymap: Option<String>,
+    /// Directory where screenshots and recordings are saved.
+    #[clap(long, default_value = ".")]
+    pub capture_dir: String,
+    /// Pins a memory location to a given value, e.g. `--cheat '$80=$05'`.
+    /// Both numbers are hexadecimal. Can be repeated.
+    #[clap(long = "cheat")]
+    pub cheats: Vec<Cheat>,
+    /// Records all input events, along with frame numbers, to a given file.
+    #[clap(long, conflicts_with = "replay-input")]
+    pub record_input: Option<String>,
+    /// Replays input events recorded with `--record-input`. Use together with
+    /// `--deterministic` to reproduce the recorded session exactly.
+    #[clap(long)]
+    pub replay_input: Option<String>,
+    /// Darkens gaps between scanlines, like on a CRT screen.
+    #[clap(long)]
+    pub scanlines: bool,
+    /// Imitates the afterglow of CRT phosphor by blending each frame with the
+    /// previous ones. Makes objects that flicker at 30 Hz look translucent
+    /// instead of blinking.
+    #[clap(long)]
+    pub phosphor: bool,
+    /// Blurs colors horizontally while keeping the brightness sharp, imitating
+    /// the limited color bandwidth of composite video.
+    #[clap(long)]
+    pub color_bleeding: bool,
+    /// Library used to open the window and render frames.
+    #[clap(long, arg_enum, default_value = "piston")]
+    pub backend: BackendKind,
+    /// Reloads the program and resets the machine whenever the program file
+    /// changes, e.g. after being rebuilt by an assembler. Breakpoints set in
+    /// the debugger are kept.
+    #[clap(long)]
+    pub watch: bool,
+}
+
+/// Parses the `--speed` argument, rejecting values that would stop the frame
+/// governor.
+fn parse_speed(arg: &str) -> Result<f64, String> {
+    let speed: f64 = arg.parse().map_err(|e| format!("{}", e))?;
+    if !(speed > 0.0 && speed.is_finite()) {
+        return Err("The speed has to be a positive number".to_string());
+    }
+    return Ok(speed);
+}
+
+impl CommonCliArguments {
+    /// Creates a debugger, if it's enabled by the command line arguments.
+    pub fn create_debugger(&self) -> Option<Debugger<Box<dyn DebugAdapter>>> {
+        let adapter: Box<dyn DebugAdapter> = if self.monitor {
+            Box::new(MonitorAdapter::new())
+        } else if self.debugger_stdio {
+            Box::new(StdioDebugAdapter::new())
+        } else if self.debugger {
+            match self.debugger_protocol.unwrap_or(DebuggerProtocol::Dap) {
+                DebuggerProtocol::Dap => Box::new(TcpDebugAdapter::new(self.debugger_port)),
+                DebuggerProtocol::Gdb => Box::new(GdbAdapter::new(self.debugger_port)),
+            }
+        } else {
+            return None;
+        };
+        let mut debugger = Debugger::new(adapter);
+        if let Some(path) = &self.debugger_symbols {
+            let file = File::open(path).expect("Unable to open the symbol file");
+            let symbols =
+                read_dasm_symbols(BufReader::new(file)).expect("Unable to read the symbol file");
+            debugger = debugger.with_symbols(symbols);
+        }
+        return Some(debugger);
+    }
+
+    /// Creates an instruction tracer, if it's enabled by the command line
+    /// arguments.
+    pub fn create_tracer(&self) -> Option<Tracer<BufWriter<File>>> {
+        let file = File::create(self.trace.as_ref()?).expect("Unable to create the trace file");
+        let mut tracer = Tracer::new(BufWriter::new(file));
+        if self.trace_bus {
+            tracer = tracer.with_bus_cycles();
+        }
+        return Some(match self.trace_buffer {
+            Some(size) => tracer.with_ring_buffer(size),
+            None => tracer,
+        });
+    }
+
+    /// Creates a code coverage tracker, if it's enabled by the command line
+    /// arguments.
+    pub fn create_coverage_tracker(&self) -> Option<CoverageTracker<BufWriter<File>>> {
+        let file =
+            File::create(self.coverage.as_ref()?).expect("Unable to create the coverage file");
+        let mut tracker = CoverageTracker::new(BufWriter::new(file));
+        if self.coverage_data {
+            tracker = tracker.with_data_accesses();
+        }
+        return Some(tracker);
+    }
+
+    /// Returns the CPU power-on state requested by the command line arguments.
+    pub fn power_on_state(&self) -> PowerOnState {
+        if self.deterministic {
+            PowerOnState::Seeded(0)
+        } else {
+            PowerOnState::Random
+        }
+    }
+
+    /// Returns the power-on RAM pattern requested by the command line
+    /// arguments.
+    pub fn ram_pattern(&self) -> RamPattern {
+        match self.ram_pattern {
+            RamPatternArg::Zeros => RamPattern::Filled(0),
+            RamPatternArg::Stripes => RamPattern::Stripes {
+                block_size: self.ram_stripe_size,
+            },
+            RamPatternArg::Random => RamPattern::Random(self.power_on_state()),
+        }
+    }
+
+    /// Returns the CRT effects requested by the command line arguments.
+    pub fn crt_effects(&self) -> CrtEffects {
+        CrtEffects {
+            scanlines: self.scanlines,
+            phosphor: self.phosphor,
+            color_bleeding: self.color_bleeding,
+        }
+    }
+
+    /// Wraps a given controller, so that it records or replays input events as
+    /// requested by the command line arguments.
+    pub fn create_input_log_controller<C: AppController>(
+        &self,
+        controller: C,
+    ) -> InputLogController<C> {
+        let mut controller = InputLogController::new(controller);
+        if let Some(path) = &self.record_input {
+            let file = File::create(path).expect("Unable to create the input log file");
+            controller = controller.with_recording(BufWriter::new(file));
+        }
+        if let Some(path) = &self.replay_input {
+            let file = File::open(path).expect("Unable to open the input log file");
+            let entries =
+                read_input_log(BufReader::new(file)).expect("Unable to read the input log file");
+            controller = controller.with_playback(entries);
+        }
+        return controller;
+    }
+
+    /// Wraps a given controller, so that it reloads the program from a given
+    /// file whenever it changes, if requested by the command line arguments.
+    #[cfg(feature = "desktop")]
+    pub fn create_watching_controller<C: AppController>(
+        &self,
+        controller: C,
+        program: Option<&Path>,
+    ) -> WatchingController<C> {
+        let mut controller = WatchingController::new(controller);
+        if let (true, Some(path)) = (self.watch, program) {
+            let watcher = FileWatcher::new(path).expect("Unable to watch the program file");
+            controller = controller.with_watcher(watcher);
+        }
+        return controller;
+    }
+
+    /// Creates a runner for the headless mode that stores frames as requested
+    /// by the command line arguments.
+    pub fn create_headless_runner<C: AppController>(&self, controller: C) -> HeadlessRunner<C> {
+        let mut runner = HeadlessRunner::new(controller);
+        if let Some(path) = &self.frame_dir {
+            runner = runner.with_frame_dir(path);
+        }
+        if let Some(path) = &self.frame_hashes {
+            runner = runner.with_hash_file(path);
+        }
+        return runner;
+    }
 }
 
 /// A generic interface that provides basic operations common to all emulated
 /// machines.
 pub trait Machine: MachineInspector {
+    /// Resets the machine, as if the reset button was pressed.
     fn reset(&mut self);
+    /// Performs a single clock tick. Reports whether it completed a frame, or
+    /// an error that stopped the CPU.
     fn tick(&mut self) -> MachineTickResult;
+    /// Returns the frame image. It's guaranteed to contain a complete frame
+    /// right after [`Machine::tick`] reports [`FrameStatus::Complete`].
     fn frame_image(&self) -> &RgbaImage;
+    /// Returns a human-readable description of the machine state, for
+    /// debugging.
     fn display_state(&self) -> String;
+    /// Returns the current beam position, as seen by the video chip.
+    fn raster_position(&self) -> RasterPosition;
+    /// Returns names of the memory-mapped hardware registers, used by the
+    /// debugger to annotate the disassembly.
+    fn register_names(&self) -> RegisterNames {
+        RegisterNames::new()
+    }
+    /// Replaces the program (a cartridge, a program file, etc.) with one
+    /// loaded from a given file. Used when the debugger client launches a
+    /// program; the machine is reset afterwards.
+    fn load_program(&mut self, _path: &Path) -> Result<(), Box<dyn Error>> {
+        Err("This machine can't load programs on demand".into())
+    }
+    /// Returns the cheats applied to the memory accessed by the CPU, or
+    /// `None` if the machine doesn't support cheats.
+    fn mut_cheats(&mut self) -> Option<&mut CheatTable> {
+        None
+    }
+    /// Runs the machine until it completes a frame and returns the frame
+    /// image. Unlike [`MachineController`], it doesn't involve the debugger
+    /// or tracing, so it's a simple way for tests and library users to
+    /// advance the machine by exactly one frame and examine its state.
+    fn run_frame(&mut self) -> Result<&RgbaImage, CpuError> {
+        loop {
+            if let FrameStatus::Complete = self.tick()? {
+                return Ok(self.frame_image());
+            }
+        }
+    }
+    /// Runs the machine for a given number of frames and calls `on_frame`
+    /// after each of them, with the machine ready to be examined. Stops at the
+    /// first error.
+    fn run_frames(&mut self, n_frames: u32, mut on_frame: impl FnMut(&Self)) -> Result<(), CpuError>
+    where
+        Self: Sized,
+    {
+        for _ in 0..n_frames {
+            self.run_frame()?;
+            on_frame(self);
+        }
+        return Ok(());
+    }
+}
+
+/// Position of the beam that draws the picture. The units are specific to a
+/// given video chip; for example, the horizontal position can be expressed in
+/// color clocks or CPU cycles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RasterPosition {
+    pub line: u32,
+    pub x: u32,
 }
 
-pub type MachineTickResult = Result<FrameStatus, Box<dyn Error>>;
+pub type MachineTickResult = Result<FrameStatus, CpuError>;
 
 pub enum FrameStatus {
     Pending,
@@ -40,20 +424,50 @@ pub enum FrameStatus {
 pub struct MachineController<'a, M: Machine, A: DebugAdapter> {
     machine: &'a mut M,
     running: bool,
+    /// Set when the user pauses the machine. Unlike stopping it with a
+    /// debugger, pausing doesn't interrupt the current frame.
+    paused: bool,
+    /// Number of frames completed by the machine so far.
+    frame_count: u64,
     interrupted: Arc<AtomicBool>,
     debugger: Option<Debugger<A>>,
+    tracer: Option<Tracer<BufWriter<File>>>,
+    coverage_tracker: Option<CoverageTracker<BufWriter<File>>>,
 }
 
 impl<'a, M: Machine, A: DebugAdapter> MachineController<'a, M, A> {
     pub fn new(machine: &'a mut M, debugger: Option<Debugger<A>>) -> Self {
+        let debugger =
+            debugger.map(|debugger| debugger.with_register_names(machine.register_names()));
         return Self {
             machine,
             running: false,
+            paused: false,
+            frame_count: 0,
             interrupted: Arc::new(AtomicBool::new(false)),
             debugger,
+            tracer: None,
+            coverage_tracker: None,
         };
     }
 
+    pub fn set_tracer(&mut self, tracer: Option<Tracer<BufWriter<File>>>) {
+        self.tracer = tracer;
+    }
+
+    pub fn set_coverage_tracker(&mut self, tracker: Option<CoverageTracker<BufWriter<File>>>) {
+        self.coverage_tracker = tracker;
+    }
+
+    /// Installs cheats in the machine. They stay installed when the machine
+    /// is reset.
+    pub fn install_cheats(&mut self, cheats: &[Cheat]) -> Result<(), CheatError> {
+        for cheat in cheats {
+            execute_cheat_command(self.machine, CheatCommand::Install(*cheat))?;
+        }
+        return Ok(());
+    }
+
     pub fn machine(&self) -> &M {
         self.machine
     }
@@ -62,9 +476,32 @@ impl<'a, M: Machine, A: DebugAdapter> MachineController<'a, M, A> {
         self.machine
     }
 
+    /// Returns `true` if the machine has been paused by the user.
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses or resumes the machine. A paused machine doesn't run, but the
+    /// debugger messages are still processed, and resuming the machine from
+    /// the debugger also lifts the pause.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Returns the number of frames completed by the machine so far. Unlike
+    /// counting the update events, it doesn't include the ones during which
+    /// the machine was paused, stopped by the debugger, or halted.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
     pub fn reset(&mut self) {
         self.machine.reset();
         self.running = true;
+        if let Some(tracker) = &mut self.coverage_tracker {
+            // Otherwise, the first instruction wouldn't be recorded.
+            tracker.update(self.machine);
+        }
         if let Some(debugger) = &mut self.debugger {
             if let Err(e) = debugger.update(self.machine) {
                 eprintln!("Debugger error: {}", e);
@@ -73,24 +510,105 @@ impl<'a, M: Machine, A: DebugAdapter> MachineController<'a, M, A> {
     }
 
     pub fn run_until_end_of_frame(&mut self) {
+        let mut launch_request = None;
         if let Some(debugger) = &mut self.debugger {
+            let stopped = debugger.stopped();
             debugger.process_messages(self.machine);
+            if stopped && !debugger.stopped() {
+                // The user explicitly resumed the machine in the debugger, so
+                // it shouldn't stay paused.
+                self.paused = false;
+            }
+            let machine = &mut *self.machine;
+            if let Err(e) =
+                debugger.execute_cheat_commands(|command| execute_cheat_command(machine, command))
+            {
+                eprintln!("Debugger error: {}", e);
+            }
+            launch_request = debugger.take_launch_request();
+        }
+        if let Some(request) = launch_request {
+            self.launch(request);
         }
         while self.running() {
             match self.tick() {
                 Ok(FrameStatus::Pending) => {}
-                Ok(FrameStatus::Complete) => return,
+                Ok(FrameStatus::Complete) => {
+                    self.frame_count += 1;
+                    return;
+                }
                 Err(e) => {
-                    self.running = false;
-                    eprintln!("ERROR: {}. Machine halted.", e);
+                    match self.debugger {
+                        Some(_) => eprintln!("ERROR: {}. Machine paused in the debugger.", e),
+                        None => eprintln!("ERROR: {}. Machine halted.", e),
+                    }
                     eprintln!("{}", self.display_state());
+                    if let Some(tracer) = &mut self.tracer {
+                        if let Err(e) = tracer.dump() {
+                            eprintln!("Tracer error: {}", e);
+                        }
+                    }
+                    match &mut self.debugger {
+                        // With a debugger attached, the machine is only
+                        // paused, so that the user can inspect it, and
+                        // possibly get the program back on track.
+                        Some(debugger) => {
+                            if let Err(e) = debugger.report_error(&e) {
+                                eprintln!("Debugger error: {}", e);
+                            }
+                        }
+                        None => self.running = false,
+                    }
                 }
             }
         }
     }
 
+    /// Replaces the program with one loaded from a given file and resets the
+    /// machine. If the program can't be loaded, the machine keeps running the
+    /// current one.
+    pub fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.machine.load_program(path)?;
+        self.clear_coverage();
+        self.reset();
+        return Ok(());
+    }
+
+    /// Loads the program requested by the debugger client and resets the
+    /// machine. If the program can't be loaded, the error is reported to the
+    /// client, and the machine keeps running the current program.
+    fn launch(&mut self, request: LaunchRequest) {
+        let result = match &request.program {
+            Some(program) => self
+                .machine
+                .load_program(program)
+                .map_err(|e| format!("Unable to load {}: {}", program.display(), e)),
+            None => Ok(()),
+        };
+        let loaded = result.is_ok();
+        if let Some(debugger) = &mut self.debugger {
+            if let Err(e) = debugger.finish_launch(result) {
+                eprintln!("Debugger error: {}", e);
+            }
+        }
+        if loaded {
+            if request.program.is_some() {
+                self.clear_coverage();
+            }
+            self.reset();
+        }
+    }
+
+    /// Forgets the coverage of a program that has just been replaced.
+    fn clear_coverage(&mut self) {
+        if let Some(tracker) = &mut self.coverage_tracker {
+            tracker.clear();
+        }
+    }
+
     fn running(&self) -> bool {
         self.running
+            && !self.paused
             && !self.interrupted.load(Ordering::Relaxed)
             && match &self.debugger {
                 Some(debugger) => !debugger.stopped(),
@@ -100,7 +618,19 @@ impl<'a, M: Machine, A: DebugAdapter> MachineController<'a, M, A> {
 
     fn tick(&mut self) -> MachineTickResult {
         let tick_result = self.machine.tick();
+        if let Some(tracer) = &mut self.tracer {
+            if let Err(e) = tracer.update(self.machine) {
+                eprintln!("Tracer error: {}", e);
+            }
+        }
+        if let Some(tracker) = &mut self.coverage_tracker {
+            tracker.update(self.machine);
+        }
         if let Some(debugger) = &mut self.debugger {
+            debugger.update_raster(
+                self.machine.raster_position().line,
+                matches!(tick_result, Ok(FrameStatus::Complete)),
+            );
             if let Err(e) = debugger.update(self.machine) {
                 eprintln!("Debugger error: {}", e);
             }
@@ -119,57 +649,301 @@ impl<'a, M: Machine, A: DebugAdapter> MachineController<'a, M, A> {
     pub fn display_state(&self) -> String {
         self.machine().display_state()
     }
+
+    /// Returns a short summary of the CPU registers and the beam position.
+    pub fn machine_status(&self) -> String {
+        let machine = self.machine();
+        let raster_position = machine.raster_position();
+        let mut status = format!(
+            "PC:{:04X} A:{:02X} X:{:02X} Y:{:02X} SP:{:02X}\nLINE:{} X:{}",
+            machine.reg_pc(),
+            machine.reg_a(),
+            machine.reg_x(),
+            machine.reg_y(),
+            machine.reg_sp(),
+            raster_position.line,
+            raster_position.x,
+        );
+        let ignored_errors = machine.ignored_error_count();
+        if ignored_errors > 0 {
+            status += &format!(" IGNORED ERRORS:{}", ignored_errors);
+        }
+        return status;
+    }
+}
+
+fn execute_cheat_command(
+    machine: &mut impl Machine,
+    command: CheatCommand,
+) -> Result<(), CheatError> {
+    return match machine.mut_cheats() {
+        Some(cheats) => cheats.execute(command),
+        None => Err(CheatError::Unsupported),
+    };
 }
 
 pub trait AppController {
     fn frame_image(&self) -> &RgbaImage;
     fn reset(&mut self);
     fn interrupted(&self) -> Arc<AtomicBool>;
+    /// See [`MachineController::paused`].
+    fn paused(&self) -> bool;
+    /// See [`MachineController::set_paused`].
+    fn set_paused(&mut self, paused: bool);
+    /// See [`MachineController::frame_count`].
+    fn frame_count(&self) -> u64;
+    /// See [`MachineController::load_program`].
+    fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn Error>>;
 
     /// Handles Piston events.
     fn event(&mut self, event: &Event);
     fn display_machine_state(&self) -> String;
+    /// Returns a short summary of the machine state, displayed by the
+    /// overlay. See [`MachineController::machine_status`].
+    fn machine_status(&self) -> String;
 }
 
-pub struct Application<C: AppController> {
-    window: PistonWindow<Sdl2Window>,
+pub struct Application<C: AppController, B: Backend = Box<dyn Backend>> {
+    backend: B,
     controller: C,
-    view: View,
+    governor: FrameGovernor,
+    speed: f64,
+    slow_motion: bool,
+    fast_forward: bool,
+    /// Set after the user requests advancing a paused machine by a single
+    /// frame.
+    frame_advance_pending: bool,
+    /// Whether any of the Ctrl keys is held, which turns some keys into
+    /// hotkeys.
+    ctrl_pressed: bool,
+    frame_rate: u64,
+    capture_dir: PathBuf,
+    recorder: Option<GifRecorder>,
+    /// Threads that still encode finished recordings.
+    encoder_threads: Vec<thread::JoinHandle<image::ImageResult<()>>>,
+    /// Until this moment, the screen flashes to indicate that a screenshot has
+    /// been taken.
+    screenshot_flash_until: Option<Instant>,
+    show_overlay: bool,
+    frame_counter: FrameCounter,
+    crt_filter: ThreadedCrtFilter,
+    scanlines: bool,
+    /// Mixer of the machine's audio channels, controlled by hotkeys.
+    mixer: Option<Mixer>,
+}
+
+/// Toggles the fast-forward mode.
+const FAST_FORWARD_KEY: Key = Key::F10;
+/// How long a single update of the event loop may spend on emulating frames in
+/// the fast-forward mode. Leaves some time for rendering and handling input.
+const FAST_FORWARD_TIME_BUDGET: Duration = Duration::from_millis(12);
+/// Saves the current frame as a PNG file.
+const SCREENSHOT_KEY: Key = Key::F9;
+/// Starts or stops recording an animated GIF file.
+const RECORD_KEY: Key = Key::F11;
+const SCREENSHOT_FLASH_DURATION: Duration = Duration::from_millis(150);
+/// Shows or hides the overlay with machine state.
+const OVERLAY_KEY: Key = Key::PageUp;
+/// Pauses or resumes the machine. Used together with Ctrl, just like the other
+/// keys that would otherwise clash with the emulated keyboards and joysticks.
+const PAUSE_KEY: Key = Key::P;
+/// Advances the machine by a single frame and pauses it.
+const FRAME_ADVANCE_KEY: Key = Key::Period;
+/// Toggles the slow-motion mode.
+const SLOW_MOTION_KEY: Key = Key::S;
+/// Emulation speed in the slow-motion mode, relative to the speed setting.
+const SLOW_MOTION_SPEED: f64 = 0.25;
+
+/// Returns the number of an audio mixer channel muted by a given digit key.
+fn mixer_channel(key: Key) -> Option<usize> {
+    let keys = [
+        Key::D0,
+        Key::D1,
+        Key::D2,
+        Key::D3,
+        Key::D4,
+        Key::D5,
+        Key::D6,
+        Key::D7,
+        Key::D8,
+        Key::D9,
+    ];
+    return keys.iter().position(|&k| k == key);
 }
 
+#[cfg(feature = "desktop")]
 impl<C: AppController> Application<C> {
     /// Creates an emulator application that processes input using a given
-    /// controller.
-    pub fn new(controller: C, window_title: &str, pixel_width: u32, pixel_height: u32) -> Self {
+    /// controller. Opens a window using a given backend, with each pixel of
+    /// the frame image scaled to given dimensions.
+    pub fn new(
+        controller: C,
+        backend_kind: BackendKind,
+        window_title: &str,
+        pixel_width: u32,
+        pixel_height: u32,
+    ) -> Self {
         let initial_frame_image = controller.frame_image();
-        let window_width = initial_frame_image.width() * pixel_width;
-        let window_height = initial_frame_image.height() * pixel_height;
-        let window_settings = WindowSettings::new(window_title, [window_width, window_height]);
-        let mut window: PistonWindow<Sdl2Window> =
-            window_settings.build().expect("Could not build a window");
-        window.set_ups(60);
-        let texture_context = window.create_texture_context();
-        let view = View::new(texture_context, initial_frame_image);
+        let window_size = [
+            initial_frame_image.width() * pixel_width,
+            initial_frame_image.height() * pixel_height,
+        ];
+        let backend = backend_kind.create(window_title, window_size);
+        return Self::with_backend(controller, backend);
+    }
+}
 
+impl<C: AppController, B: Backend> Application<C, B> {
+    /// Creates an emulator application that processes input using a given
+    /// controller and presents frames using a given backend.
+    pub fn with_backend(controller: C, mut backend: B) -> Self {
+        let frame_rate = 60;
+        let governor = FrameGovernor::new(SyncMode::Vsync, frame_rate);
+        backend.set_ups(governor.update_rate());
         Self {
-            window,
-            view,
+            backend,
             controller,
+            governor,
+            speed: 1.0,
+            slow_motion: false,
+            fast_forward: false,
+            frame_advance_pending: false,
+            ctrl_pressed: false,
+            frame_rate,
+            capture_dir: PathBuf::from("."),
+            recorder: None,
+            encoder_threads: vec![],
+            screenshot_flash_until: None,
+            show_overlay: false,
+            frame_counter: FrameCounter::new(),
+            crt_filter: ThreadedCrtFilter::new(CrtEffects::default()),
+            scanlines: false,
+            mixer: None,
         }
     }
 
+    /// Changes the number of frames emulated per second. The default is 60.
+    pub fn set_frame_rate(&mut self, frames_per_second: u64) {
+        self.frame_rate = frames_per_second;
+        self.governor.set_frame_rate(frames_per_second);
+        self.backend.set_ups(self.governor.update_rate());
+    }
+
+    /// Changes the source of timing that paces the emulation. The default is
+    /// [`SyncMode::Vsync`], which simply emulates a frame on every update
+    /// event of the backend.
+    pub fn set_sync_mode(&mut self, mode: SyncMode) {
+        self.governor.set_mode(mode);
+        self.backend.set_ups(self.governor.update_rate());
+    }
+
+    /// Limits how many frames in a row may be emulated without being
+    /// displayed. See [`FrameGovernor::set_max_frame_skip`].
+    pub fn set_max_frame_skip(&mut self, max_frame_skip: u32) {
+        self.governor.set_max_frame_skip(max_frame_skip);
+    }
+
+    /// Sets a monitor of the audio buffer that the machine writes to. It's
+    /// required to pace the emulation by the audio clock.
+    pub fn set_audio_monitor(&mut self, monitor: AudioBufferMonitor) {
+        self.governor.set_audio_monitor(monitor);
+    }
+
+    /// Starts playing audio samples from a given source using the backend.
+    pub fn play_audio(&mut self, source: AudioSource) {
+        self.backend.play_audio(source);
+    }
+
+    /// Sets a mixer of the machine's audio channels. This allows muting the
+    /// channels with Ctrl and a digit key: Ctrl+0 toggles channel 0, and so on.
+    pub fn set_mixer(&mut self, mixer: Mixer) {
+        self.mixer = Some(mixer);
+    }
+
+    /// Changes the directory where screenshots and recordings are saved. The
+    /// default is the current directory.
+    pub fn set_capture_dir(&mut self, path: impl Into<PathBuf>) {
+        self.capture_dir = path.into();
+    }
+
+    /// Enables CRT effects. They only affect what's displayed in the window;
+    /// screenshots and recordings still contain the original frames. The
+    /// frames are processed on a separate thread while the next frame is
+    /// being emulated.
+    pub fn set_crt_effects(&mut self, effects: CrtEffects) {
+        self.scanlines = effects.scanlines;
+        self.crt_filter = ThreadedCrtFilter::new(effects);
+    }
+
+    /// Changes the emulation speed, relative to the real machine. For
+    /// example, with speed set to 2, two frames are emulated per each frame
+    /// displayed on the screen.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+        self.update_speed();
+    }
+
+    fn update_speed(&mut self) {
+        self.governor.set_speed(if self.slow_motion {
+            self.speed * SLOW_MOTION_SPEED
+        } else {
+            self.speed
+        });
+    }
+
     /// Starts the machine and runs the event loop until the user decides to
     /// quit.
     pub fn run(&mut self) {
+        self.run_event_loop();
+        self.stop_recording();
+        for encoder_thread in self.encoder_threads.drain(..) {
+            if let Err(e) = encoder_thread.join().unwrap() {
+                eprintln!("Unable to save the recording: {}", e);
+            }
+        }
+    }
+
+    fn run_event_loop(&mut self) {
         self.controller.reset();
-        while let Some(e) = self.window.next() {
-            self.controller.event(&e);
-            let view = &mut self.view;
-            let frame_image = self.controller.frame_image();
-            self.window.draw_2d(&e, |ctx, graphics, device| {
-                view.draw(frame_image, ctx, graphics, device);
-            });
-            self.window.event(&e);
+        while let Some(e) = self.backend.next_event() {
+            match e.press_args() {
+                Some(Button::Keyboard(FAST_FORWARD_KEY)) => self.fast_forward = !self.fast_forward,
+                Some(Button::Keyboard(SCREENSHOT_KEY)) => self.take_screenshot(),
+                Some(Button::Keyboard(RECORD_KEY)) => self.toggle_recording(),
+                Some(Button::Keyboard(OVERLAY_KEY)) => self.show_overlay = !self.show_overlay,
+                _ => {}
+            }
+            let hotkey = self.handle_ctrl_hotkey(&e);
+            match &e {
+                _ if hotkey => {}
+                Event::Input(Input::FileDrag(FileDrag::Drop(path)), _) => self.load_program(path),
+                Event::Loop(Loop::Update(_)) => {
+                    if self.update(&e) > 0 {
+                        self.crt_filter.process(self.controller.frame_image());
+                        if let Some(recorder) = &self.recorder {
+                            recorder.add_frame(self.controller.frame_image());
+                        }
+                    }
+                }
+                _ => self.controller.event(&self.to_frame_coordinates(&e)),
+            }
+            self.crt_filter.poll();
+            let filtered_image = self
+                .crt_filter
+                .image()
+                .unwrap_or_else(|| self.controller.frame_image());
+            let overlay_image = self
+                .show_overlay
+                .then(|| self.overlay_image(filtered_image));
+            let frame_image = overlay_image.as_ref().unwrap_or(filtered_image);
+            let osd = Osd {
+                recording: self.recorder.is_some(),
+                flash: self
+                    .screenshot_flash_until
+                    .map_or(false, |until| Instant::now() < until),
+                scanlines: self.scanlines,
+            };
+            self.backend.present(&e, frame_image, &osd);
             if self.controller.interrupted().load(Ordering::Relaxed) {
                 eprintln!("Interrupted!");
                 eprintln!("{}", self.controller.display_machine_state());
@@ -178,6 +952,219 @@ impl<C: AppController> Application<C> {
         }
     }
 
+    /// Handles keys that are used together with Ctrl. Returns `true` if the
+    /// event has been consumed and shouldn't reach the machine.
+    fn handle_ctrl_hotkey(&mut self, event: &Event) -> bool {
+        let (key, pressed) = match event.button_args() {
+            Some(ButtonArgs {
+                button: Button::Keyboard(key),
+                state,
+                ..
+            }) => (key, state == ButtonState::Press),
+            _ => return false,
+        };
+        if key == Key::LCtrl || key == Key::RCtrl {
+            self.ctrl_pressed = pressed;
+            return false;
+        }
+        if !self.ctrl_pressed {
+            return false;
+        }
+        match key {
+            PAUSE_KEY if pressed => self.toggle_pause(),
+            FRAME_ADVANCE_KEY if pressed => self.advance_frame(),
+            SLOW_MOTION_KEY if pressed => self.toggle_slow_motion(),
+            // Also swallow releases, since the machine never saw the presses.
+            PAUSE_KEY | FRAME_ADVANCE_KEY | SLOW_MOTION_KEY => {}
+            _ => match mixer_channel(key) {
+                Some(channel) if self.has_mixer_channel(channel) => {
+                    if pressed {
+                        self.toggle_mute(channel);
+                    }
+                }
+                _ => return false,
+            },
+        }
+        return true;
+    }
+
+    fn has_mixer_channel(&self, channel: usize) -> bool {
+        self.mixer
+            .as_ref()
+            .map_or(false, |mixer| channel < mixer.n_channels())
+    }
+
+    fn toggle_mute(&mut self, channel: usize) {
+        if let Some(mixer) = &mut self.mixer {
+            let muted = !mixer.muted(channel);
+            mixer.set_muted(channel, muted);
+            eprintln!(
+                "Audio channel {} {}",
+                channel,
+                if muted { "muted" } else { "unmuted" }
+            );
+        }
+    }
+
+    /// Loads a program from a file that has been dropped onto the window.
+    fn load_program(&mut self, path: &Path) {
+        match self.controller.load_program(path) {
+            Ok(()) => eprintln!("Loaded {}", path.display()),
+            Err(e) => eprintln!("Unable to load {}: {}", path.display(), e),
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        let paused = !self.controller.paused();
+        self.controller.set_paused(paused);
+        self.frame_advance_pending = false;
+        eprintln!("{}", if paused { "Paused" } else { "Resumed" });
+    }
+
+    /// Pauses the machine, if it's not paused yet, and lets it run for a
+    /// single frame during the next update.
+    fn advance_frame(&mut self) {
+        self.controller.set_paused(true);
+        self.frame_advance_pending = true;
+    }
+
+    fn toggle_slow_motion(&mut self) {
+        self.slow_motion = !self.slow_motion;
+        self.update_speed();
+        eprintln!(
+            "Slow motion {}",
+            if self.slow_motion { "on" } else { "off" }
+        );
+    }
+
+    /// Translates mouse cursor positions from window coordinates to frame image
+    /// pixels, so that controllers don't need to care about the window size.
+    /// Other events are passed through.
+    fn to_frame_coordinates(&self, event: &Event) -> Event {
+        match event.mouse_cursor_args() {
+            Some([x, y]) => {
+                let [width, height] = self.backend.size();
+                let frame_image = self.controller.frame_image();
+                Event::from(Motion::MouseCursor([
+                    x * frame_image.width() as f64 / width,
+                    y * frame_image.height() as f64 / height,
+                ]))
+            }
+            None => event.clone(),
+        }
+    }
+
+    /// Returns a copy of a given frame image with the machine state drawn on
+    /// top of it.
+    fn overlay_image(&self, frame_image: &RgbaImage) -> RgbaImage {
+        let mut image = frame_image.clone();
+        let mut text = format!(
+            "FPS:{:.1} EMU:{:.1}MS FRAME:{} SKIP:{}",
+            self.frame_counter.fps(),
+            self.frame_counter.emulation_time().as_secs_f64() * 1000.0,
+            self.frame_counter.frames(),
+            self.governor.skipped_frames(),
+        );
+        if self.controller.paused() {
+            text += " PAUSED";
+        } else if self.slow_motion {
+            text += " SLOW";
+        }
+        text += "\n";
+        text += &self.controller.machine_status();
+        let (width, height) = text_size(&text);
+        // Darken the background to keep the text readable.
+        for y in 0..(height + 2).min(image.height()) {
+            for x in 0..(width + 2).min(image.width()) {
+                let pixel = image.get_pixel_mut(x, y);
+                for channel in &mut pixel.0[..3] {
+                    *channel /= 4;
+                }
+            }
+        }
+        draw_text(&mut image, 1, 1, &text, Rgba([255, 255, 255, 255]));
+        return image;
+    }
+
+    fn take_screenshot(&mut self) {
+        let path = capture_path(&self.capture_dir, "screenshot", "png");
+        match save_screenshot(self.controller.frame_image(), &path) {
+            Ok(()) => {
+                eprintln!("Screenshot saved to {}", path.display());
+                self.screenshot_flash_until = Some(Instant::now() + SCREENSHOT_FLASH_DURATION);
+            }
+            Err(e) => eprintln!("Unable to save the screenshot: {}", e),
+        }
+    }
+
+    fn toggle_recording(&mut self) {
+        if self.recorder.is_some() {
+            self.stop_recording();
+            return;
+        }
+        let path = capture_path(&self.capture_dir, "recording", "gif");
+        let frame_duration = Duration::from_secs(1) / self.frame_rate as u32;
+        match GifRecorder::start(&path, frame_duration) {
+            Ok(recorder) => {
+                eprintln!("Recording to {}", path.display());
+                self.recorder = Some(recorder);
+            }
+            Err(e) => eprintln!("Unable to start recording: {}", e),
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            eprintln!("Recording stopped");
+            self.encoder_threads.push(recorder.finish());
+        }
+    }
+
+    /// Emulates frames for a single update of the event loop and returns how
+    /// many of them were emulated. A paused machine only runs a single frame
+    /// if requested by the user. Normally, the number of frames is decided
+    /// by the frame governor, depending on the sync mode and the speed
+    /// setting. In the fast-forward mode, the machine runs as fast as
+    /// possible, limited only by the time budget.
+    fn update(&mut self, update_event: &Event) -> u32 {
+        if self.controller.paused() {
+            if !self.frame_advance_pending {
+                // The machine won't run, but the debugger still needs to
+                // process its messages.
+                self.controller.event(update_event);
+                return 0;
+            }
+            self.frame_advance_pending = false;
+            self.controller.set_paused(false);
+            self.emulate_frame(update_event);
+            self.controller.set_paused(true);
+            return 1;
+        }
+        let start = Instant::now();
+        let mut frames = 0;
+        if self.fast_forward {
+            while start.elapsed() < FAST_FORWARD_TIME_BUDGET
+                && !self.controller.interrupted().load(Ordering::Relaxed)
+            {
+                self.emulate_frame(update_event);
+                frames += 1;
+            }
+            return frames;
+        }
+        self.governor.start_update(start);
+        while self.governor.frame_due(start) {
+            self.emulate_frame(update_event);
+            frames += 1;
+        }
+        return frames;
+    }
+
+    fn emulate_frame(&mut self, update_event: &Event) {
+        let start = Instant::now();
+        self.controller.event(update_event);
+        self.frame_counter.count(start.elapsed());
+    }
+
     /// Exposes a pointer to a thread-safe interruption flag. Once it's set to
     /// `true`, the main event loop finishes, allowing the program to quit
     /// gracefully.
@@ -186,53 +1173,95 @@ impl<C: AppController> Application<C> {
     }
 }
 
-struct View {
-    texture_context: G2dTextureContext,
-    texture: G2dTexture,
+/// Counts emulated frames and measures how many of them are emulated per
+/// second, as well as how long it takes to emulate a single frame.
+struct FrameCounter {
+    frames: u64,
+    /// Frames emulated since the start of the current measurement.
+    measured_frames: u32,
+    /// Time spent on emulating frames since the start of the current
+    /// measurement.
+    measured_emulation_time: Duration,
+    measurement_start: Instant,
+    fps: f64,
+    /// Average time of emulating a single frame.
+    emulation_time: Duration,
 }
 
-impl View {
-    fn new(mut texture_context: G2dTextureContext, initial_frame_image: &RgbaImage) -> Self {
-        let texture_settings = TextureSettings::new().mag(Filter::Nearest);
-        let texture =
-            Texture::from_image(&mut texture_context, initial_frame_image, &texture_settings)
-                .expect("Could not create a texture");
-        return Self {
-            texture_context,
-            texture,
-        };
+/// How often the frame rate measurement is updated.
+const FPS_MEASUREMENT_PERIOD: Duration = Duration::from_secs(1);
+
+impl FrameCounter {
+    fn new() -> Self {
+        Self {
+            frames: 0,
+            measured_frames: 0,
+            measured_emulation_time: Duration::ZERO,
+            measurement_start: Instant::now(),
+            fps: 0.0,
+            emulation_time: Duration::ZERO,
+        }
+    }
+
+    /// Records a single frame that took a given time to emulate.
+    fn count(&mut self, emulation_time: Duration) {
+        self.frames += 1;
+        self.measured_frames += 1;
+        self.measured_emulation_time += emulation_time;
+        let elapsed = self.measurement_start.elapsed();
+        if elapsed >= FPS_MEASUREMENT_PERIOD {
+            self.fps = self.measured_frames as f64 / elapsed.as_secs_f64();
+            self.emulation_time = self.measured_emulation_time / self.measured_frames;
+            self.measured_frames = 0;
+            self.measured_emulation_time = Duration::ZERO;
+            self.measurement_start = Instant::now();
+        }
     }
 
-    fn draw(
-        &mut self,
-        frame_image: &RgbaImage,
-        ctx: piston_window::Context,
-        g: &mut G2d,
-        device: &mut GfxDevice,
-    ) {
-        let texture_context = &mut self.texture_context;
-        let texture = &mut self.texture;
-        let frame_image = frame_image;
-        texture
-            .update(texture_context, frame_image)
-            .expect("Unable to update texture");
-        graphics::clear([0.0, 0.0, 0.0, 1.0], g);
-        let view_size = ctx.get_view_size();
-        graphics::Image::new()
-            .rect([0.0, 0.0, view_size[0], view_size[1]])
-            .draw(texture, &ctx.draw_state, ctx.transform, g);
-        texture_context.encoder.flush(device);
+    fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    fn emulation_time(&self) -> Duration {
+        self.emulation_time
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::audio::create_consumer_and_source;
+    use crate::audio::StereoSample;
+    use crate::audio::OUTPUT_SAMPLE_RATE;
+    use crate::backend::null::NullBackend;
     use crate::debugger::adapter::FakeDebugAdapter;
+    use crate::debugger::dap_types::ErrorResponseEnvelope;
+    use crate::debugger::dap_types::Event as DapEvent;
+    use crate::debugger::dap_types::FunctionBreakpoint;
+    use crate::debugger::dap_types::LaunchArguments;
+    use crate::debugger::dap_types::Message;
+    use crate::debugger::dap_types::MessageEnvelope;
     use crate::debugger::dap_types::Request;
+    use crate::debugger::dap_types::Response;
+    use crate::debugger::dap_types::ResponseEnvelope;
+    use crate::debugger::dap_types::SetFunctionBreakpointsArguments;
+    use crate::debugger::dap_types::StoppedEvent;
+    use crate::debugger::dap_types::ThreadArguments;
     use image::Pixel;
     use image::Rgba;
-    use std::fmt;
+    use piston::ButtonArgs;
+    use piston::ButtonState;
+    use piston::Input;
+    use piston::RenderArgs;
+    use piston::UpdateArgs;
+    use std::assert_matches::assert_matches;
+    use ya6502::cpu::BusCycle;
+    use ya6502::cpu::Register;
+    use ya6502::memory::WriteResult;
 
     /// A very simple machine. All it does is producing three gray pixels with
     /// increasing luminosity.
@@ -241,6 +1270,7 @@ mod tests {
         color: Rgba<u8>,
         image: RgbaImage,
         broken: bool,
+        program: Option<PathBuf>,
     }
 
     impl TestMachine {
@@ -250,19 +1280,11 @@ mod tests {
                 color: Rgba::from_channels(1, 1, 1, 255),
                 image: RgbaImage::new(3, 1),
                 broken: false,
+                program: None,
             }
         }
     }
 
-    #[derive(Debug)]
-    struct SomeError {}
-    impl fmt::Display for SomeError {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            write!(f, "SomeError")
-        }
-    }
-    impl Error for SomeError {}
-
     impl Machine for TestMachine {
         fn reset(&mut self) {
             self.x = 0;
@@ -271,7 +1293,10 @@ mod tests {
         }
         fn tick(&mut self) -> MachineTickResult {
             if self.broken {
-                return Err(Box::new(SomeError {}));
+                return Err(CpuError::Halted {
+                    opcode: 0x02,
+                    address: 0x1234,
+                });
             }
             self.image.put_pixel(self.x, 0, self.color);
             if self.x >= 2 {
@@ -289,6 +1314,16 @@ mod tests {
         fn display_state(&self) -> String {
             format!("x={}", self.x)
         }
+        fn raster_position(&self) -> RasterPosition {
+            RasterPosition { line: 0, x: self.x }
+        }
+        fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+            if path == Path::new("missing.bin") {
+                return Err("File not found".into());
+            }
+            self.program = Some(path.to_path_buf());
+            Ok(())
+        }
     }
 
     impl MachineInspector for TestMachine {
@@ -316,6 +1351,58 @@ mod tests {
         fn inspect_memory(&self, _: u16) -> u8 {
             0
         }
+        fn last_bus_cycle(&self) -> Option<BusCycle> {
+            None
+        }
+        fn cycles(&self) -> u64 {
+            0
+        }
+        fn set_register(&mut self, _: Register, _: u16) {}
+        fn poke_memory(&mut self, _: u16, _: u8) -> WriteResult {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn gdb_protocol_requires_tcp() {
+        let parse = |args: &[&str]| {
+            CommonCliArguments::try_parse_from([&["steampunk", "--debugger"], args].concat())
+        };
+        assert!(parse(&["--debugger-stdio"]).is_ok());
+        assert!(parse(&["--debugger-protocol", "gdb"]).is_ok());
+        assert!(parse(&["--debugger-stdio", "--debugger-protocol", "gdb"]).is_err());
+    }
+
+    #[test]
+    fn speed_has_to_be_positive() {
+        let parse =
+            |speed: &str| CommonCliArguments::try_parse_from(["steampunk", "--speed", speed]);
+        assert_eq!(parse("2.5").unwrap().speed, 2.5);
+        assert!(parse("0").is_err());
+        assert!(parse("-1").is_err());
+        assert!(parse("inf").is_err());
+        assert!(parse("fast").is_err());
+    }
+
+    #[test]
+    fn ram_stripe_size_has_to_be_positive() {
+        let parse = |size: &str| {
+            CommonCliArguments::try_parse_from([
+                "steampunk",
+                "--ram-pattern",
+                "stripes",
+                "--ram-stripe-size",
+                size,
+            ])
+        };
+        assert_eq!(
+            parse("32").unwrap().ram_pattern(),
+            RamPattern::Stripes {
+                block_size: NonZeroUsize::new(32).unwrap()
+            }
+        );
+        assert!(parse("0").is_err());
+        assert!(parse("-1").is_err());
     }
 
     #[test]
@@ -338,6 +1425,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn machine_runs_frames() {
+        let mut machine = TestMachine::new();
+        assert_eq!(
+            machine.run_frame().unwrap().clone().into_raw(),
+            RgbaImage::from_pixel(3, 1, Rgba::from_channels(1, 1, 1, 255)).into_raw(),
+        );
+
+        let mut colors = vec![];
+        machine
+            .run_frames(2, |m| colors.push(m.frame_image().get_pixel(0, 0)[0]))
+            .unwrap();
+        assert_eq!(colors, [2, 3]);
+
+        machine.broken = true;
+        assert!(machine.run_frame().is_err());
+        assert!(machine
+            .run_frames(1, |_| panic!("Unexpected frame"))
+            .is_err());
+    }
+
+    #[test]
+    fn machine_controller_reports_status() {
+        let mut machine = TestMachine::new();
+        let mut controller =
+            MachineController::new(&mut machine, None::<Debugger<FakeDebugAdapter>>);
+        controller.reset();
+        controller.tick().unwrap();
+        assert_eq!(
+            controller.machine_status(),
+            "PC:0000 A:00 X:00 Y:00 SP:00\nLINE:0 X:1"
+        );
+    }
+
     #[test]
     fn machine_controller_resets() {
         let mut machine = TestMachine::new();
@@ -392,6 +1513,7 @@ mod tests {
             controller.frame_image().clone().into_raw(),
             RgbaImage::from_pixel(3, 1, Rgba::from_channels(2, 2, 2, 255)).into_raw(),
         );
+        assert_eq!(controller.frame_count(), 2);
 
         controller.reset();
         controller.run_until_end_of_frame();
@@ -415,12 +1537,78 @@ mod tests {
             RgbaImage::from_pixel(3, 1, Rgba::from_channels(0, 0, 0, 0)).into_raw(),
         );
 
-        debug_adapter.push_request(Request::Continue {});
+        debug_adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+        controller.run_until_end_of_frame();
+        assert_eq!(
+            controller.frame_image().clone().into_raw(),
+            RgbaImage::from_pixel(3, 1, Rgba::from_channels(1, 1, 1, 255)).into_raw(),
+        );
+    }
+
+    #[test]
+    fn machine_controller_pauses_on_error_with_debugger() {
+        let debug_adapter = FakeDebugAdapter::default();
+        let mut machine = TestMachine::new();
+        let mut controller =
+            MachineController::new(&mut machine, Some(Debugger::new(debug_adapter.clone())));
+        controller.reset();
+        debug_adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+        controller.run_until_end_of_frame();
+        purge_messages(&debug_adapter);
+
+        controller.machine.broken = true;
+        controller.run_until_end_of_frame();
+        assert_matches!(
+            debug_adapter.pop_outgoing(),
+            Some(MessageEnvelope {
+                message: Message::Event(DapEvent::Stopped(StoppedEvent {
+                    text: Some(text),
+                    ..
+                })),
+                ..
+            }) if text.starts_with("CPU halted")
+        );
+        assert_eq!(controller.frame_count(), 1);
+
+        // Unlike without a debugger, the machine can be resumed.
+        controller.machine.broken = false;
+        debug_adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+        controller.run_until_end_of_frame();
+        assert_eq!(controller.frame_count(), 2);
+    }
+
+    #[test]
+    fn machine_controller_is_resumed_by_debugger() {
+        let debug_adapter = FakeDebugAdapter::default();
+        let mut machine = TestMachine::new();
+        let mut controller =
+            MachineController::new(&mut machine, Some(Debugger::new(debug_adapter.clone())));
+        controller.reset();
+        debug_adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+        controller.run_until_end_of_frame();
+
+        controller.set_paused(true);
         controller.run_until_end_of_frame();
         assert_eq!(
             controller.frame_image().clone().into_raw(),
             RgbaImage::from_pixel(3, 1, Rgba::from_channels(1, 1, 1, 255)).into_raw(),
         );
+
+        // Continuing while the machine isn't stopped by the debugger doesn't
+        // lift the pause.
+        debug_adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+        controller.run_until_end_of_frame();
+        assert!(controller.paused());
+
+        debug_adapter.push_request(Request::Pause(ThreadArguments { thread_id: 1 }));
+        controller.run_until_end_of_frame();
+        debug_adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+        controller.run_until_end_of_frame();
+        assert!(!controller.paused());
+        assert_eq!(
+            controller.frame_image().clone().into_raw(),
+            RgbaImage::from_pixel(3, 1, Rgba::from_channels(2, 2, 2, 255)).into_raw(),
+        );
     }
 
     #[test]
@@ -431,7 +1619,7 @@ mod tests {
             MachineController::new(&mut machine, Some(Debugger::new(debug_adapter.clone())));
         controller.reset();
 
-        debug_adapter.push_request(Request::StepIn {});
+        debug_adapter.push_request(Request::StepIn(ThreadArguments { thread_id: 1 }));
         controller.run_until_end_of_frame();
         // We should have stopped after the first instruction, after filling
         // only one pixel.
@@ -440,4 +1628,395 @@ mod tests {
             vec![1, 1, 1, 255, 0, 0, 0, 0, 0, 0, 0, 0],
         );
     }
+
+    #[test]
+    fn debugger_frame_breakpoint() {
+        let debug_adapter = FakeDebugAdapter::default();
+        let mut machine = TestMachine::new();
+        let mut controller =
+            MachineController::new(&mut machine, Some(Debugger::new(debug_adapter.clone())));
+        controller.reset();
+
+        debug_adapter.push_request(Request::SetFunctionBreakpoints(
+            SetFunctionBreakpointsArguments {
+                breakpoints: vec![FunctionBreakpoint {
+                    name: "frame".to_string(),
+                }],
+            },
+        ));
+        debug_adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+        controller.run_until_end_of_frame();
+        controller.run_until_end_of_frame();
+        // The machine should have stopped right after finishing the first
+        // frame.
+        assert_eq!(
+            controller.frame_image().clone().into_raw(),
+            RgbaImage::from_pixel(3, 1, Rgba::from_channels(1, 1, 1, 255)).into_raw(),
+        );
+    }
+
+    #[test]
+    fn debugger_launches_program() {
+        let debug_adapter = FakeDebugAdapter::default();
+        let mut machine = TestMachine::new();
+        let mut controller =
+            MachineController::new(&mut machine, Some(Debugger::new(debug_adapter.clone())));
+        controller.reset();
+        debug_adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+        controller.run_until_end_of_frame();
+        controller.run_until_end_of_frame();
+        purge_messages(&debug_adapter);
+
+        debug_adapter.push_request(Request::Launch(LaunchArguments {
+            program: Some("game.bin".to_string()),
+            stop_on_entry: None,
+        }));
+        controller.run_until_end_of_frame();
+        assert_eq!(controller.machine.program, Some(PathBuf::from("game.bin")));
+        // The machine should have been reset before running the frame.
+        assert_eq!(
+            controller.frame_image().clone().into_raw(),
+            RgbaImage::from_pixel(3, 1, Rgba::from_channels(1, 1, 1, 255)).into_raw(),
+        );
+        assert_matches!(
+            debug_adapter.pop_outgoing(),
+            Some(MessageEnvelope {
+                message: Message::Response(ResponseEnvelope {
+                    success: true,
+                    response: Response::Launch,
+                    ..
+                }),
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn debugger_reports_failed_launch() {
+        let debug_adapter = FakeDebugAdapter::default();
+        let mut machine = TestMachine::new();
+        let mut controller =
+            MachineController::new(&mut machine, Some(Debugger::new(debug_adapter.clone())));
+        controller.reset();
+        debug_adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+        controller.run_until_end_of_frame();
+        controller.run_until_end_of_frame();
+        purge_messages(&debug_adapter);
+
+        debug_adapter.push_request(Request::Launch(LaunchArguments {
+            program: Some("missing.bin".to_string()),
+            stop_on_entry: None,
+        }));
+        controller.run_until_end_of_frame();
+        assert_eq!(controller.machine.program, None);
+        // The machine keeps running without a reset.
+        assert_eq!(
+            controller.frame_image().clone().into_raw(),
+            RgbaImage::from_pixel(3, 1, Rgba::from_channels(3, 3, 3, 255)).into_raw(),
+        );
+        assert_matches!(
+            debug_adapter.pop_outgoing(),
+            Some(MessageEnvelope {
+                message: Message::ErrorResponse(ErrorResponseEnvelope { message, .. }),
+                ..
+            }) if message == "Unable to load missing.bin: File not found"
+        );
+    }
+
+    fn purge_messages(adapter: &FakeDebugAdapter) {
+        while adapter.pop_outgoing().is_some() {}
+    }
+
+    /// Counts frames and remembers all input events and loaded programs.
+    struct FakeController {
+        image: RgbaImage,
+        frames: u32,
+        inputs: Vec<Input>,
+        paused: bool,
+        programs: Vec<PathBuf>,
+    }
+
+    impl FakeController {
+        fn new() -> Self {
+            Self {
+                image: RgbaImage::from_pixel(3, 2, Rgba([0, 0, 0, 255])),
+                frames: 0,
+                inputs: vec![],
+                paused: false,
+                programs: vec![],
+            }
+        }
+    }
+
+    impl AppController for FakeController {
+        fn frame_image(&self) -> &RgbaImage {
+            &self.image
+        }
+        fn reset(&mut self) {
+            self.frames = 0;
+        }
+        fn interrupted(&self) -> Arc<AtomicBool> {
+            Arc::new(AtomicBool::new(false))
+        }
+        fn paused(&self) -> bool {
+            self.paused
+        }
+        fn set_paused(&mut self, paused: bool) {
+            self.paused = paused;
+        }
+        fn frame_count(&self) -> u64 {
+            self.frames as u64
+        }
+        fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+            if path.extension().map_or(true, |e| e != "bin") {
+                return Err("Unknown file format".into());
+            }
+            self.programs.push(path.to_path_buf());
+            self.frames = 0;
+            return Ok(());
+        }
+        fn event(&mut self, event: &Event) {
+            match event {
+                Event::Input(input, _) => self.inputs.push(input.clone()),
+                Event::Loop(Loop::Update(_)) if !self.paused => self.frames += 1,
+                _ => {}
+            }
+        }
+        fn display_machine_state(&self) -> String {
+            String::new()
+        }
+        fn machine_status(&self) -> String {
+            String::new()
+        }
+    }
+
+    fn update() -> Event {
+        Event::from(UpdateArgs { dt: 1.0 / 60.0 })
+    }
+
+    fn render() -> Event {
+        Event::from(RenderArgs {
+            ext_dt: 0.0,
+            window_size: [30.0, 20.0],
+            draw_size: [30, 20],
+        })
+    }
+
+    fn key_press(key: Key) -> Event {
+        Event::from(ButtonArgs {
+            state: ButtonState::Press,
+            button: Button::Keyboard(key),
+            scancode: None,
+        })
+    }
+
+    #[test]
+    fn application_emulates_frames_at_given_speed() {
+        let backend = NullBackend::new([30.0, 20.0], [update(), update(), update()]);
+        let mut app = Application::with_backend(FakeController::new(), backend);
+        app.set_frame_rate(50);
+        app.set_speed(1.5);
+        app.run();
+        assert_eq!(app.backend.ups(), 50);
+        assert_eq!(app.controller.frames, 4);
+    }
+
+    #[test]
+    fn application_polls_backend_when_synced_to_timer() {
+        let backend = NullBackend::new([30.0, 20.0], [update()]);
+        let mut app = Application::with_backend(FakeController::new(), backend);
+        app.set_frame_rate(50);
+        app.set_sync_mode(SyncMode::Timer);
+        app.run();
+        assert_eq!(app.backend.ups(), 240);
+        // The first update always emulates a frame.
+        assert_eq!(app.controller.frames, 1);
+    }
+
+    #[test]
+    fn application_plays_audio_using_backend() {
+        let backend = NullBackend::new([30.0, 20.0], []);
+        let mut app = Application::with_backend(FakeController::new(), backend);
+        let (mut consumer, source) = create_consumer_and_source(OUTPUT_SAMPLE_RATE, Duration::ZERO);
+        app.play_audio(source);
+        consumer.consume(0.5);
+        consumer.consume(0.5);
+        let source = app.backend.audio_source().expect("Audio is not played");
+        // The first sample is interpolated from silence.
+        source.next_sample();
+        assert_eq!(source.next_sample(), StereoSample::mono(0.5));
+    }
+
+    #[test]
+    fn application_presents_frames() {
+        let backend = NullBackend::new(
+            [30.0, 20.0],
+            [
+                update(),
+                render(),
+                key_press(OVERLAY_KEY),
+                update(),
+                render(),
+            ],
+        );
+        let mut app = Application::with_backend(FakeController::new(), backend);
+        app.set_crt_effects(CrtEffects {
+            scanlines: true,
+            ..Default::default()
+        });
+        app.run();
+
+        let presented_frames = app.backend.presented_frames();
+        assert_eq!(presented_frames.len(), 2);
+        let (frame, osd) = &presented_frames[0];
+        assert_eq!(frame, &app.controller.image);
+        assert_eq!(
+            osd,
+            &Osd {
+                recording: false,
+                flash: false,
+                scanlines: true,
+            }
+        );
+        // The second frame should contain the overlay.
+        let (frame, _) = &presented_frames[1];
+        assert_ne!(frame, &app.controller.image);
+    }
+
+    fn key_release(key: Key) -> Event {
+        Event::from(ButtonArgs {
+            state: ButtonState::Release,
+            button: Button::Keyboard(key),
+            scancode: None,
+        })
+    }
+
+    #[test]
+    fn application_pauses_and_advances_frames() {
+        let backend = NullBackend::new(
+            [30.0, 20.0],
+            [
+                update(),
+                key_press(Key::LCtrl),
+                key_press(PAUSE_KEY),
+                key_release(PAUSE_KEY),
+                update(),
+                update(),
+                key_press(FRAME_ADVANCE_KEY),
+                key_release(FRAME_ADVANCE_KEY),
+                update(),
+                update(),
+                key_press(PAUSE_KEY),
+                key_release(Key::LCtrl),
+                update(),
+                key_press(PAUSE_KEY),
+            ],
+        );
+        let mut app = Application::with_backend(FakeController::new(), backend);
+        app.run();
+        assert_eq!(app.controller.frames, 3);
+        assert!(!app.controller.paused);
+        // Only the last key press should reach the machine, since it wasn't
+        // combined with Ctrl.
+        assert_eq!(
+            app.controller.inputs,
+            vec![
+                Input::from(ButtonArgs {
+                    state: ButtonState::Press,
+                    button: Button::Keyboard(Key::LCtrl),
+                    scancode: None,
+                }),
+                Input::from(ButtonArgs {
+                    state: ButtonState::Release,
+                    button: Button::Keyboard(Key::LCtrl),
+                    scancode: None,
+                }),
+                Input::from(ButtonArgs {
+                    state: ButtonState::Press,
+                    button: Button::Keyboard(PAUSE_KEY),
+                    scancode: None,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn application_mutes_audio_channels() {
+        let backend = NullBackend::new(
+            [30.0, 20.0],
+            [
+                key_press(Key::LCtrl),
+                key_press(Key::D1),
+                key_release(Key::D1),
+                // There's no channel 2, so the machine gets this key.
+                key_press(Key::D2),
+                key_release(Key::LCtrl),
+            ],
+        );
+        let mixer = Mixer::new(2);
+        let mut app = Application::with_backend(FakeController::new(), backend);
+        app.set_mixer(mixer.clone());
+        app.run();
+        assert!(!mixer.muted(0));
+        assert!(mixer.muted(1));
+        assert_eq!(
+            app.controller.inputs,
+            vec![
+                Input::from(ButtonArgs {
+                    state: ButtonState::Press,
+                    button: Button::Keyboard(Key::LCtrl),
+                    scancode: None,
+                }),
+                Input::from(ButtonArgs {
+                    state: ButtonState::Press,
+                    button: Button::Keyboard(Key::D2),
+                    scancode: None,
+                }),
+                Input::from(ButtonArgs {
+                    state: ButtonState::Release,
+                    button: Button::Keyboard(Key::LCtrl),
+                    scancode: None,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn application_translates_mouse_coordinates() {
+        let backend = NullBackend::new(
+            [30.0, 20.0],
+            [Event::from(Motion::MouseCursor([15.0, 5.0]))],
+        );
+        let mut app = Application::with_backend(FakeController::new(), backend);
+        app.run();
+        assert_eq!(
+            app.controller.inputs,
+            vec![Input::Move(Motion::MouseCursor([1.5, 0.5]))]
+        );
+    }
+
+    fn file_drop(path: &str) -> Event {
+        Event::Input(Input::FileDrag(FileDrag::Drop(PathBuf::from(path))), None)
+    }
+
+    #[test]
+    fn application_loads_dropped_files() {
+        let backend = NullBackend::new(
+            [30.0, 20.0],
+            [
+                update(),
+                update(),
+                file_drop("game.bin"),
+                update(),
+                file_drop("notes.txt"),
+                update(),
+            ],
+        );
+        let mut app = Application::with_backend(FakeController::new(), backend);
+        app.run();
+        assert_eq!(app.controller.programs, [PathBuf::from("game.bin")]);
+        // The drops shouldn't reach the machine as input events.
+        assert_eq!(app.controller.inputs, []);
+        assert_eq!(app.controller.frames, 2);
+    }
 }
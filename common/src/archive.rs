@@ -0,0 +1,200 @@
+//! Reading ROM and tape images that may be compressed. Images are often
+//! distributed as ZIP archives or gzipped files; instead of requiring users to
+//! extract them manually, the emulators can load them directly.
+
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::path::Path;
+use zip::ZipArchive;
+
+/// An image file, possibly extracted from an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageFile {
+    /// Name of the image. For compressed images, it's the name of the file
+    /// after decompression, so that it can be used to recognize the image
+    /// format.
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+impl ImageFile {
+    /// Returns `true` if the image name has a given extension, ignoring case.
+    pub fn has_extension(&self, extension: &str) -> bool {
+        has_extension(&self.name, extension)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ArchiveError {
+    #[error("I/O error: {0}")]
+    IoError(#[from] io::Error),
+
+    #[error("ZIP error: {0}")]
+    ZipError(#[from] zip::result::ZipError),
+
+    #[error("No image file found in the archive (expected one of: {})", .0.join(", "))]
+    NoImage(Vec<String>),
+
+    #[error(
+        "The archive contains multiple image files ({}); extract the right one manually",
+        .0.join(", ")
+    )]
+    MultipleImages(Vec<String>),
+}
+
+/// Reads an image file. Files with a `.zip` extension are treated as ZIP
+/// archives that should contain a single image with one of given extensions
+/// (or a single file). Files with a `.gz` extension are decompressed. Other
+/// files are read as they are.
+pub fn read_image_file(path: &Path, extensions: &[&str]) -> Result<ImageFile, ArchiveError> {
+    let name = path
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+    if has_extension(&name, "zip") {
+        return read_zip(File::open(path)?, extensions);
+    }
+    if has_extension(&name, "gz") {
+        let name = name[..name.len() - ".gz".len()].to_string();
+        return read_gzip(File::open(path)?, name);
+    }
+    return Ok(ImageFile {
+        name,
+        bytes: std::fs::read(path)?,
+    });
+}
+
+/// Extracts the image from a ZIP archive. Files with unknown extensions, like
+/// text files with instructions, are ignored, unless there's only one file in
+/// the archive.
+pub fn read_zip(reader: impl Read + Seek, extensions: &[&str]) -> Result<ImageFile, ArchiveError> {
+    let mut archive = ZipArchive::new(reader)?;
+    let mut file_names: Vec<String> = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .map(str::to_string)
+        .collect();
+    // The archive doesn't preserve the order of files.
+    file_names.sort();
+    let mut images: Vec<String> = file_names
+        .iter()
+        .filter(|name| {
+            extensions
+                .iter()
+                .any(|extension| has_extension(name, extension))
+        })
+        .cloned()
+        .collect();
+    if images.is_empty() && file_names.len() == 1 {
+        images = file_names;
+    }
+    let name = match &images[..] {
+        [] => {
+            return Err(ArchiveError::NoImage(
+                extensions.iter().map(|e| e.to_string()).collect(),
+            ))
+        }
+        [name] => name.clone(),
+        _ => return Err(ArchiveError::MultipleImages(images)),
+    };
+    let mut bytes = vec![];
+    archive.by_name(&name)?.read_to_end(&mut bytes)?;
+    return Ok(ImageFile { name, bytes });
+}
+
+/// Decompresses a gzipped image with a given name.
+pub fn read_gzip(reader: impl Read, name: String) -> Result<ImageFile, ArchiveError> {
+    let mut bytes = vec![];
+    GzDecoder::new(reader).read_to_end(&mut bytes)?;
+    return Ok(ImageFile { name, bytes });
+}
+
+fn has_extension(name: &str, extension: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .map_or(false, |e| e.eq_ignore_ascii_case(extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::assert_matches::assert_matches;
+    use std::io::Cursor;
+    use std::io::Write;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    /// Creates a ZIP archive with given files.
+    fn zip(files: &[(&str, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        for (name, contents) in files {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        let mut archive = writer.finish().unwrap();
+        archive.set_position(0);
+        return archive;
+    }
+
+    fn image(name: &str, bytes: &[u8]) -> ImageFile {
+        ImageFile {
+            name: name.to_string(),
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    #[test]
+    fn reads_zip_archives() {
+        let archive = zip(&[("README.txt", b"Have fun!"), ("Game.BIN", &[1, 2, 3])]);
+        assert_eq!(
+            read_zip(archive, &["a26", "bin"]).unwrap(),
+            image("Game.BIN", &[1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn reads_single_file_with_unknown_extension() {
+        let archive = zip(&[("game", &[1, 2, 3])]);
+        assert_eq!(
+            read_zip(archive, &["tap"]).unwrap(),
+            image("game", &[1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn reports_missing_images() {
+        let archive = zip(&[("README.txt", b"Have fun!"), ("cover.jpg", &[1, 2, 3])]);
+        assert_matches!(
+            read_zip(archive, &["tap"]),
+            Err(ArchiveError::NoImage(extensions)) if extensions == ["tap"]
+        );
+    }
+
+    #[test]
+    fn reports_multiple_images() {
+        let archive = zip(&[
+            ("side1.tap", &[1]),
+            ("side2.tap", &[2]),
+            ("README.txt", b"Have fun!"),
+        ]);
+        assert_matches!(
+            read_zip(archive, &["tap"]),
+            Err(ArchiveError::MultipleImages(names)) if names == ["side1.tap", "side2.tap"]
+        );
+    }
+
+    #[test]
+    fn reads_gzipped_files() {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&[1, 2, 3]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(
+            read_gzip(&compressed[..], "game.tap".to_string()).unwrap(),
+            image("game.tap", &[1, 2, 3])
+        );
+    }
+}
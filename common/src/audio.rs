@@ -0,0 +1,536 @@
+//! An audio output module shared by the emulated machines. Samples generated
+//! by a machine are resampled to a fixed output sample rate (since Rodio
+//! doesn't have a good resampling algorithm) and passed to the audio thread
+//! through a ring buffer. Neither side ever blocks: if the emulator runs too
+//! fast, excess samples are dropped, and if it runs too slow, the last sample
+//! is repeated until the buffer fills up again. The output is stereo; a
+//! [`Mixer`] can be used to pan, mute, and adjust volume of the channels
+//! generated by a machine.
+
+#[cfg(feature = "desktop")]
+use rodio::OutputStream;
+#[cfg(feature = "desktop")]
+use rodio::Sink;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Sample rate of the audio that is sent to the output device.
+pub const OUTPUT_SAMPLE_RATE: u32 = 44_100;
+
+/// A single sample of the stereo output.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StereoSample {
+    pub left: f32,
+    pub right: f32,
+}
+
+impl StereoSample {
+    /// Creates a sample that sounds the same on both channels.
+    pub fn mono(sample: f32) -> Self {
+        Self {
+            left: sample,
+            right: sample,
+        }
+    }
+}
+
+type SharedBuffer = Arc<Mutex<RingBuffer>>;
+
+struct RingBuffer {
+    samples: VecDeque<StereoSample>,
+    /// Number of buffered samples that corresponds to the requested latency.
+    latency: usize,
+    /// Set after the buffer runs out of samples. Playback resumes once the
+    /// buffer gets filled up to the requested latency.
+    refilling: bool,
+}
+
+impl RingBuffer {
+    fn push(&mut self, sample: StereoSample) {
+        if self.samples.len() < 2 * self.latency {
+            self.samples.push_back(sample);
+        }
+    }
+
+    fn pop(&mut self) -> Option<StereoSample> {
+        if self.refilling {
+            if self.samples.len() < self.latency {
+                return None;
+            }
+            self.refilling = false;
+        }
+        let sample = self.samples.pop_front();
+        self.refilling = sample.is_none();
+        return sample;
+    }
+}
+
+/// Receives samples from an emulated machine and resamples them to
+/// [`OUTPUT_SAMPLE_RATE`] using linear interpolation.
+pub struct AudioConsumer {
+    buffer: SharedBuffer,
+    /// Ratio of the input sample rate to the output sample rate.
+    step: f64,
+    /// Position of the next output sample between the previous and the
+    /// current input sample.
+    phase: f64,
+    previous_sample: StereoSample,
+}
+
+impl AudioConsumer {
+    /// Consumes a mono sample, which is played on both channels.
+    pub fn consume(&mut self, sample: f32) {
+        self.consume_stereo(StereoSample::mono(sample));
+    }
+
+    pub fn consume_stereo(&mut self, sample: StereoSample) {
+        let mut buffer = self.buffer.lock().unwrap();
+        let previous = self.previous_sample;
+        let interpolate = |from: f32, to: f32, phase: f64| from + (to - from) * phase as f32;
+        while self.phase < 1.0 {
+            buffer.push(StereoSample {
+                left: interpolate(previous.left, sample.left, self.phase),
+                right: interpolate(previous.right, sample.right, self.phase),
+            });
+            self.phase += self.step;
+        }
+        self.phase -= 1.0;
+        self.previous_sample = sample;
+    }
+
+    /// Returns a monitor of the buffer that this consumer writes to.
+    pub fn monitor(&self) -> AudioBufferMonitor {
+        AudioBufferMonitor {
+            buffer: self.buffer.clone(),
+        }
+    }
+}
+
+/// Observes how many samples are waiting to be played. This allows driving a
+/// machine by the audio clock instead of the video frames: the machine only
+/// needs to generate new samples when the buffer drops below the requested
+/// latency.
+#[derive(Clone)]
+pub struct AudioBufferMonitor {
+    buffer: SharedBuffer,
+}
+
+impl AudioBufferMonitor {
+    pub fn needs_samples(&self) -> bool {
+        let buffer = self.buffer.lock().unwrap();
+        return buffer.samples.len() < buffer.latency;
+    }
+}
+
+/// Plays samples resampled by an [`AudioConsumer`]. As an iterator, it
+/// returns interleaved left and right channel samples.
+pub struct AudioSource {
+    buffer: SharedBuffer,
+    last_sample: StereoSample,
+    /// Set after returning the left channel of a sample.
+    pending_right: Option<f32>,
+}
+
+impl AudioSource {
+    /// Returns the next sample, or repeats the last one if the buffer is not
+    /// ready.
+    pub fn next_sample(&mut self) -> StereoSample {
+        if let Some(sample) = self.buffer.lock().unwrap().pop() {
+            self.last_sample = sample;
+        }
+        return self.last_sample;
+    }
+}
+
+#[cfg(feature = "desktop")]
+impl rodio::Source for AudioSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+    fn channels(&self) -> u16 {
+        2
+    }
+    fn sample_rate(&self) -> u32 {
+        OUTPUT_SAMPLE_RATE
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Iterator for AudioSource {
+    type Item = f32;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+        let sample = self.next_sample();
+        self.pending_right = Some(sample.right);
+        return Some(sample.left);
+    }
+}
+
+/// Creates a connected consumer and source. The consumer accepts samples at a
+/// given sample rate. The source plays them with a given latency.
+pub fn create_consumer_and_source(
+    input_sample_rate: u32,
+    latency: Duration,
+) -> (AudioConsumer, AudioSource) {
+    let latency_samples = (OUTPUT_SAMPLE_RATE as f64 * latency.as_secs_f64()).round() as usize;
+    return create_consumer_and_source_with_step(
+        input_sample_rate as f64 / OUTPUT_SAMPLE_RATE as f64,
+        latency_samples.max(1),
+    );
+}
+
+fn create_consumer_and_source_with_step(
+    step: f64,
+    latency_samples: usize,
+) -> (AudioConsumer, AudioSource) {
+    let buffer = Arc::new(Mutex::new(RingBuffer {
+        samples: VecDeque::with_capacity(2 * latency_samples),
+        latency: latency_samples,
+        refilling: true,
+    }));
+    return (
+        AudioConsumer {
+            buffer: buffer.clone(),
+            step,
+            phase: 0.0,
+            previous_sample: StereoSample::default(),
+        },
+        AudioSource {
+            buffer,
+            last_sample: StereoSample::default(),
+            pending_right: None,
+        },
+    );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ChannelSettings {
+    pan: f32,
+    muted: bool,
+}
+
+#[derive(Clone)]
+struct MixerSettings {
+    channels: Vec<ChannelSettings>,
+    master_volume: f32,
+}
+
+/// Mixes audio channels of a machine (e.g. the two TIA channels) into a stereo
+/// output. Each channel can be panned and muted independently. Clones of a
+/// mixer share the settings, so that they can be changed at runtime, while
+/// the machine keeps mixing its samples.
+#[derive(Clone)]
+pub struct Mixer {
+    settings: Arc<Mutex<MixerSettings>>,
+    /// Incremented each time the shared settings change.
+    generation: Arc<AtomicU32>,
+    /// A copy of the shared settings used for mixing, so that the mutex
+    /// doesn't need to be locked for every sample.
+    snapshot: MixerSettings,
+    snapshot_generation: u32,
+}
+
+impl Mixer {
+    /// Creates a mixer with a given number of channels, all of them centered
+    /// and at full volume.
+    pub fn new(n_channels: usize) -> Self {
+        let settings = MixerSettings {
+            channels: vec![
+                ChannelSettings {
+                    pan: 0.0,
+                    muted: false
+                };
+                n_channels
+            ],
+            master_volume: 1.0,
+        };
+        Self {
+            settings: Arc::new(Mutex::new(settings.clone())),
+            generation: Arc::new(AtomicU32::new(0)),
+            snapshot: settings,
+            snapshot_generation: 0,
+        }
+    }
+
+    pub fn n_channels(&self) -> usize {
+        self.snapshot.channels.len()
+    }
+
+    /// Sets the volume of the output, from 0 to 1.
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.update_settings(|settings| settings.master_volume = volume.clamp(0.0, 1.0));
+    }
+
+    /// Places a given channel in the stereo field, from -1 (left) to 1
+    /// (right). Channels that don't exist are ignored.
+    pub fn set_pan(&mut self, channel: usize, pan: f32) {
+        self.update_settings(|settings| {
+            if let Some(settings) = settings.channels.get_mut(channel) {
+                settings.pan = pan.clamp(-1.0, 1.0);
+            }
+        });
+    }
+
+    /// Mutes or unmutes a given channel. Channels that don't exist are
+    /// ignored.
+    pub fn set_muted(&mut self, channel: usize, muted: bool) {
+        self.update_settings(|settings| {
+            if let Some(settings) = settings.channels.get_mut(channel) {
+                settings.muted = muted;
+            }
+        });
+    }
+
+    fn update_settings(&mut self, update: impl FnOnce(&mut MixerSettings)) {
+        let mut settings = self.settings.lock().unwrap();
+        update(&mut settings);
+        self.snapshot = settings.clone();
+        self.snapshot_generation = self.generation.fetch_add(1, Ordering::AcqRel) + 1;
+    }
+
+    /// Returns `true` if a given channel exists and is muted.
+    pub fn muted(&self, channel: usize) -> bool {
+        let settings = self.settings.lock().unwrap();
+        return settings.channels.get(channel).map_or(false, |c| c.muted);
+    }
+
+    /// Mixes samples of all channels, each of them in the [-0.5, 0.5] range,
+    /// into a stereo sample in the same range. A centered channel is played at
+    /// full volume on both sides; panning it attenuates one of them.
+    pub fn mix(&mut self, samples: &[f32]) -> StereoSample {
+        let generation = self.generation.load(Ordering::Acquire);
+        if generation != self.snapshot_generation {
+            self.snapshot = self.settings.lock().unwrap().clone();
+            self.snapshot_generation = generation;
+        }
+        let settings = &self.snapshot;
+        let mut output = StereoSample::default();
+        for (sample, channel) in samples.iter().zip(&settings.channels) {
+            if channel.muted {
+                continue;
+            }
+            output.left += sample * (1.0 - channel.pan).min(1.0);
+            output.right += sample * (1.0 + channel.pan).min(1.0);
+        }
+        let scale = settings.master_volume / settings.channels.len().max(1) as f32;
+        return StereoSample {
+            left: output.left * scale,
+            right: output.right * scale,
+        };
+    }
+}
+
+/// Stereo position of a mixer channel, given in the command line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelPan {
+    pub channel: usize,
+    pub pan: f32,
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Invalid channel pan: {text} (expected <channel>=<pan>, with pan from -1 to 1)")]
+pub struct ChannelPanParseError {
+    text: String,
+}
+
+impl FromStr for ChannelPan {
+    type Err = ChannelPanParseError;
+
+    /// Parses a pan in the `channel=pan` form, e.g. `1=-0.5`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let parse = || {
+            let (channel, pan) = text.split_once('=')?;
+            let pan: f32 = pan.trim().parse().ok()?;
+            if !(-1.0..=1.0).contains(&pan) {
+                return None;
+            }
+            Some(ChannelPan {
+                channel: channel.trim().parse().ok()?,
+                pan,
+            })
+        };
+        return parse().ok_or_else(|| ChannelPanParseError {
+            text: text.to_string(),
+        });
+    }
+}
+
+/// Opens the default audio device and starts playing. The returned stream and
+/// sink need to be kept alive for as long as the audio should be played.
+#[cfg(feature = "desktop")]
+pub fn initialize(
+    input_sample_rate: u32,
+    latency: Duration,
+) -> (AudioConsumer, OutputStream, Sink) {
+    let (audio_consumer, audio_source) = create_consumer_and_source(input_sample_rate, latency);
+    let (stream, audio_sink) = play(audio_source);
+    return (audio_consumer, stream, audio_sink);
+}
+
+/// Plays samples from a given source on the default audio device. The returned
+/// stream and sink need to be kept alive for as long as the audio should be
+/// played.
+#[cfg(feature = "desktop")]
+pub fn play(source: AudioSource) -> (OutputStream, Sink) {
+    let (stream, stream_handle) = OutputStream::try_default().unwrap();
+    let audio_sink = Sink::try_new(&stream_handle).unwrap();
+    audio_sink.set_volume(0.1);
+    audio_sink.append(source);
+    return (stream, audio_sink);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays a given number of samples and returns their left channel.
+    fn play(source: &mut AudioSource, n_samples: usize) -> Vec<f32> {
+        (0..n_samples).map(|_| source.next_sample().left).collect()
+    }
+
+    fn stereo(left: f32, right: f32) -> StereoSample {
+        StereoSample { left, right }
+    }
+
+    #[test]
+    fn resamples() {
+        let (mut consumer, mut source) = create_consumer_and_source_with_step(0.5, 4);
+        for sample in [0.5, 1.0, 0.0] {
+            consumer.consume(sample);
+        }
+        assert_eq!(play(&mut source, 6), [0.0, 0.25, 0.5, 0.75, 1.0, 0.5]);
+
+        let (mut consumer, mut source) = create_consumer_and_source_with_step(2.0, 2);
+        for sample in [0.25, 1.0, 0.0, 0.5, 0.75] {
+            consumer.consume(sample);
+        }
+        assert_eq!(play(&mut source, 3), [0.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn waits_until_latency_is_reached() {
+        let (mut consumer, mut source) = create_consumer_and_source_with_step(1.0, 3);
+        consumer.consume(0.5);
+        consumer.consume(0.25);
+        assert_eq!(play(&mut source, 2), [0.0, 0.0]);
+        consumer.consume(1.0);
+        assert_eq!(play(&mut source, 4), [0.0, 0.5, 0.25, 0.25]);
+
+        // After an underrun, the last sample is repeated until the buffer is
+        // filled up again.
+        consumer.consume(0.75);
+        consumer.consume(0.5);
+        assert_eq!(play(&mut source, 2), [0.25, 0.25]);
+        consumer.consume(0.25);
+        assert_eq!(play(&mut source, 3), [1.0, 0.75, 0.5]);
+    }
+
+    #[test]
+    fn monitors_buffer_level() {
+        let (mut consumer, mut source) = create_consumer_and_source_with_step(1.0, 2);
+        let monitor = consumer.monitor();
+        assert!(monitor.needs_samples());
+        consumer.consume(0.5);
+        assert!(monitor.needs_samples());
+        consumer.consume(0.25);
+        assert!(!monitor.needs_samples());
+        play(&mut source, 1);
+        assert!(monitor.needs_samples());
+    }
+
+    #[test]
+    fn drops_samples_when_full() {
+        let (mut consumer, mut source) = create_consumer_and_source_with_step(1.0, 2);
+        for sample in [0.1, 0.2, 0.3, 0.4, 0.5] {
+            consumer.consume(sample);
+        }
+        assert_eq!(play(&mut source, 5), [0.0, 0.1, 0.2, 0.3, 0.3]);
+    }
+
+    #[test]
+    fn plays_stereo() {
+        let (mut consumer, mut source) = create_consumer_and_source_with_step(0.5, 2);
+        consumer.consume_stereo(stereo(0.5, -0.5));
+        consumer.consume(0.25);
+        assert_eq!(
+            source.take(8).collect::<Vec<f32>>(),
+            [0.0, 0.0, 0.25, -0.25, 0.5, -0.5, 0.375, -0.125]
+        );
+    }
+
+    #[test]
+    fn mixes_centered_channels() {
+        let mut mixer = Mixer::new(2);
+        assert_eq!(mixer.mix(&[0.5, 0.5]), stereo(0.5, 0.5));
+        assert_eq!(mixer.mix(&[0.5, -0.5]), stereo(0.0, 0.0));
+        assert_eq!(mixer.mix(&[-0.5, 0.25]), stereo(-0.125, -0.125));
+    }
+
+    #[test]
+    fn pans_channels() {
+        let mut mixer = Mixer::new(2);
+        mixer.set_pan(0, -1.0);
+        mixer.set_pan(1, 0.5);
+        assert_eq!(mixer.mix(&[0.5, 0.0]), stereo(0.25, 0.0));
+        assert_eq!(mixer.mix(&[0.0, 0.5]), stereo(0.125, 0.25));
+
+        // Out of range.
+        mixer.set_pan(1, 2.0);
+        mixer.set_pan(2, 1.0);
+        assert_eq!(mixer.mix(&[0.0, 0.5]), stereo(0.0, 0.25));
+    }
+
+    #[test]
+    fn mutes_channels() {
+        let mut mixer = Mixer::new(2);
+        let mut shared_mixer = mixer.clone();
+        shared_mixer.set_muted(1, true);
+        assert!(mixer.muted(1));
+        assert!(!mixer.muted(0));
+        assert!(!mixer.muted(2));
+        assert_eq!(mixer.mix(&[0.5, 0.5]), stereo(0.25, 0.25));
+
+        mixer.set_muted(1, false);
+        assert_eq!(shared_mixer.mix(&[0.5, 0.5]), stereo(0.5, 0.5));
+    }
+
+    #[test]
+    fn master_volume() {
+        let mut mixer = Mixer::new(2);
+        mixer.set_master_volume(0.5);
+        assert_eq!(mixer.mix(&[0.5, 0.5]), stereo(0.25, 0.25));
+        mixer.set_master_volume(-1.0);
+        assert_eq!(mixer.mix(&[0.5, 0.5]), stereo(0.0, 0.0));
+    }
+
+    #[test]
+    fn parses_channel_pans() {
+        assert_eq!(
+            "1=-0.5".parse(),
+            Ok(ChannelPan {
+                channel: 1,
+                pan: -0.5
+            })
+        );
+        assert_eq!(
+            "0 = 1".parse(),
+            Ok(ChannelPan {
+                channel: 0,
+                pan: 1.0
+            })
+        );
+        assert!("0".parse::<ChannelPan>().is_err());
+        assert!("a=0".parse::<ChannelPan>().is_err());
+        assert!("0=1.5".parse::<ChannelPan>().is_err());
+    }
+}
@@ -0,0 +1,145 @@
+//! Detection of frame image lines that need to be uploaded to the screen
+//! again. Most programs only change a small part of the screen from one frame
+//! to another (think of a blinking cursor at a BASIC prompt), so uploading
+//! only the changed lines saves a lot of work.
+
+use image::RgbaImage;
+use std::ops::Range;
+
+/// Remembers the frame image that has been uploaded most recently, and compares
+/// subsequent frames against it.
+pub struct DirtyLines {
+    uploaded: RgbaImage,
+}
+
+impl DirtyLines {
+    pub fn new() -> Self {
+        Self {
+            uploaded: RgbaImage::new(0, 0),
+        }
+    }
+
+    /// Compares a given frame image with the one passed previously, and
+    /// returns ranges of lines that differ between them, in ascending order.
+    /// Adjacent dirty lines are merged into a single range. If the frame size
+    /// has changed, the whole image is dirty. The given image becomes the
+    /// reference for the next comparison.
+    pub fn update(&mut self, image: &RgbaImage) -> Vec<Range<u32>> {
+        let (width, height) = image.dimensions();
+        if self.uploaded.dimensions() != image.dimensions() {
+            self.uploaded = image.clone();
+            return if width > 0 && height > 0 {
+                vec![0..height]
+            } else {
+                vec![]
+            };
+        }
+        if width == 0 {
+            return vec![];
+        }
+
+        let line_length = width as usize * 4;
+        let mut dirty_ranges: Vec<Range<u32>> = vec![];
+        let lines = self.uploaded.chunks_exact_mut(line_length);
+        for (y, (uploaded_line, line)) in lines.zip(image.chunks_exact(line_length)).enumerate() {
+            if uploaded_line == line {
+                continue;
+            }
+            uploaded_line.copy_from_slice(line);
+            let y = y as u32;
+            match dirty_ranges.last_mut() {
+                Some(range) if range.end == y => range.end = y + 1,
+                _ => dirty_ranges.push(y..y + 1),
+            }
+        }
+        return dirty_ranges;
+    }
+
+    /// Forgets the previously uploaded image, so that the next one is
+    /// considered dirty as a whole. Useful when the image needs to be uploaded
+    /// to a brand new texture.
+    pub fn invalidate(&mut self) {
+        self.uploaded = RgbaImage::new(0, 0);
+    }
+}
+
+impl Default for DirtyLines {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns raw pixel data of a given range of lines.
+pub fn line_data(image: &RgbaImage, lines: Range<u32>) -> &[u8] {
+    let line_length = image.width() as usize * 4;
+    return &image.as_raw()[lines.start as usize * line_length..lines.end as usize * line_length];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    #[test]
+    fn first_image_is_dirty() {
+        let mut dirty_lines = DirtyLines::new();
+        assert_eq!(
+            dirty_lines.update(&RgbaImage::from_pixel(3, 4, BLACK)),
+            vec![0..4]
+        );
+        assert_eq!(dirty_lines.update(&RgbaImage::new(0, 0)), vec![]);
+    }
+
+    #[test]
+    fn finds_changed_lines() {
+        let mut dirty_lines = DirtyLines::new();
+        let mut image = RgbaImage::from_pixel(3, 6, BLACK);
+        dirty_lines.update(&image);
+        assert_eq!(dirty_lines.update(&image), vec![]);
+
+        image.put_pixel(2, 1, WHITE);
+        image.put_pixel(0, 3, WHITE);
+        image.put_pixel(1, 4, WHITE);
+        assert_eq!(dirty_lines.update(&image), vec![1..2, 3..5]);
+        assert_eq!(dirty_lines.update(&image), vec![]);
+
+        image.put_pixel(0, 5, BLACK);
+        assert_eq!(dirty_lines.update(&image), vec![]);
+        image.put_pixel(0, 5, WHITE);
+        assert_eq!(dirty_lines.update(&image), vec![5..6]);
+    }
+
+    #[test]
+    fn resizing_makes_whole_image_dirty() {
+        let mut dirty_lines = DirtyLines::new();
+        dirty_lines.update(&RgbaImage::from_pixel(3, 4, BLACK));
+        assert_eq!(
+            dirty_lines.update(&RgbaImage::from_pixel(3, 5, BLACK)),
+            vec![0..5]
+        );
+        assert_eq!(
+            dirty_lines.update(&RgbaImage::from_pixel(2, 5, BLACK)),
+            vec![0..5]
+        );
+    }
+
+    #[test]
+    fn invalidates() {
+        let mut dirty_lines = DirtyLines::new();
+        let image = RgbaImage::from_pixel(3, 4, BLACK);
+        dirty_lines.update(&image);
+        dirty_lines.invalidate();
+        assert_eq!(dirty_lines.update(&image), vec![0..4]);
+    }
+
+    #[test]
+    fn returns_line_data() {
+        let mut image = RgbaImage::from_pixel(2, 3, BLACK);
+        image.put_pixel(1, 1, WHITE);
+        assert_eq!(line_data(&image, 1..2), &[0, 0, 0, 255, 255, 255, 255, 255]);
+        assert_eq!(line_data(&image, 0..3).len(), 24);
+    }
+}
@@ -0,0 +1,119 @@
+//! Windowing and rendering backends. The application only talks to the
+//! [`Backend`] trait, so that emulators can run on top of different libraries.
+//! Regardless of the backend, input events are expressed using Piston types.
+
+pub mod dirty_lines;
+pub mod null;
+#[cfg(feature = "desktop")]
+pub mod piston_gfx;
+#[cfg(feature = "desktop")]
+pub mod sdl;
+
+use crate::audio::AudioSource;
+use image::RgbaImage;
+use piston::Event;
+
+/// Window backends that can be selected from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum BackendKind {
+    /// Piston window with a GFX renderer.
+    Piston,
+    /// Plain SDL2 window and renderer, for systems where the Piston renderer
+    /// doesn't work well.
+    Sdl2,
+}
+
+#[cfg(feature = "desktop")]
+impl BackendKind {
+    /// Opens a window with a given title and size, in points.
+    pub fn create(self, window_title: &str, window_size: [u32; 2]) -> Box<dyn Backend> {
+        match self {
+            Self::Piston => Box::new(piston_gfx::PistonBackend::new(window_title, window_size)),
+            Self::Sdl2 => Box::new(sdl::SdlBackend::new(window_title, window_size)),
+        }
+    }
+}
+
+/// Indicators and effects drawn over the frame image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Osd {
+    /// Shows that a recording is in progress.
+    pub recording: bool,
+    /// Brightens the whole window to indicate that a screenshot has been
+    /// taken.
+    pub flash: bool,
+    /// Darkens gaps between scanlines.
+    pub scanlines: bool,
+}
+
+/// Opacity of the dark gaps between scanlines.
+#[cfg(feature = "desktop")]
+const SCANLINE_GAP_OPACITY: f32 = 0.4;
+
+/// Presents frames to the user and delivers input and timing events.
+pub trait Backend {
+    /// Waits for the next event. Update events are generated at a rate set
+    /// with [`Backend::set_ups`], each of them followed by a render event.
+    /// Returns `None` once the window is closed.
+    fn next_event(&mut self) -> Option<Event>;
+
+    /// Changes the number of update events per second. Panics if `ups` is
+    /// zero.
+    fn set_ups(&mut self, ups: u64);
+
+    /// Returns the window size, in the same units as mouse cursor positions.
+    fn size(&self) -> [f64; 2];
+
+    /// Finishes handling an event returned by [`Backend::next_event`]. If it's
+    /// a render event, draws a given frame image, stretched to the whole
+    /// window, with on-screen indicators on top of it.
+    fn present(&mut self, event: &Event, frame_image: &RgbaImage, osd: &Osd);
+
+    /// Starts playing audio samples from a given source, replacing the one
+    /// played so far, if any. The source is played until the backend is
+    /// dropped.
+    fn play_audio(&mut self, source: AudioSource);
+}
+
+impl<B: Backend + ?Sized> Backend for Box<B> {
+    fn next_event(&mut self) -> Option<Event> {
+        (**self).next_event()
+    }
+
+    fn set_ups(&mut self, ups: u64) {
+        (**self).set_ups(ups)
+    }
+
+    fn size(&self) -> [f64; 2] {
+        (**self).size()
+    }
+
+    fn present(&mut self, event: &Event, frame_image: &RgbaImage, osd: &Osd) {
+        (**self).present(event, frame_image, osd)
+    }
+
+    fn play_audio(&mut self, source: AudioSource) {
+        (**self).play_audio(source)
+    }
+}
+
+/// Panics unless the number of update events per second is positive.
+fn check_ups(ups: u64) {
+    assert!(ups > 0, "Number of updates per second has to be positive");
+}
+
+/// Returns rectangles that cover the lower half of each line of pixels, given
+/// the number of lines and the size of the drawing area. Each rectangle is
+/// given as `[x, y, width, height]`.
+#[cfg(feature = "desktop")]
+fn scanline_gaps(lines: u32, [width, height]: [f64; 2]) -> impl Iterator<Item = [f64; 4]> {
+    let line_height = height / lines as f64;
+    (0..lines).map(move |y| {
+        [
+            0.0,
+            (y as f64 + 0.5) * line_height,
+            width,
+            line_height / 2.0,
+        ]
+    })
+}
@@ -0,0 +1,85 @@
+//! A backend that doesn't open any window. Useful for testing the application
+//! without a display.
+
+use crate::audio::AudioSource;
+use crate::backend::check_ups;
+use crate::backend::Backend;
+use crate::backend::Osd;
+use image::RgbaImage;
+use piston::{Event, Loop};
+use std::collections::VecDeque;
+
+/// Delivers a predefined sequence of events and remembers what would have been
+/// displayed in response to them.
+pub struct NullBackend {
+    events: VecDeque<Event>,
+    size: [f64; 2],
+    ups: u64,
+    /// Frame images presented in response to render events, along with the
+    /// on-screen indicators.
+    presented_frames: Vec<(RgbaImage, Osd)>,
+    /// The audio source that would have been played.
+    audio_source: Option<AudioSource>,
+}
+
+impl NullBackend {
+    /// Creates a backend that pretends to have a window of a given size and
+    /// returns given events, in order.
+    pub fn new(size: [f64; 2], events: impl IntoIterator<Item = Event>) -> Self {
+        Self {
+            events: events.into_iter().collect(),
+            size,
+            ups: 60,
+            presented_frames: vec![],
+            audio_source: None,
+        }
+    }
+
+    pub fn ups(&self) -> u64 {
+        self.ups
+    }
+
+    pub fn presented_frames(&self) -> &[(RgbaImage, Osd)] {
+        &self.presented_frames
+    }
+
+    pub fn audio_source(&mut self) -> Option<&mut AudioSource> {
+        self.audio_source.as_mut()
+    }
+}
+
+impl Backend for NullBackend {
+    fn next_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    fn set_ups(&mut self, ups: u64) {
+        check_ups(ups);
+        self.ups = ups;
+    }
+
+    fn size(&self) -> [f64; 2] {
+        self.size
+    }
+
+    fn present(&mut self, event: &Event, frame_image: &RgbaImage, osd: &Osd) {
+        if let Event::Loop(Loop::Render(_)) = event {
+            self.presented_frames.push((frame_image.clone(), *osd));
+        }
+    }
+
+    fn play_audio(&mut self, source: AudioSource) {
+        self.audio_source = Some(source);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic]
+    fn ups_has_to_be_positive() {
+        NullBackend::new([1.0, 1.0], []).set_ups(0);
+    }
+}
@@ -0,0 +1,158 @@
+//! The default backend, based on Piston window with an SDL2 window and a GFX
+//! renderer.
+
+use crate::audio;
+use crate::audio::AudioSource;
+use crate::backend::check_ups;
+use crate::backend::dirty_lines::line_data;
+use crate::backend::dirty_lines::DirtyLines;
+use crate::backend::scanline_gaps;
+use crate::backend::Backend;
+use crate::backend::Osd;
+use crate::backend::SCANLINE_GAP_OPACITY;
+use image::RgbaImage;
+use piston::{Event, EventLoop, Window, WindowSettings};
+use piston_window::{
+    Filter, Format as TextureFormat, G2d, G2dTexture, G2dTextureContext, GfxDevice, ImageSize,
+    PistonWindow, Texture, TextureSettings, UpdateTexture,
+};
+use rodio::OutputStream;
+use rodio::Sink;
+use sdl2_window::Sdl2Window;
+
+pub struct PistonBackend {
+    window: PistonWindow<Sdl2Window>,
+    view: View,
+    /// Keeps the audio playing.
+    audio_output: Option<(OutputStream, Sink)>,
+}
+
+impl PistonBackend {
+    /// Opens a window with a given title and size, in points.
+    pub fn new(window_title: &str, window_size: [u32; 2]) -> Self {
+        let window_settings = WindowSettings::new(window_title, window_size);
+        let mut window: PistonWindow<Sdl2Window> =
+            window_settings.build().expect("Could not build a window");
+        // Gamepad events won't be reported unless we explicitly ask for it.
+        if let Err(e) = window.window.init_joysticks() {
+            eprintln!("Unable to initialize gamepads: {}", e);
+        }
+        let view = View::new(window.create_texture_context());
+        return Self {
+            window,
+            view,
+            audio_output: None,
+        };
+    }
+}
+
+impl Backend for PistonBackend {
+    fn next_event(&mut self) -> Option<Event> {
+        self.window.next()
+    }
+
+    fn set_ups(&mut self, ups: u64) {
+        check_ups(ups);
+        self.window.set_ups(ups);
+    }
+
+    fn size(&self) -> [f64; 2] {
+        let size = self.window.size();
+        return [size.width, size.height];
+    }
+
+    fn present(&mut self, event: &Event, frame_image: &RgbaImage, osd: &Osd) {
+        let view = &mut self.view;
+        self.window.draw_2d(event, |ctx, graphics, device| {
+            view.draw(frame_image, osd, ctx, graphics, device);
+        });
+        self.window.event(event);
+    }
+
+    fn play_audio(&mut self, source: AudioSource) {
+        self.audio_output = Some(audio::play(source));
+    }
+}
+
+struct View {
+    texture_context: G2dTextureContext,
+    /// Created once the first frame is drawn.
+    texture: Option<G2dTexture>,
+    /// Tracks the texture contents, so that only the lines that have changed
+    /// since the previous frame are uploaded.
+    dirty_lines: DirtyLines,
+}
+
+impl View {
+    fn new(texture_context: G2dTextureContext) -> Self {
+        return Self {
+            texture_context,
+            texture: None,
+            dirty_lines: DirtyLines::new(),
+        };
+    }
+
+    fn draw(
+        &mut self,
+        frame_image: &RgbaImage,
+        osd: &Osd,
+        ctx: piston_window::Context,
+        g: &mut G2d,
+        device: &mut GfxDevice,
+    ) {
+        let texture_context = &mut self.texture_context;
+        let dirty_lines = self.dirty_lines.update(frame_image);
+        let texture = match &mut self.texture {
+            Some(texture) if texture.get_size() == frame_image.dimensions() => {
+                for lines in dirty_lines {
+                    UpdateTexture::update(
+                        texture,
+                        texture_context,
+                        TextureFormat::Rgba8,
+                        line_data(frame_image, lines.clone()),
+                        [0, lines.start],
+                        [frame_image.width(), lines.len() as u32],
+                    )
+                    .expect("Unable to update texture");
+                }
+                texture
+            }
+            // Some machines change the frame size at runtime. The image gets
+            // stretched to the window anyway, so we only need a new texture.
+            texture => texture.insert(create_texture(texture_context, frame_image)),
+        };
+        graphics::clear([0.0, 0.0, 0.0, 1.0], g);
+        let view_size = ctx.get_view_size();
+        graphics::Image::new()
+            .rect([0.0, 0.0, view_size[0], view_size[1]])
+            .draw(texture, &ctx.draw_state, ctx.transform, g);
+        if osd.scanlines {
+            for gap in scanline_gaps(frame_image.height(), view_size) {
+                graphics::rectangle([0.0, 0.0, 0.0, SCANLINE_GAP_OPACITY], gap, ctx.transform, g);
+            }
+        }
+        if osd.flash {
+            graphics::rectangle(
+                [1.0, 1.0, 1.0, 0.5],
+                [0.0, 0.0, view_size[0], view_size[1]],
+                ctx.transform,
+                g,
+            );
+        }
+        if osd.recording {
+            graphics::ellipse(
+                [1.0, 0.0, 0.0, 1.0],
+                [view_size[0] - 24.0, 8.0, 16.0, 16.0],
+                ctx.transform,
+                g,
+            );
+        }
+        texture_context.encoder.flush(device);
+    }
+}
+
+fn create_texture(texture_context: &mut G2dTextureContext, image: &RgbaImage) -> G2dTexture {
+    let texture_settings = TextureSettings::new().mag(Filter::Nearest);
+    return Texture::from_image(texture_context, image, &texture_settings)
+        .expect("Could not create a texture");
+}
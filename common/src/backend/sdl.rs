@@ -0,0 +1,367 @@
+//! An alternative backend that uses SDL2 directly, without the Piston window
+//! and its GFX renderer. SDL events are translated to Piston ones, just like
+//! the Piston SDL2 window does.
+
+use crate::audio;
+use crate::audio::AudioSource;
+use crate::backend::check_ups;
+use crate::backend::dirty_lines::line_data;
+use crate::backend::dirty_lines::DirtyLines;
+use crate::backend::scanline_gaps;
+use crate::backend::Backend;
+use crate::backend::Osd;
+use crate::backend::SCANLINE_GAP_OPACITY;
+use image::RgbaImage;
+use piston::{
+    Button, ButtonArgs, ButtonState, ControllerAxisArgs, ControllerButton, ControllerHat, Event,
+    FileDrag, HatState, Input, Key, Loop, Motion, MouseButton, RenderArgs, UpdateArgs,
+};
+use rodio::OutputStream;
+use rodio::Sink;
+use sdl2::event::Event as SdlEvent;
+use sdl2::event::WindowEvent;
+use sdl2::joystick::HatState as SdlHatState;
+use sdl2::joystick::Joystick;
+use sdl2::keyboard::Scancode;
+use sdl2::mouse::MouseButton as SdlMouseButton;
+use sdl2::pixels::Color;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::BlendMode;
+use sdl2::render::Canvas;
+use sdl2::render::Texture;
+use sdl2::render::TextureCreator;
+use sdl2::video::Window;
+use sdl2::video::WindowContext;
+use sdl2::EventPump;
+use sdl2::JoystickSubsystem;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+
+/// SDL pixel format that has the same memory layout as [`RgbaImage`].
+#[cfg(target_endian = "little")]
+const FRAME_PIXEL_FORMAT: PixelFormatEnum = PixelFormatEnum::ABGR8888;
+#[cfg(target_endian = "big")]
+const FRAME_PIXEL_FORMAT: PixelFormatEnum = PixelFormatEnum::RGBA8888;
+
+pub struct SdlBackend {
+    canvas: Canvas<Window>,
+    texture_creator: TextureCreator<WindowContext>,
+    /// Holds the frame image. Created once the first frame is drawn, and then
+    /// only recreated if the frame size changes.
+    texture: Option<Texture>,
+    /// Tracks the texture contents, so that only the lines that have changed
+    /// since the previous frame are uploaded.
+    dirty_lines: DirtyLines,
+    event_pump: EventPump,
+    joystick_subsystem: JoystickSubsystem,
+    /// Opened joysticks. SDL only reports events from joysticks that are open.
+    /// Each joystick, including the ones connected before startup, is opened
+    /// once SDL reports it as added.
+    joysticks: Vec<Joystick>,
+    /// Translated events that haven't been returned yet. A single SDL event
+    /// may correspond to multiple Piston ones.
+    pending_events: VecDeque<Event>,
+    update_period: Duration,
+    next_update: Instant,
+    /// Keeps the audio playing.
+    audio_output: Option<(OutputStream, Sink)>,
+}
+
+impl SdlBackend {
+    /// Opens a window with a given title and size, in points.
+    pub fn new(window_title: &str, [width, height]: [u32; 2]) -> Self {
+        let sdl = sdl2::init().expect("Unable to initialize SDL");
+        let video = sdl.video().expect("Unable to initialize SDL video");
+        let window = video
+            .window(window_title, width, height)
+            .position_centered()
+            .resizable()
+            .build()
+            .expect("Could not build a window");
+        let mut canvas = window
+            .into_canvas()
+            .present_vsync()
+            .build()
+            .expect("Could not create a renderer");
+        canvas.set_blend_mode(BlendMode::Blend);
+        let texture_creator = canvas.texture_creator();
+        let event_pump = sdl.event_pump().expect("Unable to create an event pump");
+        let joystick_subsystem = sdl.joystick().expect("Unable to initialize SDL joysticks");
+        let update_period = Duration::from_secs(1) / 60;
+        return Self {
+            canvas,
+            texture_creator,
+            texture: None,
+            dirty_lines: DirtyLines::new(),
+            event_pump,
+            joystick_subsystem,
+            joysticks: vec![],
+            pending_events: VecDeque::new(),
+            update_period,
+            next_update: Instant::now(),
+            audio_output: None,
+        };
+    }
+
+    fn open_joystick(&mut self, index: u32) {
+        match self.joystick_subsystem.open(index) {
+            Ok(joystick) => self.joysticks.push(joystick),
+            Err(e) => eprintln!("Unable to open gamepad {}: {}", index, e),
+        }
+    }
+
+    /// Translates an SDL event and queues the result. Returns `false` if the
+    /// window should be closed.
+    fn handle_sdl_event(&mut self, event: SdlEvent) -> bool {
+        let inputs = match event {
+            SdlEvent::Quit { .. } => return false,
+            SdlEvent::KeyDown {
+                keycode: Some(keycode),
+                scancode,
+                repeat: false,
+                ..
+            } => vec![key_input(keycode as i32, scancode, ButtonState::Press)],
+            SdlEvent::KeyUp {
+                keycode: Some(keycode),
+                scancode,
+                ..
+            } => vec![key_input(keycode as i32, scancode, ButtonState::Release)],
+            SdlEvent::MouseMotion {
+                x, y, xrel, yrel, ..
+            } => vec![
+                Input::Move(Motion::MouseCursor([x as f64, y as f64])),
+                Input::Move(Motion::MouseRelative([xrel as f64, yrel as f64])),
+            ],
+            SdlEvent::MouseButtonDown { mouse_btn, .. } => {
+                vec![mouse_input(mouse_btn, ButtonState::Press)]
+            }
+            SdlEvent::MouseButtonUp { mouse_btn, .. } => {
+                vec![mouse_input(mouse_btn, ButtonState::Release)]
+            }
+            SdlEvent::JoyDeviceAdded { which, .. } => {
+                self.open_joystick(which);
+                vec![]
+            }
+            SdlEvent::JoyAxisMotion {
+                which,
+                axis_idx,
+                value,
+                ..
+            } => vec![Input::Move(Motion::ControllerAxis(
+                ControllerAxisArgs::new(which, axis_idx, value as f64 / i16::MAX as f64),
+            ))],
+            SdlEvent::JoyButtonDown {
+                which, button_idx, ..
+            } => vec![controller_button_input(
+                which,
+                button_idx,
+                ButtonState::Press,
+            )],
+            SdlEvent::JoyButtonUp {
+                which, button_idx, ..
+            } => vec![controller_button_input(
+                which,
+                button_idx,
+                ButtonState::Release,
+            )],
+            SdlEvent::JoyHatMotion {
+                which,
+                hat_idx,
+                state,
+                ..
+            } => vec![Input::Button(ButtonArgs {
+                state: ButtonState::Press,
+                button: Button::Hat(ControllerHat::new(which, hat_idx, hat_state(state))),
+                scancode: None,
+            })],
+            SdlEvent::Window {
+                win_event: WindowEvent::FocusGained,
+                ..
+            } => vec![Input::Focus(true)],
+            SdlEvent::Window {
+                win_event: WindowEvent::FocusLost,
+                ..
+            } => vec![Input::Focus(false)],
+            SdlEvent::DropFile { filename, .. } => {
+                vec![Input::FileDrag(FileDrag::Drop(PathBuf::from(filename)))]
+            }
+            _ => vec![],
+        };
+        self.pending_events
+            .extend(inputs.into_iter().map(|input| Event::Input(input, None)));
+        return true;
+    }
+
+    fn render_args(&self) -> RenderArgs {
+        let (window_width, window_height) = self.canvas.window().size();
+        let (draw_width, draw_height) = self.canvas.output_size().unwrap_or((0, 0));
+        RenderArgs {
+            ext_dt: 0.0,
+            window_size: [window_width as f64, window_height as f64],
+            draw_size: [draw_width, draw_height],
+        }
+    }
+
+    fn draw(&mut self, frame_image: &RgbaImage, osd: &Osd) -> Result<(), String> {
+        let (width, height) = frame_image.dimensions();
+        let texture = match &mut self.texture {
+            Some(texture) if texture.query().width == width && texture.query().height == height => {
+                texture
+            }
+            // Some machines change the frame size at runtime. The image gets
+            // stretched to the window anyway, so we only need a new texture.
+            texture => {
+                if let Some(old_texture) = texture.take() {
+                    // SAFETY: The old texture is not referenced anywhere else,
+                    // and the renderer that created it is still alive.
+                    unsafe { old_texture.destroy() };
+                }
+                self.dirty_lines.invalidate();
+                texture.insert(
+                    self.texture_creator
+                        .create_texture_streaming(FRAME_PIXEL_FORMAT, width, height)
+                        .map_err(|e| e.to_string())?,
+                )
+            }
+        };
+        for lines in self.dirty_lines.update(frame_image) {
+            let rect = Rect::new(0, lines.start as i32, width, lines.len() as u32);
+            texture
+                .update(rect, line_data(frame_image, lines), width as usize * 4)
+                .map_err(|e| e.to_string())?;
+        }
+
+        let canvas = &mut self.canvas;
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        canvas.clear();
+        canvas.copy(texture, None, None)?;
+        let (view_width, view_height) = canvas.output_size()?;
+        if osd.scanlines {
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, (SCANLINE_GAP_OPACITY * 255.0) as u8));
+            for [x, y, w, h] in scanline_gaps(height, [view_width as f64, view_height as f64]) {
+                canvas.fill_rect(Rect::new(
+                    x as i32,
+                    y.round() as i32,
+                    w as u32,
+                    (h.round() as u32).max(1),
+                ))?;
+            }
+        }
+        if osd.flash {
+            canvas.set_draw_color(Color::RGBA(255, 255, 255, 128));
+            canvas.fill_rect(None)?;
+        }
+        if osd.recording {
+            canvas.set_draw_color(Color::RGB(255, 0, 0));
+            canvas.fill_rect(Rect::new(view_width as i32 - 24, 8, 16, 16))?;
+        }
+        canvas.present();
+        return Ok(());
+    }
+}
+
+impl Backend for SdlBackend {
+    fn next_event(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.pending_events.pop_front() {
+                return Some(event);
+            }
+            let now = Instant::now();
+            if now >= self.next_update {
+                // Don't try to catch up if we're way behind the schedule.
+                self.next_update = (self.next_update + self.update_period).max(now);
+                self.pending_events.push_back(Event::from(UpdateArgs {
+                    dt: self.update_period.as_secs_f64(),
+                }));
+                let render_args = self.render_args();
+                self.pending_events.push_back(Event::from(render_args));
+                continue;
+            }
+            let timeout = self.next_update - now;
+            let sdl_event = self
+                .event_pump
+                .wait_event_timeout(timeout.as_millis() as u32);
+            let pending_sdl_events = sdl_event
+                .into_iter()
+                .chain(self.event_pump.poll_iter())
+                .collect::<Vec<_>>();
+            for sdl_event in pending_sdl_events {
+                if !self.handle_sdl_event(sdl_event) {
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn set_ups(&mut self, ups: u64) {
+        check_ups(ups);
+        self.update_period = Duration::from_nanos(1_000_000_000 / ups);
+    }
+
+    fn size(&self) -> [f64; 2] {
+        let (width, height) = self.canvas.window().size();
+        return [width as f64, height as f64];
+    }
+
+    fn present(&mut self, event: &Event, frame_image: &RgbaImage, osd: &Osd) {
+        if let Event::Loop(Loop::Render(_)) = event {
+            if let Err(e) = self.draw(frame_image, osd) {
+                eprintln!("Unable to draw the frame: {}", e);
+            }
+        }
+    }
+
+    fn play_audio(&mut self, source: AudioSource) {
+        self.audio_output = Some(audio::play(source));
+    }
+}
+
+fn key_input(keycode: i32, scancode: Option<Scancode>, state: ButtonState) -> Input {
+    // Piston key codes are the same as SDL ones.
+    Input::Button(ButtonArgs {
+        state,
+        button: Button::Keyboard(Key::from(keycode as u32)),
+        scancode: scancode.map(|scancode| scancode as i32),
+    })
+}
+
+fn mouse_input(button: SdlMouseButton, state: ButtonState) -> Input {
+    let button = match button {
+        SdlMouseButton::Left => MouseButton::Left,
+        SdlMouseButton::Middle => MouseButton::Middle,
+        SdlMouseButton::Right => MouseButton::Right,
+        SdlMouseButton::X1 => MouseButton::X1,
+        SdlMouseButton::X2 => MouseButton::X2,
+        SdlMouseButton::Unknown => MouseButton::Unknown,
+    };
+    Input::Button(ButtonArgs {
+        state,
+        button: Button::Mouse(button),
+        scancode: None,
+    })
+}
+
+fn controller_button_input(id: u32, button: u8, state: ButtonState) -> Input {
+    Input::Button(ButtonArgs {
+        state,
+        button: Button::Controller(ControllerButton::new(id, button)),
+        scancode: None,
+    })
+}
+
+fn hat_state(state: SdlHatState) -> HatState {
+    match state {
+        SdlHatState::Centered => HatState::Centered,
+        SdlHatState::Up => HatState::Up,
+        SdlHatState::Right => HatState::Right,
+        SdlHatState::Down => HatState::Down,
+        SdlHatState::Left => HatState::Left,
+        SdlHatState::RightUp => HatState::RightUp,
+        SdlHatState::RightDown => HatState::RightDown,
+        SdlHatState::LeftUp => HatState::LeftUp,
+        SdlHatState::LeftDown => HatState::LeftDown,
+    }
+}
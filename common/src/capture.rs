@@ -0,0 +1,107 @@
+//! Capturing screenshots and animations of the emulated machine's screen.
+
+use image::codecs::gif::GifEncoder;
+use image::codecs::gif::Repeat;
+use image::Delay;
+use image::Frame;
+use image::ImageResult;
+use image::RgbaImage;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// Speed of the GIF color quantization, from 1 (best quality) to 30 (fastest).
+const GIF_ENCODING_SPEED: i32 = 10;
+
+/// Generates a path of a new capture file in a given directory. The file name
+/// consists of a prefix, a timestamp, and an extension.
+pub fn capture_path(dir: &Path, prefix: &str, extension: &str) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    return dir.join(format!("{}-{}.{}", prefix, timestamp, extension));
+}
+
+/// Saves a frame as a PNG file.
+pub fn save_screenshot(image: &RgbaImage, path: &Path) -> ImageResult<()> {
+    image.save_with_format(path, image::ImageFormat::Png)
+}
+
+/// Records frames to an animated GIF file. Since encoding a GIF takes a lot of
+/// time, it's done on a separate thread, so that the emulation doesn't slow
+/// down.
+pub struct GifRecorder {
+    sender: mpsc::Sender<RgbaImage>,
+    encoder_thread: thread::JoinHandle<ImageResult<()>>,
+}
+
+impl GifRecorder {
+    /// Creates a GIF file and starts recording. Each recorded frame will be
+    /// displayed for a given amount of time.
+    pub fn start(path: &Path, frame_duration: Duration) -> io::Result<Self> {
+        let file = File::create(path)?;
+        let delay = Delay::from_saturating_duration(frame_duration);
+        let (sender, receiver) = mpsc::channel::<RgbaImage>();
+        let encoder_thread = thread::spawn(move || {
+            let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), GIF_ENCODING_SPEED);
+            encoder.set_repeat(Repeat::Infinite)?;
+            for image in receiver {
+                encoder.encode_frame(Frame::from_parts(image, 0, 0, delay))?;
+            }
+            return Ok(());
+        });
+        return Ok(Self {
+            sender,
+            encoder_thread,
+        });
+    }
+
+    pub fn add_frame(&self, image: &RgbaImage) {
+        // If the encoder thread has failed, the error will be reported once the
+        // recording is finished.
+        let _ = self.sender.send(image.clone());
+    }
+
+    /// Stops recording. Returns a handle that can be used to wait until all of
+    /// the recorded frames are encoded and the file is complete.
+    pub fn finish(self) -> thread::JoinHandle<ImageResult<()>> {
+        drop(self.sender);
+        return self.encoder_thread;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifDecoder;
+    use image::AnimationDecoder;
+    use image::Rgba;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn records_gifs() {
+        let path = env::temp_dir().join(format!("steampunk-test-{}.gif", std::process::id()));
+        let recorder = GifRecorder::start(&path, Duration::from_millis(20)).unwrap();
+        recorder.add_frame(&RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 255])));
+        recorder.add_frame(&RgbaImage::from_pixel(2, 2, Rgba([255, 255, 255, 255])));
+        recorder.finish().join().unwrap().unwrap();
+
+        let decoder = GifDecoder::new(File::open(&path).unwrap()).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].delay().numer_denom_ms(), (20, 1));
+        assert_eq!(
+            frames[1].buffer().get_pixel(1, 1),
+            &Rgba([255, 255, 255, 255])
+        );
+    }
+}
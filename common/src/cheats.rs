@@ -0,0 +1,366 @@
+//! Cheats, or persistent memory overrides. Each cheat pins a memory location
+//! to a given value. Cheats are applied by [`CheatMemory`], a wrapper around
+//! the address space of a machine: the CPU reads the pinned value instead of
+//! whatever the memory contains, and whenever it writes to a pinned location,
+//! the pinned value is written instead, so that the program never gets to see
+//! anything else there. Since reads are overridden, cheats also work for ROM,
+//! like the good old Game Genie.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::str::FromStr;
+use thiserror::Error;
+use ya6502::memory::Inspect;
+use ya6502::memory::Memory;
+use ya6502::memory::MemoryRegion;
+use ya6502::memory::Read;
+use ya6502::memory::ReadResult;
+use ya6502::memory::Write;
+use ya6502::memory::WriteResult;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cheat {
+    pub address: u16,
+    pub value: u8,
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Invalid cheat: {text} (expected <address>=<value>, both hexadecimal)")]
+pub struct CheatParseError {
+    text: String,
+}
+
+impl FromStr for Cheat {
+    type Err = CheatParseError;
+
+    /// Parses a cheat in the `address=value` form. Both numbers are
+    /// hexadecimal, optionally prefixed with `$` or `0x`, e.g. `$0080=$05`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let parse = || {
+            let (address, value) = text.split_once('=')?;
+            Some(Cheat {
+                address: parse_cheat_address(address)?,
+                value: u8::from_str_radix(strip_hex_prefix(value.trim()), 16).ok()?,
+            })
+        };
+        return parse().ok_or_else(|| CheatParseError {
+            text: text.to_string(),
+        });
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "${:04X}=${:02X}", self.address, self.value)
+    }
+}
+
+/// Parses an address of a cheat: a hexadecimal number, optionally prefixed
+/// with `$` or `0x`.
+pub fn parse_cheat_address(text: &str) -> Option<u16> {
+    u16::from_str_radix(strip_hex_prefix(text.trim()), 16).ok()
+}
+
+fn strip_hex_prefix(text: &str) -> &str {
+    text.strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .unwrap_or(text)
+}
+
+/// A change to the set of installed cheats, requested while the machine is
+/// running.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheatCommand {
+    Install(Cheat),
+    Remove(u16),
+}
+
+impl fmt::Display for CheatCommand {
+    /// Describes the command as if it was already executed.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheatCommand::Install(cheat) => write!(f, "Cheat installed: {}", cheat),
+            CheatCommand::Remove(address) => write!(f, "Cheat removed: ${:04X}", address),
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum CheatError {
+    #[error("This machine doesn't support cheats")]
+    Unsupported,
+    #[error("No cheat installed at ${0:04X}")]
+    NotInstalled(u16),
+}
+
+/// A set of cheats, at most one per memory location. Since the same location
+/// can be visible at multiple addresses, the table uses a mirroring function
+/// to identify it by a single address.
+#[derive(Debug)]
+pub struct CheatTable {
+    values: BTreeMap<u16, u8>,
+    /// Returns the address that identifies the memory location visible at a
+    /// given address.
+    mirrored_address: fn(u16) -> u16,
+}
+
+impl Default for CheatTable {
+    fn default() -> Self {
+        Self {
+            values: BTreeMap::new(),
+            mirrored_address: |address| address,
+        }
+    }
+}
+
+impl CheatTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the mirroring function of the address space. By default, each
+    /// address is considered a separate location.
+    pub fn with_mirroring(mut self, mirrored_address: fn(u16) -> u16) -> Self {
+        self.mirrored_address = mirrored_address;
+        return self;
+    }
+
+    /// Adds a cheat to the table. See [`CheatTable::install`]. Note that the
+    /// mirroring function needs to be set first.
+    pub fn with_cheat(mut self, cheat: Cheat) -> Self {
+        self.install(cheat);
+        return self;
+    }
+
+    /// Adds a cheat, replacing any other one at the same location.
+    pub fn install(&mut self, cheat: Cheat) {
+        self.values
+            .insert((self.mirrored_address)(cheat.address), cheat.value);
+    }
+
+    /// Removes a cheat from a given location.
+    pub fn remove(&mut self, address: u16) -> Result<(), CheatError> {
+        return match self.values.remove(&(self.mirrored_address)(address)) {
+            Some(_) => Ok(()),
+            None => Err(CheatError::NotInstalled(address)),
+        };
+    }
+
+    pub fn execute(&mut self, command: CheatCommand) -> Result<(), CheatError> {
+        match command {
+            CheatCommand::Install(cheat) => self.install(cheat),
+            CheatCommand::Remove(address) => self.remove(address)?,
+        }
+        return Ok(());
+    }
+
+    /// Returns the installed cheats. Their addresses are the ones that
+    /// identify the memory locations.
+    pub fn cheats(&self) -> impl Iterator<Item = Cheat> + '_ {
+        self.values
+            .iter()
+            .map(|(&address, &value)| Cheat { address, value })
+    }
+
+    /// Returns the value that a given address is pinned to, if any.
+    pub fn value_at(&self, address: u16) -> Option<u8> {
+        if self.values.is_empty() {
+            // A shortcut for the usual case.
+            return None;
+        }
+        return self.values.get(&(self.mirrored_address)(address)).copied();
+    }
+}
+
+/// Applies cheats to the memory accessed by the CPU. Other chips that access
+/// the memory directly (like the C64 VIC) only see a pinned value once the CPU
+/// writes to its location. Dereferences to the wrapped memory, so that the
+/// machine can still access its chips.
+#[derive(Debug)]
+pub struct CheatMemory<M> {
+    memory: M,
+    cheats: CheatTable,
+}
+
+impl<M> CheatMemory<M> {
+    pub fn new(memory: M, cheats: CheatTable) -> Self {
+        Self { memory, cheats }
+    }
+
+    pub fn cheats(&self) -> &CheatTable {
+        &self.cheats
+    }
+
+    pub fn mut_cheats(&mut self) -> &mut CheatTable {
+        &mut self.cheats
+    }
+}
+
+impl<M> Deref for CheatMemory<M> {
+    type Target = M;
+
+    fn deref(&self) -> &M {
+        &self.memory
+    }
+}
+
+impl<M> DerefMut for CheatMemory<M> {
+    fn deref_mut(&mut self) -> &mut M {
+        &mut self.memory
+    }
+}
+
+impl<M: Read> Read for CheatMemory<M> {
+    fn read(&mut self, address: u16) -> ReadResult {
+        // Always perform the actual read, since it may have side effects.
+        let value = self.memory.read(address)?;
+        return Ok(self.cheats.value_at(address).unwrap_or(value));
+    }
+}
+
+impl<M: Write> Write for CheatMemory<M> {
+    fn write(&mut self, address: u16, value: u8) -> WriteResult {
+        self.memory
+            .write(address, self.cheats.value_at(address).unwrap_or(value))
+    }
+}
+
+impl<M: Memory> Memory for CheatMemory<M> {}
+
+impl<M: Inspect> Inspect for CheatMemory<M> {
+    fn inspect(&self, address: u16) -> ReadResult {
+        let value = self.memory.inspect(address)?;
+        return Ok(self.cheats.value_at(address).unwrap_or(value));
+    }
+
+    fn memory_map(&self) -> Vec<MemoryRegion> {
+        self.memory.memory_map()
+    }
+}
+
+impl<M: fmt::Display> fmt::Display for CheatMemory<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.memory.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ya6502::cpu::opcodes;
+    use ya6502::cpu::Cpu;
+    use ya6502::cpu::MachineInspector;
+    use ya6502::memory::Ram;
+    use ya6502::test_utils::reset;
+
+    #[test]
+    fn parses_cheats() {
+        assert_eq!(
+            "$0080=$05".parse(),
+            Ok(Cheat {
+                address: 0x0080,
+                value: 0x05
+            })
+        );
+        assert_eq!(
+            "0xD020 = 0x0e".parse(),
+            Ok(Cheat {
+                address: 0xD020,
+                value: 0x0E
+            })
+        );
+        assert_eq!(
+            "1234=AB".parse(),
+            Ok(Cheat {
+                address: 0x1234,
+                value: 0xAB
+            })
+        );
+        assert!("1234".parse::<Cheat>().is_err());
+        assert!("12345=AB".parse::<Cheat>().is_err());
+        assert!("1234=ABC".parse::<Cheat>().is_err());
+        assert!("$80=".parse::<Cheat>().is_err());
+    }
+
+    #[test]
+    fn formats_cheats() {
+        let cheat = Cheat {
+            address: 0x80,
+            value: 0x5,
+        };
+        assert_eq!(cheat.to_string(), "$0080=$05");
+        assert_eq!(
+            CheatCommand::Install(cheat).to_string(),
+            "Cheat installed: $0080=$05"
+        );
+        assert_eq!(
+            CheatCommand::Remove(0x80).to_string(),
+            "Cheat removed: $0080"
+        );
+    }
+
+    fn cpu_with_cheats(program: &[u8], cheats: CheatTable) -> Cpu<CheatMemory<Ram>> {
+        let memory = Box::new(CheatMemory::new(Ram::with_test_program(program), cheats));
+        let mut cpu = Cpu::new(memory);
+        reset(&mut cpu);
+        return cpu;
+    }
+
+    #[test]
+    fn overrides_reads_and_writes() {
+        #[rustfmt::skip]
+        let program = [
+            opcodes::LDA_ZP, 0x80, // 3 cycles
+            opcodes::STA_ZP, 0x81, // 3 cycles
+            opcodes::LDA_IMM, 1, // 2 cycles
+            opcodes::STA_ZP, 0x80, // 3 cycles
+            opcodes::INC_ZP, 0x80, // 5 cycles
+            opcodes::LDA_IMM, 1, // 2 cycles
+            opcodes::STA_ZP, 0x80, // 3 cycles
+        ];
+        let mut cpu = cpu_with_cheats(
+            &program,
+            CheatTable::new().with_cheat("80=2A".parse().unwrap()),
+        );
+        // The memory is not touched until the CPU writes to it.
+        assert_eq!(cpu.memory().bytes[0x80], 0);
+        assert_eq!(cpu.inspect_memory(0x80), 0x2A);
+
+        cpu.ticks(3 + 3 + 2 + 3 + 5).unwrap();
+        assert_eq!(cpu.memory().bytes[0x80..=0x81], [0x2A, 0x2A]);
+        assert_eq!(cpu.reg_a(), 1);
+
+        cpu.mut_memory().mut_cheats().remove(0x80).unwrap();
+        cpu.ticks(2 + 3).unwrap();
+        assert_eq!(cpu.memory().bytes[0x80], 1);
+        assert_eq!(
+            cpu.mut_memory().mut_cheats().remove(0x80),
+            Err(CheatError::NotInstalled(0x80))
+        );
+    }
+
+    #[test]
+    fn handles_mirrors() {
+        let mut cheats = CheatTable::new().with_mirroring(|address| address & 0x00FF);
+        cheats.install("0180=05".parse().unwrap());
+        assert_eq!(
+            cheats.cheats().collect::<Vec<_>>(),
+            vec![Cheat {
+                address: 0x80,
+                value: 0x05
+            }]
+        );
+        assert_eq!(cheats.value_at(0x0080), Some(0x05));
+        assert_eq!(cheats.value_at(0x1380), Some(0x05));
+        assert_eq!(cheats.value_at(0x0081), None);
+
+        cheats
+            .execute(CheatCommand::Install("1280=06".parse().unwrap()))
+            .unwrap();
+        assert_eq!(cheats.value_at(0x0180), Some(0x06));
+        cheats.execute(CheatCommand::Remove(0x0280)).unwrap();
+        assert_eq!(cheats.value_at(0x0080), None);
+    }
+}
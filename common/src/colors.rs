@@ -20,6 +20,38 @@ pub fn create_palette(colors: &[u32]) -> Palette {
     return palette;
 }
 
+/// A palette converted to a lookup table of raw RGBA bytes, indexed directly by
+/// 8-bit color codes. Unlike [`Palette`], it always has 256 entries, so that
+/// looking up a color doesn't need any bounds checks, and the bytes can be
+/// copied straight into an image buffer. Codes not covered by the original
+/// palette are mapped to black.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaletteLookup([[u8; 4]; 256]);
+
+impl PaletteLookup {
+    pub fn new(palette: &Palette) -> Self {
+        let mut colors = [[0x00, 0x00, 0x00, 0xFF]; 256];
+        for (entry, color) in colors.iter_mut().zip(palette) {
+            *entry = color.0;
+        }
+        return Self(colors);
+    }
+
+    /// Returns RGBA bytes of a given color.
+    #[inline]
+    pub fn rgba(&self, color: u8) -> &[u8; 4] {
+        &self.0[color as usize]
+    }
+}
+
+/// Sets the color of a pixel in a raw RGBA buffer of an image that is `width`
+/// pixels wide. Coordinates are expected to be within the image.
+#[inline]
+pub fn put_rgba(buffer: &mut [u8], width: usize, x: usize, y: usize, rgba: &[u8; 4]) {
+    let offset = (y * width + x) * 4;
+    buffer[offset..offset + 4].copy_from_slice(rgba);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -42,4 +74,23 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn palette_lookup() {
+        let lookup = PaletteLookup::new(&create_palette(&[0xFEDCBA, 0x5A0345]));
+        assert_eq!(lookup.rgba(0), &[0xFE, 0xDC, 0xBA, 0xFF]);
+        assert_eq!(lookup.rgba(1), &[0x5A, 0x03, 0x45, 0xFF]);
+        assert_eq!(lookup.rgba(2), &[0x00, 0x00, 0x00, 0xFF]);
+        assert_eq!(lookup.rgba(255), &[0x00, 0x00, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn puts_rgba_pixels() {
+        let mut buffer = vec![0; 3 * 2 * 4];
+        put_rgba(&mut buffer, 3, 2, 1, &[1, 2, 3, 4]);
+        put_rgba(&mut buffer, 3, 0, 0, &[5, 6, 7, 8]);
+        assert_eq!(&buffer[0..4], &[5, 6, 7, 8]);
+        assert_eq!(&buffer[20..24], &[1, 2, 3, 4]);
+        assert!(buffer[4..20].iter().all(|&b| b == 0));
+    }
 }
@@ -0,0 +1,90 @@
+//! Loading configuration files, like keymaps or databases of known programs.
+//! The structure of the configuration is up to the caller; all files can be
+//! written either in JSON or TOML.
+
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// Guesses the format from a file name extension. Files without a `.toml`
+    /// extension are assumed to be JSON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("toml") => Self::Toml,
+            _ => Self::Json,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("I/O error: {0}")]
+    IoError(#[from] io::Error),
+
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("TOML error: {0}")]
+    TomlError(#[from] toml::de::Error),
+}
+
+/// Reads a configuration from a file. The format is determined by the file
+/// name extension.
+pub fn read_config_file<T: DeserializeOwned>(path: &Path) -> Result<T, ConfigError> {
+    let text = fs::read_to_string(path)?;
+    return parse_config(&text, ConfigFormat::from_path(path));
+}
+
+pub fn parse_config<T: DeserializeOwned>(
+    text: &str,
+    format: ConfigFormat,
+) -> Result<T, ConfigError> {
+    return Ok(match format {
+        ConfigFormat::Json => serde_json::from_str(text)?,
+        ConfigFormat::Toml => toml::from_str(text)?,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::assert_matches::assert_matches;
+    use std::collections::HashMap;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Config {
+        keys: HashMap<String, u8>,
+    }
+
+    #[test]
+    fn parses_configs() {
+        let json: Config = parse_config(r#"{"keys": {"A": 1}}"#, ConfigFormat::Json).unwrap();
+        let toml: Config = parse_config("[keys]\nA = 1\n", ConfigFormat::Toml).unwrap();
+        assert_eq!(json, toml);
+        assert_matches!(
+            parse_config::<Config>("[keys]\nA = \"B\"\n", ConfigFormat::Toml),
+            Err(ConfigError::TomlError(_))
+        );
+    }
+
+    #[test]
+    fn detects_format() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("keys.TOML")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("keys.json")),
+            ConfigFormat::Json
+        );
+    }
+}
@@ -0,0 +1,380 @@
+//! Post-processing effects that make the picture look more like the one
+//! displayed by a CRT TV set. Scanlines are drawn by the application view on
+//! top of the texture, while the remaining effects are applied to frame images
+//! by [`CrtFilter`], once per emulated frame. [`ThreadedCrtFilter`] does the
+//! same on a separate thread.
+
+use image::Rgba;
+use image::RgbaImage;
+use std::sync::mpsc;
+use std::thread;
+
+/// How much of the previous frame's brightness survives until the next frame.
+/// With 0.5, an object that flickers at 30 Hz appears with half of its normal
+/// intensity on the "empty" frames.
+const PHOSPHOR_PERSISTENCE: f32 = 0.5;
+
+/// Horizontal blur kernel applied to the chroma components of the picture.
+const CHROMA_KERNEL: [f32; 3] = [0.25, 0.5, 0.25];
+
+/// Maximum number of frames waiting for [`ThreadedCrtFilter`] or being
+/// processed. Together with the displayed image, it makes triple buffering.
+const MAX_FRAMES_IN_FLIGHT: usize = 2;
+
+/// A set of CRT effects to apply to the picture. By default, all of them are
+/// disabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CrtEffects {
+    /// Darkens gaps between scanlines.
+    pub scanlines: bool,
+    /// Blends each frame with a fading copy of the previous ones.
+    pub phosphor: bool,
+    /// Blurs colors horizontally, keeping the luminance sharp.
+    pub color_bleeding: bool,
+}
+
+/// Applies the effects that operate on frame images. Keeps the processed image
+/// around, since the phosphor persistence depends on what has been displayed
+/// before.
+pub struct CrtFilter {
+    effects: CrtEffects,
+    image: Option<RgbaImage>,
+}
+
+impl CrtFilter {
+    pub fn new(effects: CrtEffects) -> Self {
+        Self {
+            effects,
+            image: None,
+        }
+    }
+
+    /// Processes a newly emulated frame. Should be called exactly once per
+    /// frame, as otherwise the phosphor would fade too fast.
+    pub fn process(&mut self, frame: &RgbaImage) {
+        if !self.effects.phosphor && !self.effects.color_bleeding {
+            return;
+        }
+        let mut image = if self.effects.color_bleeding {
+            bleed_colors(frame)
+        } else {
+            frame.clone()
+        };
+        if self.effects.phosphor {
+            match &self.image {
+                Some(previous) if previous.dimensions() == image.dimensions() => {
+                    apply_persistence(&mut image, previous)
+                }
+                _ => {}
+            }
+        }
+        self.image = Some(image);
+    }
+
+    /// Returns the result of processing the last frame, or `None` if there's
+    /// nothing to process and the original frame should be displayed instead.
+    pub fn image(&self) -> Option<&RgbaImage> {
+        self.image.as_ref()
+    }
+}
+
+/// Runs a [`CrtFilter`] on a separate thread, so that processing a frame
+/// overlaps with emulating the next one. The processed images lag behind the
+/// emulated frames, usually by a single frame. If the thread can't keep up,
+/// frames are skipped instead of slowing down the emulation.
+pub struct ThreadedCrtFilter {
+    /// `None` if there's nothing to process.
+    worker: Option<Worker>,
+    image: Option<RgbaImage>,
+    /// Buffers of processed images that are no longer displayed, ready to be
+    /// reused for new frames.
+    free_buffers: Vec<RgbaImage>,
+}
+
+struct Worker {
+    sender: Option<mpsc::Sender<RgbaImage>>,
+    receiver: mpsc::Receiver<RgbaImage>,
+    thread: Option<thread::JoinHandle<()>>,
+    frames_in_flight: usize,
+}
+
+impl ThreadedCrtFilter {
+    pub fn new(effects: CrtEffects) -> Self {
+        let worker = (effects.phosphor || effects.color_bleeding).then(|| {
+            let (sender, frame_receiver) = mpsc::channel::<RgbaImage>();
+            let (image_sender, receiver) = mpsc::channel();
+            let thread = thread::spawn(move || {
+                let mut filter = CrtFilter::new(effects);
+                for mut frame in frame_receiver {
+                    filter.process(&frame);
+                    if let Some(image) = filter.image() {
+                        frame.copy_from_slice(image);
+                    }
+                    if image_sender.send(frame).is_err() {
+                        return;
+                    }
+                }
+            });
+            Worker {
+                sender: Some(sender),
+                receiver,
+                thread: Some(thread),
+                frames_in_flight: 0,
+            }
+        });
+        Self {
+            worker,
+            image: None,
+            free_buffers: vec![],
+        }
+    }
+
+    /// Sends a newly emulated frame to be processed, unless there are already
+    /// too many frames waiting for the worker thread.
+    pub fn process(&mut self, frame: &RgbaImage) {
+        self.poll();
+        let worker = match &mut self.worker {
+            Some(worker) if worker.frames_in_flight < MAX_FRAMES_IN_FLIGHT => worker,
+            _ => return,
+        };
+        let buffer = match self.free_buffers.pop() {
+            Some(mut buffer) if buffer.dimensions() == frame.dimensions() => {
+                buffer.copy_from_slice(frame);
+                buffer
+            }
+            _ => frame.clone(),
+        };
+        if let Some(sender) = &worker.sender {
+            if sender.send(buffer).is_ok() {
+                worker.frames_in_flight += 1;
+            }
+        }
+    }
+
+    /// Picks up the images processed by the worker thread. Should be called
+    /// before [`ThreadedCrtFilter::image`] to get the most recent one.
+    pub fn poll(&mut self) {
+        if let Some(worker) = &mut self.worker {
+            while let Ok(image) = worker.receiver.try_recv() {
+                worker.frames_in_flight -= 1;
+                self.free_buffers.extend(self.image.replace(image));
+            }
+        }
+    }
+
+    /// Returns the last processed image, or `None` if there's nothing to
+    /// process (or nothing has been processed yet), and the original frame
+    /// should be displayed instead.
+    pub fn image(&self) -> Option<&RgbaImage> {
+        self.image.as_ref()
+    }
+
+    /// Waits until all frames sent to the worker thread are processed.
+    #[cfg(test)]
+    fn flush(&mut self) {
+        if let Some(worker) = &mut self.worker {
+            while worker.frames_in_flight > 0 {
+                let image = worker.receiver.recv().unwrap();
+                worker.frames_in_flight -= 1;
+                self.free_buffers.extend(self.image.replace(image));
+            }
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // Closing the channel makes the thread finish.
+        drop(self.sender.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Makes each pixel at least as bright as the fading previous one.
+fn apply_persistence(image: &mut RgbaImage, previous: &RgbaImage) {
+    for (pixel, previous_pixel) in image.pixels_mut().zip(previous.pixels()) {
+        for (channel, previous_channel) in pixel.0[..3].iter_mut().zip(&previous_pixel.0[..3]) {
+            let afterglow = (*previous_channel as f32 * PHOSPHOR_PERSISTENCE) as u8;
+            *channel = (*channel).max(afterglow);
+        }
+    }
+}
+
+/// Converts a pixel to the YIQ color space.
+fn to_yiq(pixel: &Rgba<u8>) -> [f32; 3] {
+    let [r, g, b, _] = pixel.0.map(|channel| channel as f32);
+    return [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        0.596 * r - 0.274 * g - 0.322 * b,
+        0.211 * r - 0.523 * g + 0.312 * b,
+    ];
+}
+
+/// Converts a YIQ color back to RGB, preserving a given alpha value.
+fn from_yiq([y, i, q]: [f32; 3], alpha: u8) -> Rgba<u8> {
+    let clamp = |channel: f32| channel.round().clamp(0.0, 255.0) as u8;
+    return Rgba([
+        clamp(y + 0.956 * i + 0.621 * q),
+        clamp(y - 0.272 * i - 0.647 * q),
+        clamp(y - 1.106 * i + 1.703 * q),
+        alpha,
+    ]);
+}
+
+/// Blurs the chroma (I and Q components) of each line, imitating the narrow
+/// bandwidth of the color signal in an NTSC composite video.
+fn bleed_colors(frame: &RgbaImage) -> RgbaImage {
+    let (width, height) = frame.dimensions();
+    let mut image = RgbaImage::new(width, height);
+    let radius = CHROMA_KERNEL.len() as i64 / 2;
+    for y in 0..height {
+        let line: Vec<[f32; 3]> = (0..width).map(|x| to_yiq(frame.get_pixel(x, y))).collect();
+        for x in 0..width {
+            let [luma, _, _] = line[x as usize];
+            let (mut i, mut q) = (0.0, 0.0);
+            for (offset, weight) in (-radius..=radius).zip(CHROMA_KERNEL) {
+                let source_x = (x as i64 + offset).clamp(0, width as i64 - 1) as usize;
+                i += line[source_x][1] * weight;
+                q += line[source_x][2] * weight;
+            }
+            image.put_pixel(x, y, from_yiq([luma, i, q], frame.get_pixel(x, y)[3]));
+        }
+    }
+    return image;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+    const RED: Rgba<u8> = Rgba([200, 0, 0, 255]);
+
+    fn image_of(pixels: &[Rgba<u8>]) -> RgbaImage {
+        let mut image = RgbaImage::new(pixels.len() as u32, 1);
+        for (x, pixel) in pixels.iter().enumerate() {
+            image.put_pixel(x as u32, 0, *pixel);
+        }
+        return image;
+    }
+
+    #[test]
+    fn does_nothing_by_default() {
+        let mut filter = CrtFilter::new(CrtEffects {
+            scanlines: true,
+            ..Default::default()
+        });
+        filter.process(&image_of(&[WHITE]));
+        assert_eq!(filter.image(), None);
+    }
+
+    #[test]
+    fn phosphor_fades_out() {
+        let mut filter = CrtFilter::new(CrtEffects {
+            phosphor: true,
+            ..Default::default()
+        });
+        filter.process(&image_of(&[WHITE, BLACK]));
+        assert_eq!(filter.image(), Some(&image_of(&[WHITE, BLACK])));
+
+        filter.process(&image_of(&[BLACK, RED]));
+        assert_eq!(
+            filter.image(),
+            Some(&image_of(&[Rgba([127, 127, 127, 255]), RED]))
+        );
+
+        filter.process(&image_of(&[BLACK, BLACK]));
+        assert_eq!(
+            filter.image(),
+            Some(&image_of(&[
+                Rgba([63, 63, 63, 255]),
+                Rgba([100, 0, 0, 255])
+            ]))
+        );
+
+        // A frame of a different size starts from scratch.
+        filter.process(&image_of(&[BLACK]));
+        assert_eq!(filter.image(), Some(&image_of(&[BLACK])));
+    }
+
+    #[test]
+    fn color_bleeding_keeps_uniform_areas() {
+        let mut filter = CrtFilter::new(CrtEffects {
+            color_bleeding: true,
+            ..Default::default()
+        });
+        let frame = image_of(&[RED, RED, RED, WHITE, WHITE]);
+        filter.process(&frame);
+        let image = filter.image().unwrap();
+        assert_eq!(image.get_pixel(0, 0), &RED);
+        assert_eq!(image.get_pixel(4, 0), &WHITE);
+    }
+
+    #[test]
+    fn threaded_filter_does_nothing_by_default() {
+        let mut filter = ThreadedCrtFilter::new(CrtEffects {
+            scanlines: true,
+            ..Default::default()
+        });
+        filter.process(&image_of(&[WHITE]));
+        filter.flush();
+        assert_eq!(filter.image(), None);
+    }
+
+    #[test]
+    fn threaded_filter_processes_frames() {
+        let mut filter = ThreadedCrtFilter::new(CrtEffects {
+            phosphor: true,
+            ..Default::default()
+        });
+        filter.process(&image_of(&[WHITE, BLACK]));
+        filter.flush();
+        assert_eq!(filter.image(), Some(&image_of(&[WHITE, BLACK])));
+
+        filter.process(&image_of(&[BLACK, RED]));
+        filter.flush();
+        assert_eq!(
+            filter.image(),
+            Some(&image_of(&[Rgba([127, 127, 127, 255]), RED]))
+        );
+    }
+
+    #[test]
+    fn threaded_filter_skips_frames_when_busy() {
+        let mut filter = ThreadedCrtFilter::new(CrtEffects {
+            color_bleeding: true,
+            ..Default::default()
+        });
+        for _ in 0..10 {
+            filter.process(&image_of(&[RED]));
+        }
+        assert!(filter.worker.as_ref().unwrap().frames_in_flight <= MAX_FRAMES_IN_FLIGHT);
+        filter.flush();
+        assert_eq!(filter.image(), Some(&image_of(&[RED])));
+    }
+
+    #[test]
+    fn color_bleeding_smears_chroma() {
+        let gray = Rgba([60, 60, 60, 255]);
+        let mut filter = CrtFilter::new(CrtEffects {
+            color_bleeding: true,
+            ..Default::default()
+        });
+        filter.process(&image_of(&[gray, RED, gray]));
+        let image = filter.image().unwrap();
+
+        // The red pixel loses some of its saturation...
+        let [r, g, b, _] = image.get_pixel(1, 0).0;
+        assert!(r < RED[0] && g > 0 && b > 0, "{:?}", image.get_pixel(1, 0));
+        // ...and its neighbors turn reddish, even though their luminance
+        // doesn't change.
+        for x in [0, 2] {
+            let pixel = image.get_pixel(x, 0);
+            assert!(pixel[0] > gray[0] && pixel[1] < gray[1], "{:?}", pixel);
+            assert!((to_yiq(pixel)[0] - to_yiq(&gray)[0]).abs() < 1.0);
+        }
+    }
+}
@@ -1,12 +1,15 @@
 use crate::debugger::dap_types::MessageEnvelope;
+use crate::debugger::dap_types::ResponseEnvelope;
 use crate::debugger::protocol::raw_messages;
 use crate::debugger::protocol::send_raw_message;
 use crate::debugger::protocol::ProtocolError;
 use crate::debugger::Message;
 use crate::debugger::Request;
+use std::cell::Cell;
 use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::error::Error;
+use std::io;
 use std::io::BufReader;
 use std::io::Read;
 use std::io::Write;
@@ -38,10 +41,10 @@ pub trait DebugAdapter {
 ///
 /// One important limitation is that only a single TCP connection is allowed at
 /// any given time, but connecting with two debuggers at once would be a bad
-/// idea anyway.
+/// idea anyway. Clients can disconnect and connect again, though; each
+/// connection starts a new debugging session.
 pub struct TcpDebugAdapter {
-    writer_command_sender: mpsc::Sender<WriterThreadCommand>,
-    message_receiver: mpsc::Receiver<MessageEnvelope>,
+    channels: ThreadChannels,
 }
 
 impl TcpDebugAdapter {
@@ -50,18 +53,120 @@ impl TcpDebugAdapter {
         let writer_command_sender = spawn_writer_thread();
         let message_receiver = spawn_reader_thread(port, writer_command_sender.clone());
         Self {
-            writer_command_sender,
-            message_receiver,
+            channels: ThreadChannels::new(writer_command_sender, message_receiver),
         }
     }
 }
 
 impl DebugAdapter for TcpDebugAdapter {
     fn try_receive_message(&self) -> DebugAdapterResult<MessageEnvelope> {
-        self.message_receiver.try_recv().map_err(|e| e.into())
+        self.channels.try_receive_message()
+    }
+
+    fn send_message(&self, message: MessageEnvelope) -> DebugAdapterResult<()> {
+        self.channels.send_message(message)
+    }
+
+    fn disconnect(&self) -> DebugAdapterResult<()> {
+        self.channels.disconnect()
+    }
+}
+
+/// Uses Debug Adapter Protocol over the standard input and output, which is
+/// how many debugger clients launch their debug adapters. Since the standard
+/// output carries protocol messages, diagnostics need to go to the standard
+/// error. The debugging session lasts until the standard input is closed.
+pub struct StdioDebugAdapter {
+    channels: ThreadChannels,
+}
+
+impl StdioDebugAdapter {
+    pub fn new() -> Self {
+        let writer_command_sender = spawn_writer_thread();
+        writer_command_sender
+            .send(WriterThreadCommand::Connect(Box::new(io::stdout())))
+            .expect("Unable to connect the debugger writer thread");
+        let (tx, rx) = mpsc::channel();
+        let writer_command_sender_for_reader = writer_command_sender.clone();
+        thread::Builder::new()
+            .name("debugger reader thread".into())
+            .spawn(move || {
+                if let Err(e) = handle_session(io::stdin(), &writer_command_sender_for_reader, &tx)
+                {
+                    eprintln!("Debugger input error: {}", e);
+                }
+            })
+            .expect("Unable to start the debugger reader thread");
+        Self {
+            channels: ThreadChannels::new(writer_command_sender, rx),
+        }
+    }
+}
+
+impl Default for StdioDebugAdapter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DebugAdapter for StdioDebugAdapter {
+    fn try_receive_message(&self) -> DebugAdapterResult<MessageEnvelope> {
+        self.channels.try_receive_message()
+    }
+
+    fn send_message(&self, message: MessageEnvelope) -> DebugAdapterResult<()> {
+        self.channels.send_message(message)
+    }
+
+    fn disconnect(&self) -> DebugAdapterResult<()> {
+        self.channels.disconnect()
+    }
+}
+
+/// Sequence number of a `disconnect` request that is issued on behalf of a
+/// client that has closed the connection without ending the debugging session.
+/// Clients number their messages starting from 1.
+const CONNECTION_CLOSED_SEQ: i64 = 0;
+
+/// Channels used to communicate with the reader and writer threads.
+struct ThreadChannels {
+    writer_command_sender: mpsc::Sender<WriterThreadCommand>,
+    message_receiver: mpsc::Receiver<MessageEnvelope>,
+    /// Set when the connection has been closed by the client, and the reader
+    /// thread has already disconnected the writer.
+    connection_closed: Cell<bool>,
+}
+
+impl ThreadChannels {
+    fn new(
+        writer_command_sender: mpsc::Sender<WriterThreadCommand>,
+        message_receiver: mpsc::Receiver<MessageEnvelope>,
+    ) -> Self {
+        Self {
+            writer_command_sender,
+            message_receiver,
+            connection_closed: Cell::new(false),
+        }
+    }
+
+    fn try_receive_message(&self) -> DebugAdapterResult<MessageEnvelope> {
+        let message = self.message_receiver.try_recv()?;
+        if message.seq == CONNECTION_CLOSED_SEQ {
+            self.connection_closed.set(true);
+        }
+        Ok(message)
     }
 
     fn send_message(&self, message: MessageEnvelope) -> DebugAdapterResult<()> {
+        if let Message::Response(ResponseEnvelope {
+            request_seq: CONNECTION_CLOSED_SEQ,
+            ..
+        }) = message.message
+        {
+            // There's nobody to respond to. Besides, the writer may already
+            // be connected to another client.
+            return Ok(());
+        }
         self.writer_command_sender
             .send(WriterThreadCommand::SendMessage(message))
             .map_err(|e| e.into())
@@ -71,12 +176,30 @@ impl DebugAdapter for TcpDebugAdapter {
     /// easy way to disconnect both ends of the connection, so let's just hope
     /// that the remote side closes the other one.
     fn disconnect(&self) -> DebugAdapterResult<()> {
+        if self.connection_closed.take() {
+            return Ok(());
+        }
         self.writer_command_sender
             .send(WriterThreadCommand::Disconnect)?;
         Ok(())
     }
 }
 
+/// Allows choosing the debug adapter at runtime.
+impl<T: DebugAdapter + ?Sized> DebugAdapter for Box<T> {
+    fn try_receive_message(&self) -> DebugAdapterResult<MessageEnvelope> {
+        (**self).try_receive_message()
+    }
+
+    fn send_message(&self, message: MessageEnvelope) -> DebugAdapterResult<()> {
+        (**self).send_message(message)
+    }
+
+    fn disconnect(&self) -> DebugAdapterResult<()> {
+        (**self).disconnect()
+    }
+}
+
 pub type DebugAdapterResult<T> = Result<T, DebugAdapterError>;
 
 #[derive(thiserror::Error, Debug)]
@@ -122,9 +245,30 @@ fn handle_connection(
     incoming_message_sender: &mpsc::Sender<MessageEnvelope>,
 ) -> Result<(), Box<dyn Error>> {
     let connection_for_writer = connection.try_clone()?;
-    writer_command_sender.send(WriterThreadCommand::Connect(connection_for_writer))?;
-    handle_input(connection, &incoming_message_sender)?;
+    writer_command_sender.send(WriterThreadCommand::Connect(Box::new(
+        connection_for_writer,
+    )))?;
+    handle_session(connection, writer_command_sender, incoming_message_sender)
+}
+
+/// Handles the input of a single debugging session and disconnects the writer
+/// once the input ends. If the client hasn't ended the session itself, a
+/// `disconnect` request is issued on its behalf, so that the machine doesn't
+/// stay paused with nobody to resume it.
+fn handle_session(
+    input: impl Read,
+    writer_command_sender: &mpsc::Sender<WriterThreadCommand>,
+    incoming_message_sender: &mpsc::Sender<MessageEnvelope>,
+) -> Result<(), Box<dyn Error>> {
+    let result = handle_input(input, incoming_message_sender);
     writer_command_sender.send(WriterThreadCommand::Disconnect)?;
+    if !matches!(result, Ok(true)) {
+        incoming_message_sender.send(MessageEnvelope {
+            seq: CONNECTION_CLOSED_SEQ,
+            message: Message::Request(Request::Disconnect(None)),
+        })?;
+    }
+    result?;
     Ok(())
 }
 
@@ -140,23 +284,27 @@ enum InputHandlingError {
     SendError(#[from] SendError<MessageEnvelope>),
 }
 
+/// Forwards incoming messages to the main thread. Returns `true` if the client
+/// has ended the session with a `disconnect` request.
 fn handle_input(
     input: impl Read,
     sender: &mpsc::Sender<MessageEnvelope>,
-) -> Result<(), InputHandlingError> {
+) -> Result<bool, InputHandlingError> {
+    let mut disconnected = false;
     let mut reader = BufReader::new(input);
     for raw_message_result in raw_messages(&mut reader) {
         let raw_message = raw_message_result?;
         // println!("-> {}", std::str::from_utf8(&raw_message).unwrap());
-        let message = serde_json::from_slice(&raw_message).map_err(|e| {
+        let message: MessageEnvelope = serde_json::from_slice(&raw_message).map_err(|e| {
             InputHandlingError::ParseError(e, String::from_utf8(raw_message).unwrap())
         })?;
+        disconnected = matches!(message.message, Message::Request(Request::Disconnect(_)));
         sender.send(message)?;
     }
-    Ok(())
+    Ok(disconnected)
 }
 
-pub enum WriterThreadCommand<W: Write = TcpStream> {
+pub enum WriterThreadCommand<W: Write = Box<dyn Write + Send>> {
     SendMessage(MessageEnvelope),
     Connect(W),
     Disconnect,
@@ -386,6 +534,86 @@ mod tests {
         assert_matches!(err, InputHandlingError::SendError(_));
     }
 
+    #[test]
+    fn session_ended_by_client() {
+        let (writer_tx, writer_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel();
+        let stream = read_session_dump();
+        handle_session(&stream[..], &writer_tx, &tx).unwrap();
+
+        rx.try_recv().unwrap(); // Ignore the initialization request.
+        assert_matches!(
+            rx.try_recv(),
+            Ok(MessageEnvelope {
+                message: Message::Request(Request::Disconnect(_)),
+                seq,
+            }) if seq != CONNECTION_CLOSED_SEQ
+        );
+        rx.try_recv().unwrap_err();
+        assert!(matches!(
+            writer_rx.try_recv(),
+            Ok(WriterThreadCommand::Disconnect)
+        ));
+    }
+
+    #[test]
+    fn session_ended_on_behalf_of_client() {
+        let (writer_tx, writer_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel();
+        let session_dump = read_session_dump();
+        // Cut the stream before the disconnect request.
+        let second_message_start = session_dump
+            .windows(15)
+            .rposition(|bytes| bytes == b"Content-Length:")
+            .unwrap();
+        handle_session(&session_dump[..second_message_start], &writer_tx, &tx).unwrap();
+
+        rx.try_recv().unwrap(); // Ignore the initialization request.
+        assert_matches!(
+            rx.try_recv(),
+            Ok(MessageEnvelope {
+                message: Message::Request(Request::Disconnect(None)),
+                seq: CONNECTION_CLOSED_SEQ,
+            })
+        );
+        assert!(matches!(
+            writer_rx.try_recv(),
+            Ok(WriterThreadCommand::Disconnect)
+        ));
+    }
+
+    #[test]
+    fn ignores_sessions_ended_on_behalf_of_client() {
+        let (writer_tx, writer_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel();
+        let channels = ThreadChannels::new(writer_tx, rx);
+        tx.send(MessageEnvelope {
+            seq: CONNECTION_CLOSED_SEQ,
+            message: Message::Request(Request::Disconnect(None)),
+        })
+        .unwrap();
+        channels.try_receive_message().unwrap();
+        channels
+            .send_message(MessageEnvelope {
+                seq: 1,
+                message: Message::Response(ResponseEnvelope {
+                    request_seq: CONNECTION_CLOSED_SEQ,
+                    success: true,
+                    response: Response::Disconnect,
+                }),
+            })
+            .unwrap();
+        channels.disconnect().unwrap();
+        assert!(writer_rx.try_recv().is_err());
+
+        // The next session is disconnected as usual.
+        channels.disconnect().unwrap();
+        assert!(matches!(
+            writer_rx.try_recv(),
+            Ok(WriterThreadCommand::Disconnect)
+        ));
+    }
+
     #[test]
     fn write_thread_handles_commands() {
         use WriterThreadCommand::*;
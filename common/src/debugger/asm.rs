@@ -0,0 +1,249 @@
+//! A tiny, line-oriented 6502 assembler for patching code of a running
+//! program. Each line contains a single instruction in the usual syntax (`LDA
+//! #$12`, `STA (ptr),Y`, `BNE loop`); everything after a `;` is a comment.
+//! Operands are expressions (see [`crate::debugger::expression`]), so they may
+//! refer to symbols, registers, and memory. There are no labels, directives,
+//! or macros.
+
+use crate::debugger::disasm::is_mnemonic;
+use crate::debugger::disasm::opcode;
+use crate::debugger::disasm::AddressingMode;
+use crate::debugger::expression;
+use crate::debugger::symbols::SymbolTable;
+use ya6502::cpu::MachineInspector;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+#[error("Line {line}: {message}")]
+pub struct AssemblyError {
+    /// 1-based number of the offending line.
+    pub line: usize,
+    pub message: String,
+}
+
+/// Assembles the source code, assuming that it will be placed at a given
+/// address. Returns machine code of all instructions, one after another.
+pub fn assemble(
+    inspector: &(impl MachineInspector + ?Sized),
+    symbols: &SymbolTable,
+    address: u16,
+    source: &str,
+) -> Result<Vec<u8>, AssemblyError> {
+    let mut bytes = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let code = line.split(';').next().unwrap_or_default().trim();
+        if code.is_empty() {
+            continue;
+        }
+        let instruction_address = address.wrapping_add(bytes.len() as u16);
+        let instruction = assemble_instruction(inspector, symbols, instruction_address, code)
+            .map_err(|message| AssemblyError {
+                line: index + 1,
+                message,
+            })?;
+        bytes.extend(instruction);
+    }
+    return Ok(bytes);
+}
+
+fn assemble_instruction(
+    inspector: &(impl MachineInspector + ?Sized),
+    symbols: &SymbolTable,
+    address: u16,
+    code: &str,
+) -> Result<Vec<u8>, String> {
+    let (mnemonic, operand) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+    let mnemonic = mnemonic.to_ascii_uppercase();
+    if !is_mnemonic(&mnemonic) {
+        return Err(format!("Unknown instruction: {}", mnemonic));
+    }
+    let operand: String = operand.chars().filter(|c| !c.is_whitespace()).collect();
+    let evaluate = |text: &str| -> Result<i64, String> {
+        expression::parse(text, symbols)
+            .map_err(|e| e.to_string())?
+            .evaluate(inspector)
+            .map_err(|e| e.to_string())
+    };
+    let encode = |addressing_mode: AddressingMode, argument: &[u8]| -> Result<Vec<u8>, String> {
+        let opcode = opcode(&mnemonic, addressing_mode)
+            .ok_or_else(|| format!("Invalid addressing mode for {}", mnemonic))?;
+        return Ok([&[opcode], argument].concat());
+    };
+    let has_mode = |addressing_mode| opcode(&mnemonic, addressing_mode).is_some();
+    let upper_operand = operand.to_ascii_uppercase();
+
+    if operand.is_empty() {
+        if has_mode(AddressingMode::Implied) {
+            return encode(AddressingMode::Implied, &[]);
+        }
+        return encode(AddressingMode::Accumulator, &[]);
+    }
+    if upper_operand == "A" && has_mode(AddressingMode::Accumulator) {
+        return encode(AddressingMode::Accumulator, &[]);
+    }
+    if let Some(value) = operand.strip_prefix('#') {
+        return encode(AddressingMode::Immediate, &[to_byte(evaluate(value)?)?]);
+    }
+    if has_mode(AddressingMode::Relative) {
+        let offset = evaluate(&operand)? - (address as i64 + 2);
+        if !(-128..=127).contains(&offset) {
+            return Err(format!("Branch target out of range ({} bytes)", offset));
+        }
+        return encode(AddressingMode::Relative, &[offset as u8]);
+    }
+    if upper_operand.starts_with('(') {
+        if let Some(pointer) = upper_operand.strip_suffix(",X)") {
+            let value = evaluate(&operand[1..pointer.len()])?;
+            return encode(AddressingMode::ZeroPageXIndirect, &[to_zero_page(value)?]);
+        }
+        if let Some(pointer) = upper_operand.strip_suffix("),Y") {
+            let value = evaluate(&operand[1..pointer.len()])?;
+            return encode(AddressingMode::ZeroPageIndirectY, &[to_zero_page(value)?]);
+        }
+        if upper_operand.ends_with(')') && has_mode(AddressingMode::Indirect) {
+            let value = evaluate(&operand[1..operand.len() - 1])?;
+            return encode(AddressingMode::Indirect, &to_word(value)?);
+        }
+    }
+    let (value, zero_page_mode, absolute_mode) =
+        if let Some(base) = upper_operand.strip_suffix(",X") {
+            (
+                evaluate(&operand[..base.len()])?,
+                AddressingMode::ZeroPageIndexedX,
+                AddressingMode::AbsoluteIndexedX,
+            )
+        } else if let Some(base) = upper_operand.strip_suffix(",Y") {
+            (
+                evaluate(&operand[..base.len()])?,
+                AddressingMode::ZeroPageIndexedY,
+                AddressingMode::AbsoluteIndexedY,
+            )
+        } else {
+            (
+                evaluate(&operand)?,
+                AddressingMode::ZeroPage,
+                AddressingMode::Absolute,
+            )
+        };
+    if (0..=0xFF).contains(&value) && has_mode(zero_page_mode) {
+        return encode(zero_page_mode, &[value as u8]);
+    }
+    return encode(absolute_mode, &to_word(value)?);
+}
+
+/// Converts an immediate value, which may also be given as a negative number.
+fn to_byte(value: i64) -> Result<u8, String> {
+    if !(-0x80..=0xFF).contains(&value) {
+        return Err(format!("Value out of range: {}", value));
+    }
+    return Ok(value as u8);
+}
+
+fn to_zero_page(value: i64) -> Result<u8, String> {
+    if !(0..=0xFF).contains(&value) {
+        return Err(format!("Zero page address out of range: {}", value));
+    }
+    return Ok(value as u8);
+}
+
+fn to_word(value: i64) -> Result<[u8; 2], String> {
+    if !(0..=0xFFFF).contains(&value) {
+        return Err(format!("Address out of range: {}", value));
+    }
+    return Ok((value as u16).to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ya6502::cpu::MockMachineInspector;
+
+    fn assemble_at(address: u16, source: &str) -> Result<Vec<u8>, AssemblyError> {
+        let mut inspector = MockMachineInspector::new();
+        inspector.expect_reg_x().return_const(7);
+        let symbols = SymbolTable::from([("ptr".to_string(), 0x80), ("VSYNC".to_string(), 0x00)]);
+        super::assemble(&inspector, &symbols, address, source)
+    }
+
+    fn assemble(source: &str) -> Vec<u8> {
+        assemble_at(0xF000, source).unwrap()
+    }
+
+    fn error(source: &str) -> String {
+        assemble_at(0xF000, source).unwrap_err().message
+    }
+
+    #[test]
+    fn addressing_modes() {
+        assert_eq!(assemble("nop"), [0xEA]);
+        assert_eq!(assemble("ASL"), [0x0A]);
+        assert_eq!(assemble("ror a"), [0x6A]);
+        assert_eq!(assemble("LDA #$12"), [0xA9, 0x12]);
+        assert_eq!(assemble("LDA $12"), [0xA5, 0x12]);
+        assert_eq!(assemble("LDA $1234"), [0xAD, 0x34, 0x12]);
+        assert_eq!(assemble("LDA $12,X"), [0xB5, 0x12]);
+        assert_eq!(assemble("LDA $1234,x"), [0xBD, 0x34, 0x12]);
+        assert_eq!(assemble("LDX $12,Y"), [0xB6, 0x12]);
+        assert_eq!(assemble("LDA $1234, Y"), [0xB9, 0x34, 0x12]);
+        assert_eq!(assemble("LDA ($12,X)"), [0xA1, 0x12]);
+        assert_eq!(assemble("LDA ( $12 ), Y"), [0xB1, 0x12]);
+        assert_eq!(assemble("JMP ($1234)"), [0x6C, 0x34, 0x12]);
+        assert_eq!(assemble("JMP $1234"), [0x4C, 0x34, 0x12]);
+    }
+
+    #[test]
+    fn zero_page_only_when_available() {
+        // There's no zero page, Y-indexed LDA.
+        assert_eq!(assemble("LDA $12,Y"), [0xB9, 0x12, 0x00]);
+        assert_eq!(assemble("JSR $0012"), [0x20, 0x12, 0x00]);
+    }
+
+    #[test]
+    fn branches() {
+        assert_eq!(assemble_at(0xF000, "BNE $F010"), Ok(vec![0xD0, 0x0E]));
+        assert_eq!(assemble_at(0xF010, "BEQ $F000"), Ok(vec![0xF0, 0xEE]));
+        assert_eq!(assemble_at(0xF000, "BCC $F081"), Ok(vec![0x90, 0x7F]));
+        assert_eq!(
+            assemble_at(0xF000, "BCC $F082").unwrap_err().message,
+            "Branch target out of range (128 bytes)"
+        );
+    }
+
+    #[test]
+    fn multiple_lines() {
+        assert_eq!(
+            assemble_at(
+                0xF000,
+                "LDX #0 ; counter\n\n  INX\n  BNE $F002\n; done\nRTS"
+            ),
+            Ok(vec![0xA2, 0x00, 0xE8, 0xD0, 0xFD, 0x60]),
+        );
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(assemble("STA VSYNC"), [0x85, 0x00]);
+        assert_eq!(assemble("LDA (ptr),Y"), [0xB1, 0x80]);
+        assert_eq!(assemble("LDA ptr+1"), [0xA5, 0x81]);
+        assert_eq!(assemble("LDA #-1"), [0xA9, 0xFF]);
+        assert_eq!(assemble("LDA #x*2"), [0xA9, 14]);
+        assert_eq!(assemble("LDA #$1234 >> 8"), [0xA9, 0x12]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("FOO"), "Unknown instruction: FOO");
+        assert_eq!(error("STA #3"), "Invalid addressing mode for STA");
+        assert_eq!(error("LDX $12,X"), "Invalid addressing mode for LDX");
+        assert_eq!(error("LDA #256"), "Value out of range: 256");
+        assert_eq!(error("LDA $10000"), "Address out of range: 65536");
+        assert_eq!(error("LDA ($100),Y"), "Zero page address out of range: 256");
+        assert_eq!(error("LDA foo"), "Unknown identifier: foo");
+        assert_eq!(
+            assemble_at(0xF000, "NOP\nLDA #"),
+            Err(AssemblyError {
+                line: 2,
+                message: "Unexpected end of expression".to_string()
+            })
+        );
+    }
+}
@@ -1,34 +1,77 @@
+use crate::debugger::expression::Expression;
+use crate::debugger::expression::ParseError;
+use crate::debugger::expression::ParseResult;
+use crate::debugger::history::History;
 use bounded_vec_deque::BoundedVecDeque;
 use serde::Deserialize;
 use serde::Serialize;
 use std::mem::replace;
+use std::rc::Rc;
 use ya6502::cpu::opcodes;
+use ya6502::cpu::Interrupt;
 use ya6502::cpu::MachineInspector;
+use ya6502::cpu::MemoryAccess;
+
+/// Number of instructions that can be reverted by stepping back.
+const HISTORY_SIZE: usize = 100_000;
 
 #[derive(PartialEq)]
 enum RunMode {
     Running,
     Stopped,
     SteppingIn,
-    SteppingOut { target_stack_depth: usize },
+    SteppingOut {
+        target_stack_depth: usize,
+    },
+    /// Runs until the first instruction after a machine reset and stops there.
+    StartingUp,
 }
 
 /// The actual logic of the debugger, free of all of the communication noise.
 pub struct DebuggerCore {
     run_mode: RunMode,
     last_stop_reason: Option<StopReason>,
-    instruction_breakpoints: Vec<u16>,
-    /// Stack frames, captured by recognizing JSR/RTS instructions. Note that
+    instruction_breakpoints: Vec<InstructionBreakpoint>,
+    data_breakpoints: Vec<DataBreakpoint>,
+    /// Set when a data breakpoint is hit. Since memory accesses happen in the
+    /// middle of an instruction, we only stop once it's finished.
+    data_breakpoint_hit: bool,
+    event_breakpoints: Vec<EventBreakpoint>,
+    /// Set when an event breakpoint is hit. Just like with data breakpoints,
+    /// we only stop once the current instruction (or interrupt sequence) is
+    /// finished.
+    event_breakpoint_hit: bool,
+    /// The raster line reported by the last call to
+    /// [`DebuggerCore::update_raster`].
+    raster_line: Option<u32>,
+    /// Stack frames, captured by recognizing JSR/RTS instructions and
+    /// interrupts (which return with RTI). Note that
     /// this is not a simple vector, but a bounded deque, since we can't
     /// guarantee that the underlying program is sane and won't overflow the
     /// stack. An edge case of consistently overflowing stack would cause a
     /// dramatic memory leak here, and since the stack entries would be
     /// clobbered anyway, the bounded deque is the perfect structure here.
     ///
-    /// TODO: Support stepping out of interrupt handlers.
-    stack_frames: BoundedVecDeque<StackFrame>,
+    /// The frames are shared with the history snapshots, and they only get
+    /// copied when a subroutine is entered or left.
+    stack_frames: Rc<BoundedVecDeque<StackFrame>>,
+    /// Address of the last instruction started. Becomes the program counter of
+    /// the current stack frame once it gets covered by another one.
+    instruction_pc: u16,
+    will_enter_subroutine: bool,
+    will_return_from_subroutine: bool,
+    /// Set while the CPU performs an interrupt sequence. The interrupt handler
+    /// gets its own stack frame, so that it can be stepped over or out of.
+    will_enter_interrupt: bool,
+    history: History<CallStackState>,
+}
+
+/// Part of the debugger state that needs to be restored when stepping back.
+struct CallStackState {
+    stack_frames: Rc<BoundedVecDeque<StackFrame>>,
     will_enter_subroutine: bool,
     will_return_from_subroutine: bool,
+    will_enter_interrupt: bool,
 }
 
 impl DebuggerCore {
@@ -37,53 +80,119 @@ impl DebuggerCore {
             run_mode: RunMode::Stopped,
             last_stop_reason: None,
             instruction_breakpoints: vec![],
-            stack_frames: BoundedVecDeque::new(256),
+            data_breakpoints: vec![],
+            data_breakpoint_hit: false,
+            event_breakpoints: vec![],
+            event_breakpoint_hit: false,
+            raster_line: None,
+            stack_frames: Rc::new(BoundedVecDeque::new(256)),
+            instruction_pc: 0,
             will_enter_subroutine: true,
             will_return_from_subroutine: false,
+            will_enter_interrupt: false,
+            history: History::new(HISTORY_SIZE),
         }
     }
 
-    pub fn set_instruction_breakpoints(&mut self, breakpoints: Vec<u16>) {
+    pub fn set_instruction_breakpoints(&mut self, breakpoints: Vec<InstructionBreakpoint>) {
         self.instruction_breakpoints = breakpoints;
     }
 
+    pub fn set_data_breakpoints(&mut self, breakpoints: Vec<DataBreakpoint>) {
+        self.data_breakpoints = breakpoints;
+    }
+
+    pub fn set_event_breakpoints(&mut self, breakpoints: Vec<EventBreakpoint>) {
+        self.event_breakpoints = breakpoints;
+    }
+
     /// Reads the machine state. Expected to be called after the CPU is
     /// initialized, and then after every single cycle.
-    pub fn update(&mut self, inspector: &impl MachineInspector) {
+    pub fn update(&mut self, inspector: &(impl MachineInspector + ?Sized)) {
+        if let Some(access) = inspector.last_memory_access() {
+            if self.run_mode != RunMode::Stopped
+                && self.data_breakpoints.iter().any(|bp| bp.matches(access))
+            {
+                self.data_breakpoint_hit = true;
+            }
+        }
+        if let Some(interrupt) = inspector.current_interrupt() {
+            self.check_event(EventBreakpoint::Interrupt(interrupt));
+            if !self.will_enter_interrupt {
+                // IRQ and NMI are handled instead of executing the instruction
+                // that we saw at the last instruction start, so it won't enter
+                // or leave a subroutine.
+                self.will_enter_subroutine = false;
+                self.will_return_from_subroutine = false;
+                self.will_enter_interrupt = true;
+            }
+        }
         if inspector.at_instruction_start() {
-            if self.will_enter_subroutine {
-                self.stack_frames.push_back(StackFrame {
+            if self.will_return_from_subroutine {
+                Rc::make_mut(&mut self.stack_frames).pop_back();
+                self.will_return_from_subroutine = false;
+            }
+            if self.will_enter_subroutine || self.will_enter_interrupt {
+                let stack_frames = Rc::make_mut(&mut self.stack_frames);
+                // Remember where the covered frame has been left.
+                if let Some(current_frame) = stack_frames.back_mut() {
+                    current_frame.pc = self.instruction_pc;
+                }
+                stack_frames.push_back(StackFrame {
                     entry: inspector.reg_pc(),
                     pc: 0,
                 });
                 self.will_enter_subroutine = false;
+                self.will_enter_interrupt = false;
             }
-            if self.will_return_from_subroutine {
-                self.stack_frames.pop_back();
-                self.will_return_from_subroutine = false;
-            }
+            self.instruction_pc = inspector.reg_pc();
             let opcode = inspector.inspect_memory(inspector.reg_pc());
             match opcode {
                 opcodes::JSR => {
                     self.will_enter_subroutine = true;
-                    if let Some(current_frame) = self.stack_frames.back_mut() {
-                        current_frame.pc = inspector.reg_pc();
-                    }
                 }
-                opcodes::RTS => {
+                opcodes::RTS | opcodes::RTI => {
                     self.will_return_from_subroutine = true;
                 }
                 _ => {}
             }
+        }
+        self.history.update(inspector, || CallStackState {
+            stack_frames: self.stack_frames.clone(),
+            will_enter_subroutine: self.will_enter_subroutine,
+            will_return_from_subroutine: self.will_return_from_subroutine,
+            will_enter_interrupt: self.will_enter_interrupt,
+        });
+        if inspector.at_instruction_start() {
+            if self.data_breakpoint_hit {
+                self.stop(StopReason::DataBreakpoint);
+                return;
+            }
+            if self.event_breakpoint_hit {
+                self.stop(StopReason::FunctionBreakpoint);
+                return;
+            }
             match self.run_mode {
                 RunMode::Running => {
-                    if self.instruction_breakpoints.contains(&inspector.reg_pc()) {
+                    let pc = inspector.reg_pc();
+                    let mut should_stop = false;
+                    // Note: we need to visit all breakpoints at this address
+                    // to keep their hit counts up to date.
+                    for breakpoint in &mut self.instruction_breakpoints {
+                        if breakpoint.address == pc && breakpoint.hit(inspector) {
+                            should_stop = true;
+                        }
+                    }
+                    if should_stop {
                         self.stop(StopReason::Breakpoint);
                     }
                 }
                 RunMode::SteppingIn => self.stop(StopReason::Step),
+                RunMode::StartingUp => self.stop(StopReason::Entry),
                 RunMode::SteppingOut { target_stack_depth } => {
-                    if self.stack_frames.len() == target_stack_depth {
+                    // Note that we can end up below the target depth, for
+                    // example when stepping over an RTS instruction.
+                    if self.stack_frames.len() <= target_stack_depth {
                         self.stop(StopReason::Step);
                     }
                 }
@@ -92,6 +201,25 @@ impl DebuggerCore {
         }
     }
 
+    /// Reads the beam position of the machine. Expected to be called after
+    /// every machine tick; `frame_complete` tells whether the tick has
+    /// completed a frame.
+    pub fn update_raster(&mut self, line: u32, frame_complete: bool) {
+        if frame_complete {
+            self.check_event(EventBreakpoint::Frame);
+        }
+        if self.raster_line != Some(line) {
+            self.raster_line = Some(line);
+            self.check_event(EventBreakpoint::RasterLine(line));
+        }
+    }
+
+    fn check_event(&mut self, event: EventBreakpoint) {
+        if self.run_mode != RunMode::Stopped && self.event_breakpoints.contains(&event) {
+            self.event_breakpoint_hit = true;
+        }
+    }
+
     pub fn stopped(&self) -> bool {
         self.run_mode == RunMode::Stopped
     }
@@ -105,8 +233,8 @@ impl DebuggerCore {
         replace(&mut self.last_stop_reason, None)
     }
 
-    pub fn stack_trace(&self, inspector: &impl MachineInspector) -> Vec<StackFrame> {
-        let mut frames: Vec<StackFrame> = self.stack_frames.clone().into_unbounded().into();
+    pub fn stack_trace(&self, inspector: &(impl MachineInspector + ?Sized)) -> Vec<StackFrame> {
+        let mut frames: Vec<StackFrame> = self.stack_frames.iter().cloned().collect();
         frames.reverse();
         if let Some(top_frame) = frames.first_mut() {
             top_frame.pc = inspector.reg_pc();
@@ -125,40 +253,115 @@ impl DebuggerCore {
     fn run(&mut self, mode: RunMode) {
         self.run_mode = mode;
         self.last_stop_reason = None;
+        self.data_breakpoint_hit = false;
+        self.event_breakpoint_hit = false;
     }
 
     pub fn pause(&mut self) {
         self.stop(StopReason::Pause);
     }
 
+    /// Stops the execution because the machine reported an error.
+    pub fn stop_on_error(&mut self) {
+        self.stop(StopReason::Exception);
+    }
+
+    /// Stops the execution without reporting a stop reason. Used when another
+    /// thread stops, since the whole machine stops with it.
+    pub fn hold(&mut self) {
+        self.run_mode = RunMode::Stopped;
+    }
+
     fn stop(&mut self, reason: StopReason) {
         self.run_mode = RunMode::Stopped;
+        self.data_breakpoint_hit = false;
+        self.event_breakpoint_hit = false;
         self.last_stop_reason = Some(reason);
     }
 
+    /// Prepares the core for a freshly reset machine: forgets the call stack
+    /// and the recorded history, and lets the machine run. If `stop_on_entry`
+    /// is set, the execution stops at the first instruction.
+    pub fn restart(&mut self, stop_on_entry: bool) {
+        Rc::make_mut(&mut self.stack_frames).clear();
+        self.will_enter_subroutine = true;
+        self.will_return_from_subroutine = false;
+        self.will_enter_interrupt = false;
+        self.history.clear();
+        self.run(if stop_on_entry {
+            RunMode::StartingUp
+        } else {
+            RunMode::Running
+        });
+    }
+
     pub fn step_into(&mut self) {
         self.run(RunMode::SteppingIn);
     }
 
-    pub fn step_over(&mut self, inspector: &impl MachineInspector) {
-        let pc = inspector.reg_pc();
-        let opcode = inspector.inspect_memory(pc);
-        // Note: Stepping over is only "special" when we perform a jump into a
-        // subroutine. Otherwise, it's the same as stepping in.
-        if opcode == opcodes::JSR {
-            self.run(RunMode::SteppingOut {
-                target_stack_depth: self.stack_frames.len(),
-            });
-        } else {
-            self.run(RunMode::SteppingIn);
-        };
+    /// Runs until the next instruction in the current stack frame, skipping
+    /// any subroutines called by the current instruction, as well as any
+    /// interrupt handlers executed in the meantime. If an interrupt happens
+    /// before the current instruction is executed, we stop at the same
+    /// instruction once the handler returns.
+    pub fn step_over(&mut self) {
+        self.run(RunMode::SteppingOut {
+            target_stack_depth: self.stack_frames.len(),
+        });
     }
 
     pub fn step_out(&mut self) {
         self.run(RunMode::SteppingOut {
-            target_stack_depth: self.stack_frames.len() - 1,
+            target_stack_depth: self.stack_frames.len().saturating_sub(1),
         });
     }
+
+    /// Brings the machine back to the start of the previous instruction.
+    pub fn step_back(&mut self, inspector: &mut (impl MachineInspector + ?Sized)) {
+        if !self.restore_previous_state(inspector) {
+            eprintln!("Reached the beginning of the recorded history");
+        }
+        self.stop(StopReason::Step);
+    }
+
+    /// Goes back in time until an instruction breakpoint is hit or the
+    /// recorded history ends. Hit conditions and data breakpoints are ignored.
+    pub fn reverse_continue(&mut self, inspector: &mut (impl MachineInspector + ?Sized)) {
+        while self.restore_previous_state(inspector) {
+            let pc = inspector.reg_pc();
+            let inspector = &*inspector;
+            if self
+                .instruction_breakpoints
+                .iter()
+                .any(|breakpoint| breakpoint.address == pc && breakpoint.condition_met(inspector))
+            {
+                self.stop(StopReason::Breakpoint);
+                return;
+            }
+        }
+        eprintln!("Reached the beginning of the recorded history");
+        self.stop(StopReason::Step);
+    }
+
+    fn restore_previous_state(&mut self, inspector: &mut (impl MachineInspector + ?Sized)) -> bool {
+        return match self.history.step_back(inspector) {
+            Some(state) => {
+                self.stack_frames = state.stack_frames.clone();
+                self.instruction_pc = inspector.reg_pc();
+                self.will_enter_subroutine = state.will_enter_subroutine;
+                self.will_return_from_subroutine = state.will_return_from_subroutine;
+                self.will_enter_interrupt = state.will_enter_interrupt;
+                true
+            }
+            None => false,
+        };
+    }
+
+    /// Forgets the recorded history. Called when the machine state is modified
+    /// by the user.
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -174,12 +377,191 @@ pub enum StopReason {
     Pause,
     Step,
     Breakpoint,
+    #[serde(rename = "data breakpoint")]
+    DataBreakpoint,
+    #[serde(rename = "function breakpoint")]
+    FunctionBreakpoint,
+    Exception,
+}
+
+/// Stops the execution when the CPU is about to execute an instruction at a
+/// given address, optionally only if a condition is met or the breakpoint has
+/// been hit a given number of times.
+#[derive(Debug, PartialEq, Clone)]
+pub struct InstructionBreakpoint {
+    pub address: u16,
+    condition: Option<Expression>,
+    hit_condition: Option<HitCondition>,
+    hit_count: u32,
+}
+
+impl InstructionBreakpoint {
+    pub fn new(address: u16) -> Self {
+        Self {
+            address,
+            condition: None,
+            hit_condition: None,
+            hit_count: 0,
+        }
+    }
+
+    pub fn with_condition(mut self, condition: Expression) -> Self {
+        self.condition = Some(condition);
+        return self;
+    }
+
+    pub fn with_hit_condition(mut self, hit_condition: HitCondition) -> Self {
+        self.hit_condition = Some(hit_condition);
+        return self;
+    }
+
+    /// Called when the CPU reaches the breakpoint address. Returns `true` if
+    /// the execution should stop. Only the hits that satisfy the condition
+    /// are counted. If the condition can't be evaluated, the breakpoint is
+    /// treated as hit, so that the user can inspect the problem.
+    fn hit(&mut self, inspector: &(impl MachineInspector + ?Sized)) -> bool {
+        if !self.condition_met(inspector) {
+            return false;
+        }
+        self.hit_count += 1;
+        return match &self.hit_condition {
+            Some(hit_condition) => hit_condition.matches(self.hit_count),
+            None => true,
+        };
+    }
+
+    fn condition_met(&self, inspector: &(impl MachineInspector + ?Sized)) -> bool {
+        if let Some(condition) = &self.condition {
+            match condition.evaluate(inspector) {
+                Ok(0) => return false,
+                Ok(_) => {}
+                Err(e) => eprintln!(
+                    "Unable to evaluate breakpoint condition at ${:04X}: {}",
+                    self.address, e
+                ),
+            }
+        }
+        return true;
+    }
+}
+
+/// Decides which hits of a breakpoint stop the execution. Parsed from strings
+/// like `5` (only the 5th hit), `>= 5` (5th and subsequent ones), or `% 5`
+/// (every 5th hit).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HitCondition {
+    Equal(u32),
+    Greater(u32),
+    GreaterOrEqual(u32),
+    Less(u32),
+    LessOrEqual(u32),
+    Multiple(u32),
+}
+
+impl HitCondition {
+    pub fn parse(text: &str) -> ParseResult<Self> {
+        let text = text.trim();
+        let operators: [(&str, fn(u32) -> Self); 7] = [
+            (">=", Self::GreaterOrEqual),
+            ("<=", Self::LessOrEqual),
+            ("==", Self::Equal),
+            (">", Self::Greater),
+            ("<", Self::Less),
+            ("%", Self::Multiple),
+            ("", Self::Equal),
+        ];
+        let (operator, constructor) = operators
+            .iter()
+            .find(|(operator, _)| text.starts_with(operator))
+            .unwrap();
+        let count_text = text[operator.len()..].trim();
+        return match count_text.parse() {
+            Ok(0) if *operator == "%" => Err(ParseError::new("Hit count modulo can't be 0")),
+            Ok(count) => Ok(constructor(count)),
+            Err(_) => Err(ParseError::new(format!("Invalid hit condition: {}", text))),
+        };
+    }
+
+    fn matches(&self, hit_count: u32) -> bool {
+        match *self {
+            Self::Equal(n) => hit_count == n,
+            Self::Greater(n) => hit_count > n,
+            Self::GreaterOrEqual(n) => hit_count >= n,
+            Self::Less(n) => hit_count < n,
+            Self::LessOrEqual(n) => hit_count <= n,
+            Self::Multiple(n) => hit_count % n == 0,
+        }
+    }
+}
+
+/// Stops the execution when the CPU accesses a given memory address.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DataBreakpoint {
+    pub address: u16,
+    pub access_type: DataBreakpointAccessType,
+}
+
+impl DataBreakpoint {
+    fn matches(&self, access: MemoryAccess) -> bool {
+        use DataBreakpointAccessType::*;
+        match (access, self.access_type) {
+            (MemoryAccess::Read(address), Read | ReadWrite) => address == self.address,
+            (MemoryAccess::Write(address), Write | ReadWrite) => address == self.address,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum DataBreakpointAccessType {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// Stops the execution when something happens in the machine, regardless of
+/// the executed code. The execution stops at the first instruction after the
+/// event.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EventBreakpoint {
+    /// Stops at the first instruction of an interrupt handler.
+    Interrupt(Interrupt),
+    /// Stops once a frame is complete.
+    Frame,
+    /// Stops once the beam enters a given raster line. Line numbers are
+    /// specific to the video chip of a given machine.
+    RasterLine(u32),
+}
+
+impl EventBreakpoint {
+    /// Parses an event name: `brk`, `irq`, `nmi`, `frame`, or `line N`.
+    pub fn parse(text: &str) -> ParseResult<Self> {
+        let text = text.trim().to_lowercase();
+        return match text.as_str() {
+            "brk" => Ok(Self::Interrupt(Interrupt::Brk)),
+            "irq" => Ok(Self::Interrupt(Interrupt::Irq)),
+            "nmi" => Ok(Self::Interrupt(Interrupt::Nmi)),
+            "frame" => Ok(Self::Frame),
+            _ => match text.strip_prefix("line") {
+                Some(line) => match line.trim().parse() {
+                    Ok(line) => Ok(Self::RasterLine(line)),
+                    Err(_) => Err(ParseError::new(format!("Invalid raster line: {}", line))),
+                },
+                None => Err(ParseError::new(format!("Unknown event: {}", text))),
+            },
+        };
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::debugger::expression;
+    use crate::debugger::symbols::SymbolTable;
+    use ya6502::cpu::CompositeMachine;
     use ya6502::cpu::Cpu;
+    use ya6502::cpu::Register;
     use ya6502::cpu_with_code;
     use ya6502::memory::Ram;
 
@@ -321,16 +703,16 @@ mod tests {
         let mut dc = DebuggerCore::new();
         dc.update(&cpu);
 
-        dc.step_over(&cpu);
+        dc.step_over();
         tick_while_running(&mut dc, &mut cpu);
         assert_eq!(cpu.reg_pc(), 0xF002);
         assert_eq!(dc.last_stop_reason(), Some(StopReason::Step));
 
-        dc.step_over(&cpu);
+        dc.step_over();
         tick_while_running(&mut dc, &mut cpu);
         assert_eq!(cpu.reg_pc(), 0xF004);
 
-        dc.step_over(&cpu);
+        dc.step_over();
         tick_while_running(&mut dc, &mut cpu);
         assert_eq!(cpu.reg_pc(), 0xF007);
     }
@@ -349,11 +731,11 @@ mod tests {
         let mut dc = DebuggerCore::new();
         dc.update(&cpu);
 
-        dc.step_over(&cpu);
+        dc.step_over();
         tick_while_running(&mut dc, &mut cpu);
         assert_eq!(cpu.reg_pc(), 0xF003);
 
-        dc.step_over(&cpu);
+        dc.step_over();
         tick_while_running(&mut dc, &mut cpu);
         assert_eq!(cpu.reg_pc(), 0xF006);
     }
@@ -377,25 +759,141 @@ mod tests {
         let mut dc = DebuggerCore::new();
         dc.update(&cpu);
 
-        dc.step_over(&cpu);
+        dc.step_over();
         tick_while_running(&mut dc, &mut cpu);
-        dc.step_over(&cpu);
+        dc.step_over();
         tick_while_running(&mut dc, &mut cpu);
         dc.step_into();
         tick_while_running(&mut dc, &mut cpu);
         assert_eq!(cpu.reg_pc(), 0xF00A);
 
-        dc.step_over(&cpu);
+        dc.step_over();
         tick_while_running(&mut dc, &mut cpu);
-        dc.step_over(&cpu);
+        dc.step_over();
         tick_while_running(&mut dc, &mut cpu);
-        dc.step_over(&cpu);
+        dc.step_over();
         tick_while_running(&mut dc, &mut cpu);
 
         assert_eq!(cpu.reg_pc(), 0xF010);
         assert_eq!(cpu.reg_y(), 5);
     }
 
+    #[test]
+    fn step_over_with_interrupt() {
+        let mut cpu = cpu_with_code! {
+                jsr subroutine // 0xF000
+            loop:
+                jmp loop       // 0xF003
+            subroutine:
+                nop            // 0xF006
+                rts            // 0xF007
+            interrupt:
+                jsr subroutine // 0xF008
+                inc 0x80       // 0xF00B
+                rti            // 0xF00D
+        };
+        cpu.mut_memory().bytes[0xFFFA..=0xFFFB].copy_from_slice(&[0x08, 0xF0]);
+        let mut dc = DebuggerCore::new();
+        dc.update(&cpu);
+
+        // Trigger NMI in the middle of JSR.
+        dc.step_over();
+        for _ in 0..3 {
+            cpu.tick().unwrap();
+            dc.update(&cpu);
+        }
+        cpu.set_nmi_pin(true);
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF003);
+        assert_eq!(cpu.memory().bytes[0x80], 1);
+        assert_eq!(dc.stack_depth(), 1);
+    }
+
+    #[test]
+    fn step_over_interrupted_instruction() {
+        let mut cpu = cpu_with_code! {
+                nop            // 0xF000
+                nop            // 0xF001
+            loop:
+                jmp loop       // 0xF002
+            interrupt:
+                rti            // 0xF005
+        };
+        cpu.mut_memory().bytes[0xFFFA..=0xFFFB].copy_from_slice(&[0x05, 0xF0]);
+        let mut dc = DebuggerCore::new();
+        dc.update(&cpu);
+
+        // The interrupt is handled before executing the first instruction, so
+        // we expect to stop at the same place.
+        cpu.set_nmi_pin(true);
+        dc.step_over();
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF000);
+        assert_eq!(dc.stack_depth(), 1);
+
+        dc.step_over();
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF001);
+    }
+
+    #[test]
+    fn step_over_with_stalled_cpu() {
+        /// Stalls the CPU for a couple of ticks every now and then, like a
+        /// program that waits for the horizontal sync.
+        struct StallingMachine {
+            cpu: Cpu<Ram>,
+            ticks: u32,
+        }
+
+        impl StallingMachine {
+            fn tick(&mut self) {
+                self.ticks += 1;
+                if self.at_cpu_cycle() {
+                    self.cpu.tick().unwrap();
+                }
+            }
+        }
+
+        impl CompositeMachine for StallingMachine {
+            type Memory = Ram;
+            fn cpu(&self) -> &Cpu<Ram> {
+                &self.cpu
+            }
+            fn mut_cpu(&mut self) -> &mut Cpu<Ram> {
+                &mut self.cpu
+            }
+            fn at_cpu_cycle(&self) -> bool {
+                self.ticks % 5 < 2
+            }
+        }
+
+        let mut machine = StallingMachine {
+            cpu: cpu_with_code! {
+                    jsr subroutine // 0xF000
+                loop:
+                    jmp loop       // 0xF003
+                subroutine:
+                    nop            // 0xF006
+                    rts            // 0xF007
+            },
+            ticks: 0,
+        };
+        let mut dc = DebuggerCore::new();
+        dc.update(&machine);
+
+        dc.step_over();
+        for _ in 0..100 {
+            if dc.stopped() {
+                break;
+            }
+            machine.tick();
+            dc.update(&machine);
+        }
+        assert!(dc.stopped());
+        assert_eq!(machine.reg_pc(), 0xF003);
+        assert_eq!(dc.stack_depth(), 1);
+    }
+
     #[test]
     fn step_out() {
         let mut cpu = cpu_with_code! {
@@ -470,6 +968,47 @@ mod tests {
         assert_eq!(cpu.reg_pc(), 0xF003);
     }
 
+    #[test]
+    fn step_out_of_interrupt_handler() {
+        let mut cpu = cpu_with_code! {
+                nop            // 0xF000
+            loop:
+                jmp loop       // 0xF001
+            interrupt:
+                nop            // 0xF004
+                nop            // 0xF005
+                rti            // 0xF006
+        };
+        cpu.mut_memory().bytes[0xFFFE..=0xFFFF].copy_from_slice(&[0x04, 0xF0]);
+        let mut dc = DebuggerCore::new();
+        dc.update(&cpu);
+
+        cpu.set_irq_pin(true);
+        cpu.set_register(Register::Flags, 0);
+        dc.step_into();
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF004);
+        assert_eq!(
+            dc.stack_trace(&cpu),
+            vec![
+                StackFrame {
+                    entry: 0xF004,
+                    pc: 0xF004
+                },
+                StackFrame {
+                    entry: 0xF000,
+                    pc: 0xF000
+                }
+            ]
+        );
+
+        cpu.set_irq_pin(false);
+        dc.step_out();
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF000);
+        assert_eq!(dc.stack_depth(), 1);
+    }
+
     #[test]
     fn instruction_breakpoints() {
         let mut cpu = cpu_with_code! {
@@ -482,7 +1021,7 @@ mod tests {
         };
         let mut dc = DebuggerCore::new();
         dc.update(&cpu);
-        dc.set_instruction_breakpoints(vec![0xF002]);
+        dc.set_instruction_breakpoints(vec![InstructionBreakpoint::new(0xF002)]);
         dc.resume();
 
         tick_while_running(&mut dc, &mut cpu);
@@ -490,7 +1029,10 @@ mod tests {
         assert_eq!(dc.last_stop_reason(), Some(StopReason::Breakpoint));
 
         cpu.reset();
-        dc.set_instruction_breakpoints(vec![0xF001, 0xF003]);
+        dc.set_instruction_breakpoints(vec![
+            InstructionBreakpoint::new(0xF001),
+            InstructionBreakpoint::new(0xF003),
+        ]);
 
         dc.resume();
         tick_while_running(&mut dc, &mut cpu);
@@ -503,6 +1045,213 @@ mod tests {
         assert_eq!(dc.last_stop_reason(), Some(StopReason::Breakpoint));
     }
 
+    #[test]
+    fn conditional_breakpoints() {
+        let mut cpu = cpu_with_code! {
+                ldx #0         // 0xF000
+            loop:
+                inx            // 0xF002
+                jmp loop       // 0xF003
+        };
+        let mut dc = DebuggerCore::new();
+        dc.update(&cpu);
+        dc.set_instruction_breakpoints(vec![InstructionBreakpoint::new(0xF003)
+            .with_condition(expression::parse("X == 3", &SymbolTable::new()).unwrap())]);
+        dc.resume();
+
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF003);
+        assert_eq!(cpu.reg_x(), 3);
+        assert_eq!(dc.last_stop_reason(), Some(StopReason::Breakpoint));
+
+        dc.set_instruction_breakpoints(vec![
+            InstructionBreakpoint::new(0xF003).with_hit_condition(HitCondition::Multiple(4))
+        ]);
+        dc.resume();
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_x(), 7);
+        dc.resume();
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_x(), 11);
+
+        dc.set_instruction_breakpoints(vec![InstructionBreakpoint::new(0xF003)
+            .with_condition(expression::parse("X & 1", &SymbolTable::new()).unwrap())
+            .with_hit_condition(HitCondition::Equal(2))]);
+        dc.resume();
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_x(), 15);
+    }
+
+    #[test]
+    fn parses_hit_conditions() {
+        assert_eq!(HitCondition::parse("5"), Ok(HitCondition::Equal(5)));
+        assert_eq!(HitCondition::parse("== 5"), Ok(HitCondition::Equal(5)));
+        assert_eq!(HitCondition::parse(">3"), Ok(HitCondition::Greater(3)));
+        assert_eq!(
+            HitCondition::parse(" >= 3 "),
+            Ok(HitCondition::GreaterOrEqual(3))
+        );
+        assert_eq!(HitCondition::parse("<3"), Ok(HitCondition::Less(3)));
+        assert_eq!(HitCondition::parse("<=3"), Ok(HitCondition::LessOrEqual(3)));
+        assert_eq!(HitCondition::parse("%2"), Ok(HitCondition::Multiple(2)));
+        assert!(HitCondition::parse("% 0").is_err());
+        assert!(HitCondition::parse("").is_err());
+        assert!(HitCondition::parse("=> 2").is_err());
+    }
+
+    #[test]
+    fn parses_event_breakpoints() {
+        assert_eq!(
+            EventBreakpoint::parse("brk"),
+            Ok(EventBreakpoint::Interrupt(Interrupt::Brk))
+        );
+        assert_eq!(
+            EventBreakpoint::parse("IRQ"),
+            Ok(EventBreakpoint::Interrupt(Interrupt::Irq))
+        );
+        assert_eq!(
+            EventBreakpoint::parse(" nmi "),
+            Ok(EventBreakpoint::Interrupt(Interrupt::Nmi))
+        );
+        assert_eq!(EventBreakpoint::parse("frame"), Ok(EventBreakpoint::Frame));
+        assert_eq!(
+            EventBreakpoint::parse("line 100"),
+            Ok(EventBreakpoint::RasterLine(100))
+        );
+        assert_eq!(
+            EventBreakpoint::parse("Line20"),
+            Ok(EventBreakpoint::RasterLine(20))
+        );
+        assert!(EventBreakpoint::parse("line").is_err());
+        assert!(EventBreakpoint::parse("line -1").is_err());
+        assert!(EventBreakpoint::parse("reset").is_err());
+    }
+
+    #[test]
+    fn data_breakpoints() {
+        let mut cpu = cpu_with_code! {
+                lda #1         // 0xF000
+                sta 0x80       // 0xF002
+                lda 0x80       // 0xF004
+                sta 0x81       // 0xF006
+            loop:
+                jmp loop       // 0xF008
+        };
+        let mut dc = DebuggerCore::new();
+        dc.update(&cpu);
+        dc.set_data_breakpoints(vec![DataBreakpoint {
+            address: 0x80,
+            access_type: DataBreakpointAccessType::Write,
+        }]);
+        dc.resume();
+
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF004);
+        assert_eq!(dc.last_stop_reason(), Some(StopReason::DataBreakpoint));
+
+        dc.set_data_breakpoints(vec![
+            DataBreakpoint {
+                address: 0x80,
+                access_type: DataBreakpointAccessType::Read,
+            },
+            DataBreakpoint {
+                address: 0x81,
+                access_type: DataBreakpointAccessType::ReadWrite,
+            },
+        ]);
+        dc.resume();
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF006);
+        assert_eq!(dc.last_stop_reason(), Some(StopReason::DataBreakpoint));
+
+        dc.resume();
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF008);
+        assert_eq!(dc.last_stop_reason(), Some(StopReason::DataBreakpoint));
+    }
+
+    #[test]
+    fn interrupt_breakpoints() {
+        let mut cpu = cpu_with_code! {
+                cli            // 0xF000
+                brk            // 0xF001
+                nop            // 0xF002 (skipped by BRK)
+            loop:
+                jmp loop       // 0xF003
+            interrupt:
+                rti            // 0xF006
+        };
+        let memory = cpu.mut_memory();
+        memory.bytes[0xFFFA..=0xFFFB].copy_from_slice(&[0x06, 0xF0]);
+        memory.bytes[0xFFFE..=0xFFFF].copy_from_slice(&[0x06, 0xF0]);
+        let mut dc = DebuggerCore::new();
+        dc.update(&cpu);
+        dc.set_event_breakpoints(vec![EventBreakpoint::Interrupt(Interrupt::Brk)]);
+        dc.resume();
+
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF006);
+        assert_eq!(dc.last_stop_reason(), Some(StopReason::FunctionBreakpoint));
+
+        // Interrupts other than the one we wait for are ignored.
+        dc.set_event_breakpoints(vec![EventBreakpoint::Interrupt(Interrupt::Nmi)]);
+        dc.resume();
+        cpu.set_irq_pin(true);
+        for _ in 0..100 {
+            cpu.tick().unwrap();
+            dc.update(&cpu);
+        }
+        assert!(!dc.stopped());
+
+        cpu.set_irq_pin(false);
+        cpu.set_nmi_pin(true);
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF006);
+        assert_eq!(dc.last_stop_reason(), Some(StopReason::FunctionBreakpoint));
+    }
+
+    #[test]
+    fn raster_breakpoints() {
+        let mut cpu = cpu_with_code! {
+            loop:
+                jmp loop
+        };
+        let mut dc = DebuggerCore::new();
+        dc.update(&cpu);
+        dc.set_event_breakpoints(vec![EventBreakpoint::RasterLine(1), EventBreakpoint::Frame]);
+        dc.resume();
+        let mut tick = |dc: &mut DebuggerCore, line, frame_complete| {
+            cpu.tick().unwrap();
+            dc.update_raster(line, frame_complete);
+            dc.update(&cpu);
+        };
+
+        tick(&mut dc, 0, false);
+        tick(&mut dc, 0, false);
+        tick(&mut dc, 0, false);
+        tick(&mut dc, 1, false);
+        assert!(!dc.stopped());
+        // We only stop at the beginning of the next instruction.
+        tick(&mut dc, 1, false);
+        assert!(!dc.stopped());
+        tick(&mut dc, 1, false);
+        assert!(dc.stopped());
+        assert_eq!(dc.last_stop_reason(), Some(StopReason::FunctionBreakpoint));
+
+        // Staying on the same line doesn't stop the execution again.
+        dc.resume();
+        tick(&mut dc, 1, false);
+        tick(&mut dc, 1, false);
+        tick(&mut dc, 1, false);
+        assert!(!dc.stopped());
+
+        tick(&mut dc, 1, true);
+        tick(&mut dc, 2, false);
+        tick(&mut dc, 2, false);
+        assert!(dc.stopped());
+        assert_eq!(dc.last_stop_reason(), Some(StopReason::FunctionBreakpoint));
+    }
+
     #[test]
     fn stack_frames_only_top() {
         let mut cpu = cpu_with_code! {
@@ -565,4 +1314,77 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn step_back() {
+        let mut cpu = cpu_with_code! {
+                jsr subroutine // 0xF000
+            loop:
+                jmp loop       // 0xF003
+            subroutine:
+                inx            // 0xF006
+                rts            // 0xF007
+        };
+        let mut dc = DebuggerCore::new();
+        dc.update(&cpu);
+        let x = cpu.reg_x();
+        dc.step_into();
+        tick_while_running(&mut dc, &mut cpu);
+        dc.step_into();
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF007);
+        assert_eq!(dc.stack_depth(), 2);
+        dc.last_stop_reason();
+
+        dc.step_back(&mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF006);
+        assert_eq!(cpu.reg_x(), x);
+        assert_eq!(dc.stack_depth(), 2);
+        assert_eq!(dc.last_stop_reason(), Some(StopReason::Step));
+
+        dc.step_back(&mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF000);
+        assert_eq!(dc.stack_depth(), 1);
+
+        dc.step_back(&mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF000);
+        assert_eq!(dc.last_stop_reason(), Some(StopReason::Step));
+
+        dc.step_into();
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF006);
+        assert_eq!(dc.stack_depth(), 2);
+    }
+
+    #[test]
+    fn reverse_continue() {
+        let mut cpu = cpu_with_code! {
+                ldx #0         // 0xF000
+            loop:
+                inx            // 0xF002
+                jmp loop       // 0xF003
+        };
+        let mut dc = DebuggerCore::new();
+        dc.update(&cpu);
+        dc.set_instruction_breakpoints(vec![InstructionBreakpoint::new(0xF003)
+            .with_condition(expression::parse("X == 5", &SymbolTable::new()).unwrap())]);
+        dc.resume();
+        tick_while_running(&mut dc, &mut cpu);
+        assert_eq!(cpu.reg_x(), 5);
+        dc.resume();
+        for _ in 0..100 {
+            cpu.tick().unwrap();
+            dc.update(&cpu);
+        }
+
+        dc.reverse_continue(&mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF003);
+        assert_eq!(cpu.reg_x(), 5);
+        assert_eq!(dc.last_stop_reason(), Some(StopReason::Breakpoint));
+
+        dc.set_instruction_breakpoints(vec![]);
+        dc.reverse_continue(&mut cpu);
+        assert_eq!(cpu.reg_pc(), 0xF000);
+        assert_eq!(dc.last_stop_reason(), Some(StopReason::Step));
+    }
 }
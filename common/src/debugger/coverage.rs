@@ -0,0 +1,180 @@
+use std::io;
+use std::io::Write;
+use ya6502::cpu::MachineInspector;
+use ya6502::cpu::MemoryAccess;
+
+const EXECUTED: u8 = 1 << 0;
+const READ: u8 = 1 << 1;
+const WRITTEN: u8 = 1 << 2;
+
+/// Records which addresses have been executed by the CPU, and optionally,
+/// which ones have been read or written. The coverage map is written out when
+/// the tracker is dropped (or when [`CoverageTracker::dump`] is called), one
+/// line per address that has been touched, in ascending order:
+///
+/// ```text
+/// 0080 RW
+/// F000 X
+/// F002 XR
+/// ```
+///
+/// `X` marks addresses where an instruction started, `R` and `W` mark
+/// addresses that were read and written, respectively. Note that instruction
+/// fetches count as reads, too.
+pub struct CoverageTracker<W: Write> {
+    writer: W,
+    flags: Box<[u8; 0x10000]>,
+    track_data: bool,
+}
+
+impl<W: Write> CoverageTracker<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            flags: Box::new([0; 0x10000]),
+            track_data: false,
+        }
+    }
+
+    /// Makes the tracker also record memory reads and writes.
+    pub fn with_data_accesses(mut self) -> Self {
+        self.track_data = true;
+        return self;
+    }
+
+    /// Records the last memory access, if enabled, and the address of an
+    /// instruction, if the CPU is about to execute one. Should be called after
+    /// every machine tick.
+    pub fn update(&mut self, inspector: &impl MachineInspector) {
+        if self.track_data {
+            match inspector.last_memory_access() {
+                Some(MemoryAccess::Read(address)) => self.flags[address as usize] |= READ,
+                Some(MemoryAccess::Write(address)) => self.flags[address as usize] |= WRITTEN,
+                None => {}
+            }
+        }
+        if inspector.at_instruction_start() && inspector.current_interrupt().is_none() {
+            self.flags[inspector.reg_pc() as usize] |= EXECUTED;
+        }
+    }
+
+    /// Returns `true` if an instruction at a given address has been executed.
+    pub fn executed(&self, address: u16) -> bool {
+        self.flags[address as usize] & EXECUTED != 0
+    }
+
+    /// Forgets everything that has been recorded so far.
+    pub fn clear(&mut self) {
+        self.flags.fill(0);
+    }
+
+    /// Writes out the coverage map and flushes the writer. The recorded
+    /// coverage is kept, so the map can be written again later.
+    pub fn dump(&mut self) -> io::Result<()> {
+        for (address, &flags) in self.flags.iter().enumerate() {
+            if flags != 0 {
+                writeln!(self.writer, "{:04X} {}", address, format_flags(flags))?;
+            }
+        }
+        return self.writer.flush();
+    }
+
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+}
+
+impl<W: Write> Drop for CoverageTracker<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.dump() {
+            eprintln!("Unable to write the coverage map: {}", e);
+        }
+    }
+}
+
+fn format_flags(flags: u8) -> String {
+    [(EXECUTED, 'X'), (READ, 'R'), (WRITTEN, 'W')]
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, letter)| letter)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ya6502::cpu::opcodes;
+    use ya6502::cpu::Cpu;
+    use ya6502::memory::Ram;
+    use ya6502::test_utils::cpu_with_program;
+
+    /// Note that the instruction at the starting point is only recorded if the
+    /// tracker gets updated before the first tick.
+    fn run(tracker: &mut CoverageTracker<Vec<u8>>, cpu: &mut Cpu<Ram>, n_ticks: u32) {
+        tracker.update(cpu);
+        for _ in 0..n_ticks {
+            cpu.tick().unwrap();
+            tracker.update(cpu);
+        }
+    }
+
+    fn coverage_map(tracker: &mut CoverageTracker<Vec<u8>>) -> String {
+        tracker.dump().unwrap();
+        return String::from_utf8(std::mem::take(&mut tracker.writer)).unwrap();
+    }
+
+    #[test]
+    fn records_executed_instructions() {
+        let mut cpu = cpu_with_program(&[
+            opcodes::LDX_IMM,
+            0x01,
+            opcodes::BNE,
+            0x01,
+            opcodes::NOP, // Skipped
+            opcodes::INX,
+        ]);
+        let mut tracker = CoverageTracker::new(Vec::new());
+        run(&mut tracker, &mut cpu, 7);
+
+        assert!(tracker.executed(0xF000));
+        assert!(!tracker.executed(0xF001));
+        assert!(!tracker.executed(0xF004));
+        assert!(tracker.executed(0xF005));
+        assert_eq!(
+            coverage_map(&mut tracker),
+            "F000 X\nF002 X\nF005 X\nF006 X\n"
+        );
+    }
+
+    #[test]
+    fn records_data_accesses() {
+        let mut cpu = cpu_with_program(&[
+            opcodes::LDA_ZP,
+            0x80,
+            opcodes::STA_ZP,
+            0x81,
+            opcodes::INC_ZP,
+            0x82,
+        ]);
+        let mut tracker = CoverageTracker::new(Vec::new()).with_data_accesses();
+        run(&mut tracker, &mut cpu, 11);
+        // The last read of the reset sequence is also there.
+
+        assert_eq!(
+            coverage_map(&mut tracker),
+            "0080 R\n0081 W\n0082 RW\n\
+             F000 XR\nF001 R\nF002 XR\nF003 R\nF004 XR\nF005 R\nF006 X\n\
+             FFFD R\n"
+        );
+    }
+
+    #[test]
+    fn clears_coverage() {
+        let mut cpu = cpu_with_program(&[opcodes::NOP, opcodes::NOP]);
+        let mut tracker = CoverageTracker::new(Vec::new());
+        run(&mut tracker, &mut cpu, 2);
+        tracker.clear();
+        run(&mut tracker, &mut cpu, 2);
+        assert_eq!(coverage_map(&mut tracker), "F001 X\nF002 X\n");
+    }
+}
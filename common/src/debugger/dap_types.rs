@@ -9,6 +9,7 @@
 //! Note that this crate deliberately doesn't contain all of the types, and the
 //! types only have the fields that we really use.
 
+use crate::debugger::core::DataBreakpointAccessType;
 use crate::debugger::core::StopReason;
 use serde::Deserialize;
 use serde::Serialize;
@@ -27,6 +28,11 @@ pub struct MessageEnvelope {
 pub enum Message {
     Request(Request),
     Response(ResponseEnvelope),
+    /// A response to a request that couldn't be fulfilled. It's a regular
+    /// response on the wire, but it carries an error message instead of the
+    /// body. We never receive responses, so it's only used for serialization.
+    #[serde(rename = "response", skip_deserializing)]
+    ErrorResponse(ErrorResponseEnvelope),
     Event(Event),
 }
 
@@ -36,21 +42,41 @@ pub enum Request {
     Initialize(InitializeArguments),
     SetExceptionBreakpoints {},
     SetInstructionBreakpoints(SetInstructionBreakpointsArguments),
+    DataBreakpointInfo(DataBreakpointInfoArguments),
+    SetDataBreakpoints(SetDataBreakpointsArguments),
+    SetFunctionBreakpoints(SetFunctionBreakpointsArguments),
     Attach {},
+    Launch(LaunchArguments),
     Threads,
-    StackTrace {},
+    StackTrace(ThreadArguments),
     Scopes(ScopesArguments),
     Variables(VariablesArguments),
     Disassemble(DisassembleArguments),
     ReadMemory(ReadMemoryArguments),
+    WriteMemory(WriteMemoryArguments),
+    SetVariable(SetVariableArguments),
+    Evaluate(EvaluateArguments),
 
-    Continue {},
-    Pause {},
-    Next {},
-    StepIn {},
-    StepOut {},
+    Continue(ThreadArguments),
+    Pause(ThreadArguments),
+    Next(ThreadArguments),
+    StepIn(ThreadArguments),
+    StepOut(ThreadArguments),
+    StepBack(ThreadArguments),
+    ReverseContinue(ThreadArguments),
 
     Disconnect(Option<DisconnectArguments>),
+
+    /// A custom request for register values of the support chips (like
+    /// video or I/O chips) of the emulated machine.
+    #[serde(rename = "steampunk/chipState")]
+    ChipState {},
+    /// A custom request for the current layout of the CPU address space.
+    #[serde(rename = "steampunk/memoryMap")]
+    MemoryMap {},
+    /// A custom request to assemble instructions and write them to memory.
+    #[serde(rename = "steampunk/assemble")]
+    Assemble(AssembleArguments),
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -59,12 +85,54 @@ pub struct InitializeArguments {
     pub client_name: Option<String>,
 }
 
+/// Arguments of the launch request. These come from the launch configuration
+/// of the IDE, so they are specific to this emulator.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchArguments {
+    /// Path to the program image (a ROM, cartridge, or a program file) that
+    /// replaces the one the emulator was started with. If not specified, the
+    /// current program is simply restarted.
+    pub program: Option<String>,
+    /// If set, the machine stops on the first instruction after reset.
+    pub stop_on_entry: Option<bool>,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct SetInstructionBreakpointsArguments {
     pub breakpoints: Vec<InstructionBreakpoint>,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DataBreakpointInfoArguments {
+    /// Name of the variable or expression to watch. We don't have any
+    /// symbols, so it's expected to be a memory address, either in the `$ABCD`
+    /// or `0xABCD` form.
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SetDataBreakpointsArguments {
+    pub breakpoints: Vec<DataBreakpoint>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SetFunctionBreakpointsArguments {
+    pub breakpoints: Vec<FunctionBreakpoint>,
+}
+
+/// Arguments of requests that refer to a single thread, like stepping or
+/// retrieving the stack trace.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadArguments {
+    pub thread_id: i64,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ScopesArguments {
@@ -102,6 +170,51 @@ pub struct ReadMemoryArguments {
     pub count: i64,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteMemoryArguments {
+    pub memory_reference: String,
+    pub offset: Option<i64>,
+    /// Bytes to write, encoded using base64.
+    pub data: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AssembleArguments {
+    /// Where to put the first instruction.
+    pub memory_reference: String,
+    /// Assembly source, one instruction per line.
+    pub source: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SetVariableArguments {
+    pub variables_reference: i64,
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluateArguments {
+    pub expression: String,
+    pub frame_id: Option<i64>,
+    /// Where the expression comes from: `watch`, `repl`, `hover`, etc.
+    pub context: Option<String>,
+}
+
+impl Request {
+    /// Returns the name of the command, as sent by the client.
+    pub fn command(&self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|value| value["command"].as_str().map(str::to_string))
+            .unwrap_or_default()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct ResponseEnvelope {
     pub request_seq: i64,
@@ -111,27 +224,52 @@ pub struct ResponseEnvelope {
     pub response: Response,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ErrorResponseEnvelope {
+    pub request_seq: i64,
+    /// Always `false`.
+    pub success: bool,
+    pub command: String,
+    pub message: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(tag = "command", content = "body", rename_all = "camelCase")]
 pub enum Response {
     Initialize(Capabilities),
     SetExceptionBreakpoints,
     SetInstructionBreakpoints(SetInstructionBreakpointsResponse),
+    DataBreakpointInfo(DataBreakpointInfoResponse),
+    SetDataBreakpoints(SetDataBreakpointsResponse),
+    SetFunctionBreakpoints(SetFunctionBreakpointsResponse),
     Attach,
+    Launch,
     Threads(ThreadsResponse),
     StackTrace(StackTraceResponse),
     Scopes(ScopesResponse),
     Variables(VariablesResponse),
     Disassemble(DisassembleResponse),
     ReadMemory(ReadMemoryResponse),
+    WriteMemory(WriteMemoryResponse),
+    SetVariable(SetVariableResponse),
+    Evaluate(EvaluateResponse),
 
     Continue {},
     Pause,
     Next,
     StepIn,
     StepOut,
+    StepBack,
+    ReverseContinue,
 
     Disconnect,
+
+    #[serde(rename = "steampunk/chipState")]
+    ChipState(ChipStateResponse),
+    #[serde(rename = "steampunk/memoryMap")]
+    MemoryMap(MemoryMapResponse),
+    #[serde(rename = "steampunk/assemble")]
+    Assemble(AssembleResponse),
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -139,7 +277,14 @@ pub enum Response {
 pub struct Capabilities {
     pub supports_disassemble_request: bool,
     pub supports_instruction_breakpoints: bool,
+    pub supports_conditional_breakpoints: bool,
+    pub supports_hit_conditional_breakpoints: bool,
+    pub supports_data_breakpoints: bool,
+    pub supports_function_breakpoints: bool,
     pub supports_read_memory_request: bool,
+    pub supports_write_memory_request: bool,
+    pub supports_set_variable: bool,
+    pub supports_step_back: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -148,6 +293,28 @@ pub struct SetInstructionBreakpointsResponse {
     pub breakpoints: Vec<Breakpoint>,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DataBreakpointInfoResponse {
+    /// An identifier to be used in [`DataBreakpoint::data_id`], or `None` if
+    /// no data breakpoint can be set for a given name.
+    pub data_id: Option<String>,
+    pub description: String,
+    pub access_types: Option<Vec<DataBreakpointAccessType>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SetDataBreakpointsResponse {
+    pub breakpoints: Vec<Breakpoint>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SetFunctionBreakpointsResponse {
+    pub breakpoints: Vec<Breakpoint>,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ThreadsResponse {
@@ -202,6 +369,79 @@ pub struct ReadMemoryResponse {
     pub unreadable_bytes: i64,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteMemoryResponse {
+    pub bytes_written: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SetVariableResponse {
+    /// The new value of the variable, formatted the same way as in the
+    /// [`VariablesResponse`].
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluateResponse {
+    pub result: String,
+    pub variables_reference: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChipStateResponse {
+    pub chips: Vec<Chip>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryMapResponse {
+    pub regions: Vec<MemoryRegion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AssembleResponse {
+    pub bytes_written: i64,
+    /// Describes why the source couldn't be assembled. If present, the memory
+    /// is left untouched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryRegion {
+    /// The first address of the region, as a hexadecimal memory reference.
+    pub start: String,
+    /// The last address of the region (inclusive).
+    pub end: String,
+    pub kind: MemoryKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum MemoryKind {
+    Ram,
+    Rom,
+    Io,
+    Unmapped,
+}
+
+impl From<ya6502::memory::MemoryKind> for MemoryKind {
+    fn from(kind: ya6502::memory::MemoryKind) -> Self {
+        match kind {
+            ya6502::memory::MemoryKind::Ram => Self::Ram,
+            ya6502::memory::MemoryKind::Rom => Self::Rom,
+            ya6502::memory::MemoryKind::Io => Self::Io,
+            ya6502::memory::MemoryKind::Unmapped => Self::Unmapped,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct DisassembledInstruction {
@@ -221,6 +461,20 @@ pub struct Variable {
     pub memory_reference: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Chip {
+    pub name: String,
+    pub registers: Vec<ChipRegister>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChipRegister {
+    pub name: String,
+    pub value: i64,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(tag = "event", content = "body", rename_all = "camelCase")]
 pub enum Event {
@@ -234,6 +488,9 @@ pub struct StoppedEvent {
     pub reason: StopReason,
     pub thread_id: i64,
     pub all_threads_stopped: bool,
+    /// Additional information about the reason, displayed to the user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -258,13 +515,36 @@ pub struct Thread {
 pub struct InstructionBreakpoint {
     pub instruction_reference: String,
     pub offset: Option<i64>,
+    /// An expression that needs to evaluate to a non-zero value for the
+    /// breakpoint to be hit, e.g. `A == $3F && X > 4`.
+    pub condition: Option<String>,
+    /// Controls which hits stop the execution, e.g. `5`, `>= 5`, or `% 5`.
+    pub hit_condition: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Breakpoint {
     pub verified: bool,
-    pub instruction_reference: String,
+    pub instruction_reference: Option<String>,
+    /// Explains why the breakpoint couldn't be verified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DataBreakpoint {
+    pub data_id: String,
+    pub access_type: Option<DataBreakpointAccessType>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionBreakpoint {
+    /// Name of the function. Since we don't know any functions, it's
+    /// interpreted as a name of a machine event, like `irq` or `line 100`.
+    pub name: String,
 }
 
 /// This empty struct is here only because `Serde` doesn't allow us to use an
@@ -291,6 +571,33 @@ mod tests {
         .unwrap()
     }
 
+    #[test]
+    fn error_response_serialization() {
+        let message = MessageEnvelope {
+            seq: 48,
+            message: Message::ErrorResponse(ErrorResponseEnvelope {
+                request_seq: 16,
+                success: false,
+                command: "writeMemory".to_string(),
+                message: "Invalid base64 data".to_string(),
+            }),
+        };
+        let serialized: serde_json::Value = serde_json::to_value(&message).unwrap();
+        let expected: serde_json::Value =
+            serde_json::from_str(&read_test_string("error_response.json")).unwrap();
+        assert_eq!(serialized, expected);
+    }
+
+    #[test]
+    fn request_command() {
+        assert_eq!(Request::Threads.command(), "threads");
+        assert_eq!(Request::ChipState {}.command(), "steampunk/chipState");
+        assert_eq!(
+            Request::Continue(ThreadArguments { thread_id: 1 }).command(),
+            "continue"
+        );
+    }
+
     macro_rules! message_serialization_tests {
         ($($name:ident: $message:expr,)*) => {$(
             #[test]
@@ -327,26 +634,73 @@ mod tests {
                         InstructionBreakpoint {
                             instruction_reference: "0xAB12".to_string(),
                             offset: None,
+                            condition: None,
+                            hit_condition: None,
                         },
                         InstructionBreakpoint {
                             instruction_reference: "0x12AB".to_string(),
                             offset: Some(-12),
+                            condition: Some("A == $3F && X > 4".to_string()),
+                            hit_condition: Some(">= 2".to_string()),
                         }
                     ]
                 }
             )),
         },
+        data_breakpoint_info_request: MessageEnvelope {
+            seq: 4,
+            message: Message::Request(Request::DataBreakpointInfo(DataBreakpointInfoArguments {
+                name: "$0080".to_string(),
+            })),
+        },
+        set_data_breakpoints_request: MessageEnvelope {
+            seq: 5,
+            message: Message::Request(Request::SetDataBreakpoints(SetDataBreakpointsArguments {
+                breakpoints: vec![
+                    DataBreakpoint {
+                        data_id: "0x0080".to_string(),
+                        access_type: Some(DataBreakpointAccessType::ReadWrite),
+                    },
+                    DataBreakpoint {
+                        data_id: "0x0281".to_string(),
+                        access_type: None,
+                    },
+                ],
+            })),
+        },
+        set_function_breakpoints_request: MessageEnvelope {
+            seq: 6,
+            message: Message::Request(Request::SetFunctionBreakpoints(
+                SetFunctionBreakpointsArguments {
+                    breakpoints: vec![
+                        FunctionBreakpoint {
+                            name: "irq".to_string(),
+                        },
+                        FunctionBreakpoint {
+                            name: "line 100".to_string(),
+                        },
+                    ],
+                }
+            )),
+        },
         attach_request: MessageEnvelope {
             seq: 2,
             message: Message::Request(Request::Attach {}),
         },
+        launch_request: MessageEnvelope {
+            seq: 2,
+            message: Message::Request(Request::Launch(LaunchArguments {
+                program: Some("/home/user/game/game.bin".to_string()),
+                stop_on_entry: Some(true),
+            })),
+        },
         threads_request: MessageEnvelope {
             seq: 4,
             message: Message::Request(Request::Threads),
         },
         stack_trace_request: MessageEnvelope {
             seq: 6,
-            message: Message::Request(Request::StackTrace {}),
+            message: Message::Request(Request::StackTrace(ThreadArguments { thread_id: 1 })),
         },
         scopes_request: MessageEnvelope {
             seq: 7,
@@ -377,25 +731,57 @@ mod tests {
                 count: 131072,
             })),
         },
+        write_memory_request: MessageEnvelope {
+            seq: 16,
+            message: Message::Request(Request::WriteMemory(WriteMemoryArguments {
+                memory_reference: "0x0080".to_string(),
+                offset: Some(2),
+                data: "3q0=".to_string(),
+            })),
+        },
+        set_variable_request: MessageEnvelope {
+            seq: 17,
+            message: Message::Request(Request::SetVariable(SetVariableArguments {
+                variables_reference: 1,
+                name: "X".to_string(),
+                value: "$2A".to_string(),
+            })),
+        },
+        evaluate_request: MessageEnvelope {
+            seq: 18,
+            message: Message::Request(Request::Evaluate(EvaluateArguments {
+                expression: "[$80] + X".to_string(),
+                frame_id: Some(1),
+                context: Some("watch".to_string()),
+            })),
+        },
         continue_request: MessageEnvelope {
             seq: 10,
-            message: Message::Request(Request::Continue {}),
+            message: Message::Request(Request::Continue(ThreadArguments { thread_id: 1 })),
         },
         pause_request: MessageEnvelope {
             seq: 10,
-            message: Message::Request(Request::Pause {}),
+            message: Message::Request(Request::Pause(ThreadArguments { thread_id: 1 })),
         },
         next_request: MessageEnvelope {
             seq: 9,
-            message: Message::Request(Request::Next {}),
+            message: Message::Request(Request::Next(ThreadArguments { thread_id: 1 })),
         },
         step_in_request: MessageEnvelope {
             seq: 9,
-            message: Message::Request(Request::StepIn {}),
+            message: Message::Request(Request::StepIn(ThreadArguments { thread_id: 1 })),
         },
         step_out_request: MessageEnvelope {
             seq: 9,
-            message: Message::Request(Request::StepOut {}),
+            message: Message::Request(Request::StepOut(ThreadArguments { thread_id: 1 })),
+        },
+        step_back_request: MessageEnvelope {
+            seq: 21,
+            message: Message::Request(Request::StepBack(ThreadArguments { thread_id: 1 })),
+        },
+        reverse_continue_request: MessageEnvelope {
+            seq: 23,
+            message: Message::Request(Request::ReverseContinue(ThreadArguments { thread_id: 1 })),
         },
         disconnect_request: MessageEnvelope {
             seq: 2,
@@ -405,6 +791,21 @@ mod tests {
             seq: 2,
             message: Message::Request(Request::Disconnect(None)),
         },
+        chip_state_request: MessageEnvelope {
+            seq: 24,
+            message: Message::Request(Request::ChipState {}),
+        },
+        memory_map_request: MessageEnvelope {
+            seq: 25,
+            message: Message::Request(Request::MemoryMap {}),
+        },
+        assemble_request: MessageEnvelope {
+            seq: 26,
+            message: Message::Request(Request::Assemble(AssembleArguments {
+                memory_reference: "0xF010".to_string(),
+                source: "LDA #$12\nSTA COLUBK".to_string(),
+            })),
+        },
 
         initialize_response: MessageEnvelope {
             seq: 1,
@@ -414,7 +815,14 @@ mod tests {
                 response: Response::Initialize(Capabilities {
                     supports_disassemble_request: true,
                     supports_instruction_breakpoints: true,
+                    supports_conditional_breakpoints: true,
+                    supports_hit_conditional_breakpoints: true,
+                    supports_data_breakpoints: true,
+                    supports_function_breakpoints: true,
                     supports_read_memory_request: true,
+                    supports_write_memory_request: true,
+                    supports_set_variable: true,
+                    supports_step_back: true,
                 }),
             }),
         },
@@ -435,12 +843,64 @@ mod tests {
                     SetInstructionBreakpointsResponse {
                         breakpoints: vec![Breakpoint {
                             verified: true,
-                            instruction_reference: "0x9876".to_string(),
+                            instruction_reference: Some("0x9876".to_string()),
+                            message: None,
                         }]
                     }
                 ),
             }),
         },
+        data_breakpoint_info_response: MessageEnvelope {
+            seq: 3,
+            message: Message::Response(ResponseEnvelope {
+                request_seq: 4,
+                success: true,
+                response: Response::DataBreakpointInfo(DataBreakpointInfoResponse {
+                    data_id: Some("0x0080".to_string()),
+                    description: "$0080".to_string(),
+                    access_types: Some(vec![
+                        DataBreakpointAccessType::Read,
+                        DataBreakpointAccessType::Write,
+                        DataBreakpointAccessType::ReadWrite,
+                    ]),
+                }),
+            }),
+        },
+        set_data_breakpoints_response: MessageEnvelope {
+            seq: 4,
+            message: Message::Response(ResponseEnvelope {
+                request_seq: 5,
+                success: true,
+                response: Response::SetDataBreakpoints(SetDataBreakpointsResponse {
+                    breakpoints: vec![Breakpoint {
+                        verified: true,
+                        instruction_reference: None,
+                        message: None,
+                    }],
+                }),
+            }),
+        },
+        set_function_breakpoints_response: MessageEnvelope {
+            seq: 5,
+            message: Message::Response(ResponseEnvelope {
+                request_seq: 6,
+                success: true,
+                response: Response::SetFunctionBreakpoints(SetFunctionBreakpointsResponse {
+                    breakpoints: vec![
+                        Breakpoint {
+                            verified: true,
+                            instruction_reference: None,
+                            message: None,
+                        },
+                        Breakpoint {
+                            verified: false,
+                            instruction_reference: None,
+                            message: None,
+                        },
+                    ],
+                }),
+            }),
+        },
         attach_response: MessageEnvelope {
             seq: 3,
             message: Message::Response(ResponseEnvelope {
@@ -449,6 +909,14 @@ mod tests {
                 response: Response::Attach,
             }),
         },
+        launch_response: MessageEnvelope {
+            seq: 3,
+            message: Message::Response(ResponseEnvelope {
+                request_seq: 2,
+                success: true,
+                response: Response::Launch,
+            }),
+        },
         threads_response: MessageEnvelope {
             seq: 54,
             message: Message::Response(ResponseEnvelope {
@@ -542,6 +1010,37 @@ mod tests {
                 }),
             }),
         },
+        write_memory_response: MessageEnvelope {
+            seq: 77,
+            message: Message::Response(ResponseEnvelope {
+                request_seq: 16,
+                success: true,
+                response: Response::WriteMemory(WriteMemoryResponse {
+                    bytes_written: Some(2),
+                }),
+            }),
+        },
+        set_variable_response: MessageEnvelope {
+            seq: 78,
+            message: Message::Response(ResponseEnvelope {
+                request_seq: 17,
+                success: true,
+                response: Response::SetVariable(SetVariableResponse {
+                    value: "$2A".to_string(),
+                }),
+            }),
+        },
+        evaluate_response: MessageEnvelope {
+            seq: 79,
+            message: Message::Response(ResponseEnvelope {
+                request_seq: 18,
+                success: true,
+                response: Response::Evaluate(EvaluateResponse {
+                    result: "$3F (63)".to_string(),
+                    variables_reference: 0,
+                }),
+            }),
+        },
         continue_response: MessageEnvelope {
             seq: 11,
             message: Message::Response(ResponseEnvelope {
@@ -582,6 +1081,22 @@ mod tests {
                 response: Response::StepOut,
             }),
         },
+        step_back_response: MessageEnvelope {
+            seq: 40,
+            message: Message::Response(ResponseEnvelope {
+                request_seq: 21,
+                success: true,
+                response: Response::StepBack,
+            }),
+        },
+        reverse_continue_response: MessageEnvelope {
+            seq: 43,
+            message: Message::Response(ResponseEnvelope {
+                request_seq: 23,
+                success: true,
+                response: Response::ReverseContinue,
+            }),
+        },
         disconnect_response: MessageEnvelope {
             seq: 64,
             message: Message::Response(ResponseEnvelope {
@@ -590,6 +1105,76 @@ mod tests {
                 response: Response::Disconnect,
             }),
         },
+        chip_state_response: MessageEnvelope {
+            seq: 44,
+            message: Message::Response(ResponseEnvelope {
+                request_seq: 24,
+                success: true,
+                response: Response::ChipState(ChipStateResponse {
+                    chips: vec![Chip {
+                        name: "RIOT".to_string(),
+                        registers: vec![
+                            ChipRegister {
+                                name: "SWCHA".to_string(),
+                                value: 0xFF,
+                            },
+                            ChipRegister {
+                                name: "INTIM".to_string(),
+                                value: 0x2A,
+                            },
+                        ],
+                    }],
+                }),
+            }),
+        },
+        memory_map_response: MessageEnvelope {
+            seq: 45,
+            message: Message::Response(ResponseEnvelope {
+                request_seq: 25,
+                success: true,
+                response: Response::MemoryMap(MemoryMapResponse {
+                    regions: vec![
+                        MemoryRegion {
+                            start: "0x0000".to_string(),
+                            end: "0x007F".to_string(),
+                            kind: MemoryKind::Io,
+                        },
+                        MemoryRegion {
+                            start: "0x0080".to_string(),
+                            end: "0x00FF".to_string(),
+                            kind: MemoryKind::Ram,
+                        },
+                        MemoryRegion {
+                            start: "0xF000".to_string(),
+                            end: "0xFFFF".to_string(),
+                            kind: MemoryKind::Rom,
+                        },
+                    ],
+                }),
+            }),
+        },
+        assemble_response: MessageEnvelope {
+            seq: 46,
+            message: Message::Response(ResponseEnvelope {
+                request_seq: 26,
+                success: true,
+                response: Response::Assemble(AssembleResponse {
+                    bytes_written: 4,
+                    error: None,
+                }),
+            }),
+        },
+        assemble_error_response: MessageEnvelope {
+            seq: 47,
+            message: Message::Response(ResponseEnvelope {
+                request_seq: 27,
+                success: true,
+                response: Response::Assemble(AssembleResponse {
+                    bytes_written: 0,
+                    error: Some("Line 1: Unknown instruction: LAD".to_string()),
+                }),
+            }),
+        },
 
         initialized_event: MessageEnvelope {
             seq: 74,
@@ -601,6 +1186,16 @@ mod tests {
                 reason: StopReason::Entry,
                 thread_id: 1,
                 all_threads_stopped: true,
+                text: None,
+            })),
+        },
+        data_breakpoint_stopped_event: MessageEnvelope {
+            seq: 11,
+            message: Message::Event(Event::Stopped(StoppedEvent {
+                reason: StopReason::DataBreakpoint,
+                thread_id: 1,
+                all_threads_stopped: true,
+                text: None,
             })),
         },
     }
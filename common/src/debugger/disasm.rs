@@ -1,9 +1,12 @@
 use crate::debugger::dap_types::DisassembledInstruction;
+use crate::debugger::symbols::RegisterNames;
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::iter;
 use ya6502::cpu::MachineInspector;
+use ya6502::memory::MemoryKind;
+use ya6502::memory::MemoryRegion;
 
 /// Disassembles a memory region. The region starts at `start_address`. First
 /// `margin` instructions are ignored to allow for a "runway" in disassembling
@@ -14,14 +17,21 @@ use ya6502::cpu::MachineInspector;
 /// currently a PC or belongs to a chain of already disassembled instructions).
 /// This way, multiple disassembly requests for adjacent or overlapping memory
 /// regions are guaranteed to produce a coherent output.
-pub fn disassemble<I: MachineInspector>(
+///
+/// Operands that point directly at hardware registers listed in
+/// `register_names` are annotated with the register names in a comment column.
+/// Bytes that lie in the I/O or unmapped regions of the `memory_map` are not
+/// decoded as instructions.
+pub fn disassemble<I: MachineInspector + ?Sized>(
     inspector: &I,
+    register_names: &RegisterNames,
+    memory_map: &[MemoryRegion],
     origin: u16,
     start_address: u16,
     margin: usize,
     length: usize,
 ) -> Vec<DisassembledInstruction> {
-    let mut memory_stream = MemoryStream::new(inspector, start_address);
+    let mut memory_stream = MemoryStream::new(inspector, start_address).with_memory_map(memory_map);
     return iter::from_fn(|| {
         let instruction_start = memory_stream.ptr;
         let instruction = read_instruction_unless_crosses_origin(&mut memory_stream, origin);
@@ -39,10 +49,20 @@ pub fn disassemble<I: MachineInspector>(
         };
         let instruction_parts = [mnemonic, argument];
         let non_empty_instruction_parts = instruction_parts.iter().filter(|s| s.len() > 0);
+        let instruction_text = format!("{}", non_empty_instruction_parts.format(" "));
+        let register_name = match (instruction.descriptor, instruction.argument) {
+            (Some(descriptor), Some(argument)) => {
+                accessed_register_name(register_names, descriptor.mnemonic, argument)
+            }
+            _ => None,
+        };
         return Some(DisassembledInstruction {
             address: format!("0x{:04X}", instruction_start),
             instruction_bytes: format!("{:02X}", all_bytes.iter().format(" ")),
-            instruction: format!("{}", non_empty_instruction_parts.format(" ")),
+            instruction: match register_name {
+                Some(name) => format!("{:<12}; {}", instruction_text, name),
+                None => instruction_text,
+            },
         });
     })
     .skip(margin)
@@ -50,12 +70,38 @@ pub fn disassemble<I: MachineInspector>(
     .collect();
 }
 
+/// Returns the name of a hardware register that an instruction accesses
+/// through its absolute or zero page operand (possibly indexed), if there is
+/// one. Stores use the names of write registers, and read-modify-write
+/// instructions fall back to the read ones. Jumps are never annotated, since
+/// their operands point at the code.
+fn accessed_register_name<'a>(
+    register_names: &'a RegisterNames,
+    mnemonic: &str,
+    argument: Argument,
+) -> Option<&'a str> {
+    use Argument::*;
+    let address = match argument {
+        Absolute(address) | AbsoluteIndexedX(address) | AbsoluteIndexedY(address) => address,
+        ZeroPage(address) | ZeroPageIndexedX(address) | ZeroPageIndexedY(address) => address as u16,
+        _ => return None,
+    };
+    return match mnemonic {
+        "JMP" | "JSR" => None,
+        "STA" | "STX" | "STY" => register_names.write_name(address),
+        "ASL" | "LSR" | "ROL" | "ROR" | "INC" | "DEC" => register_names
+            .write_name(address)
+            .or_else(|| register_names.read_name(address)),
+        _ => register_names.read_name(address),
+    };
+}
+
 fn read_instruction_unless_crosses_origin<'a, I>(
     stream: &mut MemoryStream<I>,
     origin: u16,
 ) -> Instruction<'a>
 where
-    I: MachineInspector,
+    I: MachineInspector + ?Sized,
 {
     let instruction_start = stream.ptr;
     let instruction = stream.read_instruction();
@@ -78,9 +124,16 @@ where
 /// Adds a given number of instructions (`offset`) to the `origin` address. If
 /// the offset is positive, adding is analogous to the actual disassembly
 /// process; if it's negative, we use a heuristic algorithm that minimizes the
-/// number of unknown instructions.
-pub fn seek_instruction<I: MachineInspector>(inspector: &I, origin: u16, offset: i64) -> u16 {
-    let mut stream = MemoryStream::new(inspector, origin);
+/// number of unknown instructions. Just like in [`disassemble`], bytes in the
+/// I/O and unmapped regions of the `memory_map` are treated as unknown
+/// instructions.
+pub fn seek_instruction<I: MachineInspector + ?Sized>(
+    inspector: &I,
+    memory_map: &[MemoryRegion],
+    origin: u16,
+    offset: i64,
+) -> u16 {
+    let mut stream = MemoryStream::new(inspector, origin).with_memory_map(memory_map);
 
     if offset >= 0 {
         for _ in 0..offset {
@@ -191,8 +244,8 @@ fn seeking_backward_finished(chain_links: &[ChainLink], offset: i64) -> bool {
             .all(|link| link.num_instructions >= -offset)
 }
 
-#[derive(Clone, Copy, Debug)]
-enum AddressingMode {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressingMode {
     Accumulator,
     Immediate,
     Implied,
@@ -271,7 +324,7 @@ impl AddressingMode {
     /// Reads an instruction argument from a memory stream.
     fn read_argument<'a, I>(self, stream: &mut MemoryStream<'a, I>) -> Argument
     where
-        I: MachineInspector,
+        I: MachineInspector + ?Sized,
     {
         match self {
             AddressingMode::Accumulator => Argument::Accumulator,
@@ -295,15 +348,67 @@ impl AddressingMode {
     }
 }
 
+/// Returns the opcode of an instruction with a given mnemonic and addressing
+/// mode, if there is one.
+pub fn opcode(mnemonic: &str, addressing_mode: AddressingMode) -> Option<u8> {
+    INSTRUCTION_DESCRIPTORS.with(|descriptors| {
+        (0..=0xFF).find(|&opcode| {
+            matches!(
+                descriptors[opcode as usize],
+                Some(descriptor) if descriptor.mnemonic == mnemonic
+                    && descriptor.addressing_mode == addressing_mode
+            )
+        })
+    })
+}
+
+/// Checks whether a given mnemonic denotes a known instruction.
+pub fn is_mnemonic(mnemonic: &str) -> bool {
+    INSTRUCTION_DESCRIPTORS.with(|descriptors| {
+        descriptors
+            .iter()
+            .flatten()
+            .any(|descriptor| descriptor.mnemonic == mnemonic)
+    })
+}
+
+/// Checks whether a given address lies in RAM or ROM, according to a memory
+/// map. Addresses not covered by the map are assumed to contain code.
+pub fn may_contain_code(memory_map: &[MemoryRegion], address: u16) -> bool {
+    match memory_map
+        .iter()
+        .find(|region| (region.start..=region.end).contains(&address))
+    {
+        Some(region) => matches!(region.kind, MemoryKind::Ram | MemoryKind::Rom),
+        None => true,
+    }
+}
+
 /// A reader that reads data from the machine inspector's address space.
-struct MemoryStream<'a, I: MachineInspector> {
+struct MemoryStream<'a, I: MachineInspector + ?Sized> {
     inspector: &'a I,
     ptr: u16,
+    /// Used to tell which addresses can contain code. If empty, all of them
+    /// are assumed to.
+    memory_map: &'a [MemoryRegion],
 }
 
-impl<'a, I: MachineInspector> MemoryStream<'a, I> {
+impl<'a, I: MachineInspector + ?Sized> MemoryStream<'a, I> {
     fn new(inspector: &'a I, ptr: u16) -> Self {
-        Self { inspector, ptr }
+        Self {
+            inspector,
+            ptr,
+            memory_map: &[],
+        }
+    }
+    fn with_memory_map(mut self, memory_map: &'a [MemoryRegion]) -> Self {
+        self.memory_map = memory_map;
+        return self;
+    }
+    /// Checks whether a given address can contain code. Decoding I/O registers
+    /// or unmapped memory as instructions would only produce garbage.
+    fn may_contain_code(&self, address: u16) -> bool {
+        may_contain_code(self.memory_map, address)
     }
     fn read_byte(&mut self) -> u8 {
         let b = self.inspector.inspect_memory(self.ptr);
@@ -319,6 +424,13 @@ impl<'a, I: MachineInspector> MemoryStream<'a, I> {
     // the returned instruction implicitly borrows `self` mutably. Don't even
     // ask me how.
     fn read_instruction<'b>(&mut self) -> Instruction<'b> {
+        if !self.may_contain_code(self.ptr) {
+            return Instruction {
+                opcode: self.read_byte(),
+                descriptor: None,
+                argument: None,
+            };
+        }
         let opcode = self.read_byte();
         let descriptor = INSTRUCTION_DESCRIPTORS.with(|descriptors| descriptors[opcode as usize]);
         let argument = descriptor.map(|d| d.addressing_mode.read_argument(self));
@@ -614,8 +726,8 @@ mod tests {
     #[test]
     fn seek_at_origin() {
         let cpu = cpu_with_program(&[]);
-        assert_eq!(seek_instruction(&cpu, 0x483A, 0), 0x483A);
-        assert_eq!(seek_instruction(&cpu, 0xA384, 0), 0xA384);
+        assert_eq!(seek_instruction(&cpu, &[], 0x483A, 0), 0x483A);
+        assert_eq!(seek_instruction(&cpu, &[], 0xA384, 0), 0xA384);
     }
 
     #[test]
@@ -625,9 +737,9 @@ mod tests {
                 lda #0x2B
                 sta abs 0x1234
         };
-        assert_eq!(seek_instruction(&cpu, 0xF000, 1), 0xF001);
-        assert_eq!(seek_instruction(&cpu, 0xF000, 2), 0xF003);
-        assert_eq!(seek_instruction(&cpu, 0xF000, 3), 0xF006);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF000, 1), 0xF001);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF000, 2), 0xF003);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF000, 3), 0xF006);
     }
 
     #[test]
@@ -641,8 +753,8 @@ mod tests {
         cpu.mut_memory().bytes[0xF002] = 0x02;
         cpu.mut_memory().bytes[0xF005] = 0x02;
 
-        assert_eq!(seek_instruction(&cpu, 0xF000, 2), 0xF003);
-        assert_eq!(seek_instruction(&cpu, 0xF000, 4), 0xF006);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF000, 2), 0xF003);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF000, 4), 0xF006);
     }
 
     #[test]
@@ -652,9 +764,9 @@ mod tests {
                 lda #0x2B
                 sta abs 0x1234
         };
-        assert_eq!(seek_instruction(&cpu, 0xF006, -1), 0xF003);
-        assert_eq!(seek_instruction(&cpu, 0xF006, -2), 0xF001);
-        assert_eq!(seek_instruction(&cpu, 0xF006, -3), 0xF000);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF006, -1), 0xF003);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF006, -2), 0xF001);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF006, -3), 0xF000);
     }
 
     #[test]
@@ -669,13 +781,13 @@ mod tests {
         };
 
         // Interpret 1 instruction as NOP
-        assert_eq!(seek_instruction(&cpu, 0xF005, -1), 0xF004);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF005, -1), 0xF004);
         // Interpret 2 instructions as NOP, LDA $EA
-        assert_eq!(seek_instruction(&cpu, 0xF005, -2), 0xF002);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF005, -2), 0xF002);
         // Interpret 3 instructions as INX, LDA $EA, LDA $EA
-        assert_eq!(seek_instruction(&cpu, 0xF005, -3), 0xF000);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF005, -3), 0xF000);
         // Interpret 3 instructions as NOP, LDA $EA, LDA $EAEA
-        assert_eq!(seek_instruction(&cpu, 0xF008, -3), 0xF002);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF008, -3), 0xF002);
     }
 
     #[test]
@@ -689,7 +801,7 @@ mod tests {
 
         // 0xF001 should be preferred to 0xF003, since it has 1 unknown
         // instruction less.
-        assert_eq!(seek_instruction(&cpu, 0xF005, -2), 0xF001);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF005, -2), 0xF001);
     }
 
     #[test]
@@ -702,7 +814,7 @@ mod tests {
         // There's no way to land on 0xF003 (the last byte of the stx
         // instruction). In such case, we expect the stx instruction to be
         // interpreted entirely as data.
-        assert_eq!(seek_instruction(&cpu, 0xF002, -2), 0xF000);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF002, -2), 0xF000);
     }
 
     #[test]
@@ -711,20 +823,20 @@ mod tests {
             nop
             stx abs 0x2B2B
         };
-        assert_eq!(seek_instruction(&cpu, 0xF003, -3), 0xF000);
+        assert_eq!(seek_instruction(&cpu, &[], 0xF003, -3), 0xF000);
     }
 
     #[test]
     fn seek_backward_with_wrapping() {
         let mut cpu = cpu_with_program(&[]);
         cpu.mut_memory().bytes[0xFFFF] = 0xEA;
-        assert_eq!(seek_instruction(&cpu, 0x0000, -1), 0xFFFF);
+        assert_eq!(seek_instruction(&cpu, &[], 0x0000, -1), 0xFFFF);
 
         let mut cpu = cpu_with_program(&[]);
         // LDA $12
         cpu.mut_memory().bytes[0xFFFF] = 0xA5;
         cpu.mut_memory().bytes[0x0000] = 0x12;
-        assert_eq!(seek_instruction(&cpu, 0x0001, -1), 0xFFFF);
+        assert_eq!(seek_instruction(&cpu, &[], 0x0001, -1), 0xFFFF);
     }
 
     #[test]
@@ -738,9 +850,12 @@ mod tests {
                 bne loop
         };
 
-        assert_eq!(disassemble(&cpu, 0xF000, 0xF000, 0, 0), vec![]);
         assert_eq!(
-            disassemble(&cpu, 0xF000, 0xF000, 0, 5),
+            disassemble(&cpu, &RegisterNames::new(), &[], 0xF000, 0xF000, 0, 0),
+            vec![]
+        );
+        assert_eq!(
+            disassemble(&cpu, &RegisterNames::new(), &[], 0xF000, 0xF000, 0, 5),
             vec![
                 disassembled("0xF000", "A5 45", "LDA $45"),
                 disassembled("0xF002", "A2 04", "LDX #$04"),
@@ -750,7 +865,7 @@ mod tests {
             ]
         );
         assert_eq!(
-            disassemble(&cpu, 0xF002, 0xF002, 0, 2),
+            disassemble(&cpu, &RegisterNames::new(), &[], 0xF002, 0xF002, 0, 2),
             vec![
                 disassembled("0xF002", "A2 04", "LDX #$04"),
                 disassembled("0xF004", "9D EF BE", "STA $BEEF,X"),
@@ -762,7 +877,7 @@ mod tests {
     fn disassemble_unknown_instruction() {
         let cpu = cpu_with_program(&[0xEA, 0x67, 0xEA]);
         assert_eq!(
-            disassemble(&cpu, 0xF000, 0xF000, 0, 3),
+            disassemble(&cpu, &RegisterNames::new(), &[], 0xF000, 0xF000, 0, 3),
             vec![
                 disassembled("0xF000", "EA", "NOP"),
                 disassembled("0xF001", "67", ""),
@@ -780,7 +895,7 @@ mod tests {
         };
 
         assert_eq!(
-            disassemble(&cpu, 0xF002, 0xF000, 0, 3),
+            disassemble(&cpu, &RegisterNames::new(), &[], 0xF002, 0xF000, 0, 3),
             vec![
                 disassembled("0xF000", "A5 45", "LDA $45"),
                 disassembled("0xF002", "85 EA", "STA $EA"),
@@ -788,7 +903,7 @@ mod tests {
             ]
         );
         assert_eq!(
-            disassemble(&cpu, 0xF003, 0xF000, 0, 4),
+            disassemble(&cpu, &RegisterNames::new(), &[], 0xF003, 0xF000, 0, 4),
             vec![
                 disassembled("0xF000", "A5 45", "LDA $45"),
                 disassembled("0xF002", "85", ""),
@@ -806,7 +921,7 @@ mod tests {
                 stx 0x46
         };
         assert_eq!(
-            disassemble(&cpu, 0xF003, 0xF000, 1, 2),
+            disassemble(&cpu, &RegisterNames::new(), &[], 0xF003, 0xF000, 1, 2),
             vec![
                 disassembled("0xF002", "E8", "INX"),
                 disassembled("0xF003", "86 46", "STX $46"),
@@ -814,6 +929,78 @@ mod tests {
         )
     }
 
+    #[test]
+    fn disassemble_with_register_names() {
+        let cpu = cpu_with_program(&[
+            0x85, 0x09, // STA $09
+            0xA5, 0x09, // LDA $09
+            0xE6, 0x0A, // INC $0A
+            0x95, 0x10, // STA $10,X
+            0xB1, 0x09, // LDA ($09),Y
+            0x8D, 0x80, 0x02, // STA $0280
+            0x4C, 0x80, 0x02, // JMP $0280
+            0x85, 0x80, // STA $80
+        ]);
+        let register_names = RegisterNames::new()
+            .with_write_registers([("COLUBK", 0x09), ("CTRLPF", 0x0A), ("RESP0", 0x10)])
+            .with_read_registers([("INPT1", 0x09)])
+            .with_registers([("SWCHA", 0x0280)]);
+        assert_eq!(
+            disassemble(&cpu, &register_names, &[], 0xF000, 0xF000, 0, 8),
+            vec![
+                disassembled("0xF000", "85 09", "STA $09     ; COLUBK"),
+                disassembled("0xF002", "A5 09", "LDA $09     ; INPT1"),
+                disassembled("0xF004", "E6 0A", "INC $0A     ; CTRLPF"),
+                disassembled("0xF006", "95 10", "STA $10,X   ; RESP0"),
+                disassembled("0xF008", "B1 09", "LDA ($09),Y"),
+                disassembled("0xF00A", "8D 80 02", "STA $0280   ; SWCHA"),
+                disassembled("0xF00D", "4C 80 02", "JMP $0280"),
+                disassembled("0xF010", "85 80", "STA $80"),
+            ]
+        );
+    }
+
+    #[test]
+    fn disassemble_skips_io_and_unmapped_memory() {
+        let cpu = cpu_with_program(&[0xA9, 0x01, 0xA9, 0x02, 0xA9, 0x03, 0xEA]);
+        let memory_map = [
+            MemoryRegion {
+                start: 0xF002,
+                end: 0xF002,
+                kind: MemoryKind::Io,
+            },
+            MemoryRegion {
+                start: 0xF003,
+                end: 0xF004,
+                kind: MemoryKind::Unmapped,
+            },
+            MemoryRegion {
+                start: 0xF005,
+                end: 0xFFFF,
+                kind: MemoryKind::Rom,
+            },
+        ];
+        assert_eq!(
+            disassemble(
+                &cpu,
+                &RegisterNames::new(),
+                &memory_map,
+                0xF000,
+                0xF000,
+                0,
+                6
+            ),
+            vec![
+                disassembled("0xF000", "A9 01", "LDA #$01"),
+                disassembled("0xF002", "A9", ""),
+                disassembled("0xF003", "02", ""),
+                disassembled("0xF004", "A9", ""),
+                disassembled("0xF005", "03", ""),
+                disassembled("0xF006", "EA", "NOP"),
+            ]
+        );
+    }
+
     /// Tests some incredibly rare edge cases that occur when we perform
     /// wrapping arithmetic operations close to the wrapping point.
     #[test]
@@ -823,7 +1010,7 @@ mod tests {
         cpu.mut_memory().bytes[0xFFFE] = 0x85;
         cpu.mut_memory().bytes[0xFFFF] = 0xEA;
         assert_eq!(
-            disassemble(&cpu, 0xFFFF, 0xFFFE, 0, 1),
+            disassemble(&cpu, &RegisterNames::new(), &[], 0xFFFF, 0xFFFE, 0, 1),
             vec![disassembled("0xFFFE", "85", "")]
         );
 
@@ -831,7 +1018,7 @@ mod tests {
         cpu.mut_memory().bytes[0xFFFF] = 0x85;
         cpu.mut_memory().bytes[0x0000] = 0xEA;
         assert_eq!(
-            disassemble(&cpu, 0x0000, 0xFFFF, 0, 1),
+            disassemble(&cpu, &RegisterNames::new(), &[], 0x0000, 0xFFFF, 0, 1),
             vec![disassembled("0xFFFF", "85", "")]
         );
     }
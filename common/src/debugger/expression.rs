@@ -0,0 +1,517 @@
+//! A tiny, C-like expression language used for breakpoint conditions and
+//! evaluating expressions in the debugger UI. It operates on registers (`A`,
+//! `X`, `Y`, `SP`, `PC`), flags (`N`, `V`, `D`, `I`, `Z`, `C`), symbols loaded
+//! from the assembler output, and memory (`[$80]`). Numbers can be written in
+//! decimal, hexadecimal (`$3F`, `0x3F`, or `$0x3F`), or binary (`0b0011_1111`)
+//! notation. All values are integers; comparisons and logical operators yield
+//! 1 for true and 0 for false.
+
+use crate::debugger::symbols::SymbolTable;
+use std::error;
+use std::fmt;
+use ya6502::cpu::flags;
+use ya6502::cpu::MachineInspector;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(i64),
+    Register(Register),
+    /// A single processor flag, identified by its bit mask.
+    Flag(u8),
+    /// A byte of memory at the address given by the inner expression.
+    Memory(Box<Expression>),
+    Unary(UnaryOperator, Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Register {
+    A,
+    X,
+    Y,
+    SP,
+    PC,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOperator {
+    Not,
+    Negate,
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+impl BinaryOperator {
+    fn from_symbol(symbol: &str) -> Option<Self> {
+        use BinaryOperator::*;
+        match symbol {
+            "||" => Some(Or),
+            "&&" => Some(And),
+            "|" => Some(BitOr),
+            "^" => Some(BitXor),
+            "&" => Some(BitAnd),
+            "==" => Some(Equal),
+            "!=" => Some(NotEqual),
+            "<" => Some(Less),
+            "<=" => Some(LessOrEqual),
+            ">" => Some(Greater),
+            ">=" => Some(GreaterOrEqual),
+            "<<" => Some(ShiftLeft),
+            ">>" => Some(ShiftRight),
+            "+" => Some(Add),
+            "-" => Some(Subtract),
+            "*" => Some(Multiply),
+            "/" => Some(Divide),
+            "%" => Some(Remainder),
+            _ => None,
+        }
+    }
+
+    /// Operator precedence; the higher, the tighter the operator binds.
+    fn precedence(&self) -> u8 {
+        use BinaryOperator::*;
+        match self {
+            Or => 1,
+            And => 2,
+            BitOr => 3,
+            BitXor => 4,
+            BitAnd => 5,
+            Equal | NotEqual => 6,
+            Less | LessOrEqual | Greater | GreaterOrEqual => 7,
+            ShiftLeft | ShiftRight => 8,
+            Add | Subtract => 9,
+            Multiply | Divide | Remainder => 10,
+        }
+    }
+}
+
+impl Expression {
+    pub fn evaluate(&self, inspector: &(impl MachineInspector + ?Sized)) -> EvaluationResult {
+        let value = match self {
+            Self::Number(value) => *value,
+            Self::Register(register) => match register {
+                Register::A => inspector.reg_a() as i64,
+                Register::X => inspector.reg_x() as i64,
+                Register::Y => inspector.reg_y() as i64,
+                Register::SP => inspector.reg_sp() as i64,
+                Register::PC => inspector.reg_pc() as i64,
+            },
+            Self::Flag(mask) => (inspector.flags() & mask != 0) as i64,
+            Self::Memory(address) => {
+                inspector.inspect_memory(address.evaluate(inspector)? as u16) as i64
+            }
+            Self::Unary(operator, operand) => {
+                let operand = operand.evaluate(inspector)?;
+                match operator {
+                    UnaryOperator::Not => (operand == 0) as i64,
+                    UnaryOperator::Negate => operand.wrapping_neg(),
+                    UnaryOperator::BitNot => !operand,
+                }
+            }
+            Self::Binary(operator, lhs, rhs) => {
+                use BinaryOperator::*;
+                let lhs = lhs.evaluate(inspector)?;
+                // Logical operators short-circuit.
+                match operator {
+                    Or if lhs != 0 => return Ok(1),
+                    And if lhs == 0 => return Ok(0),
+                    _ => {}
+                }
+                let rhs = rhs.evaluate(inspector)?;
+                match operator {
+                    Or | And => (rhs != 0) as i64,
+                    BitOr => lhs | rhs,
+                    BitXor => lhs ^ rhs,
+                    BitAnd => lhs & rhs,
+                    Equal => (lhs == rhs) as i64,
+                    NotEqual => (lhs != rhs) as i64,
+                    Less => (lhs < rhs) as i64,
+                    LessOrEqual => (lhs <= rhs) as i64,
+                    Greater => (lhs > rhs) as i64,
+                    GreaterOrEqual => (lhs >= rhs) as i64,
+                    ShiftLeft => lhs.wrapping_shl(rhs as u32),
+                    ShiftRight => lhs.wrapping_shr(rhs as u32),
+                    Add => lhs.wrapping_add(rhs),
+                    Subtract => lhs.wrapping_sub(rhs),
+                    Multiply => lhs.wrapping_mul(rhs),
+                    Divide => lhs
+                        .checked_div(rhs)
+                        .ok_or(EvaluationError::DivisionByZero)?,
+                    Remainder => lhs
+                        .checked_rem(rhs)
+                        .ok_or(EvaluationError::DivisionByZero)?,
+                }
+            }
+        };
+        return Ok(value);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EvaluationError {
+    DivisionByZero,
+}
+
+impl error::Error for EvaluationError {}
+
+impl fmt::Display for EvaluationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DivisionByZero => write!(f, "Division by zero"),
+        }
+    }
+}
+
+pub type EvaluationResult = Result<i64, EvaluationError>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    message: String,
+}
+
+impl ParseError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+pub type ParseResult<T> = Result<T, ParseError>;
+
+/// Parses an expression. See the module documentation for the syntax.
+/// Symbols are resolved to their values while parsing.
+pub fn parse(text: &str, symbols: &SymbolTable) -> ParseResult<Expression> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        position: 0,
+        symbols,
+    };
+    let expression = parser.parse_expression(0)?;
+    if let Some(token) = parser.tokens.get(parser.position) {
+        return Err(ParseError::new(format!("Unexpected {}", token)));
+    }
+    return Ok(expression);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Identifier(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "number {}", value),
+            Token::Identifier(name) => write!(f, "identifier {}", name),
+            Token::Symbol(symbol) => write!(f, "\"{}\"", symbol),
+        }
+    }
+}
+
+/// All symbols recognized by the tokenizer. Longer symbols need to go first,
+/// so that we don't recognize `<=` as `<` followed by `=`.
+const SYMBOLS: &[&str] = &[
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "|", "^", "&", "<", ">", "+", "-", "*", "/",
+    "%", "!", "~", "(", ")", "[", "]",
+];
+
+fn tokenize(text: &str) -> ParseResult<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_alphanumeric() || c == '$' || c == '_' {
+            let length = rest[1..]
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .map_or(rest.len(), |i| i + 1);
+            let word = &rest[..length];
+            tokens.push(if c.is_ascii_digit() || c == '$' {
+                Token::Number(parse_number(word)?)
+            } else {
+                Token::Identifier(word.to_string())
+            });
+            rest = &rest[length..];
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .copied()
+                .find(|symbol| rest.starts_with(symbol))
+                .ok_or_else(|| ParseError::new(format!("Unexpected character: {}", c)))?;
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    return Ok(tokens);
+}
+
+fn parse_number(word: &str) -> ParseResult<i64> {
+    let digits = word.replace('_', "");
+    let result = if let Some(hex) = digits.strip_prefix('$') {
+        i64::from_str_radix(hex.strip_prefix("0x").unwrap_or(hex), 16)
+    } else if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2)
+    } else {
+        digits.parse()
+    };
+    return result.map_err(|_| ParseError::new(format!("Invalid number: {}", word)));
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    symbols: &'a SymbolTable,
+}
+
+impl<'a> Parser<'a> {
+    fn next_token(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        return token;
+    }
+
+    fn expect(&mut self, symbol: &str) -> ParseResult<()> {
+        match self.next_token() {
+            Some(Token::Symbol(s)) if s == symbol => Ok(()),
+            Some(token) => Err(ParseError::new(format!(
+                "Expected \"{}\", got {}",
+                symbol, token
+            ))),
+            None => Err(ParseError::new(format!(
+                "Expected \"{}\", got end of expression",
+                symbol
+            ))),
+        }
+    }
+
+    /// Parses a sequence of binary operations, using the precedence climbing
+    /// method. Only operators with precedence of at least `min_precedence` are
+    /// consumed.
+    fn parse_expression(&mut self, min_precedence: u8) -> ParseResult<Expression> {
+        let mut lhs = self.parse_operand()?;
+        while let Some(Token::Symbol(symbol)) = self.tokens.get(self.position) {
+            let operator = match BinaryOperator::from_symbol(symbol) {
+                Some(operator) if operator.precedence() >= min_precedence => operator,
+                _ => break,
+            };
+            self.position += 1;
+            let rhs = self.parse_expression(operator.precedence() + 1)?;
+            lhs = Expression::Binary(operator, Box::new(lhs), Box::new(rhs));
+        }
+        return Ok(lhs);
+    }
+
+    fn parse_operand(&mut self) -> ParseResult<Expression> {
+        match self.next_token() {
+            Some(Token::Number(value)) => Ok(Expression::Number(value)),
+            Some(Token::Identifier(name)) => self.parse_identifier(&name),
+            Some(Token::Symbol("!")) => self.parse_unary(UnaryOperator::Not),
+            Some(Token::Symbol("-")) => self.parse_unary(UnaryOperator::Negate),
+            Some(Token::Symbol("~")) => self.parse_unary(UnaryOperator::BitNot),
+            Some(Token::Symbol("(")) => {
+                let expression = self.parse_expression(0)?;
+                self.expect(")")?;
+                Ok(expression)
+            }
+            Some(Token::Symbol("[")) => {
+                let address = self.parse_expression(0)?;
+                self.expect("]")?;
+                Ok(Expression::Memory(Box::new(address)))
+            }
+            Some(token) => Err(ParseError::new(format!("Unexpected {}", token))),
+            None => Err(ParseError::new("Unexpected end of expression")),
+        }
+    }
+
+    fn parse_unary(&mut self, operator: UnaryOperator) -> ParseResult<Expression> {
+        Ok(Expression::Unary(operator, Box::new(self.parse_operand()?)))
+    }
+
+    /// Parses a register or flag name, or a symbol. Register and flag names
+    /// are case-insensitive and take precedence over symbols.
+    fn parse_identifier(&self, name: &str) -> ParseResult<Expression> {
+        match name.to_ascii_uppercase().as_str() {
+            "A" => Ok(Expression::Register(Register::A)),
+            "X" => Ok(Expression::Register(Register::X)),
+            "Y" => Ok(Expression::Register(Register::Y)),
+            "SP" => Ok(Expression::Register(Register::SP)),
+            "PC" => Ok(Expression::Register(Register::PC)),
+            "N" => Ok(Expression::Flag(flags::N)),
+            "V" => Ok(Expression::Flag(flags::V)),
+            "D" => Ok(Expression::Flag(flags::D)),
+            "I" => Ok(Expression::Flag(flags::I)),
+            "Z" => Ok(Expression::Flag(flags::Z)),
+            "C" => Ok(Expression::Flag(flags::C)),
+            _ => match self.symbols.get(name) {
+                Some(value) => Ok(Expression::Number(*value)),
+                None => Err(ParseError::new(format!("Unknown identifier: {}", name))),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ya6502::cpu::MockMachineInspector;
+
+    fn inspector() -> MockMachineInspector {
+        let mut inspector = MockMachineInspector::new();
+        inspector.expect_reg_a().return_const(0x3F);
+        inspector.expect_reg_x().return_const(5);
+        inspector.expect_reg_y().return_const(0);
+        inspector.expect_reg_sp().return_const(0xFD);
+        inspector.expect_reg_pc().return_const(0xF00Du16);
+        inspector
+            .expect_flags()
+            .return_const(flags::N | flags::C | flags::UNUSED);
+        inspector
+            .expect_inspect_memory()
+            .returning(|address| (address & 0xFF) as u8 ^ 0xFF);
+        return inspector;
+    }
+
+    fn symbols() -> SymbolTable {
+        SymbolTable::from([("COLUBK".to_string(), 0x09), ("score".to_string(), 0x80)])
+    }
+
+    fn parse(text: &str) -> ParseResult<Expression> {
+        super::parse(text, &symbols())
+    }
+
+    fn evaluate(text: &str) -> i64 {
+        parse(text).unwrap().evaluate(&inspector()).unwrap()
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(evaluate("42"), 42);
+        assert_eq!(evaluate("$3F"), 0x3F);
+        assert_eq!(evaluate("0xABCD"), 0xABCD);
+        assert_eq!(evaluate("0b0000_0101"), 5);
+        assert_eq!(evaluate("$0x80"), 0x80);
+    }
+
+    #[test]
+    fn registers_flags_and_memory() {
+        assert_eq!(evaluate("A"), 0x3F);
+        assert_eq!(evaluate("x"), 5);
+        assert_eq!(evaluate("SP"), 0xFD);
+        assert_eq!(evaluate("PC"), 0xF00D);
+        assert_eq!(evaluate("N"), 1);
+        assert_eq!(evaluate("Z"), 0);
+        assert_eq!(evaluate("C"), 1);
+        assert_eq!(evaluate("[$80]"), 0x7F);
+        assert_eq!(evaluate("[X]"), 0xFA);
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(evaluate("A == 0x3F && X > 4"), 1);
+        assert_eq!(evaluate("A == 0x3F && X > 5"), 0);
+        assert_eq!(evaluate("A != 0x3F || X >= 5"), 1);
+        assert_eq!(evaluate("Y < 1 && X <= 4"), 0);
+        assert_eq!(evaluate("!Z && !(A == 1)"), 1);
+        assert_eq!(evaluate("A & $0F | $40"), 0x4F);
+        assert_eq!(evaluate("A ^ $FF"), 0xC0);
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(evaluate("A + 1"), 0x40);
+        assert_eq!(evaluate("X - 7"), -2);
+        assert_eq!(evaluate("X * 3 / 2"), 7);
+        assert_eq!(evaluate("X % 3"), 2);
+        assert_eq!(evaluate("1 << X"), 32);
+        assert_eq!(evaluate("PC >> 8"), 0xF0);
+        assert_eq!(evaluate("-X"), -5);
+        assert_eq!(evaluate("~A & $FF"), 0xC0);
+        assert_eq!(evaluate("[$80 + X]"), 0x7A);
+    }
+
+    #[test]
+    fn division_by_zero() {
+        let inspector = inspector();
+        assert_eq!(
+            parse("A / Y").unwrap().evaluate(&inspector),
+            Err(EvaluationError::DivisionByZero)
+        );
+        assert_eq!(
+            parse("A % Y").unwrap().evaluate(&inspector),
+            Err(EvaluationError::DivisionByZero)
+        );
+        assert_eq!(parse("Y && A / Y").unwrap().evaluate(&inspector), Ok(0));
+    }
+
+    #[test]
+    fn resolves_symbols() {
+        assert_eq!(evaluate("COLUBK"), 0x09);
+        assert_eq!(evaluate("[score + 1]"), 0x7E);
+        // Registers take precedence over symbols.
+        assert_eq!(
+            super::parse("A", &SymbolTable::from([("A".to_string(), 1)])),
+            Ok(Expression::Register(Register::A))
+        );
+        assert!(parse("SCORE").is_err());
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(evaluate("0 && 0 || 1"), 1);
+        assert_eq!(evaluate("0 && (0 || 1)"), 0);
+        assert_eq!(evaluate("A & 1 == 1"), 1);
+        assert_eq!(evaluate("1 == 1 == 1"), 1);
+        assert_eq!(evaluate("1 + 2 * 3"), 7);
+        assert_eq!(evaluate("(1 + 2) * 3"), 9);
+        assert_eq!(evaluate("1 << 2 + 1"), 8);
+        assert_eq!(evaluate("10 - 4 - 3"), 3);
+        assert_eq!(evaluate("-X + 1"), -4);
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(parse("").is_err());
+        assert!(parse("A ==").is_err());
+        assert!(parse("(A == 1").is_err());
+        assert!(parse("[$80").is_err());
+        assert!(parse("A B").is_err());
+        assert!(parse("Q == 1").is_err());
+        assert!(parse("$XY").is_err());
+        assert!(parse("A = 1").is_err());
+        assert!(parse("A +").is_err());
+        assert!(parse("* 2").is_err());
+    }
+}
@@ -0,0 +1,788 @@
+//! A debug adapter that speaks the GDB Remote Serial Protocol, so that clients
+//! like GDB, LLDB, or IDEs that use them as backends can connect to the
+//! emulator. GDB packets are translated to Debug Adapter Protocol requests,
+//! and responses are translated back, so the adapter supports the same
+//! features as the DAP one.
+//!
+//! Since GDB doesn't know the 6502 architecture, clients need to be told the
+//! register layout: `g` and `G` packets transfer registers A, X, Y, P (flags),
+//! and SP as single bytes, followed by PC as a 16-bit little-endian value.
+//! Registers are numbered in the same order for `p` and `P` packets.
+
+use crate::debugger::adapter::DebugAdapter;
+use crate::debugger::adapter::DebugAdapterResult;
+use crate::debugger::core::DataBreakpointAccessType;
+use crate::debugger::core::StopReason;
+use crate::debugger::dap_types::DataBreakpoint;
+use crate::debugger::dap_types::Event;
+use crate::debugger::dap_types::InstructionBreakpoint;
+use crate::debugger::dap_types::Message;
+use crate::debugger::dap_types::MessageEnvelope;
+use crate::debugger::dap_types::ReadMemoryArguments;
+use crate::debugger::dap_types::Request;
+use crate::debugger::dap_types::Response;
+use crate::debugger::dap_types::ResponseEnvelope;
+use crate::debugger::dap_types::SetDataBreakpointsArguments;
+use crate::debugger::dap_types::SetInstructionBreakpointsArguments;
+use crate::debugger::dap_types::SetVariableArguments;
+use crate::debugger::dap_types::StoppedEvent;
+use crate::debugger::dap_types::ThreadArguments;
+use crate::debugger::dap_types::Variable;
+use crate::debugger::dap_types::VariablesArguments;
+use crate::debugger::dap_types::WriteMemoryArguments;
+use crate::debugger::MAIN_THREAD_ID;
+use crate::debugger::REGISTERS_VARIABLES_REFERENCE;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::sync::mpsc::TryRecvError;
+use std::thread;
+use ya6502::cpu::flags::string_to_flags;
+
+/// Register names, as understood by the debugger, in the order used by the
+/// `g`, `G`, `p`, and `P` packets.
+const REGISTERS: [&str; 6] = ["A", "X", "Y", "FLAGS", "SP", "PC"];
+const PC_REGISTER_INDEX: usize = 5;
+
+/// A byte sent by the client to interrupt a running program.
+const INTERRUPT: u8 = 0x03;
+
+/// Signal numbers reported in stop replies.
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+/// Listens for GDB connections on a TCP port. Like [`TcpDebugAdapter`], it
+/// only accepts a single connection at a time.
+///
+/// [`TcpDebugAdapter`]: crate::debugger::adapter::TcpDebugAdapter
+pub struct GdbAdapter {
+    input: mpsc::Receiver<GdbInput>,
+    output: RefCell<Option<Box<dyn Write + Send>>>,
+    state: RefCell<GdbState>,
+}
+
+/// Events produced by the thread that handles incoming connections.
+pub enum GdbInput {
+    Connected(Box<dyn Write + Send>),
+    Packet(String),
+    Interrupt,
+    Disconnected,
+}
+
+/// Describes how to reply to the client once a response to a given request
+/// arrives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reply {
+    /// The request is a part of a command that is replied to by another one.
+    None,
+    Ok,
+    Registers,
+    Register(usize),
+    Memory,
+    /// The reply is sent once the machine stops.
+    Stop,
+}
+
+#[derive(Default)]
+struct GdbState {
+    sequence_number: i64,
+    pending_requests: VecDeque<(Request, Reply)>,
+    /// Replies to requests that have been passed to the debugger, in order.
+    awaited_responses: VecDeque<Reply>,
+    breakpoints: Vec<u16>,
+    watchpoints: Vec<(u16, DataBreakpointAccessType)>,
+    /// Set when the client waits for the machine to stop.
+    running: bool,
+    last_signal: Option<u8>,
+}
+
+impl GdbAdapter {
+    /// Creates a new `GdbAdapter` and starts listening on a given port.
+    pub fn new(port: u16) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::Builder::new()
+            .name("gdb reader thread".into())
+            .spawn(move || {
+                let address = SocketAddr::from(([127, 0, 0, 1], port));
+                let listener = TcpListener::bind(address).expect("Unable to listen for GDB");
+                eprintln!("Listening for GDB at {}...", address);
+                loop {
+                    let (connection, address) =
+                        listener.accept().expect("Unable to accept a connection");
+                    eprintln!("GDB connection accepted from {}", address);
+                    if let Err(e) = handle_connection(connection, &tx) {
+                        eprintln!("GDB connection error: {}", e);
+                    }
+                    if tx.send(GdbInput::Disconnected).is_err() {
+                        return;
+                    }
+                }
+            })
+            .expect("Unable to start the GDB reader thread");
+        return Self::with_input(rx);
+    }
+
+    /// Creates an adapter that receives connections and packets from a given
+    /// channel.
+    pub fn with_input(input: mpsc::Receiver<GdbInput>) -> Self {
+        Self {
+            input,
+            output: RefCell::new(None),
+            state: RefCell::new(GdbState::default()),
+        }
+    }
+
+    fn send_packet(&self, data: &str) {
+        if let Some(output) = &mut *self.output.borrow_mut() {
+            if let Err(e) = write_packet(output, data) {
+                eprintln!("Unable to send a GDB packet: {}", e);
+            }
+        }
+    }
+
+    fn handle_input(&self, input: GdbInput) {
+        match input {
+            GdbInput::Connected(output) => {
+                *self.output.borrow_mut() = Some(output);
+                // The client expects the program to be stopped once it
+                // connects.
+                let mut state = self.state.borrow_mut();
+                *state = GdbState {
+                    sequence_number: state.sequence_number,
+                    ..GdbState::default()
+                };
+                state.queue(Request::Pause(main_thread()), Reply::None);
+            }
+            GdbInput::Packet(packet) => {
+                let result = self.state.borrow_mut().requests(&packet);
+                match result {
+                    Ok(requests) if requests.is_empty() => {
+                        let reply = self.state.borrow().immediate_reply(&packet);
+                        self.send_packet(&reply);
+                    }
+                    Ok(requests) => self.state.borrow_mut().pending_requests.extend(requests),
+                    Err(()) => self.send_packet("E01"),
+                }
+            }
+            GdbInput::Interrupt => {
+                self.state
+                    .borrow_mut()
+                    .queue(Request::Pause(main_thread()), Reply::None);
+            }
+            GdbInput::Disconnected => {
+                // Let the machine run once the client is gone.
+                self.state
+                    .borrow_mut()
+                    .queue(Request::Disconnect(None), Reply::None);
+            }
+        }
+    }
+
+    /// Reports a rejected request to the client, unless the request was an
+    /// intermediate step of a packet that doesn't expect a reply yet.
+    fn handle_error_response(&self) {
+        let reply = self.state.borrow_mut().awaited_responses.pop_front();
+        match reply {
+            None | Some(Reply::None) => {}
+            Some(_) => self.send_packet("E01"),
+        }
+    }
+
+    fn handle_response(&self, response: Response) {
+        let reply = self.state.borrow_mut().awaited_responses.pop_front();
+        match (reply, response) {
+            (Some(Reply::Ok), _) => self.send_packet("OK"),
+            (Some(Reply::Registers), Response::Variables(response)) => {
+                let mut values = register_values(&response.variables);
+                let pc = values.pop().unwrap();
+                let mut data: String = values
+                    .iter()
+                    .map(|value| format!("{:02x}", value))
+                    .collect();
+                data += &encode_hex(&pc.to_le_bytes());
+                self.send_packet(&data);
+            }
+            (Some(Reply::Register(index)), Response::Variables(response)) => {
+                let value = register_values(&response.variables)[index];
+                self.send_packet(&if index == PC_REGISTER_INDEX {
+                    encode_hex(&value.to_le_bytes())
+                } else {
+                    format!("{:02x}", value)
+                });
+            }
+            (Some(Reply::Memory), Response::ReadMemory(response)) => {
+                let data = base64::decode(&response.data).unwrap_or_default();
+                self.send_packet(&encode_hex(&data));
+            }
+            (Some(Reply::Stop), _) => self.state.borrow_mut().running = true,
+            _ => {}
+        }
+    }
+
+    fn handle_event(&self, event: Event) {
+        if let Event::Stopped(StoppedEvent { reason, .. }) = event {
+            let signal = match reason {
+                StopReason::Pause => SIGINT,
+                StopReason::Exception => SIGILL,
+                _ => SIGTRAP,
+            };
+            let mut state = self.state.borrow_mut();
+            state.last_signal = Some(signal);
+            if state.running {
+                state.running = false;
+                drop(state);
+                self.send_packet(&format!("S{:02x}", signal));
+            }
+        }
+    }
+}
+
+impl GdbState {
+    fn queue(&mut self, request: Request, reply: Reply) {
+        self.pending_requests.push_back((request, reply));
+    }
+
+    /// Translates a packet to a list of debugger requests. Packets that can
+    /// be replied to right away produce no requests.
+    fn requests(&mut self, packet: &str) -> Result<Vec<(Request, Reply)>, ()> {
+        let (command, args) = packet.split_at(packet.len().min(1));
+        return Ok(match command {
+            "g" => vec![(registers_request(), Reply::Registers)],
+            "G" => {
+                let bytes = decode_hex(args)?;
+                if bytes.len() != REGISTERS.len() + 1 {
+                    return Err(());
+                }
+                let mut values: Vec<u16> = bytes[..PC_REGISTER_INDEX]
+                    .iter()
+                    .map(|&byte| byte as u16)
+                    .collect();
+                values.push(u16::from_le_bytes([bytes[5], bytes[6]]));
+                let mut requests: Vec<(Request, Reply)> = REGISTERS
+                    .iter()
+                    .zip(values)
+                    .map(|(name, value)| (set_register_request(name, value), Reply::None))
+                    .collect();
+                requests.last_mut().unwrap().1 = Reply::Ok;
+                requests
+            }
+            "p" => {
+                let index = register_index(args)?;
+                vec![(registers_request(), Reply::Register(index))]
+            }
+            "P" => {
+                let (index, value) = args.split_once('=').ok_or(())?;
+                let index = register_index(index)?;
+                let bytes = decode_hex(value)?;
+                let value = match bytes[..] {
+                    [byte] => byte as u16,
+                    [low, high] => u16::from_le_bytes([low, high]),
+                    _ => return Err(()),
+                };
+                vec![(set_register_request(REGISTERS[index], value), Reply::Ok)]
+            }
+            "m" => {
+                let (address, length) = parse_address_and_length(args)?;
+                vec![(
+                    Request::ReadMemory(ReadMemoryArguments {
+                        memory_reference: format!("0x{:04X}", address),
+                        offset: None,
+                        count: length as i64,
+                    }),
+                    Reply::Memory,
+                )]
+            }
+            "M" => {
+                let (range, data) = args.split_once(':').ok_or(())?;
+                let (address, length) = parse_address_and_length(range)?;
+                let data = decode_hex(data)?;
+                if data.len() != length {
+                    return Err(());
+                }
+                vec![(
+                    Request::WriteMemory(WriteMemoryArguments {
+                        memory_reference: format!("0x{:04X}", address),
+                        offset: None,
+                        data: base64::encode(data),
+                    }),
+                    Reply::Ok,
+                )]
+            }
+            "c" | "s" => {
+                let mut requests = vec![];
+                if !args.is_empty() {
+                    let address = parse_hex(args)?;
+                    requests.push((set_register_request("PC", address), Reply::None));
+                }
+                requests.push(if command == "c" {
+                    (Request::Continue(main_thread()), Reply::Stop)
+                } else {
+                    (Request::StepIn(main_thread()), Reply::Stop)
+                });
+                requests
+            }
+            "Z" | "z" => {
+                let mut parts = args.split(',');
+                let kind = parts.next().ok_or(())?;
+                let address = parse_hex(parts.next().ok_or(())?)?;
+                let length = match parts.next() {
+                    Some(length) => parse_hex(length)?.max(1),
+                    None => 1,
+                };
+                let insert = command == "Z";
+                let request = match kind {
+                    "0" | "1" => {
+                        self.breakpoints.retain(|breakpoint| *breakpoint != address);
+                        if insert {
+                            self.breakpoints.push(address);
+                        }
+                        self.instruction_breakpoints_request()
+                    }
+                    "2" | "3" | "4" => {
+                        let access_type = match kind {
+                            "2" => DataBreakpointAccessType::Write,
+                            "3" => DataBreakpointAccessType::Read,
+                            _ => DataBreakpointAccessType::ReadWrite,
+                        };
+                        for watched in (0..length).map(|offset| address.wrapping_add(offset)) {
+                            let watchpoint = (watched, access_type);
+                            self.watchpoints.retain(|existing| *existing != watchpoint);
+                            if insert {
+                                self.watchpoints.push(watchpoint);
+                            }
+                        }
+                        self.data_breakpoints_request()
+                    }
+                    _ => return Ok(vec![]),
+                };
+                vec![(request, Reply::Ok)]
+            }
+            "D" => vec![(Request::Disconnect(None), Reply::Ok)],
+            "k" => vec![(Request::Disconnect(None), Reply::None)],
+            "?" if self.last_signal.is_none() => {
+                // The machine hasn't stopped yet after connecting; reply once
+                // it does.
+                vec![(Request::Pause(main_thread()), Reply::Stop)]
+            }
+            _ => vec![],
+        });
+    }
+
+    /// Returns a reply to a packet that doesn't require talking to the
+    /// debugger. An empty reply means that the packet is not supported.
+    fn immediate_reply(&self, packet: &str) -> String {
+        return match packet {
+            "?" => format!("S{:02x}", self.last_signal.unwrap_or(SIGTRAP)),
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            "qSymbol::" => "OK".to_string(),
+            _ if packet.starts_with("qSupported") => "PacketSize=1000".to_string(),
+            _ if packet.starts_with('H') => "OK".to_string(),
+            _ => "".to_string(),
+        };
+    }
+
+    fn instruction_breakpoints_request(&self) -> Request {
+        Request::SetInstructionBreakpoints(SetInstructionBreakpointsArguments {
+            breakpoints: self
+                .breakpoints
+                .iter()
+                .map(|address| InstructionBreakpoint {
+                    instruction_reference: format!("0x{:04X}", address),
+                    offset: None,
+                    condition: None,
+                    hit_condition: None,
+                })
+                .collect(),
+        })
+    }
+
+    fn data_breakpoints_request(&self) -> Request {
+        Request::SetDataBreakpoints(SetDataBreakpointsArguments {
+            breakpoints: self
+                .watchpoints
+                .iter()
+                .map(|(address, access_type)| DataBreakpoint {
+                    data_id: format!("0x{:04X}", address),
+                    access_type: Some(*access_type),
+                })
+                .collect(),
+        })
+    }
+}
+
+impl DebugAdapter for GdbAdapter {
+    fn try_receive_message(&self) -> DebugAdapterResult<MessageEnvelope> {
+        loop {
+            let mut state = self.state.borrow_mut();
+            if let Some((request, reply)) = state.pending_requests.pop_front() {
+                state.awaited_responses.push_back(reply);
+                state.sequence_number += 1;
+                return Ok(MessageEnvelope {
+                    seq: state.sequence_number,
+                    message: Message::Request(request),
+                });
+            }
+            drop(state);
+            match self.input.try_recv() {
+                Ok(input) => self.handle_input(input),
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => {
+                    return Err(TryRecvError::Empty.into())
+                }
+            }
+        }
+    }
+
+    fn send_message(&self, message: MessageEnvelope) -> DebugAdapterResult<()> {
+        match message.message {
+            Message::Response(ResponseEnvelope { response, .. }) => self.handle_response(response),
+            Message::ErrorResponse(_) => self.handle_error_response(),
+            Message::Event(event) => self.handle_event(event),
+            Message::Request(_) => {}
+        }
+        Ok(())
+    }
+
+    fn disconnect(&self) -> DebugAdapterResult<()> {
+        *self.output.borrow_mut() = None;
+        Ok(())
+    }
+}
+
+fn handle_connection(
+    connection: TcpStream,
+    sender: &mpsc::Sender<GdbInput>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    sender.send(GdbInput::Connected(Box::new(connection.try_clone()?)))?;
+    let mut acknowledgements = connection.try_clone()?;
+    read_packets(connection, &mut acknowledgements, sender)?;
+    Ok(())
+}
+
+/// Reads packets and interrupts until the end of input, acknowledging each
+/// packet. Packets with invalid checksums are rejected, so that the client
+/// sends them again.
+fn read_packets(
+    input: impl Read,
+    acknowledgements: &mut impl Write,
+    sender: &mpsc::Sender<GdbInput>,
+) -> io::Result<()> {
+    let mut bytes = BufReader::new(input).bytes();
+    while let Some(byte) = bytes.next() {
+        let input = match byte? {
+            INTERRUPT => GdbInput::Interrupt,
+            b'$' => {
+                let mut data = vec![];
+                let mut checksum = vec![];
+                for byte in bytes.by_ref() {
+                    let byte = byte?;
+                    if byte == b'#' {
+                        break;
+                    }
+                    data.push(byte);
+                }
+                for byte in bytes.by_ref().take(2) {
+                    checksum.push(byte?);
+                }
+                let valid = std::str::from_utf8(&checksum)
+                    .ok()
+                    .and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+                    == Some(packet_checksum(&data));
+                acknowledgements.write_all(if valid { b"+" } else { b"-" })?;
+                if !valid {
+                    continue;
+                }
+                GdbInput::Packet(String::from_utf8_lossy(&data).into_owned())
+            }
+            // Acknowledgements and anything outside of packets.
+            _ => continue,
+        };
+        if sender.send(input).is_err() {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+fn write_packet(output: &mut impl Write, data: &str) -> io::Result<()> {
+    write!(output, "${}#{:02x}", data, packet_checksum(data.as_bytes()))?;
+    output.flush()
+}
+
+fn packet_checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn main_thread() -> ThreadArguments {
+    ThreadArguments {
+        thread_id: MAIN_THREAD_ID,
+    }
+}
+
+fn registers_request() -> Request {
+    Request::Variables(VariablesArguments {
+        variables_reference: REGISTERS_VARIABLES_REFERENCE,
+    })
+}
+
+fn set_register_request(name: &str, value: u16) -> Request {
+    Request::SetVariable(SetVariableArguments {
+        variables_reference: REGISTERS_VARIABLES_REFERENCE,
+        name: name.to_string(),
+        value: format!("${:X}", value),
+    })
+}
+
+/// Extracts register values from a register variable list, in the order of
+/// [`REGISTERS`].
+fn register_values(variables: &[Variable]) -> Vec<u16> {
+    REGISTERS
+        .iter()
+        .map(|name| {
+            let value = variables
+                .iter()
+                .find(|variable| variable.name == *name)
+                .map(|variable| variable.value.as_str())
+                .unwrap_or_default();
+            match value.strip_prefix('$') {
+                Some(hex) => u16::from_str_radix(hex, 16).unwrap_or(0),
+                None => string_to_flags(value).unwrap_or(0) as u16,
+            }
+        })
+        .collect()
+}
+
+fn register_index(text: &str) -> Result<usize, ()> {
+    let index = usize::from_str_radix(text, 16).map_err(|_| ())?;
+    return if index < REGISTERS.len() {
+        Ok(index)
+    } else {
+        Err(())
+    };
+}
+
+fn parse_hex(text: &str) -> Result<u16, ()> {
+    u16::from_str_radix(text, 16).map_err(|_| ())
+}
+
+fn parse_address_and_length(text: &str) -> Result<(u16, usize), ()> {
+    let (address, length) = text.split_once(',').ok_or(())?;
+    let length = usize::from_str_radix(length, 16).map_err(|_| ())?;
+    return Ok((parse_hex(address)?, length));
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(text: &str) -> Result<Vec<u8>, ()> {
+    if text.len() % 2 != 0 || !text.is_ascii() {
+        return Err(());
+    }
+    return (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| ()))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::Debugger;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use ya6502::cpu::opcodes;
+    use ya6502::cpu::Cpu;
+    use ya6502::cpu::MachineInspector;
+    use ya6502::memory::Ram;
+    use ya6502::test_utils::cpu_with_program;
+
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedOutput {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn packet(data: &str) -> String {
+        let mut output = vec![];
+        write_packet(&mut output, data).unwrap();
+        return String::from_utf8(output).unwrap();
+    }
+
+    struct Fixture {
+        input: mpsc::Sender<GdbInput>,
+        output: SharedOutput,
+        debugger: Debugger<GdbAdapter>,
+        cpu: Cpu<Ram>,
+    }
+
+    impl Fixture {
+        /// Creates a machine with a GDB client connected to it.
+        fn new(program: &[u8]) -> Self {
+            let (input, rx) = mpsc::channel();
+            let output = SharedOutput::default();
+            let mut debugger = Debugger::new(GdbAdapter::with_input(rx));
+            let mut cpu = cpu_with_program(program);
+            debugger.update(&cpu).unwrap();
+            input
+                .send(GdbInput::Connected(Box::new(output.clone())))
+                .unwrap();
+            debugger.process_messages(&mut cpu);
+            Self {
+                input,
+                output,
+                debugger,
+                cpu,
+            }
+        }
+
+        /// Sends a packet and returns the reply.
+        fn send(&mut self, data: &str) -> String {
+            self.input.send(GdbInput::Packet(data.to_string())).unwrap();
+            self.debugger.process_messages(&mut self.cpu);
+            return self.output.take();
+        }
+
+        /// Runs the CPU until the debugger stops it, and returns the output.
+        fn run(&mut self) -> String {
+            for _ in 0..1000 {
+                if self.debugger.stopped() {
+                    break;
+                }
+                self.cpu.tick().unwrap();
+                self.debugger.update(&self.cpu).unwrap();
+            }
+            self.debugger.process_messages(&mut self.cpu);
+            return self.output.take();
+        }
+    }
+
+    #[test]
+    fn reads_packets() {
+        let (tx, rx) = mpsc::channel();
+        let mut acknowledgements = vec![];
+        let input = b"+$g#67$m0,2#00\x03$qC#b4";
+        read_packets(&input[..], &mut acknowledgements, &tx).unwrap();
+
+        assert_eq!(acknowledgements, b"+-+");
+        assert!(matches!(rx.try_recv(), Ok(GdbInput::Packet(data)) if data == "g"));
+        assert!(matches!(rx.try_recv(), Ok(GdbInput::Interrupt)));
+        assert!(matches!(rx.try_recv(), Ok(GdbInput::Packet(data)) if data == "qC"));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn writes_packets() {
+        assert_eq!(packet("OK"), "$OK#9a");
+        assert_eq!(packet(""), "$#00");
+    }
+
+    #[test]
+    fn general_queries() {
+        let mut fixture = Fixture::new(&[opcodes::NOP]);
+        assert_eq!(fixture.send("?"), packet("S02"));
+        assert_eq!(fixture.send("qAttached"), packet("1"));
+        assert_eq!(fixture.send("vMustReplyEmpty"), packet(""));
+    }
+
+    #[test]
+    fn registers() {
+        let mut fixture = Fixture::new(&[opcodes::NOP]);
+        assert_eq!(fixture.send("G0a0b0c241d00f0"), packet("OK"));
+        assert_eq!(fixture.cpu.reg_a(), 0x0A);
+        assert_eq!(fixture.cpu.reg_x(), 0x0B);
+        assert_eq!(fixture.cpu.reg_y(), 0x0C);
+        assert_eq!(fixture.cpu.flags(), 0x24);
+        assert_eq!(fixture.cpu.reg_sp(), 0x1D);
+        assert_eq!(fixture.cpu.reg_pc(), 0xF000);
+        assert_eq!(fixture.send("g"), packet("0a0b0c241d00f0"));
+
+        assert_eq!(fixture.send("P1=7f"), packet("OK"));
+        assert_eq!(fixture.send("p1"), packet("7f"));
+        assert_eq!(fixture.send("P5=01f0"), packet("OK"));
+        assert_eq!(fixture.send("p5"), packet("01f0"));
+        assert_eq!(fixture.send("p6"), packet("E01"));
+    }
+
+    #[test]
+    fn memory() {
+        let mut fixture = Fixture::new(&[opcodes::NOP]);
+        assert_eq!(fixture.send("M80,3:123456"), packet("OK"));
+        assert_eq!(fixture.cpu.inspect_memory(0x82), 0x56);
+        assert_eq!(fixture.send("m7f,5"), packet("0012345600"));
+        assert_eq!(fixture.send("M80,3:1234"), packet("E01"));
+    }
+
+    #[test]
+    fn stepping_and_breakpoints() {
+        let mut fixture = Fixture::new(&[
+            opcodes::LDA_IMM,
+            0x45,
+            opcodes::STA_ZP,
+            0x80,
+            opcodes::NOP,
+            opcodes::NOP,
+        ]);
+        assert_eq!(fixture.send("s"), "");
+        assert_eq!(fixture.run(), packet("S05"));
+        assert_eq!(fixture.cpu.reg_pc(), 0xF002);
+
+        assert_eq!(fixture.send("Z0,f005,1"), packet("OK"));
+        assert_eq!(fixture.send("c"), "");
+        assert_eq!(fixture.run(), packet("S05"));
+        assert_eq!(fixture.cpu.reg_pc(), 0xF005);
+        assert_eq!(fixture.cpu.inspect_memory(0x80), 0x45);
+
+        assert_eq!(fixture.send("z0,f005,1"), packet("OK"));
+        assert_eq!(fixture.send("Z2,80,1"), packet("OK"));
+        assert_eq!(fixture.send("cf000"), "");
+        assert_eq!(fixture.run(), packet("S05"));
+        assert_eq!(fixture.cpu.reg_pc(), 0xF004);
+    }
+
+    #[test]
+    fn interrupts() {
+        let mut fixture = Fixture::new(&[opcodes::JMP_ABS, 0x00, 0xF0]);
+        fixture.send("c");
+        fixture.cpu.ticks(10).unwrap();
+        fixture.debugger.update(&fixture.cpu).unwrap();
+        assert!(!fixture.debugger.stopped());
+
+        fixture.input.send(GdbInput::Interrupt).unwrap();
+        fixture.debugger.process_messages(&mut fixture.cpu);
+        assert_eq!(fixture.output.take(), packet("S02"));
+        assert!(fixture.debugger.stopped());
+    }
+
+    #[test]
+    fn resumes_after_disconnecting() {
+        let mut fixture = Fixture::new(&[opcodes::NOP]);
+        assert!(fixture.debugger.stopped());
+        fixture.input.send(GdbInput::Disconnected).unwrap();
+        fixture.debugger.process_messages(&mut fixture.cpu);
+        assert!(!fixture.debugger.stopped());
+    }
+}
@@ -0,0 +1,194 @@
+use bounded_vec_deque::BoundedVecDeque;
+use std::mem::take;
+use ya6502::cpu::MachineInspector;
+use ya6502::cpu::MemoryAccess;
+use ya6502::cpu::Register;
+
+/// Keeps snapshots of the machine state taken at the start of recently
+/// executed instructions, so that the debugger can go back in time. Each
+/// snapshot consists of CPU registers, memory changes made by the instruction,
+/// and an arbitrary piece of state provided by the owner of the history.
+///
+/// Only the CPU registers and memory are rewound; other chips keep their
+/// current state. Since the machine can only be inspected, and not cloned, the
+/// previous memory values are taken from a shadow copy of the address space.
+/// Memory-mapped registers don't necessarily read back what has been written
+/// to them, so a change is only reverted if the memory still holds the value
+/// that the instruction left there.
+pub struct History<T> {
+    snapshots: BoundedVecDeque<Snapshot<T>>,
+    memory: Vec<u8>,
+}
+
+struct Snapshot<T> {
+    registers: [(Register, u16); 6],
+    memory_changes: Vec<MemoryChange>,
+    state: T,
+}
+
+struct MemoryChange {
+    address: u16,
+    old_value: u8,
+    new_value: u8,
+}
+
+impl<T> History<T> {
+    /// Creates an empty history that keeps at most `max_len` snapshots. The
+    /// memory is allocated as the snapshots are recorded.
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            snapshots: BoundedVecDeque::with_capacity(0, max_len),
+            memory: vec![],
+        }
+    }
+
+    /// Reads the machine state. Expected to be called after every single
+    /// cycle. The `state` function is only called if the CPU is about to
+    /// execute an instruction.
+    pub fn update(
+        &mut self,
+        inspector: &(impl MachineInspector + ?Sized),
+        state: impl FnOnce() -> T,
+    ) {
+        if self.memory.is_empty() {
+            self.memory = (0..=0xFFFF)
+                .map(|address| inspector.inspect_memory(address))
+                .collect();
+        }
+        if let Some(MemoryAccess::Write(address)) = inspector.last_memory_access() {
+            let old_value = self.memory[address as usize];
+            let new_value = inspector.inspect_memory(address);
+            if new_value != old_value {
+                self.memory[address as usize] = new_value;
+                if let Some(snapshot) = self.snapshots.back_mut() {
+                    snapshot.memory_changes.push(MemoryChange {
+                        address,
+                        old_value,
+                        new_value,
+                    });
+                }
+            }
+        }
+        if inspector.at_instruction_start() {
+            self.snapshots.push_back(Snapshot {
+                registers: [
+                    (Register::A, inspector.reg_a() as u16),
+                    (Register::X, inspector.reg_x() as u16),
+                    (Register::Y, inspector.reg_y() as u16),
+                    (Register::SP, inspector.reg_sp() as u16),
+                    (Register::Flags, inspector.flags() as u16),
+                    (Register::PC, inspector.reg_pc()),
+                ],
+                memory_changes: vec![],
+                state: state(),
+            });
+        }
+    }
+
+    /// Brings the machine back to the start of the previous instruction and
+    /// returns the state that was recorded along with it. Returns `None` if
+    /// there is no previous instruction in the history.
+    pub fn step_back(&mut self, inspector: &mut (impl MachineInspector + ?Sized)) -> Option<&T> {
+        if self.snapshots.len() < 2 {
+            return None;
+        }
+        let current = self.snapshots.pop_back().unwrap();
+        revert(&mut self.memory, inspector, &current.memory_changes);
+        let previous = self.snapshots.back_mut().unwrap();
+        revert(
+            &mut self.memory,
+            inspector,
+            &take(&mut previous.memory_changes),
+        );
+        for (register, value) in previous.registers {
+            inspector.set_register(register, value);
+        }
+        return Some(&previous.state);
+    }
+
+    /// Forgets all the recorded snapshots. Needs to be called whenever the
+    /// memory is modified by anything else than the CPU.
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.memory.clear();
+    }
+}
+
+fn revert(
+    memory: &mut [u8],
+    inspector: &mut (impl MachineInspector + ?Sized),
+    changes: &[MemoryChange],
+) {
+    for change in changes.iter().rev() {
+        if inspector.inspect_memory(change.address) == change.new_value {
+            // If the write fails, the current value is recorded below.
+            let _ = inspector.poke_memory(change.address, change.old_value);
+        }
+        memory[change.address as usize] = inspector.inspect_memory(change.address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ya6502::cpu::Cpu;
+    use ya6502::cpu_with_code;
+    use ya6502::memory::Ram;
+
+    fn run(history: &mut History<u16>, cpu: &mut Cpu<Ram>, n_ticks: u32) {
+        for _ in 0..n_ticks {
+            cpu.tick().unwrap();
+            history.update(cpu, || cpu.reg_pc());
+        }
+    }
+
+    #[test]
+    fn steps_back() {
+        let mut cpu = cpu_with_code! {
+                ldx #0x01      // 0xF000
+                stx 0x80       // 0xF002
+                inx            // 0xF004
+                stx 0x80       // 0xF005
+                inx            // 0xF007
+        };
+        cpu.poke_memory(0x80, 0xAA).unwrap();
+        let mut history = History::new(10);
+        history.update(&cpu, || cpu.reg_pc());
+        run(&mut history, &mut cpu, 2 + 3 + 2 + 3);
+        assert_eq!(cpu.reg_pc(), 0xF007);
+        assert_eq!((cpu.reg_x(), cpu.inspect_memory(0x80)), (0x02, 0x02));
+
+        assert_eq!(history.step_back(&mut cpu), Some(&0xF005));
+        assert_eq!(cpu.reg_pc(), 0xF005);
+        assert_eq!((cpu.reg_x(), cpu.inspect_memory(0x80)), (0x02, 0x01));
+
+        assert_eq!(history.step_back(&mut cpu), Some(&0xF004));
+        assert_eq!(history.step_back(&mut cpu), Some(&0xF002));
+        assert_eq!((cpu.reg_x(), cpu.inspect_memory(0x80)), (0x01, 0xAA));
+
+        // Going forward again records a new history.
+        run(&mut history, &mut cpu, 3 + 2);
+        assert_eq!(cpu.reg_pc(), 0xF005);
+        assert_eq!((cpu.reg_x(), cpu.inspect_memory(0x80)), (0x02, 0x01));
+        assert_eq!(history.step_back(&mut cpu), Some(&0xF004));
+        assert_eq!(history.step_back(&mut cpu), Some(&0xF002));
+        assert_eq!(history.step_back(&mut cpu), Some(&0xF000));
+        assert_eq!(history.step_back(&mut cpu), None);
+        assert_eq!(cpu.reg_pc(), 0xF000);
+    }
+
+    #[test]
+    fn forgets_old_snapshots() {
+        let mut cpu = cpu_with_code! {
+            loop:
+                inx            // 0xF000
+                jmp loop       // 0xF001
+        };
+        let mut history = History::new(3);
+        history.update(&cpu, || cpu.reg_pc());
+        run(&mut history, &mut cpu, 5 * (2 + 3));
+        assert_eq!(history.step_back(&mut cpu), Some(&0xF001));
+        assert_eq!(history.step_back(&mut cpu), Some(&0xF000));
+        assert_eq!(history.step_back(&mut cpu), None);
+    }
+}
@@ -1,22 +1,53 @@
 pub mod adapter;
+pub mod coverage;
 pub mod dap_types;
+pub mod gdb;
+pub mod monitor;
+pub mod symbols;
+pub mod trace;
 
+mod asm;
 mod core;
 mod disasm;
+mod expression;
+mod history;
 mod protocol;
 mod tests;
 
+use crate::cheats::parse_cheat_address;
+use crate::cheats::Cheat;
+use crate::cheats::CheatCommand;
+use crate::cheats::CheatError;
 use crate::debugger::adapter::DebugAdapter;
 use crate::debugger::adapter::DebugAdapterError;
 use crate::debugger::adapter::DebugAdapterResult;
+use crate::debugger::asm::assemble;
+use crate::debugger::core::DataBreakpoint;
+use crate::debugger::core::DataBreakpointAccessType;
 use crate::debugger::core::DebuggerCore;
+use crate::debugger::core::EventBreakpoint;
+use crate::debugger::core::HitCondition;
+use crate::debugger::core::InstructionBreakpoint;
 use crate::debugger::core::StopReason;
+use crate::debugger::dap_types::AssembleArguments;
+use crate::debugger::dap_types::AssembleResponse;
 use crate::debugger::dap_types::Breakpoint;
 use crate::debugger::dap_types::Capabilities;
+use crate::debugger::dap_types::Chip;
+use crate::debugger::dap_types::ChipRegister;
+use crate::debugger::dap_types::ChipStateResponse;
+use crate::debugger::dap_types::DataBreakpointInfoArguments;
+use crate::debugger::dap_types::DataBreakpointInfoResponse;
 use crate::debugger::dap_types::DisassembleArguments;
 use crate::debugger::dap_types::DisassembleResponse;
+use crate::debugger::dap_types::ErrorResponseEnvelope;
+use crate::debugger::dap_types::EvaluateArguments;
+use crate::debugger::dap_types::EvaluateResponse;
 use crate::debugger::dap_types::Event;
 use crate::debugger::dap_types::InitializeArguments;
+use crate::debugger::dap_types::LaunchArguments;
+use crate::debugger::dap_types::MemoryMapResponse;
+use crate::debugger::dap_types::MemoryRegion;
 use crate::debugger::dap_types::Message;
 use crate::debugger::dap_types::MessageEnvelope;
 use crate::debugger::dap_types::ReadMemoryArguments;
@@ -28,8 +59,14 @@ use crate::debugger::dap_types::Scope;
 use crate::debugger::dap_types::ScopePresentationHint;
 use crate::debugger::dap_types::ScopesArguments;
 use crate::debugger::dap_types::ScopesResponse;
+use crate::debugger::dap_types::SetDataBreakpointsArguments;
+use crate::debugger::dap_types::SetDataBreakpointsResponse;
+use crate::debugger::dap_types::SetFunctionBreakpointsArguments;
+use crate::debugger::dap_types::SetFunctionBreakpointsResponse;
 use crate::debugger::dap_types::SetInstructionBreakpointsArguments;
 use crate::debugger::dap_types::SetInstructionBreakpointsResponse;
+use crate::debugger::dap_types::SetVariableArguments;
+use crate::debugger::dap_types::SetVariableResponse;
 use crate::debugger::dap_types::StackFrame;
 use crate::debugger::dap_types::StackTraceResponse;
 use crate::debugger::dap_types::StoppedEvent;
@@ -38,14 +75,26 @@ use crate::debugger::dap_types::ThreadsResponse;
 use crate::debugger::dap_types::Variable;
 use crate::debugger::dap_types::VariablesArguments;
 use crate::debugger::dap_types::VariablesResponse;
+use crate::debugger::dap_types::WriteMemoryArguments;
+use crate::debugger::dap_types::WriteMemoryResponse;
 use crate::debugger::disasm::disassemble;
+use crate::debugger::disasm::may_contain_code;
 use crate::debugger::disasm::seek_instruction;
+use crate::debugger::expression::ParseError;
+use crate::debugger::expression::ParseResult;
+use crate::debugger::symbols::RegisterNames;
+use crate::debugger::symbols::SymbolTable;
 use std::cmp::max;
 use std::cmp::min;
+use std::error::Error;
+use std::path::PathBuf;
 use std::sync::mpsc::TryRecvError;
 use ya6502::cpu::flags::flags_to_string;
+use ya6502::cpu::flags::string_to_flags;
 use ya6502::cpu::flags::FlagRepresentation;
+use ya6502::cpu::CpuError;
 use ya6502::cpu::MachineInspector;
+use ya6502::cpu::Register;
 
 /// Default margin for disassembling code. Whenever a disassembly request comes
 /// in, we adjust the instruction offset by this number to make sure that we get
@@ -57,57 +106,295 @@ const DISASSEMBLY_MARGIN: usize = 20;
 const REGISTERS_VARIABLES_REFERENCE: i64 = 1;
 const MEMORY_VARIABLES_REFERENCE: i64 = 2;
 
+/// ID of the thread that represents the main CPU of the machine. Additional
+/// threads get subsequent IDs.
+pub const MAIN_THREAD_ID: i64 = 1;
+
+/// Stack frame IDs and variable references are only unique within a thread. To
+/// make them globally unique, the thread index, multiplied by this number, is
+/// added to them.
+const THREAD_ID_SPACING: i64 = 0x1_0000;
+
 /// A debugger for 6502-based machines. Uses Debug Adapter Protocol internally
-/// to communicate with a debugger UI.
+/// to communicate with a debugger UI. Each processor of the machine (for
+/// example, the main CPU and a disk drive CPU) is presented as a separate
+/// thread that can be stepped on its own. Since the processors share the
+/// clock, stopping one of them stops the whole machine.
 pub struct Debugger<A: DebugAdapter> {
     adapter: A,
     sequence_number: i64,
+    threads: Vec<DebuggedThread>,
+    /// Index of the thread that requests which don't specify one (like reading
+    /// the memory) refer to. It's the thread that has been stopped, stepped,
+    /// or inspected most recently.
+    current_thread: usize,
+    symbols: SymbolTable,
+    register_names: RegisterNames,
+    /// Cheat commands issued from the debug console, waiting to be executed
+    /// by the machine controller. See [`Debugger::execute_cheat_commands`].
+    cheat_commands: Vec<PendingCheatCommand>,
+    /// A launch request, waiting to be picked up by the machine controller.
+    launch_request: Option<LaunchRequest>,
+    /// A launch request that has been picked up, but not finished yet. See
+    /// [`Debugger::finish_launch`].
+    pending_launch: Option<PendingLaunch>,
+}
+
+/// Asks the machine controller to load a program and reset the machine. Issued
+/// when the debugger client launches a debugging session.
+#[derive(Debug, PartialEq)]
+pub struct LaunchRequest {
+    /// Program image to load before resetting the machine. If `None`, the
+    /// machine is simply reset.
+    pub program: Option<PathBuf>,
+}
+
+/// Remembers how to respond to a launch request once the machine controller
+/// reports whether the program has been loaded.
+struct PendingLaunch {
+    request_seq: i64,
+    stop_on_entry: bool,
+}
+
+/// A cheat command that will be responded to once the machine controller
+/// executes it.
+struct PendingCheatCommand {
+    request_seq: i64,
+    command: CheatCommand,
+}
+
+/// A single processor of the debugged machine.
+struct DebuggedThread {
+    name: String,
     core: DebuggerCore,
 }
 
+impl DebuggedThread {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            core: DebuggerCore::new(),
+        }
+    }
+}
+
 type RequestOutcome<A> = (
     Response,
     Option<Box<dyn FnOnce(&mut Debugger<A>) -> DebugAdapterResult<()>>>,
 );
 
+/// Outcome of a request that may be rejected. The error message is passed to
+/// the client in an unsuccessful response.
+type RequestResult<A> = Result<RequestOutcome<A>, String>;
+
 impl<A: DebugAdapter> Debugger<A> {
     pub fn new(adapter: A) -> Self {
         Self {
             adapter,
             sequence_number: 0,
-            core: DebuggerCore::new(),
+            threads: vec![DebuggedThread::new("main thread")],
+            current_thread: 0,
+            symbols: SymbolTable::new(),
+            register_names: RegisterNames::new(),
+            cheat_commands: vec![],
+            launch_request: None,
+            pending_launch: None,
+        }
+    }
+
+    /// Sets symbols that can be used in expressions and breakpoint conditions.
+    pub fn with_symbols(mut self, symbols: SymbolTable) -> Self {
+        self.symbols = symbols;
+        return self;
+    }
+
+    /// Sets names of the machine's hardware registers, used to annotate the
+    /// disassembly.
+    pub fn with_register_names(mut self, register_names: RegisterNames) -> Self {
+        self.register_names = register_names;
+        return self;
+    }
+
+    /// Adds a thread that represents another processor of the machine. Threads
+    /// get consecutive IDs, starting with [`MAIN_THREAD_ID`].
+    pub fn with_thread(mut self, name: &str) -> Self {
+        self.threads.push(DebuggedThread::new(name));
+        return self;
+    }
+
+    /// Executes cheat commands issued since the last call using a given
+    /// function, and reports the outcome of each of them to the client.
+    pub fn execute_cheat_commands(
+        &mut self,
+        mut execute: impl FnMut(CheatCommand) -> Result<(), CheatError>,
+    ) -> DebugAdapterResult<()> {
+        for PendingCheatCommand {
+            request_seq,
+            command,
+        } in std::mem::take(&mut self.cheat_commands)
+        {
+            let message = match execute(command) {
+                Ok(()) => {
+                    // Cheats change what the CPU reads, so the recorded
+                    // history no longer applies.
+                    self.mut_core().clear_history();
+                    Message::Response(ResponseEnvelope {
+                        request_seq,
+                        success: true,
+                        response: Response::Evaluate(EvaluateResponse {
+                            result: command.to_string(),
+                            variables_reference: 0,
+                        }),
+                    })
+                }
+                Err(e) => Message::ErrorResponse(ErrorResponseEnvelope {
+                    request_seq,
+                    success: false,
+                    command: "evaluate".to_string(),
+                    message: e.to_string(),
+                }),
+            };
+            self.send_message(message)?;
+        }
+        return Ok(());
+    }
+
+    /// Returns the launch request issued since the last call, if any. The
+    /// caller is expected to load the program, and report the outcome using
+    /// [`Debugger::finish_launch`] before resetting the machine.
+    pub fn take_launch_request(&mut self) -> Option<LaunchRequest> {
+        self.launch_request.take()
+    }
+
+    /// Responds to the launch request. If the program has been loaded, the
+    /// debugger restarts all threads (and stops on entry, if requested);
+    /// otherwise, the error is reported to the client, and the debugger state
+    /// is left untouched.
+    pub fn finish_launch(&mut self, result: Result<(), String>) -> DebugAdapterResult<()> {
+        let PendingLaunch {
+            request_seq,
+            stop_on_entry,
+        } = match self.pending_launch.take() {
+            Some(pending_launch) => pending_launch,
+            None => return Ok(()),
+        };
+        match result {
+            Ok(()) => {
+                for (index, thread) in self.threads.iter_mut().enumerate() {
+                    thread.core.restart(stop_on_entry && index == 0);
+                }
+                self.send_message(Message::Response(ResponseEnvelope {
+                    request_seq,
+                    success: true,
+                    response: Response::Launch,
+                }))
+            }
+            Err(message) => self.send_message(Message::ErrorResponse(ErrorResponseEnvelope {
+                request_seq,
+                success: false,
+                command: "launch".to_string(),
+                message,
+            })),
         }
     }
 
     pub fn stopped(&self) -> bool {
-        self.core.stopped()
+        self.threads.iter().any(|thread| thread.core.stopped())
     }
 
-    pub fn update(&mut self, inspector: &impl MachineInspector) -> DebugAdapterResult<()> {
-        self.core.update(inspector);
-        if let Some(reason) = self.core.last_stop_reason() {
+    /// Reads the state of the main thread. See [`Debugger::update_thread`].
+    pub fn update(
+        &mut self,
+        inspector: &(impl MachineInspector + ?Sized),
+    ) -> DebugAdapterResult<()> {
+        self.update_thread(MAIN_THREAD_ID, inspector)
+    }
+
+    /// Reads the state of a processor represented by a given thread. Expected
+    /// to be called after the machine is initialized, and then after every
+    /// single cycle.
+    pub fn update_thread(
+        &mut self,
+        thread_id: i64,
+        inspector: &(impl MachineInspector + ?Sized),
+    ) -> DebugAdapterResult<()> {
+        let thread_index = self
+            .thread_index(thread_id)
+            .unwrap_or_else(|e| panic!("{}", e));
+        self.threads[thread_index].core.update(inspector);
+        return self.report_stop(thread_index);
+    }
+
+    /// Reads the beam position, which is needed by raster line and frame
+    /// breakpoints. Expected to be called after every machine tick, right
+    /// before [`Debugger::update`]. Since these events belong to the machine
+    /// as a whole, they stop the main thread.
+    pub fn update_raster(&mut self, line: u32, frame_complete: bool) {
+        self.threads[0].core.update_raster(line, frame_complete);
+    }
+
+    /// Stops the execution because of an error reported by the machine, and
+    /// notifies the client, passing the error message.
+    pub fn report_error(&mut self, error: &CpuError) -> DebugAdapterResult<()> {
+        self.threads[0].core.stop_on_error();
+        return self.report_stop_with_text(0, Some(error.to_string()));
+    }
+
+    /// Notifies the client if a given thread has just stopped.
+    fn report_stop(&mut self, thread_index: usize) -> DebugAdapterResult<()> {
+        self.report_stop_with_text(thread_index, None)
+    }
+
+    fn report_stop_with_text(
+        &mut self,
+        thread_index: usize,
+        text: Option<String>,
+    ) -> DebugAdapterResult<()> {
+        if let Some(reason) = self.threads[thread_index].core.last_stop_reason() {
+            for thread in &mut self.threads {
+                thread.core.hold();
+            }
+            self.current_thread = thread_index;
             self.send_event(Event::Stopped(StoppedEvent {
-                thread_id: 1,
+                thread_id: thread_id(thread_index),
                 reason,
                 all_threads_stopped: true,
+                text,
             }))?;
         }
         Ok(())
     }
 
-    pub fn process_messages(&mut self, inspector: &impl MachineInspector) {
+    /// Processes incoming messages, assuming that the machine only has the
+    /// main thread.
+    pub fn process_messages(&mut self, inspector: &mut impl MachineInspector) {
+        self.process_thread_messages(&mut [inspector as &mut dyn MachineInspector]);
+    }
+
+    /// Processes incoming messages. Expects an inspector for each thread, in
+    /// the order of thread IDs.
+    pub fn process_thread_messages(&mut self, inspectors: &mut [&mut dyn MachineInspector]) {
+        assert_eq!(
+            inspectors.len(),
+            self.threads.len(),
+            "Expected an inspector for each thread"
+        );
         loop {
             match self.adapter.try_receive_message() {
-                Ok(envelope) => self.process_message(envelope, inspector),
+                Ok(envelope) => self.process_message(envelope, inspectors),
                 Err(DebugAdapterError::TryRecvError(TryRecvError::Empty)) => return,
                 Err(e) => panic!("{}", e),
             }
         }
     }
 
-    fn process_message(&mut self, envelope: MessageEnvelope, inspector: &impl MachineInspector) {
+    fn process_message(
+        &mut self,
+        envelope: MessageEnvelope,
+        inspectors: &mut [&mut dyn MachineInspector],
+    ) {
         match envelope.message {
-            Message::Request(request) => self.process_request(envelope.seq, request, inspector),
+            Message::Request(request) => self.process_request(envelope.seq, request, inspectors),
             other => eprintln!("Unsupported message: {:?}", other),
         };
     }
@@ -116,37 +403,147 @@ impl<A: DebugAdapter> Debugger<A> {
         &mut self,
         request_seq: i64,
         request: Request,
-        inspector: &impl MachineInspector,
+        inspectors: &mut [&mut dyn MachineInspector],
     ) {
-        let (response, continuation) = match request {
-            Request::Initialize(args) => self.initialize(args),
-            Request::SetExceptionBreakpoints {} => self.set_exception_breakpoints(),
-            Request::SetInstructionBreakpoints(args) => self.set_instruction_breakpoints(args),
-            Request::Attach {} => self.attach(),
-            Request::Threads => self.threads(),
-            Request::StackTrace {} => self.stack_trace(inspector),
-            Request::Scopes(args) => self.scopes(args),
-            Request::Variables(args) => self.variables(inspector, args),
+        let command = request.command();
+        match self.requested_thread(&request) {
+            Ok(Some(thread_index)) => self.current_thread = thread_index,
+            Ok(None) => {}
+            Err(message) => return self.send_error_response(request_seq, command, message),
+        }
+        let inspector = &mut *inspectors[self.current_thread];
+        let result = match request {
+            Request::Initialize(args) => Ok(self.initialize(args)),
+            Request::SetExceptionBreakpoints {} => Ok(self.set_exception_breakpoints()),
+            Request::SetInstructionBreakpoints(args) => {
+                self.set_instruction_breakpoints(args, inspectors)
+            }
+            Request::DataBreakpointInfo(args) => Ok(self.data_breakpoint_info(args)),
+            Request::SetDataBreakpoints(args) => Ok(self.set_data_breakpoints(args)),
+            Request::SetFunctionBreakpoints(args) => Ok(self.set_function_breakpoints(args)),
+            Request::Attach {} => Ok(self.attach()),
+            // The response is deferred until the program is loaded.
+            Request::Launch(args) => return self.launch(request_seq, args),
+            Request::Threads => Ok(self.threads()),
+            Request::StackTrace(_) => Ok(self.stack_trace(inspector)),
+            Request::Scopes(args) => Ok(self.scopes(args)),
+            Request::Variables(args) => Ok(self.variables(inspector, args)),
             Request::Disassemble(args) => self.disassemble(inspector, args),
             Request::ReadMemory(args) => self.read_memory(inspector, args),
+            Request::WriteMemory(args) => self.write_memory(inspector, args),
+            Request::SetVariable(args) => self.set_variable(inspector, args),
+            Request::Evaluate(args) => match repl_cheat_command(&args) {
+                // The response is deferred until the command is executed.
+                Some(Ok(command)) => {
+                    return self.cheat_commands.push(PendingCheatCommand {
+                        request_seq,
+                        command,
+                    })
+                }
+                Some(Err(e)) => Err(e.to_string()),
+                None => self.evaluate(inspector, args),
+            },
+
+            Request::Continue(_) => Ok(self.resume()),
+            Request::Pause(_) => Ok(self.pause()),
+            Request::Next(_) => Ok(self.next()),
+            Request::StepIn(_) => Ok(self.step_in()),
+            Request::StepOut(_) => Ok(self.step_out()),
+            Request::StepBack(_) => Ok(self.step_back(inspector)),
+            Request::ReverseContinue(_) => Ok(self.reverse_continue(inspector)),
 
-            Request::Continue {} => self.resume(),
-            Request::Pause {} => self.pause(),
-            Request::Next {} => self.next(inspector),
-            Request::StepIn {} => self.step_in(),
-            Request::StepOut {} => self.step_out(),
+            Request::Disconnect(_) => Ok(self.disconnect()),
 
-            Request::Disconnect(_) => self.disconnect(),
+            Request::ChipState {} => self.chip_state(inspector),
+            Request::MemoryMap {} => Ok(self.memory_map(inspector)),
+            Request::Assemble(args) => Ok(self.assemble(inspector, args)),
         };
-        self.send_message(Message::Response(ResponseEnvelope {
+        match result {
+            Ok((response, continuation)) => {
+                self.send_message(Message::Response(ResponseEnvelope {
+                    request_seq,
+                    success: true,
+                    response,
+                }))
+                .unwrap();
+                if let Some(continuation) = continuation {
+                    continuation(self).unwrap();
+                }
+            }
+            Err(message) => self.send_error_response(request_seq, command, message),
+        }
+    }
+
+    fn send_error_response(&mut self, request_seq: i64, command: String, message: String) {
+        self.send_message(Message::ErrorResponse(ErrorResponseEnvelope {
             request_seq,
-            success: true,
-            response,
+            success: false,
+            command,
+            message,
         }))
         .unwrap();
-        if let Some(continuation) = continuation {
-            continuation(self).unwrap();
+    }
+
+    /// Returns the index of a thread that a given request explicitly refers
+    /// to, either by its ID, or by an ID of a stack frame or a variable
+    /// reference. Fails if the thread doesn't exist.
+    fn requested_thread(&self, request: &Request) -> Result<Option<usize>, String> {
+        return match request {
+            Request::StackTrace(args)
+            | Request::Continue(args)
+            | Request::Pause(args)
+            | Request::Next(args)
+            | Request::StepIn(args)
+            | Request::StepOut(args)
+            | Request::StepBack(args)
+            | Request::ReverseContinue(args) => self.thread_index(args.thread_id).map(Some),
+            Request::Scopes(args) => self.thread_index_of_local_id(args.frame_id).map(Some),
+            Request::Evaluate(args) => args
+                .frame_id
+                .map(|frame_id| self.thread_index_of_local_id(frame_id))
+                .transpose(),
+            Request::Variables(VariablesArguments {
+                variables_reference,
+            })
+            | Request::SetVariable(SetVariableArguments {
+                variables_reference,
+                ..
+            }) => self
+                .thread_index_of_local_id(*variables_reference)
+                .map(Some),
+            _ => Ok(None),
+        };
+    }
+
+    /// Translates a thread ID to an index in the thread list. Fails if there's
+    /// no such thread.
+    fn thread_index(&self, thread_id: i64) -> Result<usize, String> {
+        let index = thread_id - MAIN_THREAD_ID;
+        if index < 0 || index >= self.threads.len() as i64 {
+            return Err(format!("Unknown thread ID: {}", thread_id));
         }
+        return Ok(index as usize);
+    }
+
+    /// Returns the index of a thread that a given stack frame ID or variable
+    /// reference belongs to.
+    fn thread_index_of_local_id(&self, id: i64) -> Result<usize, String> {
+        self.thread_index(id / THREAD_ID_SPACING + MAIN_THREAD_ID)
+    }
+
+    /// Returns the debugger core of the current thread.
+    fn core(&self) -> &DebuggerCore {
+        &self.threads[self.current_thread].core
+    }
+
+    fn mut_core(&mut self) -> &mut DebuggerCore {
+        &mut self.threads[self.current_thread].core
+    }
+
+    /// Makes a stack frame ID or a variable reference of the current thread
+    /// globally unique.
+    fn global_id(&self, local_id: i64) -> i64 {
+        self.current_thread as i64 * THREAD_ID_SPACING + local_id
     }
 
     fn send_event(&mut self, event: Event) -> DebugAdapterResult<()> {
@@ -162,7 +559,14 @@ impl<A: DebugAdapter> Debugger<A> {
             Response::Initialize(Capabilities {
                 supports_disassemble_request: true,
                 supports_instruction_breakpoints: true,
+                supports_conditional_breakpoints: true,
+                supports_hit_conditional_breakpoints: true,
+                supports_data_breakpoints: true,
+                supports_function_breakpoints: true,
                 supports_read_memory_request: true,
+                supports_write_memory_request: true,
+                supports_set_variable: true,
+                supports_step_back: true,
             }),
             Some(Box::new(|me| me.send_event(Event::Initialized))),
         )
@@ -172,26 +576,155 @@ impl<A: DebugAdapter> Debugger<A> {
         (Response::SetExceptionBreakpoints, None)
     }
 
+    /// Sets instruction breakpoints in the current thread, replacing its
+    /// previous instruction breakpoints. Since the protocol doesn't tie
+    /// breakpoints to threads, they belong to the thread that has been stopped
+    /// or inspected most recently, and breakpoints of other threads stay
+    /// intact. Breakpoints at addresses that the processor can't execute code
+    /// from (like I/O registers or unmapped memory) are still installed, since
+    /// the memory layout may change, but they are reported as unverified.
     fn set_instruction_breakpoints(
         &mut self,
         args: SetInstructionBreakpointsArguments,
+        inspectors: &[&mut dyn MachineInspector],
+    ) -> RequestResult<A> {
+        let memory_map = inspectors[self.current_thread].memory_map();
+        let breakpoints: Vec<(u16, ParseResult<InstructionBreakpoint>)> = args
+            .breakpoints
+            .iter()
+            .map(|breakpoint| {
+                let address =
+                    parse_memory_reference(&breakpoint.instruction_reference, breakpoint.offset)?
+                        as u16;
+                Ok((
+                    address,
+                    instruction_breakpoint(address, breakpoint, &self.symbols),
+                ))
+            })
+            .collect::<Result<_, String>>()?;
+        for (address, breakpoint) in &breakpoints {
+            if let Err(e) = breakpoint {
+                eprintln!("Invalid breakpoint at {}: {}", format_word(*address), e);
+            }
+        }
+        self.mut_core().set_instruction_breakpoints(
+            breakpoints
+                .iter()
+                .filter_map(|(_, breakpoint)| breakpoint.as_ref().ok().cloned())
+                .collect(),
+        );
+        Ok((
+            Response::SetInstructionBreakpoints(SetInstructionBreakpointsResponse {
+                breakpoints: breakpoints
+                    .iter()
+                    .map(|(address, breakpoint)| {
+                        let executable = may_contain_code(&memory_map, *address);
+                        Breakpoint {
+                            verified: breakpoint.is_ok() && executable,
+                            instruction_reference: Some(format!("0x{:04X}", address)),
+                            message: if executable {
+                                None
+                            } else {
+                                Some(format!(
+                                    "No code can be executed at {}",
+                                    format_word(*address)
+                                ))
+                            },
+                        }
+                    })
+                    .collect(),
+            }),
+            None,
+        ))
+    }
+
+    fn data_breakpoint_info(&self, args: DataBreakpointInfoArguments) -> RequestOutcome<A> {
+        let response = match parse_address(&args.name) {
+            Some(address) => DataBreakpointInfoResponse {
+                data_id: Some(format!("0x{:04X}", address)),
+                description: format_word(address),
+                access_types: Some(vec![
+                    DataBreakpointAccessType::Read,
+                    DataBreakpointAccessType::Write,
+                    DataBreakpointAccessType::ReadWrite,
+                ]),
+            },
+            None => DataBreakpointInfoResponse {
+                data_id: None,
+                description: format!("{} is not a memory address", args.name),
+                access_types: None,
+            },
+        };
+        (Response::DataBreakpointInfo(response), None)
+    }
+
+    /// Sets data breakpoints in the current thread. See
+    /// [`Debugger::set_instruction_breakpoints`].
+    fn set_data_breakpoints(&mut self, args: SetDataBreakpointsArguments) -> RequestOutcome<A> {
+        let breakpoints: Vec<Option<DataBreakpoint>> = args
+            .breakpoints
+            .iter()
+            .map(|breakpoint| {
+                parse_address(&breakpoint.data_id).map(|address| DataBreakpoint {
+                    address,
+                    // The protocol doesn't specify a default; watching writes
+                    // is what users usually expect.
+                    access_type: breakpoint
+                        .access_type
+                        .unwrap_or(DataBreakpointAccessType::Write),
+                })
+            })
+            .collect();
+        self.mut_core()
+            .set_data_breakpoints(breakpoints.iter().flatten().cloned().collect());
+        (
+            Response::SetDataBreakpoints(SetDataBreakpointsResponse {
+                breakpoints: breakpoints
+                    .iter()
+                    .map(|breakpoint| Breakpoint {
+                        verified: breakpoint.is_some(),
+                        instruction_reference: None,
+                        message: None,
+                    })
+                    .collect(),
+            }),
+            None,
+        )
+    }
+
+    /// Sets breakpoints on machine events. We don't have any functions to
+    /// break on, so instead, function breakpoint names are interpreted as
+    /// event names; see [`EventBreakpoint::parse`].
+    fn set_function_breakpoints(
+        &mut self,
+        args: SetFunctionBreakpointsArguments,
     ) -> RequestOutcome<A> {
-        let addresses_iter = args.breakpoints.iter().map(|breakpoint| {
-            (i64::from_str_radix(
-                breakpoint.instruction_reference.strip_prefix("0x").unwrap(),
-                16,
-            )
-            .unwrap()
-                + breakpoint.offset.unwrap_or(0)) as u16
-        });
-        self.core
-            .set_instruction_breakpoints(addresses_iter.clone().collect());
+        let breakpoints: Vec<ParseResult<EventBreakpoint>> = args
+            .breakpoints
+            .iter()
+            .map(|breakpoint| EventBreakpoint::parse(&breakpoint.name))
+            .collect();
+        for (args, breakpoint) in args.breakpoints.iter().zip(&breakpoints) {
+            if let Err(e) = breakpoint {
+                eprintln!("Invalid function breakpoint {}: {}", args.name, e);
+            }
+        }
+        // Machine events are only reported to the main thread; see
+        // `update_raster`.
+        self.threads[0].core.set_event_breakpoints(
+            breakpoints
+                .iter()
+                .filter_map(|breakpoint| breakpoint.as_ref().ok().copied())
+                .collect(),
+        );
         (
-            Response::SetInstructionBreakpoints(SetInstructionBreakpointsResponse {
-                breakpoints: addresses_iter
-                    .map(|address| Breakpoint {
-                        verified: true,
-                        instruction_reference: format!("0x{:04X}", address),
+            Response::SetFunctionBreakpoints(SetFunctionBreakpointsResponse {
+                breakpoints: breakpoints
+                    .iter()
+                    .map(|breakpoint| Breakpoint {
+                        verified: breakpoint.is_ok(),
+                        instruction_reference: None,
+                        message: None,
                     })
                     .collect(),
             }),
@@ -199,14 +732,30 @@ impl<A: DebugAdapter> Debugger<A> {
         )
     }
 
+    /// Restarts the debugged program. The machine controller picks up the
+    /// launch request, loads the program, and calls
+    /// [`Debugger::finish_launch`], which responds to the client and restarts
+    /// the threads. The main thread then stops on the reset vector if
+    /// requested.
+    fn launch(&mut self, request_seq: i64, args: LaunchArguments) {
+        self.pending_launch = Some(PendingLaunch {
+            request_seq,
+            stop_on_entry: args.stop_on_entry.unwrap_or(false),
+        });
+        self.launch_request = Some(LaunchRequest {
+            program: args.program.map(PathBuf::from),
+        });
+    }
+
     fn attach(&self) -> RequestOutcome<A> {
         (
             Response::Attach,
             Some(Box::new(|me| {
                 me.send_event(Event::Stopped(StoppedEvent {
                     reason: StopReason::Entry,
-                    thread_id: 1,
+                    thread_id: MAIN_THREAD_ID,
                     all_threads_stopped: true,
+                    text: None,
                 }))
             })),
         )
@@ -215,23 +764,28 @@ impl<A: DebugAdapter> Debugger<A> {
     fn threads(&self) -> RequestOutcome<A> {
         (
             Response::Threads(ThreadsResponse {
-                threads: vec![Thread {
-                    id: 1,
-                    name: "main thread".to_string(),
-                }],
+                threads: self
+                    .threads
+                    .iter()
+                    .enumerate()
+                    .map(|(index, thread)| Thread {
+                        id: thread_id(index),
+                        name: thread.name.clone(),
+                    })
+                    .collect(),
             }),
             None,
         )
     }
 
-    fn stack_trace(&self, inspector: &impl MachineInspector) -> RequestOutcome<A> {
-        let stack_trace = self.core.stack_trace(inspector);
+    fn stack_trace(&self, inspector: &(impl MachineInspector + ?Sized)) -> RequestOutcome<A> {
+        let stack_trace = self.core().stack_trace(inspector);
         let num_frames = stack_trace.len();
         let stack_frames = stack_trace
             .iter()
             .enumerate()
             .map(|(i, frame)| StackFrame {
-                id: (num_frames - i) as i64,
+                id: self.global_id((num_frames - i) as i64),
                 name: format_word(frame.entry),
                 instruction_pointer_reference: format!("0x{:04X}", frame.pc),
                 line: 0,
@@ -248,11 +802,11 @@ impl<A: DebugAdapter> Debugger<A> {
     }
 
     fn scopes(&self, args: ScopesArguments) -> RequestOutcome<A> {
-        let mut scopes = if args.frame_id == self.core.stack_depth() as i64 {
+        let mut scopes = if args.frame_id == self.global_id(self.core().stack_depth() as i64) {
             vec![Scope {
                 name: "Registers".to_string(),
                 presentation_hint: Some(ScopePresentationHint::Registers),
-                variables_reference: REGISTERS_VARIABLES_REFERENCE,
+                variables_reference: self.global_id(REGISTERS_VARIABLES_REFERENCE),
                 expensive: false,
             }]
         } else {
@@ -261,7 +815,7 @@ impl<A: DebugAdapter> Debugger<A> {
         scopes.push(Scope {
             name: "Memory".to_string(),
             presentation_hint: None,
-            variables_reference: MEMORY_VARIABLES_REFERENCE,
+            variables_reference: self.global_id(MEMORY_VARIABLES_REFERENCE),
             expensive: false,
         });
         return (Response::Scopes(ScopesResponse { scopes }), None);
@@ -269,28 +823,11 @@ impl<A: DebugAdapter> Debugger<A> {
 
     fn variables(
         &self,
-        inspector: &impl MachineInspector,
+        inspector: &(impl MachineInspector + ?Sized),
         args: VariablesArguments,
     ) -> RequestOutcome<A> {
-        let vars = match args.variables_reference {
-            REGISTERS_VARIABLES_REFERENCE => vec![
-                byte_variable("A", inspector.reg_a()),
-                byte_variable("X", inspector.reg_x()),
-                byte_variable("Y", inspector.reg_y()),
-                byte_variable("SP", inspector.reg_sp()),
-                Variable {
-                    name: "PC".to_string(),
-                    value: format_word(inspector.reg_pc()),
-                    variables_reference: 0,
-                    memory_reference: None,
-                },
-                Variable {
-                    name: "FLAGS".to_string(),
-                    value: flags_to_string(inspector.flags(), FlagRepresentation::Letters),
-                    variables_reference: 0,
-                    memory_reference: None,
-                },
-            ],
+        let vars = match args.variables_reference % THREAD_ID_SPACING {
+            REGISTERS_VARIABLES_REFERENCE => register_variables(inspector),
             MEMORY_VARIABLES_REFERENCE => vec![Variable {
                 name: "Memory".to_string(),
                 value: "$0000".to_string(),
@@ -307,96 +844,281 @@ impl<A: DebugAdapter> Debugger<A> {
 
     fn disassemble(
         &self,
-        inspector: &impl MachineInspector,
+        inspector: &(impl MachineInspector + ?Sized),
         args: DisassembleArguments,
-    ) -> RequestOutcome<A> {
-        let mem_reference =
-            i64::from_str_radix(&args.memory_reference.strip_prefix("0x").unwrap(), 16).unwrap();
-        let origin = (mem_reference + args.offset.unwrap_or(0)) as u16;
+    ) -> RequestResult<A> {
+        let origin = parse_memory_reference(&args.memory_reference, args.offset)? as u16;
+        let memory_map = inspector.memory_map();
         let disassembly_start = seek_instruction(
             inspector,
+            &memory_map,
             origin,
             args.instruction_offset.unwrap_or(0) - DISASSEMBLY_MARGIN as i64,
         );
         let instructions = disassemble(
             inspector,
+            &self.register_names,
+            &memory_map,
             origin,
             disassembly_start,
             DISASSEMBLY_MARGIN,
-            usize::try_from(args.instruction_count).unwrap(),
+            usize::try_from(args.instruction_count)
+                .map_err(|_| format!("Invalid instruction count: {}", args.instruction_count))?,
         );
-        (
+        Ok((
             Response::Disassemble(DisassembleResponse { instructions }),
             None,
-        )
+        ))
     }
 
     fn read_memory(
         &self,
-        inspector: &impl MachineInspector,
+        inspector: &(impl MachineInspector + ?Sized),
         args: ReadMemoryArguments,
-    ) -> RequestOutcome<A> {
-        let start_address =
-            i64::from_str_radix(&args.memory_reference.strip_prefix("0x").unwrap(), 16).unwrap()
-                + args.offset.unwrap_or(0);
+    ) -> RequestResult<A> {
+        let start_address = parse_memory_reference(&args.memory_reference, args.offset)?;
+        if start_address < 0 {
+            return Err(format!("Address out of range: {}", start_address));
+        }
         let requested_end_address = start_address + args.count;
         let end_address = min(requested_end_address, 0x10000);
         let mem_dump: Vec<u8> = (start_address..end_address)
             .map(|a| inspector.inspect_memory(a as u16))
             .collect();
         let data = base64::encode(mem_dump);
-        (
+        Ok((
             Response::ReadMemory(ReadMemoryResponse {
                 address: format!("0x{:04X}", start_address),
                 data,
                 unreadable_bytes: max(requested_end_address - 0x10000, 0),
             }),
             None,
-        )
+        ))
+    }
+
+    /// Writes data to memory. Data that doesn't fit in the address space is
+    /// dropped.
+    fn write_memory(
+        &mut self,
+        inspector: &mut (impl MachineInspector + ?Sized),
+        args: WriteMemoryArguments,
+    ) -> RequestResult<A> {
+        let start_address = parse_memory_reference(&args.memory_reference, args.offset)?;
+        if !(0..0x10000).contains(&start_address) {
+            return Err(format!("Address out of range: {}", start_address));
+        }
+        let data = base64::decode(args.data).map_err(|e| format!("Invalid data: {}", e))?;
+        let end_address = min(start_address + data.len() as i64, 0x10000);
+        let result = (start_address..end_address)
+            .zip(data)
+            .try_for_each(|(address, value)| inspector.poke_memory(address as u16, value));
+        self.mut_core().clear_history();
+        result.map_err(|e| e.to_string())?;
+        Ok((
+            Response::WriteMemory(WriteMemoryResponse {
+                bytes_written: Some(end_address - start_address),
+            }),
+            None,
+        ))
+    }
+
+    /// Modifies a register. If the new value is invalid, the request is
+    /// rejected, and the register is left untouched.
+    fn set_variable(
+        &mut self,
+        inspector: &mut (impl MachineInspector + ?Sized),
+        args: SetVariableArguments,
+    ) -> RequestResult<A> {
+        if args.variables_reference % THREAD_ID_SPACING != REGISTERS_VARIABLES_REFERENCE {
+            return Err(format!("{} can't be modified", args.name));
+        }
+        let (register, value) =
+            parse_register_value(inspector, &self.symbols, &args.name, &args.value)
+                .map_err(|e| format!("Unable to set {} to {}: {}", args.name, args.value, e))?;
+        inspector.set_register(register, value);
+        self.mut_core().clear_history();
+        let value = register_variables(inspector)
+            .into_iter()
+            .find(|variable| variable.name == args.name)
+            .map(|variable| variable.value)
+            .unwrap_or_default();
+        return Ok((Response::SetVariable(SetVariableResponse { value }), None));
+    }
+
+    /// Evaluates an expression. Cheat commands typed in the debug console are
+    /// handled separately; see [`Debugger::execute_cheat_commands`].
+    fn evaluate(
+        &mut self,
+        inspector: &(impl MachineInspector + ?Sized),
+        args: EvaluateArguments,
+    ) -> RequestResult<A> {
+        let result = match evaluate_expression(inspector, &self.symbols, &args.expression) {
+            Ok(value) => format_value(value),
+            Err(e) => return Err(e.to_string()),
+        };
+        return Ok((
+            Response::Evaluate(EvaluateResponse {
+                result,
+                variables_reference: 0,
+            }),
+            None,
+        ));
     }
 
     fn resume(&mut self) -> RequestOutcome<A> {
-        self.core.resume();
+        self.resume_all_threads();
         (Response::Continue {}, None)
     }
 
     fn pause(&mut self) -> RequestOutcome<A> {
-        self.core.pause();
+        self.mut_core().pause();
         (
             Response::Pause {},
-            Some(Box::new(|me| {
-                me.send_event(Event::Stopped(StoppedEvent {
-                    reason: StopReason::Pause,
-                    thread_id: 1,
-                    all_threads_stopped: true,
-                }))
-            })),
+            Some(self.report_stop_of_current_thread()),
         )
     }
 
     fn step_in(&mut self) -> RequestOutcome<A> {
-        self.core.step_into();
+        self.resume_all_threads();
+        self.mut_core().step_into();
         (Response::StepIn {}, None)
     }
 
-    fn next(&mut self, inspector: &impl MachineInspector) -> RequestOutcome<A> {
-        self.core.step_over(inspector);
+    fn next(&mut self) -> RequestOutcome<A> {
+        self.resume_all_threads();
+        self.mut_core().step_over();
         (Response::Next {}, None)
     }
 
     fn step_out(&mut self) -> RequestOutcome<A> {
-        self.core.step_out();
+        self.resume_all_threads();
+        self.mut_core().step_out();
         (Response::StepOut {}, None)
     }
 
+    fn step_back(&mut self, inspector: &mut (impl MachineInspector + ?Sized)) -> RequestOutcome<A> {
+        self.mut_core().step_back(inspector);
+        (
+            Response::StepBack,
+            Some(self.report_stop_of_current_thread()),
+        )
+    }
+
+    fn reverse_continue(
+        &mut self,
+        inspector: &mut (impl MachineInspector + ?Sized),
+    ) -> RequestOutcome<A> {
+        self.mut_core().reverse_continue(inspector);
+        (
+            Response::ReverseContinue,
+            Some(self.report_stop_of_current_thread()),
+        )
+    }
+
+    /// Lets all threads run. Note that when a thread is being stepped, the
+    /// other ones need to run, too, since they share the machine clock.
+    fn resume_all_threads(&mut self) {
+        for thread in &mut self.threads {
+            thread.core.resume();
+        }
+    }
+
+    /// Returns a request continuation that notifies the client if the current
+    /// thread has just stopped.
+    fn report_stop_of_current_thread(
+        &self,
+    ) -> Box<dyn FnOnce(&mut Debugger<A>) -> DebugAdapterResult<()>> {
+        let thread_index = self.current_thread;
+        Box::new(move |me| me.report_stop(thread_index))
+    }
+
+    /// Ends the debugging session. Breakpoints are removed, so that the
+    /// machine doesn't stop with no client attached, and the next session
+    /// starts from scratch.
     fn disconnect(&mut self) -> RequestOutcome<A> {
-        self.core.resume();
+        for thread in &mut self.threads {
+            thread.core.set_instruction_breakpoints(vec![]);
+            thread.core.set_data_breakpoints(vec![]);
+            thread.core.set_event_breakpoints(vec![]);
+        }
+        self.resume_all_threads();
         (
             Response::Disconnect,
             Some(Box::new(|me| me.adapter.disconnect())),
         )
     }
 
+    fn chip_state(&self, inspector: &(impl MachineInspector + ?Sized)) -> RequestResult<A> {
+        let chips = inspector
+            .chip_states()
+            .map_err(|e| format!("Unable to inspect chips: {}", e))?
+            .into_iter()
+            .map(|chip| Chip {
+                name: chip.name,
+                registers: chip
+                    .registers
+                    .into_iter()
+                    .map(|register| ChipRegister {
+                        name: register.name,
+                        value: register.value.into(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        return Ok((Response::ChipState(ChipStateResponse { chips }), None));
+    }
+
+    fn memory_map(&self, inspector: &(impl MachineInspector + ?Sized)) -> RequestOutcome<A> {
+        let regions = inspector
+            .memory_map()
+            .into_iter()
+            .map(|region| MemoryRegion {
+                start: format!("0x{:04X}", region.start),
+                end: format!("0x{:04X}", region.end),
+                kind: region.kind.into(),
+            })
+            .collect();
+        return (Response::MemoryMap(MemoryMapResponse { regions }), None);
+    }
+
+    /// Assembles instructions and writes them to memory. Nothing is written if
+    /// any of the instructions is invalid.
+    fn assemble(
+        &mut self,
+        inspector: &mut (impl MachineInspector + ?Sized),
+        args: AssembleArguments,
+    ) -> RequestOutcome<A> {
+        let result = match parse_address(&args.memory_reference) {
+            Some(address) => assemble(inspector, &self.symbols, address, &args.source)
+                .map(|bytes| (address, bytes))
+                .map_err(|e| e.to_string()),
+            None => Err(format!("Invalid address: {}", args.memory_reference)),
+        };
+        let response = match result {
+            Ok((address, bytes)) => {
+                let result = bytes.iter().enumerate().try_for_each(|(offset, value)| {
+                    inspector.poke_memory(address.wrapping_add(offset as u16), *value)
+                });
+                self.mut_core().clear_history();
+                match result {
+                    Ok(()) => AssembleResponse {
+                        bytes_written: bytes.len() as i64,
+                        error: None,
+                    },
+                    Err(e) => AssembleResponse {
+                        bytes_written: (e.address.wrapping_sub(address)) as i64,
+                        error: Some(e.to_string()),
+                    },
+                }
+            }
+            Err(error) => AssembleResponse {
+                bytes_written: 0,
+                error: Some(error),
+            },
+        };
+        return (Response::Assemble(response), None);
+    }
+
     fn send_message(&mut self, message: Message) -> DebugAdapterResult<()> {
         let seq = self.next_sequence_number();
         return self.adapter.send_message(MessageEnvelope { seq, message });
@@ -408,6 +1130,11 @@ impl<A: DebugAdapter> Debugger<A> {
     }
 }
 
+/// Translates an index in the thread list to a thread ID.
+fn thread_id(thread_index: usize) -> i64 {
+    thread_index as i64 + MAIN_THREAD_ID
+}
+
 fn format_byte(val: u8) -> String {
     format!("${:02X}", val)
 }
@@ -416,6 +1143,136 @@ fn format_word(val: u16) -> String {
     format!("${:04X}", val)
 }
 
+/// Formats a result of an expression, both in hexadecimal and decimal.
+fn format_value(val: i64) -> String {
+    match val {
+        0..=0xFF => format!("{} ({})", format_byte(val as u8), val),
+        0x100..=0xFFFF => format!("{} ({})", format_word(val as u16), val),
+        _ => val.to_string(),
+    }
+}
+
+/// Creates a debugger core breakpoint out of its DAP counterpart. Empty
+/// conditions are ignored.
+fn instruction_breakpoint(
+    address: u16,
+    breakpoint: &dap_types::InstructionBreakpoint,
+    symbols: &SymbolTable,
+) -> ParseResult<InstructionBreakpoint> {
+    let mut result = InstructionBreakpoint::new(address);
+    if let Some(condition) = non_empty(&breakpoint.condition) {
+        result = result.with_condition(expression::parse(condition, symbols)?);
+    }
+    if let Some(hit_condition) = non_empty(&breakpoint.hit_condition) {
+        result = result.with_hit_condition(HitCondition::parse(hit_condition)?);
+    }
+    return Ok(result);
+}
+
+fn non_empty(text: &Option<String>) -> Option<&str> {
+    text.as_deref().filter(|text| !text.trim().is_empty())
+}
+
+/// Parses a cheat command typed in the debug console. Returns `None` if the
+/// expression is not a cheat command at all, or if it doesn't come from the
+/// debug console.
+fn repl_cheat_command(args: &EvaluateArguments) -> Option<Result<CheatCommand, Box<dyn Error>>> {
+    if args.context.as_deref() != Some("repl") {
+        return None;
+    }
+    return cheat_command(&args.expression);
+}
+
+/// Parses a debug console command that installs or removes a cheat. Returns
+/// `None` if the text is not a cheat command at all.
+fn cheat_command(text: &str) -> Option<Result<CheatCommand, Box<dyn Error>>> {
+    let (command, argument) = text.trim().split_once(' ')?;
+    return match command {
+        "cheat" => Some(
+            argument
+                .parse::<Cheat>()
+                .map(CheatCommand::Install)
+                .map_err(|e| e.into()),
+        ),
+        "uncheat" => Some(
+            parse_cheat_address(argument)
+                .map(CheatCommand::Remove)
+                .ok_or_else(|| format!("Invalid address: {}", argument.trim()).into()),
+        ),
+        _ => None,
+    };
+}
+
+/// Parses a DAP memory reference (a hexadecimal number prefixed with `0x`) and
+/// adds an optional offset to it. The result may lie outside the address space.
+fn parse_memory_reference(memory_reference: &str, offset: Option<i64>) -> Result<i64, String> {
+    memory_reference
+        .strip_prefix("0x")
+        .and_then(|digits| i64::from_str_radix(digits, 16).ok())
+        .and_then(|address| address.checked_add(offset.unwrap_or(0)))
+        .ok_or_else(|| format!("Invalid memory reference: {}", memory_reference))
+}
+
+/// Parses a memory address given either as `$ABCD` or `0xABCD`.
+fn parse_address(text: &str) -> Option<u16> {
+    let hex_digits = text.strip_prefix('$').or_else(|| text.strip_prefix("0x"))?;
+    u16::from_str_radix(hex_digits, 16).ok()
+}
+
+/// Parses a new value of a register, given its variable name. Flags can be
+/// specified either as an expression or using the same letters that are used to
+/// display them.
+fn parse_register_value(
+    inspector: &(impl MachineInspector + ?Sized),
+    symbols: &SymbolTable,
+    name: &str,
+    text: &str,
+) -> Result<(Register, u16), Box<dyn Error>> {
+    let register = match name {
+        "A" => Register::A,
+        "X" => Register::X,
+        "Y" => Register::Y,
+        "SP" => Register::SP,
+        "PC" => Register::PC,
+        "FLAGS" => Register::Flags,
+        _ => return Err(ParseError::new(format!("Unknown register: {}", name)).into()),
+    };
+    if let (Register::Flags, Some(flags)) = (register, string_to_flags(text.trim())) {
+        return Ok((register, flags as u16));
+    }
+    let value = evaluate_expression(inspector, symbols, text)?;
+    return Ok((register, value as u16));
+}
+
+fn evaluate_expression(
+    inspector: &(impl MachineInspector + ?Sized),
+    symbols: &SymbolTable,
+    text: &str,
+) -> Result<i64, Box<dyn Error>> {
+    Ok(expression::parse(text, symbols)?.evaluate(inspector)?)
+}
+
+fn register_variables(inspector: &(impl MachineInspector + ?Sized)) -> Vec<Variable> {
+    vec![
+        byte_variable("A", inspector.reg_a()),
+        byte_variable("X", inspector.reg_x()),
+        byte_variable("Y", inspector.reg_y()),
+        byte_variable("SP", inspector.reg_sp()),
+        Variable {
+            name: "PC".to_string(),
+            value: format_word(inspector.reg_pc()),
+            variables_reference: 0,
+            memory_reference: None,
+        },
+        Variable {
+            name: "FLAGS".to_string(),
+            value: flags_to_string(inspector.flags(), FlagRepresentation::Letters),
+            variables_reference: 0,
+            memory_reference: None,
+        },
+    ]
+}
+
 fn byte_variable(name: &str, value: u8) -> Variable {
     Variable {
         name: name.to_string(),
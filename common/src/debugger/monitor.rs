@@ -0,0 +1,824 @@
+//! A text-based machine monitor for users who don't run a Debug Adapter
+//! Protocol client. Commands typed in the terminal are translated to DAP
+//! requests, so the monitor shares all its functionality with the graphical
+//! debugger, and the responses are printed as plain text.
+
+use crate::debugger::adapter::DebugAdapter;
+use crate::debugger::adapter::DebugAdapterResult;
+use crate::debugger::core::StopReason;
+use crate::debugger::dap_types::AssembleArguments;
+use crate::debugger::dap_types::DisassembleArguments;
+use crate::debugger::dap_types::DisassembledInstruction;
+use crate::debugger::dap_types::ErrorResponseEnvelope;
+use crate::debugger::dap_types::EvaluateArguments;
+use crate::debugger::dap_types::Event;
+use crate::debugger::dap_types::InstructionBreakpoint;
+use crate::debugger::dap_types::Message;
+use crate::debugger::dap_types::MessageEnvelope;
+use crate::debugger::dap_types::ReadMemoryArguments;
+use crate::debugger::dap_types::ReadMemoryResponse;
+use crate::debugger::dap_types::Request;
+use crate::debugger::dap_types::Response;
+use crate::debugger::dap_types::ResponseEnvelope;
+use crate::debugger::dap_types::SetInstructionBreakpointsArguments;
+use crate::debugger::dap_types::SetVariableArguments;
+use crate::debugger::dap_types::StoppedEvent;
+use crate::debugger::dap_types::ThreadArguments;
+use crate::debugger::dap_types::Variable;
+use crate::debugger::dap_types::VariablesArguments;
+use crate::debugger::dap_types::WriteMemoryArguments;
+use crate::debugger::format_word;
+use crate::debugger::MAIN_THREAD_ID;
+use crate::debugger::REGISTERS_VARIABLES_REFERENCE;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::sync::mpsc;
+use std::sync::mpsc::TryRecvError;
+use std::thread;
+
+/// Number of bytes displayed by the `m` command if the end address is not
+/// given.
+const DEFAULT_MEMORY_DUMP_LENGTH: u16 = 0x80;
+const MEMORY_DUMP_LINE_LENGTH: usize = 16;
+/// Number of instructions displayed by the `d` command.
+const DEFAULT_DISASSEMBLY_LENGTH: i64 = 16;
+
+const HELP: &str = "\
+Monitor commands (addresses and values are hexadecimal):
+  m [start [end]]       dump memory
+  > addr byte...        write bytes to memory
+  a addr instruction    assemble an instruction and write it to memory
+  d [start]             disassemble
+  r                     show registers
+  r reg=value           set a register (A, X, Y, SP, PC, FLAGS)
+  g [addr]              continue, optionally from a given address
+  z                     step into
+  n                     step over
+  ret                   step out
+  stop                  pause the machine
+  b addr                add a breakpoint
+  bd [addr]             delete a breakpoint, or all of them
+  bl                    list breakpoints
+  e expr                evaluate an expression or a cheat command
+  help                  show this help";
+
+/// A debug adapter that reads monitor commands line by line and writes the
+/// results as text. Use [`MonitorAdapter::new`] to talk to the terminal.
+pub struct MonitorAdapter {
+    input: mpsc::Receiver<String>,
+    output: RefCell<Box<dyn Write>>,
+    state: RefCell<MonitorState>,
+}
+
+#[derive(Default)]
+struct MonitorState {
+    sequence_number: i64,
+    /// Requests generated by a single command, waiting to be picked up by the
+    /// debugger.
+    pending_requests: VecDeque<Request>,
+    breakpoints: Vec<u16>,
+    /// Program counter, as reported by the last register dump.
+    pc: Option<u16>,
+    /// Address where the `m` and `d` commands without arguments continue.
+    next_memory_address: u16,
+    next_disassembly_address: Option<u16>,
+    /// Set when the machine stops, so that the instruction at the new program
+    /// counter is displayed along with the registers.
+    disassemble_at_pc: bool,
+    /// Address of the instruction sent by the last `a` command, so that it can
+    /// be displayed once it's assembled.
+    assembly_address: Option<u16>,
+    /// Number of bytes sent by the last `>` command, so that a partial write
+    /// can be reported.
+    write_length: Option<usize>,
+}
+
+impl MonitorAdapter {
+    /// Creates a monitor that reads commands from the standard input and
+    /// prints results to the standard output.
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::Builder::new()
+            .name("monitor input thread".into())
+            .spawn(move || {
+                for line in io::stdin().lock().lines() {
+                    match line.map(|line| tx.send(line)) {
+                        Ok(Ok(())) => {}
+                        _ => return,
+                    }
+                }
+            })
+            .expect("Unable to start the monitor input thread");
+        let monitor = Self::with_io(rx, io::stdout());
+        monitor.print("Monitor ready. Type \"help\" for a list of commands.");
+        return monitor;
+    }
+
+    /// Creates a monitor that receives command lines from a given channel and
+    /// writes results to a given output.
+    pub fn with_io(input: mpsc::Receiver<String>, output: impl Write + 'static) -> Self {
+        Self {
+            input,
+            output: RefCell::new(Box::new(output)),
+            state: RefCell::new(MonitorState::default()),
+        }
+    }
+
+    fn print(&self, text: &str) {
+        let mut output = self.output.borrow_mut();
+        if let Err(e) = writeln!(output, "{}", text).and_then(|_| output.flush()) {
+            eprintln!("Unable to write monitor output: {}", e);
+        }
+    }
+
+    /// Parses a command line and queues the resulting requests. Errors and
+    /// results of commands that don't need the debugger are printed right
+    /// away.
+    fn execute(&self, line: &str) {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return,
+        };
+        let args: Vec<&str> = words.collect();
+        let result = match command {
+            "help" | "h" => {
+                self.print(HELP);
+                Ok(vec![])
+            }
+            "bl" => {
+                self.print(&self.list_breakpoints());
+                Ok(vec![])
+            }
+            _ => self.state.borrow_mut().requests(command, &args, line),
+        };
+        match result {
+            Ok(requests) => self.state.borrow_mut().pending_requests.extend(requests),
+            Err(message) => self.print(&message),
+        }
+    }
+
+    fn list_breakpoints(&self) -> String {
+        let state = self.state.borrow();
+        if state.breakpoints.is_empty() {
+            return "No breakpoints".to_string();
+        }
+        return state
+            .breakpoints
+            .iter()
+            .map(|address| format_word(*address))
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+
+    /// Formats a response to a request issued by one of the commands.
+    fn handle_response(&self, response: Response) {
+        match response {
+            Response::ReadMemory(response) => self.print(&format_memory_dump(&response)),
+            Response::Disassemble(response) => {
+                if let Some(last) = response.instructions.last() {
+                    self.state.borrow_mut().next_disassembly_address = Some(address_after(last));
+                }
+                let lines: Vec<String> = response
+                    .instructions
+                    .iter()
+                    .map(format_instruction)
+                    .collect();
+                self.print(&lines.join("\n"));
+            }
+            Response::Variables(response) => {
+                let pc = response
+                    .variables
+                    .iter()
+                    .find(|variable| variable.name == "PC")
+                    .and_then(|variable| parse_address(&variable.value));
+                let mut state = self.state.borrow_mut();
+                state.pc = pc;
+                if let (Some(pc), true) = (pc, state.disassemble_at_pc) {
+                    state.disassemble_at_pc = false;
+                    state.pending_requests.push_back(disassemble_request(pc, 1));
+                }
+                drop(state);
+                self.print(&format_registers(&response.variables));
+            }
+            Response::Evaluate(response) => self.print(&response.result),
+            Response::WriteMemory(response) => {
+                let requested = self.state.borrow_mut().write_length.take();
+                if let (Some(written), Some(requested)) = (response.bytes_written, requested) {
+                    if (written as usize) < requested {
+                        self.print(&format!("Wrote only {} of {} bytes", written, requested));
+                    }
+                }
+            }
+            Response::Assemble(response) => {
+                let mut state = self.state.borrow_mut();
+                let address = state.assembly_address.take();
+                match (response.error, address) {
+                    (Some(error), _) => {
+                        drop(state);
+                        self.print(&error);
+                    }
+                    (None, Some(address)) => state
+                        .pending_requests
+                        .push_back(disassemble_request(address, 1)),
+                    (None, None) => {}
+                }
+            }
+            Response::SetInstructionBreakpoints(response) => {
+                for breakpoint in response.breakpoints.iter().filter(|b| !b.verified) {
+                    let reference = breakpoint.instruction_reference.as_deref().unwrap_or("?");
+                    self.print(&match &breakpoint.message {
+                        Some(message) => format!("Invalid breakpoint: {} ({})", reference, message),
+                        None => format!("Invalid breakpoint: {}", reference),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_event(&self, event: Event) {
+        if let Event::Stopped(StoppedEvent { reason, text, .. }) = event {
+            let description = describe_stop_reason(&reason);
+            self.print(&match text {
+                Some(text) => format!("Stopped: {} ({})", description, text),
+                None => format!("Stopped: {}", description),
+            });
+            let mut state = self.state.borrow_mut();
+            state.disassemble_at_pc = true;
+            state.pending_requests.push_back(registers_request());
+        }
+    }
+}
+
+impl MonitorState {
+    /// Translates a command to a list of debugger requests.
+    fn requests(
+        &mut self,
+        command: &str,
+        args: &[&str],
+        line: &str,
+    ) -> Result<Vec<Request>, String> {
+        return match (command, args) {
+            ("m", _) if args.len() <= 2 => {
+                let start = match args.first() {
+                    Some(arg) => parse_address_arg(arg)?,
+                    None => self.next_memory_address,
+                };
+                let end = match args.get(1) {
+                    Some(arg) => parse_address_arg(arg)?,
+                    None => start.saturating_add(DEFAULT_MEMORY_DUMP_LENGTH - 1),
+                };
+                if end < start {
+                    return Err("The end address precedes the start address".to_string());
+                }
+                self.next_memory_address = end.wrapping_add(1);
+                Ok(vec![Request::ReadMemory(ReadMemoryArguments {
+                    memory_reference: format!("0x{:04X}", start),
+                    offset: None,
+                    count: (end - start) as i64 + 1,
+                })])
+            }
+            (">", [address, values @ ..]) if !values.is_empty() => {
+                let address = parse_address_arg(address)?;
+                let data = values
+                    .iter()
+                    .map(|value| parse_byte_arg(value))
+                    .collect::<Result<Vec<u8>, String>>()?;
+                self.write_length = Some(data.len());
+                Ok(vec![Request::WriteMemory(WriteMemoryArguments {
+                    memory_reference: format!("0x{:04X}", address),
+                    offset: None,
+                    data: base64::encode(data),
+                })])
+            }
+            ("a", [address, _, ..]) => {
+                let start = parse_address_arg(address)?;
+                let instruction = line.trim_start().strip_prefix('a').unwrap().trim_start();
+                let instruction = instruction.strip_prefix(address).unwrap().trim();
+                self.assembly_address = Some(start);
+                Ok(vec![Request::Assemble(AssembleArguments {
+                    memory_reference: format!("0x{:04X}", start),
+                    source: instruction.to_string(),
+                })])
+            }
+            ("d", _) if args.len() <= 1 => {
+                let start = match args.first() {
+                    Some(arg) => parse_address_arg(arg)?,
+                    None => self
+                        .next_disassembly_address
+                        .or(self.pc)
+                        .ok_or("Unknown program counter; give the address explicitly")?,
+                };
+                Ok(vec![disassemble_request(start, DEFAULT_DISASSEMBLY_LENGTH)])
+            }
+            ("r", []) => Ok(vec![registers_request()]),
+            ("r", [assignment]) => {
+                let (name, value) = assignment
+                    .split_once('=')
+                    .ok_or_else(|| format!("Invalid register assignment: {}", assignment))?;
+                Ok(vec![
+                    set_register_request(&name.to_uppercase(), value),
+                    registers_request(),
+                ])
+            }
+            ("g", []) => Ok(vec![Request::Continue(main_thread())]),
+            ("g", [address]) => {
+                let address = parse_address_arg(address)?;
+                self.next_disassembly_address = None;
+                Ok(vec![
+                    set_register_request("PC", &format_word(address)),
+                    Request::Continue(main_thread()),
+                ])
+            }
+            ("z", []) => Ok(self.step(Request::StepIn(main_thread()))),
+            ("n", []) => Ok(self.step(Request::Next(main_thread()))),
+            ("ret", []) => Ok(self.step(Request::StepOut(main_thread()))),
+            ("stop", []) => Ok(vec![Request::Pause(main_thread())]),
+            ("b", [address]) => {
+                let address = parse_address_arg(address)?;
+                if !self.breakpoints.contains(&address) {
+                    self.breakpoints.push(address);
+                }
+                Ok(vec![self.breakpoints_request()])
+            }
+            ("bd", []) => {
+                self.breakpoints.clear();
+                Ok(vec![self.breakpoints_request()])
+            }
+            ("bd", [address]) => {
+                let address = parse_address_arg(address)?;
+                self.breakpoints.retain(|breakpoint| *breakpoint != address);
+                Ok(vec![self.breakpoints_request()])
+            }
+            ("e", _) if !args.is_empty() => {
+                let expression = line.trim_start().strip_prefix('e').unwrap().trim();
+                Ok(vec![Request::Evaluate(EvaluateArguments {
+                    expression: expression.to_string(),
+                    frame_id: None,
+                    context: Some("repl".to_string()),
+                })])
+            }
+            _ => Err(format!(
+                "Invalid command: {}. Type \"help\" for a list of commands.",
+                line.trim()
+            )),
+        };
+    }
+
+    /// Returns a stepping request. Since stepping changes the program counter,
+    /// the disassembly continues from the new one.
+    fn step(&mut self, request: Request) -> Vec<Request> {
+        self.next_disassembly_address = None;
+        return vec![request];
+    }
+
+    fn breakpoints_request(&self) -> Request {
+        Request::SetInstructionBreakpoints(SetInstructionBreakpointsArguments {
+            breakpoints: self
+                .breakpoints
+                .iter()
+                .map(|address| InstructionBreakpoint {
+                    instruction_reference: format!("0x{:04X}", address),
+                    offset: None,
+                    condition: None,
+                    hit_condition: None,
+                })
+                .collect(),
+        })
+    }
+}
+
+impl Default for MonitorAdapter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DebugAdapter for MonitorAdapter {
+    fn try_receive_message(&self) -> DebugAdapterResult<MessageEnvelope> {
+        loop {
+            let mut state = self.state.borrow_mut();
+            if let Some(request) = state.pending_requests.pop_front() {
+                state.sequence_number += 1;
+                return Ok(MessageEnvelope {
+                    seq: state.sequence_number,
+                    message: Message::Request(request),
+                });
+            }
+            drop(state);
+            match self.input.try_recv() {
+                Ok(line) => self.execute(&line),
+                // Once the input is closed, the monitor simply stops accepting
+                // commands; the machine keeps running.
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => {
+                    return Err(TryRecvError::Empty.into())
+                }
+            }
+        }
+    }
+
+    fn send_message(&self, message: MessageEnvelope) -> DebugAdapterResult<()> {
+        match message.message {
+            Message::Response(ResponseEnvelope { response, .. }) => self.handle_response(response),
+            Message::ErrorResponse(ErrorResponseEnvelope { message, .. }) => self.print(&message),
+            Message::Event(event) => self.handle_event(event),
+            Message::Request(_) => {}
+        }
+        Ok(())
+    }
+
+    fn disconnect(&self) -> DebugAdapterResult<()> {
+        Ok(())
+    }
+}
+
+fn main_thread() -> ThreadArguments {
+    ThreadArguments {
+        thread_id: MAIN_THREAD_ID,
+    }
+}
+
+fn registers_request() -> Request {
+    Request::Variables(VariablesArguments {
+        variables_reference: REGISTERS_VARIABLES_REFERENCE,
+    })
+}
+
+fn set_register_request(name: &str, value: &str) -> Request {
+    Request::SetVariable(SetVariableArguments {
+        variables_reference: REGISTERS_VARIABLES_REFERENCE,
+        name: name.to_string(),
+        value: value.to_string(),
+    })
+}
+
+fn disassemble_request(address: u16, instruction_count: i64) -> Request {
+    Request::Disassemble(DisassembleArguments {
+        memory_reference: format!("0x{:04X}", address),
+        offset: None,
+        instruction_offset: Some(0),
+        instruction_count,
+    })
+}
+
+/// Parses a hexadecimal address, optionally prefixed with `$` or `0x`.
+fn parse_address(text: &str) -> Option<u16> {
+    let hex_digits = text
+        .strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .unwrap_or(text);
+    u16::from_str_radix(hex_digits, 16).ok()
+}
+
+fn parse_address_arg(text: &str) -> Result<u16, String> {
+    parse_address(text).ok_or_else(|| format!("Invalid address: {}", text))
+}
+
+fn parse_byte_arg(text: &str) -> Result<u8, String> {
+    parse_address(text)
+        .and_then(|value| u8::try_from(value).ok())
+        .ok_or_else(|| format!("Invalid byte value: {}", text))
+}
+
+/// Returns the address of an instruction that follows a given one.
+fn address_after(instruction: &DisassembledInstruction) -> u16 {
+    let address = parse_address(&instruction.address).unwrap_or(0);
+    let length = instruction.instruction_bytes.split_whitespace().count();
+    return address.wrapping_add(length as u16);
+}
+
+fn format_instruction(instruction: &DisassembledInstruction) -> String {
+    let address = parse_address(&instruction.address).unwrap_or(0);
+    format!(
+        "{}  {:<8}  {}",
+        format_word(address),
+        instruction.instruction_bytes,
+        instruction.instruction
+    )
+}
+
+/// Formats memory contents as lines of hexadecimal bytes, followed by the same
+/// bytes as ASCII characters.
+fn format_memory_dump(response: &ReadMemoryResponse) -> String {
+    let start = parse_address(&response.address).unwrap_or(0);
+    let data = base64::decode(&response.data).unwrap_or_default();
+    return data
+        .chunks(MEMORY_DUMP_LINE_LENGTH)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let address = start.wrapping_add((i * MEMORY_DUMP_LINE_LENGTH) as u16);
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+            let text: String = chunk
+                .iter()
+                .map(|&byte| match byte {
+                    0x20..=0x7E => byte as char,
+                    _ => '.',
+                })
+                .collect();
+            format!(
+                "{}  {:<width$}  {}",
+                format_word(address),
+                hex.join(" "),
+                text,
+                width = MEMORY_DUMP_LINE_LENGTH * 3 - 1,
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+}
+
+fn format_registers(variables: &[Variable]) -> String {
+    variables
+        .iter()
+        .map(|variable| format!("{}={}", variable.name, variable.value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn describe_stop_reason(reason: &StopReason) -> &'static str {
+    match reason {
+        StopReason::Entry => "entry",
+        StopReason::Pause => "paused",
+        StopReason::Step => "step",
+        StopReason::Breakpoint => "breakpoint",
+        StopReason::DataBreakpoint => "data breakpoint",
+        StopReason::FunctionBreakpoint => "event breakpoint",
+        StopReason::Exception => "error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::Debugger;
+    use std::fmt::Debug;
+    use std::rc::Rc;
+    use ya6502::cpu::opcodes;
+    use ya6502::cpu::Cpu;
+    use ya6502::cpu::MachineInspector;
+    use ya6502::memory::Inspect;
+    use ya6502::memory::Memory;
+    use ya6502::memory::Ram;
+    use ya6502::memory::Read;
+    use ya6502::memory::ReadResult;
+    use ya6502::memory::WriteError;
+    use ya6502::memory::WriteResult;
+    use ya6502::test_utils::cpu_with_program;
+    use ya6502::test_utils::reset;
+
+    /// A writer that collects the monitor output, so that it can be inspected
+    /// while the monitor still owns it.
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl SharedOutput {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.borrow_mut())).unwrap()
+        }
+    }
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// RAM that rejects writes to its upper half, as if it was a ROM.
+    #[derive(Debug)]
+    struct HalfRom(Ram);
+
+    impl Inspect for HalfRom {
+        fn inspect(&self, address: u16) -> ReadResult {
+            self.0.inspect(address)
+        }
+    }
+
+    impl Read for HalfRom {
+        fn read(&mut self, address: u16) -> ReadResult {
+            self.0.read(address)
+        }
+    }
+
+    impl ya6502::memory::Write for HalfRom {
+        fn write(&mut self, address: u16, value: u8) -> WriteResult {
+            if address >= 0x8000 {
+                return Err(WriteError { address, value });
+            }
+            return self.0.write(address, value);
+        }
+    }
+
+    impl Memory for HalfRom {}
+
+    struct Fixture<M: Memory + Inspect + Debug = Ram> {
+        commands: mpsc::Sender<String>,
+        output: SharedOutput,
+        debugger: Debugger<MonitorAdapter>,
+        cpu: Cpu<M>,
+    }
+
+    impl Fixture {
+        fn new(program: &[u8]) -> Self {
+            return Self::with_cpu(cpu_with_program(program));
+        }
+    }
+
+    impl<M: Memory + Inspect + Debug> Fixture<M> {
+        fn with_cpu(cpu: Cpu<M>) -> Self {
+            let (commands, input) = mpsc::channel();
+            let output = SharedOutput::default();
+            let mut debugger = Debugger::new(MonitorAdapter::with_io(input, output.clone()));
+            debugger.update(&cpu).unwrap();
+            Self {
+                commands,
+                output,
+                debugger,
+                cpu,
+            }
+        }
+
+        /// Executes a command and returns the monitor output.
+        fn command(&mut self, command: &str) -> String {
+            self.commands.send(command.to_string()).unwrap();
+            self.debugger.process_messages(&mut self.cpu);
+            return self.output.take();
+        }
+
+        /// Runs the CPU until the debugger stops it, and returns the monitor
+        /// output.
+        fn run(&mut self) -> String {
+            for _ in 0..1000 {
+                if self.debugger.stopped() {
+                    break;
+                }
+                self.cpu.tick().unwrap();
+                self.debugger.update(&self.cpu).unwrap();
+            }
+            self.debugger.process_messages(&mut self.cpu);
+            return self.output.take();
+        }
+    }
+
+    #[test]
+    fn memory_dump_and_edit() {
+        let mut fixture = Fixture::new(&[opcodes::NOP]);
+        assert_eq!(fixture.command("> 0200 41 42 $43"), "");
+        assert_eq!(
+            fixture.command("m 0200 0211"),
+            "$0200  41 42 43 00 00 00 00 00 00 00 00 00 00 00 00 00  ABC.............\n\
+             $0210  00 00                                            ..\n"
+        );
+        assert_eq!(fixture.cpu.inspect_memory(0x0202), 0x43);
+
+        // Without arguments, the dump continues where the previous one ended.
+        assert!(fixture.command("m").starts_with("$0212  00 00"));
+
+        assert_eq!(fixture.command("> 0200 100"), "Invalid byte value: 100\n");
+        assert_eq!(fixture.command("> ffff 41 42"), "Wrote only 1 of 2 bytes\n");
+        assert_eq!(
+            fixture.command("m 0200 01FF"),
+            "The end address precedes the start address\n"
+        );
+    }
+
+    #[test]
+    fn failed_writes() {
+        let mut cpu = Cpu::new(Box::new(HalfRom(Ram::with_test_program(&[opcodes::NOP]))));
+        reset(&mut cpu);
+        let mut fixture = Fixture::with_cpu(cpu);
+        assert_eq!(
+            fixture.command("> 7fff 41 42"),
+            "Unable to write $42 to address $8000\n"
+        );
+        assert_eq!(fixture.cpu.inspect_memory(0x7FFF), 0x41);
+        assert_eq!(
+            fixture.command("a f000 lda #$12"),
+            "Unable to write $A9 to address $F000\n"
+        );
+        assert_eq!(fixture.cpu.inspect_memory(0xF000), opcodes::NOP);
+    }
+
+    #[test]
+    fn assembly() {
+        let mut fixture = Fixture::new(&[opcodes::NOP]);
+        assert_eq!(
+            fixture.command("a f000 lda #$12"),
+            "$F000  A9 12     LDA #$12\n"
+        );
+        assert_eq!(
+            fixture.command("a f002 sta ($80),y"),
+            "$F002  91 80     STA ($80),Y\n"
+        );
+        // The disassembly continues after the new instructions.
+        assert_eq!(
+            fixture.command("d").lines().next(),
+            Some("$F004  00        BRK")
+        );
+        assert_eq!(
+            fixture.command("a f004 bne $f000"),
+            "$F004  D0 FA     BNE $F000\n"
+        );
+        assert_eq!(fixture.cpu.inspect_memory(0xF005), 0xFA);
+
+        assert_eq!(
+            fixture.command("a f000 lda #$1234"),
+            "Line 1: Value out of range: 4660\n"
+        );
+        assert_eq!(fixture.cpu.inspect_memory(0xF001), 0x12);
+    }
+
+    #[test]
+    fn registers() {
+        let mut fixture = Fixture::new(&[opcodes::NOP]);
+        assert!(fixture.command("r").contains(" PC=$F000 FLAGS="));
+        assert!(fixture.command("r x=$12").contains(" X=$12 "));
+        assert_eq!(fixture.cpu.reg_x(), 0x12);
+        assert_eq!(fixture.command("r x"), "Invalid register assignment: x\n");
+        assert!(fixture
+            .command("r x=zz")
+            .starts_with("Unable to set X to zz: "));
+        assert_eq!(fixture.cpu.reg_x(), 0x12);
+    }
+
+    #[test]
+    fn disassembly() {
+        let mut fixture =
+            Fixture::new(&[opcodes::LDA_IMM, 0x45, opcodes::STA_ZP, 0xEA, opcodes::NOP]);
+        assert_eq!(
+            fixture.command("d"),
+            "Unknown program counter; give the address explicitly\n"
+        );
+        fixture.command("r");
+        let output = fixture.command("d");
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), DEFAULT_DISASSEMBLY_LENGTH as usize);
+        assert_eq!(lines[0], "$F000  A9 45     LDA #$45");
+        assert_eq!(lines[1], "$F002  85 EA     STA $EA");
+        assert_eq!(lines[2], "$F004  EA        NOP");
+    }
+
+    #[test]
+    fn stepping_and_breakpoints() {
+        let mut fixture = Fixture::new(&[
+            opcodes::LDA_IMM,
+            0x45,
+            opcodes::STA_ZP,
+            0xEA,
+            opcodes::NOP,
+            opcodes::NOP,
+        ]);
+        let output = fixture.command("stop");
+        assert!(output.starts_with("Stopped: paused\nA="), "{}", output);
+        assert!(
+            output.ends_with("$F000  A9 45     LDA #$45\n"),
+            "{}",
+            output
+        );
+
+        fixture.command("z");
+        let output = fixture.run();
+        assert!(output.starts_with("Stopped: step\nA=$45"), "{}", output);
+        assert!(output.ends_with("$F002  85 EA     STA $EA\n"), "{}", output);
+
+        assert_eq!(fixture.command("b f005"), "");
+        assert_eq!(fixture.command("bl"), "$F005\n");
+        fixture.command("g");
+        let output = fixture.run();
+        assert!(output.starts_with("Stopped: breakpoint\n"), "{}", output);
+        assert_eq!(fixture.cpu.reg_pc(), 0xF005);
+        assert_eq!(fixture.cpu.inspect_memory(0xEA), 0x45);
+
+        fixture.command("bd");
+        assert_eq!(fixture.command("bl"), "No breakpoints\n");
+    }
+
+    #[test]
+    fn evaluation() {
+        let mut fixture = Fixture::new(&[opcodes::NOP]);
+        assert_eq!(fixture.command("e 2 + 3 * 4"), "$0E (14)\n");
+        // Cheat commands are executed by the machine controller.
+        assert_eq!(fixture.command("e cheat $80=$05"), "");
+        fixture.debugger.execute_cheat_commands(|_| Ok(())).unwrap();
+        assert_eq!(fixture.output.take(), "Cheat installed: $0080=$05\n");
+        assert_eq!(fixture.command("e 1 / 0"), "Division by zero\n");
+    }
+
+    #[test]
+    fn invalid_commands() {
+        let mut fixture = Fixture::new(&[opcodes::NOP]);
+        assert_eq!(
+            fixture.command("foo 1 2"),
+            "Invalid command: foo 1 2. Type \"help\" for a list of commands.\n"
+        );
+        assert_eq!(fixture.command("   "), "");
+        assert!(fixture.command("help").starts_with("Monitor commands"));
+    }
+}
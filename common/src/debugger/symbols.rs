@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::io;
+
+/// Maps symbol names (labels, constants, etc.) to their values.
+pub type SymbolTable = HashMap<String, i64>;
+
+/// Reads a symbol table in the format produced by the `-s` option of DASM.
+/// Each line consists of a symbol name, its hexadecimal value, and optional
+/// flags. The header and footer lines, as well as lines that don't follow this
+/// format, are ignored.
+pub fn read_dasm_symbols(reader: impl io::BufRead) -> io::Result<SymbolTable> {
+    let mut symbols = SymbolTable::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.starts_with("---") {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        if let (Some(name), Some(value)) = (fields.next(), fields.next()) {
+            if let Ok(value) = i64::from_str_radix(value, 16) {
+                symbols.insert(name.to_string(), value);
+            }
+        }
+    }
+    return Ok(symbols);
+}
+
+/// Names of memory-mapped hardware registers of a given machine, used to
+/// annotate instruction operands in the disassembly. Some chips (like the TIA)
+/// map different registers at the same address for reading and writing, so the
+/// names are kept separately for both kinds of access. Since chips usually
+/// don't decode all the address lines, the table uses a mirroring function to
+/// find the names of registers visible at multiple addresses.
+#[derive(Debug, Clone)]
+pub struct RegisterNames {
+    reads: HashMap<u16, String>,
+    writes: HashMap<u16, String>,
+    /// Returns the address at which the name of a register visible at a
+    /// given address is stored.
+    mirrored_address: fn(u16) -> u16,
+}
+
+impl Default for RegisterNames {
+    fn default() -> Self {
+        Self {
+            reads: HashMap::new(),
+            writes: HashMap::new(),
+            mirrored_address: |address| address,
+        }
+    }
+}
+
+impl RegisterNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds registers that can be both read and written.
+    pub fn with_registers<'a>(self, registers: impl IntoIterator<Item = (&'a str, u16)>) -> Self {
+        let registers: Vec<_> = registers.into_iter().collect();
+        return self
+            .with_read_registers(registers.iter().copied())
+            .with_write_registers(registers);
+    }
+
+    /// Adds registers that are only meaningful when read.
+    pub fn with_read_registers<'a>(
+        mut self,
+        registers: impl IntoIterator<Item = (&'a str, u16)>,
+    ) -> Self {
+        for (name, address) in registers {
+            self.reads.insert(address, name.to_string());
+        }
+        return self;
+    }
+
+    /// Adds registers that are only meaningful when written.
+    pub fn with_write_registers<'a>(
+        mut self,
+        registers: impl IntoIterator<Item = (&'a str, u16)>,
+    ) -> Self {
+        for (name, address) in registers {
+            self.writes.insert(address, name.to_string());
+        }
+        return self;
+    }
+
+    /// Sets the mirroring function of the address space. By default, each
+    /// register name only applies to a single address.
+    pub fn with_mirroring(mut self, mirrored_address: fn(u16) -> u16) -> Self {
+        self.mirrored_address = mirrored_address;
+        return self;
+    }
+
+    /// Adds all registers from another table, for example, one that describes
+    /// another chip of the same machine. The mirroring function of this table
+    /// is kept.
+    pub fn merge(mut self, other: RegisterNames) -> Self {
+        self.reads.extend(other.reads);
+        self.writes.extend(other.writes);
+        return self;
+    }
+
+    /// Returns the name of a register that is read from a given address.
+    pub fn read_name(&self, address: u16) -> Option<&str> {
+        self.reads
+            .get(&(self.mirrored_address)(address))
+            .map(String::as_str)
+    }
+
+    /// Returns the name of a register that is written to a given address.
+    pub fn write_name(&self, address: u16) -> Option<&str> {
+        self.writes
+            .get(&(self.mirrored_address)(address))
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_dasm_symbols() {
+        let symbol_file = "--- Symbol List (sorted by name)\n\
+                           COLUBK                   0009              (R )\n\
+                           Start                    f000              (R )\n\
+                           score                    0080\n\
+                           --- End of Symbol List.\n";
+        assert_eq!(
+            read_dasm_symbols(symbol_file.as_bytes()).unwrap(),
+            SymbolTable::from([
+                ("COLUBK".to_string(), 0x0009),
+                ("Start".to_string(), 0xF000),
+                ("score".to_string(), 0x0080),
+            ]),
+        );
+    }
+
+    #[test]
+    fn register_names() {
+        let names = RegisterNames::new()
+            .with_registers([("SWCHA", 0x280)])
+            .with_read_registers([("INPT4", 0x0C)])
+            .merge(RegisterNames::new().with_write_registers([("REFP1", 0x0C)]));
+        assert_eq!(names.read_name(0x280), Some("SWCHA"));
+        assert_eq!(names.write_name(0x280), Some("SWCHA"));
+        assert_eq!(names.read_name(0x0C), Some("INPT4"));
+        assert_eq!(names.write_name(0x0C), Some("REFP1"));
+        assert_eq!(names.read_name(0x0D), None);
+    }
+
+    #[test]
+    fn mirrored_register_names() {
+        let names = RegisterNames::new()
+            .with_read_registers([("INPT4", 0x0C)])
+            .with_write_registers([("COLUBK", 0x09)])
+            .with_mirroring(|address| address & 0x0F);
+        assert_eq!(names.read_name(0x3C), Some("INPT4"));
+        assert_eq!(names.write_name(0x49), Some("COLUBK"));
+        assert_eq!(names.write_name(0x4C), None);
+    }
+}
@@ -1,16 +1,35 @@
 #![cfg(test)]
 
 use super::*;
+use crate::cheats::CheatTable;
 use crate::debugger::adapter::FakeDebugAdapter;
 use crate::debugger::dap_types::Breakpoint;
+use crate::debugger::dap_types::Chip;
+use crate::debugger::dap_types::ChipRegister;
+use crate::debugger::dap_types::ChipStateResponse;
+use crate::debugger::dap_types::DataBreakpoint;
 use crate::debugger::dap_types::DisassembledInstruction;
+use crate::debugger::dap_types::ErrorResponseEnvelope;
+use crate::debugger::dap_types::EvaluateArguments;
+use crate::debugger::dap_types::EvaluateResponse;
+use crate::debugger::dap_types::FunctionBreakpoint;
 use crate::debugger::dap_types::InitializeArguments;
 use crate::debugger::dap_types::InstructionBreakpoint;
+use crate::debugger::dap_types::LaunchArguments;
+use crate::debugger::dap_types::MemoryKind;
 use crate::debugger::dap_types::MessageEnvelope;
 use crate::debugger::dap_types::ScopesArguments;
 use crate::debugger::dap_types::SetInstructionBreakpointsArguments;
+use crate::debugger::dap_types::SetVariableArguments;
+use crate::debugger::dap_types::SetVariableResponse;
+use crate::debugger::dap_types::ThreadArguments;
 use crate::debugger::dap_types::VariablesArguments;
+use crate::debugger::dap_types::WriteMemoryArguments;
+use crate::debugger::dap_types::WriteMemoryResponse;
 use std::assert_matches::assert_matches;
+use ya6502::cpu::flags;
+use ya6502::cpu::opcodes;
+use ya6502::cpu::ChipState;
 use ya6502::cpu::Cpu;
 use ya6502::cpu::MockMachineInspector;
 use ya6502::cpu_with_code;
@@ -27,6 +46,16 @@ fn pop_response(adapter: &FakeDebugAdapter) -> Response {
     }
 }
 
+fn pop_error_message(adapter: &FakeDebugAdapter) -> String {
+    match adapter.pop_outgoing() {
+        Some(MessageEnvelope {
+            message: Message::ErrorResponse(ErrorResponseEnvelope { message, .. }),
+            ..
+        }) => message,
+        other => panic!("Expected an error response, got {:?}", other),
+    }
+}
+
 fn assert_responded_with(adapter: &FakeDebugAdapter, expected_response: Response) {
     let response = pop_response(adapter);
     assert_eq!(response, expected_response);
@@ -64,9 +93,9 @@ fn tick_while_running<A: DebugAdapter>(debugger: &mut Debugger<A>, cpu: &mut Cpu
 fn get_stack_frames(
     adapter: &FakeDebugAdapter,
     debugger: &mut Debugger<FakeDebugAdapter>,
-    cpu: &Cpu<Ram>,
+    cpu: &mut Cpu<Ram>,
 ) -> Vec<StackFrame> {
-    adapter.push_request(Request::StackTrace {});
+    adapter.push_request(Request::StackTrace(ThreadArguments { thread_id: 1 }));
     debugger.process_messages(cpu);
     let stack_trace_response = pop_response(&adapter);
     return match stack_trace_response {
@@ -78,7 +107,7 @@ fn get_stack_frames(
 fn get_scopes(
     adapter: &FakeDebugAdapter,
     debugger: &mut Debugger<FakeDebugAdapter>,
-    cpu: &Cpu<Ram>,
+    cpu: &mut Cpu<Ram>,
     frame_id: i64,
 ) -> Vec<Scope> {
     adapter.push_request(Request::Scopes(ScopesArguments { frame_id }));
@@ -92,7 +121,7 @@ fn get_scopes(
 
 #[test]
 fn uses_sequence_numbers() {
-    let inspector = MockMachineInspector::new();
+    let mut inspector = MockMachineInspector::new();
     let adapter = FakeDebugAdapter::default();
     adapter.push_incoming(Ok(MessageEnvelope {
         seq: 5,
@@ -110,7 +139,7 @@ fn uses_sequence_numbers() {
     }));
     let mut debugger = Debugger::new(adapter.clone());
 
-    debugger.process_messages(&inspector);
+    debugger.process_messages(&mut inspector);
 
     assert_matches!(
         adapter.pop_outgoing(),
@@ -140,7 +169,8 @@ fn uses_sequence_numbers() {
 
 #[test]
 fn initialization_sequence() {
-    let inspector = MockMachineInspector::new();
+    let mut inspector = MockMachineInspector::new();
+    inspector.expect_memory_map().returning(Vec::new);
     let adapter = FakeDebugAdapter::default();
     adapter.push_request(Request::Initialize(InitializeArguments {
         client_name: Some("Visual Studio Code".into()),
@@ -155,14 +185,21 @@ fn initialization_sequence() {
     adapter.push_request(Request::Threads {});
     let mut debugger = Debugger::new(adapter.clone());
 
-    debugger.process_messages(&inspector);
+    debugger.process_messages(&mut inspector);
 
     assert_responded_with(
         &adapter,
         Response::Initialize(Capabilities {
             supports_disassemble_request: true,
             supports_instruction_breakpoints: true,
+            supports_conditional_breakpoints: true,
+            supports_hit_conditional_breakpoints: true,
+            supports_data_breakpoints: true,
+            supports_function_breakpoints: true,
             supports_read_memory_request: true,
+            supports_write_memory_request: true,
+            supports_set_variable: true,
+            supports_step_back: true,
         }),
     );
     assert_emitted(&adapter, Event::Initialized);
@@ -173,6 +210,7 @@ fn initialization_sequence() {
             thread_id: 1,
             reason: StopReason::Entry,
             all_threads_stopped: true,
+            text: None,
         }),
     );
     assert_responded_with(&adapter, Response::SetExceptionBreakpoints);
@@ -208,8 +246,8 @@ fn stack_trace() {
     let mut debugger = Debugger::new(adapter.clone());
     debugger.update(&cpu).unwrap();
 
-    adapter.push_request(Request::StackTrace {});
-    debugger.process_messages(&cpu);
+    adapter.push_request(Request::StackTrace(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
     assert_responded_with(
         &adapter,
         Response::StackTrace(StackTraceResponse {
@@ -225,17 +263,17 @@ fn stack_trace() {
     );
     assert_eq!(adapter.pop_outgoing(), None);
 
-    adapter.push_request(Request::StepIn {});
-    debugger.process_messages(&cpu);
+    adapter.push_request(Request::StepIn(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
     tick_while_running(&mut debugger, &mut cpu);
-    adapter.push_request(Request::StepIn {});
-    debugger.process_messages(&cpu);
+    adapter.push_request(Request::StepIn(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
     tick_while_running(&mut debugger, &mut cpu);
     purge_messages(&adapter);
     assert_eq!(cpu.reg_pc(), 0xF005);
 
-    adapter.push_request(Request::StackTrace {});
-    debugger.process_messages(&cpu);
+    adapter.push_request(Request::StackTrace(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
     assert_responded_with(
         &adapter,
         Response::StackTrace(StackTraceResponse {
@@ -263,7 +301,7 @@ fn stack_trace() {
 
 #[test]
 fn disassembly() {
-    let cpu = cpu_with_code! {
+    let mut cpu = cpu_with_code! {
             lda 0x45
             sta 0xEA
     };
@@ -283,7 +321,7 @@ fn disassembly() {
         instruction_offset: None,
         instruction_count: 1,
     }));
-    debugger.process_messages(&cpu);
+    debugger.process_messages(&mut cpu);
 
     assert_responded_with(
         &adapter,
@@ -315,9 +353,45 @@ fn disassembly() {
     assert_eq!(adapter.pop_outgoing(), None);
 }
 
+#[test]
+fn disassembly_with_register_names() {
+    let mut cpu = cpu_with_program(&[opcodes::STA_ABS, 0x20, 0xD0, opcodes::LDA_ZP, 0x45]);
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone())
+        .with_register_names(RegisterNames::new().with_registers([("EXTCOL", 0xD020)]));
+    debugger.update(&cpu).unwrap();
+
+    adapter.push_request(Request::Disassemble(DisassembleArguments {
+        memory_reference: "0xF000".to_string(),
+        offset: None,
+        instruction_offset: None,
+        instruction_count: 2,
+    }));
+    debugger.process_messages(&mut cpu);
+
+    assert_responded_with(
+        &adapter,
+        Response::Disassemble(DisassembleResponse {
+            instructions: vec![
+                DisassembledInstruction {
+                    address: "0xF000".to_string(),
+                    instruction_bytes: "8D 20 D0".to_string(),
+                    instruction: "STA $D020   ; EXTCOL".to_string(),
+                },
+                DisassembledInstruction {
+                    address: "0xF003".to_string(),
+                    instruction_bytes: "A5 45".to_string(),
+                    instruction: "LDA $45".to_string(),
+                },
+            ],
+        }),
+    );
+    assert_eq!(adapter.pop_outgoing(), None);
+}
+
 #[test]
 fn disassembly_ambiguous() {
-    let cpu = cpu_with_code! {
+    let mut cpu = cpu_with_code! {
             lda 0x45
             sta 0xEA
             sta 0xAE
@@ -338,7 +412,7 @@ fn disassembly_ambiguous() {
         instruction_offset: Some(-1),
         instruction_count: 2,
     }));
-    debugger.process_messages(&cpu);
+    debugger.process_messages(&mut cpu);
 
     assert_responded_with(
         &adapter,
@@ -389,7 +463,7 @@ fn disassembly_ambiguous() {
 
 #[test]
 fn read_memory() {
-    let cpu = cpu_with_program(&[0x8B, 0xAD, 0xF0, 0x0D]);
+    let mut cpu = cpu_with_program(&[0x8B, 0xAD, 0xF0, 0x0D]);
     let adapter = FakeDebugAdapter::default();
     let mut debugger = Debugger::new(adapter.clone());
     debugger.update(&cpu).unwrap();
@@ -404,7 +478,7 @@ fn read_memory() {
         offset: None,
         count: 2,
     }));
-    debugger.process_messages(&cpu);
+    debugger.process_messages(&mut cpu);
 
     assert_responded_with(
         &adapter,
@@ -427,7 +501,7 @@ fn read_memory() {
 
 #[test]
 fn read_memory_with_offset() {
-    let cpu = cpu_with_program(&[0x8B, 0xAD, 0xF0, 0x0D]);
+    let mut cpu = cpu_with_program(&[0x8B, 0xAD, 0xF0, 0x0D]);
     let adapter = FakeDebugAdapter::default();
     let mut debugger = Debugger::new(adapter.clone());
     debugger.update(&cpu).unwrap();
@@ -437,7 +511,7 @@ fn read_memory_with_offset() {
         offset: Some(-2),
         count: 2,
     }));
-    debugger.process_messages(&cpu);
+    debugger.process_messages(&mut cpu);
 
     assert_responded_with(
         &adapter,
@@ -463,7 +537,7 @@ fn read_memory_truncates_after_last_bytes() {
         offset: Some(0),
         count: 10,
     }));
-    debugger.process_messages(&cpu);
+    debugger.process_messages(&mut cpu);
 
     assert_responded_with(
         &adapter,
@@ -476,6 +550,121 @@ fn read_memory_truncates_after_last_bytes() {
     assert_eq!(adapter.pop_outgoing(), None);
 }
 
+#[test]
+fn write_memory() {
+    let mut cpu = cpu_with_program(&[]);
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.update(&cpu).unwrap();
+
+    adapter.push_request(Request::WriteMemory(WriteMemoryArguments {
+        memory_reference: "0x0080".to_string(),
+        offset: Some(2),
+        data: "i63wDQ==".to_string(),
+    }));
+    adapter.push_request(Request::WriteMemory(WriteMemoryArguments {
+        memory_reference: "0xFFFE".to_string(),
+        offset: None,
+        data: "i63wDQ==".to_string(),
+    }));
+    debugger.process_messages(&mut cpu);
+
+    assert_responded_with(
+        &adapter,
+        Response::WriteMemory(WriteMemoryResponse {
+            bytes_written: Some(4),
+        }),
+    );
+    assert_responded_with(
+        &adapter,
+        Response::WriteMemory(WriteMemoryResponse {
+            bytes_written: Some(2),
+        }),
+    );
+    assert_eq!(adapter.pop_outgoing(), None);
+    assert_eq!(
+        cpu.memory().bytes[0x0082..=0x0085],
+        [0x8B, 0xAD, 0xF0, 0x0D]
+    );
+    assert_eq!(cpu.memory().bytes[0xFFFE..=0xFFFF], [0x8B, 0xAD]);
+}
+
+#[test]
+fn write_memory_errors() {
+    let mut cpu = cpu_with_program(&[]);
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.update(&cpu).unwrap();
+    let write_memory = |memory_reference: &str, offset, data: &str| {
+        Request::WriteMemory(WriteMemoryArguments {
+            memory_reference: memory_reference.to_string(),
+            offset,
+            data: data.to_string(),
+        })
+    };
+
+    adapter.push_request(write_memory("0080", None, "AQI="));
+    adapter.push_request(write_memory("0xZZ", None, "AQI="));
+    adapter.push_request(write_memory("0x0001", Some(-2), "AQI="));
+    adapter.push_request(write_memory("0xFFFF", Some(1), "AQI="));
+    adapter.push_request(write_memory("0x0080", None, "not base64!"));
+    debugger.process_messages(&mut cpu);
+
+    assert_eq!(
+        pop_error_message(&adapter),
+        "Invalid memory reference: 0080"
+    );
+    assert_eq!(
+        pop_error_message(&adapter),
+        "Invalid memory reference: 0xZZ"
+    );
+    assert_eq!(pop_error_message(&adapter), "Address out of range: -1");
+    assert_eq!(pop_error_message(&adapter), "Address out of range: 65536");
+    assert!(pop_error_message(&adapter).starts_with("Invalid data: "));
+    assert_eq!(adapter.pop_outgoing(), None);
+    assert_eq!(cpu.memory().bytes[0x0000..=0x0001], [0x00, 0x00]);
+    assert_eq!(cpu.memory().bytes[0x0080..=0x0081], [0x00, 0x00]);
+}
+
+#[test]
+fn assemble() {
+    let mut cpu = cpu_with_program(&[opcodes::NOP, opcodes::NOP, opcodes::NOP]);
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.update(&cpu).unwrap();
+
+    adapter.push_request(Request::Assemble(AssembleArguments {
+        memory_reference: "0xF001".to_string(),
+        source: "LDA #$12\nSTA $80".to_string(),
+    }));
+    adapter.push_request(Request::Assemble(AssembleArguments {
+        memory_reference: "0xF000".to_string(),
+        source: "INX\nLDA #$1234".to_string(),
+    }));
+    debugger.process_messages(&mut cpu);
+
+    assert_responded_with(
+        &adapter,
+        Response::Assemble(AssembleResponse {
+            bytes_written: 4,
+            error: None,
+        }),
+    );
+    assert_responded_with(
+        &adapter,
+        Response::Assemble(AssembleResponse {
+            bytes_written: 0,
+            error: Some("Line 2: Value out of range: 4660".to_string()),
+        }),
+    );
+    assert_eq!(adapter.pop_outgoing(), None);
+    // The second request shouldn't have changed anything.
+    assert_eq!(
+        cpu.memory().bytes[0xF000..=0xF005],
+        [opcodes::NOP, 0xA9, 0x12, 0x85, 0x80, 0x00]
+    );
+}
+
 // And the prize for the uglies test in this entire codebase goes to...
 #[test]
 fn variables() {
@@ -506,23 +695,27 @@ fn variables() {
                 InstructionBreakpoint {
                     instruction_reference: "0xF008".to_string(),
                     offset: None,
+                    condition: None,
+                    hit_condition: None,
                 },
                 InstructionBreakpoint {
                     instruction_reference: "0xF011".to_string(),
                     offset: None,
+                    condition: None,
+                    hit_condition: None,
                 },
             ],
         },
     ));
-    adapter.push_request(Request::Continue {});
-    debugger.process_messages(&cpu);
+    adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
     tick_while_running(&mut debugger, &mut cpu);
     purge_messages(&adapter);
     assert_eq!(cpu.reg_pc(), 0xF008);
 
-    let stack_frames = get_stack_frames(&adapter, &mut debugger, &cpu);
+    let stack_frames = get_stack_frames(&adapter, &mut debugger, &mut cpu);
     let frame_1_id = stack_frames[0].id;
-    let scopes = get_scopes(&adapter, &mut debugger, &cpu, frame_1_id);
+    let scopes = get_scopes(&adapter, &mut debugger, &mut cpu, frame_1_id);
     assert_eq!(scopes.len(), 2);
     assert_eq!(scopes[0].name, "Registers");
     assert_eq!(
@@ -539,7 +732,7 @@ fn variables() {
     adapter.push_request(Request::Variables(VariablesArguments {
         variables_reference: registers_reference,
     }));
-    debugger.process_messages(&cpu);
+    debugger.process_messages(&mut cpu);
     assert_responded_with(
         &adapter,
         Response::Variables(VariablesResponse {
@@ -587,7 +780,7 @@ fn variables() {
     adapter.push_request(Request::Variables(VariablesArguments {
         variables_reference: memory_reference,
     }));
-    debugger.process_messages(&cpu);
+    debugger.process_messages(&mut cpu);
     assert_responded_with(
         &adapter,
         Response::Variables(VariablesResponse {
@@ -600,16 +793,16 @@ fn variables() {
         }),
     );
 
-    adapter.push_request(Request::Continue {});
-    debugger.process_messages(&cpu);
+    adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
     tick_while_running(&mut debugger, &mut cpu);
     purge_messages(&adapter);
     assert_eq!(cpu.reg_pc(), 0xF011);
 
-    let stack_frames = get_stack_frames(&adapter, &mut debugger, &cpu);
+    let stack_frames = get_stack_frames(&adapter, &mut debugger, &mut cpu);
     assert_eq!(stack_frames.len(), 2);
     let frame_2_id = stack_frames[0].id;
-    let scopes = get_scopes(&adapter, &mut debugger, &cpu, frame_2_id);
+    let scopes = get_scopes(&adapter, &mut debugger, &mut cpu, frame_2_id);
     assert_eq!(scopes.len(), 2);
     assert_eq!(scopes[0].name, "Registers");
     assert_eq!(
@@ -624,7 +817,7 @@ fn variables() {
     adapter.push_request(Request::Variables(VariablesArguments {
         variables_reference: memory_reference,
     }));
-    debugger.process_messages(&cpu);
+    debugger.process_messages(&mut cpu);
     assert_responded_with(
         &adapter,
         Response::Variables(VariablesResponse {
@@ -640,7 +833,7 @@ fn variables() {
     adapter.push_request(Request::Variables(VariablesArguments {
         variables_reference: registers_reference,
     }));
-    debugger.process_messages(&cpu);
+    debugger.process_messages(&mut cpu);
     assert_responded_with(
         &adapter,
         Response::Variables(VariablesResponse {
@@ -686,7 +879,7 @@ fn variables() {
     );
 
     assert_eq!(stack_frames[1].id, frame_1_id);
-    let scopes = get_scopes(&adapter, &mut debugger, &cpu, frame_1_id);
+    let scopes = get_scopes(&adapter, &mut debugger, &mut cpu, frame_1_id);
     assert_eq!(scopes.len(), 1);
     assert_eq!(scopes[0].name, "Memory");
     let memory_reference = scopes[0].variables_reference;
@@ -694,7 +887,7 @@ fn variables() {
     adapter.push_request(Request::Variables(VariablesArguments {
         variables_reference: memory_reference,
     }));
-    debugger.process_messages(&cpu);
+    debugger.process_messages(&mut cpu);
     assert_responded_with(
         &adapter,
         Response::Variables(VariablesResponse {
@@ -709,164 +902,597 @@ fn variables() {
 }
 
 #[test]
-fn continue_and_pause() {
-    let inspector = MockMachineInspector::new();
+fn set_variable() {
+    let mut cpu = cpu_with_code! {
+            lda #0x01 // 0xF000
+            ldx #0x02 // 0xF002
+    };
     let adapter = FakeDebugAdapter::default();
-    adapter.push_request(Request::Continue {});
     let mut debugger = Debugger::new(adapter.clone());
-    assert!(debugger.stopped());
-
-    debugger.process_messages(&inspector);
-
-    assert_responded_with(&adapter, Response::Continue {});
-    assert!(!debugger.stopped());
+    debugger.update(&cpu).unwrap();
 
-    adapter.push_request(Request::Pause {});
-    debugger.process_messages(&inspector);
+    let set_variable = |name: &str, value: &str| {
+        Request::SetVariable(SetVariableArguments {
+            variables_reference: REGISTERS_VARIABLES_REFERENCE,
+            name: name.to_string(),
+            value: value.to_string(),
+        })
+    };
+    let variable_value = |value: &str| {
+        Response::SetVariable(SetVariableResponse {
+            value: value.to_string(),
+        })
+    };
 
-    assert_responded_with(&adapter, Response::Pause {});
-    assert_emitted(
-        &adapter,
-        Event::Stopped(StoppedEvent {
-            thread_id: 1,
-            reason: StopReason::Pause,
-            all_threads_stopped: true,
-        }),
+    adapter.push_request(set_variable("A", "$3F"));
+    adapter.push_request(set_variable("X", "A == $3F"));
+    adapter.push_request(set_variable("Y", "[$F001]"));
+    adapter.push_request(set_variable("SP", "0x1FE"));
+    adapter.push_request(set_variable("FLAGS", "n.-..iZc"));
+    adapter.push_request(set_variable("PC", "$F002"));
+    debugger.process_messages(&mut cpu);
+    assert_responded_with(&adapter, variable_value("$3F"));
+    assert_responded_with(&adapter, variable_value("$01"));
+    assert_responded_with(&adapter, variable_value("$01"));
+    assert_responded_with(&adapter, variable_value("$FE"));
+    assert_responded_with(&adapter, variable_value("N.-..IZC"));
+    assert_responded_with(&adapter, variable_value("$F002"));
+    assert_eq!(adapter.pop_outgoing(), None);
+    assert_eq!(cpu.reg_a(), 0x3F);
+    assert_eq!(cpu.reg_x(), 0x01);
+    assert_eq!(cpu.reg_y(), 0x01);
+    assert_eq!(cpu.reg_sp(), 0xFE);
+    assert_eq!(
+        cpu.flags(),
+        flags::N | flags::UNUSED | flags::I | flags::Z | flags::C
     );
-    assert!(debugger.stopped());
+
+    // Invalid values are rejected and leave the registers untouched.
+    adapter.push_request(set_variable("A", "$3F +"));
+    adapter.push_request(set_variable("FLAGS", "NV"));
+    debugger.process_messages(&mut cpu);
+    assert!(pop_error_message(&adapter).starts_with("Unable to set A to $3F +"));
+    assert!(pop_error_message(&adapter).starts_with("Unable to set FLAGS to NV"));
     assert_eq!(adapter.pop_outgoing(), None);
+
+    cpu.ticks(2).unwrap();
+    assert_eq!(cpu.reg_a(), 0x3F);
+    assert_eq!(cpu.reg_x(), 0x02);
 }
 
 #[test]
-fn step_in() {
-    let mut cpu = cpu_with_code! {
-            nop
-    };
-
+fn set_variable_clears_history() {
+    let mut cpu = cpu_with_program(&[opcodes::LDX_IMM, 0x01, opcodes::INX, opcodes::NOP]);
     let adapter = FakeDebugAdapter::default();
-    adapter.push_request(Request::StepIn {});
     let mut debugger = Debugger::new(adapter.clone());
     debugger.update(&cpu).unwrap();
+    for _ in 0..4 {
+        cpu.tick().unwrap();
+        debugger.update(&cpu).unwrap();
+    }
+    assert_eq!(cpu.reg_x(), 2);
 
-    debugger.process_messages(&cpu);
-
-    assert_responded_with(&adapter, Response::StepIn {});
-    assert!(!debugger.stopped());
-
-    cpu.tick().unwrap();
-    debugger.update(&cpu).unwrap();
-    cpu.tick().unwrap();
-    assert_eq!(adapter.pop_outgoing(), None);
-
-    debugger.update(&cpu).unwrap();
-    assert!(debugger.stopped());
-    assert_emitted(
-        &adapter,
-        Event::Stopped(StoppedEvent {
-            thread_id: 1,
-            reason: StopReason::Step,
-            all_threads_stopped: true,
-        }),
-    )
+    adapter.push_request(Request::SetVariable(SetVariableArguments {
+        variables_reference: REGISTERS_VARIABLES_REFERENCE,
+        name: "X".to_string(),
+        value: "$10".to_string(),
+    }));
+    adapter.push_request(Request::StepBack(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
+    purge_messages(&adapter);
+    // The history recorded before the write is gone, so there's nothing to
+    // step back to.
+    assert_eq!(cpu.reg_pc(), 0xF003);
+    assert_eq!(cpu.reg_x(), 0x10);
 }
 
 #[test]
-fn next() {
+fn evaluate() {
     let mut cpu = cpu_with_code! {
-            jsr subroutine
-            nop
-        subroutine:
-            rts
+            ldx #0x02 // 0xF000
     };
-
+    cpu.mut_memory().bytes[0x80] = 0x3D;
     let adapter = FakeDebugAdapter::default();
-    adapter.push_request(Request::Next {});
-    let mut debugger = Debugger::new(adapter.clone());
+    let mut debugger = Debugger::new(adapter.clone())
+        .with_symbols(SymbolTable::from([("score".to_string(), 0x80)]));
     debugger.update(&cpu).unwrap();
+    cpu.ticks(2).unwrap();
 
-    debugger.process_messages(&cpu);
+    let evaluate = |expression: &str| {
+        Request::Evaluate(EvaluateArguments {
+            expression: expression.to_string(),
+            frame_id: None,
+            context: Some("watch".to_string()),
+        })
+    };
+    let result = |result: &str| {
+        Response::Evaluate(EvaluateResponse {
+            result: result.to_string(),
+            variables_reference: 0,
+        })
+    };
 
-    purge_messages(&adapter);
-    tick_while_running(&mut debugger, &mut cpu);
-    assert_eq!(cpu.reg_pc(), 0xF003);
-    assert_emitted(
-        &adapter,
-        Event::Stopped(StoppedEvent {
-            thread_id: 1,
-            reason: StopReason::Step,
-            all_threads_stopped: true,
-        }),
-    );
+    adapter.push_request(evaluate("[$0x80] + X"));
+    adapter.push_request(evaluate("score * 2 + 1"));
+    adapter.push_request(evaluate("X - 3"));
+    adapter.push_request(evaluate("score / (X - 2)"));
+    adapter.push_request(evaluate("lives"));
+    debugger.process_messages(&mut cpu);
+    assert_responded_with(&adapter, result("$3F (63)"));
+    assert_responded_with(&adapter, result("$0101 (257)"));
+    assert_responded_with(&adapter, result("-1"));
+    assert_eq!(pop_error_message(&adapter), "Division by zero");
+    assert_eq!(pop_error_message(&adapter), "Unknown identifier: lives");
     assert_eq!(adapter.pop_outgoing(), None);
 }
 
 #[test]
-fn step_out() {
-    let mut cpu = cpu_with_code! {
-            jsr subroutine // 0xF000
-        loop:
-            jmp loop       // 0xF003
-        subroutine:
-            nop            // 0xF006
-            nop
-            rts
-    };
-
+fn cheat_commands() {
+    let mut cpu = cpu_with_program(&[opcodes::NOP]);
     let adapter = FakeDebugAdapter::default();
-    adapter.push_request(Request::StepIn {});
     let mut debugger = Debugger::new(adapter.clone());
-    debugger.update(&cpu).unwrap();
-    debugger.process_messages(&cpu);
-    tick_while_running(&mut debugger, &mut cpu);
-    assert_eq!(cpu.reg_pc(), 0xF006);
+    let mut cheats = CheatTable::new();
 
-    purge_messages(&adapter);
-    adapter.push_request(Request::StepOut {});
-    debugger.process_messages(&cpu);
-    assert_responded_with(&adapter, Response::StepOut {});
+    let command = |expression: &str, context: &str| {
+        Request::Evaluate(EvaluateArguments {
+            expression: expression.to_string(),
+            frame_id: None,
+            context: Some(context.to_string()),
+        })
+    };
+    let result = |result: &str| {
+        Response::Evaluate(EvaluateResponse {
+            result: result.to_string(),
+            variables_reference: 0,
+        })
+    };
+
+    adapter.push_request(command("cheat $80=$2A", "repl"));
+    adapter.push_request(command("uncheat 0x1234", "repl"));
+    adapter.push_request(command("cheat 80", "repl"));
+    adapter.push_request(command("cheat $80", "watch"));
+    adapter.push_request(command("uncheat 80", "repl"));
+    debugger.process_messages(&mut cpu);
+    // Cheat commands are only responded to once they are executed.
+    assert_eq!(
+        pop_error_message(&adapter),
+        "Invalid cheat: 80 (expected <address>=<value>, both hexadecimal)"
+    );
+    assert_eq!(pop_error_message(&adapter), "Unknown identifier: cheat");
     assert_eq!(adapter.pop_outgoing(), None);
 
-    tick_while_running(&mut debugger, &mut cpu);
-    assert_eq!(cpu.reg_pc(), 0xF003);
-    assert_emitted(
+    debugger
+        .execute_cheat_commands(|command| cheats.execute(command))
+        .unwrap();
+    assert_responded_with(&adapter, result("Cheat installed: $0080=$2A"));
+    assert_eq!(pop_error_message(&adapter), "No cheat installed at $1234");
+    assert_responded_with(&adapter, result("Cheat removed: $0080"));
+    assert_eq!(adapter.pop_outgoing(), None);
+    assert_eq!(cheats.cheats().count(), 0);
+
+    debugger
+        .execute_cheat_commands(|command| cheats.execute(command))
+        .unwrap();
+    assert_eq!(adapter.pop_outgoing(), None);
+}
+
+#[test]
+fn chip_state() {
+    let mut inspector = MockMachineInspector::new();
+    inspector.expect_chip_states().returning(|| {
+        Ok(vec![ChipState {
+            name: "RIOT".to_string(),
+            registers: vec![
+                ya6502::cpu::ChipRegister::new("SWCHA", 0xFFu8),
+                ya6502::cpu::ChipRegister::new("INTERVAL", 1024u16),
+            ],
+        }])
+    });
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone());
+
+    adapter.push_request(Request::ChipState {});
+    debugger.process_messages(&mut inspector);
+    assert_responded_with(
         &adapter,
-        Event::Stopped(StoppedEvent {
-            thread_id: 1,
-            reason: StopReason::Step,
-            all_threads_stopped: true,
+        Response::ChipState(ChipStateResponse {
+            chips: vec![Chip {
+                name: "RIOT".to_string(),
+                registers: vec![
+                    ChipRegister {
+                        name: "SWCHA".to_string(),
+                        value: 0xFF,
+                    },
+                    ChipRegister {
+                        name: "INTERVAL".to_string(),
+                        value: 1024,
+                    },
+                ],
+            }],
         }),
     );
+    assert_eq!(adapter.pop_outgoing(), None);
 }
 
 #[test]
-fn instruction_breakpoints() {
-    let mut cpu = cpu_with_code! {
-            nop
-            nop
-            nop
-            nop
-        loop:
-            jmp loop
-    };
+fn instruction_breakpoints_outside_code_are_not_verified() {
+    let mut inspector = MockMachineInspector::new();
+    inspector.expect_memory_map().returning(|| {
+        vec![
+            ya6502::memory::MemoryRegion {
+                start: 0x0000,
+                end: 0x0FFF,
+                kind: ya6502::memory::MemoryKind::Ram,
+            },
+            ya6502::memory::MemoryRegion {
+                start: 0x1000,
+                end: 0x1FFF,
+                kind: ya6502::memory::MemoryKind::Io,
+            },
+            ya6502::memory::MemoryRegion {
+                start: 0x2000,
+                end: 0xEFFF,
+                kind: ya6502::memory::MemoryKind::Unmapped,
+            },
+            ya6502::memory::MemoryRegion {
+                start: 0xF000,
+                end: 0xFFFF,
+                kind: ya6502::memory::MemoryKind::Rom,
+            },
+        ]
+    });
     let adapter = FakeDebugAdapter::default();
     let mut debugger = Debugger::new(adapter.clone());
-    debugger.update(&cpu).unwrap();
 
     adapter.push_request(Request::SetInstructionBreakpoints(
         SetInstructionBreakpointsArguments {
-            breakpoints: vec![
+            breakpoints: ["0x0080", "0x1000", "0x8000", "0xF000"]
+                .into_iter()
+                .map(|address| InstructionBreakpoint {
+                    instruction_reference: address.to_string(),
+                    offset: None,
+                    condition: None,
+                    hit_condition: None,
+                })
+                .collect(),
+        },
+    ));
+    debugger.process_messages(&mut inspector);
+    assert_responded_with(
+        &adapter,
+        Response::SetInstructionBreakpoints(SetInstructionBreakpointsResponse {
+            breakpoints: vec![
+                Breakpoint {
+                    verified: true,
+                    instruction_reference: Some("0x0080".to_string()),
+                    message: None,
+                },
+                Breakpoint {
+                    verified: false,
+                    instruction_reference: Some("0x1000".to_string()),
+                    message: Some("No code can be executed at $1000".to_string()),
+                },
+                Breakpoint {
+                    verified: false,
+                    instruction_reference: Some("0x8000".to_string()),
+                    message: Some("No code can be executed at $8000".to_string()),
+                },
+                Breakpoint {
+                    verified: true,
+                    instruction_reference: Some("0xF000".to_string()),
+                    message: None,
+                },
+            ],
+        }),
+    );
+}
+
+#[test]
+fn memory_map() {
+    let mut inspector = MockMachineInspector::new();
+    inspector.expect_memory_map().returning(|| {
+        vec![
+            ya6502::memory::MemoryRegion {
+                start: 0x0000,
+                end: 0x7FFF,
+                kind: ya6502::memory::MemoryKind::Ram,
+            },
+            ya6502::memory::MemoryRegion {
+                start: 0x8000,
+                end: 0xFFFF,
+                kind: ya6502::memory::MemoryKind::Unmapped,
+            },
+        ]
+    });
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone());
+
+    adapter.push_request(Request::MemoryMap {});
+    debugger.process_messages(&mut inspector);
+    assert_responded_with(
+        &adapter,
+        Response::MemoryMap(MemoryMapResponse {
+            regions: vec![
+                MemoryRegion {
+                    start: "0x0000".to_string(),
+                    end: "0x7FFF".to_string(),
+                    kind: MemoryKind::Ram,
+                },
+                MemoryRegion {
+                    start: "0x8000".to_string(),
+                    end: "0xFFFF".to_string(),
+                    kind: MemoryKind::Unmapped,
+                },
+            ],
+        }),
+    );
+    assert_eq!(adapter.pop_outgoing(), None);
+}
+
+#[test]
+fn continue_and_pause() {
+    let mut inspector = MockMachineInspector::new();
+    let adapter = FakeDebugAdapter::default();
+    adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+    let mut debugger = Debugger::new(adapter.clone());
+    assert!(debugger.stopped());
+
+    debugger.process_messages(&mut inspector);
+
+    assert_responded_with(&adapter, Response::Continue {});
+    assert!(!debugger.stopped());
+
+    adapter.push_request(Request::Pause(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut inspector);
+
+    assert_responded_with(&adapter, Response::Pause {});
+    assert_emitted(
+        &adapter,
+        Event::Stopped(StoppedEvent {
+            thread_id: 1,
+            reason: StopReason::Pause,
+            all_threads_stopped: true,
+            text: None,
+        }),
+    );
+    assert!(debugger.stopped());
+    assert_eq!(adapter.pop_outgoing(), None);
+}
+
+#[test]
+fn stops_on_error() {
+    let mut inspector = MockMachineInspector::new();
+    let adapter = FakeDebugAdapter::default();
+    adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.process_messages(&mut inspector);
+    assert_responded_with(&adapter, Response::Continue {});
+
+    debugger
+        .report_error(&CpuError::UnknownOpcode {
+            opcode: 0x9C,
+            address: 0xF123,
+        })
+        .unwrap();
+
+    assert_emitted(
+        &adapter,
+        Event::Stopped(StoppedEvent {
+            thread_id: 1,
+            reason: StopReason::Exception,
+            all_threads_stopped: true,
+            text: Some("Unknown opcode: $9C at $F123".to_string()),
+        }),
+    );
+    assert!(debugger.stopped());
+    assert_eq!(adapter.pop_outgoing(), None);
+}
+
+#[test]
+fn step_in() {
+    let mut cpu = cpu_with_code! {
+            nop
+    };
+
+    let adapter = FakeDebugAdapter::default();
+    adapter.push_request(Request::StepIn(ThreadArguments { thread_id: 1 }));
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.update(&cpu).unwrap();
+
+    debugger.process_messages(&mut cpu);
+
+    assert_responded_with(&adapter, Response::StepIn {});
+    assert!(!debugger.stopped());
+
+    cpu.tick().unwrap();
+    debugger.update(&cpu).unwrap();
+    cpu.tick().unwrap();
+    assert_eq!(adapter.pop_outgoing(), None);
+
+    debugger.update(&cpu).unwrap();
+    assert!(debugger.stopped());
+    assert_emitted(
+        &adapter,
+        Event::Stopped(StoppedEvent {
+            thread_id: 1,
+            reason: StopReason::Step,
+            all_threads_stopped: true,
+            text: None,
+        }),
+    )
+}
+
+#[test]
+fn next() {
+    let mut cpu = cpu_with_code! {
+            jsr subroutine
+            nop
+        subroutine:
+            rts
+    };
+
+    let adapter = FakeDebugAdapter::default();
+    adapter.push_request(Request::Next(ThreadArguments { thread_id: 1 }));
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.update(&cpu).unwrap();
+
+    debugger.process_messages(&mut cpu);
+
+    purge_messages(&adapter);
+    tick_while_running(&mut debugger, &mut cpu);
+    assert_eq!(cpu.reg_pc(), 0xF003);
+    assert_emitted(
+        &adapter,
+        Event::Stopped(StoppedEvent {
+            thread_id: 1,
+            reason: StopReason::Step,
+            all_threads_stopped: true,
+            text: None,
+        }),
+    );
+    assert_eq!(adapter.pop_outgoing(), None);
+}
+
+#[test]
+fn step_out() {
+    let mut cpu = cpu_with_code! {
+            jsr subroutine // 0xF000
+        loop:
+            jmp loop       // 0xF003
+        subroutine:
+            nop            // 0xF006
+            nop
+            rts
+    };
+
+    let adapter = FakeDebugAdapter::default();
+    adapter.push_request(Request::StepIn(ThreadArguments { thread_id: 1 }));
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.update(&cpu).unwrap();
+    debugger.process_messages(&mut cpu);
+    tick_while_running(&mut debugger, &mut cpu);
+    assert_eq!(cpu.reg_pc(), 0xF006);
+
+    purge_messages(&adapter);
+    adapter.push_request(Request::StepOut(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
+    assert_responded_with(&adapter, Response::StepOut {});
+    assert_eq!(adapter.pop_outgoing(), None);
+
+    tick_while_running(&mut debugger, &mut cpu);
+    assert_eq!(cpu.reg_pc(), 0xF003);
+    assert_emitted(
+        &adapter,
+        Event::Stopped(StoppedEvent {
+            thread_id: 1,
+            reason: StopReason::Step,
+            all_threads_stopped: true,
+            text: None,
+        }),
+    );
+}
+
+#[test]
+fn step_back_and_reverse_continue() {
+    let mut cpu = cpu_with_code! {
+            ldx #0         // 0xF000
+        loop:
+            inx            // 0xF002
+            stx 0x80       // 0xF003
+            jmp loop       // 0xF005
+    };
+
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.update(&cpu).unwrap();
+    adapter.push_request(Request::SetInstructionBreakpoints(
+        SetInstructionBreakpointsArguments {
+            breakpoints: vec![InstructionBreakpoint {
+                instruction_reference: "0xF005".to_string(),
+                offset: None,
+                condition: Some("[$80] == 2".to_string()),
+                hit_condition: None,
+            }],
+        },
+    ));
+    adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
+    tick_while_running(&mut debugger, &mut cpu);
+    assert_eq!(cpu.reg_pc(), 0xF005);
+    adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
+    for _ in 0..50 {
+        cpu.tick().unwrap();
+        debugger.update(&cpu).unwrap();
+    }
+    purge_messages(&adapter);
+
+    adapter.push_request(Request::ReverseContinue(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
+    assert_responded_with(&adapter, Response::ReverseContinue);
+    assert_emitted(
+        &adapter,
+        Event::Stopped(StoppedEvent {
+            thread_id: 1,
+            reason: StopReason::Breakpoint,
+            all_threads_stopped: true,
+            text: None,
+        }),
+    );
+    assert_eq!(cpu.reg_pc(), 0xF005);
+    assert_eq!(cpu.inspect_memory(0x80), 2);
+
+    adapter.push_request(Request::StepBack(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
+    assert_responded_with(&adapter, Response::StepBack);
+    assert_emitted(
+        &adapter,
+        Event::Stopped(StoppedEvent {
+            thread_id: 1,
+            reason: StopReason::Step,
+            all_threads_stopped: true,
+            text: None,
+        }),
+    );
+    assert_eq!(cpu.reg_pc(), 0xF003);
+    assert_eq!(cpu.reg_x(), 2);
+    assert_eq!(cpu.inspect_memory(0x80), 1);
+    assert_eq!(adapter.pop_outgoing(), None);
+}
+
+#[test]
+fn instruction_breakpoints() {
+    let mut cpu = cpu_with_code! {
+            nop
+            nop
+            nop
+            nop
+        loop:
+            jmp loop
+    };
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.update(&cpu).unwrap();
+
+    adapter.push_request(Request::SetInstructionBreakpoints(
+        SetInstructionBreakpointsArguments {
+            breakpoints: vec![
                 InstructionBreakpoint {
                     instruction_reference: "0xF001".to_string(),
                     offset: None,
+                    condition: None,
+                    hit_condition: None,
                 },
                 InstructionBreakpoint {
                     instruction_reference: "0xEFFF".to_string(),
                     offset: Some(4), // Effective address: 0xF003
+                    condition: None,
+                    hit_condition: None,
                 },
             ],
         },
     ));
-    adapter.push_request(Request::Continue {});
+    adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
     debugger.process_messages(&mut cpu);
     assert_responded_with(
         &adapter,
@@ -874,11 +1500,13 @@ fn instruction_breakpoints() {
             breakpoints: vec![
                 Breakpoint {
                     verified: true,
-                    instruction_reference: "0xF001".to_string(),
+                    instruction_reference: Some("0xF001".to_string()),
+                    message: None,
                 },
                 Breakpoint {
                     verified: true,
-                    instruction_reference: "0xF003".to_string(),
+                    instruction_reference: Some("0xF003".to_string()),
+                    message: None,
                 },
             ],
         }),
@@ -892,11 +1520,12 @@ fn instruction_breakpoints() {
             thread_id: 1,
             reason: StopReason::Breakpoint,
             all_threads_stopped: true,
+            text: None,
         }),
     );
     assert_eq!(cpu.reg_pc(), 0xF001);
 
-    adapter.push_request(Request::Continue {});
+    adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
     debugger.process_messages(&mut cpu);
 
     purge_messages(&adapter);
@@ -907,21 +1536,573 @@ fn instruction_breakpoints() {
             thread_id: 1,
             reason: StopReason::Breakpoint,
             all_threads_stopped: true,
+            text: None,
         }),
     );
     assert_eq!(cpu.reg_pc(), 0xF003);
 }
 
 #[test]
-fn disconnects() {
-    let inspector = MockMachineInspector::new();
+fn conditional_instruction_breakpoints() {
+    let mut cpu = cpu_with_code! {
+            ldx #0         // 0xF000
+        loop:
+            inx            // 0xF002
+            jmp loop       // 0xF003
+    };
     let adapter = FakeDebugAdapter::default();
-    adapter.push_request(Request::Disconnect(None));
-    adapter.expect_disconnect();
     let mut debugger = Debugger::new(adapter.clone());
-    debugger.process_messages(&inspector);
+    debugger.update(&cpu).unwrap();
 
-    assert_responded_with(&adapter, Response::Disconnect);
-    assert!(adapter.disconnected());
-    assert!(!debugger.stopped());
+    adapter.push_request(Request::SetInstructionBreakpoints(
+        SetInstructionBreakpointsArguments {
+            breakpoints: vec![
+                InstructionBreakpoint {
+                    instruction_reference: "0xF003".to_string(),
+                    offset: None,
+                    condition: Some("X >= 2".to_string()),
+                    hit_condition: Some("2".to_string()),
+                },
+                InstructionBreakpoint {
+                    instruction_reference: "0xF002".to_string(),
+                    offset: None,
+                    condition: Some("X ==".to_string()),
+                    hit_condition: None,
+                },
+                InstructionBreakpoint {
+                    instruction_reference: "0xF002".to_string(),
+                    offset: None,
+                    condition: None,
+                    hit_condition: Some("foo".to_string()),
+                },
+            ],
+        },
+    ));
+    adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
+    assert_responded_with(
+        &adapter,
+        Response::SetInstructionBreakpoints(SetInstructionBreakpointsResponse {
+            breakpoints: vec![
+                Breakpoint {
+                    verified: true,
+                    instruction_reference: Some("0xF003".to_string()),
+                    message: None,
+                },
+                Breakpoint {
+                    verified: false,
+                    instruction_reference: Some("0xF002".to_string()),
+                    message: None,
+                },
+                Breakpoint {
+                    verified: false,
+                    instruction_reference: Some("0xF002".to_string()),
+                    message: None,
+                },
+            ],
+        }),
+    );
+
+    purge_messages(&adapter);
+    tick_while_running(&mut debugger, &mut cpu);
+    assert_emitted(
+        &adapter,
+        Event::Stopped(StoppedEvent {
+            thread_id: 1,
+            reason: StopReason::Breakpoint,
+            all_threads_stopped: true,
+            text: None,
+        }),
+    );
+    assert_eq!(cpu.reg_pc(), 0xF003);
+    assert_eq!(cpu.reg_x(), 3);
+}
+
+#[test]
+fn data_breakpoints() {
+    let mut cpu = cpu_with_code! {
+            lda #1         // 0xF000
+            sta 0x80       // 0xF002
+        loop:
+            jmp loop       // 0xF004
+    };
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.update(&cpu).unwrap();
+
+    adapter.push_request(Request::DataBreakpointInfo(DataBreakpointInfoArguments {
+        name: "$80".to_string(),
+    }));
+    adapter.push_request(Request::DataBreakpointInfo(DataBreakpointInfoArguments {
+        name: "foo".to_string(),
+    }));
+    debugger.process_messages(&mut cpu);
+    assert_responded_with(
+        &adapter,
+        Response::DataBreakpointInfo(DataBreakpointInfoResponse {
+            data_id: Some("0x0080".to_string()),
+            description: "$0080".to_string(),
+            access_types: Some(vec![
+                DataBreakpointAccessType::Read,
+                DataBreakpointAccessType::Write,
+                DataBreakpointAccessType::ReadWrite,
+            ]),
+        }),
+    );
+    assert_responded_with(
+        &adapter,
+        Response::DataBreakpointInfo(DataBreakpointInfoResponse {
+            data_id: None,
+            description: "foo is not a memory address".to_string(),
+            access_types: None,
+        }),
+    );
+
+    adapter.push_request(Request::SetDataBreakpoints(SetDataBreakpointsArguments {
+        breakpoints: vec![
+            DataBreakpoint {
+                data_id: "0x0080".to_string(),
+                access_type: None,
+            },
+            DataBreakpoint {
+                data_id: "bar".to_string(),
+                access_type: Some(DataBreakpointAccessType::Read),
+            },
+        ],
+    }));
+    adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
+    assert_responded_with(
+        &adapter,
+        Response::SetDataBreakpoints(SetDataBreakpointsResponse {
+            breakpoints: vec![
+                Breakpoint {
+                    verified: true,
+                    instruction_reference: None,
+                    message: None,
+                },
+                Breakpoint {
+                    verified: false,
+                    instruction_reference: None,
+                    message: None,
+                },
+            ],
+        }),
+    );
+
+    purge_messages(&adapter);
+    tick_while_running(&mut debugger, &mut cpu);
+    assert_emitted(
+        &adapter,
+        Event::Stopped(StoppedEvent {
+            thread_id: 1,
+            reason: StopReason::DataBreakpoint,
+            all_threads_stopped: true,
+            text: None,
+        }),
+    );
+    assert_eq!(cpu.reg_pc(), 0xF004);
+}
+
+#[test]
+fn function_breakpoints() {
+    let mut cpu = cpu_with_program(&[opcodes::BRK, 0x00, opcodes::NOP, opcodes::NOP]);
+    cpu.mut_memory().bytes[0xFFFE..=0xFFFF].copy_from_slice(&[0x02, 0xF0]);
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.update(&cpu).unwrap();
+
+    adapter.push_request(Request::SetFunctionBreakpoints(
+        SetFunctionBreakpointsArguments {
+            breakpoints: vec![
+                FunctionBreakpoint {
+                    name: "brk".to_string(),
+                },
+                FunctionBreakpoint {
+                    name: "main".to_string(),
+                },
+            ],
+        },
+    ));
+    adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
+    assert_responded_with(
+        &adapter,
+        Response::SetFunctionBreakpoints(SetFunctionBreakpointsResponse {
+            breakpoints: vec![
+                Breakpoint {
+                    verified: true,
+                    instruction_reference: None,
+                    message: None,
+                },
+                Breakpoint {
+                    verified: false,
+                    instruction_reference: None,
+                    message: None,
+                },
+            ],
+        }),
+    );
+
+    purge_messages(&adapter);
+    tick_while_running(&mut debugger, &mut cpu);
+    assert_emitted(
+        &adapter,
+        Event::Stopped(StoppedEvent {
+            thread_id: 1,
+            reason: StopReason::FunctionBreakpoint,
+            all_threads_stopped: true,
+            text: None,
+        }),
+    );
+    assert_eq!(cpu.reg_pc(), 0xF002);
+}
+
+#[test]
+fn disconnects() {
+    let mut inspector = MockMachineInspector::new();
+    let adapter = FakeDebugAdapter::default();
+    adapter.push_request(Request::Disconnect(None));
+    adapter.expect_disconnect();
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.process_messages(&mut inspector);
+
+    assert_responded_with(&adapter, Response::Disconnect);
+    assert!(adapter.disconnected());
+    assert!(!debugger.stopped());
+}
+
+#[test]
+fn disconnecting_removes_breakpoints() {
+    let mut cpu = cpu_with_program(&[opcodes::NOP, opcodes::NOP, opcodes::JMP_ABS, 0x00, 0xF0]);
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.update(&cpu).unwrap();
+
+    adapter.push_request(Request::SetInstructionBreakpoints(
+        SetInstructionBreakpointsArguments {
+            breakpoints: vec![InstructionBreakpoint {
+                instruction_reference: "0xF001".to_string(),
+                offset: None,
+                condition: None,
+                hit_condition: None,
+            }],
+        },
+    ));
+    adapter.push_request(Request::Disconnect(None));
+    adapter.expect_disconnect();
+    debugger.process_messages(&mut cpu);
+
+    for _ in 0..100 {
+        cpu.tick().unwrap();
+        debugger.update(&cpu).unwrap();
+    }
+    assert!(!debugger.stopped());
+}
+
+#[test]
+fn launches_and_stops_on_entry() {
+    let mut cpu = cpu_with_program(&[opcodes::NOP, opcodes::NOP, opcodes::JMP_ABS, 0x01, 0xF0]);
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.update(&cpu).unwrap();
+
+    adapter.push_request(Request::Launch(LaunchArguments {
+        program: Some("game.bin".to_string()),
+        stop_on_entry: Some(true),
+    }));
+    debugger.process_messages(&mut cpu);
+    // The response waits until the program is loaded.
+    assert_eq!(adapter.pop_outgoing(), None);
+    assert_eq!(
+        debugger.take_launch_request(),
+        Some(LaunchRequest {
+            program: Some(PathBuf::from("game.bin")),
+        }),
+    );
+    assert_eq!(debugger.take_launch_request(), None);
+    debugger.finish_launch(Ok(())).unwrap();
+    assert_responded_with(&adapter, Response::Launch);
+    assert!(!debugger.stopped());
+
+    cpu.tick().unwrap();
+    debugger.update(&cpu).unwrap();
+    cpu.reset();
+    tick_while_running(&mut debugger, &mut cpu);
+    assert_emitted(
+        &adapter,
+        Event::Stopped(StoppedEvent {
+            thread_id: 1,
+            reason: StopReason::Entry,
+            all_threads_stopped: true,
+            text: None,
+        }),
+    );
+    assert_eq!(cpu.reg_pc(), 0xF000);
+    let stack_frames = get_stack_frames(&adapter, &mut debugger, &mut cpu);
+    assert_eq!(stack_frames.len(), 1);
+}
+
+#[test]
+fn launches_without_stopping() {
+    let mut cpu = cpu_with_program(&[opcodes::NOP, opcodes::JMP_ABS, 0x00, 0xF0]);
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.update(&cpu).unwrap();
+
+    adapter.push_request(Request::Launch(LaunchArguments {
+        program: None,
+        stop_on_entry: None,
+    }));
+    debugger.process_messages(&mut cpu);
+    assert_eq!(
+        debugger.take_launch_request(),
+        Some(LaunchRequest { program: None }),
+    );
+    debugger.finish_launch(Ok(())).unwrap();
+    assert_responded_with(&adapter, Response::Launch);
+
+    cpu.reset();
+    for _ in 0..100 {
+        cpu.tick().unwrap();
+        debugger.update(&cpu).unwrap();
+    }
+    assert!(!debugger.stopped());
+    assert_eq!(adapter.pop_outgoing(), None);
+}
+
+#[test]
+fn reports_failed_launch() {
+    let mut cpu = cpu_with_program(&[opcodes::NOP, opcodes::JMP_ABS, 0x00, 0xF0]);
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone());
+    debugger.update(&cpu).unwrap();
+    adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+    debugger.process_messages(&mut cpu);
+    purge_messages(&adapter);
+
+    adapter.push_request(Request::Launch(LaunchArguments {
+        program: Some("missing.bin".to_string()),
+        stop_on_entry: Some(true),
+    }));
+    debugger.process_messages(&mut cpu);
+    debugger.take_launch_request();
+    debugger
+        .finish_launch(Err("Unable to load missing.bin".to_string()))
+        .unwrap();
+    assert_eq!(pop_error_message(&adapter), "Unable to load missing.bin");
+
+    // The launch didn't happen, so the debugger doesn't stop on entry.
+    cpu.reset();
+    for _ in 0..100 {
+        cpu.tick().unwrap();
+        debugger.update(&cpu).unwrap();
+    }
+    assert!(!debugger.stopped());
+    assert_eq!(adapter.pop_outgoing(), None);
+}
+
+fn tick_threads_while_running<A: DebugAdapter>(debugger: &mut Debugger<A>, cpus: &mut [Cpu<Ram>]) {
+    for _ in 0..1000 {
+        if debugger.stopped() {
+            return;
+        }
+        for (index, cpu) in cpus.iter_mut().enumerate() {
+            cpu.tick().unwrap();
+            debugger
+                .update_thread(MAIN_THREAD_ID + index as i64, cpu)
+                .unwrap();
+        }
+    }
+    panic!("CPUs still running");
+}
+
+fn update_threads<A: DebugAdapter>(debugger: &mut Debugger<A>, cpus: &[Cpu<Ram>]) {
+    for (index, cpu) in cpus.iter().enumerate() {
+        debugger
+            .update_thread(MAIN_THREAD_ID + index as i64, cpu)
+            .unwrap();
+    }
+}
+
+fn process_thread_messages<A: DebugAdapter>(debugger: &mut Debugger<A>, cpus: &mut [Cpu<Ram>]) {
+    let [main_cpu, drive_cpu] = cpus else {
+        panic!("Expected two CPUs");
+    };
+    debugger.process_thread_messages(&mut [main_cpu, drive_cpu]);
+}
+
+#[test]
+fn lists_threads() {
+    let mut cpus = [cpu_with_program(&[]), cpu_with_program(&[])];
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone()).with_thread("drive");
+    adapter.push_request(Request::Threads);
+    process_thread_messages(&mut debugger, &mut cpus);
+
+    assert_responded_with(
+        &adapter,
+        Response::Threads(ThreadsResponse {
+            threads: vec![
+                Thread {
+                    id: 1,
+                    name: "main thread".to_string(),
+                },
+                Thread {
+                    id: 2,
+                    name: "drive".to_string(),
+                },
+            ],
+        }),
+    );
+}
+
+#[test]
+fn steps_a_single_thread() {
+    let mut cpus = [
+        cpu_with_program(&[opcodes::NOP; 10]),
+        cpu_with_program(&[opcodes::LDX_IMM, 0x2A, opcodes::NOP, opcodes::NOP]),
+    ];
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone()).with_thread("drive");
+    update_threads(&mut debugger, &cpus);
+
+    adapter.push_request(Request::StepIn(ThreadArguments { thread_id: 2 }));
+    process_thread_messages(&mut debugger, &mut cpus);
+    assert_responded_with(&adapter, Response::StepIn {});
+    tick_threads_while_running(&mut debugger, &mut cpus);
+
+    assert_emitted(
+        &adapter,
+        Event::Stopped(StoppedEvent {
+            thread_id: 2,
+            reason: StopReason::Step,
+            all_threads_stopped: true,
+            text: None,
+        }),
+    );
+    assert_eq!(cpus[0].reg_pc(), 0xF001);
+    assert_eq!(cpus[1].reg_pc(), 0xF002);
+
+    // Stack frames and variables of other threads than the main one have IDs
+    // that don't collide with the main thread.
+    adapter.push_request(Request::StackTrace(ThreadArguments { thread_id: 2 }));
+    process_thread_messages(&mut debugger, &mut cpus);
+    let stack_frames = match pop_response(&adapter) {
+        Response::StackTrace(StackTraceResponse { stack_frames, .. }) => stack_frames,
+        other => panic!("Expected StackTraceResponse, got {:?}", other),
+    };
+    assert_eq!(stack_frames.len(), 1);
+    assert_ne!(stack_frames[0].id, 1);
+
+    adapter.push_request(Request::Scopes(ScopesArguments {
+        frame_id: stack_frames[0].id,
+    }));
+    process_thread_messages(&mut debugger, &mut cpus);
+    let scopes = match pop_response(&adapter) {
+        Response::Scopes(ScopesResponse { scopes }) => scopes,
+        other => panic!("Expected a ScopesResponse, got {:?}", other),
+    };
+    assert_eq!(scopes[0].name, "Registers");
+    assert_ne!(scopes[0].variables_reference, REGISTERS_VARIABLES_REFERENCE);
+
+    adapter.push_request(Request::Variables(VariablesArguments {
+        variables_reference: scopes[0].variables_reference,
+    }));
+    process_thread_messages(&mut debugger, &mut cpus);
+    let variables = match pop_response(&adapter) {
+        Response::Variables(VariablesResponse { variables }) => variables,
+        other => panic!("Expected a VariablesResponse, got {:?}", other),
+    };
+    assert_eq!(variables[1].name, "X");
+    assert_eq!(variables[1].value, "$2A");
+}
+
+#[test]
+fn breakpoints_belong_to_a_single_thread() {
+    let mut cpus = [
+        cpu_with_program(&[opcodes::NOP; 10]),
+        cpu_with_program(&[
+            opcodes::JMP_ABS,
+            0x04,
+            0xF0,
+            opcodes::NOP,
+            opcodes::NOP,
+            opcodes::NOP,
+            opcodes::NOP,
+            opcodes::NOP,
+        ]),
+    ];
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone()).with_thread("drive");
+    update_threads(&mut debugger, &cpus);
+    let set_breakpoint = |address: &str| {
+        Request::SetInstructionBreakpoints(SetInstructionBreakpointsArguments {
+            breakpoints: vec![InstructionBreakpoint {
+                instruction_reference: address.to_string(),
+                offset: None,
+                condition: None,
+                hit_condition: None,
+            }],
+        })
+    };
+
+    // The breakpoint belongs to the main thread, so the drive CPU, which gets
+    // to $F004 first, doesn't stop there.
+    adapter.push_request(set_breakpoint("0xF004"));
+    adapter.push_request(Request::Continue(ThreadArguments { thread_id: 1 }));
+    process_thread_messages(&mut debugger, &mut cpus);
+    purge_messages(&adapter);
+
+    tick_threads_while_running(&mut debugger, &mut cpus);
+    assert_emitted(
+        &adapter,
+        Event::Stopped(StoppedEvent {
+            thread_id: 1,
+            reason: StopReason::Breakpoint,
+            all_threads_stopped: true,
+            text: None,
+        }),
+    );
+    assert_eq!(adapter.pop_outgoing(), None);
+    assert_eq!(cpus[0].reg_pc(), 0xF004);
+    assert_eq!(cpus[1].reg_pc(), 0xF007);
+
+    // Inspecting the drive thread makes it current, so the next breakpoint
+    // goes there, and the main thread keeps its own.
+    adapter.push_request(Request::StackTrace(ThreadArguments { thread_id: 2 }));
+    adapter.push_request(set_breakpoint("0xF007"));
+    adapter.push_request(Request::Continue(ThreadArguments { thread_id: 2 }));
+    process_thread_messages(&mut debugger, &mut cpus);
+    purge_messages(&adapter);
+
+    tick_threads_while_running(&mut debugger, &mut cpus);
+    assert_emitted(
+        &adapter,
+        Event::Stopped(StoppedEvent {
+            thread_id: 2,
+            reason: StopReason::Breakpoint,
+            all_threads_stopped: true,
+            text: None,
+        }),
+    );
+    assert_eq!(cpus[0].reg_pc(), 0xF005);
+    assert_eq!(cpus[1].reg_pc(), 0xF007);
+}
+
+#[test]
+fn rejects_unknown_threads() {
+    let mut cpus = [cpu_with_program(&[]), cpu_with_program(&[])];
+    let adapter = FakeDebugAdapter::default();
+    let mut debugger = Debugger::new(adapter.clone()).with_thread("drive");
+    update_threads(&mut debugger, &cpus);
+
+    adapter.push_request(Request::StepIn(ThreadArguments { thread_id: 3 }));
+    adapter.push_request(Request::Scopes(ScopesArguments {
+        frame_id: 2 * THREAD_ID_SPACING + 1,
+    }));
+    process_thread_messages(&mut debugger, &mut cpus);
+    assert_eq!(pop_error_message(&adapter), "Unknown thread ID: 3");
+    assert_eq!(pop_error_message(&adapter), "Unknown thread ID: 3");
+    assert_eq!(adapter.pop_outgoing(), None);
 }
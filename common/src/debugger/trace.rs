@@ -0,0 +1,230 @@
+use crate::debugger::disasm::disassemble;
+use crate::debugger::symbols::RegisterNames;
+use std::collections::VecDeque;
+use std::io;
+use std::io::Write;
+use ya6502::cpu::flags::flags_to_string;
+use ya6502::cpu::flags::FlagRepresentation;
+use ya6502::cpu::BusCycle;
+use ya6502::cpu::MachineInspector;
+use ya6502::cpu::MemoryAccess;
+
+/// Logs instructions executed by the CPU. Each entry contains the address and
+/// disassembly of an instruction that is about to be executed, along with the
+/// register values and the CPU cycle counter. Entries are either written
+/// immediately or, if the tracer uses a ring buffer, only the most recent ones
+/// are kept in memory until [`Tracer::dump`] is called. The latter mode is
+/// useful for diagnosing crashes that happen after a long time.
+///
+/// Optionally, the tracer also logs every bus cycle performed by the CPU,
+/// right below the instruction that caused it.
+pub struct Tracer<W: Write> {
+    writer: W,
+    ring_buffer: VecDeque<String>,
+    ring_buffer_size: Option<usize>,
+    log_bus_cycles: bool,
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            ring_buffer: VecDeque::new(),
+            ring_buffer_size: None,
+            log_bus_cycles: false,
+        }
+    }
+
+    /// Makes the tracer keep only a given number of recent entries in memory
+    /// instead of writing them immediately.
+    pub fn with_ring_buffer(mut self, size: usize) -> Self {
+        self.ring_buffer = VecDeque::with_capacity(size);
+        self.ring_buffer_size = Some(size);
+        return self;
+    }
+
+    /// Makes the tracer log every memory read and write performed by the CPU.
+    pub fn with_bus_cycles(mut self) -> Self {
+        self.log_bus_cycles = true;
+        return self;
+    }
+
+    /// Records the last bus cycle, if enabled, and an instruction, if the CPU
+    /// is about to execute one. Should be called after every machine tick.
+    pub fn update(&mut self, inspector: &impl MachineInspector) -> io::Result<()> {
+        if self.log_bus_cycles {
+            if let Some(bus_cycle) = inspector.last_bus_cycle() {
+                self.record(bus_cycle_entry(bus_cycle))?;
+            }
+        }
+        if inspector.at_instruction_start() {
+            self.record(trace_entry(inspector))?;
+        }
+        return Ok(());
+    }
+
+    fn record(&mut self, entry: String) -> io::Result<()> {
+        match self.ring_buffer_size {
+            Some(size) => {
+                if self.ring_buffer.len() >= size {
+                    self.ring_buffer.pop_front();
+                }
+                self.ring_buffer.push_back(entry);
+                Ok(())
+            }
+            None => writeln!(self.writer, "{}", entry),
+        }
+    }
+
+    /// Writes out all the entries kept in the ring buffer and flushes the
+    /// writer.
+    pub fn dump(&mut self) -> io::Result<()> {
+        for entry in self.ring_buffer.drain(..) {
+            writeln!(self.writer, "{}", entry)?;
+        }
+        return self.writer.flush();
+    }
+
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+}
+
+fn trace_entry(inspector: &impl MachineInspector) -> String {
+    let pc = inspector.reg_pc();
+    // Register names are not used here, since they would break the columns.
+    let instruction = &disassemble(inspector, &RegisterNames::new(), &[], pc, pc, 0, 1)[0];
+    return format!(
+        "{:04X}  {:<8}  {:<12}  A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} P:{} CYC:{}",
+        pc,
+        instruction.instruction_bytes,
+        instruction.instruction,
+        inspector.reg_a(),
+        inspector.reg_x(),
+        inspector.reg_y(),
+        inspector.reg_sp(),
+        flags_to_string(inspector.flags(), FlagRepresentation::Letters),
+        inspector.cycles(),
+    );
+}
+
+fn bus_cycle_entry(bus_cycle: BusCycle) -> String {
+    let (kind, address) = match bus_cycle.access {
+        MemoryAccess::Read(address) => ("READ", address),
+        MemoryAccess::Write(address) => ("WRITE", address),
+    };
+    return format!(
+        "      {:<5} {:04X}: {:02X}  CYC:{}",
+        kind, address, bus_cycle.value, bus_cycle.cycle,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ya6502::cpu::flags;
+    use ya6502::cpu::Cpu;
+    use ya6502::cpu::Register;
+    use ya6502::cpu_with_code;
+    use ya6502::memory::Ram;
+
+    fn trace(tracer: &mut Tracer<Vec<u8>>, cpu: &mut Cpu<Ram>, n_ticks: u32) {
+        for _ in 0..n_ticks {
+            cpu.tick().unwrap();
+            tracer.update(cpu).unwrap();
+        }
+    }
+
+    fn lines(tracer: &Tracer<Vec<u8>>) -> Vec<&str> {
+        std::str::from_utf8(tracer.writer())
+            .unwrap()
+            .lines()
+            .collect()
+    }
+
+    #[test]
+    fn logs_instructions() {
+        let mut cpu = cpu_with_code! {
+                lda #0x3F    // 0xF000
+                sta 0x1234   // 0xF002
+                ldx #0       // 0xF005
+        };
+        cpu.set_register(Register::A, 0x00);
+        cpu.set_register(Register::X, 0x12);
+        cpu.set_register(Register::Y, 0x34);
+        cpu.set_register(Register::SP, 0xFD);
+        cpu.set_register(Register::Flags, (flags::UNUSED | flags::I) as u16);
+        let start = cpu.cycles();
+        let mut tracer = Tracer::new(Vec::new());
+        tracer.update(&cpu).unwrap();
+        trace(&mut tracer, &mut cpu, 6);
+
+        assert_eq!(
+            lines(&tracer),
+            [
+                format!(
+                    "F000  A9 3F     LDA #$3F      A:00 X:12 Y:34 SP:FD P:..-..I.. CYC:{}",
+                    start
+                ),
+                format!(
+                    "F002  8D 34 12  STA $1234     A:3F X:12 Y:34 SP:FD P:..-..I.. CYC:{}",
+                    start + 2
+                ),
+                format!(
+                    "F005  A2 00     LDX #$00      A:3F X:12 Y:34 SP:FD P:..-..I.. CYC:{}",
+                    start + 6
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn logs_bus_cycles() {
+        let mut cpu = cpu_with_code! {
+                lda 0x42     // 0xF000
+                sta 0x43     // 0xF002
+        };
+        cpu.mut_memory().bytes[0x42] = 0x99;
+        let start = cpu.cycles();
+        let mut tracer = Tracer::new(Vec::new()).with_bus_cycles();
+        trace(&mut tracer, &mut cpu, 6);
+
+        let lines = lines(&tracer);
+        assert_eq!(lines.len(), 8);
+        assert_eq!(
+            lines[0..3],
+            [
+                format!("      READ  F000: A5  CYC:{}", start),
+                format!("      READ  F001: 42  CYC:{}", start + 1),
+                format!("      READ  0042: 99  CYC:{}", start + 2),
+            ]
+        );
+        assert!(lines[3].starts_with("F002  85 43"), "{}", lines[3]);
+        assert_eq!(
+            lines[4..7],
+            [
+                format!("      READ  F002: 85  CYC:{}", start + 3),
+                format!("      READ  F003: 43  CYC:{}", start + 4),
+                format!("      WRITE 0043: 99  CYC:{}", start + 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn ring_buffer() {
+        let mut cpu = cpu_with_code! {
+            loop:
+                inx          // 0xF000
+                jmp loop     // 0xF001
+        };
+        let mut tracer = Tracer::new(Vec::new()).with_ring_buffer(2);
+        trace(&mut tracer, &mut cpu, 100);
+        assert!(lines(&tracer).is_empty());
+
+        tracer.dump().unwrap();
+        let lines = lines(&tracer);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("F001  4C 00 F0"), "{}", lines[0]);
+        assert!(lines[1].starts_with("F000  E8"), "{}", lines[1]);
+    }
+}
@@ -0,0 +1,290 @@
+use clap::Parser;
+use piston::{
+    Button, ButtonArgs, ButtonState, ControllerAxisArgs, ControllerButton, ControllerHat, Event,
+    HatState, Input, Motion,
+};
+use std::cmp::Ordering;
+
+/// Describes how host gamepads are mapped to joystick inputs. Directions can be
+/// controlled both by analog axes and hats (D-pads).
+#[derive(Parser, Debug, Clone)]
+pub struct GamepadMapping {
+    /// Comma-separated list of gamepad buttons that act as a fire button.
+    #[clap(
+        long = "gamepad-fire-buttons",
+        default_value = "0,1",
+        use_value_delimiter = true
+    )]
+    pub fire_buttons: Vec<u8>,
+    /// Gamepad axis used for horizontal movement.
+    #[clap(long = "gamepad-x-axis", default_value = "0")]
+    pub x_axis: u8,
+    /// Gamepad axis used for vertical movement.
+    #[clap(long = "gamepad-y-axis", default_value = "1")]
+    pub y_axis: u8,
+    /// Minimal axis deflection (between 0 and 1) that counts as a movement.
+    #[clap(long = "gamepad-dead-zone", default_value = "0.5")]
+    pub dead_zone: f64,
+}
+
+impl Default for GamepadMapping {
+    fn default() -> Self {
+        Self {
+            fire_buttons: vec![0, 1],
+            x_axis: 0,
+            y_axis: 1,
+            dead_zone: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Fire,
+}
+
+/// A change of a single joystick input reported by a given gamepad.
+#[derive(Debug, PartialEq)]
+pub struct GamepadEvent {
+    pub gamepad: u32,
+    pub input: GamepadInput,
+    pub pressed: bool,
+}
+
+impl GamepadMapping {
+    /// Translates a Piston event into joystick input changes. Axis and hat
+    /// movements always report both directions of a given axis, the released
+    /// one first, so that the consumer doesn't need to keep track of the
+    /// previous state.
+    pub fn translate(&self, event: &Event) -> Vec<GamepadEvent> {
+        match event {
+            Event::Input(
+                Input::Button(ButtonArgs {
+                    button: Button::Controller(ControllerButton { id, button }),
+                    state,
+                    ..
+                }),
+                _timestamp,
+            ) if self.fire_buttons.contains(button) => vec![GamepadEvent {
+                gamepad: *id,
+                input: GamepadInput::Fire,
+                pressed: *state == ButtonState::Press,
+            }],
+            Event::Input(
+                Input::Button(ButtonArgs {
+                    button: Button::Hat(ControllerHat { id, state, .. }),
+                    ..
+                }),
+                _timestamp,
+            ) => {
+                let (horizontal, vertical) = hat_deflection(*state);
+                let mut events =
+                    axis_events(*id, GamepadInput::Left, GamepadInput::Right, horizontal);
+                events.extend(axis_events(
+                    *id,
+                    GamepadInput::Up,
+                    GamepadInput::Down,
+                    vertical,
+                ));
+                events
+            }
+            Event::Input(
+                Input::Move(Motion::ControllerAxis(ControllerAxisArgs { id, axis, position })),
+                _timestamp,
+            ) => {
+                if *axis == self.x_axis {
+                    axis_events(
+                        *id,
+                        GamepadInput::Left,
+                        GamepadInput::Right,
+                        self.axis_deflection(*position),
+                    )
+                } else if *axis == self.y_axis {
+                    axis_events(
+                        *id,
+                        GamepadInput::Up,
+                        GamepadInput::Down,
+                        self.axis_deflection(*position),
+                    )
+                } else {
+                    vec![]
+                }
+            }
+            _ => vec![],
+        }
+    }
+
+    fn axis_deflection(&self, position: f64) -> Ordering {
+        if position <= -self.dead_zone {
+            Ordering::Less
+        } else if position >= self.dead_zone {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+}
+
+/// Returns horizontal and vertical deflection of a hat. Negative values point
+/// left and up, respectively.
+fn hat_deflection(state: HatState) -> (Ordering, Ordering) {
+    use Ordering::*;
+    match state {
+        HatState::Centered => (Equal, Equal),
+        HatState::Up => (Equal, Less),
+        HatState::Down => (Equal, Greater),
+        HatState::Left => (Less, Equal),
+        HatState::Right => (Greater, Equal),
+        HatState::LeftUp => (Less, Less),
+        HatState::LeftDown => (Less, Greater),
+        HatState::RightUp => (Greater, Less),
+        HatState::RightDown => (Greater, Greater),
+    }
+}
+
+fn axis_events(
+    gamepad: u32,
+    negative: GamepadInput,
+    positive: GamepadInput,
+    deflection: Ordering,
+) -> Vec<GamepadEvent> {
+    let event = |input, pressed| GamepadEvent {
+        gamepad,
+        input,
+        pressed,
+    };
+    match deflection {
+        Ordering::Less => vec![event(positive, false), event(negative, true)],
+        Ordering::Equal => vec![event(negative, false), event(positive, false)],
+        Ordering::Greater => vec![event(negative, false), event(positive, true)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn button_event(id: u32, button: u8, state: ButtonState) -> Event {
+        Event::from(ButtonArgs {
+            button: Button::Controller(ControllerButton::new(id, button)),
+            state,
+            scancode: None,
+        })
+    }
+
+    fn hat_event(id: u32, state: HatState) -> Event {
+        Event::from(ButtonArgs {
+            button: Button::Hat(ControllerHat::new(id, 0, state)),
+            state: ButtonState::Press,
+            scancode: None,
+        })
+    }
+
+    fn axis_event(id: u32, axis: u8, position: f64) -> Event {
+        Event::from(ControllerAxisArgs::new(id, axis, position))
+    }
+
+    fn gamepad_event(gamepad: u32, input: GamepadInput, pressed: bool) -> GamepadEvent {
+        GamepadEvent {
+            gamepad,
+            input,
+            pressed,
+        }
+    }
+
+    #[test]
+    fn fire_buttons() {
+        let mapping = GamepadMapping::default();
+        assert_eq!(
+            mapping.translate(&button_event(0, 1, ButtonState::Press)),
+            vec![gamepad_event(0, GamepadInput::Fire, true)],
+        );
+        assert_eq!(
+            mapping.translate(&button_event(3, 0, ButtonState::Release)),
+            vec![gamepad_event(3, GamepadInput::Fire, false)],
+        );
+        assert_eq!(
+            mapping.translate(&button_event(0, 2, ButtonState::Press)),
+            vec![]
+        );
+
+        let mapping = GamepadMapping {
+            fire_buttons: vec![5],
+            ..Default::default()
+        };
+        assert_eq!(
+            mapping.translate(&button_event(0, 0, ButtonState::Press)),
+            vec![]
+        );
+        assert_eq!(
+            mapping.translate(&button_event(0, 5, ButtonState::Press)),
+            vec![gamepad_event(0, GamepadInput::Fire, true)],
+        );
+    }
+
+    #[test]
+    fn axes() {
+        use GamepadInput::*;
+        let mapping = GamepadMapping::default();
+        assert_eq!(
+            mapping.translate(&axis_event(1, 0, -0.7)),
+            vec![gamepad_event(1, Right, false), gamepad_event(1, Left, true)],
+        );
+        assert_eq!(
+            mapping.translate(&axis_event(1, 0, 0.3)),
+            vec![
+                gamepad_event(1, Left, false),
+                gamepad_event(1, Right, false)
+            ],
+        );
+        assert_eq!(
+            mapping.translate(&axis_event(1, 1, 1.0)),
+            vec![gamepad_event(1, Up, false), gamepad_event(1, Down, true)],
+        );
+        assert_eq!(mapping.translate(&axis_event(1, 2, 1.0)), vec![]);
+
+        let mapping = GamepadMapping {
+            x_axis: 3,
+            y_axis: 4,
+            dead_zone: 0.2,
+            ..Default::default()
+        };
+        assert_eq!(mapping.translate(&axis_event(0, 0, 1.0)), vec![]);
+        assert_eq!(
+            mapping.translate(&axis_event(0, 3, 0.3)),
+            vec![gamepad_event(0, Left, false), gamepad_event(0, Right, true)],
+        );
+        assert_eq!(
+            mapping.translate(&axis_event(0, 4, -0.3)),
+            vec![gamepad_event(0, Down, false), gamepad_event(0, Up, true)],
+        );
+    }
+
+    #[test]
+    fn hats() {
+        use GamepadInput::*;
+        let mapping = GamepadMapping::default();
+        assert_eq!(
+            mapping.translate(&hat_event(0, HatState::RightUp)),
+            vec![
+                gamepad_event(0, Left, false),
+                gamepad_event(0, Right, true),
+                gamepad_event(0, Down, false),
+                gamepad_event(0, Up, true),
+            ],
+        );
+        assert_eq!(
+            mapping.translate(&hat_event(0, HatState::Centered)),
+            vec![
+                gamepad_event(0, Left, false),
+                gamepad_event(0, Right, false),
+                gamepad_event(0, Up, false),
+                gamepad_event(0, Down, false),
+            ],
+        );
+    }
+}
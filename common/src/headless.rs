@@ -0,0 +1,103 @@
+//! Runs machines without opening a window. Rendered frames can be stored as
+//! PNG files or summarized as hashes, which is useful for regression testing
+//! ROMs and comparing screenshots.
+
+use crate::app::AppController;
+use image::RgbaImage;
+use piston::Event;
+use piston::UpdateArgs;
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+
+/// Runs a machine using a given controller for a given number of frames.
+pub struct HeadlessRunner<C: AppController> {
+    controller: C,
+    /// If set, each frame is stored in this directory as a PNG file.
+    frame_dir: Option<PathBuf>,
+    /// If set, a hash of each frame is written to this file, one per line.
+    hash_file: Option<PathBuf>,
+}
+
+impl<C: AppController> HeadlessRunner<C> {
+    pub fn new(controller: C) -> Self {
+        Self {
+            controller,
+            frame_dir: None,
+            hash_file: None,
+        }
+    }
+
+    pub fn with_frame_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.frame_dir = Some(path.into());
+        return self;
+    }
+
+    pub fn with_hash_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.hash_file = Some(path.into());
+        return self;
+    }
+
+    /// Resets the machine and emulates a given number of frames, unless
+    /// interrupted earlier.
+    pub fn run(&mut self, frames: u32) -> Result<(), Box<dyn Error>> {
+        if let Some(dir) = &self.frame_dir {
+            fs::create_dir_all(dir)?;
+        }
+        let mut hash_writer = match &self.hash_file {
+            Some(path) => Some(BufWriter::new(File::create(path)?)),
+            None => None,
+        };
+
+        let update_event = Event::from(UpdateArgs { dt: 1.0 / 60.0 });
+        self.controller.reset();
+        for frame in 1..=frames {
+            if self.controller.interrupted().load(Ordering::Relaxed) {
+                eprintln!("Interrupted!");
+                eprintln!("{}", self.controller.display_machine_state());
+                break;
+            }
+            self.controller.event(&update_event);
+            let frame_image = self.controller.frame_image();
+            if let Some(dir) = &self.frame_dir {
+                frame_image.save(dir.join(format!("frame_{:05}.png", frame)))?;
+            }
+            if let Some(writer) = &mut hash_writer {
+                writeln!(writer, "{:016x}", frame_hash(frame_image))?;
+            }
+        }
+        if let Some(writer) = &mut hash_writer {
+            writer.flush()?;
+        }
+        return Ok(());
+    }
+}
+
+/// Computes a 64-bit FNV-1a hash of the frame pixels. Unlike the standard
+/// library hashers, it's guaranteed to stay the same between Rust versions.
+pub fn frame_hash(image: &RgbaImage) -> u64 {
+    const OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01B3;
+    return image.as_raw().iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(PRIME)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn hashes_frames() {
+        assert_eq!(frame_hash(&RgbaImage::new(0, 0)), 0xCBF2_9CE4_8422_2325);
+        assert_eq!(
+            frame_hash(&RgbaImage::from_pixel(1, 1, Rgba([1, 2, 3, 255]))),
+            0xBE7A_0377_5164_DDBC
+        );
+    }
+}
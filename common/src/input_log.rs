@@ -0,0 +1,546 @@
+//! Frame-accurate recording and playback of input events. Together with a
+//! deterministic power-on state, a recorded input log makes it possible to
+//! replay a session exactly as it happened, which is useful for regression
+//! testing of gameplay and for tool-assisted runs.
+//!
+//! An input log is a JSON Lines file. Each line contains a single input event,
+//! along with the number of the frame during which it has been received.
+//! Loading a program is also logged, since it resets the machine and starts
+//! counting frames from the beginning.
+
+use crate::app::AppController;
+use image::RgbaImage;
+use piston::Event;
+use piston::Input;
+use serde::Deserialize;
+use serde::Serialize;
+use std::error::Error;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// A single input event, stamped with a frame number.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputLogEntry {
+    /// Number of frames emulated since the last reset or program load before
+    /// the event was received.
+    pub frame: u64,
+    #[serde(flatten)]
+    pub event: InputLogEvent,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputLogEvent {
+    Input(Input),
+    /// A program loaded from a given file.
+    Load(PathBuf),
+}
+
+#[derive(Error, Debug)]
+pub enum InputLogError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Malformed input log entry in line {line}: {source}")]
+    Parse {
+        line: usize,
+        source: serde_json::Error,
+    },
+}
+
+/// Reads an input log. The entries are expected to be sorted by frame number.
+pub fn read_input_log(reader: impl BufRead) -> Result<Vec<InputLogEntry>, InputLogError> {
+    let mut entries = vec![];
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line).map_err(|source| InputLogError::Parse {
+            line: index + 1,
+            source,
+        })?;
+        entries.push(entry);
+    }
+    return Ok(entries);
+}
+
+enum Mode {
+    /// Events are passed through without being logged.
+    Passthrough,
+    /// Input events are passed through and written to a log.
+    Recording(Box<dyn Write>),
+    /// Input events are taken from a log, and the live ones are ignored until
+    /// the whole log is replayed.
+    Playback {
+        entries: Vec<InputLogEntry>,
+        position: usize,
+    },
+}
+
+/// An [`AppController`] that wraps another one, recording or replaying its
+/// input events. Only the frames actually completed by the machine are
+/// counted, so the update events during which it's paused, stopped by the
+/// debugger, or halted don't affect the frame numbers.
+pub struct InputLogController<C: AppController> {
+    controller: C,
+    mode: Mode,
+    frame: u64,
+}
+
+impl<C: AppController> InputLogController<C> {
+    /// Wraps a given controller. By default, input is neither recorded nor
+    /// replayed.
+    pub fn new(controller: C) -> Self {
+        Self {
+            controller,
+            mode: Mode::Passthrough,
+            frame: 0,
+        }
+    }
+
+    /// Writes all input events to a given writer.
+    pub fn with_recording(mut self, writer: impl Write + 'static) -> Self {
+        self.mode = Mode::Recording(Box::new(writer));
+        return self;
+    }
+
+    /// Replays given input events instead of the live ones.
+    pub fn with_playback(mut self, entries: Vec<InputLogEntry>) -> Self {
+        self.mode = Mode::Playback {
+            entries,
+            position: 0,
+        };
+        return self;
+    }
+
+    pub fn controller(&self) -> &C {
+        &self.controller
+    }
+
+    pub fn mut_controller(&mut self) -> &mut C {
+        &mut self.controller
+    }
+
+    /// Passes all the recorded events for the current frame to the wrapped
+    /// controller.
+    fn replay_frame(&mut self) {
+        if let Mode::Playback { entries, position } = &mut self.mode {
+            while let Some(entry) = entries.get(*position) {
+                if entry.frame > self.frame {
+                    break;
+                }
+                match &entry.event {
+                    InputLogEvent::Input(input) => {
+                        self.controller.event(&Event::Input(input.clone(), None))
+                    }
+                    InputLogEvent::Load(path) => {
+                        if let Err(e) = self.controller.load_program(path) {
+                            eprintln!("Unable to load {}: {}", path.display(), e);
+                        }
+                        self.frame = 0;
+                    }
+                }
+                *position += 1;
+            }
+        }
+    }
+
+    /// Writes an event that has been received during the current frame to
+    /// the log, if it's being recorded.
+    fn record(&mut self, event: InputLogEvent) {
+        if let Mode::Recording(writer) = &mut self.mode {
+            let entry = InputLogEntry {
+                frame: self.frame,
+                event,
+            };
+            if let Err(e) = serde_json::to_writer(&mut *writer, &entry)
+                .map_err(io::Error::from)
+                .and_then(|_| writeln!(writer))
+            {
+                eprintln!("Input log error: {}", e);
+            }
+        }
+    }
+
+    fn playback_in_progress(&self) -> bool {
+        match &self.mode {
+            Mode::Playback { entries, position } => *position < entries.len(),
+            _ => false,
+        }
+    }
+}
+
+impl<C: AppController> AppController for InputLogController<C> {
+    fn frame_image(&self) -> &RgbaImage {
+        self.controller.frame_image()
+    }
+
+    fn reset(&mut self) {
+        self.frame = 0;
+        if let Mode::Playback { position, .. } = &mut self.mode {
+            *position = 0;
+        }
+        self.controller.reset();
+    }
+
+    fn interrupted(&self) -> Arc<AtomicBool> {
+        self.controller.interrupted()
+    }
+
+    fn paused(&self) -> bool {
+        self.controller.paused()
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.controller.set_paused(paused);
+    }
+
+    fn frame_count(&self) -> u64 {
+        self.controller.frame_count()
+    }
+
+    /// Loads a program and starts counting frames from the beginning, since
+    /// the machine gets reset. The load is recorded in the log, so that the
+    /// playback loads the same program at the same time. During the playback,
+    /// programs can't be loaded, since that would make the rest of the log
+    /// meaningless.
+    fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if self.playback_in_progress() {
+            return Err("Unable to load a program while replaying an input log".into());
+        }
+        self.controller.load_program(path)?;
+        self.record(InputLogEvent::Load(path.to_path_buf()));
+        self.frame = 0;
+        return Ok(());
+    }
+
+    fn event(&mut self, event: &Event) {
+        match event {
+            Event::Input(input, _) => {
+                if self.playback_in_progress() {
+                    return;
+                }
+                self.record(InputLogEvent::Input(input.clone()));
+            }
+            Event::Loop(piston::Loop::Update(_)) => {
+                self.replay_frame();
+                let frame_count = self.controller.frame_count();
+                self.controller.event(event);
+                self.frame += self.controller.frame_count() - frame_count;
+                return;
+            }
+            _ => {}
+        }
+        self.controller.event(event);
+    }
+
+    fn display_machine_state(&self) -> String {
+        self.controller.display_machine_state()
+    }
+
+    fn machine_status(&self) -> String {
+        self.controller.machine_status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use piston::Button;
+    use piston::ButtonArgs;
+    use piston::ButtonState;
+    use piston::Key;
+    use piston::UpdateArgs;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Remembers all input events, along with numbers of frames during which
+    /// they have been received.
+    struct FakeController {
+        image: RgbaImage,
+        frame: u64,
+        inputs: Vec<(u64, Input)>,
+        programs: Vec<PathBuf>,
+        paused: bool,
+        /// Set if the machine doesn't run, e.g. because it's been stopped by
+        /// the debugger.
+        stopped: bool,
+    }
+
+    impl FakeController {
+        fn new() -> Self {
+            Self {
+                image: RgbaImage::new(1, 1),
+                frame: 0,
+                inputs: vec![],
+                programs: vec![],
+                paused: false,
+                stopped: false,
+            }
+        }
+    }
+
+    impl AppController for FakeController {
+        fn frame_image(&self) -> &RgbaImage {
+            &self.image
+        }
+        fn reset(&mut self) {
+            self.frame = 0;
+        }
+        fn interrupted(&self) -> Arc<AtomicBool> {
+            Arc::new(AtomicBool::new(false))
+        }
+        fn paused(&self) -> bool {
+            self.paused
+        }
+        fn set_paused(&mut self, paused: bool) {
+            self.paused = paused;
+        }
+        fn frame_count(&self) -> u64 {
+            self.frame
+        }
+        fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+            self.frame = 0;
+            self.programs.push(path.to_path_buf());
+            return Ok(());
+        }
+        fn event(&mut self, event: &Event) {
+            match event {
+                Event::Input(input, _) => self.inputs.push((self.frame, input.clone())),
+                Event::Loop(piston::Loop::Update(_)) if !self.paused && !self.stopped => {
+                    self.frame += 1
+                }
+                _ => {}
+            }
+        }
+        fn display_machine_state(&self) -> String {
+            String::new()
+        }
+        fn machine_status(&self) -> String {
+            String::new()
+        }
+    }
+
+    /// A writer that can still be inspected after being moved into the
+    /// controller.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn key_press(key: Key) -> Input {
+        Input::Button(ButtonArgs {
+            state: ButtonState::Press,
+            button: Button::Keyboard(key),
+            scancode: None,
+        })
+    }
+
+    fn update() -> Event {
+        Event::from(UpdateArgs { dt: 1.0 / 60.0 })
+    }
+
+    /// Feeds a sequence of frames to a given controller. Each frame starts
+    /// with a given list of input events, followed by an update.
+    fn run_frames(controller: &mut impl AppController, frames: &[&[Input]]) {
+        controller.reset();
+        for inputs in frames {
+            for input in *inputs {
+                controller.event(&Event::Input(input.clone(), None));
+            }
+            controller.event(&update());
+        }
+    }
+
+    #[test]
+    fn records_inputs_with_frame_numbers() {
+        let buffer = SharedBuffer::default();
+        let mut controller =
+            InputLogController::new(FakeController::new()).with_recording(buffer.clone());
+        run_frames(
+            &mut controller,
+            &[
+                &[key_press(Key::A)],
+                &[],
+                &[key_press(Key::B), key_press(Key::C)],
+            ],
+        );
+
+        let entries = read_input_log(&buffer.0.borrow()[..]).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                InputLogEntry {
+                    frame: 0,
+                    event: InputLogEvent::Input(key_press(Key::A)),
+                },
+                InputLogEntry {
+                    frame: 2,
+                    event: InputLogEvent::Input(key_press(Key::B)),
+                },
+                InputLogEntry {
+                    frame: 2,
+                    event: InputLogEvent::Input(key_press(Key::C)),
+                },
+            ]
+        );
+        assert_eq!(controller.controller().inputs.len(), 3);
+    }
+
+    #[test]
+    fn does_not_count_paused_frames() {
+        let buffer = SharedBuffer::default();
+        let mut controller =
+            InputLogController::new(FakeController::new()).with_recording(buffer.clone());
+        run_frames(&mut controller, &[&[]]);
+        controller.set_paused(true);
+        controller.event(&update());
+        controller.event(&update());
+        controller.set_paused(false);
+        controller.event(&Event::Input(key_press(Key::A), None));
+
+        let entries = read_input_log(&buffer.0.borrow()[..]).unwrap();
+        assert_eq!(
+            entries,
+            vec![InputLogEntry {
+                frame: 1,
+                event: InputLogEvent::Input(key_press(Key::A)),
+            }]
+        );
+    }
+
+    #[test]
+    fn does_not_count_frames_that_did_not_run() {
+        let buffer = SharedBuffer::default();
+        let mut controller =
+            InputLogController::new(FakeController::new()).with_recording(buffer.clone());
+        run_frames(&mut controller, &[&[]]);
+        controller.mut_controller().stopped = true;
+        controller.event(&update());
+        controller.event(&Event::Input(key_press(Key::A), None));
+        controller.mut_controller().stopped = false;
+        controller.event(&update());
+        controller.event(&Event::Input(key_press(Key::B), None));
+
+        let entries = read_input_log(&buffer.0.borrow()[..]).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                InputLogEntry {
+                    frame: 1,
+                    event: InputLogEvent::Input(key_press(Key::A)),
+                },
+                InputLogEntry {
+                    frame: 2,
+                    event: InputLogEvent::Input(key_press(Key::B)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn restarts_counting_frames_after_loading_program() {
+        let buffer = SharedBuffer::default();
+        let mut controller =
+            InputLogController::new(FakeController::new()).with_recording(buffer.clone());
+        run_frames(&mut controller, &[&[], &[]]);
+        controller.load_program(Path::new("game.bin")).unwrap();
+        run_frames(&mut controller, &[&[key_press(Key::A)]]);
+
+        let entries = read_input_log(&buffer.0.borrow()[..]).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                InputLogEntry {
+                    frame: 2,
+                    event: InputLogEvent::Load(PathBuf::from("game.bin")),
+                },
+                InputLogEntry {
+                    frame: 0,
+                    event: InputLogEvent::Input(key_press(Key::A)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn replays_inputs() {
+        let buffer = SharedBuffer::default();
+        let mut recording =
+            InputLogController::new(FakeController::new()).with_recording(buffer.clone());
+        run_frames(
+            &mut recording,
+            &[&[], &[key_press(Key::Up)], &[], &[key_press(Key::Down)]],
+        );
+
+        let entries = read_input_log(&buffer.0.borrow()[..]).unwrap();
+        let mut playback = InputLogController::new(FakeController::new()).with_playback(entries);
+        // Live events should be ignored during playback.
+        run_frames(&mut playback, &[&[key_press(Key::X)], &[], &[], &[], &[]]);
+        assert_eq!(playback.controller().inputs, recording.controller().inputs);
+
+        // Once the log is exhausted, live events are accepted again.
+        playback.event(&Event::Input(key_press(Key::Y), None));
+        assert_eq!(
+            playback.controller().inputs.last(),
+            Some(&(5, key_press(Key::Y)))
+        );
+    }
+
+    #[test]
+    fn replays_program_loads() {
+        let buffer = SharedBuffer::default();
+        let mut recording =
+            InputLogController::new(FakeController::new()).with_recording(buffer.clone());
+        run_frames(&mut recording, &[&[key_press(Key::Up)], &[]]);
+        recording.event(&Event::Input(key_press(Key::Left), None));
+        recording.load_program(Path::new("game.bin")).unwrap();
+        for _ in 0..3 {
+            recording.event(&update());
+        }
+        recording.event(&Event::Input(key_press(Key::Down), None));
+
+        let entries = read_input_log(&buffer.0.borrow()[..]).unwrap();
+        let mut playback = InputLogController::new(FakeController::new()).with_playback(entries);
+        run_frames(&mut playback, &[&[], &[], &[], &[], &[], &[]]);
+        assert_eq!(playback.controller().inputs, recording.controller().inputs);
+        assert_eq!(
+            playback.controller().programs,
+            vec![PathBuf::from("game.bin")]
+        );
+    }
+
+    #[test]
+    fn ignores_program_loads_during_playback() {
+        let entries = vec![InputLogEntry {
+            frame: 2,
+            event: InputLogEvent::Input(key_press(Key::A)),
+        }];
+        let mut playback = InputLogController::new(FakeController::new()).with_playback(entries);
+        run_frames(&mut playback, &[&[]]);
+        assert!(playback.load_program(Path::new("game.bin")).is_err());
+        assert!(playback.controller().programs.is_empty());
+    }
+
+    #[test]
+    fn reports_malformed_entries() {
+        let log = "{\"frame\":1,\"input\":{\"Focus\":true}}\n\ngarbage\n";
+        assert!(matches!(
+            read_input_log(log.as_bytes()),
+            Err(InputLogError::Parse { line: 3, .. })
+        ));
+    }
+}
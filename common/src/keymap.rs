@@ -0,0 +1,53 @@
+//! Loading key mapping configuration files. The structure of the configuration
+//! is machine-specific, but all machines share the file formats (see
+//! [`crate::config`]) and the way host keys are named.
+
+use crate::config::ConfigError;
+use piston::Key;
+use serde::de::value::StrDeserializer;
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(thiserror::Error, Debug)]
+pub enum KeymapError {
+    #[error(transparent)]
+    ConfigError(#[from] ConfigError),
+
+    #[error("Unknown key: {0}")]
+    UnknownKey(String),
+}
+
+/// Parses a host key name, as used by Piston, e.g. `A`, `LShift`, or
+/// `NumPad8`.
+pub fn parse_key(name: &str) -> Result<Key, KeymapError> {
+    let deserializer: StrDeserializer<serde::de::value::Error> = name.into_deserializer();
+    return Key::deserialize(deserializer).map_err(|_| KeymapError::UnknownKey(name.to_string()));
+}
+
+/// Converts a map indexed by host key names to a map indexed by keys.
+pub fn parse_key_map<T>(map: HashMap<String, T>) -> Result<HashMap<Key, T>, KeymapError> {
+    return map
+        .into_iter()
+        .map(|(name, value)| Ok((parse_key(&name)?, value)))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::assert_matches::assert_matches;
+
+    #[test]
+    fn parses_key_maps() {
+        let map = HashMap::from([("A".to_string(), 1)]);
+        assert_eq!(parse_key_map(map).unwrap(), HashMap::from([(Key::A, 1)]));
+    }
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!(parse_key("LShift").unwrap(), Key::LShift);
+        assert_eq!(parse_key("NumPad8").unwrap(), Key::NumPad8);
+        assert_matches!(parse_key("Foo"), Err(KeymapError::UnknownKey(name)) if name == "Foo");
+    }
+}
@@ -1,10 +1,27 @@
 #![feature(assert_matches)]
 
 pub mod app;
+pub mod archive;
+pub mod audio;
+pub mod backend;
 pub mod build_utils;
+pub mod capture;
+pub mod cheats;
 pub mod colors;
+pub mod config;
+pub mod crt;
 pub mod debugger;
+pub mod gamepad;
+pub mod headless;
+pub mod input_log;
+pub mod keymap;
+pub mod pacing;
+pub mod player;
+pub mod scheduler;
 pub mod test_utils;
+pub mod text;
+#[cfg(feature = "desktop")]
+pub mod watch;
 
 #[cfg(test)]
 #[macro_use]
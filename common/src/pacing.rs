@@ -0,0 +1,319 @@
+//! Frame pacing. Decides when to emulate the next frame, so that the emulation
+//! speed doesn't depend on how often the window backend delivers update
+//! events, which is usually tied to the refresh rate of the display.
+
+use crate::audio::AudioBufferMonitor;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Sources of timing that drive the emulation. Can be selected from the
+/// command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum SyncMode {
+    /// Emulates frames whenever the audio output needs more samples, which
+    /// prevents the sound from crackling. Falls back to `timer` if the machine
+    /// doesn't produce any sound or the speed is different than 1.
+    Audio,
+    /// Emulates a frame on every update event of the window. Only gives the
+    /// correct speed if the updates arrive at the frame rate of the emulated
+    /// machine.
+    Vsync,
+    /// Emulates frames at the machine's frame rate, measured with a monotonic
+    /// clock.
+    Timer,
+}
+
+/// Default limit of frames that may be emulated without being displayed. See
+/// [`FrameGovernor::set_max_frame_skip`].
+pub const DEFAULT_MAX_FRAME_SKIP: u32 = 4;
+
+/// Rate of update events requested from the window backend in the modes that
+/// don't rely on the updates for timing.
+const UPDATE_POLL_RATE: u64 = 240;
+
+/// Decides how many frames to emulate on each update event of the event loop.
+pub struct FrameGovernor {
+    mode: SyncMode,
+    frame_rate: u64,
+    speed: f64,
+    audio_monitor: Option<AudioBufferMonitor>,
+    /// Accumulates fractions of frames to emulate in the `vsync` mode if the
+    /// speed is not an integer.
+    pending_frames: f64,
+    /// The moment when the next frame is due in the `timer` mode. Set on the
+    /// first update.
+    next_frame: Option<Instant>,
+    max_frame_skip: u32,
+    /// Frames emulated during the current update.
+    update_frames: u32,
+    /// Total number of frames that were emulated, but not displayed.
+    skipped_frames: u64,
+}
+
+impl FrameGovernor {
+    pub fn new(mode: SyncMode, frame_rate: u64) -> Self {
+        Self {
+            mode,
+            frame_rate,
+            speed: 1.0,
+            audio_monitor: None,
+            pending_frames: 0.0,
+            next_frame: None,
+            max_frame_skip: DEFAULT_MAX_FRAME_SKIP,
+            update_frames: 0,
+            skipped_frames: 0,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: SyncMode) {
+        self.mode = mode;
+    }
+
+    pub fn set_frame_rate(&mut self, frames_per_second: u64) {
+        self.frame_rate = frames_per_second;
+    }
+
+    /// Sets the emulation speed, relative to the real machine. Has to be
+    /// positive, since it determines the frame period.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    /// Limits how many frames in a row may be emulated without being displayed
+    /// in the `audio` and `timer` modes. If the host computer can't keep up
+    /// with emulating and displaying every frame, the governor emulates
+    /// several frames per update and only the last one gets displayed. This
+    /// keeps the speed (and the sound) right at the cost of a choppier
+    /// picture. If even that's not enough, the emulation slows down. Setting
+    /// the limit to 0 disables frame skipping.
+    pub fn set_max_frame_skip(&mut self, max_frame_skip: u32) {
+        self.max_frame_skip = max_frame_skip;
+    }
+
+    /// Returns the total number of frames that were emulated, but skipped
+    /// instead of being displayed.
+    pub fn skipped_frames(&self) -> u64 {
+        self.skipped_frames
+    }
+
+    /// Sets a monitor of the audio buffer that drives the emulation in the
+    /// `audio` mode.
+    pub fn set_audio_monitor(&mut self, monitor: AudioBufferMonitor) {
+        self.audio_monitor = Some(monitor);
+    }
+
+    /// Returns the mode that is actually in use, taking fallbacks into
+    /// account.
+    pub fn effective_mode(&self) -> SyncMode {
+        match self.mode {
+            SyncMode::Audio if self.audio_monitor.is_none() || self.speed != 1.0 => SyncMode::Timer,
+            mode => mode,
+        }
+    }
+
+    /// Returns how often the window backend should deliver update events. In
+    /// the `vsync` mode, each update corresponds to a single frame. Other modes
+    /// poll the clock more often to keep the frame timing accurate.
+    pub fn update_rate(&self) -> u64 {
+        match self.mode {
+            SyncMode::Vsync => self.frame_rate,
+            SyncMode::Audio | SyncMode::Timer => UPDATE_POLL_RATE.max(self.frame_rate),
+        }
+    }
+
+    /// Starts deciding about frames for a new update event that arrived at a
+    /// given moment.
+    pub fn start_update(&mut self, now: Instant) {
+        self.update_frames = 0;
+        match self.effective_mode() {
+            SyncMode::Vsync => self.pending_frames += self.speed,
+            SyncMode::Timer => {
+                // Give up catching up if we're so far behind the schedule that
+                // skipping frames won't help. This also protects from running
+                // in a burst after the application was suspended.
+                let max_lag = self.frame_period() * self.max_frame_skip;
+                match self.next_frame {
+                    Some(next_frame) if now <= next_frame + max_lag => {}
+                    _ => self.next_frame = Some(now),
+                }
+            }
+            SyncMode::Audio => {}
+        }
+    }
+
+    /// Returns `true` if another frame should be emulated during the current
+    /// update event, and counts it as emulated.
+    pub fn frame_due(&mut self, now: Instant) -> bool {
+        let mode = self.effective_mode();
+        if mode == SyncMode::Vsync {
+            if self.pending_frames < 1.0 {
+                return false;
+            }
+            self.pending_frames -= 1.0;
+            return true;
+        }
+        // In the audio mode, the limit also prevents an infinite loop if the
+        // machine stops producing samples, e.g. because it's paused by the
+        // debugger.
+        if self.update_frames > self.max_frame_skip {
+            return false;
+        }
+        let due = match mode {
+            SyncMode::Timer => {
+                let frame_period = self.frame_period();
+                let next_frame = self.next_frame.get_or_insert(now);
+                let due = *next_frame <= now;
+                if due {
+                    *next_frame += frame_period;
+                }
+                due
+            }
+            _ => self.audio_monitor.as_ref().unwrap().needs_samples(),
+        };
+        if due {
+            if self.update_frames > 0 {
+                self.skipped_frames += 1;
+            }
+            self.update_frames += 1;
+        }
+        return due;
+    }
+
+    /// Returns the time between two frames, taking the speed into account.
+    fn frame_period(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / (self.frame_rate as f64 * self.speed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::create_consumer_and_source;
+
+    /// Returns the number of frames that the governor decides to emulate
+    /// during an update that arrives at a given moment.
+    fn update(governor: &mut FrameGovernor, now: Instant) -> u32 {
+        governor.start_update(now);
+        let mut frames = 0;
+        while governor.frame_due(now) {
+            frames += 1;
+        }
+        return frames;
+    }
+
+    #[test]
+    fn vsync_mode_follows_updates() {
+        let mut governor = FrameGovernor::new(SyncMode::Vsync, 50);
+        governor.set_speed(1.5);
+        let now = Instant::now();
+        let frames: Vec<u32> = (0..4).map(|_| update(&mut governor, now)).collect();
+        assert_eq!(frames, [1, 2, 1, 2]);
+        assert_eq!(governor.update_rate(), 50);
+    }
+
+    #[test]
+    fn timer_mode_follows_clock() {
+        let mut governor = FrameGovernor::new(SyncMode::Timer, 50);
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        assert_eq!(update(&mut governor, start), 1);
+        // Updates arrive at 144 Hz.
+        assert_eq!(update(&mut governor, ms(7)), 0);
+        assert_eq!(update(&mut governor, ms(14)), 0);
+        assert_eq!(update(&mut governor, ms(21)), 1);
+        assert_eq!(update(&mut governor, ms(28)), 0);
+        assert_eq!(update(&mut governor, ms(35)), 0);
+        assert_eq!(update(&mut governor, ms(42)), 1);
+        // A late update catches up.
+        assert_eq!(update(&mut governor, ms(90)), 2);
+        assert_eq!(governor.update_rate(), 240);
+    }
+
+    #[test]
+    fn timer_mode_takes_speed_into_account() {
+        let mut governor = FrameGovernor::new(SyncMode::Timer, 50);
+        governor.set_speed(2.0);
+        let start = Instant::now();
+        assert_eq!(update(&mut governor, start), 1);
+        assert_eq!(update(&mut governor, start + Duration::from_millis(40)), 4);
+    }
+
+    #[test]
+    fn timer_mode_gives_up_catching_up() {
+        let mut governor = FrameGovernor::new(SyncMode::Timer, 50);
+        let start = Instant::now();
+        assert_eq!(update(&mut governor, start), 1);
+        assert_eq!(update(&mut governor, start + Duration::from_secs(5)), 1);
+        assert_eq!(
+            update(&mut governor, start + Duration::from_millis(5010)),
+            0
+        );
+        assert_eq!(
+            update(&mut governor, start + Duration::from_millis(5020)),
+            1
+        );
+    }
+
+    #[test]
+    fn timer_mode_skips_frames() {
+        let mut governor = FrameGovernor::new(SyncMode::Timer, 50);
+        governor.set_max_frame_skip(2);
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        assert_eq!(update(&mut governor, start), 1);
+        assert_eq!(update(&mut governor, ms(60)), 3);
+        assert_eq!(governor.skipped_frames(), 2);
+        // Too far behind to catch up by skipping frames.
+        assert_eq!(update(&mut governor, ms(130)), 1);
+        assert_eq!(governor.skipped_frames(), 2);
+        assert_eq!(update(&mut governor, ms(150)), 1);
+    }
+
+    #[test]
+    fn timer_mode_without_frame_skipping() {
+        let mut governor = FrameGovernor::new(SyncMode::Timer, 50);
+        governor.set_max_frame_skip(0);
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        assert_eq!(update(&mut governor, start), 1);
+        assert_eq!(update(&mut governor, ms(45)), 1);
+        assert_eq!(update(&mut governor, ms(60)), 0);
+        assert_eq!(update(&mut governor, ms(65)), 1);
+        assert_eq!(governor.skipped_frames(), 0);
+    }
+
+    #[test]
+    fn audio_mode_follows_buffer() {
+        let (mut consumer, _source) =
+            create_consumer_and_source(44_100, Duration::from_micros(100));
+        let mut governor = FrameGovernor::new(SyncMode::Audio, 50);
+        governor.set_audio_monitor(consumer.monitor());
+        assert_eq!(governor.effective_mode(), SyncMode::Audio);
+        let now = Instant::now();
+
+        // The machine doesn't produce any samples, so the governor gives up
+        // after a couple of frames.
+        assert_eq!(update(&mut governor, now), DEFAULT_MAX_FRAME_SKIP + 1);
+        assert_eq!(governor.skipped_frames(), DEFAULT_MAX_FRAME_SKIP as u64);
+
+        governor.start_update(now);
+        assert!(governor.frame_due(now));
+        for _ in 0..10 {
+            consumer.consume(0.0);
+        }
+        assert!(!governor.frame_due(now));
+    }
+
+    #[test]
+    fn audio_mode_falls_back_to_timer() {
+        let mut governor = FrameGovernor::new(SyncMode::Audio, 50);
+        assert_eq!(governor.effective_mode(), SyncMode::Timer);
+
+        let (consumer, _source) = create_consumer_and_source(44_100, Duration::from_millis(50));
+        governor.set_audio_monitor(consumer.monitor());
+        governor.set_speed(2.0);
+        assert_eq!(governor.effective_mode(), SyncMode::Timer);
+        governor.set_speed(1.0);
+        assert_eq!(governor.effective_mode(), SyncMode::Audio);
+    }
+}
@@ -0,0 +1,58 @@
+//! Plays music by running a machine without a window. Instead of emulating
+//! whole video frames at the display refresh rate, the machine is driven by the
+//! audio clock: it only runs while the audio buffer needs more samples.
+
+use crate::app::Machine;
+use crate::audio::AudioBufferMonitor;
+use std::error::Error;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Number of machine ticks emulated between checks of the audio buffer level.
+const TICKS_PER_CHECK: u32 = 1000;
+
+pub struct AudioPlayer<'a, M: Machine> {
+    machine: &'a mut M,
+    monitor: AudioBufferMonitor,
+    interrupted: Arc<AtomicBool>,
+}
+
+impl<'a, M: Machine> AudioPlayer<'a, M> {
+    /// Creates a player for a machine that sends its audio output to a buffer
+    /// observed by a given monitor.
+    pub fn new(machine: &'a mut M, monitor: AudioBufferMonitor) -> Self {
+        Self {
+            machine,
+            monitor,
+            interrupted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn interrupted(&self) -> Arc<AtomicBool> {
+        self.interrupted.clone()
+    }
+
+    /// Resets the machine and plays its audio output for a given time, or until
+    /// interrupted if the duration is `None`.
+    pub fn run(&mut self, duration: Option<Duration>) -> Result<(), Box<dyn Error>> {
+        self.machine.reset();
+        let start_time = Instant::now();
+        while !self.interrupted.load(Ordering::Relaxed) {
+            if duration.map_or(false, |duration| start_time.elapsed() >= duration) {
+                break;
+            }
+            if self.monitor.needs_samples() {
+                for _ in 0..TICKS_PER_CHECK {
+                    self.machine.tick()?;
+                }
+            } else {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,198 @@
+//! A clock scheduler shared by the emulated machines. Each of them has a
+//! master clock (the TIA color clock, the VIC dot clock, etc.) that is divided
+//! to get the clock of the CPU and the other chips. On top of that, the CPU can
+//! be stalled: by a program that waits for the horizontal sync (WSYNC), by the
+//! video chip that takes over the bus (VIC bad lines), or by DMA transfers.
+
+/// Result of a single master clock tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScheduledTick {
+    /// If `true`, the chips driven by the divided clock perform a cycle in
+    /// this tick, regardless of whether the CPU is stalled.
+    pub chip_cycle: bool,
+    /// If `true`, the CPU performs a cycle in this tick.
+    pub cpu_cycle: bool,
+}
+
+/// Divides the master clock of a machine and decides when the CPU is allowed
+/// to perform a cycle.
+#[derive(Debug, Clone)]
+pub struct CpuScheduler {
+    /// Number of master clock ticks per CPU cycle.
+    divider: u32,
+    /// Index of the next master clock tick within the current CPU cycle.
+    phase: u32,
+    /// The state of the RDY line. If `false`, the CPU is stalled until the
+    /// line is released.
+    ready: bool,
+    /// Set if the CPU waits for the horizontal sync.
+    waiting_for_sync: bool,
+    /// Number of the upcoming CPU cycles for which the CPU stays stalled.
+    stall_cycles: u32,
+}
+
+impl CpuScheduler {
+    /// Creates a scheduler that lets the CPU perform a cycle once every
+    /// `divider` master clock ticks, starting with the first one.
+    pub fn new(divider: u32) -> Self {
+        assert!(divider > 0, "Clock divider must be positive");
+        Self {
+            divider,
+            phase: 0,
+            ready: true,
+            waiting_for_sync: false,
+            stall_cycles: 0,
+        }
+    }
+
+    /// Processes a single master clock tick.
+    pub fn tick(&mut self) -> ScheduledTick {
+        let chip_cycle = self.phase == 0;
+        let cpu_cycle = chip_cycle && !self.stalled();
+        if chip_cycle && self.stall_cycles > 0 {
+            self.stall_cycles -= 1;
+        }
+        self.phase = (self.phase + 1) % self.divider;
+        return ScheduledTick {
+            chip_cycle,
+            cpu_cycle,
+        };
+    }
+
+    /// Returns the index of the next master clock tick within the current CPU
+    /// cycle. Zero means that the next tick starts a new cycle.
+    pub fn phase(&self) -> u32 {
+        self.phase
+    }
+
+    /// Restarts the divided clock, so that the next master clock tick starts
+    /// a new CPU cycle.
+    pub fn restart(&mut self) {
+        self.phase = 0;
+    }
+
+    /// Returns `true` if the CPU is currently stalled for any reason.
+    pub fn stalled(&self) -> bool {
+        !self.ready || self.waiting_for_sync || self.stall_cycles > 0
+    }
+
+    /// Sets the state of the RDY line. The CPU is stalled as long as it's
+    /// `false`.
+    pub fn set_ready(&mut self, ready: bool) {
+        self.ready = ready;
+    }
+
+    /// Stalls the CPU until [`CpuScheduler::sync`] is called.
+    pub fn wait_for_sync(&mut self) {
+        self.waiting_for_sync = true;
+    }
+
+    /// Releases the CPU if it waits for the horizontal sync.
+    pub fn sync(&mut self) {
+        self.waiting_for_sync = false;
+    }
+
+    /// Stalls the CPU for a given number of upcoming CPU cycles, for example,
+    /// to perform a DMA transfer. Stalls requested while the CPU is already
+    /// stalled this way add up.
+    pub fn stall(&mut self, cycles: u32) {
+        self.stall_cycles += cycles;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticks(scheduler: &mut CpuScheduler, n: usize) -> Vec<(bool, bool)> {
+        (0..n)
+            .map(|_| {
+                let tick = scheduler.tick();
+                (tick.chip_cycle, tick.cpu_cycle)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn divides_clock() {
+        let mut scheduler = CpuScheduler::new(3);
+        assert_eq!(
+            ticks(&mut scheduler, 7),
+            [
+                (true, true),
+                (false, false),
+                (false, false),
+                (true, true),
+                (false, false),
+                (false, false),
+                (true, true),
+            ]
+        );
+        assert_eq!(scheduler.phase(), 1);
+
+        let mut scheduler = CpuScheduler::new(1);
+        assert_eq!(ticks(&mut scheduler, 2), [(true, true), (true, true)]);
+    }
+
+    #[test]
+    fn restarts() {
+        let mut scheduler = CpuScheduler::new(3);
+        scheduler.tick();
+        scheduler.restart();
+        assert_eq!(
+            ticks(&mut scheduler, 4),
+            [(true, true), (false, false), (false, false), (true, true)]
+        );
+    }
+
+    #[test]
+    fn waits_for_sync() {
+        let mut scheduler = CpuScheduler::new(2);
+        scheduler.wait_for_sync();
+        assert!(scheduler.stalled());
+        assert_eq!(
+            ticks(&mut scheduler, 4),
+            [(true, false), (false, false), (true, false), (false, false)]
+        );
+        scheduler.sync();
+        assert!(!scheduler.stalled());
+        assert_eq!(ticks(&mut scheduler, 2), [(true, true), (false, false)]);
+    }
+
+    #[test]
+    fn respects_ready_line() {
+        let mut scheduler = CpuScheduler::new(1);
+        scheduler.set_ready(false);
+        assert_eq!(ticks(&mut scheduler, 2), [(true, false), (true, false)]);
+        scheduler.set_ready(true);
+        assert_eq!(ticks(&mut scheduler, 1), [(true, true)]);
+    }
+
+    #[test]
+    fn stalls_for_cycles() {
+        let mut scheduler = CpuScheduler::new(2);
+        scheduler.stall(1);
+        scheduler.stall(1);
+        assert_eq!(
+            ticks(&mut scheduler, 6),
+            [
+                (true, false),
+                (false, false),
+                (true, false),
+                (false, false),
+                (true, true),
+                (false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn combines_stalls() {
+        let mut scheduler = CpuScheduler::new(1);
+        scheduler.stall(1);
+        scheduler.wait_for_sync();
+        assert_eq!(ticks(&mut scheduler, 2), [(true, false), (true, false)]);
+        scheduler.sync();
+        assert_eq!(ticks(&mut scheduler, 1), [(true, true)]);
+    }
+}
@@ -0,0 +1,155 @@
+//! A tiny bitmap font renderer for drawing text directly on frame images. The
+//! font is 3x5 pixels, uppercase only, and covers just enough characters to
+//! display numbers and short labels.
+
+use image::Rgba;
+use image::RgbaImage;
+
+pub const GLYPH_WIDTH: u32 = 3;
+pub const GLYPH_HEIGHT: u32 = 5;
+/// Horizontal distance between two consecutive characters.
+pub const CHAR_ADVANCE: u32 = GLYPH_WIDTH + 1;
+/// Vertical distance between two consecutive lines of text.
+pub const LINE_ADVANCE: u32 = GLYPH_HEIGHT + 1;
+
+/// Returns the bitmap of a given character. Each row is encoded as 3 bits, the
+/// most significant one being the leftmost pixel. Lowercase letters are
+/// rendered as uppercase ones, and unsupported characters as question marks.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT as usize] {
+    match c.to_ascii_uppercase() {
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '$' => [0b011, 0b110, 0b010, 0b011, 0b110],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+/// Returns the width and height of a given (possibly multi-line) text, in
+/// pixels. The size doesn't include the spacing after the last character and
+/// below the last line.
+pub fn text_size(text: &str) -> (u32, u32) {
+    let lines = text.lines().count() as u32;
+    let columns = text
+        .lines()
+        .map(|line| line.chars().count() as u32)
+        .max()
+        .unwrap_or(0);
+    return (
+        (columns * CHAR_ADVANCE).saturating_sub(1),
+        (lines * LINE_ADVANCE).saturating_sub(1),
+    );
+}
+
+/// Draws a (possibly multi-line) text on an image, with its top left corner at
+/// given coordinates. Pixels that would fall outside the image are clipped.
+pub fn draw_text(image: &mut RgbaImage, x: u32, y: u32, text: &str, color: Rgba<u8>) {
+    for (line_index, line) in text.lines().enumerate() {
+        let line_y = y + line_index as u32 * LINE_ADVANCE;
+        for (char_index, c) in line.chars().enumerate() {
+            let char_x = x + char_index as u32 * CHAR_ADVANCE;
+            for (row_index, row) in glyph(c).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if row & (0b100 >> column) == 0 {
+                        continue;
+                    }
+                    let (pixel_x, pixel_y) = (char_x + column, line_y + row_index as u32);
+                    if pixel_x < image.width() && pixel_y < image.height() {
+                        image.put_pixel(pixel_x, pixel_y, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: Rgba<u8> = Rgba([255, 255, 255, 255]);
+    const O: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+    fn pixels(image: &RgbaImage) -> Vec<Vec<Rgba<u8>>> {
+        (0..image.height())
+            .map(|y| (0..image.width()).map(|x| *image.get_pixel(x, y)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn draws_text() {
+        let mut image = RgbaImage::new(9, 7);
+        draw_text(&mut image, 1, 1, "1t", W);
+        assert_eq!(
+            pixels(&image),
+            vec![
+                vec![O, O, O, O, O, O, O, O, O],
+                vec![O, O, W, O, O, W, W, W, O],
+                vec![O, W, W, O, O, O, W, O, O],
+                vec![O, O, W, O, O, O, W, O, O],
+                vec![O, O, W, O, O, O, W, O, O],
+                vec![O, W, W, W, O, O, W, O, O],
+                vec![O, O, O, O, O, O, O, O, O],
+            ]
+        );
+    }
+
+    #[test]
+    fn clips_text() {
+        let mut image = RgbaImage::new(2, 2);
+        draw_text(&mut image, 0, 0, "8\n8", W);
+        assert_eq!(pixels(&image), vec![vec![W, W], vec![W, O]]);
+    }
+
+    #[test]
+    fn measures_text() {
+        assert_eq!(text_size(""), (0, 0));
+        assert_eq!(text_size("A"), (3, 5));
+        assert_eq!(text_size("AB\nCDE"), (11, 11));
+    }
+}
@@ -0,0 +1,330 @@
+//! Reloading programs when their files change. During homebrew development,
+//! this lets the emulator pick up every new build produced by the assembler
+//! without being restarted, so that the debugger session (including
+//! breakpoints) stays intact.
+
+use crate::app::AppController;
+use image::RgbaImage;
+use notify::event::ModifyKind;
+use notify::Event as FileEvent;
+use notify::EventKind;
+use notify::RecommendedWatcher;
+use notify::RecursiveMode;
+use notify::Watcher;
+use piston::Event;
+use piston::Loop;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How long a file needs to stay untouched after being changed before it's
+/// considered ready to read. Assemblers often write their output in several
+/// chunks, so reading it right after the first change could yield a truncated
+/// program.
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Watches a single file for changes.
+pub struct FileWatcher {
+    path: PathBuf,
+    file_name: OsString,
+    events: Receiver<notify::Result<FileEvent>>,
+    /// Keeps the watcher alive; dropping it stops watching.
+    _watcher: RecommendedWatcher,
+    /// The moment when the most recent change was noticed, if it hasn't been
+    /// reported yet.
+    last_change: Option<Instant>,
+}
+
+impl FileWatcher {
+    /// Starts watching a given file. Actually, the whole directory is watched,
+    /// since some tools replace files by deleting and recreating them, which
+    /// would end watching the file itself.
+    pub fn new(path: &Path) -> notify::Result<Self> {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| notify::Error::generic("Not a file path"))?
+            .to_os_string();
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        return Ok(Self {
+            path: path.to_path_buf(),
+            file_name,
+            events,
+            _watcher: watcher,
+            last_change: None,
+        });
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns `true` if the file has changed since the last time it was
+    /// reported, and it hasn't been touched for a while, so it's safe to read.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        for event in self.events.try_iter() {
+            match event {
+                Ok(event) if is_change_of(&event, &self.file_name) => self.last_change = Some(now),
+                Ok(_) => {}
+                Err(e) => eprintln!("File watcher error: {}", e),
+            }
+        }
+        return settled(&mut self.last_change, now);
+    }
+}
+
+/// Returns `true` if a given event means that the contents of a file with a
+/// given name may have changed. Metadata changes, such as touching the file or
+/// changing its permissions, are ignored. Some platforms don't tell what kind
+/// of modification happened, so these events count as changes, too.
+fn is_change_of(event: &FileEvent, file_name: &OsStr) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Create(_)
+            | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any)
+    ) && event
+        .paths
+        .iter()
+        .any(|path| path.file_name() == Some(file_name))
+}
+
+/// Returns `true` and forgets the last change if it happened at least
+/// [`SETTLE_TIME`] ago.
+fn settled(last_change: &mut Option<Instant>, now: Instant) -> bool {
+    match *last_change {
+        Some(time) if now >= time + SETTLE_TIME => {
+            *last_change = None;
+            return true;
+        }
+        _ => return false,
+    }
+}
+
+/// An [`AppController`] that wraps another one, reloading the program and
+/// resetting the machine whenever the program file changes. By default, no
+/// file is watched.
+pub struct WatchingController<C: AppController> {
+    controller: C,
+    watcher: Option<FileWatcher>,
+}
+
+impl<C: AppController> WatchingController<C> {
+    pub fn new(controller: C) -> Self {
+        Self {
+            controller,
+            watcher: None,
+        }
+    }
+
+    /// Reloads the program from a file watched by a given watcher.
+    pub fn with_watcher(mut self, watcher: FileWatcher) -> Self {
+        self.watcher = Some(watcher);
+        return self;
+    }
+
+    pub fn controller(&self) -> &C {
+        &self.controller
+    }
+
+    pub fn mut_controller(&mut self) -> &mut C {
+        &mut self.controller
+    }
+
+    fn reload_if_changed(&mut self) {
+        let watcher = match &mut self.watcher {
+            Some(watcher) => watcher,
+            None => return,
+        };
+        if !watcher.poll(Instant::now()) {
+            return;
+        }
+        let path = watcher.path();
+        match self.controller.load_program(path) {
+            Ok(()) => eprintln!("Reloaded {}", path.display()),
+            Err(e) => eprintln!("Unable to reload {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl<C: AppController> AppController for WatchingController<C> {
+    fn frame_image(&self) -> &RgbaImage {
+        self.controller.frame_image()
+    }
+
+    fn reset(&mut self) {
+        self.controller.reset();
+    }
+
+    fn interrupted(&self) -> Arc<AtomicBool> {
+        self.controller.interrupted()
+    }
+
+    fn paused(&self) -> bool {
+        self.controller.paused()
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.controller.set_paused(paused);
+    }
+
+    fn frame_count(&self) -> u64 {
+        self.controller.frame_count()
+    }
+
+    /// Loads a program and, if a file is being watched, starts watching the
+    /// new one instead.
+    fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.controller.load_program(path)?;
+        if let Some(watcher) = &mut self.watcher {
+            if watcher.path() != path {
+                match FileWatcher::new(path) {
+                    Ok(new_watcher) => *watcher = new_watcher,
+                    Err(e) => eprintln!("Unable to watch {}: {}", path.display(), e),
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn event(&mut self, event: &Event) {
+        // Also check while the machine is paused, so that the new version is
+        // ready to be stepped through in the debugger.
+        if let Event::Loop(Loop::Update(_)) = event {
+            self.reload_if_changed();
+        }
+        self.controller.event(event);
+    }
+
+    fn display_machine_state(&self) -> String {
+        self.controller.display_machine_state()
+    }
+
+    fn machine_status(&self) -> String {
+        self.controller.machine_status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::CreateKind;
+    use notify::event::DataChange;
+    use notify::event::MetadataKind;
+    use notify::event::RemoveKind;
+    use notify::event::RenameMode;
+
+    #[test]
+    fn recognizes_changes() {
+        let file_name = OsString::from("game.bin");
+        let modify = EventKind::Modify(ModifyKind::Data(DataChange::Any));
+        assert!(is_change_of(
+            &FileEvent::new(modify).add_path(PathBuf::from("./game.bin")),
+            &file_name
+        ));
+        assert!(is_change_of(
+            &FileEvent::new(EventKind::Create(CreateKind::File))
+                .add_path(PathBuf::from("/home/me/game/game.bin")),
+            &file_name
+        ));
+        assert!(!is_change_of(
+            &FileEvent::new(modify).add_path(PathBuf::from("./game.sym")),
+            &file_name
+        ));
+        assert!(!is_change_of(
+            &FileEvent::new(EventKind::Remove(RemoveKind::File))
+                .add_path(PathBuf::from("./game.bin")),
+            &file_name
+        ));
+
+        // Replacing the file by renaming another one counts as a change, but
+        // touching it doesn't.
+        assert!(is_change_of(
+            &FileEvent::new(EventKind::Modify(ModifyKind::Name(RenameMode::To)))
+                .add_path(PathBuf::from("./game.bin")),
+            &file_name
+        ));
+        assert!(!is_change_of(
+            &FileEvent::new(EventKind::Modify(ModifyKind::Metadata(
+                MetadataKind::WriteTime
+            )))
+            .add_path(PathBuf::from("./game.bin")),
+            &file_name
+        ));
+    }
+
+    /// Remembers loaded programs; everything else is a no-op.
+    struct ProgramLoader {
+        image: RgbaImage,
+        programs: Vec<PathBuf>,
+    }
+
+    impl AppController for ProgramLoader {
+        fn frame_image(&self) -> &RgbaImage {
+            &self.image
+        }
+        fn reset(&mut self) {}
+        fn interrupted(&self) -> Arc<AtomicBool> {
+            Arc::new(AtomicBool::new(false))
+        }
+        fn paused(&self) -> bool {
+            false
+        }
+        fn set_paused(&mut self, _: bool) {}
+        fn frame_count(&self) -> u64 {
+            0
+        }
+        fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+            self.programs.push(path.to_path_buf());
+            Ok(())
+        }
+        fn event(&mut self, _: &Event) {}
+        fn display_machine_state(&self) -> String {
+            String::new()
+        }
+        fn machine_status(&self) -> String {
+            String::new()
+        }
+    }
+
+    #[test]
+    fn watches_loaded_programs() {
+        let loader = ProgramLoader {
+            image: RgbaImage::new(1, 1),
+            programs: vec![],
+        };
+        let mut controller = WatchingController::new(loader)
+            .with_watcher(FileWatcher::new(Path::new("game.bin")).unwrap());
+        controller.load_program(Path::new("other.bin")).unwrap();
+        assert_eq!(controller.controller().programs, [Path::new("other.bin")]);
+        assert_eq!(
+            controller.watcher.as_ref().map(FileWatcher::path),
+            Some(Path::new("other.bin"))
+        );
+    }
+
+    #[test]
+    fn waits_until_file_settles() {
+        let start = Instant::now();
+        let mut last_change = None;
+        assert!(!settled(&mut last_change, start));
+
+        last_change = Some(start);
+        assert!(!settled(&mut last_change, start + SETTLE_TIME / 2));
+        assert!(settled(&mut last_change, start + SETTLE_TIME));
+        // Each change is only reported once.
+        assert!(!settled(&mut last_change, start + SETTLE_TIME * 2));
+    }
+}
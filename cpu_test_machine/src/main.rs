@@ -1,12 +1,12 @@
 use clap::Parser;
+use std::fs::File;
+use std::io::BufWriter;
 use std::time::Duration;
 
-use common::{
-    app::CommonCliArguments,
-    debugger::{adapter::TcpDebugAdapter, Debugger},
-};
+use common::app::CommonCliArguments;
+use common::debugger::trace::Tracer;
 use ya6502::{
-    cpu::{Cpu, MachineInspector},
+    cpu::{Cpu, CpuVariant, MachineInspector},
     memory::Ram,
 };
 
@@ -24,30 +24,26 @@ fn main() {
 
     let mut ram = Box::new(Ram::new(16));
     ram.bytes[0x0000..=0xFFFF].copy_from_slice(&test_program);
-    let mut cpu = Cpu::new(ram);
+    let mut cpu = Cpu::with_power_on_state(ram, CpuVariant::Nmos6502, args.common.power_on_state());
+    cpu.set_permissive(args.common.permissive);
     cpu.jump_to(0x400);
 
-    let mut debugger = if args.common.debugger {
-        let mut dbg = Debugger::new(TcpDebugAdapter::new(args.common.debugger_port));
-        if let Err(e) = dbg.update(&cpu) {
+    let mut debugger = args.common.create_debugger();
+    if let Some(debugger) = &mut debugger {
+        if let Err(e) = debugger.update(&cpu) {
             eprintln!("Debugger error: {}", e);
         }
-        Some(dbg)
-    } else {
-        None
-    };
+    }
 
+    let mut tracer = args.common.create_tracer();
     let mut prev_pc = 0;
 
     loop {
         // println!("PC: ${:04X}", cpu.reg_pc());
         if let Some(debugger) = &mut debugger {
-            debugger.process_messages(&cpu);
+            debugger.process_messages(&mut cpu);
             if !debugger.stopped() {
-                if let Err(e) = cpu.tick() {
-                    eprintln!("CPU error: {}", e);
-                    eprintln!("{}", &cpu);
-                }
+                tick(&mut cpu, &mut tracer);
                 if let Err(e) = debugger.update(&cpu) {
                     eprintln!("Debugger error: {}", e);
                 }
@@ -57,14 +53,12 @@ fn main() {
                 std::thread::sleep(Duration::from_millis(10));
             }
         } else {
-            if let Err(e) = cpu.tick() {
-                eprintln!("CPU error: {}", e);
-                eprintln!("{}", &cpu);
-            }
+            tick(&mut cpu, &mut tracer);
             if cpu.at_instruction_start() {
                 let new_pc = cpu.reg_pc();
                 if new_pc == prev_pc {
                     println!("{}", &cpu);
+                    dump_trace(&mut tracer);
                     return;
                 }
                 prev_pc = new_pc;
@@ -72,3 +66,26 @@ fn main() {
         }
     }
 }
+
+/// Performs a single CPU cycle and records it in the trace log, if enabled.
+fn tick(cpu: &mut Cpu<Ram>, tracer: &mut Option<Tracer<BufWriter<File>>>) {
+    let result = cpu.tick();
+    if let Some(tracer) = tracer {
+        if let Err(e) = tracer.update(cpu) {
+            eprintln!("Tracer error: {}", e);
+        }
+    }
+    if let Err(e) = result {
+        eprintln!("CPU error: {}", e);
+        eprintln!("{}", cpu);
+        dump_trace(tracer);
+    }
+}
+
+fn dump_trace(tracer: &mut Option<Tracer<BufWriter<File>>>) {
+    if let Some(tracer) = tracer {
+        if let Err(e) = tracer.dump() {
+            eprintln!("Tracer error: {}", e);
+        }
+    }
+}
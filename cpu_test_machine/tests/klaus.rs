@@ -0,0 +1,85 @@
+//! Runs Klaus Dormann's functional and interrupt tests to completion. The test
+//! binaries aren't included in the repository; see the README file for
+//! instructions on how to get them. The tests take a while, so they only run
+//! with the `klaus-tests` feature enabled.
+
+#![cfg(feature = "klaus-tests")]
+
+use std::env;
+use std::path::PathBuf;
+use ya6502::cpu::Cpu;
+use ya6502::cpu::CpuVariant;
+use ya6502::cpu::MachineInspector;
+use ya6502::memory::Ram;
+
+/// Address of the trap that indicates success of the functional test, as
+/// assembled in the original `bin_files` directory.
+const FUNCTIONAL_TEST_SUCCESS: u16 = 0x3469;
+/// Address of the trap that indicates success of the interrupt test, as
+/// assembled in the original `bin_files` directory.
+const INTERRUPT_TEST_SUCCESS: u16 = 0x06F5;
+
+/// Address of the feedback register that the interrupt test uses to trigger
+/// interrupts. Setting bit 0 asserts IRQ, setting bit 1 asserts NMI.
+const FEEDBACK_PORT: u16 = 0xBFFC;
+const FEEDBACK_IRQ: u8 = 1 << 0;
+const FEEDBACK_NMI: u8 = 1 << 1;
+
+/// Gives up if the test doesn't finish after this many cycles. The functional
+/// test needs about 100 million of them.
+const MAX_CYCLES: u64 = 200_000_000;
+
+/// Loads a test binary from the directory given by the `KLAUS_TESTS_DIR`
+/// environment variable, or from `test_data` by default.
+fn cpu_with_test(file_name: &str) -> Cpu<Ram> {
+    let directory = match env::var_os("KLAUS_TESTS_DIR") {
+        Some(directory) => PathBuf::from(directory),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_data"),
+    };
+    let path = directory.join(file_name);
+    let test_program =
+        std::fs::read(&path).unwrap_or_else(|e| panic!("Unable to read {}: {}", path.display(), e));
+    let mut ram = Box::new(Ram::new(16));
+    ram.bytes.copy_from_slice(&test_program);
+    let mut cpu = Cpu::with_variant(ram, CpuVariant::Nmos6502);
+    cpu.jump_to(0x400);
+    return cpu;
+}
+
+/// Runs the test until it reaches a trap (an instruction that jumps to itself)
+/// and panics unless it's the one at the `success` address. If `feedback` is
+/// set, the interrupt pins are driven by the feedback register.
+fn run_until_success(cpu: &mut Cpu<Ram>, success: u16, feedback: bool) {
+    let mut prev_pc = None;
+    while cpu.cycles() < MAX_CYCLES {
+        if let Err(e) = cpu.tick() {
+            panic!("CPU error: {}\n{}", e, cpu);
+        }
+        if feedback {
+            let port = cpu.memory().bytes[FEEDBACK_PORT as usize];
+            cpu.set_irq_pin(port & FEEDBACK_IRQ != 0);
+            cpu.set_nmi_pin(port & FEEDBACK_NMI != 0);
+        }
+        if cpu.at_instruction_start() {
+            let pc = cpu.reg_pc();
+            if prev_pc == Some(pc) {
+                assert!(pc == success, "Trapped at ${:04X}\n{}", pc, cpu);
+                return;
+            }
+            prev_pc = Some(pc);
+        }
+    }
+    panic!("Test timed out\n{}", cpu);
+}
+
+#[test]
+fn functional_test() {
+    let mut cpu = cpu_with_test("6502_functional_test.bin");
+    run_until_success(&mut cpu, FUNCTIONAL_TEST_SUCCESS, false);
+}
+
+#[test]
+fn interrupt_test() {
+    let mut cpu = cpu_with_test("6502_interrupt_test.bin");
+    run_until_success(&mut cpu, INTERRUPT_TEST_SUCCESS, true);
+}
@@ -0,0 +1,88 @@
+/*
+ * C interface to the Steampunk emulators. Link with the library built from
+ * the `steampunk-ffi` crate (libsteampunk.a or libsteampunk.so). See the Rust
+ * documentation of the crate for details on each function.
+ */
+
+#ifndef STEAMPUNK_H
+#define STEAMPUNK_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct SteampunkMachine SteampunkMachine;
+
+typedef enum {
+  STEAMPUNK_ATARI2600_NTSC = 0,
+  STEAMPUNK_ATARI2600_PAL = 1,
+  STEAMPUNK_ATARI2600_SECAM = 2,
+  STEAMPUNK_C64 = 3,
+} SteampunkMachineType;
+
+typedef enum {
+  STEAMPUNK_OK = 0,
+  STEAMPUNK_NULL_POINTER = 1,
+  STEAMPUNK_INVALID_ARGUMENT = 2,
+  STEAMPUNK_INVALID_ROM = 3,
+  STEAMPUNK_NO_ROM = 4,
+  STEAMPUNK_UNSUPPORTED = 5,
+  STEAMPUNK_MACHINE_HALTED = 6,
+  STEAMPUNK_INTERNAL_ERROR = 7,
+} SteampunkResult;
+
+typedef enum {
+  STEAMPUNK_JOYSTICK_UP = 0,
+  STEAMPUNK_JOYSTICK_DOWN = 1,
+  STEAMPUNK_JOYSTICK_LEFT = 2,
+  STEAMPUNK_JOYSTICK_RIGHT = 3,
+  STEAMPUNK_JOYSTICK_FIRE = 4,
+} SteampunkJoystickInput;
+
+typedef enum {
+  STEAMPUNK_ATARI_TV_TYPE = 0,
+  STEAMPUNK_ATARI_LEFT_DIFFICULTY = 1,
+  STEAMPUNK_ATARI_RIGHT_DIFFICULTY = 2,
+  STEAMPUNK_ATARI_GAME_SELECT = 3,
+  STEAMPUNK_ATARI_GAME_RESET = 4,
+} SteampunkAtariSwitch;
+
+/* Returns NULL if the machine can't be created. */
+SteampunkMachine *steampunk_machine_new(unsigned int machine_type);
+void steampunk_machine_free(SteampunkMachine *machine);
+
+SteampunkResult steampunk_machine_load_rom(SteampunkMachine *machine,
+                                           const uint8_t *data, size_t size);
+SteampunkResult steampunk_machine_reset(SteampunkMachine *machine);
+SteampunkResult steampunk_machine_run_frame(SteampunkMachine *machine);
+
+/*
+ * Returns RGBA pixels of the last frame, or NULL if there's none. Valid until
+ * the next call that takes a non-const machine pointer.
+ */
+const uint8_t *steampunk_machine_frame_buffer(const SteampunkMachine *machine,
+                                              uint32_t *width,
+                                              uint32_t *height);
+
+SteampunkResult steampunk_machine_set_joystick(SteampunkMachine *machine,
+                                               unsigned int port,
+                                               unsigned int input,
+                                               bool pressed);
+SteampunkResult steampunk_atari2600_set_switch(SteampunkMachine *machine,
+                                               unsigned int switch_id,
+                                               bool down);
+SteampunkResult steampunk_c64_set_key(SteampunkMachine *machine,
+                                      const char *key, bool pressed);
+
+/* Describes the last error; empty if the last operation succeeded. */
+const char *steampunk_machine_last_error(const SteampunkMachine *machine);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* STEAMPUNK_H */
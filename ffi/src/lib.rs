@@ -0,0 +1,635 @@
+//! A C-compatible interface to the emulated machines, for embedding them in
+//! frontends that aren't written in Rust, like libretro shells. The C
+//! declarations live in `include/steampunk.h`.
+//!
+//! Machines are opaque objects created with [`steampunk_machine_new`] and
+//! owned by the caller until passed to [`steampunk_machine_free`]. Enumerated
+//! arguments are passed as plain integers and validated, so that an
+//! out-of-range value coming from C results in an error instead of undefined
+//! behavior. For the same reason, panics never cross the boundary: they are
+//! reported as [`SteampunkResult::InternalError`], and the machine that caused
+//! them should be freed, since its state is no longer reliable.
+
+use atari2600::address_space::Cartridge as AtariCartridge;
+use atari2600::tv_standard::TvStandard;
+use atari2600::JoystickInput as AtariJoystickInput;
+use atari2600::JoystickPort as AtariJoystickPort;
+use atari2600::{Atari, AtariBuilder, Switch, SwitchPosition};
+use c64::cartridge::read_crt_file;
+use c64::cartridge::Cartridge as C64Cartridge;
+use c64::cartridge::CartridgeMode;
+use c64::cartridge::CRT_SIGNATURE;
+use c64::joystick::JoystickInput as C64JoystickInput;
+use c64::joystick::JoystickPort as C64JoystickPort;
+use c64::keyboard::{Key, KeyState};
+use c64::{C64Builder, C64};
+use common::app::Machine;
+use serde::de::value::StrDeserializer;
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use std::any::Any;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_uint};
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::ptr;
+use std::slice;
+use thiserror::Error;
+
+/// Machines that can be created with [`steampunk_machine_new`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SteampunkMachineType {
+    Atari2600Ntsc = 0,
+    Atari2600Pal = 1,
+    Atari2600Secam = 2,
+    C64 = 3,
+}
+
+impl SteampunkMachineType {
+    fn from_raw(value: c_uint) -> Option<Self> {
+        match value {
+            0 => Some(Self::Atari2600Ntsc),
+            1 => Some(Self::Atari2600Pal),
+            2 => Some(Self::Atari2600Secam),
+            3 => Some(Self::C64),
+            _ => None,
+        }
+    }
+}
+
+/// Outcome of an operation. On failure, [`steampunk_machine_last_error`]
+/// returns a detailed description.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SteampunkResult {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidArgument = 2,
+    InvalidRom = 3,
+    NoRom = 4,
+    Unsupported = 5,
+    MachineHalted = 6,
+    InternalError = 7,
+}
+
+/// Joystick inputs, as accepted by [`steampunk_machine_set_joystick`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SteampunkJoystickInput {
+    Up = 0,
+    Down = 1,
+    Left = 2,
+    Right = 3,
+    Fire = 4,
+}
+
+/// Atari 2600 console switches, as accepted by
+/// [`steampunk_atari2600_set_switch`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SteampunkAtariSwitch {
+    TvType = 0,
+    LeftDifficulty = 1,
+    RightDifficulty = 2,
+    GameSelect = 3,
+    GameReset = 4,
+}
+
+#[derive(Error, Debug)]
+enum FfiError {
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Invalid ROM: {0}")]
+    InvalidRom(String),
+    #[error("No ROM loaded")]
+    NoRom,
+    #[error("Not supported by this machine: {0}")]
+    Unsupported(&'static str),
+    #[error("Machine halted: {0}")]
+    MachineHalted(String),
+    #[error("Internal error: {0}")]
+    InternalError(String),
+}
+
+impl FfiError {
+    fn result(&self) -> SteampunkResult {
+        match self {
+            Self::InvalidArgument(_) => SteampunkResult::InvalidArgument,
+            Self::InvalidRom(_) => SteampunkResult::InvalidRom,
+            Self::NoRom => SteampunkResult::NoRom,
+            Self::Unsupported(_) => SteampunkResult::Unsupported,
+            Self::MachineHalted(_) => SteampunkResult::MachineHalted,
+            Self::InternalError(_) => SteampunkResult::InternalError,
+        }
+    }
+}
+
+enum Emulated {
+    /// An Atari 2600 console. It can't run without a cartridge, so it's only
+    /// created once a ROM is loaded.
+    Atari2600(TvStandard, Option<Atari>),
+    C64(C64),
+}
+
+/// An emulated machine, opaque to C code.
+pub struct SteampunkMachine {
+    emulated: Emulated,
+    last_error: CString,
+}
+
+impl SteampunkMachine {
+    fn machine(&self) -> Result<&dyn Machine, FfiError> {
+        match &self.emulated {
+            Emulated::Atari2600(_, Some(atari)) => Ok(atari),
+            Emulated::Atari2600(_, None) => Err(FfiError::NoRom),
+            Emulated::C64(c64) => Ok(c64),
+        }
+    }
+
+    fn mut_machine(&mut self) -> Result<&mut dyn Machine, FfiError> {
+        match &mut self.emulated {
+            Emulated::Atari2600(_, Some(atari)) => Ok(atari),
+            Emulated::Atari2600(_, None) => Err(FfiError::NoRom),
+            Emulated::C64(c64) => Ok(c64),
+        }
+    }
+
+    fn load_rom(&mut self, data: &[u8]) -> Result<(), FfiError> {
+        match &mut self.emulated {
+            Emulated::Atari2600(tv_standard, atari) => {
+                let cartridge = AtariCartridge::with_detected_bank_switching(data)
+                    .map_err(|e| FfiError::InvalidRom(e.to_string()))?;
+                let mut new_atari = AtariBuilder::new(cartridge)
+                    .with_tv_standard(*tv_standard)
+                    .build();
+                new_atari.reset();
+                *atari = Some(new_atari);
+            }
+            Emulated::C64(c64) => {
+                let cartridge = if data.starts_with(CRT_SIGNATURE) {
+                    read_crt_file(data)
+                } else {
+                    C64Cartridge::from_raw_image(CartridgeMode::Ultimax, data)
+                };
+                let cartridge = cartridge.map_err(|e| FfiError::InvalidRom(e.to_string()))?;
+                c64.set_cartridge(Some(Box::new(cartridge)));
+                c64.reset();
+            }
+        }
+        return Ok(());
+    }
+
+    fn run_frame(&mut self) -> Result<(), FfiError> {
+        self.mut_machine()?
+            .run_frame()
+            .map(|_| ())
+            .map_err(|e| FfiError::MachineHalted(e.to_string()))
+    }
+
+    fn set_joystick(&mut self, port: c_uint, input: c_uint, pressed: bool) -> Result<(), FfiError> {
+        let input = match input {
+            0 => SteampunkJoystickInput::Up,
+            1 => SteampunkJoystickInput::Down,
+            2 => SteampunkJoystickInput::Left,
+            3 => SteampunkJoystickInput::Right,
+            4 => SteampunkJoystickInput::Fire,
+            _ => return Err(invalid_value("joystick input", input)),
+        };
+        match &mut self.emulated {
+            Emulated::Atari2600(_, None) => return Err(FfiError::NoRom),
+            Emulated::Atari2600(_, Some(atari)) => {
+                let port = match port {
+                    0 => AtariJoystickPort::Left,
+                    1 => AtariJoystickPort::Right,
+                    _ => return Err(invalid_value("joystick port", port)),
+                };
+                let input = match input {
+                    SteampunkJoystickInput::Up => AtariJoystickInput::Up,
+                    SteampunkJoystickInput::Down => AtariJoystickInput::Down,
+                    SteampunkJoystickInput::Left => AtariJoystickInput::Left,
+                    SteampunkJoystickInput::Right => AtariJoystickInput::Right,
+                    SteampunkJoystickInput::Fire => AtariJoystickInput::Fire,
+                };
+                atari.set_joystick_input_state(port, input, pressed);
+            }
+            Emulated::C64(c64) => {
+                let port = match port {
+                    0 => C64JoystickPort::Port1,
+                    1 => C64JoystickPort::Port2,
+                    _ => return Err(invalid_value("joystick port", port)),
+                };
+                let input = match input {
+                    SteampunkJoystickInput::Up => C64JoystickInput::Up,
+                    SteampunkJoystickInput::Down => C64JoystickInput::Down,
+                    SteampunkJoystickInput::Left => C64JoystickInput::Left,
+                    SteampunkJoystickInput::Right => C64JoystickInput::Right,
+                    SteampunkJoystickInput::Fire => C64JoystickInput::Fire,
+                };
+                c64.set_joystick_input_state(port, input, pressed);
+            }
+        }
+        return Ok(());
+    }
+
+    fn set_atari_switch(&mut self, switch: c_uint, down: bool) -> Result<(), FfiError> {
+        let switch = match switch {
+            0 => Switch::TvType,
+            1 => Switch::LeftDifficulty,
+            2 => Switch::RightDifficulty,
+            3 => Switch::GameSelect,
+            4 => Switch::GameReset,
+            _ => return Err(invalid_value("console switch", switch)),
+        };
+        let position = if down {
+            SwitchPosition::Down
+        } else {
+            SwitchPosition::Up
+        };
+        match &mut self.emulated {
+            Emulated::Atari2600(_, Some(atari)) => atari.flip_switch(switch, position),
+            Emulated::Atari2600(_, None) => return Err(FfiError::NoRom),
+            Emulated::C64(_) => return Err(FfiError::Unsupported("console switches")),
+        }
+        return Ok(());
+    }
+
+    fn set_c64_key(&mut self, name: &str, pressed: bool) -> Result<(), FfiError> {
+        let c64 = match &mut self.emulated {
+            Emulated::C64(c64) => c64,
+            _ => return Err(FfiError::Unsupported("C64 keyboard")),
+        };
+        let deserializer: StrDeserializer<serde::de::value::Error> = name.into_deserializer();
+        let key = Key::deserialize(deserializer)
+            .map_err(|_| FfiError::InvalidArgument(format!("unknown key: {}", name)))?;
+        let state = if pressed {
+            KeyState::Pressed
+        } else {
+            KeyState::Released
+        };
+        c64.set_key_state(key, state);
+        return Ok(());
+    }
+}
+
+fn invalid_value(what: &str, value: c_uint) -> FfiError {
+    FfiError::InvalidArgument(format!("invalid {}: {}", what, value))
+}
+
+/// Calls a function and stops a panic from unwinding into the C code, which
+/// would be undefined behavior. Returns the panic message on failure.
+fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    return panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| panic_message(&*payload));
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return message.to_string();
+    }
+    if let Some(message) = payload.downcast_ref::<String>() {
+        return message.clone();
+    }
+    return "unknown panic".to_string();
+}
+
+/// Performs an operation on a machine and records its error, if any.
+unsafe fn with_machine(
+    machine: *mut SteampunkMachine,
+    operation: impl FnOnce(&mut SteampunkMachine) -> Result<(), FfiError>,
+) -> SteampunkResult {
+    let machine = match machine.as_mut() {
+        Some(machine) => machine,
+        None => return SteampunkResult::NullPointer,
+    };
+    let result = catch_panic(|| operation(machine))
+        .unwrap_or_else(|message| Err(FfiError::InternalError(message)));
+    return match result {
+        Ok(()) => {
+            machine.last_error = CString::default();
+            SteampunkResult::Ok
+        }
+        Err(e) => {
+            // Error messages never contain null characters, but better safe
+            // than sorry.
+            machine.last_error = CString::new(e.to_string()).unwrap_or_default();
+            e.result()
+        }
+    };
+}
+
+/// Creates a machine of a given type (see [`SteampunkMachineType`]). Returns
+/// null if the type is invalid or the machine can't be created. An Atari 2600
+/// can only run after a ROM is loaded; a C64 is ready to run right away.
+#[no_mangle]
+pub extern "C" fn steampunk_machine_new(machine_type: c_uint) -> *mut SteampunkMachine {
+    return catch_panic(|| new_machine(machine_type)).unwrap_or(ptr::null_mut());
+}
+
+fn new_machine(machine_type: c_uint) -> *mut SteampunkMachine {
+    let emulated = match SteampunkMachineType::from_raw(machine_type) {
+        Some(SteampunkMachineType::Atari2600Ntsc) => Emulated::Atari2600(TvStandard::Ntsc, None),
+        Some(SteampunkMachineType::Atari2600Pal) => Emulated::Atari2600(TvStandard::Pal, None),
+        Some(SteampunkMachineType::Atari2600Secam) => Emulated::Atari2600(TvStandard::Secam, None),
+        Some(SteampunkMachineType::C64) => match C64Builder::new().build() {
+            Ok(mut c64) => {
+                c64.reset();
+                Emulated::C64(c64)
+            }
+            Err(_) => return ptr::null_mut(),
+        },
+        None => return ptr::null_mut(),
+    };
+    return Box::into_raw(Box::new(SteampunkMachine {
+        emulated,
+        last_error: CString::default(),
+    }));
+}
+
+/// Destroys a machine created with [`steampunk_machine_new`]. Does nothing if
+/// `machine` is null.
+///
+/// # Safety
+///
+/// `machine` must be null or a pointer returned by [`steampunk_machine_new`]
+/// that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn steampunk_machine_free(machine: *mut SteampunkMachine) {
+    if !machine.is_null() {
+        // There's no way to report an error here, and the memory is lost
+        // anyway, so a panic is simply ignored.
+        let _ = catch_panic(|| drop(Box::from_raw(machine)));
+    }
+}
+
+/// Loads a ROM and resets the machine. For an Atari 2600, it's a cartridge
+/// image; the bank switching scheme is detected automatically. For a C64, it's
+/// a cartridge in the CRT format or a raw Ultimax cartridge image.
+///
+/// # Safety
+///
+/// `machine` must be null or a valid machine pointer, and `data` must be null
+/// or point to `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn steampunk_machine_load_rom(
+    machine: *mut SteampunkMachine,
+    data: *const u8,
+    size: usize,
+) -> SteampunkResult {
+    if data.is_null() {
+        return SteampunkResult::NullPointer;
+    }
+    return with_machine(machine, |machine| {
+        machine.load_rom(slice::from_raw_parts(data, size))
+    });
+}
+
+/// Resets the machine, as if the reset button was pressed.
+///
+/// # Safety
+///
+/// `machine` must be null or a valid machine pointer.
+#[no_mangle]
+pub unsafe extern "C" fn steampunk_machine_reset(
+    machine: *mut SteampunkMachine,
+) -> SteampunkResult {
+    return with_machine(machine, |machine| {
+        machine.mut_machine()?.reset();
+        Ok(())
+    });
+}
+
+/// Emulates the machine until a frame is complete.
+///
+/// # Safety
+///
+/// `machine` must be null or a valid machine pointer.
+#[no_mangle]
+pub unsafe extern "C" fn steampunk_machine_run_frame(
+    machine: *mut SteampunkMachine,
+) -> SteampunkResult {
+    return with_machine(machine, SteampunkMachine::run_frame);
+}
+
+/// Returns a pointer to the last frame, stored as RGBA pixels, 4 bytes per
+/// pixel, row by row, with no padding. The frame dimensions are stored in
+/// `width` and `height`, unless they are null. Returns null if there's no
+/// frame to show. The pointer stays valid until the next call that takes a
+/// mutable machine pointer.
+///
+/// # Safety
+///
+/// `machine` must be null or a valid machine pointer; `width` and `height`
+/// must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn steampunk_machine_frame_buffer(
+    machine: *const SteampunkMachine,
+    width: *mut u32,
+    height: *mut u32,
+) -> *const u8 {
+    return catch_panic(|| {
+        let frame = match machine.as_ref().map(|machine| machine.machine()) {
+            Some(Ok(machine)) => machine.frame_image(),
+            _ => return ptr::null(),
+        };
+        if let Some(width) = width.as_mut() {
+            *width = frame.width();
+        }
+        if let Some(height) = height.as_mut() {
+            *height = frame.height();
+        }
+        return frame.as_ptr();
+    })
+    .unwrap_or(ptr::null());
+}
+
+/// Presses or releases a joystick input (see [`SteampunkJoystickInput`]).
+/// Ports are numbered from 0: on an Atari 2600, 0 is the left port, and on a
+/// C64, 0 is control port 1.
+///
+/// # Safety
+///
+/// `machine` must be null or a valid machine pointer.
+#[no_mangle]
+pub unsafe extern "C" fn steampunk_machine_set_joystick(
+    machine: *mut SteampunkMachine,
+    port: c_uint,
+    input: c_uint,
+    pressed: bool,
+) -> SteampunkResult {
+    return with_machine(machine, |machine| {
+        machine.set_joystick(port, input, pressed)
+    });
+}
+
+/// Moves an Atari 2600 console switch (see [`SteampunkAtariSwitch`]) down or
+/// up. For the difficulty switches, down means "B", and for the TV type
+/// switch, it means black and white.
+///
+/// # Safety
+///
+/// `machine` must be null or a valid machine pointer.
+#[no_mangle]
+pub unsafe extern "C" fn steampunk_atari2600_set_switch(
+    machine: *mut SteampunkMachine,
+    switch: c_uint,
+    down: bool,
+) -> SteampunkResult {
+    return with_machine(machine, |machine| machine.set_atari_switch(switch, down));
+}
+
+/// Presses or releases a C64 key. Keys are identified by the same names as in
+/// the C64 keymap files, e.g. `"A"`, `"D1"`, `"Space"`, or `"RunStop"`.
+///
+/// # Safety
+///
+/// `machine` must be null or a valid machine pointer; `key` must be null or a
+/// valid, null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn steampunk_c64_set_key(
+    machine: *mut SteampunkMachine,
+    key: *const c_char,
+    pressed: bool,
+) -> SteampunkResult {
+    if key.is_null() {
+        return SteampunkResult::NullPointer;
+    }
+    return with_machine(machine, |machine| {
+        machine.set_c64_key(&CStr::from_ptr(key).to_string_lossy(), pressed)
+    });
+}
+
+/// Returns a description of the error reported by the last failed operation
+/// on a given machine, or an empty string if the last operation succeeded.
+/// The string stays valid until the next call that takes a mutable machine
+/// pointer.
+///
+/// # Safety
+///
+/// `machine` must be null or a valid machine pointer.
+#[no_mangle]
+pub unsafe extern "C" fn steampunk_machine_last_error(
+    machine: *const SteampunkMachine,
+) -> *const c_char {
+    return catch_panic(|| match machine.as_ref() {
+        Some(machine) => machine.last_error.as_ptr(),
+        None => ptr::null(),
+    })
+    .unwrap_or(ptr::null());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_invalid_machine_type() {
+        assert!(steampunk_machine_new(42).is_null());
+    }
+
+    #[test]
+    fn handles_null_pointers() {
+        unsafe {
+            assert_eq!(
+                steampunk_machine_run_frame(ptr::null_mut()),
+                SteampunkResult::NullPointer
+            );
+            assert!(
+                steampunk_machine_frame_buffer(ptr::null(), ptr::null_mut(), ptr::null_mut())
+                    .is_null()
+            );
+            steampunk_machine_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn reports_panics() {
+        unsafe {
+            let machine = steampunk_machine_new(SteampunkMachineType::C64 as c_uint);
+            assert_eq!(
+                with_machine(machine, |_| panic!("Oops")),
+                SteampunkResult::InternalError
+            );
+            assert_eq!(
+                CStr::from_ptr(steampunk_machine_last_error(machine)).to_str(),
+                Ok("Internal error: Oops")
+            );
+            assert_eq!(
+                with_machine(machine, |_| panic!("Oops: {}", 42)),
+                SteampunkResult::InternalError
+            );
+            assert_eq!(
+                CStr::from_ptr(steampunk_machine_last_error(machine)).to_str(),
+                Ok("Internal error: Oops: 42")
+            );
+            steampunk_machine_free(machine);
+        }
+    }
+
+    #[test]
+    fn atari_requires_rom() {
+        unsafe {
+            let machine = steampunk_machine_new(SteampunkMachineType::Atari2600Pal as c_uint);
+            assert_eq!(steampunk_machine_run_frame(machine), SteampunkResult::NoRom);
+            assert_eq!(
+                CStr::from_ptr(steampunk_machine_last_error(machine)).to_str(),
+                Ok("No ROM loaded")
+            );
+            assert!(
+                steampunk_machine_frame_buffer(machine, ptr::null_mut(), ptr::null_mut()).is_null()
+            );
+
+            let rom = [0u8; 3];
+            assert_eq!(
+                steampunk_machine_load_rom(machine, rom.as_ptr(), rom.len()),
+                SteampunkResult::InvalidRom
+            );
+            steampunk_machine_free(machine);
+        }
+    }
+
+    #[test]
+    fn runs_c64() {
+        unsafe {
+            let machine = steampunk_machine_new(SteampunkMachineType::C64 as c_uint);
+            assert!(!machine.is_null());
+            assert_eq!(steampunk_machine_run_frame(machine), SteampunkResult::Ok);
+            assert_eq!(
+                steampunk_machine_set_joystick(
+                    machine,
+                    1,
+                    SteampunkJoystickInput::Fire as c_uint,
+                    true
+                ),
+                SteampunkResult::Ok
+            );
+            assert_eq!(
+                steampunk_machine_set_joystick(
+                    machine,
+                    2,
+                    SteampunkJoystickInput::Fire as c_uint,
+                    true
+                ),
+                SteampunkResult::InvalidArgument
+            );
+            let key = CString::new("Space").unwrap();
+            assert_eq!(
+                steampunk_c64_set_key(machine, key.as_ptr(), true),
+                SteampunkResult::Ok
+            );
+            assert_eq!(
+                steampunk_atari2600_set_switch(
+                    machine,
+                    SteampunkAtariSwitch::GameReset as c_uint,
+                    true
+                ),
+                SteampunkResult::Unsupported
+            );
+
+            let (mut width, mut height) = (0, 0);
+            let frame = steampunk_machine_frame_buffer(machine, &mut width, &mut height);
+            assert!(!frame.is_null());
+            assert!(width > 0 && height > 0);
+            steampunk_machine_free(machine);
+        }
+    }
+}
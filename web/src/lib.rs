@@ -0,0 +1,158 @@
+//! Runs the Atari 2600 emulator in a web browser. This crate only exposes the
+//! machine to JavaScript; the page (see `index.html`) is responsible for
+//! scheduling frames, forwarding keyboard events, and playing the audio.
+
+use atari2600::address_space::Cartridge;
+use atari2600::atari::{Atari, AtariBuilder, AUDIO_SAMPLE_RATE};
+use atari2600::keymap::Keymap;
+use atari2600::tv_standard::TvStandard;
+use clap::ArgEnum;
+use common::app::Machine;
+use common::audio::{create_consumer_and_source, AudioSource, OUTPUT_SAMPLE_RATE};
+use common::keymap::parse_key;
+use piston::Key;
+use std::time::Duration;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::Clamped;
+use web_sys::{CanvasRenderingContext2d, ImageData};
+
+/// Audio latency. Browsers request audio in fairly large chunks, so it needs
+/// to be higher than on desktop.
+const AUDIO_LATENCY: Duration = Duration::from_millis(100);
+
+#[wasm_bindgen]
+pub struct AtariEmulator {
+    atari: Atari,
+    tv_standard: TvStandard,
+    audio_source: AudioSource,
+    keymap: Keymap,
+}
+
+#[wasm_bindgen]
+impl AtariEmulator {
+    /// Creates an emulator running a given cartridge image. The TV standard is
+    /// one of `ntsc`, `pal`, or `secam`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8], tv_standard: &str) -> Result<AtariEmulator, JsValue> {
+        let tv_standard =
+            TvStandard::from_str(tv_standard, true).map_err(|e| JsValue::from_str(&e))?;
+        let cartridge = Cartridge::with_detected_bank_switching(rom)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let (audio_consumer, audio_source) =
+            create_consumer_and_source(AUDIO_SAMPLE_RATE, AUDIO_LATENCY);
+        let mut atari = AtariBuilder::new(cartridge)
+            .with_tv_standard(tv_standard)
+            .with_audio_consumer(audio_consumer)
+            .build();
+        atari.reset();
+        return Ok(AtariEmulator {
+            atari,
+            tv_standard,
+            audio_source,
+            keymap: Keymap::default(),
+        });
+    }
+
+    /// Number of frames that should be emulated per second.
+    pub fn frame_rate(&self) -> u32 {
+        self.tv_standard.frame_rate() as u32
+    }
+
+    /// Emulates the machine until a frame is complete.
+    pub fn run_frame(&mut self) -> Result<(), JsValue> {
+        self.atari
+            .run_frame()
+            .map(|_| ())
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Draws the last complete frame on a canvas. The canvas is resized to
+    /// match the frame, one canvas pixel per frame pixel; scaling it up is up
+    /// to the page.
+    pub fn draw(&self, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+        let frame = self.atari.frame_image();
+        let (width, height) = frame.dimensions();
+        if let Some(canvas) = context.canvas() {
+            if canvas.width() != width || canvas.height() != height {
+                canvas.set_width(width);
+                canvas.set_height(height);
+            }
+        }
+        let image_data = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(frame.as_raw().as_slice()),
+            width,
+            height,
+        )?;
+        return context.put_image_data(&image_data, 0.0, 0.0);
+    }
+
+    /// Handles a key being pressed or released, using the default keymap.
+    /// Keys are identified by the `code` property of JavaScript keyboard
+    /// events. Returns `true` if the key is mapped to an action, so that the
+    /// page can suppress its default behavior.
+    pub fn key_event(&mut self, code: &str, pressed: bool) -> bool {
+        let action = key_from_code(code).and_then(|key| self.keymap.action(key));
+        if let Some(action) = action {
+            action.perform(&mut self.atari, pressed);
+        }
+        return action.is_some();
+    }
+
+    /// Sample rate of the audio returned by [`AtariEmulator::audio_samples`].
+    pub fn audio_sample_rate() -> u32 {
+        OUTPUT_SAMPLE_RATE
+    }
+
+    /// Returns a given number of mono audio samples to be played next. Both
+    /// stereo channels are mixed together.
+    pub fn audio_samples(&mut self, count: usize) -> Vec<f32> {
+        (0..count)
+            .map(|_| {
+                let sample = self.audio_source.next_sample();
+                (sample.left + sample.right) / 2.0
+            })
+            .collect()
+    }
+}
+
+/// Translates the `code` property of a JavaScript keyboard event to a Piston
+/// key. Returns `None` for keys that Piston doesn't know.
+fn key_from_code(code: &str) -> Option<Key> {
+    let name = if let Some(letter) = code.strip_prefix("Key") {
+        letter.to_string()
+    } else if let Some(digit) = code.strip_prefix("Digit") {
+        format!("D{}", digit)
+    } else if let Some(arrow) = code.strip_prefix("Arrow") {
+        arrow.to_string()
+    } else {
+        match code {
+            "Enter" => "Return",
+            "ShiftLeft" => "LShift",
+            "ShiftRight" => "RShift",
+            "ControlLeft" => "LCtrl",
+            "ControlRight" => "RCtrl",
+            "AltLeft" => "LAlt",
+            "AltRight" => "RAlt",
+            other => other,
+        }
+        .to_string()
+    };
+    return parse_key(&name).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_key_codes() {
+        assert_eq!(key_from_code("KeyW"), Some(Key::W));
+        assert_eq!(key_from_code("Digit1"), Some(Key::D1));
+        assert_eq!(key_from_code("ArrowUp"), Some(Key::Up));
+        assert_eq!(key_from_code("ShiftLeft"), Some(Key::LShift));
+        assert_eq!(key_from_code("Space"), Some(Key::Space));
+        assert_eq!(key_from_code("F5"), Some(Key::F5));
+        assert_eq!(key_from_code("Enter"), Some(Key::Return));
+        assert_eq!(key_from_code("IntlBackslash"), None);
+    }
+}
@@ -0,0 +1,98 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use ya6502::cpu::opcodes;
+use ya6502::cpu::Cpu;
+use ya6502::memory::Inspect;
+use ya6502::memory::Memory;
+use ya6502::memory::Ram;
+use ya6502::memory::Read;
+use ya6502::memory::ReadResult;
+use ya6502::memory::Write;
+use ya6502::memory::WriteError;
+use ya6502::memory::WriteResult;
+use ya6502::test_utils::{cpu_with_program, reset};
+
+fn cpu_ticks(c: &mut Criterion) {
+    // The same program as the one used by the CPU tests' benchmark: a loop
+    // that mixes implied, immediate, zero page, and indexed instructions.
+    let mut cpu = cpu_with_program(&[
+        opcodes::CLC,
+        opcodes::CLD,
+        opcodes::LDX_IMM,
+        1,
+        opcodes::LDA_IMM,
+        42,
+        // loop:
+        opcodes::STA_ZP_X,
+        0,
+        opcodes::ADC_IMM,
+        64,
+        opcodes::ASL_ZP,
+        1,
+        opcodes::LSR_ZP,
+        2,
+        opcodes::INX,
+        opcodes::JMP_ABS,
+        0x06,
+        0xF0,
+    ]);
+    c.bench_function("cpu_1000_ticks", |b| {
+        b.iter(|| {
+            reset(&mut cpu);
+            cpu.ticks(1000).unwrap();
+        })
+    });
+}
+
+/// RAM that rejects all writes to the upper half of the address space.
+#[derive(Debug)]
+struct HalfReadOnlyRam(Ram);
+
+impl Read for HalfReadOnlyRam {
+    fn read(&mut self, address: u16) -> ReadResult {
+        self.0.read(address)
+    }
+}
+
+impl Inspect for HalfReadOnlyRam {
+    fn inspect(&self, address: u16) -> ReadResult {
+        self.0.inspect(address)
+    }
+}
+
+impl Write for HalfReadOnlyRam {
+    fn write(&mut self, address: u16, value: u8) -> WriteResult {
+        if address >= 0x8000 {
+            return Err(WriteError { address, value });
+        }
+        self.0.write(address, value)
+    }
+}
+
+impl Memory for HalfReadOnlyRam {}
+
+fn cpu_ticks_with_ignored_errors(c: &mut Criterion) {
+    // A loop that keeps writing to ROM, which is ignored in the permissive
+    // mode.
+    let mut cpu = Cpu::new(Box::new(HalfReadOnlyRam(Ram::with_test_program(&[
+        opcodes::LDX_IMM,
+        0,
+        // loop:
+        opcodes::STA_ABS_X,
+        0x00,
+        0x90,
+        opcodes::INX,
+        opcodes::JMP_ABS,
+        0x02,
+        0xF0,
+    ]))));
+    cpu.set_permissive(true);
+    c.bench_function("cpu_1000_ticks_with_ignored_errors", |b| {
+        b.iter(|| {
+            reset(&mut cpu);
+            cpu.ticks(1000).unwrap();
+        })
+    });
+}
+
+criterion_group!(benches, cpu_ticks, cpu_ticks_with_ignored_errors);
+criterion_main!(benches);
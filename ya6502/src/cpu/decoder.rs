@@ -0,0 +1,640 @@
+//! Instruction decoding. Every opcode is translated to a descriptor that
+//! consists of a bus access pattern with an addressing mode, which determine
+//! what happens during each cycle of the instruction, and an operation
+//! performed on the data. The descriptor table is generated at compile time.
+
+use super::flags;
+use super::opcodes;
+use super::CpuVariant;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressingMode {
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    XIndirect,
+    IndirectY,
+    /// 65C02 only.
+    ZeroPageIndirect,
+}
+
+/// Operations that only affect registers and take a single cycle after
+/// fetching the opcode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InternalOperation {
+    Nop,
+    Inx,
+    Iny,
+    Dex,
+    Dey,
+    Tax,
+    Tay,
+    Txa,
+    Tya,
+    Txs,
+    Tsx,
+    Sei,
+    Cli,
+    Sed,
+    Cld,
+    Sec,
+    Clc,
+    Clv,
+    AslA,
+    LsrA,
+    RolA,
+    RorA,
+    // 65C02 operations.
+    IncA,
+    DecA,
+}
+
+/// Operations that consume a byte read from the memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReadOperation {
+    Lda,
+    Ldx,
+    Ldy,
+    And,
+    Ora,
+    Eor,
+    Adc,
+    Sbc,
+    Cmp,
+    Cpx,
+    Cpy,
+    Bit,
+    Nop,
+    // 65C02 operations.
+    /// Unlike other BIT instructions, BIT #imm only affects the Z flag.
+    BitImmediate,
+    // Unofficial operations.
+    Lax,
+    Anc,
+    Alr,
+    Arr,
+    Axs,
+}
+
+/// Operations that store a byte in the memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WriteOperation {
+    Sta,
+    Stx,
+    Sty,
+    // 65C02 operations.
+    Stz,
+    // Unofficial operations.
+    Sax,
+}
+
+/// Operations that read a byte from the memory, modify it, and store the
+/// result at the same address.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModifyOperation {
+    Asl,
+    Lsr,
+    Rol,
+    Ror,
+    Inc,
+    Dec,
+    // 65C02 operations.
+    Tsb,
+    Trb,
+    // Unofficial operations.
+    Dcp,
+    Isc,
+    Slo,
+    Rla,
+    Sre,
+    Rra,
+}
+
+/// Describes how a given opcode is executed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    Internal(InternalOperation),
+    Read(AddressingMode, ReadOperation),
+    Write(AddressingMode, WriteOperation),
+    ReadModifyWrite(AddressingMode, ModifyOperation),
+    Php,
+    Pha,
+    Plp,
+    Pla,
+    Phx,
+    Plx,
+    Phy,
+    Ply,
+    /// Branches if `flags & flag == value`.
+    Branch {
+        flag: u8,
+        value: u8,
+    },
+    JmpAbsolute,
+    /// JMP (ind) of NMOS 6502, which doesn't carry to the high byte of the
+    /// pointer address, so `JMP ($12FF)` reads the target from $12FF and
+    /// $1200.
+    JmpIndirect,
+    /// JMP (ind) of 65C02, which reads the target address correctly, but takes
+    /// an additional cycle.
+    JmpIndirectFixed,
+    /// 65C02 only.
+    JmpAbsoluteXIndirect,
+    Jsr,
+    Rts,
+    Brk,
+    Rti,
+    /// An undefined 65C02 opcode that doesn't do anything, not even take an
+    /// additional cycle after fetching the opcode.
+    SingleCycleNop,
+    /// The undefined 65C02 opcode $5C, which takes 3 bytes and 8 cycles.
+    LongNop,
+    Halt,
+    Unknown,
+}
+
+/// Instruction descriptors of NMOS 6502, indexed by opcode.
+pub static NMOS_INSTRUCTIONS: [Instruction; 256] = instruction_table(CpuVariant::Nmos6502);
+/// Instruction descriptors of 65C02, indexed by opcode.
+pub static CMOS_INSTRUCTIONS: [Instruction; 256] = instruction_table(CpuVariant::Cmos65C02);
+
+const fn instruction_table(variant: CpuVariant) -> [Instruction; 256] {
+    let mut table = [Instruction::Unknown; 256];
+    let mut opcode = 0;
+    while opcode < table.len() {
+        table[opcode] = match variant {
+            CpuVariant::Nmos6502 => decode(opcode as u8),
+            CpuVariant::Cmos65C02 => decode_cmos(opcode as u8),
+        };
+        opcode += 1;
+    }
+    return table;
+}
+
+/// Decodes 65C02 opcodes. The CMOS CPU doesn't have any unofficial
+/// instructions; instead, it uses some of the opcodes for its new
+/// instructions, and treats the remaining ones as no-ops. Rockwell and WDC bit
+/// manipulation instructions are not supported.
+const fn decode_cmos(opcode: u8) -> Instruction {
+    use AddressingMode::*;
+    use Instruction::*;
+    use InternalOperation as Int;
+    use ModifyOperation as Mod;
+    use ReadOperation as Rd;
+    use WriteOperation as Wr;
+
+    match opcode {
+        opcodes::PHX => Phx,
+        opcodes::PLX => Plx,
+        opcodes::PHY => Phy,
+        opcodes::PLY => Ply,
+
+        opcodes::STZ_ZP => Write(ZeroPage, Wr::Stz),
+        opcodes::STZ_ZP_X => Write(ZeroPageX, Wr::Stz),
+        opcodes::STZ_ABS => Write(Absolute, Wr::Stz),
+        opcodes::STZ_ABS_X => Write(AbsoluteX, Wr::Stz),
+
+        opcodes::BRA => Branch { flag: 0, value: 0 },
+        opcodes::JMP_INDIR => JmpIndirectFixed,
+        opcodes::JMP_ABS_X_INDIR => JmpAbsoluteXIndirect,
+
+        opcodes::INC_A => Internal(Int::IncA),
+        opcodes::DEC_A => Internal(Int::DecA),
+
+        opcodes::TSB_ZP => ReadModifyWrite(ZeroPage, Mod::Tsb),
+        opcodes::TSB_ABS => ReadModifyWrite(Absolute, Mod::Tsb),
+        opcodes::TRB_ZP => ReadModifyWrite(ZeroPage, Mod::Trb),
+        opcodes::TRB_ABS => ReadModifyWrite(Absolute, Mod::Trb),
+
+        opcodes::BIT_IMM => Read(Immediate, Rd::BitImmediate),
+        opcodes::BIT_ZP_X => Read(ZeroPageX, Rd::Bit),
+        opcodes::BIT_ABS_X => Read(AbsoluteX, Rd::Bit),
+
+        opcodes::ORA_ZP_INDIR => Read(ZeroPageIndirect, Rd::Ora),
+        opcodes::AND_ZP_INDIR => Read(ZeroPageIndirect, Rd::And),
+        opcodes::EOR_ZP_INDIR => Read(ZeroPageIndirect, Rd::Eor),
+        opcodes::ADC_ZP_INDIR => Read(ZeroPageIndirect, Rd::Adc),
+        opcodes::STA_ZP_INDIR => Write(ZeroPageIndirect, Wr::Sta),
+        opcodes::LDA_ZP_INDIR => Read(ZeroPageIndirect, Rd::Lda),
+        opcodes::CMP_ZP_INDIR => Read(ZeroPageIndirect, Rd::Cmp),
+        opcodes::SBC_ZP_INDIR => Read(ZeroPageIndirect, Rd::Sbc),
+
+        // Undefined opcodes.
+        0x02 | 0x22 | 0x42 | 0x62 | 0x82 | 0xC2 | 0xE2 => Read(Immediate, Rd::Nop),
+        0x44 => Read(ZeroPage, Rd::Nop),
+        0x54 | 0xD4 | 0xF4 => Read(ZeroPageX, Rd::Nop),
+        0xDC | 0xFC => Read(Absolute, Rd::Nop),
+        0x5C => LongNop,
+        _ if opcode & 0b11 == 0b11 => SingleCycleNop,
+
+        // All other opcodes are the same as in NMOS 6502.
+        _ => decode(opcode),
+    }
+}
+
+const fn decode(opcode: u8) -> Instruction {
+    use AddressingMode::*;
+    use Instruction::*;
+    use InternalOperation as Int;
+    use ModifyOperation as Mod;
+    use ReadOperation as Rd;
+    use WriteOperation as Wr;
+
+    match opcode {
+        opcodes::NOP => Internal(Int::Nop),
+
+        opcodes::LDA_IMM => Read(Immediate, Rd::Lda),
+        opcodes::LDA_ZP => Read(ZeroPage, Rd::Lda),
+        opcodes::LDA_ZP_X => Read(ZeroPageX, Rd::Lda),
+        opcodes::LDA_ABS => Read(Absolute, Rd::Lda),
+        opcodes::LDA_ABS_X => Read(AbsoluteX, Rd::Lda),
+        opcodes::LDA_ABS_Y => Read(AbsoluteY, Rd::Lda),
+        opcodes::LDA_X_INDIR => Read(XIndirect, Rd::Lda),
+        opcodes::LDA_INDIR_Y => Read(IndirectY, Rd::Lda),
+
+        opcodes::LDX_IMM => Read(Immediate, Rd::Ldx),
+        opcodes::LDX_ZP => Read(ZeroPage, Rd::Ldx),
+        opcodes::LDX_ZP_Y => Read(ZeroPageY, Rd::Ldx),
+        opcodes::LDX_ABS => Read(Absolute, Rd::Ldx),
+        opcodes::LDX_ABS_Y => Read(AbsoluteY, Rd::Ldx),
+
+        opcodes::LDY_IMM => Read(Immediate, Rd::Ldy),
+        opcodes::LDY_ZP => Read(ZeroPage, Rd::Ldy),
+        opcodes::LDY_ZP_X => Read(ZeroPageX, Rd::Ldy),
+        opcodes::LDY_ABS => Read(Absolute, Rd::Ldy),
+        opcodes::LDY_ABS_X => Read(AbsoluteX, Rd::Ldy),
+
+        opcodes::STA_ZP => Write(ZeroPage, Wr::Sta),
+        opcodes::STA_ZP_X => Write(ZeroPageX, Wr::Sta),
+        opcodes::STA_ABS => Write(Absolute, Wr::Sta),
+        opcodes::STA_ABS_X => Write(AbsoluteX, Wr::Sta),
+        opcodes::STA_ABS_Y => Write(AbsoluteY, Wr::Sta),
+        opcodes::STA_X_INDIR => Write(XIndirect, Wr::Sta),
+        opcodes::STA_INDIR_Y => Write(IndirectY, Wr::Sta),
+
+        opcodes::STX_ZP => Write(ZeroPage, Wr::Stx),
+        opcodes::STX_ZP_Y => Write(ZeroPageY, Wr::Stx),
+        opcodes::STX_ABS => Write(Absolute, Wr::Stx),
+
+        opcodes::STY_ZP => Write(ZeroPage, Wr::Sty),
+        opcodes::STY_ZP_X => Write(ZeroPageX, Wr::Sty),
+        opcodes::STY_ABS => Write(Absolute, Wr::Sty),
+
+        opcodes::AND_IMM => Read(Immediate, Rd::And),
+        opcodes::AND_ZP => Read(ZeroPage, Rd::And),
+        opcodes::AND_ZP_X => Read(ZeroPageX, Rd::And),
+        opcodes::AND_ABS => Read(Absolute, Rd::And),
+        opcodes::AND_ABS_X => Read(AbsoluteX, Rd::And),
+        opcodes::AND_ABS_Y => Read(AbsoluteY, Rd::And),
+        opcodes::AND_X_INDIR => Read(XIndirect, Rd::And),
+        opcodes::AND_INDIR_Y => Read(IndirectY, Rd::And),
+
+        opcodes::ORA_IMM => Read(Immediate, Rd::Ora),
+        opcodes::ORA_ZP => Read(ZeroPage, Rd::Ora),
+        opcodes::ORA_ZP_X => Read(ZeroPageX, Rd::Ora),
+        opcodes::ORA_ABS => Read(Absolute, Rd::Ora),
+        opcodes::ORA_ABS_X => Read(AbsoluteX, Rd::Ora),
+        opcodes::ORA_ABS_Y => Read(AbsoluteY, Rd::Ora),
+        opcodes::ORA_X_INDIR => Read(XIndirect, Rd::Ora),
+        opcodes::ORA_INDIR_Y => Read(IndirectY, Rd::Ora),
+
+        opcodes::EOR_IMM => Read(Immediate, Rd::Eor),
+        opcodes::EOR_ZP => Read(ZeroPage, Rd::Eor),
+        opcodes::EOR_ZP_X => Read(ZeroPageX, Rd::Eor),
+        opcodes::EOR_ABS => Read(Absolute, Rd::Eor),
+        opcodes::EOR_ABS_X => Read(AbsoluteX, Rd::Eor),
+        opcodes::EOR_ABS_Y => Read(AbsoluteY, Rd::Eor),
+        opcodes::EOR_X_INDIR => Read(XIndirect, Rd::Eor),
+        opcodes::EOR_INDIR_Y => Read(IndirectY, Rd::Eor),
+
+        opcodes::ASL_A => Internal(Int::AslA),
+        opcodes::ASL_ZP => ReadModifyWrite(ZeroPage, Mod::Asl),
+        opcodes::ASL_ZP_X => ReadModifyWrite(ZeroPageX, Mod::Asl),
+        opcodes::ASL_ABS => ReadModifyWrite(Absolute, Mod::Asl),
+        opcodes::ASL_ABS_X => ReadModifyWrite(AbsoluteX, Mod::Asl),
+
+        opcodes::LSR_A => Internal(Int::LsrA),
+        opcodes::LSR_ZP => ReadModifyWrite(ZeroPage, Mod::Lsr),
+        opcodes::LSR_ZP_X => ReadModifyWrite(ZeroPageX, Mod::Lsr),
+        opcodes::LSR_ABS => ReadModifyWrite(Absolute, Mod::Lsr),
+        opcodes::LSR_ABS_X => ReadModifyWrite(AbsoluteX, Mod::Lsr),
+
+        opcodes::ROL_A => Internal(Int::RolA),
+        opcodes::ROL_ZP => ReadModifyWrite(ZeroPage, Mod::Rol),
+        opcodes::ROL_ZP_X => ReadModifyWrite(ZeroPageX, Mod::Rol),
+        opcodes::ROL_ABS => ReadModifyWrite(Absolute, Mod::Rol),
+        opcodes::ROL_ABS_X => ReadModifyWrite(AbsoluteX, Mod::Rol),
+
+        opcodes::ROR_A => Internal(Int::RorA),
+        opcodes::ROR_ZP => ReadModifyWrite(ZeroPage, Mod::Ror),
+        opcodes::ROR_ZP_X => ReadModifyWrite(ZeroPageX, Mod::Ror),
+        opcodes::ROR_ABS => ReadModifyWrite(Absolute, Mod::Ror),
+        opcodes::ROR_ABS_X => ReadModifyWrite(AbsoluteX, Mod::Ror),
+
+        opcodes::CMP_IMM => Read(Immediate, Rd::Cmp),
+        opcodes::CMP_ZP => Read(ZeroPage, Rd::Cmp),
+        opcodes::CMP_ZP_X => Read(ZeroPageX, Rd::Cmp),
+        opcodes::CMP_ABS => Read(Absolute, Rd::Cmp),
+        opcodes::CMP_ABS_X => Read(AbsoluteX, Rd::Cmp),
+        opcodes::CMP_ABS_Y => Read(AbsoluteY, Rd::Cmp),
+        opcodes::CMP_X_INDIR => Read(XIndirect, Rd::Cmp),
+        opcodes::CMP_INDIR_Y => Read(IndirectY, Rd::Cmp),
+
+        opcodes::CPX_IMM => Read(Immediate, Rd::Cpx),
+        opcodes::CPX_ZP => Read(ZeroPage, Rd::Cpx),
+        opcodes::CPX_ABS => Read(Absolute, Rd::Cpx),
+
+        opcodes::CPY_IMM => Read(Immediate, Rd::Cpy),
+        opcodes::CPY_ZP => Read(ZeroPage, Rd::Cpy),
+        opcodes::CPY_ABS => Read(Absolute, Rd::Cpy),
+
+        opcodes::BIT_ZP => Read(ZeroPage, Rd::Bit),
+        opcodes::BIT_ABS => Read(Absolute, Rd::Bit),
+
+        opcodes::ADC_IMM => Read(Immediate, Rd::Adc),
+        opcodes::ADC_ZP => Read(ZeroPage, Rd::Adc),
+        opcodes::ADC_ZP_X => Read(ZeroPageX, Rd::Adc),
+        opcodes::ADC_ABS => Read(Absolute, Rd::Adc),
+        opcodes::ADC_ABS_X => Read(AbsoluteX, Rd::Adc),
+        opcodes::ADC_ABS_Y => Read(AbsoluteY, Rd::Adc),
+        opcodes::ADC_X_INDIR => Read(XIndirect, Rd::Adc),
+        opcodes::ADC_INDIR_Y => Read(IndirectY, Rd::Adc),
+
+        opcodes::SBC_IMM | opcodes::SBC_IMM_EB => Read(Immediate, Rd::Sbc),
+        opcodes::SBC_ZP => Read(ZeroPage, Rd::Sbc),
+        opcodes::SBC_ZP_X => Read(ZeroPageX, Rd::Sbc),
+        opcodes::SBC_ABS => Read(Absolute, Rd::Sbc),
+        opcodes::SBC_ABS_X => Read(AbsoluteX, Rd::Sbc),
+        opcodes::SBC_ABS_Y => Read(AbsoluteY, Rd::Sbc),
+        opcodes::SBC_X_INDIR => Read(XIndirect, Rd::Sbc),
+        opcodes::SBC_INDIR_Y => Read(IndirectY, Rd::Sbc),
+
+        opcodes::INC_ZP => ReadModifyWrite(ZeroPage, Mod::Inc),
+        opcodes::INC_ZP_X => ReadModifyWrite(ZeroPageX, Mod::Inc),
+        opcodes::INC_ABS => ReadModifyWrite(Absolute, Mod::Inc),
+        opcodes::INC_ABS_X => ReadModifyWrite(AbsoluteX, Mod::Inc),
+
+        opcodes::DEC_ZP => ReadModifyWrite(ZeroPage, Mod::Dec),
+        opcodes::DEC_ZP_X => ReadModifyWrite(ZeroPageX, Mod::Dec),
+        opcodes::DEC_ABS => ReadModifyWrite(Absolute, Mod::Dec),
+        opcodes::DEC_ABS_X => ReadModifyWrite(AbsoluteX, Mod::Dec),
+
+        opcodes::INX => Internal(Int::Inx),
+        opcodes::INY => Internal(Int::Iny),
+        opcodes::DEX => Internal(Int::Dex),
+        opcodes::DEY => Internal(Int::Dey),
+
+        opcodes::TAX => Internal(Int::Tax),
+        opcodes::TAY => Internal(Int::Tay),
+        opcodes::TXA => Internal(Int::Txa),
+        opcodes::TYA => Internal(Int::Tya),
+        opcodes::TXS => Internal(Int::Txs),
+        opcodes::TSX => Internal(Int::Tsx),
+
+        opcodes::PHP => Php,
+        opcodes::PLP => Plp,
+        opcodes::PHA => Pha,
+        opcodes::PLA => Pla,
+
+        opcodes::SEI => Internal(Int::Sei),
+        opcodes::CLI => Internal(Int::Cli),
+        opcodes::SED => Internal(Int::Sed),
+        opcodes::CLD => Internal(Int::Cld),
+        opcodes::SEC => Internal(Int::Sec),
+        opcodes::CLC => Internal(Int::Clc),
+        opcodes::CLV => Internal(Int::Clv),
+
+        opcodes::BEQ => Branch {
+            flag: flags::Z,
+            value: flags::Z,
+        },
+        opcodes::BNE => Branch {
+            flag: flags::Z,
+            value: 0,
+        },
+        opcodes::BCC => Branch {
+            flag: flags::C,
+            value: 0,
+        },
+        opcodes::BCS => Branch {
+            flag: flags::C,
+            value: flags::C,
+        },
+        opcodes::BPL => Branch {
+            flag: flags::N,
+            value: 0,
+        },
+        opcodes::BMI => Branch {
+            flag: flags::N,
+            value: flags::N,
+        },
+        opcodes::BVS => Branch {
+            flag: flags::V,
+            value: flags::V,
+        },
+        opcodes::BVC => Branch {
+            flag: flags::V,
+            value: 0,
+        },
+
+        opcodes::JMP_ABS => JmpAbsolute,
+        opcodes::JMP_INDIR => JmpIndirect,
+        opcodes::JSR => Jsr,
+        opcodes::RTS => Rts,
+        opcodes::BRK => Brk,
+        opcodes::RTI => Rti,
+
+        // Unofficial opcodes
+        opcodes::HLT1 => Halt,
+
+        opcodes::LAX_ZP => Read(ZeroPage, Rd::Lax),
+        opcodes::LAX_ZP_Y => Read(ZeroPageY, Rd::Lax),
+        opcodes::LAX_ABS => Read(Absolute, Rd::Lax),
+        opcodes::LAX_ABS_Y => Read(AbsoluteY, Rd::Lax),
+        opcodes::LAX_X_INDIR => Read(XIndirect, Rd::Lax),
+        opcodes::LAX_INDIR_Y => Read(IndirectY, Rd::Lax),
+
+        opcodes::SAX_ZP => Write(ZeroPage, Wr::Sax),
+        opcodes::SAX_ZP_Y => Write(ZeroPageY, Wr::Sax),
+        opcodes::SAX_ABS => Write(Absolute, Wr::Sax),
+        opcodes::SAX_X_INDIR => Write(XIndirect, Wr::Sax),
+
+        opcodes::DCP_ZP => ReadModifyWrite(ZeroPage, Mod::Dcp),
+        opcodes::DCP_ZP_X => ReadModifyWrite(ZeroPageX, Mod::Dcp),
+        opcodes::DCP_ABS => ReadModifyWrite(Absolute, Mod::Dcp),
+        opcodes::DCP_ABS_X => ReadModifyWrite(AbsoluteX, Mod::Dcp),
+        opcodes::DCP_ABS_Y => ReadModifyWrite(AbsoluteY, Mod::Dcp),
+        opcodes::DCP_X_INDIR => ReadModifyWrite(XIndirect, Mod::Dcp),
+        opcodes::DCP_INDIR_Y => ReadModifyWrite(IndirectY, Mod::Dcp),
+
+        opcodes::ISC_ZP => ReadModifyWrite(ZeroPage, Mod::Isc),
+        opcodes::ISC_ZP_X => ReadModifyWrite(ZeroPageX, Mod::Isc),
+        opcodes::ISC_ABS => ReadModifyWrite(Absolute, Mod::Isc),
+        opcodes::ISC_ABS_X => ReadModifyWrite(AbsoluteX, Mod::Isc),
+        opcodes::ISC_ABS_Y => ReadModifyWrite(AbsoluteY, Mod::Isc),
+        opcodes::ISC_X_INDIR => ReadModifyWrite(XIndirect, Mod::Isc),
+        opcodes::ISC_INDIR_Y => ReadModifyWrite(IndirectY, Mod::Isc),
+
+        opcodes::SLO_ZP => ReadModifyWrite(ZeroPage, Mod::Slo),
+        opcodes::SLO_ZP_X => ReadModifyWrite(ZeroPageX, Mod::Slo),
+        opcodes::SLO_ABS => ReadModifyWrite(Absolute, Mod::Slo),
+        opcodes::SLO_ABS_X => ReadModifyWrite(AbsoluteX, Mod::Slo),
+        opcodes::SLO_ABS_Y => ReadModifyWrite(AbsoluteY, Mod::Slo),
+        opcodes::SLO_X_INDIR => ReadModifyWrite(XIndirect, Mod::Slo),
+        opcodes::SLO_INDIR_Y => ReadModifyWrite(IndirectY, Mod::Slo),
+
+        opcodes::RLA_ZP => ReadModifyWrite(ZeroPage, Mod::Rla),
+        opcodes::RLA_ZP_X => ReadModifyWrite(ZeroPageX, Mod::Rla),
+        opcodes::RLA_ABS => ReadModifyWrite(Absolute, Mod::Rla),
+        opcodes::RLA_ABS_X => ReadModifyWrite(AbsoluteX, Mod::Rla),
+        opcodes::RLA_ABS_Y => ReadModifyWrite(AbsoluteY, Mod::Rla),
+        opcodes::RLA_X_INDIR => ReadModifyWrite(XIndirect, Mod::Rla),
+        opcodes::RLA_INDIR_Y => ReadModifyWrite(IndirectY, Mod::Rla),
+
+        opcodes::SRE_ZP => ReadModifyWrite(ZeroPage, Mod::Sre),
+        opcodes::SRE_ZP_X => ReadModifyWrite(ZeroPageX, Mod::Sre),
+        opcodes::SRE_ABS => ReadModifyWrite(Absolute, Mod::Sre),
+        opcodes::SRE_ABS_X => ReadModifyWrite(AbsoluteX, Mod::Sre),
+        opcodes::SRE_ABS_Y => ReadModifyWrite(AbsoluteY, Mod::Sre),
+        opcodes::SRE_X_INDIR => ReadModifyWrite(XIndirect, Mod::Sre),
+        opcodes::SRE_INDIR_Y => ReadModifyWrite(IndirectY, Mod::Sre),
+
+        opcodes::RRA_ZP => ReadModifyWrite(ZeroPage, Mod::Rra),
+        opcodes::RRA_ZP_X => ReadModifyWrite(ZeroPageX, Mod::Rra),
+        opcodes::RRA_ABS => ReadModifyWrite(Absolute, Mod::Rra),
+        opcodes::RRA_ABS_X => ReadModifyWrite(AbsoluteX, Mod::Rra),
+        opcodes::RRA_ABS_Y => ReadModifyWrite(AbsoluteY, Mod::Rra),
+        opcodes::RRA_X_INDIR => ReadModifyWrite(XIndirect, Mod::Rra),
+        opcodes::RRA_INDIR_Y => ReadModifyWrite(IndirectY, Mod::Rra),
+
+        opcodes::ANC_IMM_0B | opcodes::ANC_IMM_2B => Read(Immediate, Rd::Anc),
+        opcodes::ALR_IMM => Read(Immediate, Rd::Alr),
+        opcodes::ARR_IMM => Read(Immediate, Rd::Arr),
+        opcodes::AXS_IMM => Read(Immediate, Rd::Axs),
+
+        opcodes::NOP_1A
+        | opcodes::NOP_3A
+        | opcodes::NOP_5A
+        | opcodes::NOP_7A
+        | opcodes::NOP_DA
+        | opcodes::NOP_FA => Internal(Int::Nop),
+        opcodes::NOP_IMM_80
+        | opcodes::NOP_IMM_82
+        | opcodes::NOP_IMM_89
+        | opcodes::NOP_IMM_C2
+        | opcodes::NOP_IMM_E2 => Read(Immediate, Rd::Nop),
+        opcodes::NOP_ZP_04 | opcodes::NOP_ZP_44 | opcodes::NOP_ZP_64 => Read(ZeroPage, Rd::Nop),
+        opcodes::NOP_ZP_X_14
+        | opcodes::NOP_ZP_X_34
+        | opcodes::NOP_ZP_X_54
+        | opcodes::NOP_ZP_X_74
+        | opcodes::NOP_ZP_X_D4
+        | opcodes::NOP_ZP_X_F4 => Read(ZeroPageX, Rd::Nop),
+        opcodes::NOP_ABS_0C => Read(Absolute, Rd::Nop),
+        opcodes::NOP_ABS_X_1C
+        | opcodes::NOP_ABS_X_3C
+        | opcodes::NOP_ABS_X_5C
+        | opcodes::NOP_ABS_X_7C
+        | opcodes::NOP_ABS_X_DC
+        | opcodes::NOP_ABS_X_FC => Read(AbsoluteX, Rd::Nop),
+
+        // Oh no, we don't support it! (Yet.)
+        _ => Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_instructions() {
+        assert_eq!(
+            NMOS_INSTRUCTIONS[opcodes::LDA_INDIR_Y as usize],
+            Instruction::Read(AddressingMode::IndirectY, ReadOperation::Lda)
+        );
+        assert_eq!(
+            NMOS_INSTRUCTIONS[opcodes::SAX_ZP_Y as usize],
+            Instruction::Write(AddressingMode::ZeroPageY, WriteOperation::Sax)
+        );
+        assert_eq!(
+            NMOS_INSTRUCTIONS[opcodes::ROR_A as usize],
+            Instruction::Internal(InternalOperation::RorA)
+        );
+        assert_eq!(NMOS_INSTRUCTIONS[opcodes::HLT1 as usize], Instruction::Halt);
+        // SHY abs,X isn't supported.
+        assert_eq!(NMOS_INSTRUCTIONS[0x9C], Instruction::Unknown);
+    }
+
+    #[test]
+    fn decodes_cmos_instructions() {
+        assert_eq!(
+            CMOS_INSTRUCTIONS[opcodes::LDA_INDIR_Y as usize],
+            NMOS_INSTRUCTIONS[opcodes::LDA_INDIR_Y as usize]
+        );
+        assert_eq!(
+            CMOS_INSTRUCTIONS[opcodes::STZ_ABS as usize],
+            Instruction::Write(AddressingMode::Absolute, WriteOperation::Stz)
+        );
+        assert_eq!(
+            CMOS_INSTRUCTIONS[opcodes::JMP_INDIR as usize],
+            Instruction::JmpIndirectFixed
+        );
+        assert_eq!(
+            CMOS_INSTRUCTIONS[opcodes::NOP_IMM_C2 as usize],
+            Instruction::Read(AddressingMode::Immediate, ReadOperation::Nop)
+        );
+        assert_eq!(CMOS_INSTRUCTIONS[0xFF], Instruction::SingleCycleNop);
+    }
+
+    #[test]
+    fn cmos_has_no_unofficial_instructions() {
+        for (opcode, instruction) in CMOS_INSTRUCTIONS.iter().enumerate() {
+            let unofficial = match instruction {
+                Instruction::Read(_, operation) => matches!(
+                    operation,
+                    ReadOperation::Lax
+                        | ReadOperation::Anc
+                        | ReadOperation::Alr
+                        | ReadOperation::Arr
+                        | ReadOperation::Axs
+                ),
+                Instruction::Write(_, operation) => *operation == WriteOperation::Sax,
+                Instruction::ReadModifyWrite(_, operation) => matches!(
+                    operation,
+                    ModifyOperation::Dcp
+                        | ModifyOperation::Isc
+                        | ModifyOperation::Slo
+                        | ModifyOperation::Rla
+                        | ModifyOperation::Sre
+                        | ModifyOperation::Rra
+                ),
+                Instruction::Halt | Instruction::Unknown => true,
+                _ => false,
+            };
+            assert!(!unofficial, "Opcode ${:02X}: {:?}", opcode, instruction);
+        }
+    }
+
+    #[test]
+    fn uses_supported_addressing_modes() {
+        // The CPU doesn't implement immediate writes and read-modify-write
+        // instructions with zero page, Y or zero page indirect addressing,
+        // since such opcodes don't exist.
+        for instruction in NMOS_INSTRUCTIONS.iter().chain(CMOS_INSTRUCTIONS.iter()) {
+            match *instruction {
+                Instruction::Write(mode, _) => assert_ne!(mode, AddressingMode::Immediate),
+                Instruction::ReadModifyWrite(mode, _) => {
+                    assert_ne!(mode, AddressingMode::Immediate);
+                    assert_ne!(mode, AddressingMode::ZeroPageY);
+                    assert_ne!(mode, AddressingMode::ZeroPageIndirect);
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -36,6 +36,26 @@ pub fn flags_to_string(flags: u8, representation: FlagRepresentation) -> String
         .collect()
 }
 
+/// Parses a string produced by [`flags_to_string`] using the
+/// [`FlagRepresentation::Letters`] representation. Letters are
+/// case-insensitive. Returns `None` if the string is not a valid flag
+/// representation.
+pub fn string_to_flags(text: &str) -> Option<u8> {
+    if text.chars().count() != 8 {
+        return None;
+    }
+    let mut flags = 0;
+    for ((ch, letter), unset) in text.chars().zip(FLAGS_SET_LETTERS).zip(FLAGS_UNSET) {
+        flags <<= 1;
+        if ch.to_ascii_uppercase() == letter {
+            flags |= 1;
+        } else if ch != unset {
+            return None;
+        }
+    }
+    return Some(flags);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -53,4 +73,13 @@ mod tests {
         assert_eq!(flags_to_string(0b1010_1010, Letters), "N.-.D.Z.");
         assert_eq!(flags_to_string(0b0101_0101, Letters), ".V-B.I.C");
     }
+
+    #[test]
+    fn string_to_flags_letters() {
+        assert_eq!(string_to_flags("N.-.D.Z."), Some(0b1010_1010));
+        assert_eq!(string_to_flags(".v-b.i.c"), Some(0b0111_0101));
+        assert_eq!(string_to_flags("..-....."), Some(UNUSED));
+        assert_eq!(string_to_flags("NV-BDIZ"), None);
+        assert_eq!(string_to_flags("0b101010"), None);
+    }
 }
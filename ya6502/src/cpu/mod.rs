@@ -1,16 +1,24 @@
 mod bcd;
+mod decoder;
 pub mod flags;
 pub mod opcodes;
 mod tests;
 
 use crate::memory::Inspect;
-use crate::memory::{Memory, ReadError, ReadResult};
+use crate::memory::MemoryRegion;
+use crate::memory::{Memory, ReadError, ReadResult, WriteError, WriteResult};
+use decoder::{
+    AddressingMode, Instruction, InternalOperation, ModifyOperation, ReadOperation, WriteOperation,
+    CMOS_INSTRUCTIONS, NMOS_INSTRUCTIONS,
+};
 use flags::FlagRepresentation;
 use mockall::automock;
+use rand::rngs::StdRng;
 use rand::Rng;
-use std::error;
+use rand::SeedableRng;
 use std::fmt;
 use std::fmt::Debug;
+use thiserror::Error;
 
 #[derive(Debug, PartialEq)]
 enum SequenceState {
@@ -21,12 +29,51 @@ enum SequenceState {
     Nmi(u32),
 }
 
+/// Selects the instruction set and quirks of the emulated CPU.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CpuVariant {
+    /// The original NMOS 6502 (or one of its derivatives, like 6507 or 6510),
+    /// including its unofficial opcodes.
+    Nmos6502,
+    /// The CMOS 65C02, with its additional instructions and the JMP (ind) bug
+    /// fixed. All undefined opcodes are no-ops, and the D flag is cleared when
+    /// an interrupt is handled. Timing differences of the existing
+    /// instructions (like the additional cycle of ADC and SBC in decimal
+    /// mode) are not emulated.
+    Cmos65C02,
+}
+
+/// Determines the initial values of CPU registers and other chip state, which
+/// are undefined on real hardware.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerOnState {
+    /// Every run starts with different, random values.
+    Random,
+    /// The values are pseudo-random, but derived from a given seed, so every
+    /// run starts in exactly the same state. Useful for tests and debugging.
+    Seeded(u64),
+}
+
+impl PowerOnState {
+    /// Returns a random number generator for the initial values. Each call
+    /// with a seeded state produces the same sequence.
+    pub fn rng(&self) -> StdRng {
+        match self {
+            Self::Random => StdRng::from_entropy(),
+            Self::Seeded(seed) => StdRng::seed_from_u64(*seed),
+        }
+    }
+}
+
 /// A 6502 CPU that operates on a given type of memory. A key to creating a
 /// working hardware implementation is to provide a `Memory` implementation
 /// specific to your particular hardware.
 #[derive(Debug)]
 pub struct Cpu<M: Memory> {
     memory: Box<M>,
+    variant: CpuVariant,
+    /// Instruction descriptors of the emulated CPU variant, indexed by opcode.
+    instructions: &'static [Instruction; 256],
 
     // Interrupt sensors.
     irq_pin: bool,
@@ -56,65 +103,195 @@ pub struct Cpu<M: Memory> {
     ial: u8,
     iah: u8,
     tmp_data: u8,
-}
 
-type TickResult = Result<(), Box<dyn error::Error>>;
+    /// Memory access performed during the last cycle, if any, along with the
+    /// transferred value. Used by debuggers to implement data breakpoints and
+    /// by tracers to log the bus traffic.
+    last_bus_cycle: Option<BusCycle>,
+    /// Number of cycles performed since the CPU has been created.
+    cycles: u64,
+
+    /// If `true`, unknown opcodes and failed memory writes don't stop the CPU.
+    /// See [`Cpu::set_permissive`].
+    permissive: bool,
+    /// Number of errors ignored in the permissive mode.
+    ignored_error_count: u64,
+    /// Errors that have already been reported, so that each of them is only
+    /// reported once.
+    reported_errors: ReportedErrors,
+}
 
-// enum CpuError {
-//     ReadError,
-//     WriteError,
-// }
+/// Keeps track of errors ignored in the permissive mode that have already been
+/// reported. Write errors are told apart by their address, regardless of the
+/// written value, and unknown opcodes by the opcode itself. The errors are
+/// kept in bit sets that are allocated up front, so that recording an error
+/// doesn't allocate during emulation.
+struct ReportedErrors {
+    reads: Box<[u64; 0x10000 / 64]>,
+    writes: Box<[u64; 0x10000 / 64]>,
+    unknown_opcodes: [u64; 0x100 / 64],
+    halting_opcodes: [u64; 0x100 / 64],
+}
 
-#[derive(Debug, Clone)]
-struct UnknownOpcodeError {
-    pub opcode: u8,
-    pub address: u16,
+impl ReportedErrors {
+    /// Records an error. Returns `true` if it hasn't been reported before.
+    fn insert(&mut self, error: CpuError) -> bool {
+        match error {
+            CpuError::ReadError(e) => insert_bit(&mut self.reads[..], e.address as usize),
+            CpuError::WriteError(e) => insert_bit(&mut self.writes[..], e.address as usize),
+            CpuError::UnknownOpcode { opcode, .. } => {
+                insert_bit(&mut self.unknown_opcodes, opcode as usize)
+            }
+            CpuError::Halted { opcode, .. } => {
+                insert_bit(&mut self.halting_opcodes, opcode as usize)
+            }
+        }
+    }
 }
 
-impl error::Error for UnknownOpcodeError {}
+impl Default for ReportedErrors {
+    fn default() -> Self {
+        Self {
+            reads: Box::new([0; 0x10000 / 64]),
+            writes: Box::new([0; 0x10000 / 64]),
+            unknown_opcodes: [0; 0x100 / 64],
+            halting_opcodes: [0; 0x100 / 64],
+        }
+    }
+}
 
-impl fmt::Display for UnknownOpcodeError {
+impl Debug for ReportedErrors {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "Unknown opcode: ${:02X} at ${:04X}",
-            self.opcode, self.address
-        )
+        f.debug_struct("ReportedErrors").finish_non_exhaustive()
     }
 }
 
+/// Sets a bit with a given index in a bit set. Returns `true` if it hasn't
+/// been set before.
+fn insert_bit(bits: &mut [u64], index: usize) -> bool {
+    let mask = 1 << (index % 64);
+    let word = &mut bits[index / 64];
+    let inserted = *word & mask == 0;
+    *word |= mask;
+    return inserted;
+}
+
+/// A single memory access performed by the CPU on the bus.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemoryAccess {
+    Read(u16),
+    Write(u16),
+}
+
+/// A complete record of a single CPU cycle that accessed the bus.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BusCycle {
+    pub access: MemoryAccess,
+    /// The value that was read or written.
+    pub value: u8,
+    /// Number of CPU cycles performed before this one. This way, the opcode
+    /// fetch of an instruction has the same cycle number as the one reported
+    /// by [`MachineInspector::cycles`] when the instruction is about to start.
+    pub cycle: u64,
+}
+
+/// A kind of interrupt sequence performed by the CPU, reported by
+/// [`MachineInspector::current_interrupt`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    /// A software interrupt, caused by the BRK instruction.
+    Brk,
+    Irq,
+    Nmi,
+}
+
+/// CPU registers that can be modified by [`MachineInspector::set_register`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Register {
+    A,
+    X,
+    Y,
+    SP,
+    PC,
+    Flags,
+}
+
+/// A snapshot of registers of a single support chip (e.g. a video or I/O
+/// chip), reported for debugging purposes.
 #[derive(Debug, Clone, PartialEq)]
-pub struct CpuHaltedError {
-    pub opcode: u8,
-    pub address: u16,
+pub struct ChipState {
+    pub name: String,
+    pub registers: Vec<ChipRegister>,
 }
 
-impl error::Error for CpuHaltedError {}
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChipRegister {
+    pub name: String,
+    /// Register value. Most registers are 8-bit wide, but some, like timers
+    /// and counters, occupy 16 bits.
+    pub value: u16,
+}
 
-impl fmt::Display for CpuHaltedError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "CPU halted by opcode ${:02X} at ${:04X}",
-            self.opcode, self.address
-        )
+impl ChipRegister {
+    pub fn new(name: &str, value: impl Into<u16>) -> Self {
+        Self {
+            name: name.to_string(),
+            value: value.into(),
+        }
     }
 }
 
-// impl From<ReadError> for CpuError {
-//     fn from(err: ReadError) -> Self {
-//         CpuError::ReadError(err)
-//     }
-// }
+pub type TickResult = Result<(), CpuError>;
+
+/// An error that stops the CPU. It's a small value type that doesn't require
+/// any allocations, so that it can be cheaply produced and discarded in the
+/// permissive mode.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CpuError {
+    /// The CPU attempted to read from an unsupported memory location.
+    #[error(transparent)]
+    ReadError(#[from] ReadError),
+    /// The CPU attempted to write to an unsupported memory location.
+    #[error(transparent)]
+    WriteError(#[from] WriteError),
+    /// The CPU encountered an opcode that is not emulated.
+    #[error("Unknown opcode: ${opcode:02X} at ${address:04X}")]
+    UnknownOpcode { opcode: u8, address: u16 },
+    /// The CPU executed one of the opcodes that halt it until it's reset.
+    #[error("CPU halted by opcode ${opcode:02X} at ${address:04X}")]
+    Halted { opcode: u8, address: u16 },
+}
 
 impl<M: Memory + Debug> Cpu<M> {
     /// Creates a new `CPU` that owns given `memory`. The newly created `CPU` is
     /// not yet ready for executing programs; it first needs to be reset using
     /// the [`reset`](#method.reset) method.
     pub fn new(memory: Box<M>) -> Self {
-        let mut rng = rand::thread_rng();
+        Self::with_variant(memory, CpuVariant::Nmos6502)
+    }
+
+    /// Creates a new `CPU` of a given variant that owns given `memory`. See
+    /// [`new`](#method.new) for details.
+    pub fn with_variant(memory: Box<M>, variant: CpuVariant) -> Self {
+        Self::with_power_on_state(memory, variant, PowerOnState::Random)
+    }
+
+    /// Creates a new `CPU` of a given variant that owns given `memory`, with
+    /// registers initialized according to `power_on_state`. See
+    /// [`new`](#method.new) for details.
+    pub fn with_power_on_state(
+        memory: Box<M>,
+        variant: CpuVariant,
+        power_on_state: PowerOnState,
+    ) -> Self {
+        let mut rng = power_on_state.rng();
         Cpu {
             memory: memory,
+            variant,
+            instructions: match variant {
+                CpuVariant::Nmos6502 => &NMOS_INSTRUCTIONS,
+                CpuVariant::Cmos65C02 => &CMOS_INSTRUCTIONS,
+            },
 
             irq_pin: false,
             nmi_pin: false,
@@ -137,6 +314,13 @@ impl<M: Memory + Debug> Cpu<M> {
             ial: rng.gen(),
             iah: rng.gen(),
             tmp_data: rng.gen(),
+
+            last_bus_cycle: None,
+            cycles: 0,
+
+            permissive: false,
+            ignored_error_count: 0,
+            reported_errors: ReportedErrors::default(),
         }
     }
 
@@ -170,6 +354,67 @@ impl<M: Memory + Debug> Cpu<M> {
         self.nmi_pin = nmi_pin;
     }
 
+    /// Enables or disables the permissive mode. In this mode, unknown opcodes
+    /// are executed as single-byte NOPs, and writes to unsupported memory
+    /// locations are simply dropped, instead of stopping the CPU with an
+    /// error. Each distinct error is reported on the standard error stream
+    /// once. This makes it possible to explore programs that rely on features
+    /// that aren't emulated yet.
+    pub fn set_permissive(&mut self, permissive: bool) {
+        self.permissive = permissive;
+    }
+
+    pub fn permissive(&self) -> bool {
+        self.permissive
+    }
+
+    /// Returns `true` if the upcoming cycle is a write cycle. Unlike read
+    /// cycles, write cycles ignore the RDY line, so a machine that stops the
+    /// CPU with RDY needs to let them through.
+    pub fn next_cycle_writes(&self) -> bool {
+        return match self.sequence_state {
+            SequenceState::Opcode(opcode, subcycle) => match self.instructions[opcode as usize] {
+                Instruction::Write(mode, _) => match mode {
+                    AddressingMode::ZeroPage => subcycle == 2,
+                    AddressingMode::ZeroPageX
+                    | AddressingMode::ZeroPageY
+                    | AddressingMode::Absolute => subcycle == 3,
+                    AddressingMode::AbsoluteX
+                    | AddressingMode::AbsoluteY
+                    | AddressingMode::ZeroPageIndirect => subcycle == 4,
+                    AddressingMode::XIndirect | AddressingMode::IndirectY => subcycle == 5,
+                    AddressingMode::Immediate => false,
+                },
+                // The last cycle writes the result. On NMOS 6502, the one
+                // before it writes back the unmodified value; 65C02 performs a
+                // read instead.
+                Instruction::ReadModifyWrite(mode, _) => {
+                    let last_subcycle = match mode {
+                        AddressingMode::ZeroPage => 4,
+                        AddressingMode::ZeroPageX | AddressingMode::Absolute => 5,
+                        AddressingMode::AbsoluteX | AddressingMode::AbsoluteY => 6,
+                        AddressingMode::XIndirect | AddressingMode::IndirectY => 7,
+                        AddressingMode::Immediate
+                        | AddressingMode::ZeroPageY
+                        | AddressingMode::ZeroPageIndirect => return false,
+                    };
+                    subcycle == last_subcycle
+                        || (subcycle + 1 == last_subcycle && self.variant == CpuVariant::Nmos6502)
+                }
+                Instruction::Php | Instruction::Pha | Instruction::Phx | Instruction::Phy => {
+                    subcycle == 2
+                }
+                Instruction::Jsr => (3..=4).contains(&subcycle),
+                Instruction::Brk => (2..=4).contains(&subcycle),
+                _ => false,
+            },
+            SequenceState::Irq(subcycle) | SequenceState::Nmi(subcycle) => {
+                (2..=4).contains(&subcycle)
+            }
+            SequenceState::Reset(_) | SequenceState::Ready => false,
+        };
+    }
+
     pub fn jump_to(&mut self, address: u16) {
         self.reg_pc = address;
         self.sequence_state = SequenceState::Ready;
@@ -182,6 +427,8 @@ impl<M: Memory + Debug> Cpu<M> {
             self.nmi_latch = true;
         }
         self.nmi_buffer = self.nmi_pin;
+        self.last_bus_cycle = None;
+        self.cycles += 1;
 
         match self.sequence_state {
             // Fetching the opcode. A small trick: at first, we use 0 for
@@ -197,679 +444,194 @@ impl<M: Memory + Debug> Cpu<M> {
                     self.phantom_read(self.reg_pc);
                     self.sequence_state = SequenceState::Irq(0);
                 } else {
-                    self.sequence_state = SequenceState::Opcode(self.consume_program_byte()?, 0);
+                    let opcode = self.consume_program_byte()?;
+                    // Single-cycle no-ops are done right after fetching the
+                    // opcode.
+                    if !matches!(
+                        self.instructions[opcode as usize],
+                        Instruction::SingleCycleNop
+                    ) {
+                        self.sequence_state = SequenceState::Opcode(opcode, 0);
+                    }
                 }
             }
 
-            // List ALL the opcodes!
-            SequenceState::Opcode(opcodes::NOP, _) => {
-                self.tick_simple_internal_operation(&mut |_| {})?;
-            }
-
-            SequenceState::Opcode(opcodes::LDA_IMM, _) => {
-                self.tick_load_immediate(&mut |me, value| me.set_reg_a(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDA_ZP, _) => {
-                self.tick_load_zero_page(&mut |me, value| me.set_reg_a(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDA_ZP_X, _) => {
-                self.tick_load_zero_page_indexed(self.reg_x, &mut |me, value| me.set_reg_a(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDA_ABS, _) => {
-                self.tick_load_absolute(&mut |me, value| me.set_reg_a(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDA_ABS_X, _) => {
-                self.tick_load_absolute_indexed(self.reg_x, &mut |me, value| me.set_reg_a(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDA_ABS_Y, _) => {
-                self.tick_load_absolute_indexed(self.reg_y, &mut |me, value| me.set_reg_a(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDA_X_INDIR, _) => {
-                self.tick_load_x_indirect(&mut |me, value| me.set_reg_a(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDA_INDIR_Y, _) => {
-                self.tick_load_indirect_y(&mut |me, value| me.set_reg_a(value))?;
-            }
-
-            SequenceState::Opcode(opcodes::LDX_IMM, _) => {
-                self.tick_load_immediate(&mut |me, value| me.set_reg_x(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDX_ZP, _) => {
-                self.tick_load_zero_page(&mut |me, value| me.set_reg_x(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDX_ZP_Y, _) => {
-                self.tick_load_zero_page_indexed(self.reg_y, &mut |me, value| me.set_reg_x(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDX_ABS, _) => {
-                self.tick_load_absolute(&mut |me, value| me.set_reg_x(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDX_ABS_Y, _) => {
-                self.tick_load_absolute_indexed(self.reg_y, &mut |me, value| me.set_reg_x(value))?;
-            }
-
-            SequenceState::Opcode(opcodes::LDY_IMM, _) => {
-                self.tick_load_immediate(&mut |me, value| me.set_reg_y(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDY_ZP, _) => {
-                self.tick_load_zero_page(&mut |me, value| me.set_reg_y(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDY_ZP_X, _) => {
-                self.tick_load_zero_page_indexed(self.reg_x, &mut |me, value| me.set_reg_y(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDY_ABS, _) => {
-                self.tick_load_absolute(&mut |me, value| me.set_reg_y(value))?;
-            }
-            SequenceState::Opcode(opcodes::LDY_ABS_X, _) => {
-                self.tick_load_absolute_indexed(self.reg_x, &mut |me, value| me.set_reg_y(value))?;
-            }
-
-            SequenceState::Opcode(opcodes::STA_ZP, _) => {
-                self.tick_store_zero_page(self.reg_a)?;
-            }
-            SequenceState::Opcode(opcodes::STA_ZP_X, _) => {
-                self.tick_store_zero_page_indexed(self.reg_x, self.reg_a)?;
-            }
-            SequenceState::Opcode(opcodes::STA_ABS, _) => {
-                self.tick_store_abs(self.reg_a)?;
-            }
-            SequenceState::Opcode(opcodes::STA_ABS_X, _) => {
-                self.tick_store_abs_indexed(self.reg_x, self.reg_a)?;
-            }
-            SequenceState::Opcode(opcodes::STA_ABS_Y, _) => {
-                self.tick_store_abs_indexed(self.reg_y, self.reg_a)?;
-            }
-            SequenceState::Opcode(opcodes::STA_X_INDIR, _) => {
-                self.tick_store_x_indirect(self.reg_a)?;
-            }
-            SequenceState::Opcode(opcodes::STA_INDIR_Y, _) => {
-                self.tick_store_indirect_y(self.reg_a)?;
-            }
-
-            SequenceState::Opcode(opcodes::STX_ZP, _) => {
-                self.tick_store_zero_page(self.reg_x)?;
-            }
-            SequenceState::Opcode(opcodes::STX_ZP_Y, _) => {
-                self.tick_store_zero_page_indexed(self.reg_y, self.reg_x)?;
-            }
-            SequenceState::Opcode(opcodes::STX_ABS, _) => {
-                self.tick_store_abs(self.reg_x)?;
-            }
-
-            SequenceState::Opcode(opcodes::STY_ZP, _) => {
-                self.tick_store_zero_page(self.reg_y)?;
-            }
-            SequenceState::Opcode(opcodes::STY_ZP_X, _) => {
-                self.tick_store_zero_page_indexed(self.reg_x, self.reg_y)?;
-            }
-            SequenceState::Opcode(opcodes::STY_ABS, _) => {
-                self.tick_store_abs(self.reg_y)?;
-            }
-
-            SequenceState::Opcode(opcodes::AND_IMM, _) => {
-                self.tick_load_immediate(&mut |me, value| me.set_reg_a(me.reg_a & value))?;
-            }
-            SequenceState::Opcode(opcodes::AND_ZP, _) => {
-                self.tick_load_zero_page(&mut |me, value| me.set_reg_a(me.reg_a & value))?;
-            }
-            SequenceState::Opcode(opcodes::AND_ZP_X, _) => {
-                self.tick_load_zero_page_indexed(self.reg_x, &mut |me, value| {
-                    me.set_reg_a(me.reg_a & value)
-                })?;
-            }
-            SequenceState::Opcode(opcodes::AND_ABS, _) => {
-                self.tick_load_absolute(&mut |me, value| me.set_reg_a(me.reg_a & value))?;
-            }
-            SequenceState::Opcode(opcodes::AND_ABS_X, _) => {
-                self.tick_load_absolute_indexed(self.reg_x, &mut |me, value| {
-                    me.set_reg_a(me.reg_a & value)
-                })?;
-            }
-            SequenceState::Opcode(opcodes::AND_ABS_Y, _) => {
-                self.tick_load_absolute_indexed(self.reg_y, &mut |me, value| {
-                    me.set_reg_a(me.reg_a & value)
-                })?;
-            }
-            SequenceState::Opcode(opcodes::AND_X_INDIR, _) => {
-                self.tick_load_x_indirect(&mut |me, value| me.set_reg_a(me.reg_a & value))?;
-            }
-            SequenceState::Opcode(opcodes::AND_INDIR_Y, _) => {
-                self.tick_load_indirect_y(&mut |me, value| me.set_reg_a(me.reg_a & value))?;
-            }
-
-            SequenceState::Opcode(opcodes::ORA_IMM, _) => {
-                self.tick_load_immediate(&mut |me, value| me.set_reg_a(me.reg_a | value))?;
-            }
-            SequenceState::Opcode(opcodes::ORA_ZP, _) => {
-                self.tick_load_zero_page(&mut |me, value| me.set_reg_a(me.reg_a | value))?;
-            }
-            SequenceState::Opcode(opcodes::ORA_ZP_X, _) => {
-                self.tick_load_zero_page_indexed(self.reg_x, &mut |me, value| {
-                    me.set_reg_a(me.reg_a | value)
-                })?;
-            }
-            SequenceState::Opcode(opcodes::ORA_ABS, _) => {
-                self.tick_load_absolute(&mut |me, value| me.set_reg_a(me.reg_a | value))?;
-            }
-            SequenceState::Opcode(opcodes::ORA_ABS_X, _) => {
-                self.tick_load_absolute_indexed(self.reg_x, &mut |me, value| {
-                    me.set_reg_a(me.reg_a | value)
-                })?;
-            }
-            SequenceState::Opcode(opcodes::ORA_ABS_Y, _) => {
-                self.tick_load_absolute_indexed(self.reg_y, &mut |me, value| {
-                    me.set_reg_a(me.reg_a | value)
-                })?;
-            }
-            SequenceState::Opcode(opcodes::ORA_X_INDIR, _) => {
-                self.tick_load_x_indirect(&mut |me, value| me.set_reg_a(me.reg_a | value))?;
-            }
-            SequenceState::Opcode(opcodes::ORA_INDIR_Y, _) => {
-                self.tick_load_indirect_y(&mut |me, value| me.set_reg_a(me.reg_a | value))?;
-            }
-
-            SequenceState::Opcode(opcodes::EOR_IMM, _) => {
-                self.tick_load_immediate(&mut |me, value| me.set_reg_a(me.reg_a ^ value))?;
-            }
-            SequenceState::Opcode(opcodes::EOR_ZP, _) => {
-                self.tick_load_zero_page(&mut |me, value| me.set_reg_a(me.reg_a ^ value))?;
-            }
-            SequenceState::Opcode(opcodes::EOR_ZP_X, _) => {
-                self.tick_load_zero_page_indexed(self.reg_x, &mut |me, value| {
-                    me.set_reg_a(me.reg_a ^ value)
-                })?;
-            }
-            SequenceState::Opcode(opcodes::EOR_ABS, _) => {
-                self.tick_load_absolute(&mut |me, value| me.set_reg_a(me.reg_a ^ value))?;
-            }
-            SequenceState::Opcode(opcodes::EOR_ABS_X, _) => {
-                self.tick_load_absolute_indexed(self.reg_x, &mut |me, value| {
-                    me.set_reg_a(me.reg_a ^ value)
-                })?;
-            }
-            SequenceState::Opcode(opcodes::EOR_ABS_Y, _) => {
-                self.tick_load_absolute_indexed(self.reg_y, &mut |me, value| {
-                    me.set_reg_a(me.reg_a ^ value)
-                })?;
-            }
-            SequenceState::Opcode(opcodes::EOR_X_INDIR, _) => {
-                self.tick_load_x_indirect(&mut |me, value| me.set_reg_a(me.reg_a ^ value))?;
-            }
-            SequenceState::Opcode(opcodes::EOR_INDIR_Y, _) => {
-                self.tick_load_indirect_y(&mut |me, value| me.set_reg_a(me.reg_a ^ value))?;
-            }
-
-            SequenceState::Opcode(opcodes::ASL_A, _) => {
-                self.tick_simple_internal_operation(&mut |me| {
-                    let shifted = me.shift_left(me.reg_a);
-                    me.set_reg_a(shifted);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::ASL_ZP, _) => {
-                self.tick_load_modify_store_zero_page(&mut |me, value| me.shift_left(value))?;
-            }
-            SequenceState::Opcode(opcodes::ASL_ZP_X, _) => {
-                self.tick_load_modify_store_zero_page_x(&mut |me, value| me.shift_left(value))?;
-            }
-            SequenceState::Opcode(opcodes::ASL_ABS, _) => {
-                self.tick_load_modify_store_absolute(&mut |me, value| me.shift_left(value))?;
-            }
-            SequenceState::Opcode(opcodes::ASL_ABS_X, _) => {
-                self.tick_load_modify_store_absolute_indexed(self.reg_x, &mut |me, value| {
-                    me.shift_left(value)
-                })?;
-            }
-
-            SequenceState::Opcode(opcodes::LSR_A, _) => {
-                self.tick_simple_internal_operation(&mut |me| {
-                    let shifted = me.shift_right(me.reg_a);
-                    me.set_reg_a(shifted);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::LSR_ZP, _) => {
-                self.tick_load_modify_store_zero_page(&mut |me, value| me.shift_right(value))?;
-            }
-            SequenceState::Opcode(opcodes::LSR_ZP_X, _) => {
-                self.tick_load_modify_store_zero_page_x(&mut |me, value| me.shift_right(value))?;
-            }
-            SequenceState::Opcode(opcodes::LSR_ABS, _) => {
-                self.tick_load_modify_store_absolute(&mut |me, value| me.shift_right(value))?;
-            }
-            SequenceState::Opcode(opcodes::LSR_ABS_X, _) => {
-                self.tick_load_modify_store_absolute_indexed(self.reg_x, &mut |me, value| {
-                    me.shift_right(value)
-                })?;
-            }
-
-            SequenceState::Opcode(opcodes::ROL_A, _) => {
-                self.tick_simple_internal_operation(&mut |me| {
-                    let rotated = me.rotate_left(me.reg_a);
-                    me.set_reg_a(rotated);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::ROL_ZP, _) => {
-                self.tick_load_modify_store_zero_page(&mut |me, value| me.rotate_left(value))?;
-            }
-            SequenceState::Opcode(opcodes::ROL_ZP_X, _) => {
-                self.tick_load_modify_store_zero_page_x(&mut |me, value| me.rotate_left(value))?;
-            }
-            SequenceState::Opcode(opcodes::ROL_ABS, _) => {
-                self.tick_load_modify_store_absolute(&mut |me, value| me.rotate_left(value))?;
-            }
-            SequenceState::Opcode(opcodes::ROL_ABS_X, _) => {
-                self.tick_load_modify_store_absolute_indexed(self.reg_x, &mut |me, value| {
-                    me.rotate_left(value)
-                })?;
-            }
-
-            SequenceState::Opcode(opcodes::ROR_A, _) => {
-                self.tick_simple_internal_operation(&mut |me| {
-                    let rotated = me.rotate_right(me.reg_a);
-                    me.set_reg_a(rotated);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::ROR_ZP, _) => {
-                self.tick_load_modify_store_zero_page(&mut |me, value| me.rotate_right(value))?;
-            }
-            SequenceState::Opcode(opcodes::ROR_ZP_X, _) => {
-                self.tick_load_modify_store_zero_page_x(&mut |me, value| me.rotate_right(value))?;
-            }
-            SequenceState::Opcode(opcodes::ROR_ABS, _) => {
-                self.tick_load_modify_store_absolute(&mut |me, value| me.rotate_right(value))?;
-            }
-            SequenceState::Opcode(opcodes::ROR_ABS_X, _) => {
-                self.tick_load_modify_store_absolute_indexed(self.reg_x, &mut |me, value| {
-                    me.rotate_right(value)
-                })?;
-            }
-
-            SequenceState::Opcode(opcodes::CMP_IMM, _) => {
-                self.tick_compare_immediate(self.reg_a)?;
-            }
-            SequenceState::Opcode(opcodes::CMP_ZP, _) => {
-                self.tick_compare_zero_page(self.reg_a)?;
-            }
-            SequenceState::Opcode(opcodes::CMP_ZP_X, _) => {
-                self.tick_compare_zero_page_x(self.reg_a)?;
-            }
-            SequenceState::Opcode(opcodes::CMP_ABS, _) => {
-                self.tick_compare_absolute(self.reg_a)?;
-            }
-            SequenceState::Opcode(opcodes::CMP_ABS_X, _) => {
-                self.tick_compare_absolute_indexed(self.reg_x, self.reg_a)?;
-            }
-            SequenceState::Opcode(opcodes::CMP_ABS_Y, _) => {
-                self.tick_compare_absolute_indexed(self.reg_y, self.reg_a)?;
-            }
-            SequenceState::Opcode(opcodes::CMP_X_INDIR, _) => {
-                self.tick_compare_x_indirect(self.reg_a)?;
-            }
-            SequenceState::Opcode(opcodes::CMP_INDIR_Y, _) => {
-                self.tick_compare_indirect_y(self.reg_a)?;
-            }
-
-            SequenceState::Opcode(opcodes::CPX_IMM, _) => {
-                self.tick_compare_immediate(self.reg_x)?;
-            }
-            SequenceState::Opcode(opcodes::CPX_ZP, _) => {
-                self.tick_compare_zero_page(self.reg_x)?;
-            }
-            SequenceState::Opcode(opcodes::CPX_ABS, _) => {
-                self.tick_compare_absolute(self.reg_x)?;
-            }
-
-            SequenceState::Opcode(opcodes::CPY_IMM, _) => {
-                self.tick_compare_immediate(self.reg_y)?;
-            }
-            SequenceState::Opcode(opcodes::CPY_ZP, _) => {
-                self.tick_compare_zero_page(self.reg_y)?;
-            }
-            SequenceState::Opcode(opcodes::CPY_ABS, _) => {
-                self.tick_compare_absolute(self.reg_y)?;
-            }
-
-            SequenceState::Opcode(opcodes::BIT_ZP, _) => {
-                self.tick_load_zero_page(&mut |me, value| me.test_bits(value))?;
-            }
-            SequenceState::Opcode(opcodes::BIT_ABS, _) => {
-                self.tick_load_absolute(&mut |me, value| me.test_bits(value))?;
-            }
-
-            SequenceState::Opcode(opcodes::ADC_IMM, _) => {
-                self.tick_load_immediate(&mut |me, value| {
-                    let sum = me.add_with_carry(me.reg_a, value);
-                    me.set_reg_a(sum);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::ADC_ZP, _) => {
-                self.tick_load_zero_page(&mut |me, value| {
-                    let sum = me.add_with_carry(me.reg_a, value);
-                    me.set_reg_a(sum);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::ADC_ZP_X, _) => {
-                self.tick_load_zero_page_indexed(self.reg_x, &mut |me, value| {
-                    let sum = me.add_with_carry(me.reg_a, value);
-                    me.set_reg_a(sum);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::ADC_ABS, _) => {
-                self.tick_load_absolute(&mut |me, value| {
-                    let sum = me.add_with_carry(me.reg_a, value);
-                    me.set_reg_a(sum);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::ADC_ABS_X, _) => {
-                self.tick_load_absolute_indexed(self.reg_x, &mut |me, value| {
-                    let sum = me.add_with_carry(me.reg_a, value);
-                    me.set_reg_a(sum);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::ADC_ABS_Y, _) => {
-                self.tick_load_absolute_indexed(self.reg_y, &mut |me, value| {
-                    let sum = me.add_with_carry(me.reg_a, value);
-                    me.set_reg_a(sum);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::ADC_X_INDIR, _) => {
-                self.tick_load_x_indirect(&mut |me, value| {
-                    let sum = me.add_with_carry(me.reg_a, value);
-                    me.set_reg_a(sum);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::ADC_INDIR_Y, _) => {
-                self.tick_load_indirect_y(&mut |me, value| {
-                    let sum = me.add_with_carry(me.reg_a, value);
-                    me.set_reg_a(sum);
-                })?;
-            }
-
-            SequenceState::Opcode(opcodes::SBC_IMM, _) => {
-                self.tick_load_immediate(&mut |me, value| {
-                    let diff = me.sub_with_carry(me.reg_a, value);
-                    me.set_reg_a(diff);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::SBC_ZP, _) => {
-                self.tick_load_zero_page(&mut |me, value| {
-                    let diff = me.sub_with_carry(me.reg_a, value);
-                    me.set_reg_a(diff);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::SBC_ZP_X, _) => {
-                self.tick_load_zero_page_indexed(self.reg_x, &mut |me, value| {
-                    let diff = me.sub_with_carry(me.reg_a, value);
-                    me.set_reg_a(diff);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::SBC_ABS, _) => {
-                self.tick_load_absolute(&mut |me, value| {
-                    let diff = me.sub_with_carry(me.reg_a, value);
-                    me.set_reg_a(diff);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::SBC_ABS_X, _) => {
-                self.tick_load_absolute_indexed(self.reg_x, &mut |me, value| {
-                    let diff = me.sub_with_carry(me.reg_a, value);
-                    me.set_reg_a(diff);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::SBC_ABS_Y, _) => {
-                self.tick_load_absolute_indexed(self.reg_y, &mut |me, value| {
-                    let diff = me.sub_with_carry(me.reg_a, value);
-                    me.set_reg_a(diff);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::SBC_X_INDIR, _) => {
-                self.tick_load_x_indirect(&mut |me, value| {
-                    let diff = me.sub_with_carry(me.reg_a, value);
-                    me.set_reg_a(diff);
-                })?;
-            }
-            SequenceState::Opcode(opcodes::SBC_INDIR_Y, _) => {
-                self.tick_load_indirect_y(&mut |me, value| {
-                    let diff = me.sub_with_carry(me.reg_a, value);
-                    me.set_reg_a(diff);
-                })?;
-            }
-
-            SequenceState::Opcode(opcodes::INC_ZP, _) => {
-                self.tick_load_modify_store_zero_page(&mut |me, val| me.inc(val))?;
-            }
-            SequenceState::Opcode(opcodes::INC_ZP_X, _) => {
-                self.tick_load_modify_store_zero_page_x(&mut |me, val| me.inc(val))?;
-            }
-            SequenceState::Opcode(opcodes::INC_ABS, _) => {
-                self.tick_load_modify_store_absolute(&mut |me, val| me.inc(val))?;
-            }
-            SequenceState::Opcode(opcodes::INC_ABS_X, _) => {
-                self.tick_load_modify_store_absolute_indexed(self.reg_x, &mut |me, val| {
-                    me.inc(val)
-                })?;
-            }
-
-            SequenceState::Opcode(opcodes::DEC_ZP, _) => {
-                self.tick_load_modify_store_zero_page(&mut |me, val| me.dec(val))?;
-            }
-            SequenceState::Opcode(opcodes::DEC_ZP_X, _) => {
-                self.tick_load_modify_store_zero_page_x(&mut |me, val| me.dec(val))?;
-            }
-            SequenceState::Opcode(opcodes::DEC_ABS, _) => {
-                self.tick_load_modify_store_absolute(&mut |me, val| me.dec(val))?;
-            }
-            SequenceState::Opcode(opcodes::DEC_ABS_X, _) => {
-                self.tick_load_modify_store_absolute_indexed(self.reg_x, &mut |me, val| {
-                    me.dec(val)
-                })?;
-            }
-
-            SequenceState::Opcode(opcodes::INX, _) => {
-                self.tick_simple_internal_operation(&mut |me| {
-                    me.set_reg_x(me.reg_x.wrapping_add(1))
-                })?;
-            }
-            SequenceState::Opcode(opcodes::INY, _) => {
-                self.tick_simple_internal_operation(&mut |me| {
-                    me.set_reg_y(me.reg_y.wrapping_add(1))
-                })?;
-            }
-            SequenceState::Opcode(opcodes::DEX, _) => {
-                self.tick_simple_internal_operation(&mut |me| {
-                    me.set_reg_x(me.reg_x.wrapping_sub(1))
-                })?;
-            }
-            SequenceState::Opcode(opcodes::DEY, _) => {
-                self.tick_simple_internal_operation(&mut |me| {
-                    me.set_reg_y(me.reg_y.wrapping_sub(1))
-                })?;
-            }
-
-            SequenceState::Opcode(opcodes::TAX, _) => {
-                self.tick_simple_internal_operation(&mut |me| me.set_reg_x(me.reg_a))?;
-            }
-            SequenceState::Opcode(opcodes::TAY, _) => {
-                self.tick_simple_internal_operation(&mut |me| me.set_reg_y(me.reg_a))?;
-            }
-            SequenceState::Opcode(opcodes::TXA, _) => {
-                self.tick_simple_internal_operation(&mut |me| me.set_reg_a(me.reg_x))?;
-            }
-            SequenceState::Opcode(opcodes::TYA, _) => {
-                self.tick_simple_internal_operation(&mut |me| me.set_reg_a(me.reg_y))?;
-            }
-            SequenceState::Opcode(opcodes::TXS, _) => {
-                self.tick_simple_internal_operation(&mut |me| me.reg_sp = me.reg_x)?;
-            }
-            SequenceState::Opcode(opcodes::TSX, _) => {
-                self.tick_simple_internal_operation(&mut |me| me.set_reg_x(me.reg_sp))?;
-            }
-
-            SequenceState::Opcode(opcodes::PHP, _) => {
-                self.tick_push(self.flags | flags::PUSHED)?;
-            }
-            SequenceState::Opcode(opcodes::PLP, _) => {
-                self.tick_pull(&mut |me, value| me.flags = value & !flags::PUSHED)?;
-            }
-            SequenceState::Opcode(opcodes::PHA, _) => {
-                self.tick_push(self.reg_a)?;
-            }
-            SequenceState::Opcode(opcodes::PLA, _) => {
-                self.tick_pull(&mut |me, value| me.set_reg_a(value))?;
-            }
-
-            SequenceState::Opcode(opcodes::SEI, _) => {
-                self.tick_simple_internal_operation(&mut |me| me.flags |= flags::I)?;
-            }
-            SequenceState::Opcode(opcodes::CLI, _) => {
-                self.tick_simple_internal_operation(&mut |me| me.flags &= !flags::I)?;
-            }
-            SequenceState::Opcode(opcodes::SED, _) => {
-                self.tick_simple_internal_operation(&mut |me| me.flags |= flags::D)?;
-            }
-            SequenceState::Opcode(opcodes::CLD, _) => {
-                self.tick_simple_internal_operation(&mut |me| me.flags &= !flags::D)?;
-            }
-            SequenceState::Opcode(opcodes::SEC, _) => {
-                self.tick_simple_internal_operation(&mut |me| me.flags |= flags::C)?;
-            }
-            SequenceState::Opcode(opcodes::CLC, _) => {
-                self.tick_simple_internal_operation(&mut |me| me.flags &= !flags::C)?;
-            }
-            SequenceState::Opcode(opcodes::CLV, _) => {
-                self.tick_simple_internal_operation(&mut |me| me.flags &= !flags::V)?;
-            }
-
-            SequenceState::Opcode(opcodes::BEQ, _) => {
-                self.tick_branch_if_flag(flags::Z, flags::Z)?;
-            }
-            SequenceState::Opcode(opcodes::BNE, _) => {
-                self.tick_branch_if_flag(flags::Z, 0)?;
-            }
-            SequenceState::Opcode(opcodes::BCC, _) => {
-                self.tick_branch_if_flag(flags::C, 0)?;
-            }
-            SequenceState::Opcode(opcodes::BCS, _) => {
-                self.tick_branch_if_flag(flags::C, flags::C)?;
-            }
-            SequenceState::Opcode(opcodes::BPL, _) => {
-                self.tick_branch_if_flag(flags::N, 0)?;
-            }
-            SequenceState::Opcode(opcodes::BMI, _) => {
-                self.tick_branch_if_flag(flags::N, flags::N)?;
-            }
-            SequenceState::Opcode(opcodes::BVS, _) => {
-                self.tick_branch_if_flag(flags::V, flags::V)?;
-            }
-            SequenceState::Opcode(opcodes::BVC, _) => {
-                self.tick_branch_if_flag(flags::V, 0)?;
-            }
-
-            SequenceState::Opcode(opcodes::JMP_ABS, subcycle) => match subcycle {
-                1 => self.adl = self.consume_program_byte()?,
-                _ => {
-                    self.adh = self.memory.read(self.reg_pc)?;
-                    self.reg_pc = self.address();
-                    self.sequence_state = SequenceState::Ready;
+            SequenceState::Opcode(opcode, subcycle) => match self.instructions[opcode as usize] {
+                Instruction::Internal(operation) => {
+                    self.tick_simple_internal_operation(operation)?;
                 }
-            },
-            SequenceState::Opcode(opcodes::JMP_INDIR, subcycle) => match subcycle {
-                1 => self.ial = self.consume_program_byte()?,
-                2 => self.iah = self.consume_program_byte()?,
-                3 => self.adl = self.memory.read(u16::from_le_bytes([self.ial, self.iah]))?,
-                _ => {
-                    self.adh = self
-                        .memory
-                        .read(u16::from_le_bytes([self.ial.wrapping_add(1), self.iah]))?;
-                    self.reg_pc = self.address();
-                    self.sequence_state = SequenceState::Ready;
+                Instruction::Read(mode, operation) => self.tick_read(mode, operation)?,
+                Instruction::Write(mode, operation) => self.tick_write(mode, operation)?,
+                Instruction::ReadModifyWrite(mode, operation) => {
+                    self.tick_read_modify_write(mode, operation)?;
                 }
-            },
 
-            SequenceState::Opcode(opcodes::JSR, subcycle) => match subcycle {
-                1 => self.adl = self.consume_program_byte()?,
-                2 => {
-                    self.phantom_read(self.stack_pointer());
-                }
-                3 => {
-                    self.memory
-                        .write(self.stack_pointer(), (self.reg_pc >> 8) as u8)?;
-                    self.reg_sp = self.reg_sp.wrapping_sub(1);
+                Instruction::Php => self.tick_push(self.flags | flags::PUSHED)?,
+                Instruction::Plp => {
+                    self.tick_pull(&mut |me, value| me.flags = value & !flags::PUSHED)?;
                 }
-                4 => {
-                    self.memory.write(self.stack_pointer(), self.reg_pc as u8)?;
-                    self.reg_sp = self.reg_sp.wrapping_sub(1);
-                }
-                _ => {
-                    self.adh = self.memory.read(self.reg_pc)?;
-                    self.reg_pc = self.address();
-                    self.sequence_state = SequenceState::Ready;
+                Instruction::Pha => self.tick_push(self.reg_a)?,
+                Instruction::Pla => self.tick_pull(&mut |me, value| me.set_reg_a(value))?,
+                Instruction::Phx => self.tick_push(self.reg_x)?,
+                Instruction::Plx => self.tick_pull(&mut |me, value| me.set_reg_x(value))?,
+                Instruction::Phy => self.tick_push(self.reg_y)?,
+                Instruction::Ply => self.tick_pull(&mut |me, value| me.set_reg_y(value))?,
+
+                Instruction::Branch { flag, value } => self.tick_branch_if_flag(flag, value)?,
+
+                Instruction::JmpAbsolute => match subcycle {
+                    1 => self.adl = self.consume_program_byte()?,
+                    _ => {
+                        self.adh = self.read_memory(self.reg_pc)?;
+                        self.reg_pc = self.address();
+                        self.sequence_state = SequenceState::Ready;
+                    }
+                },
+                Instruction::JmpIndirect => match subcycle {
+                    1 => self.ial = self.consume_program_byte()?,
+                    2 => self.iah = self.consume_program_byte()?,
+                    3 => self.adl = self.read_memory(u16::from_le_bytes([self.ial, self.iah]))?,
+                    _ => {
+                        self.adh = self.read_memory(u16::from_le_bytes([
+                            self.ial.wrapping_add(1),
+                            self.iah,
+                        ]))?;
+                        self.reg_pc = self.address();
+                        self.sequence_state = SequenceState::Ready;
+                    }
+                },
+                Instruction::JmpIndirectFixed => match subcycle {
+                    1 => self.ial = self.consume_program_byte()?,
+                    2 => self.iah = self.consume_program_byte()?,
+                    3 => self.phantom_read(self.reg_pc.wrapping_sub(1)),
+                    4 => self.adl = self.read_memory(u16::from_le_bytes([self.ial, self.iah]))?,
+                    _ => {
+                        let pointer = u16::from_le_bytes([self.ial, self.iah]);
+                        self.adh = self.read_memory(pointer.wrapping_add(1))?;
+                        self.reg_pc = self.address();
+                        self.sequence_state = SequenceState::Ready;
+                    }
+                },
+                Instruction::JmpAbsoluteXIndirect => match subcycle {
+                    1 => self.bal = self.consume_program_byte()?,
+                    2 => self.bah = self.consume_program_byte()?,
+                    3 => self.phantom_read(self.reg_pc.wrapping_sub(1)),
+                    4 => {
+                        let pointer = self.base_address().wrapping_add(self.reg_x as u16);
+                        self.adl = self.read_memory(pointer)?;
+                    }
+                    _ => {
+                        let pointer = self.base_address().wrapping_add(self.reg_x as u16);
+                        self.adh = self.read_memory(pointer.wrapping_add(1))?;
+                        self.reg_pc = self.address();
+                        self.sequence_state = SequenceState::Ready;
+                    }
+                },
+
+                Instruction::Jsr => match subcycle {
+                    1 => self.adl = self.consume_program_byte()?,
+                    2 => {
+                        self.phantom_read(self.stack_pointer());
+                    }
+                    3 => {
+                        self.write_memory(self.stack_pointer(), (self.reg_pc >> 8) as u8)?;
+                        self.reg_sp = self.reg_sp.wrapping_sub(1);
+                    }
+                    4 => {
+                        self.write_memory(self.stack_pointer(), self.reg_pc as u8)?;
+                        self.reg_sp = self.reg_sp.wrapping_sub(1);
+                    }
+                    _ => {
+                        self.adh = self.read_memory(self.reg_pc)?;
+                        self.reg_pc = self.address();
+                        self.sequence_state = SequenceState::Ready;
+                    }
+                },
+                Instruction::Rts => match subcycle {
+                    1 => {
+                        let _ = self.consume_program_byte();
+                    }
+                    2 => {
+                        self.phantom_read(self.stack_pointer());
+                        self.reg_sp = self.reg_sp.wrapping_add(1);
+                    }
+                    3 => {
+                        self.reg_pc =
+                            self.reg_pc & 0xFF00 | self.read_memory(self.stack_pointer())? as u16;
+                        self.reg_sp = self.reg_sp.wrapping_add(1);
+                    }
+                    4 => {
+                        self.reg_pc = self.reg_pc & 0xFF
+                            | ((self.read_memory(self.stack_pointer())? as u16) << 8)
+                    }
+                    _ => {
+                        let _ = self.consume_program_byte();
+                        self.sequence_state = SequenceState::Ready;
+                    }
+                },
+
+                Instruction::Brk => match subcycle {
+                    1 => {
+                        self.consume_program_byte()?;
+                    }
+                    _ => self.tick_interrupt_sequence(subcycle, 0xFFFE, flags::PUSHED)?,
+                },
+                Instruction::Rti => match subcycle {
+                    1 => self.phantom_read(self.reg_pc),
+                    2 => {
+                        self.phantom_read(self.stack_pointer());
+                        self.reg_sp = self.reg_sp.wrapping_add(1);
+                    }
+                    3 => {
+                        self.flags = self.read_memory(self.stack_pointer())?;
+                        self.reg_sp = self.reg_sp.wrapping_add(1);
+                    }
+                    4 => {
+                        self.reg_pc =
+                            self.reg_pc & 0xFF00 | self.read_memory(self.stack_pointer())? as u16;
+                        self.reg_sp = self.reg_sp.wrapping_add(1);
+                    }
+                    _ => {
+                        self.reg_pc = self.reg_pc & 0xFF
+                            | ((self.read_memory(self.stack_pointer())? as u16) << 8);
+                        self.sequence_state = SequenceState::Ready;
+                    }
+                },
+
+                Instruction::LongNop => match subcycle {
+                    1 => self.adl = self.consume_program_byte()?,
+                    2 => self.adh = self.consume_program_byte()?,
+                    // The remaining cycles read from $FFxx, where xx is the
+                    // low byte of the operand.
+                    3..=6 => self.phantom_read(u16::from_le_bytes([self.adl, 0xFF])),
+                    _ => {
+                        self.phantom_read(u16::from_le_bytes([self.adl, 0xFF]));
+                        self.sequence_state = SequenceState::Ready;
+                    }
+                },
+                Instruction::SingleCycleNop => {
+                    unreachable!("Single-cycle no-op executed after the opcode fetch")
                 }
-            },
-            SequenceState::Opcode(opcodes::RTS, subcycle) => match subcycle {
-                1 => {
-                    let _ = self.consume_program_byte();
-                }
-                2 => {
-                    self.phantom_read(self.stack_pointer());
-                    self.reg_sp = self.reg_sp.wrapping_add(1);
-                }
-                3 => {
-                    self.reg_pc =
-                        self.reg_pc & 0xFF00 | self.memory.read(self.stack_pointer())? as u16;
-                    self.reg_sp = self.reg_sp.wrapping_add(1);
-                }
-                4 => {
-                    self.reg_pc =
-                        self.reg_pc & 0xFF | ((self.memory.read(self.stack_pointer())? as u16) << 8)
-                }
-                _ => {
-                    let _ = self.consume_program_byte();
-                    self.sequence_state = SequenceState::Ready;
-                }
-            },
 
-            SequenceState::Opcode(opcodes::BRK, subcycle) => match subcycle {
-                1 => {
-                    self.consume_program_byte()?;
-                }
-                _ => self.tick_interrupt_sequence(subcycle, 0xFFFE, flags::PUSHED)?,
-            },
-            SequenceState::Opcode(opcodes::RTI, subcycle) => match subcycle {
-                1 => self.phantom_read(self.reg_pc),
-                2 => {
-                    self.phantom_read(self.stack_pointer());
-                    self.reg_sp = self.reg_sp.wrapping_add(1);
-                }
-                3 => {
-                    self.flags = self.memory.read(self.stack_pointer())?;
-                    self.reg_sp = self.reg_sp.wrapping_add(1);
-                }
-                4 => {
-                    self.reg_pc =
-                        self.reg_pc & 0xFF00 | self.memory.read(self.stack_pointer())? as u16;
-                    self.reg_sp = self.reg_sp.wrapping_add(1);
+                Instruction::Halt => {
+                    return Err(CpuError::Halted {
+                        opcode,
+                        address: self.reg_pc.wrapping_sub(1),
+                    });
                 }
-                _ => {
-                    self.reg_pc = self.reg_pc & 0xFF
-                        | ((self.memory.read(self.stack_pointer())? as u16) << 8);
-                    self.sequence_state = SequenceState::Ready;
+                Instruction::Unknown => {
+                    let error = CpuError::UnknownOpcode {
+                        opcode,
+                        address: self.reg_pc.wrapping_sub(1),
+                    };
+                    if !self.permissive {
+                        return Err(error);
+                    }
+                    self.ignore_error(error);
+                    self.tick_simple_internal_operation(InternalOperation::Nop)?;
                 }
             },
 
-            // Unofficial opcodes
-            SequenceState::Opcode(opcodes::HLT1, _) => {
-                return Err(Box::new(CpuHaltedError {
-                    opcode: opcodes::HLT1,
-                    address: self.reg_pc.wrapping_sub(1),
-                }));
-            }
-
-            // Oh no, we don't support it! (Yet.)
-            SequenceState::Opcode(other_opcode, _) => {
-                return Err(Box::new(UnknownOpcodeError {
-                    opcode: other_opcode,
-                    address: self.reg_pc.wrapping_sub(1),
-                }));
-            }
-
             // Reset sequence.
             SequenceState::Reset(subcycle) => match subcycle {
                 0 => self.phantom_read(self.reg_pc),
@@ -878,9 +640,9 @@ impl<M: Memory + Debug> Cpu<M> {
                     self.phantom_read(self.stack_pointer());
                     self.reg_sp = self.reg_sp.wrapping_sub(1);
                 }
-                5 => self.reg_pc = self.reg_pc & 0xFF00 | (self.memory.read(0xFFFC)? as u16),
+                5 => self.reg_pc = self.reg_pc & 0xFF00 | (self.read_memory(0xFFFC)? as u16),
                 _ => {
-                    self.reg_pc = self.reg_pc & 0xFF | ((self.memory.read(0xFFFD)? as u16) << 8);
+                    self.reg_pc = self.reg_pc & 0xFF | ((self.read_memory(0xFFFD)? as u16) << 8);
                     self.sequence_state = SequenceState::Ready;
                     self.flags |= flags::I;
                 }
@@ -911,35 +673,96 @@ impl<M: Memory + Debug> Cpu<M> {
         Ok(())
     }
 
+    fn tick_read(
+        &mut self,
+        mode: AddressingMode,
+        operation: ReadOperation,
+    ) -> Result<(), ReadError> {
+        match mode {
+            AddressingMode::Immediate => self.tick_load_immediate(operation),
+            AddressingMode::ZeroPage => self.tick_load_zero_page(operation),
+            AddressingMode::ZeroPageX => self.tick_load_zero_page_indexed(self.reg_x, operation),
+            AddressingMode::ZeroPageY => self.tick_load_zero_page_indexed(self.reg_y, operation),
+            AddressingMode::Absolute => self.tick_load_absolute(operation),
+            AddressingMode::AbsoluteX => self.tick_load_absolute_indexed(self.reg_x, operation),
+            AddressingMode::AbsoluteY => self.tick_load_absolute_indexed(self.reg_y, operation),
+            AddressingMode::XIndirect => self.tick_load_x_indirect(operation),
+            AddressingMode::IndirectY => self.tick_load_indirect_y(operation),
+            AddressingMode::ZeroPageIndirect => self.tick_load_zero_page_indirect(operation),
+        }
+    }
+
+    fn tick_write(&mut self, mode: AddressingMode, operation: WriteOperation) -> TickResult {
+        let value = match operation {
+            WriteOperation::Sta => self.reg_a,
+            WriteOperation::Stx => self.reg_x,
+            WriteOperation::Sty => self.reg_y,
+            WriteOperation::Stz => 0,
+            WriteOperation::Sax => self.reg_a & self.reg_x,
+        };
+        match mode {
+            AddressingMode::ZeroPage => self.tick_store_zero_page(value),
+            AddressingMode::ZeroPageX => self.tick_store_zero_page_indexed(self.reg_x, value),
+            AddressingMode::ZeroPageY => self.tick_store_zero_page_indexed(self.reg_y, value),
+            AddressingMode::Absolute => self.tick_store_abs(value),
+            AddressingMode::AbsoluteX => self.tick_store_abs_indexed(self.reg_x, value),
+            AddressingMode::AbsoluteY => self.tick_store_abs_indexed(self.reg_y, value),
+            AddressingMode::XIndirect => self.tick_store_x_indirect(value),
+            AddressingMode::IndirectY => self.tick_store_indirect_y(value),
+            AddressingMode::ZeroPageIndirect => self.tick_store_zero_page_indirect(value),
+            AddressingMode::Immediate => unreachable!("Immediate addressing mode used for writing"),
+        }
+    }
+
+    fn tick_read_modify_write(
+        &mut self,
+        mode: AddressingMode,
+        operation: ModifyOperation,
+    ) -> TickResult {
+        match mode {
+            AddressingMode::ZeroPage => self.tick_load_modify_store_zero_page(operation),
+            AddressingMode::ZeroPageX => self.tick_load_modify_store_zero_page_x(operation),
+            AddressingMode::Absolute => self.tick_load_modify_store_absolute(operation),
+            AddressingMode::AbsoluteX => {
+                self.tick_load_modify_store_absolute_indexed(self.reg_x, operation)
+            }
+            AddressingMode::AbsoluteY => {
+                self.tick_load_modify_store_absolute_indexed(self.reg_y, operation)
+            }
+            AddressingMode::XIndirect => self.tick_load_modify_store_x_indirect(operation),
+            AddressingMode::IndirectY => self.tick_load_modify_store_indirect_y(operation),
+            AddressingMode::Immediate
+            | AddressingMode::ZeroPageY
+            | AddressingMode::ZeroPageIndirect => unreachable!(
+                "Unsupported addressing mode for a read-modify-write instruction: {:?}",
+                mode
+            ),
+        }
+    }
+
     fn tick_simple_internal_operation(
         &mut self,
-        operation: &mut dyn FnMut(&mut Self),
+        operation: InternalOperation,
     ) -> Result<(), ReadError> {
         self.phantom_read(self.reg_pc);
-        operation(self);
+        self.execute_internal(operation);
         self.sequence_state = SequenceState::Ready;
         Ok(())
     }
 
-    fn tick_load_immediate(
-        &mut self,
-        load: &mut dyn FnMut(&mut Self, u8),
-    ) -> Result<(), ReadError> {
+    fn tick_load_immediate(&mut self, operation: ReadOperation) -> Result<(), ReadError> {
         let value = self.consume_program_byte()?;
-        load(self, value);
+        self.execute_read(operation, value);
         self.sequence_state = SequenceState::Ready;
         Ok(())
     }
 
-    fn tick_load_zero_page(
-        &mut self,
-        load: &mut dyn FnMut(&mut Self, u8),
-    ) -> Result<(), ReadError> {
+    fn tick_load_zero_page(&mut self, operation: ReadOperation) -> Result<(), ReadError> {
         match self.sequence_state {
             SequenceState::Opcode(_, 1) => self.adl = self.consume_program_byte()?,
             _ => {
-                let value = self.memory.read(self.adl as u16)?;
-                load(self, value);
+                let value = self.read_memory(self.adl as u16)?;
+                self.execute_read(operation, value);
                 self.sequence_state = SequenceState::Ready;
             }
         };
@@ -949,27 +772,27 @@ impl<M: Memory + Debug> Cpu<M> {
     fn tick_load_zero_page_indexed(
         &mut self,
         index: u8,
-        load: &mut dyn FnMut(&mut Self, u8),
+        operation: ReadOperation,
     ) -> Result<(), ReadError> {
         match self.sequence_state {
             SequenceState::Opcode(_, 1) => self.bal = self.consume_program_byte()?,
             SequenceState::Opcode(_, 2) => self.phantom_read(self.bal as u16),
             _ => {
-                let value = self.memory.read(self.bal.wrapping_add(index) as u16)?;
-                load(self, value);
+                let value = self.read_memory(self.bal.wrapping_add(index) as u16)?;
+                self.execute_read(operation, value);
                 self.sequence_state = SequenceState::Ready;
             }
         };
         Ok(())
     }
 
-    fn tick_load_absolute(&mut self, load: &mut dyn FnMut(&mut Self, u8)) -> Result<(), ReadError> {
+    fn tick_load_absolute(&mut self, operation: ReadOperation) -> Result<(), ReadError> {
         match self.sequence_state {
             SequenceState::Opcode(_, 1) => self.adl = self.consume_program_byte()?,
             SequenceState::Opcode(_, 2) => self.adh = self.consume_program_byte()?,
             _ => {
-                let value = self.memory.read(self.address())?;
-                load(self, value);
+                let value = self.read_memory(self.address())?;
+                self.execute_read(operation, value);
                 self.sequence_state = SequenceState::Ready;
             }
         };
@@ -979,7 +802,7 @@ impl<M: Memory + Debug> Cpu<M> {
     fn tick_load_absolute_indexed(
         &mut self,
         index: u8,
-        load: &mut dyn FnMut(&mut Self, u8),
+        operation: ReadOperation,
     ) -> Result<(), ReadError> {
         match self.sequence_state {
             SequenceState::Opcode(_, 1) => self.bal = self.consume_program_byte()?,
@@ -990,55 +813,46 @@ impl<M: Memory + Debug> Cpu<M> {
                 if carry {
                     self.phantom_read(address);
                 } else {
-                    let value = self.memory.read(address)?;
-                    load(self, value);
+                    let value = self.read_memory(address)?;
+                    self.execute_read(operation, value);
                     self.sequence_state = SequenceState::Ready;
                 }
             }
             _ => {
-                let value = self
-                    .memory
-                    .read(self.base_address().wrapping_add(index as u16))?;
-                load(self, value);
+                let value = self.read_memory(self.base_address().wrapping_add(index as u16))?;
+                self.execute_read(operation, value);
                 self.sequence_state = SequenceState::Ready;
             }
         };
         Ok(())
     }
 
-    fn tick_load_x_indirect(
-        &mut self,
-        load: &mut dyn FnMut(&mut Self, u8),
-    ) -> Result<(), ReadError> {
+    fn tick_load_x_indirect(&mut self, operation: ReadOperation) -> Result<(), ReadError> {
         match self.sequence_state {
             SequenceState::Opcode(_, 1) => self.bal = self.consume_program_byte()?,
             SequenceState::Opcode(_, 2) => self.phantom_read(self.bal as u16),
             SequenceState::Opcode(_, 3) => {
-                self.adl = self.memory.read(self.bal.wrapping_add(self.reg_x) as u16)?;
+                self.adl = self.read_memory(self.bal.wrapping_add(self.reg_x) as u16)?;
             }
             SequenceState::Opcode(_, 4) => {
-                self.adh = self
-                    .memory
-                    .read(self.bal.wrapping_add(self.reg_x).wrapping_add(1) as u16)?;
+                self.adh =
+                    self.read_memory(self.bal.wrapping_add(self.reg_x).wrapping_add(1) as u16)?;
             }
             _ => {
-                let value = self.memory.read(self.address())?;
-                load(self, value);
+                let value = self.read_memory(self.address())?;
+                self.execute_read(operation, value);
                 self.sequence_state = SequenceState::Ready;
             }
         }
         Ok(())
     }
 
-    fn tick_load_indirect_y(
-        &mut self,
-        load: &mut dyn FnMut(&mut Self, u8),
-    ) -> Result<(), ReadError> {
+    fn tick_load_indirect_y(&mut self, operation: ReadOperation) -> Result<(), ReadError> {
         match self.sequence_state {
             SequenceState::Opcode(_, 1) => self.ial = self.consume_program_byte()?,
-            SequenceState::Opcode(_, 2) => self.bal = self.memory.read(self.ial as u16)?,
+            SequenceState::Opcode(_, 2) => self.bal = self.read_memory(self.ial as u16)?,
             SequenceState::Opcode(_, 3) => {
-                self.bah = self.memory.read(self.ial.wrapping_add(1) as u16)?
+                self.bah = self.read_memory(self.ial.wrapping_add(1) as u16)?
             }
             SequenceState::Opcode(_, 4) => {
                 let (adl, carry) = self.bal.overflowing_add(self.reg_y);
@@ -1046,16 +860,31 @@ impl<M: Memory + Debug> Cpu<M> {
                 if carry {
                     self.phantom_read(address);
                 } else {
-                    let value = self.memory.read(address)?;
-                    load(self, value);
+                    let value = self.read_memory(address)?;
+                    self.execute_read(operation, value);
                     self.sequence_state = SequenceState::Ready;
                 }
             }
             _ => {
-                let value = self
-                    .memory
-                    .read(self.base_address().wrapping_add(self.reg_y as u16))?;
-                load(self, value);
+                let value =
+                    self.read_memory(self.base_address().wrapping_add(self.reg_y as u16))?;
+                self.execute_read(operation, value);
+                self.sequence_state = SequenceState::Ready;
+            }
+        }
+        Ok(())
+    }
+
+    fn tick_load_zero_page_indirect(&mut self, operation: ReadOperation) -> Result<(), ReadError> {
+        match self.sequence_state {
+            SequenceState::Opcode(_, 1) => self.ial = self.consume_program_byte()?,
+            SequenceState::Opcode(_, 2) => self.adl = self.read_memory(self.ial as u16)?,
+            SequenceState::Opcode(_, 3) => {
+                self.adh = self.read_memory(self.ial.wrapping_add(1) as u16)?
+            }
+            _ => {
+                let value = self.read_memory(self.address())?;
+                self.execute_read(operation, value);
                 self.sequence_state = SequenceState::Ready;
             }
         }
@@ -1066,7 +895,7 @@ impl<M: Memory + Debug> Cpu<M> {
         match self.sequence_state {
             SequenceState::Opcode(_, 1) => self.adl = self.consume_program_byte()?,
             _ => {
-                self.memory.write(self.adl as u16, value)?;
+                self.write_memory(self.adl as u16, value)?;
                 self.sequence_state = SequenceState::Ready;
             }
         };
@@ -1078,8 +907,7 @@ impl<M: Memory + Debug> Cpu<M> {
             SequenceState::Opcode(_, 1) => self.bal = self.consume_program_byte()?,
             SequenceState::Opcode(_, 2) => self.phantom_read(self.bal as u16),
             _ => {
-                self.memory
-                    .write((self.bal.wrapping_add(index)) as u16, value)?;
+                self.write_memory((self.bal.wrapping_add(index)) as u16, value)?;
                 self.sequence_state = SequenceState::Ready;
             }
         };
@@ -1091,7 +919,7 @@ impl<M: Memory + Debug> Cpu<M> {
             SequenceState::Opcode(_, 1) => self.adl = self.consume_program_byte()?,
             SequenceState::Opcode(_, 2) => self.adh = self.consume_program_byte()?,
             _ => {
-                self.memory.write(self.address(), value)?;
+                self.write_memory(self.address(), value)?;
                 self.sequence_state = SequenceState::Ready;
             }
         }
@@ -1106,8 +934,7 @@ impl<M: Memory + Debug> Cpu<M> {
                 self.phantom_read(u16::from_le_bytes([self.bal.wrapping_add(index), self.bah]));
             }
             _ => {
-                self.memory
-                    .write(self.base_address().wrapping_add(index as u16), value)?;
+                self.write_memory(self.base_address().wrapping_add(index as u16), value)?;
                 self.sequence_state = SequenceState::Ready;
             }
         }
@@ -1119,15 +946,14 @@ impl<M: Memory + Debug> Cpu<M> {
             SequenceState::Opcode(_, 1) => self.bal = self.consume_program_byte()?,
             SequenceState::Opcode(_, 2) => self.phantom_read(self.bal as u16),
             SequenceState::Opcode(_, 3) => {
-                self.adl = self.memory.read(self.bal.wrapping_add(self.reg_x) as u16)?;
+                self.adl = self.read_memory(self.bal.wrapping_add(self.reg_x) as u16)?;
             }
             SequenceState::Opcode(_, 4) => {
-                self.adh = self
-                    .memory
-                    .read(self.bal.wrapping_add(self.reg_x).wrapping_add(1) as u16)?;
+                self.adh =
+                    self.read_memory(self.bal.wrapping_add(self.reg_x).wrapping_add(1) as u16)?;
             }
             _ => {
-                self.memory.write(self.address(), value)?;
+                self.write_memory(self.address(), value)?;
                 self.sequence_state = SequenceState::Ready;
             }
         }
@@ -1137,9 +963,9 @@ impl<M: Memory + Debug> Cpu<M> {
     fn tick_store_indirect_y(&mut self, value: u8) -> TickResult {
         match self.sequence_state {
             SequenceState::Opcode(_, 1) => self.ial = self.consume_program_byte()?,
-            SequenceState::Opcode(_, 2) => self.bal = self.memory.read(self.ial as u16)?,
+            SequenceState::Opcode(_, 2) => self.bal = self.read_memory(self.ial as u16)?,
             SequenceState::Opcode(_, 3) => {
-                self.bah = self.memory.read(self.ial.wrapping_add(1) as u16)?
+                self.bah = self.read_memory(self.ial.wrapping_add(1) as u16)?
             }
             SequenceState::Opcode(_, 4) => {
                 self.phantom_read(u16::from_le_bytes([
@@ -1148,77 +974,71 @@ impl<M: Memory + Debug> Cpu<M> {
                 ]));
             }
             _ => {
-                self.memory
-                    .write(self.base_address().wrapping_add(self.reg_y as u16), value)?;
+                self.write_memory(self.base_address().wrapping_add(self.reg_y as u16), value)?;
                 self.sequence_state = SequenceState::Ready;
             }
         }
         Ok(())
     }
 
-    fn tick_load_modify_store_zero_page(
-        &mut self,
-        operation: &mut dyn FnMut(&mut Self, u8) -> u8,
-    ) -> TickResult {
+    fn tick_store_zero_page_indirect(&mut self, value: u8) -> TickResult {
         match self.sequence_state {
-            SequenceState::Opcode(_, 1) => self.adl = self.consume_program_byte()?,
-            SequenceState::Opcode(_, 2) => self.tmp_data = self.memory.read(self.adl as u16)?,
+            SequenceState::Opcode(_, 1) => self.ial = self.consume_program_byte()?,
+            SequenceState::Opcode(_, 2) => self.adl = self.read_memory(self.ial as u16)?,
             SequenceState::Opcode(_, 3) => {
-                // A rare case of a "phantom write". Since we write the same
-                // data, it doesn't really matter (that much), but we need to
-                // simulate it anyway.
-                self.memory.write(self.adl as u16, self.tmp_data)?;
+                self.adh = self.read_memory(self.ial.wrapping_add(1) as u16)?
             }
             _ => {
-                let result = operation(self, self.tmp_data);
-                self.memory.write(self.adl as u16, result)?;
+                self.write_memory(self.address(), value)?;
                 self.sequence_state = SequenceState::Ready;
             }
         }
         Ok(())
     }
 
-    fn tick_load_modify_store_zero_page_x(
-        &mut self,
-        operation: &mut dyn FnMut(&mut Self, u8) -> u8,
-    ) -> TickResult {
+    fn tick_load_modify_store_zero_page(&mut self, operation: ModifyOperation) -> TickResult {
+        match self.sequence_state {
+            SequenceState::Opcode(_, 1) => self.adl = self.consume_program_byte()?,
+            SequenceState::Opcode(_, 2) => self.tmp_data = self.read_memory(self.adl as u16)?,
+            SequenceState::Opcode(_, 3) => self.modify_dummy_cycle(self.adl as u16)?,
+            _ => {
+                let result = self.execute_modify(operation, self.tmp_data);
+                self.write_memory(self.adl as u16, result)?;
+                self.sequence_state = SequenceState::Ready;
+            }
+        }
+        Ok(())
+    }
+
+    fn tick_load_modify_store_zero_page_x(&mut self, operation: ModifyOperation) -> TickResult {
         match self.sequence_state {
             SequenceState::Opcode(_, 1) => self.bal = self.consume_program_byte()?,
             SequenceState::Opcode(_, 2) => self.phantom_read(self.bal as u16),
             SequenceState::Opcode(_, 3) => {
                 self.adl = self.bal.wrapping_add(self.reg_x);
-                self.tmp_data = self.memory.read(self.adl as u16)?;
-            }
-            SequenceState::Opcode(_, 4) => {
-                // Phantom write.
-                self.memory.write(self.adl as u16, self.tmp_data)?;
+                self.tmp_data = self.read_memory(self.adl as u16)?;
             }
+            SequenceState::Opcode(_, 4) => self.modify_dummy_cycle(self.adl as u16)?,
             _ => {
-                let result = operation(self, self.tmp_data);
-                self.memory.write(self.adl as u16, result)?;
+                let result = self.execute_modify(operation, self.tmp_data);
+                self.write_memory(self.adl as u16, result)?;
                 self.sequence_state = SequenceState::Ready;
             }
         }
         Ok(())
     }
 
-    fn tick_load_modify_store_absolute(
-        &mut self,
-        operation: &mut dyn FnMut(&mut Self, u8) -> u8,
-    ) -> TickResult {
+    fn tick_load_modify_store_absolute(&mut self, operation: ModifyOperation) -> TickResult {
         match self.sequence_state {
             SequenceState::Opcode(_, 1) => self.adl = self.consume_program_byte()?,
             SequenceState::Opcode(_, 2) => self.adh = self.consume_program_byte()?,
             SequenceState::Opcode(_, 3) => {
-                self.tmp_data = self.memory.read(self.address())?;
-            }
-            SequenceState::Opcode(_, 4) => {
-                // Phantom write.
-                self.memory.write(self.address(), self.tmp_data)?;
+                self.tmp_data = self.read_memory(self.address())?;
             }
+            SequenceState::Opcode(_, 4) => self.modify_dummy_cycle(self.address())?,
             _ => {
-                let result = operation(self, self.tmp_data);
-                self.memory.write(self.address(), result)?;
+                let result = self.execute_modify(operation, self.tmp_data);
+                self.write_memory(self.address(), result)?;
                 self.sequence_state = SequenceState::Ready;
             }
         }
@@ -1228,7 +1048,7 @@ impl<M: Memory + Debug> Cpu<M> {
     fn tick_load_modify_store_absolute_indexed(
         &mut self,
         index: u8,
-        operation: &mut dyn FnMut(&mut Self, u8) -> u8,
+        operation: ModifyOperation,
     ) -> TickResult {
         match self.sequence_state {
             SequenceState::Opcode(_, 1) => self.bal = self.consume_program_byte()?,
@@ -1237,60 +1057,78 @@ impl<M: Memory + Debug> Cpu<M> {
                 self.phantom_read(u16::from_le_bytes([self.bal.wrapping_add(index), self.bah]));
             }
             SequenceState::Opcode(_, 4) => {
-                self.tmp_data = self
-                    .memory
-                    .read(self.base_address().wrapping_add(index as u16))?;
+                self.tmp_data = self.read_memory(self.base_address().wrapping_add(index as u16))?;
             }
             SequenceState::Opcode(_, 5) => {
-                // Phantom write.
-                self.memory.write(
-                    self.base_address().wrapping_add(index as u16),
-                    self.tmp_data,
-                )?;
+                self.modify_dummy_cycle(self.base_address().wrapping_add(index as u16))?
             }
             _ => {
-                let result = operation(self, self.tmp_data);
-                self.memory
-                    .write(self.base_address().wrapping_add(index as u16), result)?;
+                let result = self.execute_modify(operation, self.tmp_data);
+                self.write_memory(self.base_address().wrapping_add(index as u16), result)?;
                 self.sequence_state = SequenceState::Ready;
             }
         }
         Ok(())
     }
 
-    fn tick_compare_immediate(&mut self, register: u8) -> Result<(), ReadError> {
-        self.tick_load_immediate(&mut |me, value| me.compare(register, value))
-    }
-
-    fn tick_compare_zero_page(&mut self, register: u8) -> Result<(), ReadError> {
-        self.tick_load_zero_page(&mut |me, value| me.compare(register, value))
-    }
-
-    fn tick_compare_zero_page_x(&mut self, register: u8) -> Result<(), ReadError> {
-        self.tick_load_zero_page_indexed(self.reg_x, &mut |me, value| me.compare(register, value))
-    }
-
-    fn tick_compare_absolute(&mut self, register: u8) -> Result<(), ReadError> {
-        self.tick_load_absolute(&mut |me, value| me.compare(register, value))
-    }
-
-    fn tick_compare_absolute_indexed(&mut self, index: u8, register: u8) -> Result<(), ReadError> {
-        self.tick_load_absolute_indexed(index, &mut |me, value| me.compare(register, value))
-    }
-
-    fn tick_compare_x_indirect(&mut self, register: u8) -> Result<(), ReadError> {
-        self.tick_load_x_indirect(&mut |me, value| me.compare(register, value))
+    fn tick_load_modify_store_x_indirect(&mut self, operation: ModifyOperation) -> TickResult {
+        match self.sequence_state {
+            SequenceState::Opcode(_, 1) => self.bal = self.consume_program_byte()?,
+            SequenceState::Opcode(_, 2) => self.phantom_read(self.bal as u16),
+            SequenceState::Opcode(_, 3) => {
+                self.adl = self.read_memory(self.bal.wrapping_add(self.reg_x) as u16)?;
+            }
+            SequenceState::Opcode(_, 4) => {
+                self.adh =
+                    self.read_memory(self.bal.wrapping_add(self.reg_x).wrapping_add(1) as u16)?;
+            }
+            SequenceState::Opcode(_, 5) => {
+                self.tmp_data = self.read_memory(self.address())?;
+            }
+            SequenceState::Opcode(_, 6) => self.modify_dummy_cycle(self.address())?,
+            _ => {
+                let result = self.execute_modify(operation, self.tmp_data);
+                self.write_memory(self.address(), result)?;
+                self.sequence_state = SequenceState::Ready;
+            }
+        }
+        Ok(())
     }
 
-    fn tick_compare_indirect_y(&mut self, register: u8) -> Result<(), ReadError> {
-        self.tick_load_indirect_y(&mut |me, value| me.compare(register, value))
+    fn tick_load_modify_store_indirect_y(&mut self, operation: ModifyOperation) -> TickResult {
+        match self.sequence_state {
+            SequenceState::Opcode(_, 1) => self.ial = self.consume_program_byte()?,
+            SequenceState::Opcode(_, 2) => self.bal = self.read_memory(self.ial as u16)?,
+            SequenceState::Opcode(_, 3) => {
+                self.bah = self.read_memory(self.ial.wrapping_add(1) as u16)?
+            }
+            SequenceState::Opcode(_, 4) => {
+                self.phantom_read(u16::from_le_bytes([
+                    self.bal.wrapping_add(self.reg_y),
+                    self.bah,
+                ]));
+            }
+            SequenceState::Opcode(_, 5) => {
+                self.tmp_data =
+                    self.read_memory(self.base_address().wrapping_add(self.reg_y as u16))?;
+            }
+            SequenceState::Opcode(_, 6) => {
+                self.modify_dummy_cycle(self.base_address().wrapping_add(self.reg_y as u16))?
+            }
+            _ => {
+                let result = self.execute_modify(operation, self.tmp_data);
+                self.write_memory(self.base_address().wrapping_add(self.reg_y as u16), result)?;
+                self.sequence_state = SequenceState::Ready;
+            }
+        }
+        Ok(())
     }
 
     fn tick_push(&mut self, value: u8) -> TickResult {
         match self.sequence_state {
             SequenceState::Opcode(_, 1) => self.phantom_read(self.reg_pc),
             _ => {
-                self.memory.write(self.stack_pointer(), value)?;
+                self.write_memory(self.stack_pointer(), value)?;
                 self.reg_sp = self.reg_sp.wrapping_sub(1);
                 self.sequence_state = SequenceState::Ready;
             }
@@ -1306,7 +1144,7 @@ impl<M: Memory + Debug> Cpu<M> {
                 self.reg_sp = self.reg_sp.wrapping_add(1);
             }
             _ => {
-                let value = self.memory.read(self.stack_pointer())?;
+                let value = self.read_memory(self.stack_pointer())?;
                 load(self, value);
                 self.sequence_state = SequenceState::Ready;
             }
@@ -1351,32 +1189,85 @@ impl<M: Memory + Debug> Cpu<M> {
         match subcycle {
             1 => self.phantom_read(self.reg_pc),
             2 => {
-                self.memory
-                    .write(self.stack_pointer(), (self.reg_pc >> 8) as u8)?;
+                self.write_memory(self.stack_pointer(), (self.reg_pc >> 8) as u8)?;
                 self.reg_sp = self.reg_sp.wrapping_sub(1);
             }
             3 => {
-                self.memory.write(self.stack_pointer(), self.reg_pc as u8)?;
+                self.write_memory(self.stack_pointer(), self.reg_pc as u8)?;
                 self.reg_sp = self.reg_sp.wrapping_sub(1);
             }
             4 => {
-                self.memory
-                    .write(self.stack_pointer(), self.flags | flag_mask)?;
+                self.write_memory(self.stack_pointer(), self.flags | flag_mask)?;
                 self.reg_sp = self.reg_sp.wrapping_sub(1);
             }
-            5 => self.reg_pc = self.reg_pc & 0xFF00 | (self.memory.read(vector)? as u16),
+            5 => self.reg_pc = self.reg_pc & 0xFF00 | (self.read_memory(vector)? as u16),
             _ => {
-                self.reg_pc = self.reg_pc & 0xFF | ((self.memory.read(vector + 1)? as u16) << 8);
+                self.reg_pc = self.reg_pc & 0xFF | ((self.read_memory(vector + 1)? as u16) << 8);
                 self.sequence_state = SequenceState::Ready;
                 self.flags |= flags::I;
+                if self.variant == CpuVariant::Cmos65C02 {
+                    self.flags &= !flags::D;
+                }
             }
         }
         Ok(())
     }
 
+    /// Reads a byte from the memory and records the access.
+    fn read_memory(&mut self, address: u16) -> ReadResult {
+        let value = self.memory.read(address)?;
+        self.record_bus_cycle(MemoryAccess::Read(address), value);
+        return Ok(value);
+    }
+
+    /// Writes a byte to the memory and records the access.
+    fn write_memory(&mut self, address: u16, value: u8) -> WriteResult {
+        self.record_bus_cycle(MemoryAccess::Write(address), value);
+        return match self.memory.write(address, value) {
+            Err(e) if self.permissive => {
+                self.ignore_error(e.into());
+                Ok(())
+            }
+            result => result,
+        };
+    }
+
+    /// Performs the cycle of a read-modify-write instruction in which the value
+    /// is being modified. NMOS 6502 writes the unmodified value back, a rare
+    /// case of a "phantom write". Since it writes the same data, it doesn't
+    /// really matter (that much), but we need to simulate it anyway. 65C02
+    /// reads the address again instead.
+    fn modify_dummy_cycle(&mut self, address: u16) -> WriteResult {
+        match self.variant {
+            CpuVariant::Nmos6502 => self.write_memory(address, self.tmp_data),
+            CpuVariant::Cmos65C02 => {
+                self.phantom_read(address);
+                Ok(())
+            }
+        }
+    }
+
+    /// Counts an error that has been ignored in the permissive mode, and
+    /// reports it unless the same error has already been reported.
+    fn ignore_error(&mut self, error: CpuError) {
+        self.ignored_error_count += 1;
+        if self.reported_errors.insert(error) {
+            eprintln!("WARNING: {}. Ignored in the permissive mode.", error);
+        }
+    }
+
+    fn record_bus_cycle(&mut self, access: MemoryAccess, value: u8) {
+        self.last_bus_cycle = Some(BusCycle {
+            access,
+            value,
+            // The counter has already been advanced by `tick`.
+            cycle: self.cycles - 1,
+        });
+    }
+
     /// Reads one byte from the program and advances the program counter.
     fn consume_program_byte(&mut self) -> ReadResult {
-        let result = self.memory.read(self.reg_pc)?;
+        let result = self.read_memory(self.reg_pc)?;
         self.reg_pc = self.reg_pc.wrapping_add(1);
         return Ok(result);
     }
@@ -1386,7 +1277,116 @@ impl<M: Memory + Debug> Cpu<M> {
     /// we don't use the result value, we don't even care if it was a read
     /// error.
     fn phantom_read(&mut self, address: u16) {
-        let _ = self.memory.read(address);
+        let _ = self.read_memory(address);
+    }
+
+    fn execute_internal(&mut self, operation: InternalOperation) {
+        match operation {
+            InternalOperation::Nop => {}
+            InternalOperation::Inx => self.set_reg_x(self.reg_x.wrapping_add(1)),
+            InternalOperation::Iny => self.set_reg_y(self.reg_y.wrapping_add(1)),
+            InternalOperation::Dex => self.set_reg_x(self.reg_x.wrapping_sub(1)),
+            InternalOperation::Dey => self.set_reg_y(self.reg_y.wrapping_sub(1)),
+            InternalOperation::Tax => self.set_reg_x(self.reg_a),
+            InternalOperation::Tay => self.set_reg_y(self.reg_a),
+            InternalOperation::Txa => self.set_reg_a(self.reg_x),
+            InternalOperation::Tya => self.set_reg_a(self.reg_y),
+            InternalOperation::Txs => self.reg_sp = self.reg_x,
+            InternalOperation::Tsx => self.set_reg_x(self.reg_sp),
+            InternalOperation::Sei => self.flags |= flags::I,
+            InternalOperation::Cli => self.flags &= !flags::I,
+            InternalOperation::Sed => self.flags |= flags::D,
+            InternalOperation::Cld => self.flags &= !flags::D,
+            InternalOperation::Sec => self.flags |= flags::C,
+            InternalOperation::Clc => self.flags &= !flags::C,
+            InternalOperation::Clv => self.flags &= !flags::V,
+            InternalOperation::AslA => {
+                let shifted = self.shift_left(self.reg_a);
+                self.set_reg_a(shifted);
+            }
+            InternalOperation::LsrA => {
+                let shifted = self.shift_right(self.reg_a);
+                self.set_reg_a(shifted);
+            }
+            InternalOperation::RolA => {
+                let rotated = self.rotate_left(self.reg_a);
+                self.set_reg_a(rotated);
+            }
+            InternalOperation::RorA => {
+                let rotated = self.rotate_right(self.reg_a);
+                self.set_reg_a(rotated);
+            }
+            InternalOperation::IncA => self.set_reg_a(self.reg_a.wrapping_add(1)),
+            InternalOperation::DecA => self.set_reg_a(self.reg_a.wrapping_sub(1)),
+        }
+    }
+
+    fn execute_read(&mut self, operation: ReadOperation, value: u8) {
+        match operation {
+            ReadOperation::Lda => self.set_reg_a(value),
+            ReadOperation::Ldx => self.set_reg_x(value),
+            ReadOperation::Ldy => self.set_reg_y(value),
+            ReadOperation::And => self.set_reg_a(self.reg_a & value),
+            ReadOperation::Ora => self.set_reg_a(self.reg_a | value),
+            ReadOperation::Eor => self.set_reg_a(self.reg_a ^ value),
+            ReadOperation::Adc => {
+                let sum = self.add_with_carry(self.reg_a, value);
+                self.set_reg_a(sum);
+            }
+            ReadOperation::Sbc => {
+                let diff = self.sub_with_carry(self.reg_a, value);
+                self.set_reg_a(diff);
+            }
+            ReadOperation::Cmp => self.compare(self.reg_a, value),
+            ReadOperation::Cpx => self.compare(self.reg_x, value),
+            ReadOperation::Cpy => self.compare(self.reg_y, value),
+            ReadOperation::Bit => self.test_bits(value),
+            ReadOperation::Nop => {}
+            ReadOperation::BitImmediate => self.test_bits_z(value),
+            ReadOperation::Lax => self.load_a_and_x(value),
+            ReadOperation::Anc => {
+                self.set_reg_a(self.reg_a & value);
+                // Copy the N flag to C.
+                self.flags = self.flags & !flags::C | (self.flags & flags::N) >> 7;
+            }
+            ReadOperation::Alr => {
+                let shifted = self.shift_right(self.reg_a & value);
+                self.set_reg_a(shifted);
+            }
+            ReadOperation::Arr => self.reg_a = self.and_rotate_right(value),
+            ReadOperation::Axs => {
+                let masked = self.reg_a & self.reg_x;
+                self.compare(masked, value);
+                self.reg_x = masked.wrapping_sub(value);
+            }
+        }
+    }
+
+    /// Performs a read-modify-write operation and returns the value to be
+    /// written back to the memory.
+    fn execute_modify(&mut self, operation: ModifyOperation, value: u8) -> u8 {
+        match operation {
+            ModifyOperation::Asl => self.shift_left(value),
+            ModifyOperation::Lsr => self.shift_right(value),
+            ModifyOperation::Rol => self.rotate_left(value),
+            ModifyOperation::Ror => self.rotate_right(value),
+            ModifyOperation::Inc => self.inc(value),
+            ModifyOperation::Dec => self.dec(value),
+            ModifyOperation::Tsb => {
+                self.test_bits_z(value);
+                value | self.reg_a
+            }
+            ModifyOperation::Trb => {
+                self.test_bits_z(value);
+                value & !self.reg_a
+            }
+            ModifyOperation::Dcp => self.dec_compare(value),
+            ModifyOperation::Isc => self.inc_subtract(value),
+            ModifyOperation::Slo => self.shift_left_or(value),
+            ModifyOperation::Rla => self.rotate_left_and(value),
+            ModifyOperation::Sre => self.shift_right_eor(value),
+            ModifyOperation::Rra => self.rotate_right_add(value),
+        }
     }
 
     fn set_reg_a(&mut self, value: u8) {
@@ -1423,6 +1423,11 @@ impl<M: Memory + Debug> Cpu<M> {
             | if value & self.reg_a == 0 { flags::Z } else { 0 };
     }
 
+    /// Updates only the Z flag by performing an AND with the accumulator.
+    fn test_bits_z(&mut self, value: u8) {
+        self.flags = self.flags & !flags::Z | if value & self.reg_a == 0 { flags::Z } else { 0 };
+    }
+
     /// Calculates lhs+rhs+C, updates the C and V flags, and returns the result.
     /// The V flag is not set in BCD mode, which is not how the real CPU works,
     /// but it's undefined anyway.
@@ -1544,6 +1549,95 @@ impl<M: Memory + Debug> Cpu<M> {
         result
     }
 
+    /// Loads the same value to both A and X registers (LAX).
+    fn load_a_and_x(&mut self, value: u8) {
+        self.reg_a = value;
+        self.set_reg_x(value);
+    }
+
+    /// Decrements a value and compares it with the accumulator (DCP).
+    fn dec_compare(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_sub(1);
+        self.compare(self.reg_a, result);
+        result
+    }
+
+    /// Increments a value and subtracts it from the accumulator (ISC).
+    fn inc_subtract(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_add(1);
+        let diff = self.sub_with_carry(self.reg_a, result);
+        self.set_reg_a(diff);
+        result
+    }
+
+    /// Shifts a value left and ORs it with the accumulator (SLO).
+    fn shift_left_or(&mut self, value: u8) -> u8 {
+        let result = self.shift_left(value);
+        self.set_reg_a(self.reg_a | result);
+        result
+    }
+
+    /// Rotates a value left and ANDs it with the accumulator (RLA).
+    fn rotate_left_and(&mut self, value: u8) -> u8 {
+        let result = self.rotate_left(value);
+        self.set_reg_a(self.reg_a & result);
+        result
+    }
+
+    /// Shifts a value right and XORs it with the accumulator (SRE).
+    fn shift_right_eor(&mut self, value: u8) -> u8 {
+        let result = self.shift_right(value);
+        self.set_reg_a(self.reg_a ^ result);
+        result
+    }
+
+    /// Rotates a value right and adds it to the accumulator (RRA). The carry
+    /// flag shifted out of the value is used as an input for the addition.
+    fn rotate_right_add(&mut self, value: u8) -> u8 {
+        let result = self.rotate_right(value);
+        let sum = self.add_with_carry(self.reg_a, result);
+        self.set_reg_a(sum);
+        result
+    }
+
+    /// ANDs the accumulator with a value and rotates the result right (ARR).
+    /// Flags are affected in a weird way: C is copied from bit 6 of the
+    /// result, and V is bit 6 XOR bit 5. In decimal mode, the result
+    /// additionally gets a half-baked BCD correction.
+    fn and_rotate_right(&mut self, value: u8) -> u8 {
+        let and_result = self.reg_a & value;
+        let prev_carry = self.flags & flags::C;
+        let mut result = (and_result >> 1) | (prev_carry << 7);
+        self.update_flags_nz(result);
+        if self.flags & flags::D == 0 {
+            let bit6 = (result >> 6) & 1;
+            let bit5 = (result >> 5) & 1;
+            self.flags = self.flags & !(flags::C | flags::V)
+                | if bit6 != 0 { flags::C } else { 0 }
+                | if bit6 ^ bit5 != 0 { flags::V } else { 0 };
+            return result;
+        }
+
+        self.flags = self.flags & !flags::V
+            | if (and_result ^ result) & (1 << 6) != 0 {
+                flags::V
+            } else {
+                0
+            };
+        let low_nibble = and_result & 0x0F;
+        let high_nibble = and_result >> 4;
+        if low_nibble + (low_nibble & 1) > 5 {
+            result = (result & 0xF0) | (result.wrapping_add(6) & 0x0F);
+        }
+        if high_nibble + (high_nibble & 1) > 5 {
+            self.flags |= flags::C;
+            result = result.wrapping_add(0x60);
+        } else {
+            self.flags &= !flags::C;
+        }
+        return result;
+    }
+
     fn stack_pointer(&self) -> u16 {
         0x100 | self.reg_sp as u16
     }
@@ -1593,9 +1687,46 @@ pub trait MachineInspector {
     fn flags(&self) -> u8;
     fn at_instruction_start(&self) -> bool;
     fn inspect_memory(&self, address: u16) -> u8;
+    /// Returns the memory access performed by the CPU during the last cycle.
+    fn last_memory_access(&self) -> Option<MemoryAccess> {
+        self.last_bus_cycle().map(|bus_cycle| bus_cycle.access)
+    }
+    /// Returns the memory access performed by the CPU during the last cycle,
+    /// along with the transferred value and the cycle number.
+    fn last_bus_cycle(&self) -> Option<BusCycle>;
+    /// Returns the number of CPU cycles performed since the machine has been
+    /// turned on.
+    fn cycles(&self) -> u64;
+    /// Returns the interrupt whose sequence is currently being performed by the
+    /// CPU, or `None` if the CPU executes a regular instruction.
+    fn current_interrupt(&self) -> Option<Interrupt> {
+        None
+    }
+    /// Overwrites a register. 8-bit registers only use the lower byte of the
+    /// value. Setting the program counter aborts the current instruction.
+    fn set_register(&mut self, register: Register, value: u16);
+    /// Writes a byte to the memory on behalf of a debugger. Fails if the
+    /// memory doesn't accept writes at a given address.
+    fn poke_memory(&mut self, address: u16, value: u8) -> WriteResult;
+    /// Returns register snapshots of all support chips of the machine. A bare
+    /// CPU doesn't have any. Fails if any of the chip registers can't be
+    /// inspected.
+    fn chip_states(&self) -> Result<Vec<ChipState>, ReadError> {
+        Ok(vec![])
+    }
+    /// Returns the current layout of the CPU address space. See
+    /// [`Inspect::memory_map`].
+    fn memory_map(&self) -> Vec<MemoryRegion> {
+        vec![]
+    }
+    /// Returns the number of errors that have been ignored in the permissive
+    /// mode. See [`Cpu::set_permissive`].
+    fn ignored_error_count(&self) -> u64 {
+        0
+    }
 }
 
-impl<M: Memory + Inspect> MachineInspector for Cpu<M> {
+impl<M: Memory + Inspect + Debug> MachineInspector for Cpu<M> {
     fn reg_pc(&self) -> u16 {
         self.reg_pc
     }
@@ -1627,4 +1758,135 @@ impl<M: Memory + Inspect> MachineInspector for Cpu<M> {
     fn inspect_memory(&self, address: u16) -> u8 {
         self.memory.inspect(address).unwrap_or(0xFF)
     }
+
+    fn last_bus_cycle(&self) -> Option<BusCycle> {
+        self.last_bus_cycle
+    }
+
+    fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    fn current_interrupt(&self) -> Option<Interrupt> {
+        match self.sequence_state {
+            SequenceState::Opcode(opcode, _) => match self.instructions[opcode as usize] {
+                Instruction::Brk => Some(Interrupt::Brk),
+                _ => None,
+            },
+            SequenceState::Irq(_) => Some(Interrupt::Irq),
+            SequenceState::Nmi(_) => Some(Interrupt::Nmi),
+            _ => None,
+        }
+    }
+
+    fn set_register(&mut self, register: Register, value: u16) {
+        match register {
+            Register::A => self.reg_a = value as u8,
+            Register::X => self.reg_x = value as u8,
+            Register::Y => self.reg_y = value as u8,
+            Register::SP => self.reg_sp = value as u8,
+            Register::PC => self.jump_to(value),
+            Register::Flags => self.flags = value as u8,
+        }
+    }
+
+    fn poke_memory(&mut self, address: u16, value: u8) -> WriteResult {
+        self.memory.write(address, value)
+    }
+
+    fn memory_map(&self) -> Vec<MemoryRegion> {
+        self.memory.memory_map()
+    }
+
+    fn ignored_error_count(&self) -> u64 {
+        self.ignored_error_count
+    }
+}
+
+/// A machine that consists of a CPU and other chips. Since the CPU is usually
+/// clocked slower than the rest of the machine, not every machine tick is a CPU
+/// cycle. Implementing this trait provides a [`MachineInspector`]
+/// implementation that delegates to the CPU, and so inspects the memory
+/// through the [`Inspect`] trait of the entire address space.
+pub trait CompositeMachine {
+    type Memory: Memory + Inspect + Debug;
+
+    fn cpu(&self) -> &Cpu<Self::Memory>;
+    fn mut_cpu(&mut self) -> &mut Cpu<Self::Memory>;
+    /// Returns `true` if the last machine tick was also a CPU cycle.
+    fn at_cpu_cycle(&self) -> bool;
+    /// See [`MachineInspector::chip_states`].
+    fn chip_states(&self) -> Result<Vec<ChipState>, ReadError> {
+        Ok(vec![])
+    }
+}
+
+impl<T: CompositeMachine> MachineInspector for T {
+    fn reg_pc(&self) -> u16 {
+        self.cpu().reg_pc()
+    }
+
+    fn reg_a(&self) -> u8 {
+        self.cpu().reg_a()
+    }
+
+    fn reg_x(&self) -> u8 {
+        self.cpu().reg_x()
+    }
+
+    fn reg_y(&self) -> u8 {
+        self.cpu().reg_y()
+    }
+
+    fn reg_sp(&self) -> u8 {
+        self.cpu().reg_sp()
+    }
+
+    fn flags(&self) -> u8 {
+        self.cpu().flags()
+    }
+
+    fn at_instruction_start(&self) -> bool {
+        self.at_cpu_cycle() && self.cpu().at_instruction_start()
+    }
+
+    fn inspect_memory(&self, address: u16) -> u8 {
+        self.cpu().inspect_memory(address)
+    }
+
+    fn last_bus_cycle(&self) -> Option<BusCycle> {
+        if self.at_cpu_cycle() {
+            self.cpu().last_bus_cycle()
+        } else {
+            None
+        }
+    }
+
+    fn cycles(&self) -> u64 {
+        self.cpu().cycles()
+    }
+
+    fn current_interrupt(&self) -> Option<Interrupt> {
+        self.cpu().current_interrupt()
+    }
+
+    fn set_register(&mut self, register: Register, value: u16) {
+        self.mut_cpu().set_register(register, value)
+    }
+
+    fn poke_memory(&mut self, address: u16, value: u8) -> WriteResult {
+        self.mut_cpu().poke_memory(address, value)
+    }
+
+    fn chip_states(&self) -> Result<Vec<ChipState>, ReadError> {
+        CompositeMachine::chip_states(self)
+    }
+
+    fn memory_map(&self) -> Vec<MemoryRegion> {
+        self.cpu().memory_map()
+    }
+
+    fn ignored_error_count(&self) -> u64 {
+        self.cpu().ignored_error_count()
+    }
 }
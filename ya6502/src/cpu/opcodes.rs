@@ -177,4 +177,138 @@ pub const RTS: u8 = 0x60;
 pub const BRK: u8 = 0x00;
 pub const RTI: u8 = 0x40;
 
+// Unofficial opcodes.
+
 pub const HLT1: u8 = 0x02;
+
+pub const LAX_ZP: u8 = 0xA7;
+pub const LAX_ZP_Y: u8 = 0xB7;
+pub const LAX_ABS: u8 = 0xAF;
+pub const LAX_ABS_Y: u8 = 0xBF;
+pub const LAX_X_INDIR: u8 = 0xA3;
+pub const LAX_INDIR_Y: u8 = 0xB3;
+
+pub const SAX_ZP: u8 = 0x87;
+pub const SAX_ZP_Y: u8 = 0x97;
+pub const SAX_ABS: u8 = 0x8F;
+pub const SAX_X_INDIR: u8 = 0x83;
+
+pub const DCP_ZP: u8 = 0xC7;
+pub const DCP_ZP_X: u8 = 0xD7;
+pub const DCP_ABS: u8 = 0xCF;
+pub const DCP_ABS_X: u8 = 0xDF;
+pub const DCP_ABS_Y: u8 = 0xDB;
+pub const DCP_X_INDIR: u8 = 0xC3;
+pub const DCP_INDIR_Y: u8 = 0xD3;
+
+pub const ISC_ZP: u8 = 0xE7;
+pub const ISC_ZP_X: u8 = 0xF7;
+pub const ISC_ABS: u8 = 0xEF;
+pub const ISC_ABS_X: u8 = 0xFF;
+pub const ISC_ABS_Y: u8 = 0xFB;
+pub const ISC_X_INDIR: u8 = 0xE3;
+pub const ISC_INDIR_Y: u8 = 0xF3;
+
+pub const SLO_ZP: u8 = 0x07;
+pub const SLO_ZP_X: u8 = 0x17;
+pub const SLO_ABS: u8 = 0x0F;
+pub const SLO_ABS_X: u8 = 0x1F;
+pub const SLO_ABS_Y: u8 = 0x1B;
+pub const SLO_X_INDIR: u8 = 0x03;
+pub const SLO_INDIR_Y: u8 = 0x13;
+
+pub const RLA_ZP: u8 = 0x27;
+pub const RLA_ZP_X: u8 = 0x37;
+pub const RLA_ABS: u8 = 0x2F;
+pub const RLA_ABS_X: u8 = 0x3F;
+pub const RLA_ABS_Y: u8 = 0x3B;
+pub const RLA_X_INDIR: u8 = 0x23;
+pub const RLA_INDIR_Y: u8 = 0x33;
+
+pub const SRE_ZP: u8 = 0x47;
+pub const SRE_ZP_X: u8 = 0x57;
+pub const SRE_ABS: u8 = 0x4F;
+pub const SRE_ABS_X: u8 = 0x5F;
+pub const SRE_ABS_Y: u8 = 0x5B;
+pub const SRE_X_INDIR: u8 = 0x43;
+pub const SRE_INDIR_Y: u8 = 0x53;
+
+pub const RRA_ZP: u8 = 0x67;
+pub const RRA_ZP_X: u8 = 0x77;
+pub const RRA_ABS: u8 = 0x6F;
+pub const RRA_ABS_X: u8 = 0x7F;
+pub const RRA_ABS_Y: u8 = 0x7B;
+pub const RRA_X_INDIR: u8 = 0x63;
+pub const RRA_INDIR_Y: u8 = 0x73;
+
+pub const ANC_IMM_0B: u8 = 0x0B;
+pub const ANC_IMM_2B: u8 = 0x2B;
+pub const ALR_IMM: u8 = 0x4B;
+pub const ARR_IMM: u8 = 0x6B;
+pub const AXS_IMM: u8 = 0xCB;
+pub const SBC_IMM_EB: u8 = 0xEB;
+
+pub const NOP_1A: u8 = 0x1A;
+pub const NOP_3A: u8 = 0x3A;
+pub const NOP_5A: u8 = 0x5A;
+pub const NOP_7A: u8 = 0x7A;
+pub const NOP_DA: u8 = 0xDA;
+pub const NOP_FA: u8 = 0xFA;
+pub const NOP_IMM_80: u8 = 0x80;
+pub const NOP_IMM_82: u8 = 0x82;
+pub const NOP_IMM_89: u8 = 0x89;
+pub const NOP_IMM_C2: u8 = 0xC2;
+pub const NOP_IMM_E2: u8 = 0xE2;
+pub const NOP_ZP_04: u8 = 0x04;
+pub const NOP_ZP_44: u8 = 0x44;
+pub const NOP_ZP_64: u8 = 0x64;
+pub const NOP_ZP_X_14: u8 = 0x14;
+pub const NOP_ZP_X_34: u8 = 0x34;
+pub const NOP_ZP_X_54: u8 = 0x54;
+pub const NOP_ZP_X_74: u8 = 0x74;
+pub const NOP_ZP_X_D4: u8 = 0xD4;
+pub const NOP_ZP_X_F4: u8 = 0xF4;
+pub const NOP_ABS_0C: u8 = 0x0C;
+pub const NOP_ABS_X_1C: u8 = 0x1C;
+pub const NOP_ABS_X_3C: u8 = 0x3C;
+pub const NOP_ABS_X_5C: u8 = 0x5C;
+pub const NOP_ABS_X_7C: u8 = 0x7C;
+pub const NOP_ABS_X_DC: u8 = 0xDC;
+pub const NOP_ABS_X_FC: u8 = 0xFC;
+
+// 65C02 opcodes. These are only valid in the CMOS mode; the NMOS CPU
+// interprets them as unofficial instructions.
+
+pub const PHX: u8 = 0xDA;
+pub const PLX: u8 = 0xFA;
+pub const PHY: u8 = 0x5A;
+pub const PLY: u8 = 0x7A;
+
+pub const STZ_ZP: u8 = 0x64;
+pub const STZ_ZP_X: u8 = 0x74;
+pub const STZ_ABS: u8 = 0x9C;
+pub const STZ_ABS_X: u8 = 0x9E;
+
+pub const BRA: u8 = 0x80;
+pub const JMP_ABS_X_INDIR: u8 = 0x7C;
+
+pub const INC_A: u8 = 0x1A;
+pub const DEC_A: u8 = 0x3A;
+
+pub const TSB_ZP: u8 = 0x04;
+pub const TSB_ABS: u8 = 0x0C;
+pub const TRB_ZP: u8 = 0x14;
+pub const TRB_ABS: u8 = 0x1C;
+
+pub const BIT_IMM: u8 = 0x89;
+pub const BIT_ZP_X: u8 = 0x34;
+pub const BIT_ABS_X: u8 = 0x3C;
+
+pub const ORA_ZP_INDIR: u8 = 0x12;
+pub const AND_ZP_INDIR: u8 = 0x32;
+pub const EOR_ZP_INDIR: u8 = 0x52;
+pub const ADC_ZP_INDIR: u8 = 0x72;
+pub const STA_ZP_INDIR: u8 = 0x92;
+pub const LDA_ZP_INDIR: u8 = 0xB2;
+pub const CMP_ZP_INDIR: u8 = 0xD2;
+pub const SBC_ZP_INDIR: u8 = 0xF2;
@@ -5,6 +5,8 @@ extern crate test;
 use super::*;
 use crate::cpu_with_code;
 use crate::memory::Ram;
+use crate::memory::{Read, Write, WriteError};
+use crate::test_utils::cmos_cpu_with_program;
 use crate::test_utils::cpu_with_program;
 use crate::test_utils::reset;
 use test::Bencher;
@@ -54,6 +56,27 @@ fn it_resets() {
     assert_eq!(cpu.memory.bytes[0], 2, "the second program wasn't executed");
 }
 
+#[test]
+fn seeded_power_on_state() {
+    let registers = |seed| {
+        let cpu = Cpu::with_power_on_state(
+            Box::new(Ram::new(16)),
+            CpuVariant::Nmos6502,
+            PowerOnState::Seeded(seed),
+        );
+        (
+            cpu.reg_pc(),
+            cpu.reg_a(),
+            cpu.reg_x(),
+            cpu.reg_y(),
+            cpu.reg_sp(),
+            cpu.flags(),
+        )
+    };
+    assert_eq!(registers(1), registers(1));
+    assert_ne!(registers(1), registers(2));
+}
+
 #[test]
 fn nop() {
     let mut cpu = cpu_with_code! {
@@ -1774,6 +1797,37 @@ fn nmi() {
     assert_eq!(cpu.memory.bytes[10..=15], [8, 2, 8, 0, 0, 0]);
 }
 
+#[test]
+fn reports_current_interrupt() {
+    let mut cpu = cpu_with_program(&[opcodes::CLI, opcodes::BRK, 0x00]);
+    let memory = cpu.mut_memory();
+    memory.bytes[0xFFFA..=0xFFFB].copy_from_slice(&[0x00, 0xF0]);
+    memory.bytes[0xFFFE..=0xFFFF].copy_from_slice(&[0x00, 0xF0]);
+    cpu.ticks(2).unwrap();
+    assert_eq!(cpu.current_interrupt(), None);
+
+    cpu.ticks(1).unwrap();
+    assert_eq!(cpu.current_interrupt(), Some(Interrupt::Brk));
+    cpu.ticks(6).unwrap();
+    assert_eq!(cpu.current_interrupt(), None);
+    assert_eq!(cpu.reg_pc(), 0xF000);
+
+    cpu.set_nmi_pin(true);
+    cpu.ticks(1).unwrap();
+    assert_eq!(cpu.current_interrupt(), Some(Interrupt::Nmi));
+    cpu.ticks(6).unwrap();
+    assert_eq!(cpu.current_interrupt(), None);
+    assert_eq!(cpu.reg_pc(), 0xF000);
+
+    // Interrupts are disabled now, so we need to execute CLI first.
+    cpu.ticks(2).unwrap();
+    cpu.set_irq_pin(true);
+    cpu.ticks(1).unwrap();
+    assert_eq!(cpu.current_interrupt(), Some(Interrupt::Irq));
+    cpu.ticks(6).unwrap();
+    assert_eq!(cpu.current_interrupt(), None);
+}
+
 #[test]
 fn irq_masking() {
     let mut cpu = cpu_with_code! {
@@ -1844,6 +1898,307 @@ fn i_flag_inside_interrupt_handlers() {
     );
 }
 
+#[test]
+fn lax() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::LAX_ZP, 10, // 3 cycles
+        opcodes::STA_ZP, 20, // 3 cycles
+        opcodes::STX_ZP, 21, // 3 cycles
+        opcodes::LDY_IMM, 1, // 2 cycles
+        opcodes::LAX_ZP_Y, 10, // 4 cycles
+        opcodes::STX_ZP, 22, // 3 cycles
+        opcodes::LAX_ABS, 0x34, 0x12, // 4 cycles
+        opcodes::STX_ZP, 23, // 3 cycles
+        opcodes::LAX_ABS_Y, 0x34, 0x12, // 4 cycles
+        opcodes::STX_ZP, 24, // 3 cycles
+    ]);
+    cpu.mut_memory().bytes[10..=11].copy_from_slice(&[0x12, 0x34]);
+    cpu.mut_memory().bytes[0x1234..=0x1235].copy_from_slice(&[0x56, 0x78]);
+    cpu.ticks(3 + 3 + 3 + 2 + 4 + 3 + 4 + 3 + 4 + 3).unwrap();
+    assert_eq!(cpu.memory.bytes[20..=24], [0x12, 0x12, 0x34, 0x56, 0x78]);
+}
+
+#[test]
+fn lax_indirect() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::LDX_IMM, 2, // 2 cycles
+        opcodes::LDY_IMM, 1, // 2 cycles
+        opcodes::LAX_X_INDIR, 8, // 6 cycles
+        opcodes::STA_ZP, 20, // 3 cycles
+        opcodes::LAX_INDIR_Y, 10, // 5 cycles
+        opcodes::STX_ZP, 21, // 3 cycles
+    ]);
+    cpu.mut_memory().bytes[10..=11].copy_from_slice(&[0x34, 0x12]);
+    cpu.mut_memory().bytes[0x1234..=0x1235].copy_from_slice(&[0x56, 0x78]);
+    cpu.ticks(2 + 2 + 6 + 3 + 5 + 3).unwrap();
+    assert_eq!(cpu.memory.bytes[20..=21], [0x56, 0x78]);
+}
+
+#[test]
+fn sax() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::LDA_IMM, 0b1100_1100, // 2 cycles
+        opcodes::LDX_IMM, 0b1010_1010, // 2 cycles
+        opcodes::LDY_IMM, 1, // 2 cycles
+        opcodes::SAX_ZP, 20, // 3 cycles
+        opcodes::SAX_ZP_Y, 20, // 4 cycles
+        opcodes::SAX_ABS, 0x34, 0x12, // 4 cycles
+        opcodes::SAX_X_INDIR, 0x60, // 6 cycles
+    ]);
+    // 0x60 + 0xAA wraps around to 0x0A.
+    cpu.mut_memory().bytes[10..=11].copy_from_slice(&[0x35, 0x12]);
+    cpu.ticks(2 + 2 + 2 + 3 + 4 + 4 + 6).unwrap();
+    assert_eq!(cpu.memory.bytes[20..=21], [0b1000_1000, 0b1000_1000]);
+    assert_eq!(cpu.memory.bytes[0x1234..=0x1235], [0b1000_1000, 0b1000_1000]);
+}
+
+#[test]
+fn dcp() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::LDX_IMM, 0xFE, // 2 cycles
+        opcodes::TXS, // 2 cycles
+        opcodes::PLP, // 4 cycles
+        opcodes::LDA_IMM, 4, // 2 cycles
+        opcodes::DCP_ZP, 10, // 5 cycles
+        opcodes::PHP, // 3 cycles
+        opcodes::DCP_ZP, 10, // 5 cycles
+        opcodes::PHP, // 3 cycles
+        opcodes::DCP_ZP, 11, // 5 cycles
+        opcodes::PHP, // 3 cycles
+    ]);
+    cpu.mut_memory().bytes[10..=11].copy_from_slice(&[5, 0]);
+    cpu.ticks(8 + 2 + 3 * (5 + 3)).unwrap();
+    assert_eq!(cpu.memory.bytes[10..=11], [3, 0xFF]);
+    assert_eq!(
+        reversed_stack(&cpu),
+        [
+            flags::PUSHED | flags::Z | flags::C,
+            flags::PUSHED | flags::C,
+            flags::PUSHED,
+        ]
+    );
+}
+
+#[test]
+fn isc() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::SEC, // 2 cycles
+        opcodes::CLD, // 2 cycles
+        opcodes::LDA_IMM, 10, // 2 cycles
+        opcodes::ISC_ZP, 10, // 5 cycles
+        opcodes::STA_ZP, 11, // 3 cycles
+    ]);
+    cpu.mut_memory().bytes[10] = 2;
+    cpu.ticks(2 + 2 + 2 + 5 + 3).unwrap();
+    assert_eq!(cpu.memory.bytes[10..=11], [3, 7]);
+    assert_eq!(cpu.flags & flags::C, flags::C);
+}
+
+#[test]
+fn slo() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::LDA_IMM, 0b0000_0100, // 2 cycles
+        opcodes::SLO_ZP, 10, // 5 cycles
+        opcodes::STA_ZP, 11, // 3 cycles
+    ]);
+    cpu.mut_memory().bytes[10] = 0b1000_0001;
+    cpu.ticks(2 + 5 + 3).unwrap();
+    assert_eq!(cpu.memory.bytes[10..=11], [0b0000_0010, 0b0000_0110]);
+    assert_eq!(cpu.flags & flags::C, flags::C);
+}
+
+#[test]
+fn rla() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::SEC, // 2 cycles
+        opcodes::LDA_IMM, 0b0000_0111, // 2 cycles
+        opcodes::RLA_ZP, 10, // 5 cycles
+        opcodes::STA_ZP, 11, // 3 cycles
+    ]);
+    cpu.mut_memory().bytes[10] = 0b1000_0010;
+    cpu.ticks(2 + 2 + 5 + 3).unwrap();
+    assert_eq!(cpu.memory.bytes[10..=11], [0b0000_0101, 0b0000_0101]);
+    assert_eq!(cpu.flags & flags::C, flags::C);
+}
+
+#[test]
+fn sre() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::LDA_IMM, 0b1111_0000, // 2 cycles
+        opcodes::SRE_ZP, 10, // 5 cycles
+        opcodes::STA_ZP, 11, // 3 cycles
+    ]);
+    cpu.mut_memory().bytes[10] = 0b0000_0011;
+    cpu.ticks(2 + 5 + 3).unwrap();
+    assert_eq!(cpu.memory.bytes[10..=11], [0b0000_0001, 0b1111_0001]);
+    assert_eq!(cpu.flags & (flags::N | flags::C), flags::N | flags::C);
+}
+
+#[test]
+fn rra() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::CLC, // 2 cycles
+        opcodes::CLD, // 2 cycles
+        opcodes::LDA_IMM, 10, // 2 cycles
+        opcodes::RRA_ZP, 10, // 5 cycles
+        opcodes::STA_ZP, 11, // 3 cycles
+    ]);
+    cpu.mut_memory().bytes[10] = 0b0000_1001;
+    cpu.ticks(2 + 2 + 2 + 5 + 3).unwrap();
+    // The carry bit shifted out by ROR is added to the result.
+    assert_eq!(cpu.memory.bytes[10..=11], [0b0000_0100, 15]);
+    assert_eq!(cpu.flags & flags::C, 0);
+}
+
+#[test]
+fn unofficial_read_modify_write_addressing_modes() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::LDX_IMM, 1, // 2 cycles
+        opcodes::LDY_IMM, 2, // 2 cycles
+        opcodes::ISC_ZP, 10, // 5 cycles
+        opcodes::ISC_ZP_X, 10, // 6 cycles
+        opcodes::ISC_ABS, 0x00, 0x12, // 6 cycles
+        opcodes::ISC_ABS_X, 0x00, 0x12, // 7 cycles
+        opcodes::ISC_ABS_Y, 0x00, 0x12, // 7 cycles
+        opcodes::ISC_X_INDIR, 19, // 8 cycles
+        opcodes::ISC_INDIR_Y, 22, // 8 cycles
+    ]);
+    cpu.mut_memory().bytes[20..=23].copy_from_slice(&[0x03, 0x12, 0x02, 0x12]);
+    cpu.ticks(2 + 2 + 5 + 6 + 6 + 7 + 7 + 8 + 7).unwrap();
+    assert_eq!(cpu.memory.bytes[0x1204], 0);
+    cpu.tick().unwrap();
+    assert_eq!(cpu.memory.bytes[10..=11], [1, 1]);
+    assert_eq!(cpu.memory.bytes[0x1200..=0x1204], [1, 1, 1, 1, 1]);
+}
+
+#[test]
+fn anc() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::LDX_IMM, 0xFE, // 2 cycles
+        opcodes::TXS, // 2 cycles
+        opcodes::PLP, // 4 cycles
+        opcodes::LDA_IMM, 0b1100_0011, // 2 cycles
+        opcodes::ANC_IMM_0B, 0b1000_0001, // 2 cycles
+        opcodes::PHP, // 3 cycles
+        opcodes::ANC_IMM_2B, 0b0111_1111, // 2 cycles
+        opcodes::PHP, // 3 cycles
+        opcodes::STA_ZP, 10, // 3 cycles
+    ]);
+    cpu.ticks(8 + 2 + 2 + 3 + 2 + 3 + 3).unwrap();
+    assert_eq!(cpu.memory.bytes[10], 0b0000_0001);
+    assert_eq!(
+        reversed_stack(&cpu),
+        [flags::PUSHED | flags::N | flags::C, flags::PUSHED]
+    );
+}
+
+#[test]
+fn alr() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::LDA_IMM, 0b1111_0011, // 2 cycles
+        opcodes::ALR_IMM, 0b0101_0101, // 2 cycles
+        opcodes::STA_ZP, 10, // 3 cycles
+    ]);
+    cpu.ticks(2 + 2 + 3).unwrap();
+    assert_eq!(cpu.memory.bytes[10], 0b0010_1000);
+    assert_eq!(cpu.flags & flags::C, flags::C);
+}
+
+#[test]
+fn arr() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::LDX_IMM, 0xFE, // 2 cycles
+        opcodes::TXS, // 2 cycles
+        opcodes::PLP, // 4 cycles
+        opcodes::SEC, // 2 cycles
+        opcodes::LDA_IMM, 0xFF, // 2 cycles
+        opcodes::ARR_IMM, 0b1100_0000, // 2 cycles
+        opcodes::PHP, // 3 cycles
+        opcodes::STA_ZP, 10, // 3 cycles
+        opcodes::CLC, // 2 cycles
+        opcodes::LDA_IMM, 0xFF, // 2 cycles
+        opcodes::ARR_IMM, 0b1000_0000, // 2 cycles
+        opcodes::PHP, // 3 cycles
+        opcodes::STA_ZP, 11, // 3 cycles
+    ]);
+    cpu.ticks(8 + 2 + 2 + 2 + 3 + 3 + 2 + 2 + 2 + 3 + 3).unwrap();
+    assert_eq!(cpu.memory.bytes[10..=11], [0b1110_0000, 0b0100_0000]);
+    assert_eq!(
+        reversed_stack(&cpu),
+        [
+            flags::PUSHED | flags::N | flags::C,
+            flags::PUSHED | flags::C | flags::V,
+        ]
+    );
+}
+
+#[test]
+fn arr_decimal_mode() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::SED, // 2 cycles
+        opcodes::CLC, // 2 cycles
+        opcodes::LDA_IMM, 0xFF, // 2 cycles
+        opcodes::ARR_IMM, 0x66, // 2 cycles
+        opcodes::STA_ZP, 10, // 3 cycles
+    ]);
+    cpu.ticks(2 + 2 + 2 + 2 + 3).unwrap();
+    assert_eq!(cpu.memory.bytes[10], 0x99);
+    assert_eq!(cpu.flags & (flags::C | flags::V), flags::C | flags::V);
+}
+
+#[test]
+fn axs() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::LDA_IMM, 0b1111_0000, // 2 cycles
+        opcodes::LDX_IMM, 0b0011_1100, // 2 cycles
+        opcodes::AXS_IMM, 0x10, // 2 cycles
+        opcodes::STX_ZP, 10, // 3 cycles
+        opcodes::AXS_IMM, 0x21, // 2 cycles
+        opcodes::STX_ZP, 11, // 3 cycles
+    ]);
+    cpu.ticks(2 + 2 + 2 + 3).unwrap();
+    assert_eq!(cpu.memory.bytes[10], 0x20);
+    assert_eq!(cpu.flags & (flags::N | flags::C), flags::C);
+    cpu.ticks(2 + 3).unwrap();
+    assert_eq!(cpu.memory.bytes[11], 0xFF);
+    assert_eq!(cpu.flags & (flags::N | flags::C), flags::N);
+    assert_eq!(cpu.reg_a, 0b1111_0000);
+}
+
+#[test]
+fn sbc_unofficial() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::SEC, // 2 cycles
+        opcodes::CLD, // 2 cycles
+        opcodes::LDA_IMM, 10, // 2 cycles
+        opcodes::SBC_IMM_EB, 3, // 2 cycles
+        opcodes::STA_ZP, 10, // 3 cycles
+    ]);
+    cpu.ticks(2 + 2 + 2 + 2 + 3).unwrap();
+    assert_eq!(cpu.memory.bytes[10], 7);
+}
+
+#[test]
+fn unofficial_nops() {
+    let mut cpu = cpu_with_program(&[
+        opcodes::LDA_IMM, 1, // 2 cycles
+        opcodes::NOP_1A, // 2 cycles
+        opcodes::NOP_IMM_80, 0xFF, // 2 cycles
+        opcodes::NOP_ZP_04, 0xFF, // 3 cycles
+        opcodes::NOP_ZP_X_14, 0xFF, // 4 cycles
+        opcodes::NOP_ABS_0C, 0xFF, 0xFF, // 4 cycles
+        opcodes::LDX_IMM, 0, // 2 cycles
+        opcodes::NOP_ABS_X_1C, 0x00, 0x12, // 4 cycles
+        opcodes::LDX_IMM, 0xFF, // 2 cycles
+        opcodes::NOP_ABS_X_FC, 0x01, 0x12, // 5 cycles (page crossed)
+        opcodes::STA_ZP, 10, // 3 cycles
+    ]);
+    cpu.ticks(2 + 2 + 2 + 3 + 4 + 4 + 2 + 4 + 2 + 5 + 2).unwrap();
+    assert_eq!(cpu.memory.bytes[10], 0);
+    cpu.tick().unwrap();
+    assert_eq!(cpu.memory.bytes[10], 1);
+}
+
 #[test]
 fn reports_instruction_start() {
     let mut cpu = cpu_with_code! {
@@ -1878,6 +2233,488 @@ fn reports_instruction_start() {
     assert_eq!(cpu.reg_pc(), 0xF006);
 }
 
+#[test]
+fn counts_cycles() {
+    let mut cpu = cpu_with_code! {
+            lda 0x42
+    };
+    let initial_cycles = cpu.cycles();
+    assert!(initial_cycles > 0, "Reset sequence takes cycles, too");
+    cpu.ticks(3).unwrap();
+    assert_eq!(cpu.cycles(), initial_cycles + 3);
+}
+
+#[test]
+fn reports_memory_access() {
+    let mut cpu = cpu_with_code! {
+            lda 0x42 // 0xF000
+            sta 0x43 // 0xF002
+    };
+    let mut accesses = vec![];
+    for _ in 0..6 {
+        cpu.tick().unwrap();
+        accesses.push(cpu.last_memory_access());
+    }
+    assert_eq!(
+        accesses,
+        [
+            Some(MemoryAccess::Read(0xF000)),
+            Some(MemoryAccess::Read(0xF001)),
+            Some(MemoryAccess::Read(0x0042)),
+            Some(MemoryAccess::Read(0xF002)),
+            Some(MemoryAccess::Read(0xF003)),
+            Some(MemoryAccess::Write(0x0043)),
+        ]
+    );
+}
+
+#[test]
+fn reports_bus_cycles() {
+    let mut cpu = cpu_with_code! {
+            lda 0x42 // 0xF000
+            sta 0x43 // 0xF002
+    };
+    cpu.mut_memory().bytes[0x42] = 0x99;
+    let start = cpu.cycles();
+    let mut bus_cycles = vec![];
+    for _ in 0..6 {
+        cpu.tick().unwrap();
+        bus_cycles.push(cpu.last_bus_cycle().unwrap());
+    }
+    let bus_cycle = |access, value, offset| BusCycle {
+        access,
+        value,
+        cycle: start + offset,
+    };
+    assert_eq!(
+        bus_cycles,
+        [
+            bus_cycle(MemoryAccess::Read(0xF000), opcodes::LDA_ZP, 0),
+            bus_cycle(MemoryAccess::Read(0xF001), 0x42, 1),
+            bus_cycle(MemoryAccess::Read(0x0042), 0x99, 2),
+            bus_cycle(MemoryAccess::Read(0xF002), opcodes::STA_ZP, 3),
+            bus_cycle(MemoryAccess::Read(0xF003), 0x43, 4),
+            bus_cycle(MemoryAccess::Write(0x0043), 0x99, 5),
+        ]
+    );
+}
+
+#[test]
+fn reports_indirect_memory_accesses() {
+    #[rustfmt::skip]
+    let mut cpu = cpu_with_program(&[
+        opcodes::LDX_IMM, 0x02, // 0xF000
+        opcodes::LDA_X_INDIR, 0x40, // 0xF002
+        opcodes::JMP_INDIR, 0x00, 0x12, // 0xF004
+    ]);
+    cpu.mut_memory().bytes[0x42..=0x43].copy_from_slice(&[0x34, 0x12]);
+    cpu.mut_memory().bytes[0x1200..=0x1201].copy_from_slice(&[0x00, 0xF0]);
+    cpu.ticks(2).unwrap();
+    let mut accesses = vec![];
+    for _ in 0..11 {
+        cpu.tick().unwrap();
+        accesses.push(cpu.last_memory_access().unwrap());
+    }
+    assert_eq!(
+        accesses,
+        [
+            MemoryAccess::Read(0xF002),
+            MemoryAccess::Read(0xF003),
+            MemoryAccess::Read(0x0040),
+            MemoryAccess::Read(0x0042),
+            MemoryAccess::Read(0x0043),
+            MemoryAccess::Read(0x1234),
+            MemoryAccess::Read(0xF004),
+            MemoryAccess::Read(0xF005),
+            MemoryAccess::Read(0xF006),
+            MemoryAccess::Read(0x1200),
+            MemoryAccess::Read(0x1201),
+        ]
+    );
+}
+
+#[test]
+fn predicts_write_cycles() {
+    let mut cpu = cpu_with_code! {
+            ldx #0xFF
+            txs
+            ldx #1
+            ldy #2
+            sta 10
+            sta 10,x
+            sta abs 0x1234
+            sta abs 0x1234,y
+            sta (10,x)
+            sta (12),y
+            inc 10
+            inc 10,x
+            inc abs 0x1234
+            inc abs 0x1234,x
+            pha
+            php
+            jsr sub
+            brk
+        sub:
+            rts
+    };
+    cpu.mut_memory().bytes[11..=14].copy_from_slice(&[0x00, 0x20, 0x00, 0x21]);
+    // BRK jumps to $0000, which contains another BRK, so we just keep pushing
+    // to the stack until the end of the test.
+    for _ in 0..150 {
+        let predicted = cpu.next_cycle_writes();
+        cpu.tick().unwrap();
+        assert_eq!(
+            predicted,
+            matches!(cpu.last_memory_access(), Some(MemoryAccess::Write(_))),
+            "Wrong prediction at cycle {}",
+            cpu.cycles(),
+        );
+    }
+}
+
+#[test]
+fn predicts_write_cycles_cmos() {
+    // 65C02 reads the operand again instead of performing a phantom write.
+    let mut cpu = cmos_cpu_with_program(&[
+        opcodes::LDX_IMM,
+        1,
+        opcodes::INC_ZP,
+        10,
+        opcodes::INC_ZP_X,
+        10,
+        opcodes::INC_ABS,
+        0x34,
+        0x12,
+        opcodes::INC_ABS_X,
+        0x34,
+        0x12,
+    ]);
+    let mut write_count = 0;
+    for _ in 0..2 + 5 + 6 + 6 + 7 {
+        let predicted = cpu.next_cycle_writes();
+        cpu.tick().unwrap();
+        let written = matches!(cpu.last_memory_access(), Some(MemoryAccess::Write(_)));
+        assert_eq!(
+            predicted,
+            written,
+            "Wrong prediction at cycle {}",
+            cpu.cycles(),
+        );
+        write_count += written as u32;
+    }
+    assert_eq!(write_count, 4);
+}
+
+#[test]
+fn modifies_state_for_debugging() {
+    let mut cpu = cpu_with_code! {
+            lda 0x42 // 0xF000
+            sta 0x43 // 0xF002
+    };
+    cpu.set_register(Register::A, 0x1234);
+    cpu.set_register(Register::X, 0x56);
+    cpu.set_register(Register::Y, 0x78);
+    cpu.set_register(Register::SP, 0x9A);
+    cpu.set_register(Register::Flags, (flags::C | flags::UNUSED) as u16);
+    cpu.poke_memory(0x0042, 0xAB).unwrap();
+    assert_eq!(cpu.reg_a(), 0x34);
+    assert_eq!(cpu.reg_x(), 0x56);
+    assert_eq!(cpu.reg_y(), 0x78);
+    assert_eq!(cpu.reg_sp(), 0x9A);
+    assert_eq!(cpu.flags(), flags::C | flags::UNUSED);
+    assert_eq!(cpu.inspect_memory(0x0042), 0xAB);
+
+    cpu.ticks(3).unwrap();
+    assert_eq!(cpu.reg_a(), 0xAB);
+    cpu.set_register(Register::PC, 0xF000);
+    cpu.poke_memory(0x0042, 0xCD).unwrap();
+    cpu.ticks(3).unwrap();
+    assert_eq!(cpu.reg_a(), 0xCD);
+}
+
+#[test]
+fn cmos_stack_instructions() {
+    let mut cpu = cmos_cpu_with_program(&[
+        opcodes::LDX_IMM,
+        0x12,
+        opcodes::LDY_IMM,
+        0x34,
+        opcodes::PHX,
+        opcodes::PHY,
+        opcodes::PLX,
+        opcodes::PLY,
+    ]);
+    cpu.ticks(2 + 2 + 3 + 3 + 4 + 4).unwrap();
+    assert_eq!(cpu.reg_x(), 0x34);
+    assert_eq!(cpu.reg_y(), 0x12);
+    assert!(cpu.at_instruction_start());
+}
+
+#[test]
+fn cmos_stz_and_bra() {
+    let mut cpu = cmos_cpu_with_program(&[
+        opcodes::LDA_IMM,
+        0xFF,
+        opcodes::STA_ZP,
+        0x10,
+        opcodes::STA_ZP,
+        0x11,
+        opcodes::STZ_ZP,
+        0x10,
+        opcodes::LDX_IMM,
+        1,
+        opcodes::STZ_ZP_X,
+        0x10,
+        opcodes::BRA,
+        2,
+        opcodes::STA_ZP, // Skipped
+        0x12,
+        opcodes::STA_ZP,
+        0x13,
+    ]);
+    cpu.ticks(2 + 3 + 3 + 3 + 2 + 4 + 3 + 3).unwrap();
+    assert_eq!(cpu.memory.bytes[0x10..=0x13], [0x00, 0x00, 0x00, 0xFF]);
+    assert!(cpu.at_instruction_start());
+}
+
+#[test]
+fn cmos_zero_page_indirect() {
+    let mut cpu = cmos_cpu_with_program(&[
+        opcodes::LDA_ZP_INDIR,
+        0x20,
+        opcodes::INC_A,
+        opcodes::STA_ZP_INDIR,
+        0x22,
+        opcodes::DEC_A,
+        opcodes::DEC_A,
+    ]);
+    cpu.mut_memory().bytes[0x20..=0x23].copy_from_slice(&[0x00, 0x03, 0x01, 0x03]);
+    cpu.mut_memory().bytes[0x0300] = 0x41;
+    cpu.ticks(5 + 2 + 5 + 2 + 2).unwrap();
+    assert_eq!(cpu.memory.bytes[0x0301], 0x42);
+    assert_eq!(cpu.reg_a(), 0x40);
+    assert!(cpu.at_instruction_start());
+}
+
+#[test]
+fn cmos_bit_test_instructions() {
+    let mut cpu = cmos_cpu_with_program(&[
+        opcodes::LDA_IMM,
+        0x0F,
+        opcodes::TSB_ZP,
+        0x10,
+        opcodes::LDA_IMM,
+        0xC0,
+        opcodes::TRB_ZP,
+        0x10,
+    ]);
+    cpu.mut_memory().bytes[0x10] = 0x3C;
+    cpu.ticks(2 + 5).unwrap();
+    assert_eq!(cpu.memory.bytes[0x10], 0x3F);
+    assert_eq!(cpu.flags() & flags::Z, 0);
+    cpu.ticks(2 + 5).unwrap();
+    assert_eq!(cpu.memory.bytes[0x10], 0x3F);
+    assert_eq!(cpu.flags() & flags::Z, flags::Z);
+
+    let mut cpu = cmos_cpu_with_program(&[opcodes::LDA_IMM, 0xC0, opcodes::BIT_IMM, 0x01]);
+    cpu.ticks(2 + 2).unwrap();
+    // Unlike other BIT instructions, BIT #imm only affects the Z flag.
+    assert_eq!(cpu.flags() & (flags::N | flags::Z), flags::N | flags::Z);
+}
+
+#[test]
+fn cmos_jmp_indirect() {
+    let mut cpu = cmos_cpu_with_program(&[opcodes::JMP_INDIR, 0xFF, 0x12]);
+    // NMOS 6502 would read the high byte of the target from 0x1200.
+    cpu.mut_memory().bytes[0x1200] = 0x30;
+    cpu.mut_memory().bytes[0x12FF..=0x1300].copy_from_slice(&[0x00, 0x20]);
+    cpu.ticks(5).unwrap();
+    assert!(!cpu.at_instruction_start());
+    cpu.tick().unwrap();
+    assert!(cpu.at_instruction_start());
+    assert_eq!(cpu.reg_pc(), 0x2000);
+
+    let mut cpu =
+        cmos_cpu_with_program(&[opcodes::LDX_IMM, 2, opcodes::JMP_ABS_X_INDIR, 0x34, 0x12]);
+    cpu.mut_memory().bytes[0x1236..=0x1237].copy_from_slice(&[0x78, 0x56]);
+    cpu.ticks(2 + 6).unwrap();
+    assert!(cpu.at_instruction_start());
+    assert_eq!(cpu.reg_pc(), 0x5678);
+}
+
+#[test]
+fn cmos_undefined_opcodes() {
+    let mut cpu = cmos_cpu_with_program(&[
+        0x03, // 1 byte, 1 cycle
+        0x02, 0xFF, // 2 bytes, 2 cycles
+        0xDC, 0x34, 0x12, // 3 bytes, 4 cycles
+        0x5C, 0x34, 0x12, // 3 bytes, 8 cycles
+        0x0B, // 1 byte, 1 cycle
+    ]);
+    let registers = (cpu.reg_a(), cpu.reg_x(), cpu.reg_y(), cpu.flags());
+    cpu.ticks(1 + 2 + 4 + 8).unwrap();
+    assert!(cpu.at_instruction_start());
+    assert_eq!(cpu.reg_pc(), 0xF009);
+    cpu.tick().unwrap();
+    assert!(cpu.at_instruction_start());
+    assert_eq!(cpu.reg_pc(), 0xF00A);
+    assert_eq!(
+        (cpu.reg_a(), cpu.reg_x(), cpu.reg_y(), cpu.flags()),
+        registers
+    );
+}
+
+#[test]
+fn cmos_interrupts_clear_decimal_flag() {
+    let mut cpu = cmos_cpu_with_program(&[opcodes::SED, opcodes::BRK]);
+    cpu.mut_memory().bytes[0xFFFE..=0xFFFF].copy_from_slice(&[0x00, 0x20]);
+    cpu.ticks(2 + 7).unwrap();
+    assert_eq!(cpu.reg_pc(), 0x2000);
+    assert_eq!(cpu.flags() & (flags::D | flags::I), flags::I);
+    assert_eq!(
+        cpu.memory.bytes[cpu.stack_pointer() as usize + 1] & flags::D,
+        flags::D
+    );
+}
+
+/// An opcode that is not supported by the NMOS 6502 emulation.
+const UNKNOWN_OPCODE: u8 = 0x9C;
+
+/// RAM that refuses to write a given address, just like a chip that doesn't
+/// support one of its registers.
+#[derive(Debug)]
+struct RamWithReadOnlyByte {
+    ram: Ram,
+    read_only_address: u16,
+}
+
+impl Read for RamWithReadOnlyByte {
+    fn read(&mut self, address: u16) -> ReadResult {
+        self.ram.read(address)
+    }
+}
+
+impl Inspect for RamWithReadOnlyByte {
+    fn inspect(&self, address: u16) -> ReadResult {
+        self.ram.inspect(address)
+    }
+}
+
+impl Write for RamWithReadOnlyByte {
+    fn write(&mut self, address: u16, value: u8) -> WriteResult {
+        if address == self.read_only_address {
+            return Err(WriteError { address, value });
+        }
+        self.ram.write(address, value)
+    }
+}
+
+impl Memory for RamWithReadOnlyByte {}
+
+#[test]
+fn unknown_opcode_stops_cpu() {
+    let mut cpu = cpu_with_program(&[UNKNOWN_OPCODE]);
+    assert_eq!(
+        cpu.ticks(2).unwrap_err(),
+        CpuError::UnknownOpcode {
+            opcode: UNKNOWN_OPCODE,
+            address: 0xF000,
+        }
+    );
+}
+
+#[test]
+fn halt_stops_cpu() {
+    let mut cpu = cpu_with_program(&[opcodes::NOP]);
+    assert_eq!(
+        cpu.ticks(2 + 2).unwrap_err(),
+        CpuError::Halted {
+            opcode: opcodes::HLT1,
+            address: 0xF001,
+        }
+    );
+}
+
+#[test]
+fn permissive_mode_skips_unknown_opcodes() {
+    let mut cpu = cpu_with_program(&[
+        UNKNOWN_OPCODE,
+        UNKNOWN_OPCODE,
+        opcodes::LDA_IMM,
+        42,
+    ]);
+    cpu.set_permissive(true);
+    cpu.ticks(2 + 2 + 2).unwrap();
+    assert_eq!(cpu.reg_a(), 42);
+    assert_eq!(cpu.ignored_error_count(), 2);
+    // Halting the CPU is not an error, so it's not ignored.
+    cpu.ticks(2).unwrap_err();
+}
+
+#[test]
+fn permissive_mode_ignores_write_errors() {
+    let program = [
+        opcodes::LDA_IMM,
+        42,
+        opcodes::STA_ZP,
+        0x10,
+        opcodes::STA_ZP,
+        0x11,
+        opcodes::STA_ZP,
+        0x10,
+    ];
+    let memory = RamWithReadOnlyByte {
+        ram: Ram::with_test_program(&program),
+        read_only_address: 0x10,
+    };
+    let mut cpu = Cpu::new(Box::new(memory));
+    reset(&mut cpu);
+    assert_eq!(
+        cpu.ticks(2 + 3).unwrap_err(),
+        CpuError::WriteError(WriteError {
+            address: 0x10,
+            value: 42,
+        })
+    );
+
+    cpu.set_permissive(true);
+    reset(&mut cpu);
+    cpu.ticks(2 + 3 + 3 + 3).unwrap();
+    assert_eq!(cpu.memory().ram.bytes[0x10], 0);
+    assert_eq!(cpu.memory().ram.bytes[0x11], 42);
+    assert_eq!(cpu.ignored_error_count(), 2);
+}
+
+#[test]
+fn permissive_mode_reports_each_write_error_once() {
+    let program = [
+        opcodes::LDA_IMM,
+        1,
+        opcodes::STA_ZP,
+        0x10,
+        opcodes::LDA_IMM,
+        2,
+        opcodes::STA_ZP,
+        0x10,
+    ];
+    let memory = RamWithReadOnlyByte {
+        ram: Ram::with_test_program(&program),
+        read_only_address: 0x10,
+    };
+    let mut cpu = Cpu::new(Box::new(memory));
+    cpu.set_permissive(true);
+    reset(&mut cpu);
+    cpu.ticks(2 + 3 + 2 + 3).unwrap();
+    assert_eq!(cpu.ignored_error_count(), 2);
+    let reported_count: u32 = cpu
+        .reported_errors
+        .writes
+        .iter()
+        .map(|w| w.count_ones())
+        .sum();
+    assert_eq!(reported_count, 1);
+}
+
 #[bench]
 fn benchmark(b: &mut Bencher) {
     let mut cpu = cpu_with_code! {
@@ -7,5 +7,6 @@
 extern crate rustasm6502;
 
 pub mod cpu;
+pub mod mapper;
 pub mod memory;
 pub mod test_utils;
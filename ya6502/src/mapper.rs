@@ -0,0 +1,72 @@
+use crate::memory::Inspect;
+use crate::memory::Ram;
+use crate::memory::Read;
+use crate::memory::ReadResult;
+use crate::memory::Rom;
+use std::fmt;
+
+/// Bank switching hardware, typically found on cartridges. A mapper decides
+/// what is visible through its [`Read`] and [`Inspect`] interfaces, while the
+/// address space of a given machine decides which addresses are mapped to it.
+///
+/// Cartridges are usually connected to the address and data buses, so a
+/// mapper is notified about bus traffic and can react to it by switching
+/// banks. It's up to the address space to decide which accesses are visible
+/// to the mapper; on some machines, it's only a subset of the entire bus
+/// traffic.
+pub trait Mapper: Read + Inspect + fmt::Debug {
+    /// Called after a value has been read from a given address.
+    fn observe_read(&mut self, _address: u16, _value: u8) {}
+    /// Called after a value has been written to a given address.
+    fn observe_write(&mut self, _address: u16, _value: u8) {}
+    /// Brings the mapper to its power-on state.
+    fn reset(&mut self) {}
+}
+
+/// A plain ROM chip is the simplest mapper: it never switches banks.
+impl Mapper for Rom {}
+
+/// RAM can be used in place of a mapper, too, which comes in handy in tests.
+impl Mapper for Ram {}
+
+impl<M: Mapper + ?Sized> Inspect for Box<M> {
+    fn inspect(&self, address: u16) -> ReadResult {
+        (**self).inspect(address)
+    }
+}
+
+impl<M: Mapper + ?Sized> Read for Box<M> {
+    fn read(&mut self, address: u16) -> ReadResult {
+        (**self).read(address)
+    }
+}
+
+impl<M: Mapper + ?Sized> Mapper for Box<M> {
+    fn observe_read(&mut self, address: u16, value: u8) {
+        (**self).observe_read(address, value)
+    }
+    fn observe_write(&mut self, address: u16, value: u8) {
+        (**self).observe_write(address, value)
+    }
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::LatchMapper;
+
+    #[test]
+    fn boxed_mapper() {
+        let mut mapper: Box<dyn Mapper> = Box::new(LatchMapper::default());
+        mapper.observe_read(0x1000, 0xAB);
+        assert_eq!(mapper.read(0x1000).unwrap(), 0);
+        mapper.observe_write(0x1000, 0xCD);
+        assert_eq!(mapper.read(0x1000).unwrap(), 0xCD);
+        assert_eq!(mapper.inspect(0x1234).unwrap(), 0xCD);
+        mapper.reset();
+        assert_eq!(mapper.read(0x1000).unwrap(), 0);
+    }
+}
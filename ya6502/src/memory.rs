@@ -1,5 +1,8 @@
+use crate::cpu::PowerOnState;
+use rand::Rng;
 use std::error;
 use std::fmt;
+use std::num::NonZeroUsize;
 use std::result::Result;
 
 pub trait Read {
@@ -27,6 +30,51 @@ pub trait Inspect {
     /// emulation state. Useful for debugging; all regular reads should be
     /// performed using the [`read`] function.
     fn inspect(&self, address: u16) -> ReadResult;
+
+    /// Describes the kinds of memory visible at each address. An empty map
+    /// means that the layout is unknown. Address spaces that switch banks
+    /// report the current layout.
+    fn memory_map(&self) -> Vec<MemoryRegion> {
+        vec![]
+    }
+}
+
+/// Kind of memory that occupies a given address range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryKind {
+    Ram,
+    Rom,
+    /// Registers of support chips.
+    Io,
+    /// Addresses that no device responds to.
+    Unmapped,
+}
+
+/// A range of addresses, from `start` to `end` (inclusive), occupied by the same
+/// kind of memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub start: u16,
+    pub end: u16,
+    pub kind: MemoryKind,
+}
+
+/// Builds a memory map by checking the kind of memory at each address and
+/// merging adjacent addresses of the same kind into regions.
+pub fn memory_map_from(kind_at: impl Fn(u16) -> MemoryKind) -> Vec<MemoryRegion> {
+    let mut regions: Vec<MemoryRegion> = vec![];
+    for address in 0..=0xFFFF {
+        let kind = kind_at(address);
+        match regions.last_mut() {
+            Some(region) if region.kind == kind => region.end = address,
+            _ => regions.push(MemoryRegion {
+                start: address,
+                end: address,
+                kind,
+            }),
+        }
+    }
+    return regions;
 }
 
 pub trait Write {
@@ -40,7 +88,10 @@ pub trait Memory: Read + Write {}
 
 pub type ReadResult = Result<u8, ReadError>;
 
-#[derive(Clone)]
+/// Reported when reading from an unsupported location. Memory errors are
+/// plain values that don't allocate, since they can be returned on every cycle
+/// (for example, by phantom reads, whose results are discarded anyway).
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ReadError {
     pub address: u16,
 }
@@ -63,7 +114,8 @@ impl fmt::Debug for ReadError {
 
 pub type WriteResult = Result<(), WriteError>;
 
-#[derive(Clone)]
+/// Reported when writing to an unsupported location. See [`ReadError`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct WriteError {
     pub address: u16,
     pub value: u8,
@@ -90,6 +142,27 @@ impl fmt::Debug for WriteError {
     }
 }
 
+/// Describes the contents of RAM right after powering on. Real memory chips
+/// don't start zeroed; instead, they tend to settle in patterns that depend on
+/// the chip manufacturer, and some programs (accidentally or not) rely on them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RamPattern {
+    /// Every byte is set to a given value.
+    Filled(u8),
+    /// Alternating blocks of $00 and $FF bytes, each `block_size` bytes long,
+    /// starting with $00. With 64-byte blocks, this is what a C64 typically
+    /// starts with.
+    Stripes { block_size: NonZeroUsize },
+    /// Random bytes.
+    Random(PowerOnState),
+}
+
+impl Default for RamPattern {
+    fn default() -> Self {
+        Self::Filled(0)
+    }
+}
+
 /// Random access memory.
 pub struct Ram {
     pub bytes: Vec<u8>,
@@ -115,6 +188,23 @@ impl Ram {
         }
     }
 
+    /// Creates a new RAM with an address bus of a given width (in bits),
+    /// initialized with a given pattern. The total size of the RAM will be
+    /// 2^address_width.
+    pub fn with_pattern(pattern: RamPattern, address_width: u32) -> Ram {
+        let mut ram = Self::new(address_width);
+        match pattern {
+            RamPattern::Filled(value) => ram.bytes.fill(value),
+            RamPattern::Stripes { block_size } => {
+                for (i, block) in ram.bytes.chunks_mut(block_size.get()).enumerate() {
+                    block.fill(if i % 2 == 0 { 0x00 } else { 0xFF });
+                }
+            }
+            RamPattern::Random(power_on_state) => power_on_state.rng().fill(&mut ram.bytes[..]),
+        }
+        return ram;
+    }
+
     /// Creates 64KiB of `RAM`, putting given `program` at address 0xF000. It
     /// also sets the reset pointer to 0xF000.
     pub fn with_test_program(program: &[u8]) -> Ram {
@@ -136,6 +226,10 @@ impl Inspect for Ram {
     fn inspect(&self, address: u16) -> ReadResult {
         Ok(self.bytes[(address & self.address_mask) as usize])
     }
+
+    fn memory_map(&self) -> Vec<MemoryRegion> {
+        memory_map_from(|_| MemoryKind::Ram)
+    }
 }
 
 impl Read for Ram {
@@ -193,6 +287,10 @@ impl Inspect for Rom {
     fn inspect(&self, address: u16) -> ReadResult {
         Ok(self.bytes[(address & self.address_mask) as usize])
     }
+
+    fn memory_map(&self) -> Vec<MemoryRegion> {
+        memory_map_from(|_| MemoryKind::Rom)
+    }
 }
 
 impl Read for Rom {
@@ -309,6 +407,24 @@ mod tests {
         assert_eq!(ram.bytes[0xFFFC..0xFFFE], [0x00, 0xF0]); // 0xF000
     }
 
+    #[test]
+    fn ram_patterns() {
+        let ram = Ram::with_pattern(RamPattern::Filled(0xAB), 8);
+        assert_eq!(ram.bytes, [0xAB; 0x100]);
+
+        let block_size = NonZeroUsize::new(64).unwrap();
+        let ram = Ram::with_pattern(RamPattern::Stripes { block_size }, 8);
+        assert_eq!(ram.bytes[0x00..0x40], [0x00; 0x40]);
+        assert_eq!(ram.bytes[0x40..0x80], [0xFF; 0x40]);
+        assert_eq!(ram.bytes[0x80..0xC0], [0x00; 0x40]);
+        assert_eq!(ram.bytes[0xC0..0x100], [0xFF; 0x40]);
+
+        let seeded = RamPattern::Random(PowerOnState::Seeded(5));
+        let ram = Ram::with_pattern(seeded, 8);
+        assert_eq!(ram.bytes, Ram::with_pattern(seeded, 8).bytes);
+        assert_ne!(ram.bytes, [0; 0x100]);
+    }
+
     #[test]
     fn rom_mirroring() {
         let mut program = [0u8; 0x1000];
@@ -345,4 +461,46 @@ mod tests {
         let rom = Rom::new(&[0u8; 0x20000]);
         assert_eq!(rom.err(), Some(MemorySizeError { size: 0x20000 }));
     }
+
+    #[test]
+    fn builds_memory_maps() {
+        assert_eq!(
+            memory_map_from(|address| match address {
+                0x0000..=0x00FF => MemoryKind::Io,
+                0xE000..=0xEFFF => MemoryKind::Unmapped,
+                0xF000..=0xFFFF => MemoryKind::Rom,
+                _ => MemoryKind::Ram,
+            }),
+            [
+                MemoryRegion {
+                    start: 0x0000,
+                    end: 0x00FF,
+                    kind: MemoryKind::Io,
+                },
+                MemoryRegion {
+                    start: 0x0100,
+                    end: 0xDFFF,
+                    kind: MemoryKind::Ram,
+                },
+                MemoryRegion {
+                    start: 0xE000,
+                    end: 0xEFFF,
+                    kind: MemoryKind::Unmapped,
+                },
+                MemoryRegion {
+                    start: 0xF000,
+                    end: 0xFFFF,
+                    kind: MemoryKind::Rom,
+                },
+            ]
+        );
+        assert_eq!(
+            Ram::new(16).memory_map(),
+            [MemoryRegion {
+                start: 0x0000,
+                end: 0xFFFF,
+                kind: MemoryKind::Ram,
+            }]
+        );
+    }
 }
@@ -1,7 +1,14 @@
 use crate::cpu::opcodes;
 use crate::cpu::Cpu;
+use crate::cpu::CpuVariant;
+use crate::mapper::Mapper;
+use crate::memory::Inspect;
 use crate::memory::Memory;
 use crate::memory::Ram;
+use crate::memory::Read;
+use crate::memory::ReadResult;
+use crate::memory::Write;
+use crate::memory::WriteResult;
 use std::fmt::Debug;
 
 /// Resets the CPU and waits until the reset sequence is finished.
@@ -22,6 +29,16 @@ pub fn cpu_with_program(program: &[u8]) -> Cpu<Ram> {
     return cpu;
 }
 
+/// Creates a 65C02 CPU with a given program loaded at 0xF000 and sets the
+/// reset vector to the beginning of program. Since 65C02 doesn't have any
+/// opcodes that halt the CPU, nothing is appended at the end of the program.
+pub fn cmos_cpu_with_program(program: &[u8]) -> Cpu<Ram> {
+    let memory = Box::new(Ram::with_test_program(program));
+    let mut cpu = Cpu::with_variant(memory, CpuVariant::Cmos65C02);
+    reset(&mut cpu);
+    return cpu;
+}
+
 /// Returns a CPU that will execute given assembly code. Unfortunately, since I
 /// don't know how to correctly reexport the `assemble6502` macro, the crate
 /// that uses this macro will have to import `assemble6502` explicitly.
@@ -34,3 +51,38 @@ macro_rules! cpu_with_code {
         }))
     };
 }
+
+/// A single-byte mapper that remembers the last value written anywhere on the
+/// bus and exposes it at every address. Writes addressed directly to the
+/// mapper are ignored.
+#[derive(Debug, Default)]
+pub struct LatchMapper {
+    latch: u8,
+}
+
+impl Inspect for LatchMapper {
+    fn inspect(&self, _address: u16) -> ReadResult {
+        Ok(self.latch)
+    }
+}
+
+impl Read for LatchMapper {
+    fn read(&mut self, address: u16) -> ReadResult {
+        self.inspect(address)
+    }
+}
+
+impl Write for LatchMapper {
+    fn write(&mut self, _address: u16, _value: u8) -> WriteResult {
+        Ok(())
+    }
+}
+
+impl Mapper for LatchMapper {
+    fn observe_write(&mut self, _address: u16, value: u8) {
+        self.latch = value;
+    }
+    fn reset(&mut self) {
+        self.latch = 0;
+    }
+}